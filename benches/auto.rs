@@ -0,0 +1,164 @@
+//! Benchmarks for event creation, posting, location polling, and pixel
+//! sampling.
+//!
+//! Run with `cargo bench`. Benchmarks of crate internals additionally require
+//! `--features bench-internals`.
+
+extern crate auto;
+
+#[macro_use]
+extern crate criterion;
+
+use criterion::{Criterion, black_box};
+
+use auto::color::Rgb;
+use auto::dispatch::Queue;
+
+fn color(c: &mut Criterion) {
+    let rgb = Rgb { red: 0.25f64, green: 0.5, blue: 1.5 };
+    c.bench_function("color/into_byte", move |b| {
+        b.iter(|| black_box(black_box(rgb).into_byte()))
+    });
+
+    let rgb = Rgb { red: 64u8, green: 128, blue: 255 };
+    c.bench_function("color/into_float", move |b| {
+        b.iter(|| black_box(black_box(rgb).into_float()))
+    });
+}
+
+fn dispatch(c: &mut Criterion) {
+    let queue = Queue::new("bench");
+    c.bench_function("dispatch/exec_sync", move |b| {
+        b.iter(|| black_box(queue.exec_sync(|| black_box(1))))
+    });
+
+    c.bench_function("dispatch/named", |b| {
+        b.iter(|| black_box(Queue::named(black_box("bench"))))
+    });
+}
+
+#[cfg(target_os = "macos")]
+fn os(c: &mut Criterion) {
+    use auto::os::{keyboard, mouse, wheel, EventLocation};
+    use auto::os::screen::Display;
+
+    c.bench_function("event/mouse_new", |b| {
+        let (button, kind) = (mouse::Button::Left, mouse::EventKind::Moved);
+        b.iter(|| black_box(mouse::Event::new(button, kind, (0.0, 0.0)).unwrap()))
+    });
+
+    c.bench_function("event/keyboard_new", |b| {
+        b.iter(|| black_box(keyboard::Event::new(black_box(0), false).unwrap()))
+    });
+
+    c.bench_function("event/post", |b| {
+        // A zero-line scroll is a no-op for applications receiving it
+        let event = wheel::Event::new(wheel::ScrollUnit::Line, [0]).unwrap();
+        b.iter(|| event.post(EventLocation::Session))
+    });
+
+    c.bench_function("mouse/location", |b| {
+        b.iter(|| black_box(mouse::location()))
+    });
+
+    c.bench_function("mouse/location_iter_100", |b| {
+        b.iter(|| {
+            for loc in mouse::location_iter().unwrap().take(100) {
+                black_box(loc);
+            }
+        })
+    });
+
+    c.bench_function("screen/main", |b| {
+        b.iter(|| black_box(Display::main()))
+    });
+
+    c.bench_function("screen/size", |b| {
+        let display = Display::main();
+        b.iter(|| black_box(black_box(display).size()))
+    });
+
+    c.bench_function("screen/online", |b| {
+        b.iter(|| black_box(Display::online()))
+    });
+
+    c.bench_function("screen/write_online", |b| {
+        let mut buf = Vec::new();
+        b.iter(|| Display::write_online(black_box(&mut buf)))
+    });
+
+    c.bench_function("screen/color_at", |b| {
+        let display = Display::main();
+        b.iter(|| black_box(display.color_at(black_box((0.0, 0.0)))))
+    });
+
+    c.bench_function("screen/sampler", |b| {
+        let mut sampler = Display::main().sampler((0.0, 0.0)).unwrap();
+        b.iter(|| black_box(sampler.sample()))
+    });
+
+    c.bench_function("screen/capture_rect_100", |b| {
+        use auto::os::screen::Bounds;
+        let display = Display::main();
+        let rect = Bounds { origin: (0.0, 0.0), size: (100.0, 100.0) };
+        b.iter(|| black_box(display.capture_rect(black_box(rect))))
+    });
+}
+
+#[cfg(target_os = "windows")]
+fn os(c: &mut Criterion) {
+    use auto::os::{keyboard, mouse, screen};
+
+    c.bench_function("event/keyboard_new", |b| {
+        b.iter(|| black_box(keyboard::Event::new(black_box(0x41), false)))
+    });
+
+    c.bench_function("event/post", |b| {
+        // Moving the cursor to where it already is has no visible effect
+        let loc = mouse::location().unwrap_or((0, 0));
+        b.iter(|| black_box(mouse::set_location(black_box(loc))))
+    });
+
+    c.bench_function("mouse/location", |b| {
+        b.iter(|| black_box(mouse::location()))
+    });
+
+    c.bench_function("screen/color_at", |b| {
+        b.iter(|| black_box(screen::color_at(black_box((0, 0)))))
+    });
+
+    c.bench_function("screen/sampler", |b| {
+        let sampler = screen::Sampler::new().unwrap();
+        b.iter(|| black_box(sampler.sample(black_box((0, 0)))))
+    });
+
+    c.bench_function("screen/capture_rect_100", |b| {
+        let rect = screen::Bounds { origin: (0, 0), size: (100, 100) };
+        b.iter(|| black_box(screen::capture_rect(black_box(rect))))
+    });
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn os(_: &mut Criterion) {}
+
+#[cfg(feature = "bench-internals")]
+fn internals(c: &mut Criterion) {
+    #[cfg(target_os = "macos")]
+    {
+        use auto::bench_internals::field_applies_to;
+        use auto::os::EventField;
+
+        c.bench_function("internals/field_applies_to", |b| {
+            let field = EventField::MouseClickState;
+            b.iter(|| black_box(field_applies_to(field, black_box(1))))
+        });
+    }
+
+    let _ = c;
+}
+
+#[cfg(not(feature = "bench-internals"))]
+fn internals(_: &mut Criterion) {}
+
+criterion_group!(benches, color, dispatch, os, internals);
+criterion_main!(benches);
@@ -0,0 +1,303 @@
+//! 🛑 An emergency stop for runaway automation.
+//!
+//! Automation that controls the mouse and keyboard can leave the user unable
+//! to use either to stop it. [`install`](fn.install.html) registers a
+//! system-wide hotkey that calls [`trigger`](fn.trigger.html), which stops
+//! every operation in progress:
+//!
+//! - [smooth mouse moves](../mouse/struct.SmoothMove.html)
+//! - [typing](../keyboard/struct.Typing.html) and
+//!   [held keys](../keyboard/fn.hold.html), which are released
+//! - [scripts](../script/struct.Script.html), whether run directly or on a
+//!   queue
+//! - [playback](../record/struct.Playback.html) of recordings
+//! - [delays](../dispatch/struct.Queue.html#method.delay) on queues
+//!
+//! Stopped operations return
+//! [`Error::Aborted`](../error/enum.Error.html#variant.Aborted). Operations
+//! started after the abort run normally, so the hotkey stays armed for the
+//! next time.
+//!
+//! A [session](../session/struct.Session.html) has a kill switch of its own,
+//! which only stops the operations started in that session.
+//!
+//! Like the fail-safe of PyAutoGUI, [`install_failsafe`](fn.install_failsafe.html)
+//! also aborts when the user slams the mouse into a corner of the screen.
+//! It watches the input devices in the background, so it works while a
+//! script is busy posting events rather than reading input.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use auto::abort::{self, Corners};
+//!
+//! // Control + Alt + Escape stops everything
+//! let _kill_switch = abort::install_default().unwrap();
+//! // And so does moving the mouse to the upper-left corner
+//! let _failsafe = abort::install_failsafe(Corners::TOP_LEFT).unwrap();
+//! ```
+
+use std::collections::HashMap;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use dispatch;
+use error::{Error, Result};
+use events::{self, InputEvent, Listener, Location};
+use hotkey::Hotkey;
+use keyboard::{Key, Modifiers};
+use screen::{self, Display};
+use session;
+
+/// Counts of aborts, which operations in progress watch for changes.
+#[derive(Default)]
+struct Aborts {
+    /// The number of calls to `trigger`.
+    count: u64,
+    /// The number of aborts of each live session, by session identifier.
+    sessions: HashMap<u64, u64>,
+}
+
+impl Aborts {
+    fn token(&self, session: Option<u64>) -> Token {
+        Token {
+            count: self.count,
+            session: session.map(|id| (id, self.session_count(id))),
+        }
+    }
+
+    fn session_count(&self, id: u64) -> u64 {
+        self.sessions.get(&id).cloned().unwrap_or(0)
+    }
+
+    /// Returns whether an abort that applies to `token` was triggered after
+    /// it was created.
+    fn aborted(&self, token: &Token) -> bool {
+        self.count != token.count
+            || token.session.is_some_and(|(id, count)| self.session_count(id) != count)
+    }
+}
+
+lazy_static! {
+    static ref ABORTS: (Mutex<Aborts>, Condvar) = Default::default();
+}
+
+fn lock() -> MutexGuard<'static, Aborts> {
+    ABORTS.0.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The modifiers of the hotkey installed by
+/// [`install_default`](fn.install_default.html).
+pub const DEFAULT_MODIFIERS: Modifiers = Modifiers::from_bits_truncate(
+    Modifiers::CONTROL.bits() | Modifiers::ALT.bits(),
+);
+
+/// The key of the hotkey installed by
+/// [`install_default`](fn.install_default.html).
+pub const DEFAULT_KEY: Key = Key::Escape;
+
+/// Registers a hotkey that calls [`trigger`](fn.trigger.html) whenever `key`
+/// is pressed while holding exactly `modifiers`.
+///
+/// The kill switch stays armed until the returned hotkey is dropped. See
+/// [`Hotkey::register`](../hotkey/struct.Hotkey.html#method.register) for
+/// when registration fails and, on macOS, the permission it requires.
+pub fn install(modifiers: Modifiers, key: Key) -> Result<Hotkey> {
+    Hotkey::register(modifiers, key, trigger)
+}
+
+/// Registers the kill switch on Control + Alt + Escape.
+#[inline]
+pub fn install_default() -> Result<Hotkey> {
+    install(DEFAULT_MODIFIERS, DEFAULT_KEY)
+}
+
+bitflags! {
+    /// A set of screen corners.
+    pub struct Corners: u8 {
+        /// The upper-left corner.
+        const TOP_LEFT     = 1 << 0;
+        /// The upper-right corner.
+        const TOP_RIGHT    = 1 << 1;
+        /// The lower-left corner.
+        const BOTTOM_LEFT  = 1 << 2;
+        /// The lower-right corner.
+        const BOTTOM_RIGHT = 1 << 3;
+    }
+}
+
+/// How close in points the mouse must come to a corner to trip the
+/// fail-safe.
+const FAILSAFE_MARGIN: f64 = 2.0;
+
+/// A fail-safe that calls [`trigger`](fn.trigger.html) when the mouse is
+/// moved into a screen corner, until dropped.
+#[derive(Debug)]
+pub struct Failsafe {
+    _listener: Listener,
+}
+
+/// Arms a fail-safe that calls [`trigger`](fn.trigger.html) whenever the
+/// user moves the mouse into one of `corners`.
+///
+/// Only the corners that the mouse stops at are watched, so with several
+/// displays, a corner shared with a neighboring display doesn't count. The
+/// displays are arranged as they were when the fail-safe was installed.
+/// Moves posted by software, including this crate, are ignored, and the
+/// mouse must leave the corner before it trips the fail-safe again.
+///
+/// Fails if the displays can't be read, or if the input devices can't be
+/// [listened](../events/fn.listen.html) to.
+pub fn install_failsafe(corners: Corners) -> Result<Failsafe> {
+    let points = outer_corners(&screen::displays()?, corners);
+    let mut inside = false;
+    let listener = events::listen_devices(move |event| {
+        if let InputEvent::MouseMoved((x, y)) = *event {
+            let near = |&(cx, cy): &Location| {
+                (x - cx).abs() <= FAILSAFE_MARGIN && (y - cy).abs() <= FAILSAFE_MARGIN
+            };
+            let was_inside = inside;
+            inside = points.iter().any(near);
+            if inside && !was_inside {
+                trigger();
+            }
+        }
+    })?;
+    Ok(Failsafe { _listener: listener })
+}
+
+/// Returns the outermost point of each of `corners` on every display that
+/// the mouse can't move beyond.
+fn outer_corners(displays: &[Display], corners: Corners) -> Vec<Location> {
+    let on_screen = |point| displays.iter().any(|d| d.contains(point));
+    let mut points = Vec::new();
+    for display in displays {
+        let ((x, y), (w, h)) = (display.origin, display.size);
+        let candidates = [
+            (Corners::TOP_LEFT, (x, y), (-1.0, -1.0)),
+            (Corners::TOP_RIGHT, (x + w - 1.0, y), (1.0, -1.0)),
+            (Corners::BOTTOM_LEFT, (x, y + h - 1.0), (-1.0, 1.0)),
+            (Corners::BOTTOM_RIGHT, (x + w - 1.0, y + h - 1.0), (1.0, 1.0)),
+        ];
+        for &(corner, (cx, cy), (dx, dy)) in &candidates {
+            let m = FAILSAFE_MARGIN;
+            let beyond = [
+                (cx + dx * m, cy - dy * m),
+                (cx - dx * m, cy + dy * m),
+                (cx + dx * m, cy + dy * m),
+            ];
+            if corners.contains(corner) && !beyond.iter().any(|&p| on_screen(p)) {
+                points.push((cx, cy));
+            }
+        }
+    }
+    points
+}
+
+/// Stops every operation in progress, as if the kill switch were pressed.
+pub fn trigger() {
+    let (_, ref changed) = *ABORTS;
+    lock().count += 1;
+    changed.notify_all();
+    dispatch::interrupt_delays();
+}
+
+/// Stops the operations in progress in the session identified by `id`.
+pub(crate) fn trigger_session(id: u64) {
+    let (_, ref changed) = *ABORTS;
+    *lock().sessions.entry(id).or_insert(0) += 1;
+    changed.notify_all();
+    dispatch::interrupt_session_delays(id);
+}
+
+/// Forgets the aborts of the session identified by `id`, once it is gone.
+pub(crate) fn forget_session(id: u64) {
+    lock().sessions.remove(&id);
+}
+
+/// A marker of when an operation started, for checking whether it has since
+/// been aborted.
+///
+/// Operations started in a [session](../session/index.html) are also stopped
+/// by aborting the session.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Token {
+    count: u64,
+    session: Option<(u64, u64)>,
+}
+
+impl Token {
+    /// Starts watching for aborts.
+    pub fn new() -> Token {
+        let session = session::current_id();
+        lock().token(session)
+    }
+
+    /// Fails if an abort was triggered after the token was created.
+    pub fn check(&self) -> Result<()> {
+        if lock().aborted(self) {
+            Err(Error::Aborted)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Sleeps for `duration`, failing as soon as an abort is triggered.
+    pub fn sleep(&self, duration: Duration) -> Result<()> {
+        let (_, ref changed) = *ABORTS;
+        // A deadline too far away to represent never comes
+        let deadline = Instant::now().checked_add(duration);
+        let mut current = lock();
+        while !current.aborted(self) {
+            current = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Ok(());
+                    }
+                    changed
+                        .wait_timeout(current, deadline - now)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+                None => changed.wait(current).unwrap_or_else(|e| e.into_inner()),
+            };
+        }
+        Err(Error::Aborted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn trigger_wakes_sleepers() {
+        // Abort a session of its own so other tests' tokens are unaffected
+        let id = u64::MAX;
+        let token = lock().token(Some(id));
+        assert!(token.check().is_ok());
+
+        let sleeper = thread::spawn(move || token.sleep(Duration::MAX));
+        thread::sleep(Duration::from_millis(20));
+        trigger_session(id);
+
+        assert_eq!(sleeper.join().unwrap(), Err(Error::Aborted));
+        assert_eq!(token.check(), Err(Error::Aborted));
+        let later = lock().token(Some(id));
+        assert!(later.check().is_ok());
+        forget_session(id);
+        dispatch::forget_session(id);
+    }
+
+    #[test]
+    fn shared_corners_are_skipped() {
+        let display = |origin, is_main| Display { origin, size: (100.0, 50.0), scale: 1.0, is_main };
+        let displays = [display((0.0, 0.0), true), display((100.0, 0.0), false)];
+        assert_eq!(
+            outer_corners(&displays, Corners::TOP_LEFT | Corners::TOP_RIGHT),
+            [(0.0, 0.0), (199.0, 0.0)],
+        );
+    }
+}
@@ -0,0 +1,476 @@
+//! 🛰️ Running automation from a long-lived background agent.
+//!
+//! Synthesizing input requires permissions that the user grants to each
+//! application, such as being trusted for accessibility on macOS. Rather
+//! than every front-end asking for them, one process can [`run`](fn.run.html)
+//! an agent that front-ends control through a [`Client`](struct.Client.html)
+//! over a local socket. Recordings sent to the agent are replayed one after
+//! another in the order they were received.
+//!
+//! This module is only available with the `agent` feature, and agents only
+//! run on Unix-like systems.
+//!
+//! # Security
+//!
+//! Anyone able to connect to the socket can synthesize input, so it should be
+//! created in a directory only the user can access. Events replayed by the
+//! agent are still subject to its [`safety`](../safety/index.html) deny list.
+//!
+//! # Protocol
+//!
+//! Clients send lines of text, each of which gets a reply line:
+//!
+//! - `enqueue <length> <timing>` is followed by `length` bytes of a recording
+//!   in the [text format](../record/index.html#file-format), where `timing` is
+//!   `original`, `immediate`, or `scaled <factor>`. The agent replies
+//!   `queued <id>`, or `error <message>` if the recording is invalid
+//!
+//! - `status` gets `status <running> <pending> <finished> <failed>`, where
+//!   `running` is the id of the recording being replayed or `-`
+//!
+//! - `stop` gets `stopping`, after which the agent drops the recordings that
+//!   have not started and returns once the current one finishes
+//!
+//! # Examples
+//!
+//! In the agent process:
+//!
+//! ```no_run
+//! auto::agent::run("/tmp/auto-agent.sock").unwrap();
+//! ```
+//!
+//! In a front-end:
+//!
+//! ```no_run
+//! use auto::agent::Client;
+//! use auto::record::{Recording, Timing};
+//!
+//! let mut client = Client::connect("/tmp/auto-agent.sock").unwrap();
+//! let id = client.enqueue(&Recording::load("login.rec").unwrap(), Timing::Original).unwrap();
+//! println!("queued {}: {:?}", id, client.status().unwrap());
+//! ```
+
+use std::io;
+use std::path::Path;
+
+use record::{Recording, Timing};
+
+/// The state of an agent's queue, as reported by
+/// [`Client::status`](struct.Client.html#method.status).
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct Status {
+    running: Option<u64>,
+    pending: usize,
+    finished: u64,
+    failed: u64,
+}
+
+impl Status {
+    /// Returns the id of the recording being replayed, if any.
+    #[inline]
+    pub fn running(&self) -> Option<u64> {
+        self.running
+    }
+
+    /// Returns the number of recordings waiting to be replayed.
+    #[inline]
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// Returns the number of recordings replayed to the end.
+    #[inline]
+    pub fn finished(&self) -> u64 {
+        self.finished
+    }
+
+    /// Returns the number of recordings that stopped at an error.
+    #[inline]
+    pub fn failed(&self) -> u64 {
+        self.failed
+    }
+
+    #[cfg(unix)]
+    fn to_line(self) -> String {
+        let running = self.running.map_or("-".into(), |id| id.to_string());
+        format!("status {} {} {} {}", running, self.pending, self.finished, self.failed)
+    }
+
+    #[cfg(unix)]
+    fn parse(line: &str) -> Option<Status> {
+        let mut words = line.strip_prefix("status ")?.split(' ');
+        let running = match words.next()? {
+            "-" => None,
+            id => Some(id.parse().ok()?),
+        };
+        let status = Status {
+            running,
+            pending: words.next()?.parse().ok()?,
+            finished: words.next()?.parse().ok()?,
+            failed: words.next()?.parse().ok()?,
+        };
+        match words.next() {
+            None => Some(status),
+            Some(_) => None,
+        }
+    }
+}
+
+/// Runs an agent listening on the Unix socket at `path` until a client tells
+/// it to stop.
+///
+/// A stale socket left at `path` by an agent that exited is replaced, but
+/// this fails with `AddrInUse` if another agent is still listening there.
+/// The socket is removed when the agent stops.
+#[inline]
+pub fn run<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    imp::run(path.as_ref())
+}
+
+/// A connection to an agent.
+#[derive(Debug)]
+pub struct Client {
+    inner: imp::Client,
+}
+
+impl Client {
+    /// Connects to the agent listening on the Unix socket at `path`.
+    #[inline]
+    pub fn connect<P: AsRef<Path>>(path: P) -> io::Result<Client> {
+        imp::Client::connect(path.as_ref()).map(|inner| Client { inner })
+    }
+
+    /// Adds `recording` to the end of the agent's queue, returning its id.
+    ///
+    /// Fails with `InvalidData` if the agent rejects the recording.
+    #[inline]
+    pub fn enqueue(&mut self, recording: &Recording, timing: Timing) -> io::Result<u64> {
+        self.inner.enqueue(recording, timing)
+    }
+
+    /// Returns the state of the agent's queue.
+    #[inline]
+    pub fn status(&mut self) -> io::Result<Status> {
+        self.inner.status()
+    }
+
+    /// Tells the agent to stop once the current recording finishes, dropping
+    /// the recordings that have not started.
+    #[inline]
+    pub fn stop(mut self) -> io::Result<()> {
+        self.inner.stop()
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::fs;
+    use std::io::{self, BufReader, ErrorKind, Write};
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::panic::{self, AssertUnwindSafe};
+    use std::path::Path;
+    use std::sync::{Arc, Mutex, MutexGuard};
+    use std::thread;
+
+    use dispatch::Queue;
+    use error::Result;
+    use protocol::{invalid_data, read_line, read_recording, split_command, write_recording};
+    use record::{Recording, Timing};
+    use super::Status;
+
+    #[derive(Default)]
+    struct State {
+        status: Status,
+        next_id: u64,
+        stopping: bool,
+    }
+
+    type Shared = Arc<Mutex<State>>;
+
+    fn lock(state: &Shared) -> MutexGuard<'_, State> {
+        state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Binds to `path`, replacing a socket left behind by an agent that
+    /// exited without removing it.
+    pub(super) fn bind(path: &Path) -> io::Result<UnixListener> {
+        match UnixListener::bind(path) {
+            Err(ref err) if err.kind() == ErrorKind::AddrInUse => {
+                if !fs::symlink_metadata(path)?.file_type().is_socket() {
+                    return Err(ErrorKind::AddrInUse.into());
+                }
+                match UnixStream::connect(path) {
+                    Err(ref err) if err.kind() == ErrorKind::ConnectionRefused => {},
+                    _ => return Err(ErrorKind::AddrInUse.into()),
+                }
+                fs::remove_file(path)?;
+                UnixListener::bind(path)
+            },
+            result => result,
+        }
+    }
+
+    pub fn run(path: &Path) -> io::Result<()> {
+        let listener = bind(path)?;
+        let state = Shared::default();
+        let queue = Queue::new("auto-agent");
+
+        for stream in listener.incoming() {
+            if lock(&state).stopping {
+                break;
+            }
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            let (state, queue, path) = (state.clone(), queue.clone(), path.to_owned());
+            thread::spawn(move || {
+                let stop = serve_session(stream, &state, &queue).unwrap_or(false);
+                if stop {
+                    // Wake up the accepting loop so that it sees the flag
+                    let _ = UnixStream::connect(path);
+                }
+            });
+        }
+
+        queue.wait();
+        let _ = fs::remove_file(path);
+        Ok(())
+    }
+
+    /// Serves a client until it disconnects, returning whether it stopped the
+    /// agent.
+    fn serve_session(stream: UnixStream, state: &Shared, queue: &Queue) -> io::Result<bool> {
+        let mut writer = stream.try_clone()?;
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+
+        loop {
+            match read_line(&mut reader, &mut line) {
+                Err(ref err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(false),
+                result => result?,
+            }
+
+            let (command, args) = split_command(&line);
+            match command {
+                "enqueue" => match read_recording(&mut reader, args)? {
+                    Ok((recording, timing)) => {
+                        let id = enqueue(state, queue, recording, timing);
+                        writeln!(writer, "queued {}", id)?;
+                    },
+                    Err(message) => writeln!(writer, "error {}", message)?,
+                },
+                "status" => {
+                    let status = lock(state).status;
+                    writeln!(writer, "{}", status.to_line())?;
+                },
+                "stop" => {
+                    lock(state).stopping = true;
+                    writeln!(writer, "stopping")?;
+                    return Ok(true);
+                },
+                _ => return Err(invalid_data(format!("unknown command {:?}", command))),
+            }
+        }
+    }
+
+    fn enqueue(state: &Shared, queue: &Queue, recording: Recording, timing: Timing) -> u64 {
+        let id = {
+            let mut state = lock(state);
+            state.next_id += 1;
+            state.status.pending += 1;
+            state.next_id
+        };
+        let state = state.clone();
+        queue.exec_async(move || {
+            {
+                let mut state = lock(&state);
+                state.status.pending -= 1;
+                if state.stopping {
+                    return;
+                }
+                state.status.running = Some(id);
+            }
+            let result = panic::catch_unwind(AssertUnwindSafe(|| replay(&recording, timing)));
+            let mut state = lock(&state);
+            state.status.running = None;
+            match result {
+                Ok(Ok(())) => state.status.finished += 1,
+                Ok(Err(_)) | Err(_) => state.status.failed += 1,
+            }
+        });
+        id
+    }
+
+    #[cfg(not(test))]
+    fn replay(recording: &Recording, timing: Timing) -> Result<()> {
+        recording.replay(timing)
+    }
+
+    /// Stands in for replaying in tests, which mustn't post to the desktop,
+    /// and panics for recordings that have events.
+    #[cfg(test)]
+    fn replay(recording: &Recording, _: Timing) -> Result<()> {
+        assert!(recording.events.is_empty(), "replaying events in a test");
+        Ok(())
+    }
+
+    #[derive(Debug)]
+    pub struct Client {
+        reader: BufReader<UnixStream>,
+        writer: UnixStream,
+        line: String,
+    }
+
+    impl Client {
+        pub fn connect(path: &Path) -> io::Result<Client> {
+            let stream = UnixStream::connect(path)?;
+            Ok(Client {
+                writer: stream.try_clone()?,
+                reader: BufReader::new(stream),
+                line: String::new(),
+            })
+        }
+
+        fn reply(&mut self) -> io::Result<&str> {
+            read_line(&mut self.reader, &mut self.line)?;
+            Ok(&self.line)
+        }
+
+        pub fn enqueue(&mut self, recording: &Recording, timing: Timing) -> io::Result<u64> {
+            write_recording(&mut self.writer, "enqueue", recording, timing)?;
+            let reply = self.reply()?;
+            let (status, args) = split_command(reply);
+            match status {
+                "queued" => args.parse().map_err(invalid_data),
+                "error" => Err(invalid_data(args.to_owned())),
+                _ => Err(invalid_data(format!("invalid reply {:?}", reply))),
+            }
+        }
+
+        pub fn status(&mut self) -> io::Result<Status> {
+            self.writer.write_all(b"status\n")?;
+            let reply = self.reply()?;
+            Status::parse(reply).ok_or_else(|| invalid_data(format!("invalid reply {:?}", reply)))
+        }
+
+        pub fn stop(&mut self) -> io::Result<()> {
+            self.writer.write_all(b"stop\n")?;
+            match self.reply()? {
+                "stopping" => Ok(()),
+                reply => Err(invalid_data(format!("invalid reply {:?}", reply))),
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use std::io;
+    use std::path::Path;
+
+    use record::{Recording, Timing};
+    use super::Status;
+
+    fn unsupported() -> io::Error {
+        io::Error::other("agents require Unix domain sockets")
+    }
+
+    pub fn run(_: &Path) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    #[derive(Debug)]
+    pub enum Client {}
+
+    impl Client {
+        pub fn connect(_: &Path) -> io::Result<Client> {
+            Err(unsupported())
+        }
+
+        pub fn enqueue(&mut self, _: &Recording, _: Timing) -> io::Result<u64> {
+            match *self {}
+        }
+
+        pub fn status(&mut self) -> io::Result<Status> {
+            match *self {}
+        }
+
+        pub fn stop(&mut self) -> io::Result<()> {
+            match *self {}
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::io::ErrorKind;
+    use std::process;
+    use std::thread;
+    use std::time::Duration;
+    use events::InputEvent;
+    use keyboard::Key;
+    use super::*;
+
+    #[test]
+    fn status_text() {
+        let status = Status { running: Some(7), pending: 2, finished: 5, failed: 1 };
+        assert_eq!(Status::parse(&status.to_line()), Some(status));
+        assert_eq!(Status::parse(&Status::default().to_line()), Some(Status::default()));
+        assert_eq!(Status::parse("status - 1 2"), None);
+    }
+
+    #[test]
+    fn enqueue_and_stop() {
+        let path = env::temp_dir().join(format!("auto-agent-test-{}.sock", process::id()));
+        let agent = {
+            let path = path.clone();
+            thread::spawn(move || run(path))
+        };
+        let mut client = loop {
+            match Client::connect(&path) {
+                Ok(client) => break client,
+                Err(_) => thread::yield_now(),
+            }
+        };
+
+        let first = client.enqueue(&Recording::new(), Timing::Immediate).unwrap();
+        let second = client.enqueue(&Recording::new(), Timing::Scaled(2.0)).unwrap();
+        assert!(second > first);
+        assert_eq!(client.status().unwrap().failed(), 0);
+
+        // The stand-in replay panics for this one
+        let mut panicking = Recording::new();
+        panicking.push(Duration::from_secs(1), InputEvent::KeyDown(Key::A));
+        client.enqueue(&panicking, Timing::Immediate).unwrap();
+        while client.status().unwrap().failed() == 0 {
+            thread::yield_now();
+        }
+        assert_eq!(client.status().unwrap().running(), None);
+
+        client.stop().unwrap();
+        agent.join().unwrap().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn bind_replaces_stale_sockets() {
+        let path = env::temp_dir().join(format!("auto-agent-bind-{}.sock", process::id()));
+        let _ = fs::remove_file(&path);
+
+        fs::write(&path, "not a socket").unwrap();
+        assert_eq!(imp::bind(&path).unwrap_err().kind(), ErrorKind::AddrInUse);
+        assert!(path.exists());
+        fs::remove_file(&path).unwrap();
+
+        let live = imp::bind(&path).unwrap();
+        assert_eq!(imp::bind(&path).unwrap_err().kind(), ErrorKind::AddrInUse);
+        drop(live);
+        assert!(path.exists());
+        drop(imp::bind(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+}
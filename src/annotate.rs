@@ -0,0 +1,223 @@
+//! 📝 Recording annotated user clicks for collaborative script authoring.
+//!
+//! A [`Session`](struct.Session.html) observes the user's clicks while it is
+//! active and produces a [`Report`](struct.Report.html) describing where each
+//! click landed and which application it was aimed at. The report can be
+//! shared as JSON so that others can turn it into an automation script.
+//!
+//! Sessions are currently only supported on macOS, where the process must be
+//! trusted for accessibility.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use error::Result;
+use image::Image;
+use json;
+
+/// A single click captured during a [`Session`](struct.Session.html).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Annotation {
+    /// The time since the session started.
+    pub time: Duration,
+    /// The global screen location of the click, relative to the upper-left
+    /// corner of the main display.
+    pub point: (f64, f64),
+    /// The mouse button number, where 0 is the left button and 1 is the right.
+    pub button: u8,
+    /// The name of the frontmost application at the time of the click.
+    pub app_name: Option<String>,
+    /// The title of the window under the click, if it could be determined.
+    ///
+    /// On macOS, titles are only available if the process may record the
+    /// screen.
+    pub window_title: Option<String>,
+    /// A screenshot of the area around the click, centered on the click
+    /// point where possible.
+    pub image: Option<Image>,
+}
+
+/// The annotations captured by a finished [`Session`](struct.Session.html).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    /// The captured clicks, in order.
+    pub annotations: Vec<Annotation>,
+}
+
+impl Report {
+    /// Returns the report as a JSON document.
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"annotations\":[");
+        for (i, a) in self.annotations.iter().enumerate() {
+            if i != 0 {
+                out.push(',');
+            }
+            out.push_str("{\"time_ms\":");
+            json::write_f64(&mut out, duration_ms(a.time));
+            out.push_str(",\"point\":[");
+            json::write_f64(&mut out, a.point.0);
+            out.push(',');
+            json::write_f64(&mut out, a.point.1);
+            out.push_str("],\"button\":");
+            out.push_str(&a.button.to_string());
+            out.push_str(",\"app_name\":");
+            json::write_opt_str(&mut out, a.app_name.as_deref());
+            out.push_str(",\"window_title\":");
+            json::write_opt_str(&mut out, a.window_title.as_deref());
+            out.push_str(",\"image\":");
+            json::write_opt_str(&mut out, a.image.as_ref().map(|_| image_file(i)).as_deref());
+            out.push('}');
+        }
+        out.push_str("]}");
+        out
+    }
+
+    /// Writes the report to `report.json` within `dir` along with a BMP file
+    /// for each screenshot, creating the directory if needed.
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> io::Result<()> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir.join("images"))?;
+        for (i, a) in self.annotations.iter().enumerate() {
+            if let Some(ref image) = a.image {
+                let file = File::create(dir.join(image_file(i)))?;
+                image.write_bmp(BufWriter::new(file))?;
+            }
+        }
+        fs::write(dir.join("report.json"), self.to_json())
+    }
+}
+
+fn image_file(index: usize) -> String {
+    format!("images/click-{:04}.bmp", index + 1)
+}
+
+fn duration_ms(d: Duration) -> f64 {
+    d.as_secs() as f64 * 1000.0 + f64::from(d.subsec_nanos()) / 1_000_000.0
+}
+
+/// An active annotation session that records the user's clicks.
+///
+/// Recording stops when [`finish`](#method.finish) is called or when the
+/// session is dropped.
+#[derive(Debug)]
+pub struct Session {
+    annotations: Arc<Mutex<Vec<Annotation>>>,
+    _listener: imp::Listener,
+}
+
+impl Session {
+    /// Starts recording clicks.
+    pub fn start() -> Result<Session> {
+        let annotations = Arc::new(Mutex::new(Vec::new()));
+        let listener = imp::listen(Instant::now(), annotations.clone())?;
+        Ok(Session { annotations, _listener: listener })
+    }
+
+    /// Returns the number of clicks recorded so far.
+    pub fn len(&self) -> usize {
+        self.annotations.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Returns whether no clicks have been recorded so far.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Stops recording and returns the report.
+    pub fn finish(self) -> Report {
+        let annotations = self.annotations.lock().unwrap_or_else(|e| e.into_inner());
+        Report { annotations: annotations.clone() }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    use error::Result;
+    use events::{self, InputEvent};
+    use image::Image;
+    use os::app::App;
+    use os::mouse::Location;
+    use os::screen::{Bounds, Display};
+    use os::window::Window;
+    use super::Annotation;
+
+    pub use events::Listener;
+
+    /// The width and height in points of the area captured around a click.
+    const CROP_SIZE: f64 = 200.0;
+
+    fn capture_around(point: Location) -> Option<Image> {
+        let displays = Display::active().ok()?;
+        let (display, bounds) = displays.into_iter()
+            .map(|d| (d, d.bounds()))
+            .find(|&(_, b)| b.contains(point))?;
+
+        // Keep the crop within the display, shifting it away from the click's
+        // center near the edges
+        let clamp = |p: f64, origin: f64, len: f64| {
+            (p - origin - CROP_SIZE / 2.0).min(len - CROP_SIZE).max(0.0)
+        };
+        let rect = Bounds {
+            origin: (
+                clamp(point.0, bounds.origin.0, bounds.size.0),
+                clamp(point.1, bounds.origin.1, bounds.size.1),
+            ),
+            size: (CROP_SIZE.min(bounds.size.0), CROP_SIZE.min(bounds.size.1)),
+        };
+        display.capture_rect(rect).ok()
+    }
+
+    /// Returns the title of the frontmost window at `point`, which is only
+    /// known if the process may record the screen.
+    fn window_title_at(point: Location) -> Option<String> {
+        Window::on_screen().ok()?
+            .into_iter()
+            .find(|window| window.contains(point))?
+            .title()
+            .map(String::from)
+    }
+
+    pub fn listen(start: Instant, out: Arc<Mutex<Vec<Annotation>>>) -> Result<Listener> {
+        events::listen_filtered(
+            |event| matches!(event, InputEvent::ButtonDown(..)),
+            move |event| {
+                let (button, point) = match *event {
+                    InputEvent::ButtonDown(button, point) => (button, point),
+                    _ => return,
+                };
+                let app_name = App::frontmost().and_then(|app| app.localized_name());
+                let annotation = Annotation {
+                    time: start.elapsed(),
+                    point,
+                    button: button.number(),
+                    app_name,
+                    window_title: window_title_at(point),
+                    image: capture_around(point),
+                };
+                out.lock().unwrap_or_else(|e| e.into_inner()).push(annotation);
+            },
+        )
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod imp {
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    use error::{Error, Result};
+    use super::Annotation;
+
+    #[derive(Debug)]
+    pub struct Listener;
+
+    pub fn listen(_: Instant, _: Arc<Mutex<Vec<Annotation>>>) -> Result<Listener> {
+        Err(Error::Unsupported)
+    }
+}
@@ -0,0 +1,116 @@
+//! 🔊 Controlling the volume of the default audio output.
+//!
+//! The volume media keys change the volume in coarse steps and show the
+//! volume overlay each time. These functions set an exact volume directly,
+//! without any visible feedback, which suits scripts that record audio or
+//! need a known volume before playing sounds.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use auto::audio;
+//!
+//! let previous = audio::volume().unwrap();
+//! audio::set_volume(0.5).unwrap();
+//! audio::mute(false).unwrap();
+//! // ... play and record something
+//! audio::set_volume(previous).unwrap();
+//! ```
+
+use error::Result;
+
+/// Returns the volume of the default output device, ranging from 0 to 1.
+///
+/// Returns [`Error::Unsupported`](../error/enum.Error.html#variant.Unsupported)
+/// if the device has no volume control.
+#[inline]
+pub fn volume() -> Result<f32> {
+    imp::volume()
+}
+
+/// Sets the volume of the default output device, ranging from 0 to 1.
+///
+/// Values outside of that range are clamped. Setting the volume does not
+/// unmute the device.
+#[inline]
+pub fn set_volume(volume: f32) -> Result<()> {
+    imp::set_volume(volume.clamp(0.0, 1.0))
+}
+
+/// Returns whether the default output device is muted.
+#[inline]
+pub fn is_muted() -> Result<bool> {
+    imp::is_muted()
+}
+
+/// Mutes the default output device if `muted` is `true`, or unmutes it
+/// otherwise.
+#[inline]
+pub fn mute(muted: bool) -> Result<()> {
+    imp::mute(muted)
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use error::Result;
+    use os::audio::Device;
+
+    pub fn volume() -> Result<f32> {
+        Device::default_output()?.volume()
+    }
+
+    pub fn set_volume(volume: f32) -> Result<()> {
+        Device::default_output()?.set_volume(volume)
+    }
+
+    pub fn is_muted() -> Result<bool> {
+        Device::default_output()?.is_muted()
+    }
+
+    pub fn mute(muted: bool) -> Result<()> {
+        Device::default_output()?.set_muted(muted)
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use error::Result;
+    use os::audio::Endpoint;
+
+    pub fn volume() -> Result<f32> {
+        Endpoint::default_output()?.volume()
+    }
+
+    pub fn set_volume(volume: f32) -> Result<()> {
+        Endpoint::default_output()?.set_volume(volume)
+    }
+
+    pub fn is_muted() -> Result<bool> {
+        Endpoint::default_output()?.is_muted()
+    }
+
+    pub fn mute(muted: bool) -> Result<()> {
+        Endpoint::default_output()?.set_muted(muted)
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod imp {
+    use error::{Error, Result};
+
+    pub fn volume() -> Result<f32> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn set_volume(_: f32) -> Result<()> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn is_muted() -> Result<bool> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn mute(_: bool) -> Result<()> {
+        Err(Error::Unsupported)
+    }
+}
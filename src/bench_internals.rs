@@ -0,0 +1,20 @@
+//! Crate internals exposed to the benchmark suite.
+//!
+//! Enabled by the `bench-internals` feature. Nothing in here is covered by
+//! semver; items only exist so that contributors can measure the hot paths of
+//! individual subsystems in isolation.
+
+#[cfg(target_os = "macos")]
+pub use self::macos::*;
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use os::{EventField, EventType};
+
+    /// Checks whether `field` applies to the raw Quartz event type without
+    /// calling into `CGEventGetType`.
+    #[inline]
+    pub fn field_applies_to(field: EventField, raw_type: u32) -> bool {
+        field.applies_to(EventType::from_raw(raw_type))
+    }
+}
@@ -0,0 +1,334 @@
+//! 🕸️ Coordinating replay across several machines.
+//!
+//! Each machine runs an [`Agent`](struct.Agent.html), and one controller
+//! process connects to all of them with a [`Cluster`](struct.Cluster.html).
+//! When the cluster runs, every agent first loads its recording and reports
+//! that it is ready. Once all agents are ready, they are told to start at
+//! once, and the cluster waits for every one of them to report back.
+//!
+//! This module is only available with the `cluster` feature.
+//!
+//! # Security
+//!
+//! Anyone able to connect to an agent can synthesize input on its machine, so
+//! agents should only listen on trusted networks. Events replayed by an agent
+//! are still subject to its [`safety`](../safety/index.html) deny list.
+//!
+//! # Protocol
+//!
+//! The controller and agents exchange lines of text over TCP:
+//!
+//! - `play <length> <timing>` is followed by `length` bytes of a recording in
+//!   the [text format](../record/index.html#file-format), where `timing` is
+//!   `original`, `immediate`, or `scaled <factor>`. The agent replies `ready`,
+//!   or `error <message>` if the recording is invalid
+//!
+//! - `start` begins replay, after which the agent replies `done <seconds>` or
+//!   `failed <seconds> <message>`
+//!
+//! # Examples
+//!
+//! On each machine:
+//!
+//! ```no_run
+//! use auto::cluster::Agent;
+//!
+//! Agent::bind("0.0.0.0:7727").unwrap().serve().unwrap();
+//! ```
+//!
+//! On the controller:
+//!
+//! ```no_run
+//! use auto::cluster::Cluster;
+//! use auto::record::{Recording, Timing};
+//!
+//! let mut cluster = Cluster::new();
+//! cluster.connect("host-a:7727").unwrap();
+//! cluster.connect("host-b:7727").unwrap();
+//!
+//! let recordings = [
+//!     Recording::load("host-a.rec").unwrap(),
+//!     Recording::load("host-b.rec").unwrap(),
+//! ];
+//! for outcome in cluster.run(&recordings, Timing::Original).unwrap() {
+//!     println!("{}: {:?}", outcome.addr(), outcome.error());
+//! }
+//! ```
+
+use std::fmt;
+use std::io::{self, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use protocol::{invalid_data, read_line, read_recording, split_command, write_recording};
+use record::{Recording, Timing};
+
+/// A server that replays recordings sent by a [`Cluster`](struct.Cluster.html).
+#[derive(Debug)]
+pub struct Agent {
+    listener: TcpListener,
+}
+
+impl Agent {
+    /// Creates an agent listening on `addr`.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Agent> {
+        TcpListener::bind(addr).map(|listener| Agent { listener })
+    }
+
+    /// Returns the address the agent is listening on.
+    #[inline]
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serves controllers one after another, until accepting a connection
+    /// fails.
+    ///
+    /// Errors within a session, such as the controller disconnecting, end
+    /// only that session.
+    pub fn serve(&self) -> io::Result<()> {
+        loop {
+            let (stream, _) = self.listener.accept()?;
+            let _ = serve_session(stream);
+        }
+    }
+
+    /// Serves the next controller to connect until it disconnects.
+    pub fn serve_one(&self) -> io::Result<()> {
+        let (stream, _) = self.listener.accept()?;
+        serve_session(stream)
+    }
+}
+
+fn serve_session(stream: TcpStream) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    let mut loaded = None;
+
+    loop {
+        match read_line(&mut reader, &mut line) {
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            result => result?,
+        }
+
+        let (command, args) = split_command(&line);
+        match command {
+            "play" => {
+                match read_recording(&mut reader, args)? {
+                    Ok(plan) => {
+                        loaded = Some(plan);
+                        writeln!(writer, "ready")?;
+                    },
+                    Err(message) => {
+                        loaded = None;
+                        writeln!(writer, "error {}", message)?;
+                    },
+                }
+            },
+            "start" => {
+                let (recording, timing) = loaded.take().ok_or_else(|| {
+                    invalid_data("started without a recording")
+                })?;
+                let start = Instant::now();
+                let result = recording.replay(timing);
+                let secs = start.elapsed().as_secs_f64();
+                match result {
+                    Ok(()) => writeln!(writer, "done {}", secs)?,
+                    Err(err) => writeln!(writer, "failed {} {}", secs, err)?,
+                }
+            },
+            _ => return Err(invalid_data(format!("unknown command {:?}", command))),
+        }
+    }
+}
+
+struct Machine {
+    addr: SocketAddr,
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+/// A controller connected to the agents on several machines.
+#[derive(Default)]
+pub struct Cluster {
+    machines: Vec<Machine>,
+}
+
+impl fmt::Debug for Cluster {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Cluster").field("addrs", &self.addrs()).finish()
+    }
+}
+
+impl Cluster {
+    /// Creates a cluster without any machines.
+    #[inline]
+    pub fn new() -> Cluster {
+        Cluster::default()
+    }
+
+    /// Connects to the agent at `addr`, returning the index of its machine.
+    pub fn connect<A: ToSocketAddrs>(&mut self, addr: A) -> io::Result<usize> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        let machine = Machine {
+            addr: stream.peer_addr()?,
+            writer: stream.try_clone()?,
+            reader: BufReader::new(stream),
+        };
+        self.machines.push(machine);
+        Ok(self.machines.len() - 1)
+    }
+
+    /// Returns the number of machines.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.machines.len()
+    }
+
+    /// Returns whether there are no machines.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.machines.is_empty()
+    }
+
+    /// Returns the agent addresses of the machines, in order of connection.
+    pub fn addrs(&self) -> Vec<SocketAddr> {
+        self.machines.iter().map(|m| m.addr).collect()
+    }
+
+    /// Replays `recordings[i]` on machine `i`, starting every machine at the
+    /// same time once all of them are ready, and returns their outcomes in
+    /// the same order.
+    ///
+    /// Fails without starting any machine if the number of recordings does
+    /// not match the number of machines or if an agent rejects its recording.
+    pub fn run(&mut self, recordings: &[Recording], timing: Timing) -> io::Result<Vec<Outcome>> {
+        if recordings.len() != self.machines.len() {
+            let message = format!("{} recordings for {} machines",
+                                  recordings.len(), self.machines.len());
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
+
+        for (machine, recording) in self.machines.iter_mut().zip(recordings) {
+            write_recording(&mut machine.writer, "play", recording, timing)?;
+        }
+
+        // The start barrier: wait for every agent before starting any
+        // Every reply is read so that no connection is left out of step
+        let mut line = String::new();
+        let mut rejection = None;
+        for machine in &mut self.machines {
+            read_line(&mut machine.reader, &mut line)?;
+            if line != "ready" && rejection.is_none() {
+                let message = line.trim_start_matches("error ");
+                rejection = Some(format!("{}: {}", machine.addr, message));
+            }
+        }
+        if let Some(rejection) = rejection {
+            return Err(invalid_data(rejection));
+        }
+        for machine in &mut self.machines {
+            machine.writer.write_all(b"start\n")?;
+        }
+
+        let mut outcomes = Vec::with_capacity(self.machines.len());
+        for machine in &mut self.machines {
+            read_line(&mut machine.reader, &mut line)?;
+            outcomes.push(parse_outcome(machine.addr, &line)?);
+        }
+        Ok(outcomes)
+    }
+}
+
+fn parse_outcome(addr: SocketAddr, line: &str) -> io::Result<Outcome> {
+    let mut parts = line.splitn(3, ' ');
+    let status = parts.next().unwrap_or_default();
+    let elapsed = parts.next()
+        .and_then(|s| s.parse().ok())
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .ok_or_else(|| invalid_data(format!("invalid reply {:?}", line)))?;
+    let error = match status {
+        "done" => None,
+        "failed" => Some(parts.next().unwrap_or_default().to_owned()),
+        _ => return Err(invalid_data(format!("invalid reply {:?}", line))),
+    };
+    Ok(Outcome { addr, elapsed, error })
+}
+
+/// How replay went on one machine of a [`Cluster`](struct.Cluster.html).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    addr: SocketAddr,
+    elapsed: Duration,
+    error: Option<String>,
+}
+
+impl Outcome {
+    /// Returns the address of the machine's agent.
+    #[inline]
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns how long replay took on the machine.
+    #[inline]
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns the reason replay stopped early, if it did.
+    #[inline]
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Returns whether every event was replayed.
+    #[inline]
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use super::*;
+
+    #[test]
+    fn loopback_run() {
+        let agents: Vec<_> = (0..2).map(|_| Agent::bind("127.0.0.1:0").unwrap()).collect();
+        let mut cluster = Cluster::new();
+        for agent in &agents {
+            cluster.connect(agent.local_addr().unwrap()).unwrap();
+        }
+        let servers: Vec<_> = agents.into_iter()
+            .map(|agent| thread::spawn(move || agent.serve_one()))
+            .collect();
+
+        let recordings = vec![Recording::new(); 2];
+        let outcomes = cluster.run(&recordings, Timing::Scaled(0.5)).unwrap();
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes.iter().all(Outcome::is_success));
+        assert!(cluster.run(&recordings[..1], Timing::Immediate).is_err());
+
+        drop(cluster);
+        for server in servers {
+            server.join().unwrap().unwrap();
+        }
+    }
+
+    #[test]
+    fn outcome_replies() {
+        let addr = "127.0.0.1:1".parse().unwrap();
+        let outcome = parse_outcome(addr, "failed 1.5 aborted by the kill switch").unwrap();
+        assert_eq!(outcome.elapsed(), Duration::from_millis(1500));
+        assert_eq!(outcome.error(), Some("aborted by the kill switch"));
+        assert!(parse_outcome(addr, "done 0").unwrap().is_success());
+        assert!(parse_outcome(addr, "done -1").is_err());
+        assert!(parse_outcome(addr, "done 1e300").is_err());
+        assert!(parse_outcome(addr, "done inf").is_err());
+    }
+}
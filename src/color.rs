@@ -1,8 +1,11 @@
 //! 🎨 Color utilities.
 
+use std::error::Error;
+use std::fmt;
 use std::hash;
 use std::mem;
 use std::slice;
+use std::str::FromStr;
 
 const FLOAT_RATIO: f64 = 255.0;
 
@@ -17,11 +20,11 @@ macro_rules! impl_color {
             }
         }
 
-        impl<T> Into<[T; $size]> for $t<T> {
+        impl<T> From<$t<T>> for [T; $size] {
             #[inline]
-            fn into(self) -> [T; $size] {
-                let val = unsafe { mem::transmute_copy(&self) };
-                mem::forget(self);
+            fn from(color: $t<T>) -> Self {
+                let val = unsafe { mem::transmute_copy(&color) };
+                mem::forget(color);
                 val
             }
         }
@@ -128,14 +131,467 @@ impl<T: RgbComponent> Rgb<T> {
 
     forward_method! {
         "Returns an RGB value with `u8` components where 0 and 255 correspond to
-        0.0 and 1.0 respectively.
-
+        0.0 and 1.0 respectively.\n\n\
         Keep in mind that the `self` may not be normalized, which may result in
         strange results.",
         into_byte, u8
     }
 }
 
+impl Rgb<u8> {
+    /// Returns the color whose red, green, and blue are the third, second, and
+    /// lowest bytes of `hex` (bits 16–23, 8–15, and 0–7), as in `0xff8800`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use auto::color::Rgb;
+    ///
+    /// assert_eq!(Rgb::from_hex(0xff8800), Rgb { red: 0xff, green: 0x88, blue: 0x00 });
+    /// ```
+    #[inline]
+    pub fn from_hex(hex: u32) -> Rgb<u8> {
+        Rgb { red: (hex >> 16) as u8, green: (hex >> 8) as u8, blue: hex as u8 }
+    }
+
+    /// Returns the color as a number such as `0xff8800`, the inverse of
+    /// [`from_hex`](#method.from_hex).
+    #[inline]
+    pub fn to_hex(self) -> u32 {
+        u32::from(self.red) << 16 | u32::from(self.green) << 8 | u32::from(self.blue)
+    }
+}
+
+/// Formats the color as a hex string such as `#ff8800`.
+impl fmt::Display for Rgb<u8> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:06x}", self.to_hex())
+    }
+}
+
+/// Formats the color as hex digits such as `ff8800`, or `#ff8800` with the
+/// `#` flag.
+impl fmt::LowerHex for Rgb<u8> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            f.write_str("#")?;
+        }
+        write!(f, "{:06x}", self.to_hex())
+    }
+}
+
+/// Formats the color as hex digits such as `FF8800`, or `#FF8800` with the
+/// `#` flag.
+impl fmt::UpperHex for Rgb<u8> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            f.write_str("#")?;
+        }
+        write!(f, "{:06X}", self.to_hex())
+    }
+}
+
+/// An error returned when parsing a hex color fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseRgbError(());
+
+impl fmt::Display for ParseRgbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid hex color, expected a form such as #ff8800 or #f80")
+    }
+}
+
+impl Error for ParseRgbError {}
+
+/// Parses a hex color such as `#ff8800`, or its short form `#f80`, in either
+/// case and with an optional `#`.
+///
+/// # Examples
+///
+/// ```
+/// use auto::color::Rgb;
+///
+/// let orange = Rgb::from_hex(0xff8800);
+/// assert_eq!("#ff8800".parse::<Rgb>(), Ok(orange));
+/// assert_eq!("F80".parse::<Rgb>(), Ok(orange));
+/// assert_eq!(orange.to_string(), "#ff8800");
+/// ```
+impl FromStr for Rgb<u8> {
+    type Err = ParseRgbError;
+
+    fn from_str(s: &str) -> Result<Rgb<u8>, ParseRgbError> {
+        let digits = s.strip_prefix('#').unwrap_or(s);
+        if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(ParseRgbError(()));
+        }
+        let hex = u32::from_str_radix(digits, 16).map_err(|_| ParseRgbError(()))?;
+        match digits.len() {
+            6 => Ok(Rgb::from_hex(hex)),
+            3 => {
+                // Each digit is repeated, so that `f80` means `ff8800`
+                let expand = |n: u32| (n & 0xf) as u8 * 0x11;
+                Ok(Rgb { red: expand(hex >> 8), green: expand(hex >> 4), blue: expand(hex) })
+            },
+            _ => Err(ParseRgbError(())),
+        }
+    }
+}
+
+impl<T> Rgb<T> {
+    /// Returns an RGBA value with the components of `self` and `alpha`.
+    #[inline]
+    pub fn with_alpha(self, alpha: T) -> Rgba<T> {
+        Rgba { red: self.red, green: self.green, blue: self.blue, alpha }
+    }
+}
+
+/// A ***Red-Green-Blue-Alpha*** color, where alpha is the opacity.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub struct Rgba<T=u8> {
+    /// The amount of red.
+    pub red: T,
+    /// The amount of green.
+    pub green: T,
+    /// The amount of blue.
+    pub blue: T,
+    /// The opacity, from fully transparent to fully opaque.
+    pub alpha: T,
+}
+
+impl_color! { Rgba }
+
+impl<T> Rgba<T> {
+    /// Returns the color components of `self`, discarding its opacity.
+    #[inline]
+    pub fn rgb(self) -> Rgb<T> {
+        Rgb { red: self.red, green: self.green, blue: self.blue }
+    }
+}
+
+impl<T: RgbComponent> Rgba<T> {
+    /// Normalizes `self` in-place between `T`'s upper and lower bounds.
+    #[inline]
+    pub fn normalize(&mut self) {
+        for comp in AsMut::<[T]>::as_mut(self) {
+            comp.normalize();
+        }
+    }
+
+    /// Returns an RGBA value with `f64` components that are not guaranteed to
+    /// be normalized.
+    #[inline]
+    pub fn into_float(self) -> Rgba<f64> {
+        let Rgba { red, green, blue, alpha } = self;
+        Rgb { red, green, blue }.into_float().with_alpha(alpha.into_float())
+    }
+
+    /// Returns an RGBA value with `u8` components where 0 and 255 correspond
+    /// to 0.0 and 1.0 respectively.
+    #[inline]
+    pub fn into_byte(self) -> Rgba<u8> {
+        let Rgba { red, green, blue, alpha } = self;
+        Rgb { red, green, blue }.into_byte().with_alpha(alpha.into_byte())
+    }
+}
+
+macro_rules! impl_opaque {
+    ($($t:ty => $opaque:expr),+) => { $(
+        impl From<Rgb<$t>> for Rgba<$t> {
+            /// Returns a fully opaque RGBA value.
+            #[inline]
+            fn from(rgb: Rgb<$t>) -> Rgba<$t> {
+                rgb.with_alpha($opaque)
+            }
+        }
+    )+ }
+}
+
+impl_opaque! { u8 => 255, f32 => 1.0, f64 => 1.0 }
+
+/// A ***Hue-Saturation-Lightness*** color.
+///
+/// Converting from [`Rgb<u8>`](struct.Rgb.html) and back gives the original
+/// color.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Hsl {
+    /// The hue in degrees, from 0.0 up to but excluding 360.0, where 0.0 is
+    /// red, 120.0 is green, and 240.0 is blue.
+    pub hue: f64,
+    /// The saturation, from 0.0 for gray to 1.0 for full color.
+    pub saturation: f64,
+    /// The lightness, from 0.0 for black to 1.0 for white.
+    pub lightness: f64,
+}
+
+/// A ***Hue-Saturation-Value*** color, also known as HSB.
+///
+/// Converting from [`Rgb<u8>`](struct.Rgb.html) and back gives the original
+/// color.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Hsv {
+    /// The hue in degrees, from 0.0 up to but excluding 360.0, where 0.0 is
+    /// red, 120.0 is green, and 240.0 is blue.
+    pub hue: f64,
+    /// The saturation, from 0.0 for gray to 1.0 for full color.
+    pub saturation: f64,
+    /// The value or brightness, from 0.0 for black to 1.0 for full
+    /// brightness.
+    pub value: f64,
+}
+
+impl<T: RgbComponent + Copy> Rgb<T> {
+    /// Returns the perceptual difference between `self` and `other`, as the
+    /// CIEDE2000 color difference of the colors as sRGB.
+    ///
+    /// A difference of about 1.0 is barely noticeable to a person, while
+    /// differences above 10.0 are between clearly different colors.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use auto::color::Rgb;
+    ///
+    /// let red = Rgb { red: 200u8, green: 30, blue: 30 };
+    /// let darker = Rgb { red: 196u8, green: 30, blue: 32 };
+    /// let blue = Rgb { red: 30u8, green: 30, blue: 200 };
+    /// assert!(red.distance(&darker) < 2.0);
+    /// assert!(red.distance(&blue) > 10.0);
+    /// ```
+    pub fn distance(&self, other: &Rgb<T>) -> f64 {
+        ciede2000(Lab::from(self.into_float()), Lab::from(other.into_float()))
+    }
+
+    /// Returns whether `self` and `other` differ by at most `tolerance`, as
+    /// measured by [`distance`](#method.distance).
+    ///
+    /// A tolerance of 2.0 to 3.0 absorbs the variations in color from
+    /// anti-aliasing, color profiles, and gamma.
+    #[inline]
+    pub fn matches(&self, other: &Rgb<T>, tolerance: f64) -> bool {
+        self.distance(other) <= tolerance
+    }
+}
+
+/// A color in the CIELAB color space under the D65 illuminant.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Lab {
+    l: f64,
+    a: f64,
+    b: f64,
+}
+
+impl From<Rgb<f64>> for Lab {
+    fn from(rgb: Rgb<f64>) -> Lab {
+        let linear = |c: f64| {
+            let c = c.normalized();
+            if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+        };
+        let (r, g, b) = (linear(rgb.red), linear(rgb.green), linear(rgb.blue));
+        let x = (0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b) / 0.950_47;
+        let y = 0.212_672_9 * r + 0.715_152_2 * g + 0.072_175_0 * b;
+        let z = (0.019_333_9 * r + 0.119_192_0 * g + 0.950_304_1 * b) / 1.088_83;
+
+        const DELTA: f64 = 6.0 / 29.0;
+        let f = |t: f64| {
+            if t > DELTA * DELTA * DELTA { t.cbrt() } else { t / (3.0 * DELTA * DELTA) + 4.0 / 29.0 }
+        };
+        let (fx, fy, fz) = (f(x), f(y), f(z));
+        Lab { l: 116.0 * fy - 16.0, a: 500.0 * (fx - fy), b: 200.0 * (fy - fz) }
+    }
+}
+
+/// Returns the CIEDE2000 difference between two colors, following Sharma,
+/// Wu, and Dalal's notes on the formula.
+fn ciede2000(x: Lab, y: Lab) -> f64 {
+    const POW_25_7: f64 = 6_103_515_625.0;
+
+    let c_bar = (x.a.hypot(x.b) + y.a.hypot(y.b)) / 2.0;
+    let g = 0.5 * (1.0 - (c_bar.powi(7) / (c_bar.powi(7) + POW_25_7)).sqrt());
+    let (a1, a2) = ((1.0 + g) * x.a, (1.0 + g) * y.a);
+    let (c1, c2) = (a1.hypot(x.b), a2.hypot(y.b));
+    let hue = |b: f64, a: f64| {
+        if a == 0.0 && b == 0.0 { 0.0 } else { b.atan2(a).to_degrees().rem_euclid(360.0) }
+    };
+    let (h1, h2) = (hue(x.b, a1), hue(y.b, a2));
+
+    let delta_l = y.l - x.l;
+    let delta_c = c2 - c1;
+    let delta_h = if c1 * c2 == 0.0 {
+        0.0
+    } else if h2 - h1 > 180.0 {
+        h2 - h1 - 360.0
+    } else if h2 - h1 < -180.0 {
+        h2 - h1 + 360.0
+    } else {
+        h2 - h1
+    };
+    let delta_h = 2.0 * (c1 * c2).sqrt() * (delta_h / 2.0).to_radians().sin();
+
+    let l_bar = (x.l + y.l) / 2.0;
+    let c_bar = (c1 + c2) / 2.0;
+    let h_bar = if c1 * c2 == 0.0 {
+        h1 + h2
+    } else if (h1 - h2).abs() <= 180.0 {
+        (h1 + h2) / 2.0
+    } else if h1 + h2 < 360.0 {
+        (h1 + h2 + 360.0) / 2.0
+    } else {
+        (h1 + h2 - 360.0) / 2.0
+    };
+
+    let cos = |degrees: f64| degrees.to_radians().cos();
+    let t = 1.0 - 0.17 * cos(h_bar - 30.0) + 0.24 * cos(2.0 * h_bar)
+        + 0.32 * cos(3.0 * h_bar + 6.0) - 0.20 * cos(4.0 * h_bar - 63.0);
+    let delta_theta = 30.0 * (-((h_bar - 275.0) / 25.0).powi(2)).exp();
+    let r_c = 2.0 * (c_bar.powi(7) / (c_bar.powi(7) + POW_25_7)).sqrt();
+    let s_l = 1.0 + 0.015 * (l_bar - 50.0).powi(2) / (20.0 + (l_bar - 50.0).powi(2)).sqrt();
+    let s_c = 1.0 + 0.045 * c_bar;
+    let s_h = 1.0 + 0.015 * c_bar * t;
+    let r_t = -(2.0 * delta_theta).to_radians().sin() * r_c;
+
+    let (l, c, h) = (delta_l / s_l, delta_c / s_c, delta_h / s_h);
+    (l * l + c * c + h * h + r_t * c * h).sqrt()
+}
+
+/// Returns the shortest angle in degrees between the hues `a` and `b`, from
+/// 0.0 to 180.0.
+///
+/// # Examples
+///
+/// ```
+/// use auto::color::{self, Hsv, Rgb};
+///
+/// let orange = Hsv::from(Rgb { red: 255u8, green: 140, blue: 0 });
+/// assert!(color::hue_distance(orange.hue, 30.0) < 5.0);
+/// assert!(color::hue_distance(350.0, 10.0) == 20.0);
+/// ```
+pub fn hue_distance(a: f64, b: f64) -> f64 {
+    let diff = (a - b).rem_euclid(360.0);
+    diff.min(360.0 - diff)
+}
+
+/// Returns the hue of `rgb` whose largest component is `max`, and whose
+/// largest and smallest components differ by `chroma`.
+fn hue(rgb: Rgb<f64>, max: f64, chroma: f64) -> f64 {
+    if chroma == 0.0 {
+        return 0.0;
+    }
+    let sector = if max == rgb.red {
+        ((rgb.green - rgb.blue) / chroma).rem_euclid(6.0)
+    } else if max == rgb.green {
+        (rgb.blue - rgb.red) / chroma + 2.0
+    } else {
+        (rgb.red - rgb.green) / chroma + 4.0
+    };
+    (sector * 60.0) % 360.0
+}
+
+/// Returns the RGB value with `hue` and `chroma`, whose smallest component
+/// is `min`.
+fn from_hue(hue: f64, chroma: f64, min: f64) -> Rgb<f64> {
+    let sector = (hue / 60.0).rem_euclid(6.0);
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (red, green, blue) = match sector as u8 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    Rgb { red: red + min, green: green + min, blue: blue + min }
+}
+
+/// Rounds a float component to the nearest byte, rather than truncating as
+/// `into_byte` does, so that conversions through floats round-trip.
+fn round_byte(component: f64) -> u8 {
+    (component.normalized() * FLOAT_RATIO).round() as u8
+}
+
+impl<T: RgbComponent> From<Rgb<T>> for Hsv {
+    fn from(rgb: Rgb<T>) -> Hsv {
+        let rgb = rgb.into_float().normalized();
+        let max = rgb.red.max(rgb.green).max(rgb.blue);
+        let min = rgb.red.min(rgb.green).min(rgb.blue);
+        let chroma = max - min;
+        Hsv {
+            hue: hue(rgb, max, chroma),
+            saturation: if max == 0.0 { 0.0 } else { chroma / max },
+            value: max,
+        }
+    }
+}
+
+impl<T: RgbComponent> From<Rgb<T>> for Hsl {
+    fn from(rgb: Rgb<T>) -> Hsl {
+        let rgb = rgb.into_float().normalized();
+        let max = rgb.red.max(rgb.green).max(rgb.blue);
+        let min = rgb.red.min(rgb.green).min(rgb.blue);
+        let chroma = max - min;
+        let lightness = (max + min) / 2.0;
+        let saturation = if chroma == 0.0 {
+            0.0
+        } else {
+            chroma / (1.0 - (2.0 * lightness - 1.0).abs())
+        };
+        Hsl { hue: hue(rgb, max, chroma), saturation, lightness }
+    }
+}
+
+impl From<Hsv> for Rgb<f64> {
+    fn from(hsv: Hsv) -> Rgb<f64> {
+        let chroma = hsv.value * hsv.saturation;
+        from_hue(hsv.hue, chroma, hsv.value - chroma)
+    }
+}
+
+impl From<Hsl> for Rgb<f64> {
+    fn from(hsl: Hsl) -> Rgb<f64> {
+        let chroma = (1.0 - (2.0 * hsl.lightness - 1.0).abs()) * hsl.saturation;
+        from_hue(hsl.hue, chroma, hsl.lightness - chroma / 2.0)
+    }
+}
+
+impl From<Hsv> for Rgb<u8> {
+    #[inline]
+    fn from(hsv: Hsv) -> Rgb<u8> {
+        let rgb = Rgb::<f64>::from(hsv);
+        Rgb { red: round_byte(rgb.red), green: round_byte(rgb.green), blue: round_byte(rgb.blue) }
+    }
+}
+
+impl From<Hsl> for Rgb<u8> {
+    #[inline]
+    fn from(hsl: Hsl) -> Rgb<u8> {
+        let rgb = Rgb::<f64>::from(hsl);
+        Rgb { red: round_byte(rgb.red), green: round_byte(rgb.green), blue: round_byte(rgb.blue) }
+    }
+}
+
+impl From<Hsl> for Hsv {
+    fn from(hsl: Hsl) -> Hsv {
+        let value = hsl.lightness + hsl.saturation * hsl.lightness.min(1.0 - hsl.lightness);
+        let saturation = if value == 0.0 { 0.0 } else { 2.0 * (1.0 - hsl.lightness / value) };
+        Hsv { hue: hsl.hue, saturation, value }
+    }
+}
+
+impl From<Hsv> for Hsl {
+    fn from(hsv: Hsv) -> Hsl {
+        let lightness = hsv.value * (1.0 - hsv.saturation / 2.0);
+        let saturation = if lightness == 0.0 || lightness == 1.0 {
+            0.0
+        } else {
+            (hsv.value - lightness) / lightness.min(1.0 - lightness)
+        };
+        Hsl { hue: hsv.hue, saturation, lightness }
+    }
+}
+
 /// A type that can be used as a component of [`Rgb`](struct.Rgb.html).
 pub trait RgbComponent {
     /// Normalizes `self` in-place between `T`'s upper and lower bounds.
@@ -175,6 +631,8 @@ impl RgbComponent for f32 {
         *self = self.normalized();
     }
 
+    // `clamp` would return NaN for NaN rather than 0
+    #[allow(clippy::manual_clamp)]
     #[inline]
     fn normalized(self) -> f32 {
         self.max(0.0).min(1.0)
@@ -195,6 +653,8 @@ impl RgbComponent for f64 {
         *self = self.normalized();
     }
 
+    // `clamp` would return NaN for NaN rather than 0
+    #[allow(clippy::manual_clamp)]
     #[inline]
     fn normalized(self) -> f64 {
         self.max(0.0).min(1.0)
@@ -208,3 +668,48 @@ impl RgbComponent for f64 {
         (self.normalized() * FLOAT_RATIO) as u8
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_round_trips() {
+        for red in (0..=255).step_by(5) {
+            for green in (0..=255).step_by(3) {
+                for blue in (0..=255).step_by(7) {
+                    let rgb = Rgb { red, green, blue };
+                    assert_eq!(Rgb::<u8>::from(Hsl::from(rgb)), rgb);
+                    assert_eq!(Rgb::<u8>::from(Hsv::from(rgb)), rgb);
+                    assert_eq!(Rgb::<u8>::from(Hsv::from(Hsl::from(rgb))), rgb);
+                    assert_eq!(Rgb::<u8>::from(Hsl::from(Hsv::from(rgb))), rgb);
+                }
+            }
+        }
+        let rgba = Rgb { red: 1u8, green: 2, blue: 3 }.with_alpha(4);
+        assert_eq!(<[u8; 4]>::from(rgba), [1, 2, 3, 4]);
+        assert_eq!(Rgba::from(rgba.rgb()).alpha, 255);
+    }
+
+    #[test]
+    fn ciede2000_reference() {
+        // Pairs from Sharma, Wu, and Dalal's test data
+        let pairs = [
+            ((50.0, 2.6772, -79.7751), (50.0, 0.0, -82.7485), 2.0425),
+            ((50.0, -1.3802, -84.2814), (50.0, 0.0, -82.7485), 1.0000),
+            ((50.0, 2.5, 0.0), (73.0, 25.0, -18.0), 27.1492),
+            ((60.2574, -34.0099, 36.2677), (60.4626, -34.1751, 39.4387), 1.2644),
+            ((2.0776, 0.0795, -1.1350), (0.9033, -0.0636, -0.5514), 0.9082),
+        ];
+        for &((l1, a1, b1), (l2, a2, b2), expected) in &pairs {
+            let x = Lab { l: l1, a: a1, b: b1 };
+            let y = Lab { l: l2, a: a2, b: b2 };
+            assert!((ciede2000(x, y) - expected).abs() < 1e-4);
+            assert!((ciede2000(y, x) - expected).abs() < 1e-4);
+        }
+
+        let white = Rgb { red: 255u8, green: 255, blue: 255 };
+        assert!(white.matches(&white, 0.0));
+        assert!(!white.matches(&Rgb { red: 0, green: 0, blue: 0 }, 50.0));
+    }
+}
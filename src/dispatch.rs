@@ -0,0 +1,465 @@
+//! 🚦 Ordered execution of automation tasks.
+//!
+//! Tasks submitted to the same [`Queue`](struct.Queue.html) run one at a time
+//! in submission order, while tasks on different queues may interleave. This
+//! allows independent sequences (e.g. typing and mouse movement) to proceed
+//! concurrently without reordering the events within each sequence.
+//!
+//! # Examples
+//!
+//! ```
+//! use auto::dispatch::{self, Queue};
+//!
+//! let typing = Queue::named("typing");
+//! let mouse  = Queue::named("mouse");
+//!
+//! typing.exec_async(|| { /* type a username */ });
+//! mouse.exec_async(|| { /* move towards the login button */ });
+//!
+//! // Neither queue proceeds until both have reached this point
+//! dispatch::barrier(&[&typing, &mouse]);
+//!
+//! mouse.exec_async(|| { /* click the login button */ });
+//! mouse.wait();
+//! ```
+//!
+//! # Timing
+//!
+//! Pauses belong in the queue as [`delay`](struct.Queue.html#method.delay)
+//! steps rather than as sleeps inside tasks, so that
+//! [`interrupt_delays`](fn.interrupt_delays.html) can cut them short. Queues
+//! can also mark [sync points](struct.Queue.html#method.sync_point) that
+//! other threads wait for:
+//!
+//! ```
+//! use std::time::Duration;
+//! use auto::dispatch::Queue;
+//!
+//! let queue = Queue::new("login");
+//! queue.exec_async(|| { /* type a username */ });
+//! queue.delay_range(Duration::from_millis(50)..Duration::from_millis(150))
+//!     .sync_point("username-typed")
+//!     .exec_async(|| { /* type a password */ });
+//!
+//! assert!(queue.wait_for_sync_point("username-typed", Duration::from_secs(5)));
+//! ```
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{self, mpsc, Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use session;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+lazy_static! {
+    static ref NAMED_QUEUES: Mutex<HashMap<String, Queue>> = Mutex::default();
+
+    /// Counts of interruptions, which delays watch for changes.
+    static ref INTERRUPTS: (Mutex<Interrupts>, Condvar) = Default::default();
+
+    /// Held while a barrier is submitted to its queues, so that concurrent
+    /// barriers reach every queue they share in the same order.
+    static ref BARRIERS: Mutex<()> = Mutex::default();
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Counts of interruptions of delays.
+#[derive(Default)]
+struct Interrupts {
+    /// The number of calls to `interrupt_delays`.
+    count: u64,
+    /// The number of aborts of each live session, by session identifier.
+    sessions: HashMap<u64, u64>,
+}
+
+impl Interrupts {
+    /// Returns the counts that interrupt a delay in the session `id`.
+    fn counts(&self, id: Option<u64>) -> (u64, u64) {
+        let session = id.and_then(|id| self.sessions.get(&id).cloned());
+        (self.count, session.unwrap_or(0))
+    }
+}
+
+/// A serial queue of tasks that run on a dedicated thread.
+///
+/// Tasks run in the [session](../session/index.html) that was current on the
+/// thread that submitted them, if any.
+///
+/// Cloning a queue produces another handle to the same queue. The thread exits
+/// once every handle has been dropped and all tasks have finished.
+#[derive(Clone)]
+pub struct Queue {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    name: String,
+    sender: Mutex<mpsc::Sender<Job>>,
+    sync_points: Arc<SyncPoints>,
+}
+
+/// The labels of the sync points that a queue has reached, in order.
+#[derive(Default)]
+struct SyncPoints {
+    reached: Mutex<Vec<String>>,
+    changed: Condvar,
+}
+
+impl fmt::Debug for Queue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Queue").field("name", &self.inner.name).finish()
+    }
+}
+
+impl PartialEq for Queue {
+    #[inline]
+    fn eq(&self, other: &Queue) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl Eq for Queue {}
+
+impl Queue {
+    /// Creates a new queue that is independent of all other queues, including
+    /// named queues with the same name.
+    pub fn new<S: Into<String>>(name: S) -> Queue {
+        let name = name.into();
+        let (sender, receiver) = mpsc::channel::<Job>();
+
+        thread::Builder::new()
+            .name(format!("auto-dispatch-{}", name))
+            .spawn(move || {
+                for job in receiver {
+                    // A panicking task must not take the rest of the queue
+                    // down with it
+                    let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                }
+            })
+            .expect("failed to spawn dispatch queue thread");
+
+        let sync_points = Arc::default();
+        Queue { inner: Arc::new(Inner { name, sender: Mutex::new(sender), sync_points }) }
+    }
+
+    /// Returns the process-wide queue with `name`, creating it if it does not
+    /// exist yet.
+    ///
+    /// Named queues live for the remainder of the process.
+    pub fn named(name: &str) -> Queue {
+        let mut queues = NAMED_QUEUES.lock().unwrap_or_else(|e| e.into_inner());
+        queues.entry(name.into()).or_insert_with(|| Queue::new(name)).clone()
+    }
+
+    /// Returns the name of the queue.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    /// Submits `task` to run after all previously submitted tasks and returns
+    /// immediately.
+    pub fn exec_async<F>(&self, task: F)
+        where F: FnOnce() + Send + 'static
+    {
+        let job: Job = match session::current() {
+            Some(session) => Box::new(move || session.run(task)),
+            None => Box::new(task),
+        };
+        let sender = self.inner.sender.lock().unwrap_or_else(|e| e.into_inner());
+        sender.send(job).expect("dispatch queue thread exited");
+    }
+
+    /// Submits `task` to run after all previously submitted tasks and blocks
+    /// until it returns, yielding its result.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `task` panics. Calling this from within a task on the same
+    /// queue deadlocks.
+    pub fn exec_sync<F, T>(&self, task: F) -> T
+        where F: FnOnce() -> T + Send + 'static, T: Send + 'static
+    {
+        let (tx, rx) = mpsc::channel();
+        self.exec_async(move || {
+            let _ = tx.send(task());
+        });
+        match rx.recv() {
+            Ok(value) => value,
+            Err(_) => panic!("task panicked on dispatch queue {:?}", self.name()),
+        }
+    }
+
+    /// Blocks until all previously submitted tasks have finished.
+    #[inline]
+    pub fn wait(&self) {
+        self.exec_sync(|| {});
+    }
+
+    /// Submits a pause of `duration` before the tasks submitted after it.
+    ///
+    /// The pause ends early if [`interrupt_delays`](fn.interrupt_delays.html)
+    /// is called while it is in progress.
+    pub fn delay(&self, duration: Duration) -> &Queue {
+        self.exec_async(move || sleep(duration));
+        self
+    }
+
+    /// Submits a pause of a random duration within `range`, chosen when the
+    /// pause starts.
+    pub fn delay_range(&self, range: Range<Duration>) -> &Queue {
+        self.exec_async(move || sleep(random_between(range.start, range.end)));
+        self
+    }
+
+    /// Submits a step that marks `label` as reached once every task before it
+    /// has finished.
+    pub fn sync_point(&self, label: &str) -> &Queue {
+        let label = label.to_owned();
+        let sync_points = self.inner.sync_points.clone();
+        self.exec_async(move || {
+            lock(&sync_points.reached).push(label);
+            sync_points.changed.notify_all();
+        });
+        self
+    }
+
+    /// Returns the labels of the sync points reached so far, in order.
+    pub fn sync_points_reached(&self) -> Vec<String> {
+        lock(&self.inner.sync_points.reached).clone()
+    }
+
+    /// Blocks until the sync point `label` has been reached, returning
+    /// `false` if it is not reached within `timeout`.
+    pub fn wait_for_sync_point(&self, label: &str, timeout: Duration) -> bool {
+        // A deadline too far away to represent never comes
+        let deadline = Instant::now().checked_add(timeout);
+        let sync_points = &self.inner.sync_points;
+        let mut reached = lock(&sync_points.reached);
+        loop {
+            if reached.iter().any(|l| l == label) {
+                return true;
+            }
+            reached = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return false;
+                    }
+                    sync_points.changed
+                        .wait_timeout(reached, deadline - now)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+                None => sync_points.changed.wait(reached).unwrap_or_else(|e| e.into_inner()),
+            };
+        }
+    }
+}
+
+/// Ends every [`delay`](struct.Queue.html#method.delay) in progress on any
+/// queue, so that the tasks after them start right away.
+///
+/// Delays that start after this call are not affected.
+pub fn interrupt_delays() {
+    let (ref interrupts, ref changed) = *INTERRUPTS;
+    lock(interrupts).count += 1;
+    changed.notify_all();
+}
+
+/// Ends the delays in progress in the session identified by `id`.
+pub(crate) fn interrupt_session_delays(id: u64) {
+    let (ref interrupts, ref changed) = *INTERRUPTS;
+    *lock(interrupts).sessions.entry(id).or_insert(0) += 1;
+    changed.notify_all();
+}
+
+/// Forgets the interruptions of the session identified by `id`, once it is
+/// gone.
+pub(crate) fn forget_session(id: u64) {
+    lock(&INTERRUPTS.0).sessions.remove(&id);
+}
+
+/// Sleeps for `duration` unless `interrupt_delays` is called first, or the
+/// current session is aborted.
+pub(crate) fn sleep(duration: Duration) {
+    let (ref interrupts, ref changed) = *INTERRUPTS;
+    // A deadline too far away to represent never comes
+    let deadline = Instant::now().checked_add(duration);
+    let id = session::current_id();
+    let mut current = lock(interrupts);
+    let start = current.counts(id);
+    while current.counts(id) == start {
+        current = match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    break;
+                }
+                changed
+                    .wait_timeout(current, deadline - now)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0
+            }
+            None => changed.wait(current).unwrap_or_else(|e| e.into_inner()),
+        };
+    }
+}
+
+/// Returns a duration chosen uniformly from `min..max`, or `min` if the range
+/// is empty.
+pub(crate) fn random_between(min: Duration, max: Duration) -> Duration {
+    if max <= min {
+        return min;
+    }
+    let span = (max - min).as_nanos();
+    min + Duration::from_nanos((u128::from(random()) % span) as u64)
+}
+
+/// Returns `true` with probability `chance`.
+pub(crate) fn random_chance(chance: f64) -> bool {
+    chance > 0.0 && (random() as f64 / u64::MAX as f64) < chance
+}
+
+fn random() -> u64 {
+    // Randomly keyed hashers are random enough for spacing out input
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(Instant::now().elapsed().as_nanos());
+    hasher.finish()
+}
+
+/// Inserts a barrier across `queues`.
+///
+/// Tasks submitted to any of the queues after the barrier do not start until
+/// every task submitted to all of the queues before the barrier has finished.
+/// This call does not block; use [`Queue::wait`](struct.Queue.html#method.wait)
+/// to additionally wait for the barrier to be reached.
+pub fn barrier(queues: &[&Queue]) {
+    let mut unique: Vec<&Queue> = Vec::with_capacity(queues.len());
+    for &queue in queues {
+        if !unique.contains(&queue) {
+            unique.push(queue);
+        }
+    }
+
+    let barrier = Arc::new(sync::Barrier::new(unique.len()));
+    // Otherwise two barriers could each be ahead of the other on different
+    // queues, and wait for each other forever
+    let _submitting = lock(&BARRIERS);
+    for queue in unique {
+        let barrier = barrier.clone();
+        queue.exec_async(move || {
+            barrier.wait();
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use session::Session;
+    use std::time::Duration;
+
+    #[test]
+    fn serial_order() {
+        let queue = Queue::new("test-order");
+        let log = Arc::new(Mutex::new(Vec::new()));
+        for i in 0..100 {
+            let log = log.clone();
+            queue.exec_async(move || log.lock().unwrap().push(i));
+        }
+        queue.wait();
+        assert_eq!(*log.lock().unwrap(), (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn delays_and_sync_points() {
+        // Interrupt a session of its own so other tests' delays run in full
+        let queue = Queue::new("test-delays");
+        let session = Session::new();
+        let id = session.run(|| {
+            queue.delay(Duration::MAX).sync_point("after");
+            session::current_id().unwrap()
+        });
+        assert!(!queue.wait_for_sync_point("after", Duration::from_millis(20)));
+
+        // The delay may not have started by the first interrupt
+        while !queue.wait_for_sync_point("after", Duration::from_millis(20)) {
+            interrupt_session_delays(id);
+        }
+        assert_eq!(queue.sync_points_reached(), ["after"]);
+
+        let range = Duration::from_millis(5)..Duration::from_millis(10);
+        for _ in 0..100 {
+            let delay = random_between(range.start, range.end);
+            assert!(range.start <= delay && delay < range.end);
+        }
+    }
+
+    #[test]
+    fn named_identity() {
+        assert_eq!(Queue::named("test-named"), Queue::named("test-named"));
+        assert_ne!(Queue::named("test-named"), Queue::new("test-named"));
+    }
+
+    #[test]
+    fn barrier_orders_across_queues() {
+        let (a, b) = (Queue::new("test-a"), Queue::new("test-b"));
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        let slow = log.clone();
+        a.exec_async(move || {
+            thread::sleep(Duration::from_millis(50));
+            slow.lock().unwrap().push("a");
+        });
+        barrier(&[&a, &b, &a]);
+        let fast = log.clone();
+        b.exec_async(move || fast.lock().unwrap().push("b"));
+
+        b.wait();
+        assert_eq!(*log.lock().unwrap(), ["a", "b"]);
+    }
+
+    #[test]
+    fn concurrent_barriers() {
+        let (a, b) = (Queue::new("test-concurrent-a"), Queue::new("test-concurrent-b"));
+        let threads: Vec<_> = (0..2)
+            .map(|i| {
+                let (a, b) = (a.clone(), b.clone());
+                thread::spawn(move || {
+                    for _ in 0..200 {
+                        if i == 0 {
+                            barrier(&[&a, &b]);
+                        } else {
+                            barrier(&[&b, &a]);
+                        }
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        a.sync_point("done");
+        b.sync_point("done");
+        assert!(a.wait_for_sync_point("done", Duration::from_secs(10)));
+        assert!(b.wait_for_sync_point("done", Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn survives_panic() {
+        let queue = Queue::new("test-panic");
+        queue.exec_async(|| panic!("expected"));
+        assert_eq!(queue.exec_sync(|| 42), 42);
+    }
+}
@@ -0,0 +1,234 @@
+//! ⚠️ Error handling utilities.
+
+use std::error;
+use std::fmt;
+use std::result;
+
+use verify::Failure;
+
+/// A specialized `Result` type for this crate's operations.
+pub type Result<T> = result::Result<T, Error>;
+
+/// The error type for this crate's operations.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub enum Error {
+    /// An error code reported by the operating system.
+    Os(OsError),
+    /// The process lacks a permission required for the operation, such as
+    /// being trusted for accessibility on macOS.
+    PermissionDenied,
+    /// The operation is not supported on the current platform.
+    Unsupported,
+    /// The operating system reported that the operation failed without giving
+    /// a reason.
+    Failed,
+    /// The operation was refused by the installed
+    /// [`DenyList`](../safety/struct.DenyList.html).
+    Denied,
+    /// An action did not have the effect required by the installed
+    /// verification [`Policy`](../verify/struct.Policy.html).
+    Unverified(Box<Failure>),
+    /// A key [`Sequence`](../keyboard/struct.Sequence.html) was aborted
+    /// because keyboard focus moved while it was being sent.
+    FocusChanged,
+    /// [Playback](../record/struct.Playback.html) stopped because the layout
+    /// of the displays changed while it ran.
+    LayoutChanged,
+    /// The operation was stopped by the
+    /// [kill switch](../abort/index.html).
+    Aborted,
+    /// The operation did not complete within its timeout.
+    TimedOut,
+}
+
+impl From<OsError> for Error {
+    #[inline]
+    fn from(err: OsError) -> Error {
+        Error::Os(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Os(ref err) => err.fmt(f),
+            Error::PermissionDenied => f.write_str("permission denied"),
+            Error::Unsupported => f.write_str("unsupported on this platform"),
+            Error::Failed => f.write_str("the operation failed"),
+            Error::Denied => f.write_str("denied by the safety deny list"),
+            Error::Unverified(ref failure) => failure.fmt(f),
+            Error::FocusChanged => f.write_str("keyboard focus changed during the sequence"),
+            Error::LayoutChanged => f.write_str("the display layout changed during playback"),
+            Error::Aborted => f.write_str("aborted by the kill switch"),
+            Error::TimedOut => f.write_str("the operation timed out"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Os(ref err) => Some(err),
+            Error::Unverified(ref failure) => Some(&**failure),
+            _ => None,
+        }
+    }
+}
+
+/// An error code reported by the operating system.
+///
+/// The meaning of the code depends on the current operating system:
+///
+/// - **macOS:** a `CGError` from Quartz, an `AXError` from the accessibility
+///   API, an `OSStatus` from Carbon, or a Mach `kern_return_t`
+///
+/// - **Windows:** a system error code, such as those from `GetLastError`
+///
+/// - **Linux:** an `errno` value
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct OsError {
+    code: i32,
+}
+
+impl OsError {
+    /// Creates a new instance from a raw error code.
+    #[inline]
+    pub fn from_code(code: i32) -> OsError {
+        OsError { code }
+    }
+
+    /// Returns the raw error code.
+    #[inline]
+    pub fn code(&self) -> i32 {
+        self.code
+    }
+
+    /// Returns a human-readable description of the error as provided by the
+    /// operating system.
+    pub fn message(&self) -> String {
+        imp::message(self.code)
+    }
+}
+
+impl fmt::Display for OsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (os error {})", self.message(), self.code)
+    }
+}
+
+impl error::Error for OsError {}
+
+#[cfg(target_os = "windows")]
+impl OsError {
+    /// Returns the calling thread's last-error code as reported by
+    /// `GetLastError`.
+    #[inline]
+    pub fn last() -> OsError {
+        let code = unsafe { ::winapi::um::errhandlingapi::GetLastError() };
+        OsError::from_code(code as i32)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use std::ffi::CStr;
+    use std::os::raw::c_char;
+
+    extern {
+        fn mach_error_string(error_value: i32) -> *const c_char;
+    }
+
+    pub fn message(code: i32) -> String {
+        let s = match code {
+            0    => "success",
+            1000 => "a general failure occurred",
+            1001 => "one or more of the parameters passed to a function are invalid",
+            1002 => "the parameter representing a connection to the window server is invalid",
+            1003 => "the CPS process serial number or Quartz context is invalid",
+            1004 => "the requested operation is inappropriate for the parameters passed in, or the current system state",
+            1006 => "the requested operation is not implemented",
+            1007 => "a parameter passed in has a value that is inappropriate, or which does not map to a useful operation or value",
+            1008 => "a data type or token was encountered that did not match the expected type or token",
+            1010 => "the requested operation is not valid for the parameters passed in, or the current system state",
+            1011 => "the requested operation could not be completed as the indicated resources were not found",
+            -25200 => "a system error occurred in the accessibility API",
+            -25201 => "an illegal argument was passed to the accessibility API",
+            -25202 => "the accessibility element is invalid",
+            -25204 => "the accessibility request could not be completed",
+            -25205 => "the accessibility attribute is not supported",
+            -25211 => "the accessibility API is disabled for this process",
+            -25212 => "the accessibility attribute has no value",
+            -10810 => "an unknown error occurred while launching the application",
+            -10811 => "the item is not an application",
+            -10814 => "the application could not be found",
+            -10826 => "the application is not allowed to launch",
+            -10827 => "the application has no executable",
+            _ => unsafe {
+                let ptr = mach_error_string(code);
+                if ptr.is_null() {
+                    return String::from("unknown error");
+                }
+                return CStr::from_ptr(ptr).to_string_lossy().into_owned();
+            },
+        };
+        s.into()
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use std::ptr;
+
+    use winapi::um::winbase::{
+        FormatMessageW,
+        FORMAT_MESSAGE_FROM_SYSTEM,
+        FORMAT_MESSAGE_IGNORE_INSERTS,
+    };
+
+    pub fn message(code: i32) -> String {
+        let mut buf = [0u16; 512];
+        let flags = FORMAT_MESSAGE_FROM_SYSTEM | FORMAT_MESSAGE_IGNORE_INSERTS;
+        let len = unsafe {
+            FormatMessageW(
+                flags,
+                ptr::null(),
+                code as u32,
+                0,
+                buf.as_mut_ptr(),
+                buf.len() as u32,
+                ptr::null_mut(),
+            )
+        };
+        if len == 0 {
+            return String::from("unknown error");
+        }
+        String::from_utf16_lossy(&buf[..len as usize]).trim_end().into()
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod imp {
+    use std::io;
+
+    pub fn message(code: i32) -> String {
+        let err = io::Error::from_raw_os_error(code).to_string();
+        // Strip the " (os error N)" suffix added by `io::Error`
+        match err.rfind(" (os error ") {
+            Some(index) => err[..index].into(),
+            None => err,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn os_error_display() {
+        let err = Error::from(OsError::from_code(1));
+        let msg = err.to_string();
+        assert!(msg.ends_with("(os error 1)"), "{}", msg);
+        assert!(!msg.starts_with(" ("), "{}", msg);
+    }
+}
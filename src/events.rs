@@ -0,0 +1,1687 @@
+//! 📡 Observing and synthesizing input events on any operating system.
+//!
+//! [`InputEvent`](enum.InputEvent.html) describes keyboard and mouse input in
+//! terms of the portable [`Key`](../keyboard/enum.Key.html) and
+//! [`Button`](../mouse/enum.Button.html) types. Events can be observed as they
+//! happen with [`listen`](fn.listen.html), pulled from a channel with
+//! [`subscribe`](fn.subscribe.html) or an iterator with [`iter`](fn.iter.html),
+//! swallowed or replaced with [`intercept`](fn.intercept.html), and
+//! synthesized with [`post`](fn.post.html).
+//!
+//! On Linux, events are posted through the virtual keyboard and pointer
+//! protocols of Wayland compositors based on wlroots, such as Sway and
+//! Hyprland, with locations in the compositor's layout.
+//!
+//! Listening is supported on macOS, where the process must be trusted for
+//! accessibility, on Windows, through low-level keyboard and mouse hooks, and
+//! on Linux, by reading input devices, which requires membership in the
+//! `input` group.
+//!
+//! Every listener in the process shares a single tap on the operating
+//! system's event stream, which is installed when the first listener starts
+//! and removed when the last one is dropped. Each listener can filter the
+//! events it receives with [`listen_filtered`](fn.listen_filtered.html).
+
+use std::cell::Cell;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use error::Result;
+use keyboard::{self, Key, MediaKey};
+use metrics;
+use mouse::Button;
+use safety::{self, Chord};
+use trace;
+use transform;
+
+/// A global screen location in the current operating system's coordinate
+/// space, relative to the upper-left corner of the main display.
+pub type Location = (f64, f64);
+
+/// A keyboard or mouse input event.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InputEvent {
+    /// A key was pressed, or auto-repeated while held.
+    KeyDown(Key),
+    /// A key was released.
+    KeyUp(Key),
+    /// A mouse button was pressed at a location.
+    ButtonDown(Button, Location),
+    /// A mouse button was released at a location.
+    ButtonUp(Button, Location),
+    /// The mouse moved to a location.
+    MouseMoved(Location),
+    /// The scroll wheel moved by a number of lines.
+    Scroll {
+        /// The horizontal distance, where positive values scroll right.
+        dx: i32,
+        /// The vertical distance, where positive values scroll up.
+        dy: i32,
+    },
+    /// A character was typed, independently of the keyboard layout, by
+    /// pressing and releasing a key.
+    Char(char),
+    /// A media or system key was pressed and released.
+    ///
+    /// Media keys are only posted, and never observed by listeners.
+    Media(MediaKey),
+    /// A step of a two-finger touchpad gesture centered at a location.
+    ///
+    /// Gestures are observed from trackpads on macOS and from precision
+    /// touchpads on Windows, where their wheel scrolling is left out. They are
+    /// replayed as touch input on Windows, so applications see continuous
+    /// scrolling and zooming rather than wheel clicks. On macOS, pinches are
+    /// only observed and posted with the `macos-gestures` feature.
+    Gesture(Gesture, Phase, Location),
+}
+
+/// A two-finger touchpad gesture.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Gesture {
+    /// Both fingers moved by a distance in points, where positive values move
+    /// right and down.
+    Pan {
+        /// The horizontal distance.
+        dx: f64,
+        /// The vertical distance.
+        dy: f64,
+    },
+    /// The distance between the fingers changed by a factor, where values
+    /// above 1 zoom in.
+    Pinch {
+        /// The factor relative to the previous step.
+        scale: f64,
+    },
+}
+
+/// The stage of a continuous gesture that an event belongs to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Phase {
+    /// The fingers touched down.
+    Began,
+    /// The fingers moved.
+    Changed,
+    /// The fingers lifted off.
+    Ended,
+}
+
+impl Phase {
+    /// Returns the name of the phase, such as `"began"`.
+    #[inline]
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Began => "began",
+            Phase::Changed => "changed",
+            Phase::Ended => "ended",
+        }
+    }
+}
+
+impl fmt::Display for Phase {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Phase {
+    type Err = ();
+
+    fn from_str(s: &str) -> ::std::result::Result<Phase, ()> {
+        match s {
+            "began" => Ok(Phase::Began),
+            "changed" => Ok(Phase::Changed),
+            "ended" => Ok(Phase::Ended),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The change in the distance between the fingers that counts as a step of
+/// a pinch rather than jitter.
+#[cfg(any(target_os = "windows", test))]
+const PINCH_THRESHOLD: f64 = 0.02;
+
+/// Turns frames of fingers on a touchpad into two-finger gestures, where the
+/// platform reports fingers rather than gestures.
+#[cfg(any(target_os = "windows", test))]
+#[derive(Default)]
+struct TwoFingers {
+    /// The ids and locations of the fingers in the previous frame, sorted by
+    /// id.
+    last: Option<[(u32, Location); 2]>,
+    /// The distance between the fingers at the last step of a pinch.
+    spread: f64,
+}
+
+#[cfg(any(target_os = "windows", test))]
+impl TwoFingers {
+    fn spread([(_, a), (_, b)]: [(u32, Location); 2]) -> f64 {
+        (b.0 - a.0).hypot(b.1 - a.1)
+    }
+
+    /// Returns whether two fingers are touching.
+    fn is_active(&self) -> bool {
+        self.last.is_some()
+    }
+
+    /// Returns the gesture steps made by the fingers moving to `fingers`,
+    /// as pairs of ids and locations.
+    fn update(&mut self, fingers: &[(u32, Location)]) -> Vec<(Gesture, Phase)> {
+        let mut steps = Vec::new();
+        let fingers = match *fingers {
+            [a, b] if a.0 <= b.0 => Some([a, b]),
+            [a, b] => Some([b, a]),
+            _ => None,
+        };
+        let (last, fingers) = match (self.last, fingers) {
+            (Some(last), Some(fingers)) if last[0].0 == fingers[0].0 && last[1].0 == fingers[1].0 => {
+                (last, fingers)
+            },
+            (last, fingers) => {
+                // Different fingers end the gesture and may begin another
+                if last.is_some() {
+                    steps.push((Gesture::Pan { dx: 0.0, dy: 0.0 }, Phase::Ended));
+                }
+                self.last = fingers;
+                if let Some(fingers) = fingers {
+                    self.spread = TwoFingers::spread(fingers);
+                    steps.push((Gesture::Pan { dx: 0.0, dy: 0.0 }, Phase::Began));
+                }
+                return steps;
+            },
+        };
+
+        let center = |[(_, a), (_, b)]: [(u32, Location); 2]| ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+        let (from, to) = (center(last), center(fingers));
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        if dx != 0.0 || dy != 0.0 {
+            steps.push((Gesture::Pan { dx, dy }, Phase::Changed));
+        }
+        let spread = TwoFingers::spread(fingers);
+        if self.spread > 0.0 && (spread / self.spread - 1.0).abs() >= PINCH_THRESHOLD {
+            steps.push((Gesture::Pinch { scale: spread / self.spread }, Phase::Changed));
+            self.spread = spread;
+        }
+        self.last = Some(fingers);
+        steps
+    }
+}
+
+type Predicate = Box<dyn Fn(&InputEvent) -> bool + Send + Sync>;
+
+/// A registered listener callback.
+struct Consumer {
+    id: u64,
+    filter: Predicate,
+    callback: Mutex<Callback>,
+    /// Whether the consumer sees locations before the incoming transform.
+    raw: bool,
+}
+
+/// The listeners sharing the operating system's event stream.
+#[derive(Default)]
+struct Hub {
+    /// Replaced rather than modified, so that events can be delivered without
+    /// holding the lock.
+    consumers: Arc<Vec<Arc<Consumer>>>,
+    next_id: u64,
+    source: Option<imp::Listener>,
+}
+
+lazy_static! {
+    static ref HUB: Mutex<Hub> = Mutex::default();
+}
+
+thread_local! {
+    /// Whether the current thread is delivering an event to listeners.
+    static DISPATCHING: Cell<bool> = const { Cell::new(false) };
+    /// Whether the event being delivered on this thread was posted by
+    /// software rather than coming from a device.
+    static INJECTED: Cell<bool> = const { Cell::new(false) };
+}
+
+fn hub() -> MutexGuard<'static, Hub> {
+    HUB.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Hub {
+    fn add(&mut self, filter: Predicate, callback: Callback, raw: bool) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        let consumer = Consumer { id, filter, callback: Mutex::new(callback), raw };
+        let mut consumers = (*self.consumers).clone();
+        consumers.push(Arc::new(consumer));
+        self.consumers = Arc::new(consumers);
+        id
+    }
+
+    fn remove(&mut self, id: u64) {
+        let mut consumers = (*self.consumers).clone();
+        consumers.retain(|c| c.id != id);
+        self.consumers = Arc::new(consumers);
+    }
+}
+
+/// Delivers `event` from the operating system to every interested listener.
+fn dispatch(event: &InputEvent) {
+    let consumers = hub().consumers.clone();
+    if consumers.is_empty() {
+        return;
+    }
+    let transformed = transform::incoming_event(event);
+    let _dispatching = Dispatching::enter();
+    for consumer in consumers.iter() {
+        let event = if consumer.raw { event } else { &transformed };
+        if (consumer.filter)(event) {
+            let mut callback = consumer.callback.lock().unwrap_or_else(|e| e.into_inner());
+            callback(event);
+        }
+    }
+}
+
+/// Marks the current thread as delivering an event until dropped, even if a
+/// listener panics.
+struct Dispatching;
+
+impl Dispatching {
+    fn enter() -> Dispatching {
+        DISPATCHING.with(|d| d.set(true));
+        Dispatching
+    }
+}
+
+impl Drop for Dispatching {
+    fn drop(&mut self) {
+        DISPATCHING.with(|d| d.set(false));
+    }
+}
+
+/// A handle that receives input events until dropped.
+pub struct Listener {
+    id: u64,
+}
+
+impl fmt::Debug for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Listener").field("id", &self.id).finish()
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        let source = {
+            let mut hub = hub();
+            hub.remove(self.id);
+            if hub.consumers.is_empty() { hub.source.take() } else { None }
+        };
+        if let Some(source) = source {
+            // Stopping the source waits for the thread delivering events, so
+            // it can't happen on that thread
+            if DISPATCHING.with(Cell::get) {
+                thread::spawn(move || { let _source = source; });
+            }
+        }
+    }
+}
+
+/// Calls `callback` with every keyboard and mouse event until the returned
+/// listener is dropped.
+///
+/// Locations are passed through the incoming
+/// [transform](../transform/index.html), if one is set.
+///
+/// The callback runs on a background thread shared with every other listener
+/// and observes events without being able to modify them, so it should return
+/// quickly.
+#[inline]
+pub fn listen<F>(callback: F) -> Result<Listener>
+    where F: FnMut(&InputEvent) + Send + 'static
+{
+    listen_filtered(|_| true, callback)
+}
+
+/// Calls `callback` like [`listen`](fn.listen.html), but only with the events
+/// for which `filter` returns `true`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use auto::events::{self, InputEvent};
+///
+/// let clicks = events::listen_filtered(
+///     |event| matches!(event, InputEvent::ButtonDown(..)),
+///     |event| println!("{:?}", event),
+/// ).unwrap();
+/// ```
+pub fn listen_filtered<P, F>(filter: P, callback: F) -> Result<Listener>
+    where
+        P: Fn(&InputEvent) -> bool + Send + Sync + 'static,
+        F: FnMut(&InputEvent) + Send + 'static,
+{
+    add_listener(Box::new(filter), Box::new(callback), false)
+}
+
+/// Calls `callback` with every device event like
+/// [`listen`](fn.listen.html), but with locations in screen coordinates
+/// whatever the incoming transform.
+///
+/// Events posted by software, including this crate, are skipped.
+pub(crate) fn listen_devices<F>(callback: F) -> Result<Listener>
+    where F: FnMut(&InputEvent) + Send + 'static
+{
+    let filter = |_: &InputEvent| !INJECTED.with(Cell::get);
+    add_listener(Box::new(filter), Box::new(callback), true)
+}
+
+fn add_listener(filter: Predicate, callback: Callback, raw: bool) -> Result<Listener> {
+    let mut hub = hub();
+    let id = hub.add(filter, callback, raw);
+    if hub.source.is_none() {
+        match imp::listen(Box::new(dispatch)) {
+            Ok(source) => hub.source = Some(source),
+            Err(err) => {
+                hub.remove(id);
+                return Err(err);
+            },
+        }
+    }
+    Ok(Listener { id })
+}
+
+/// Returns a channel that receives every keyboard and mouse event, as
+/// observed by [`listen`](fn.listen.html).
+///
+/// Events queue up in the channel until they are received. Listening stops
+/// at the first event after the receiver is dropped.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use auto::events;
+///
+/// let events = events::subscribe().unwrap();
+/// while let Ok(event) = events.recv_timeout(Duration::from_secs(10)) {
+///     println!("{:?}", event);
+/// }
+/// ```
+pub fn subscribe() -> Result<Receiver<InputEvent>> {
+    let (sender, receiver) = mpsc::channel();
+    let slot = Arc::new(Mutex::new(None));
+    let listener = {
+        let slot = slot.clone();
+        listen(move |event| {
+            if sender.send(*event).is_err() {
+                // Dropping the listener from its own callback is deferred
+                // until the callback returns
+                slot.lock().unwrap_or_else(|e| e.into_inner()).take();
+            }
+        })?
+    };
+    *slot.lock().unwrap_or_else(|e| e.into_inner()) = Some(listener);
+    Ok(receiver)
+}
+
+/// Returns an iterator that blocks until the next keyboard or mouse event,
+/// forever.
+///
+/// Listening stops once the iterator is dropped and another event arrives,
+/// as with [`subscribe`](fn.subscribe.html).
+///
+/// # Examples
+///
+/// ```no_run
+/// use auto::events::{self, InputEvent};
+/// use auto::keyboard::Key;
+///
+/// // Wait for the user to press Escape
+/// events::iter()
+///     .unwrap()
+///     .find(|event| *event == InputEvent::KeyDown(Key::Escape));
+/// ```
+#[inline]
+pub fn iter() -> Result<mpsc::IntoIter<InputEvent>> {
+    subscribe().map(IntoIterator::into_iter)
+}
+
+/// What happens to an event after an [interceptor](fn.intercept.html) sees
+/// it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Filter {
+    /// Passes the event on unchanged.
+    Pass,
+    /// Swallows the event, so that no application receives it.
+    Consume,
+    /// Swallows the event and posts another in its place.
+    Replace(InputEvent),
+}
+
+type InterceptCallback = Box<dyn FnMut(&InputEvent) -> Filter + Send>;
+
+/// A handle that intercepts input events until dropped.
+pub struct Interceptor {
+    _source: imp::Interceptor,
+}
+
+impl fmt::Debug for Interceptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Interceptor").finish()
+    }
+}
+
+/// Calls `callback` with every keyboard and mouse event before applications
+/// receive it, and passes, swallows, or replaces the event according to the
+/// returned [`Filter`](enum.Filter.html).
+///
+/// Unlike listeners, each interceptor has its own active tap on macOS, where
+/// the process must be trusted for accessibility, or its own low-level hooks
+/// on Windows. Input stalls while the callback runs, so it should return
+/// quickly.
+///
+/// Replacements are posted like [`post`](fn.post.html), and are not seen by
+/// the interceptors of this process. Locations are passed through the
+/// incoming [transform](../transform/index.html), if one is set.
+///
+/// # Examples
+///
+/// Swap the Caps Lock and Escape keys:
+///
+/// ```no_run
+/// use auto::events::{self, Filter, InputEvent};
+/// use auto::keyboard::Key;
+///
+/// let remapper = events::intercept(|event| match *event {
+///     InputEvent::KeyDown(Key::CapsLock) => Filter::Replace(InputEvent::KeyDown(Key::Escape)),
+///     InputEvent::KeyUp(Key::CapsLock) => Filter::Replace(InputEvent::KeyUp(Key::Escape)),
+///     InputEvent::KeyDown(Key::Escape) => Filter::Replace(InputEvent::KeyDown(Key::CapsLock)),
+///     InputEvent::KeyUp(Key::Escape) => Filter::Replace(InputEvent::KeyUp(Key::CapsLock)),
+///     _ => Filter::Pass,
+/// }).unwrap();
+/// # drop(remapper);
+/// ```
+pub fn intercept<F>(mut callback: F) -> Result<Interceptor>
+    where F: FnMut(&InputEvent) -> Filter + Send + 'static
+{
+    let source = imp::intercept(Box::new(move |event| {
+        callback(&transform::incoming_event(event))
+    }))?;
+    Ok(Interceptor { _source: source })
+}
+
+/// Returns whether to keep the event that `filter` was returned for, posting
+/// its replacement if it has one.
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn apply(filter: Filter) -> bool {
+    match filter {
+        Filter::Pass => true,
+        Filter::Consume => false,
+        Filter::Replace(event) => {
+            // The event is swallowed even if its replacement is not allowed
+            let _ = post_with(&event, &[], imp::post_replacement);
+            false
+        },
+    }
+}
+
+/// Marks replacement events so that interceptors let them through.
+pub(crate) const REPLACEMENT_MARKER: usize = 0x6175_746f;
+
+/// Synthesizes `event` as if it came from a physical input device.
+///
+/// Locations are passed through the outgoing
+/// [transform](../transform/index.html), if one is set.
+///
+/// Modifier keys pressed through this function stay applied to subsequent
+/// events until they are released through it.
+///
+/// On macOS, events are posted at the process-wide default
+/// [`EventLocation`](../os/enum.EventLocation.html), which can be overridden
+/// per call with `post_at`.
+///
+/// Returns [`Error::Denied`](../error/enum.Error.html#variant.Denied) if the
+/// event would complete a chord in the installed
+/// [deny list](../safety/index.html), and blocks while the installed
+/// [rate limit](../safety/struct.RateLimit.html) is exceeded.
+pub fn post(event: &InputEvent) -> Result<()> {
+    post_allowing(event, &[])
+}
+
+/// Synthesizes `event` like [`post`](fn.post.html), but allows the chords in
+/// `allowed` even if the installed deny list contains them.
+pub fn post_allowing(event: &InputEvent, allowed: &[Chord]) -> Result<()> {
+    post_with(event, allowed, imp::post)
+}
+
+/// Synthesizes `event` like [`post`](fn.post.html), but at `location` rather
+/// than at the [default location](../os/enum.EventLocation.html#method.default_location).
+#[cfg(target_os = "macos")]
+pub fn post_at(event: &InputEvent, location: ::os::EventLocation) -> Result<()> {
+    post_with(event, &[], |event| imp::post_at(event, location))
+}
+
+/// Synthesizes an autorepeat of the held `key`, which applications treat
+/// like a key-down that was generated by holding the key.
+pub(crate) fn post_repeat(key: Key) -> Result<()> {
+    post_with(&InputEvent::KeyDown(key), &[], imp::post_repeat)
+}
+
+/// Returns where posted events last moved the mouse, on platforms whose
+/// windowing system won't say where it is.
+#[cfg(target_os = "linux")]
+pub(crate) fn posted_location() -> Option<Location> {
+    imp::posted_location()
+}
+
+/// The modifier keys that a [`ModifierGuard`](struct.ModifierGuard.html)
+/// keeps from affecting posted events.
+const MODIFIERS: [Key; 9] = [
+    Key::Shift,
+    Key::RightShift,
+    Key::Control,
+    Key::RightControl,
+    Key::Alt,
+    Key::RightAlt,
+    Key::Meta,
+    Key::RightMeta,
+    Key::Function,
+];
+
+/// The number of live [`ModifierGuard`](struct.ModifierGuard.html)s.
+static ISOLATING: AtomicUsize = AtomicUsize::new(0);
+
+/// Keeps modifier keys that the user holds from affecting posted events,
+/// until dropped.
+///
+/// Created by [`isolate_modifiers`](fn.isolate_modifiers.html).
+pub struct ModifierGuard {
+    held: Vec<Key>,
+    /// Follows the modifiers that the user lets go of, which aren't pressed
+    /// again when isolation ends.
+    #[cfg(target_os = "windows")]
+    _releases: Listener,
+}
+
+impl fmt::Debug for ModifierGuard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ModifierGuard").field("held", &self.held).finish()
+    }
+}
+
+impl Drop for ModifierGuard {
+    #[inline]
+    fn drop(&mut self) {
+        if ISOLATING.fetch_sub(1, Ordering::SeqCst) == 1 {
+            #[cfg(target_os = "windows")]
+            imp::restore_modifiers();
+        }
+    }
+}
+
+impl ModifierGuard {
+    /// Returns the modifier keys that were held when the guard was created.
+    #[inline]
+    pub fn held(&self) -> &[Key] {
+        &self.held
+    }
+}
+
+/// Keeps the modifier keys that the user holds from affecting events posted
+/// through this module, for as long as the returned guard is alive.
+///
+/// Without it, a user holding Shift while a macro plays makes everything it
+/// types uppercase, and holding Command or Control turns its keys into
+/// shortcuts. Modifiers pressed through [`post`](fn.post.html) still apply.
+///
+/// On macOS, every posted event carries exactly the modifier flags of the
+/// keys posted through this module, and Caps Lock is ignored as well. On
+/// Windows, modifiers have no flags, so modifiers that weren't pressed by
+/// this process are released before each key, button, or character event,
+/// and the ones the user still holds are pressed again once the last guard
+/// is dropped.
+///
+/// Fails with [`Error::Unsupported`](../error/enum.Error.html#variant.Unsupported)
+/// where the keyboard's state can't be read, such as on Linux. On Windows, it
+/// also fails if the listener that follows released modifiers can't be
+/// installed.
+///
+/// # Examples
+///
+/// ```no_run
+/// use auto::events::{self, InputEvent};
+/// use auto::keyboard::Key;
+///
+/// let _guard = events::isolate_modifiers().unwrap();
+/// events::post(&InputEvent::KeyDown(Key::A)).unwrap();
+/// events::post(&InputEvent::KeyUp(Key::A)).unwrap();
+/// ```
+pub fn isolate_modifiers() -> Result<ModifierGuard> {
+    let mut held = Vec::new();
+    for &key in MODIFIERS.iter().filter(|key| key.native_code().is_some()) {
+        if keyboard::is_pressed(key)? {
+            held.push(key);
+        }
+    }
+    #[cfg(target_os = "windows")]
+    let releases = listen_devices(|event| {
+        if let InputEvent::KeyUp(key) = *event {
+            imp::forget_released(key);
+        }
+    })?;
+    ISOLATING.fetch_add(1, Ordering::SeqCst);
+    Ok(ModifierGuard {
+        held,
+        #[cfg(target_os = "windows")]
+        _releases: releases,
+    })
+}
+
+/// Returns whether a [`ModifierGuard`](struct.ModifierGuard.html) is alive.
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn isolating() -> bool {
+    ISOLATING.load(Ordering::SeqCst) != 0
+}
+
+fn post_with<F>(event: &InputEvent, allowed: &[Chord], post: F) -> Result<()>
+    where F: FnOnce(&InputEvent) -> Result<()>
+{
+    let event = transform::outgoing_event(event);
+    safety::check(&event, allowed)?;
+    safety::throttle()?;
+    metrics::timed(|r, d| r.post_latency(d), || post(&event))?;
+    metrics::record(|r| r.events_posted(1));
+    trace::record_event(&event);
+    Ok(())
+}
+
+type Callback = Box<dyn FnMut(&InputEvent) + Send>;
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use std::cell::Cell;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+
+    use error::{Error, Result};
+    use keyboard::{Key, MediaKey};
+    use mouse::Button;
+    use os::{self, EventField, EventFlags, EventLocation, EventType};
+    use os::keyboard::AuxKey;
+    use os::mouse::{double_click_interval, event_location, EventKind};
+    use os::tap::{Action, EventMask, Mode, Placement, Tap, TapHandle};
+    use os::wheel::{self, ScrollPhase, ScrollUnit};
+    #[cfg(feature = "macos-gestures")]
+    use os::gesture;
+    use session;
+    use super::{apply, Callback, Gesture, InputEvent, InterceptCallback, Phase, REPLACEMENT_MARKER};
+
+    pub type Listener = TapHandle;
+
+    // Device-dependent modifier bits that tell left and right keys apart
+    const LEFT_CONTROL: u64  = 0x0001;
+    const LEFT_SHIFT: u64    = 0x0002;
+    const RIGHT_SHIFT: u64   = 0x0004;
+    const LEFT_COMMAND: u64  = 0x0008;
+    const RIGHT_COMMAND: u64 = 0x0010;
+    const LEFT_OPTION: u64   = 0x0020;
+    const RIGHT_OPTION: u64  = 0x0040;
+    const RIGHT_CONTROL: u64 = 0x2000;
+
+    fn modifier_bits(key: Key) -> Option<(u64, EventFlags)> {
+        let bits = match key {
+            Key::Shift        => (LEFT_SHIFT, EventFlags::SHIFT),
+            Key::RightShift   => (RIGHT_SHIFT, EventFlags::SHIFT),
+            Key::Control      => (LEFT_CONTROL, EventFlags::CONTROL),
+            Key::RightControl => (RIGHT_CONTROL, EventFlags::CONTROL),
+            Key::Alt          => (LEFT_OPTION, EventFlags::ALTERNATE),
+            Key::RightAlt     => (RIGHT_OPTION, EventFlags::ALTERNATE),
+            Key::Meta         => (LEFT_COMMAND, EventFlags::COMMAND),
+            Key::RightMeta    => (RIGHT_COMMAND, EventFlags::COMMAND),
+            Key::Function     => (0, EventFlags::SECONDARY_FN),
+            Key::CapsLock     => (0, EventFlags::ALPHA_SHIFT),
+            _ => return None,
+        };
+        Some(bits)
+    }
+
+    fn convert(event: &os::Event) -> Option<InputEvent> {
+        use self::EventType::*;
+
+        let field = |field| event.integer_field(field).unwrap_or(0);
+        let key = || Key::from_native_code(field(EventField::KeyboardKeycode) as u16);
+        let button = || Button::from_number(field(EventField::MouseButtonNumber) as u8);
+
+        let event = match event.event_type() {
+            KeyDown => InputEvent::KeyDown(key()),
+            KeyUp => InputEvent::KeyUp(key()),
+            FlagsChanged => {
+                let key = key();
+                let (device, flag) = modifier_bits(key)?;
+                let flags = event.flags();
+                let down = if device != 0 {
+                    flags.bits() & device != 0
+                } else {
+                    flags.contains(flag)
+                };
+                if down {
+                    InputEvent::KeyDown(key)
+                } else {
+                    InputEvent::KeyUp(key)
+                }
+            },
+            LeftMouseDown | RightMouseDown | OtherMouseDown => {
+                InputEvent::ButtonDown(button(), event_location(event))
+            },
+            LeftMouseUp | RightMouseUp | OtherMouseUp => {
+                InputEvent::ButtonUp(button(), event_location(event))
+            },
+            MouseMoved | LeftMouseDragged | RightMouseDragged | OtherMouseDragged => {
+                InputEvent::MouseMoved(event_location(event))
+            },
+            // Trackpad scrolls report the phase of the gesture, while wheel
+            // clicks and momentum scrolling do not
+            ScrollWheel if field(EventField::ScrollPhase) != 0 => {
+                let phase = phase(ScrollPhase::from_raw(field(EventField::ScrollPhase))?)?;
+                // With natural scrolling, content follows the fingers
+                let pan = Gesture::Pan {
+                    dx: field(EventField::ScrollPointDeltaAxis2) as f64,
+                    dy: field(EventField::ScrollPointDeltaAxis1) as f64,
+                };
+                InputEvent::Gesture(pan, phase, event_location(event))
+            },
+            ScrollWheel => InputEvent::Scroll {
+                // Quartz reports leftward horizontal scrolling as positive
+                dx: -field(EventField::ScrollDeltaAxis2) as i32,
+                dy: field(EventField::ScrollDeltaAxis1) as i32,
+            },
+            // Gestures have a type that `EventType` doesn't list
+            #[cfg(feature = "macos-gestures")]
+            Null => {
+                let (step, magnification) = gesture::magnification(event)?;
+                let pinch = Gesture::Pinch { scale: 1.0 + magnification };
+                InputEvent::Gesture(pinch, phase(step)?, event_location(event))
+            },
+            _ => return None,
+        };
+        Some(event)
+    }
+
+    /// Returns the phase of a gesture step, or `None` for fingers resting on
+    /// the trackpad.
+    fn phase(phase: ScrollPhase) -> Option<Phase> {
+        match phase {
+            ScrollPhase::Began => Some(Phase::Began),
+            ScrollPhase::Changed => Some(Phase::Changed),
+            ScrollPhase::Ended | ScrollPhase::Cancelled => Some(Phase::Ended),
+            ScrollPhase::MayBegin => None,
+        }
+    }
+
+    /// The events that are converted, which include pinches with the
+    /// `macos-gestures` feature.
+    fn event_mask() -> EventMask {
+        let mask = EventMask::KEYBOARD | EventMask::MOUSE;
+        #[cfg(feature = "macos-gestures")]
+        let mask = mask | EventMask::GESTURE;
+        mask
+    }
+
+    pub fn listen(mut callback: Callback) -> Result<Listener> {
+        let tap = Tap::spawn(
+            EventLocation::Session,
+            Placement::Tail,
+            Mode::ListenOnly,
+            event_mask(),
+            move |event| {
+                // Events from devices have no source process
+                let pid = event.integer_field(EventField::SourceUnixProcessId);
+                if let Some(event) = convert(event) {
+                    super::INJECTED.with(|i| i.set(pid.unwrap_or(0) != 0));
+                    callback(&event);
+                }
+                Action::Keep
+            },
+        );
+        tap.ok_or(Error::PermissionDenied)
+    }
+
+    pub type Interceptor = TapHandle;
+
+    pub fn intercept(mut callback: InterceptCallback) -> Result<Interceptor> {
+        let tap = Tap::spawn(
+            EventLocation::Session,
+            Placement::Head,
+            Mode::Filter,
+            event_mask(),
+            move |event| {
+                let marker = event.integer_field(EventField::SourceUserData);
+                let event = match convert(event) {
+                    Some(ref event) if marker != Some(REPLACEMENT_MARKER as i64) => *event,
+                    _ => return Action::Keep,
+                };
+                if apply(callback(&event)) { Action::Keep } else { Action::Discard }
+            },
+        );
+        tap.ok_or(Error::PermissionDenied)
+    }
+
+    thread_local! {
+        /// Whether events posted on this thread replace intercepted ones.
+        static REPLACING: Cell<bool> = const { Cell::new(false) };
+    }
+
+    pub fn post_replacement(event: &InputEvent) -> Result<()> {
+        REPLACING.with(|r| r.set(true));
+        let result = post(event);
+        REPLACING.with(|r| r.set(false));
+        result
+    }
+
+    /// How far in points the mouse may move between presses that count
+    /// toward the same multiple click.
+    const CLICK_SLOP: f64 = 4.0;
+
+    /// Input state that Quartz does not track for synthesized events.
+    struct PostState {
+        flags: EventFlags,
+        pressed: Option<os::mouse::Button>,
+        last_press: Option<Press>,
+    }
+
+    /// A button press, which may be part of a multiple click.
+    #[derive(Copy, Clone)]
+    struct Press {
+        button: os::mouse::Button,
+        location: (f64, f64),
+        time: Instant,
+        count: i64,
+    }
+
+    impl PostState {
+        /// Returns the click count for a press, continuing the previous
+        /// press's count if it was recent and nearby, as the system does for
+        /// physical clicks.
+        fn press(&mut self, button: os::mouse::Button, location: (f64, f64)) -> i64 {
+            let now = Instant::now();
+            let interval = Duration::from_secs_f64(double_click_interval().unwrap_or(0.5).max(0.0));
+            let count = match self.last_press {
+                Some(last) if last.button == button
+                    && now.duration_since(last.time) <= interval
+                    && (last.location.0 - location.0).abs() <= CLICK_SLOP
+                    && (last.location.1 - location.1).abs() <= CLICK_SLOP => last.count + 1,
+                _ => 1,
+            };
+            self.last_press = Some(Press { button, location, time: now, count });
+            count
+        }
+
+        /// Returns the click count of the press that `button` is released
+        /// from.
+        fn release(&self, button: os::mouse::Button) -> i64 {
+            match self.last_press {
+                Some(last) if last.button == button => last.count,
+                _ => 1,
+            }
+        }
+    }
+
+    lazy_static! {
+        static ref STATE: Mutex<PostState> = Mutex::new(PostState {
+            flags: EventFlags::empty(),
+            pressed: None,
+            last_press: None,
+        });
+    }
+
+    fn os_button(button: Button) -> Result<os::mouse::Button> {
+        Ok(button.into())
+    }
+
+    fn scroll_phase(phase: Phase) -> ScrollPhase {
+        match phase {
+            Phase::Began => ScrollPhase::Began,
+            Phase::Changed => ScrollPhase::Changed,
+            Phase::Ended => ScrollPhase::Ended,
+        }
+    }
+
+    /// The flags that modifier keys and Caps Lock set.
+    fn modifier_flags() -> EventFlags {
+        EventFlags::ALPHA_SHIFT
+            | EventFlags::SHIFT
+            | EventFlags::CONTROL
+            | EventFlags::ALTERNATE
+            | EventFlags::COMMAND
+            | EventFlags::SECONDARY_FN
+    }
+
+    /// Applies the modifiers held through `post` to `event`, replacing those
+    /// it picked up from the keyboard while modifiers are isolated.
+    fn apply_flags(event: &mut os::Event, flags: EventFlags) {
+        if super::isolating() {
+            // Truncating also drops the device-dependent modifier bits
+            let own = EventFlags::from_bits_truncate(event.flags().bits()) - modifier_flags();
+            event.set_flags(own | flags);
+        } else {
+            event.enable_flags(flags);
+        }
+    }
+
+    pub fn post(event: &InputEvent) -> Result<()> {
+        post_at(event, EventLocation::default_location())
+    }
+
+    /// A virtual key that no keyboard has, which typed characters are posted
+    /// with so that applications don't take them for that key.
+    const NO_KEY: u16 = 0x44;
+
+    /// The virtual key of the Mission Control key on Apple keyboards.
+    const MISSION_CONTROL: u16 = 0xA0;
+
+    /// Applies the held modifiers and the source tag to `event`.
+    fn prepare(event: &mut os::Event, state: &PostState) {
+        apply_flags(event, state.flags);
+        tag(event);
+    }
+
+    /// Marks `event` as a replacement or with the source tag.
+    fn tag(event: &mut os::Event) {
+        if REPLACING.with(Cell::get) {
+            event.set_integer_field(EventField::SourceUserData, REPLACEMENT_MARKER as i64);
+        } else if let Some(tag) = session::current_source_tag() {
+            event.set_integer_field(EventField::SourceUserData, tag as i64);
+        }
+    }
+
+    /// Returns the events that press and release `key`, which are
+    /// system-defined events for all but Mission Control.
+    fn media_events(key: MediaKey) -> Result<[os::Event; 2]> {
+        let aux = match key {
+            MediaKey::PlayPause => AuxKey::Play,
+            MediaKey::Next => AuxKey::Next,
+            MediaKey::Previous => AuxKey::Previous,
+            MediaKey::VolumeUp => AuxKey::SoundUp,
+            MediaKey::VolumeDown => AuxKey::SoundDown,
+            MediaKey::Mute => AuxKey::Mute,
+            MediaKey::BrightnessUp => AuxKey::BrightnessUp,
+            MediaKey::BrightnessDown => AuxKey::BrightnessDown,
+            MediaKey::MissionControl => {
+                let down = os::keyboard::Event::new(MISSION_CONTROL, true)?;
+                let up = os::keyboard::Event::new(MISSION_CONTROL, false)?;
+                return Ok([down.into(), up.into()]);
+            },
+        };
+        Ok([os::keyboard::aux_key_event(aux, true)?, os::keyboard::aux_key_event(aux, false)?])
+    }
+
+    pub fn post_at(event: &InputEvent, location: EventLocation) -> Result<()> {
+        let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+        let mut event: os::Event = match *event {
+            InputEvent::KeyDown(key) | InputEvent::KeyUp(key) => {
+                let down = matches!(*event, InputEvent::KeyDown(_));
+                let code = key.native_code().ok_or(Error::Unsupported)?;
+                if let Some((_, flag)) = modifier_bits(key) {
+                    state.flags.set(flag, down);
+                }
+                os::keyboard::Event::new(code, down)?.into()
+            },
+            InputEvent::ButtonDown(button, location) => {
+                let button = os_button(button)?;
+                state.pressed = Some(button);
+                let count = state.press(button, location);
+                let mut event = os::mouse::Event::new(button, EventKind::Down, location)?;
+                event.set_click_count(count);
+                event.into()
+            },
+            InputEvent::ButtonUp(button, location) => {
+                let button = os_button(button)?;
+                state.pressed = None;
+                let count = state.release(button);
+                let mut event = os::mouse::Event::new(button, EventKind::Up, location)?;
+                event.set_click_count(count);
+                event.into()
+            },
+            InputEvent::MouseMoved(location) => {
+                // Applications expect drags rather than moves while a button
+                // is held
+                let (button, kind) = match state.pressed {
+                    Some(button) => (button, EventKind::Dragged),
+                    None => (os::mouse::Button::Left, EventKind::Moved),
+                };
+                os::mouse::Event::new(button, kind, location)?.into()
+            },
+            InputEvent::Scroll { dx, dy } => {
+                wheel::Event::new(ScrollUnit::Line, [dy, -dx])?.into()
+            },
+            // System-defined events keep their state in their flags
+            InputEvent::Media(key) => {
+                for mut event in media_events(key)? {
+                    tag(&mut event);
+                    event.post(location);
+                }
+                return Ok(());
+            },
+            InputEvent::Char(c) => {
+                let mut buf = [0; 4];
+                let string = c.encode_utf8(&mut buf);
+                let mut down = os::keyboard::Event::new(NO_KEY, true)?;
+                down.set_unicode_string(string);
+                let mut down = down.into();
+                prepare(&mut down, &state);
+                down.post(location);
+                let mut up = os::keyboard::Event::new(NO_KEY, false)?;
+                up.set_unicode_string(string);
+                up.into()
+            },
+            InputEvent::Gesture(Gesture::Pan { dx, dy }, phase, _) => {
+                let wheels = [dy.round() as i32, dx.round() as i32];
+                let mut event = wheel::Event::new(ScrollUnit::Pixel, wheels)?;
+                event.set_continuous(true);
+                event.set_scroll_phase(Some(scroll_phase(phase)));
+                event.into()
+            },
+            #[cfg(feature = "macos-gestures")]
+            InputEvent::Gesture(Gesture::Pinch { scale }, phase, _) => {
+                gesture::Event::magnify(scroll_phase(phase), scale - 1.0)?.into()
+            },
+            #[cfg(not(feature = "macos-gestures"))]
+            InputEvent::Gesture(Gesture::Pinch { .. }, ..) => return Err(Error::Unsupported),
+        };
+        prepare(&mut event, &state);
+        event.post(location);
+        Ok(())
+    }
+
+    pub fn post_repeat(event: &InputEvent) -> Result<()> {
+        let key = match *event {
+            InputEvent::KeyDown(key) => key,
+            _ => return post(event),
+        };
+        let state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+        let code = key.native_code().ok_or(Error::Unsupported)?;
+        let mut event: os::Event = os::keyboard::Event::new(code, true)?.into();
+        event.set_integer_field(EventField::KeyboardAutorepeat, 1);
+        apply_flags(&mut event, state.flags);
+        if let Some(tag) = session::current_source_tag() {
+            event.set_integer_field(EventField::SourceUserData, tag as i64);
+        }
+        event.post(EventLocation::default_location());
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use std::mem;
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use winapi::um::winuser;
+
+    use error::{Error, OsError, Result};
+    use keyboard::{Key, MediaKey};
+    use mouse::Button;
+    use os;
+    use os::hook::{Action, Event, Hook, HookHandle, HookMask, MouseEvent};
+    use os::touch::{self, Stage};
+    use os::touchpad::{Touchpad, TouchpadHandle};
+    use os::wheel::ScrollUnit;
+    use session;
+    use super::{apply, Callback, Gesture, InputEvent, InterceptCallback, Location, Phase};
+    use super::{TwoFingers, REPLACEMENT_MARKER};
+
+    /// Wheel movement in partial notches from high-resolution wheels, which
+    /// is carried over to later wheel events.
+    #[derive(Default)]
+    struct Wheel {
+        x: i32,
+        y: i32,
+    }
+
+    impl Wheel {
+        /// Adds `delta` to the vertical or horizontal wheel, returning the
+        /// whole notches to scroll by.
+        fn scroll(&mut self, vertical: bool, delta: i16) -> (i32, i32) {
+            let notch = winuser::WHEEL_DELTA as i32;
+            if vertical {
+                self.y += i32::from(delta);
+            } else {
+                self.x += i32::from(delta);
+            }
+            let notches = (self.x / notch, self.y / notch);
+            self.x %= notch;
+            self.y %= notch;
+            notches
+        }
+    }
+
+    fn convert_mouse(event: &MouseEvent, wheel: &mut Wheel) -> Option<InputEvent> {
+        let (x, y) = event.location();
+        let location = (x as f64, y as f64);
+        let event = match event.message() {
+            winuser::WM_MOUSEMOVE => InputEvent::MouseMoved(location),
+            winuser::WM_LBUTTONDOWN => InputEvent::ButtonDown(Button::Left, location),
+            winuser::WM_LBUTTONUP => InputEvent::ButtonUp(Button::Left, location),
+            winuser::WM_RBUTTONDOWN => InputEvent::ButtonDown(Button::Right, location),
+            winuser::WM_RBUTTONUP => InputEvent::ButtonUp(Button::Right, location),
+            winuser::WM_MBUTTONDOWN => InputEvent::ButtonDown(Button::Middle, location),
+            winuser::WM_MBUTTONUP => InputEvent::ButtonUp(Button::Middle, location),
+            winuser::WM_XBUTTONDOWN => InputEvent::ButtonDown(event.x_button()?.into(), location),
+            winuser::WM_XBUTTONUP => InputEvent::ButtonUp(event.x_button()?.into(), location),
+            message @ winuser::WM_MOUSEWHEEL | message @ winuser::WM_MOUSEHWHEEL => {
+                let vertical = message == winuser::WM_MOUSEWHEEL;
+                match wheel.scroll(vertical, event.wheel_delta()) {
+                    (0, 0) => return None,
+                    (dx, dy) => InputEvent::Scroll { dx, dy },
+                }
+            },
+            _ => return None,
+        };
+        Some(event)
+    }
+
+    fn convert(event: &Event, wheel: &mut Wheel) -> Option<InputEvent> {
+        match *event {
+            Event::Keyboard(ref event) => {
+                let key = Key::from_native_code(event.key());
+                Some(if event.is_down() { InputEvent::KeyDown(key) } else { InputEvent::KeyUp(key) })
+            },
+            Event::Mouse(ref event) => convert_mouse(event, wheel),
+        }
+    }
+
+    /// Hooks that observe the keyboard and mouse, and a reader of the
+    /// fingers on touchpads that observes their gestures.
+    pub struct Listener {
+        _hook: HookHandle,
+        _touchpad: TouchpadHandle,
+    }
+
+    pub fn listen(callback: Callback) -> Result<Listener> {
+        let callback = Arc::new(Mutex::new(callback));
+        // Touchpads scroll with the wheel as well, which would repeat their
+        // gestures
+        let gesturing = Arc::new(AtomicBool::new(false));
+
+        let (callback_, gesturing_) = (callback.clone(), gesturing.clone());
+        let mut wheel = Wheel::default();
+        let hook = Hook::spawn(HookMask::KEYBOARD | HookMask::MOUSE, move |event| {
+            let injected = match *event {
+                Event::Keyboard(ref event) => event.is_injected(),
+                Event::Mouse(ref event) => event.is_injected(),
+            };
+            match convert(event, &mut wheel) {
+                Some(InputEvent::Scroll { .. }) if !injected && gesturing_.load(Ordering::SeqCst) => {},
+                Some(event) => {
+                    super::INJECTED.with(|i| i.set(injected));
+                    let mut callback = callback_.lock().unwrap_or_else(|e| e.into_inner());
+                    callback(&event);
+                },
+                None => {},
+            }
+            Action::Keep
+        })?;
+
+        let mut fingers = TwoFingers::default();
+        let touchpad = Touchpad::spawn(move |contacts| {
+            let contacts: Vec<_> = contacts.iter().map(|contact| (contact.id, contact.location)).collect();
+            let steps = fingers.update(&contacts);
+            gesturing.store(fingers.is_active(), Ordering::SeqCst);
+            if steps.is_empty() {
+                return;
+            }
+            // Gestures act on whatever is under the cursor
+            let location = match os::mouse::location() {
+                Some((x, y)) => (x as f64, y as f64),
+                None => return,
+            };
+            super::INJECTED.with(|i| i.set(false));
+            let mut callback = callback.lock().unwrap_or_else(|e| e.into_inner());
+            for (gesture, phase) in steps {
+                callback(&InputEvent::Gesture(gesture, phase, location));
+            }
+        })?;
+        Ok(Listener { _hook: hook, _touchpad: touchpad })
+    }
+
+    pub type Interceptor = HookHandle;
+
+    pub fn intercept(mut callback: InterceptCallback) -> Result<Interceptor> {
+        let mut wheel = Wheel::default();
+        Hook::spawn(HookMask::KEYBOARD | HookMask::MOUSE, move |event| {
+            let marker = match *event {
+                Event::Keyboard(ref event) => event.extra_info(),
+                Event::Mouse(ref event) => event.extra_info(),
+            };
+            let event = match convert(event, &mut wheel) {
+                Some(event) if marker != REPLACEMENT_MARKER => event,
+                _ => return Action::Keep,
+            };
+            if apply(callback(&event)) { Action::Keep } else { Action::Discard }
+        })
+    }
+
+    pub fn post_replacement(event: &InputEvent) -> Result<()> {
+        os::with_extra_info(REPLACEMENT_MARKER, || post_untagged(event))
+    }
+
+    lazy_static! {
+        /// The modifiers released while isolating that the user hasn't let go
+        /// of since.
+        static ref RELEASED: Mutex<Vec<u16>> = Mutex::new(Vec::new());
+    }
+
+    /// Releases the modifiers that are down but weren't pressed by this
+    /// process, which the user must be holding, while modifiers are isolated.
+    fn release_foreign_modifiers() -> Result<()> {
+        if !super::isolating() {
+            return Ok(());
+        }
+        let codes: Vec<u16> = super::MODIFIERS.iter()
+            .filter_map(|key| key.native_code())
+            .filter(|&code| !os::keyboard::is_sent_down(code) && os::keyboard::is_pressed(code))
+            .collect();
+        if codes.is_empty() {
+            return Ok(());
+        }
+        let releases: Vec<os::keyboard::Event> = codes.iter().map(|&code| os::keyboard::Event::new(code, false)).collect();
+        os::keyboard::send(&releases)?;
+        let mut released = RELEASED.lock().unwrap_or_else(|e| e.into_inner());
+        released.retain(|code| !codes.contains(code));
+        released.extend(codes);
+        Ok(())
+    }
+
+    /// Forgets a released modifier once the user lets go of it.
+    pub fn forget_released(key: Key) {
+        if let Some(code) = key.native_code() {
+            RELEASED.lock().unwrap_or_else(|e| e.into_inner()).retain(|&c| c != code);
+        }
+    }
+
+    /// Presses the modifiers that were released while isolating and that
+    /// the user still holds.
+    pub fn restore_modifiers() {
+        let released = mem::take(&mut *RELEASED.lock().unwrap_or_else(|e| e.into_inner()));
+        let presses: Vec<os::keyboard::Event> = released.into_iter()
+            .filter(|&code| !os::keyboard::is_pressed(code))
+            .map(|code| os::keyboard::Event::new(code, true))
+            .collect();
+        if !presses.is_empty() {
+            let _ = os::keyboard::send(&presses);
+        }
+    }
+
+    pub fn post(event: &InputEvent) -> Result<()> {
+        match session::current_source_tag() {
+            Some(tag) => os::with_extra_info(tag, || post_untagged(event)),
+            None => post_untagged(event),
+        }
+    }
+
+    fn post_untagged(event: &InputEvent) -> Result<()> {
+        match *event {
+            InputEvent::KeyDown(key) | InputEvent::KeyUp(key) => {
+                let down = matches!(*event, InputEvent::KeyDown(_));
+                let code = key.native_code().ok_or(Error::Unsupported)?;
+                release_foreign_modifiers()?;
+                os::keyboard::Event::new(code, down).send()
+            },
+            InputEvent::ButtonDown(button, location) | InputEvent::ButtonUp(button, location) => {
+                let down = matches!(*event, InputEvent::ButtonDown(..));
+                let button = os_button(button)?;
+                move_to(location)?;
+                release_foreign_modifiers()?;
+                os::mouse::send_button(button, down)
+            },
+            InputEvent::MouseMoved(location) => move_to(location),
+            InputEvent::Gesture(gesture, phase, location) => post_gesture(gesture, phase, location),
+            InputEvent::Scroll { dx, dy } => os::wheel::scroll(ScrollUnit::Line, dy, dx),
+            InputEvent::Char(c) => {
+                release_foreign_modifiers()?;
+                let mut buf = [0; 2];
+                let events: Vec<os::keyboard::Event> = c.encode_utf16(&mut buf)
+                    .iter()
+                    .flat_map(|&unit| [true, false].map(|down| os::keyboard::Event::from_unicode(unit, down)))
+                    .collect();
+                os::keyboard::send(&events)
+            },
+            InputEvent::Media(key) => {
+                let vk = media_key(key)? as u16;
+                os::keyboard::send(&[os::keyboard::Event::new(vk, true), os::keyboard::Event::new(vk, false)])
+            },
+        }
+    }
+
+    /// Returns the virtual key of `key`, which must be a media key rather
+    /// than a system key.
+    fn media_key(key: MediaKey) -> Result<i32> {
+        match key {
+            MediaKey::PlayPause => Ok(winuser::VK_MEDIA_PLAY_PAUSE),
+            MediaKey::Next => Ok(winuser::VK_MEDIA_NEXT_TRACK),
+            MediaKey::Previous => Ok(winuser::VK_MEDIA_PREV_TRACK),
+            MediaKey::VolumeUp => Ok(winuser::VK_VOLUME_UP),
+            MediaKey::VolumeDown => Ok(winuser::VK_VOLUME_DOWN),
+            MediaKey::Mute => Ok(winuser::VK_VOLUME_MUTE),
+            MediaKey::BrightnessUp | MediaKey::BrightnessDown | MediaKey::MissionControl => {
+                Err(Error::Unsupported)
+            },
+        }
+    }
+
+    /// Windows marks a key-down as a repeat when the key is already down.
+    pub fn post_repeat(event: &InputEvent) -> Result<()> {
+        post(event)
+    }
+
+    /// The distance in pixels between the fingers when a gesture begins.
+    const INITIAL_SPREAD: f64 = 100.0;
+
+    /// The smallest distance in pixels kept between the fingers.
+    const MIN_SPREAD: f64 = 8.0;
+
+    /// Two fingers placed side by side.
+    #[derive(Copy, Clone)]
+    struct Fingers {
+        center: Location,
+        spread: f64,
+    }
+
+    impl Fingers {
+        fn points(&self) -> [(i32, i32); 2] {
+            let (x, y) = self.center;
+            let half = self.spread / 2.0;
+            [
+                ((x - half).round() as i32, y.round() as i32),
+                ((x + half).round() as i32, y.round() as i32),
+            ]
+        }
+    }
+
+    lazy_static! {
+        static ref FINGERS: Mutex<Option<Fingers>> = Mutex::new(None);
+    }
+
+    fn post_gesture(gesture: Gesture, phase: Phase, location: Location) -> Result<()> {
+        let mut state = FINGERS.lock().unwrap_or_else(|e| e.into_inner());
+
+        // A gesture that was not seen beginning starts where it is first seen
+        let mut fingers = match *state {
+            Some(fingers) if phase != Phase::Began => fingers,
+            _ => {
+                let fingers = Fingers { center: location, spread: INITIAL_SPREAD };
+                touch::inject(&fingers.points(), Stage::Down)?;
+                *state = Some(fingers);
+                fingers
+            },
+        };
+
+        match gesture {
+            Gesture::Pan { dx, dy } => {
+                fingers.center = (fingers.center.0 + dx, fingers.center.1 + dy);
+            },
+            Gesture::Pinch { scale } => {
+                fingers.spread = (fingers.spread * scale).max(MIN_SPREAD);
+            },
+        }
+        if fingers.points() != state.map(|f| f.points()).unwrap_or_default() {
+            touch::inject(&fingers.points(), Stage::Update)?;
+        }
+        *state = Some(fingers);
+
+        if phase == Phase::Ended {
+            *state = None;
+            touch::inject(&fingers.points(), Stage::Up)?;
+        }
+        Ok(())
+    }
+
+    fn move_to((x, y): Location) -> Result<()> {
+        if os::mouse::set_location((x.round() as i32, y.round() as i32)) {
+            Ok(())
+        } else {
+            Err(OsError::last().into())
+        }
+    }
+
+    fn os_button(button: Button) -> Result<os::mouse::Button> {
+        ::std::convert::TryFrom::try_from(button)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::sync::Mutex;
+
+    use error::{Error, Result};
+    use keyboard::Key;
+    use mouse::Button;
+    use os;
+    use os::virtual_input::VirtualInput;
+    use super::{Callback, InputEvent, InterceptCallback, Location};
+
+    pub type Listener = os::Listener;
+
+    lazy_static! {
+        /// The virtual devices that events are posted through, which the
+        /// compositor keeps for as long as the connection is open.
+        static ref VIRTUAL_INPUT: Mutex<Option<VirtualInput>> = Mutex::new(None);
+    }
+
+    pub fn listen(mut callback: Callback) -> Result<Listener> {
+        os::listen(move |event, device| {
+            super::INJECTED.with(|i| i.set(device.is_virtual()));
+            callback(event);
+        })
+    }
+
+    fn key_code(key: Key) -> Result<u16> {
+        key.native_code().ok_or(Error::Unsupported)
+    }
+
+    fn button_code(button: Button) -> Result<u16> {
+        button.evdev_code().ok_or(Error::Unsupported)
+    }
+
+    pub fn post(event: &InputEvent) -> Result<()> {
+        let mut input = VIRTUAL_INPUT.lock().unwrap_or_else(|e| e.into_inner());
+        if input.is_none() {
+            *input = Some(VirtualInput::connect()?);
+        }
+        let result = {
+            let input = input.as_mut().unwrap();
+            match *event {
+                InputEvent::KeyDown(key) => key_code(key).and_then(|key| input.key(key, true)),
+                InputEvent::KeyUp(key) => key_code(key).and_then(|key| input.key(key, false)),
+                InputEvent::ButtonDown(button, location) | InputEvent::ButtonUp(button, location) => {
+                    let down = matches!(*event, InputEvent::ButtonDown(..));
+                    button_code(button).and_then(|button| {
+                        input.move_to(location)?;
+                        input.button(button, down)
+                    })
+                },
+                InputEvent::MouseMoved(location) => input.move_to(location),
+                InputEvent::Scroll { dx, dy } => input.scroll(dx, dy),
+                InputEvent::Char(c) => input.type_char(c),
+                InputEvent::Media(_) | InputEvent::Gesture(..) => Err(Error::Unsupported),
+            }
+        };
+        // The compositor may have closed the connection, so the next event
+        // reconnects
+        if let Err(Error::Os(_)) = result {
+            *input = None;
+        }
+        result
+    }
+
+    pub fn post_repeat(event: &InputEvent) -> Result<()> {
+        post(event)
+    }
+
+    /// Returns where the virtual pointer was last moved to, which is all that
+    /// Wayland clients can learn of the pointer.
+    pub fn posted_location() -> Option<Location> {
+        VIRTUAL_INPUT.lock().unwrap_or_else(|e| e.into_inner()).as_ref().and_then(VirtualInput::location)
+    }
+
+    /// Posts events through `input` rather than through the compositor.
+    #[cfg(test)]
+    pub fn install(input: VirtualInput) {
+        *VIRTUAL_INPUT.lock().unwrap_or_else(|e| e.into_inner()) = Some(input);
+    }
+
+    pub struct Interceptor;
+
+    pub fn intercept(_: InterceptCallback) -> Result<Interceptor> {
+        Err(Error::Unsupported)
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+mod imp {
+    use error::{Error, Result};
+    use super::{Callback, InputEvent, InterceptCallback};
+
+    pub struct Listener;
+
+    pub fn listen(_: Callback) -> Result<Listener> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn post(_: &InputEvent) -> Result<()> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn post_repeat(_: &InputEvent) -> Result<()> {
+        Err(Error::Unsupported)
+    }
+
+    pub struct Interceptor;
+
+    pub fn intercept(_: InterceptCallback) -> Result<Interceptor> {
+        Err(Error::Unsupported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dispatch_filters() {
+        let keys = Arc::new(Mutex::new(Vec::new()));
+        let all = Arc::new(Mutex::new(0));
+
+        let (k, a) = (keys.clone(), all.clone());
+        let ids = {
+            let mut hub = hub();
+            [
+                hub.add(
+                    Box::new(|e| matches!(e, InputEvent::KeyDown(_))),
+                    Box::new(move |e| k.lock().unwrap().push(*e)),
+                    false,
+                ),
+                hub.add(Box::new(|_| true), Box::new(move |_| *a.lock().unwrap() += 1), false),
+            ]
+        };
+
+        dispatch(&InputEvent::KeyDown(Key::A));
+        dispatch(&InputEvent::MouseMoved((1.0, 2.0)));
+        for &id in &ids {
+            hub().remove(id);
+        }
+        dispatch(&InputEvent::KeyDown(Key::B));
+
+        assert_eq!(*keys.lock().unwrap(), [InputEvent::KeyDown(Key::A)]);
+        assert_eq!(*all.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn dispatching_after_panic() {
+        let result = ::std::panic::catch_unwind(|| {
+            let _dispatching = Dispatching::enter();
+            assert!(DISPATCHING.with(Cell::get));
+            panic!("listener panicked");
+        });
+        assert!(result.is_err());
+        assert!(!DISPATCHING.with(Cell::get));
+    }
+
+    #[test]
+    fn two_finger_gestures() {
+        let still = Gesture::Pan { dx: 0.0, dy: 0.0 };
+        let mut fingers = TwoFingers::default();
+        assert_eq!(fingers.update(&[(1, (0.0, 0.0))]), []);
+        assert_eq!(fingers.update(&[(2, (100.0, 0.0)), (1, (0.0, 0.0))]), [(still, Phase::Began)]);
+        assert!(fingers.is_active());
+
+        // Both fingers moving down pan without pinching
+        assert_eq!(
+            fingers.update(&[(1, (0.0, 10.0)), (2, (100.0, 10.0))]),
+            [(Gesture::Pan { dx: 0.0, dy: 10.0 }, Phase::Changed)],
+        );
+        // Jitter in the spread is not a pinch
+        assert_eq!(
+            fingers.update(&[(1, (0.0, 10.0)), (2, (101.0, 10.0))]),
+            [(Gesture::Pan { dx: 0.5, dy: 0.0 }, Phase::Changed)],
+        );
+        // Spreading apart around the same center pinches out
+        assert_eq!(
+            fingers.update(&[(1, (-49.5, 10.0)), (2, (150.5, 10.0))]),
+            [(Gesture::Pinch { scale: 2.0 }, Phase::Changed)],
+        );
+
+        assert_eq!(fingers.update(&[(1, (0.0, 0.0))]), [(still, Phase::Ended)]);
+        assert!(!fingers.is_active());
+        assert_eq!(fingers.update(&[]), []);
+
+        // A finger replaced mid-gesture ends it and begins another
+        fingers.update(&[(1, (0.0, 0.0)), (2, (10.0, 0.0))]);
+        assert_eq!(
+            fingers.update(&[(1, (0.0, 0.0)), (3, (10.0, 0.0))]),
+            [(still, Phase::Ended), (still, Phase::Began)],
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn click_at_posted_location() {
+        use std::io::Read;
+        use std::os::unix::net::UnixStream;
+        use mouse;
+        use os::screen::Bounds;
+        use os::virtual_input::VirtualInput;
+
+        // The opcode and arguments of the next request to the virtual pointer
+        fn request(stream: &mut UnixStream) -> (u16, Vec<u32>) {
+            let word = |bytes: &[u8]| u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            let mut header = [0; 8];
+            stream.read_exact(&mut header).unwrap();
+            let header = word(&header[4..]);
+            let mut args = vec![0; (header >> 16) as usize - 8];
+            stream.read_exact(&mut args).unwrap();
+            (header as u16, args.chunks(4).map(word).skip(1).collect())
+        }
+
+        let (client, mut server) = UnixStream::pair().unwrap();
+        let layout = Bounds { origin: (0.0, 0.0), size: (300.0, 200.0) };
+        imp::install(VirtualInput::with_pointer(client, layout));
+
+        mouse::move_to((50.0, 20.0)).unwrap();
+        assert_eq!(mouse::location(), Ok((50.0, 20.0)));
+        assert_eq!(request(&mut server), (1, vec![50, 20, 300, 200]));
+        assert_eq!(request(&mut server).0, 4);
+
+        mouse::click(Button::Left).unwrap();
+        for &down in &[1, 0] {
+            assert_eq!(request(&mut server), (1, vec![50, 20, 300, 200]));
+            assert_eq!(request(&mut server).0, 4);
+            assert_eq!(request(&mut server), (2, vec![0x110, down]));
+            assert_eq!(request(&mut server).0, 4);
+        }
+    }
+}
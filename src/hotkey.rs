@@ -0,0 +1,390 @@
+//! 🔥 System-wide hotkeys.
+//!
+//! A [`Hotkey`](struct.Hotkey.html) calls back into the program whenever its
+//! key chord is pressed, regardless of which application is frontmost. The
+//! chord is consumed by the system and not delivered to other applications.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use auto::hotkey::Hotkey;
+//! use auto::keyboard::{Key, Modifiers};
+//!
+//! let modifiers = Modifiers::CONTROL | Modifiers::ALT;
+//! let hotkey = Hotkey::register(modifiers, Key::R, || {
+//!     println!("starting automation");
+//! }).unwrap();
+//! # drop(hotkey);
+//! ```
+
+use std::fmt;
+#[cfg(feature = "tokio")]
+use std::pin::Pin;
+#[cfg(feature = "tokio")]
+use std::task::{Context, Poll};
+
+#[cfg(feature = "tokio")]
+use futures_core::Stream;
+#[cfg(feature = "tokio")]
+use tokio::sync::mpsc;
+
+use error::Result;
+#[cfg(any(target_os = "macos", target_os = "linux", test))]
+use events::InputEvent;
+use keyboard::{Key, Modifiers};
+
+type Callback = Box<dyn FnMut() + Send>;
+
+/// A registered system-wide hotkey that stays active until dropped.
+pub struct Hotkey {
+    modifiers: Modifiers,
+    key: Key,
+    _registration: imp::Registration,
+}
+
+impl fmt::Debug for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Hotkey")
+            .field("modifiers", &self.modifiers)
+            .field("key", &self.key)
+            .finish()
+    }
+}
+
+impl Hotkey {
+    /// Registers `callback` to be called on a background thread whenever
+    /// `key` is pressed while holding exactly `modifiers`.
+    ///
+    /// Fails if `key` does not exist on the current operating system or, on
+    /// Windows, if the chord is already registered by this or another
+    /// application.
+    ///
+    /// On macOS, the chord is intercepted with an event tap, which requires
+    /// the process to be trusted for accessibility and fails with
+    /// [`Error::PermissionDenied`](../error/enum.Error.html#variant.PermissionDenied)
+    /// otherwise.
+    ///
+    /// On Linux, hotkeys are detected by reading input devices, which
+    /// requires membership in the `input` group, and the chord still reaches
+    /// the focused application.
+    pub fn register<F>(modifiers: Modifiers, key: Key, callback: F) -> Result<Hotkey>
+        where F: FnMut() + Send + 'static
+    {
+        let registration = imp::register(modifiers, key, Box::new(callback))?;
+        Ok(Hotkey { modifiers, key, _registration: registration })
+    }
+
+    /// Returns the modifiers of the hotkey.
+    #[inline]
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+    /// Returns the key of the hotkey.
+    #[inline]
+    pub fn key(&self) -> Key {
+        self.key
+    }
+
+    /// Registers a hotkey like [`register`](#method.register), but delivers
+    /// its presses as a stream rather than to a callback.
+    ///
+    /// The hotkey stays registered until the stream is dropped.
+    #[cfg(feature = "tokio")]
+    pub fn stream(modifiers: Modifiers, key: Key) -> Result<HotkeyStream> {
+        let (sender, presses) = mpsc::unbounded_channel();
+        let hotkey = Hotkey::register(modifiers, key, move || {
+            let _ = sender.send(());
+        })?;
+        Ok(HotkeyStream { hotkey, presses })
+    }
+}
+
+/// A stream of presses of a hotkey, created by
+/// [`Hotkey::stream`](struct.Hotkey.html#method.stream).
+///
+/// Presses are buffered until the stream is polled, so none are missed while
+/// the consumer is busy.
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct HotkeyStream {
+    hotkey: Hotkey,
+    presses: mpsc::UnboundedReceiver<()>,
+}
+
+#[cfg(feature = "tokio")]
+impl HotkeyStream {
+    /// Returns the registered hotkey.
+    #[inline]
+    pub fn hotkey(&self) -> &Hotkey {
+        &self.hotkey
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Stream for HotkeyStream {
+    type Item = ();
+
+    #[inline]
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<()>> {
+        self.presses.poll_recv(cx)
+    }
+}
+
+/// Follows the modifiers held on the keyboard to spot presses of a chord.
+#[cfg(any(target_os = "macos", target_os = "linux", test))]
+struct Chord {
+    modifiers: Modifiers,
+    key: Key,
+    held: Vec<Key>,
+    /// Whether the key is down, and whether it went down as part of the
+    /// chord.
+    down: Option<bool>,
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux", test))]
+impl Chord {
+    fn new(modifiers: Modifiers, key: Key) -> Chord {
+        Chord { modifiers, key, held: Vec::new(), down: None }
+    }
+
+    /// Returns `Some(true)` if `event` presses the chord, `Some(false)` if it
+    /// repeats or releases its key after a press, and `None` otherwise.
+    fn update(&mut self, event: &InputEvent) -> Option<bool> {
+        match *event {
+            InputEvent::KeyDown(k) if k.is_modifier() => {
+                if !self.held.contains(&k) {
+                    self.held.push(k);
+                }
+                None
+            },
+            InputEvent::KeyUp(k) if k.is_modifier() => {
+                self.held.retain(|&h| h != k);
+                None
+            },
+            // Auto-repeats don't press the chord again
+            InputEvent::KeyDown(k) if k == self.key => match self.down {
+                Some(true) => Some(false),
+                Some(false) => None,
+                None => {
+                    let held = self.held.iter().filter_map(|h| h.modifier()).fold(Modifiers::empty(), |a, b| a | b);
+                    let chord = held == self.modifiers;
+                    self.down = Some(chord);
+                    if chord { Some(true) } else { None }
+                },
+            },
+            InputEvent::KeyUp(k) if k == self.key => match self.down.take() {
+                Some(true) => Some(false),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use std::sync::{Arc, Mutex};
+
+    use dispatch::Queue;
+    use error::{Error, Result};
+    use events::{self, Filter, Interceptor};
+    use keyboard::{Key, Modifiers};
+    use super::{Callback, Chord};
+
+    lazy_static! {
+        static ref QUEUE: Queue = Queue::new("hotkey");
+    }
+
+    pub struct Registration {
+        _interceptor: Interceptor,
+    }
+
+    /// Swallows the chord with an event tap, which runs its own run loop on a
+    /// background thread, unlike Carbon hotkeys, which are only received
+    /// through the main thread's run loop.
+    pub fn register(modifiers: Modifiers, key: Key, callback: Callback) -> Result<Registration> {
+        key.native_code().ok_or(Error::Unsupported)?;
+        let callback = Arc::new(Mutex::new(callback));
+        let mut chord = Chord::new(modifiers, key);
+        let interceptor = events::intercept(move |event| match chord.update(event) {
+            Some(true) => {
+                let callback = callback.clone();
+                // Input stalls while the tap waits, whatever the callback does
+                QUEUE.exec_async(move || {
+                    let mut callback = callback.lock().unwrap_or_else(|e| e.into_inner());
+                    callback();
+                });
+                Filter::Consume
+            },
+            Some(false) => Filter::Consume,
+            None => Filter::Pass,
+        })?;
+        Ok(Registration { _interceptor: interceptor })
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use std::{mem, ptr};
+    use std::sync::mpsc;
+    use std::thread::{self, JoinHandle};
+
+    use winapi::shared::minwindef::DWORD;
+    use winapi::um::processthreadsapi::GetCurrentThreadId;
+    use winapi::um::winuser::{
+        GetMessageW,
+        PeekMessageW,
+        PostThreadMessageW,
+        RegisterHotKey,
+        UnregisterHotKey,
+        MOD_ALT,
+        MOD_CONTROL,
+        MOD_NOREPEAT,
+        MOD_SHIFT,
+        MOD_WIN,
+        PM_NOREMOVE,
+        WM_HOTKEY,
+        WM_QUIT,
+        WM_USER,
+    };
+
+    use error::{Error, OsError, Result};
+    use keyboard::{Key, Modifiers};
+    use super::Callback;
+
+    pub struct Registration {
+        thread_id: DWORD,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl Drop for Registration {
+        fn drop(&mut self) {
+            unsafe { PostThreadMessageW(self.thread_id, WM_QUIT, 0, 0) };
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    fn win_modifiers(modifiers: Modifiers) -> u32 {
+        let mut win = MOD_NOREPEAT as u32;
+        if modifiers.contains(Modifiers::SHIFT) {
+            win |= MOD_SHIFT as u32;
+        }
+        if modifiers.contains(Modifiers::CONTROL) {
+            win |= MOD_CONTROL as u32;
+        }
+        if modifiers.contains(Modifiers::ALT) {
+            win |= MOD_ALT as u32;
+        }
+        if modifiers.contains(Modifiers::META) {
+            win |= MOD_WIN as u32;
+        }
+        win
+    }
+
+    pub fn register(modifiers: Modifiers, key: Key, mut callback: Callback) -> Result<Registration> {
+        let code = key.native_code().ok_or(Error::Unsupported)?;
+        let modifiers = win_modifiers(modifiers);
+        let (tx, rx) = mpsc::channel();
+
+        // `WM_HOTKEY` is posted to the thread that registered the hotkey, so
+        // each hotkey gets a thread with its own message loop
+        let thread = thread::Builder::new()
+            .name("auto-hotkey".into())
+            .spawn(move || unsafe {
+                let mut msg = mem::zeroed();
+
+                // Create the thread's message queue before anyone can post
+                // `WM_QUIT` to it
+                PeekMessageW(&mut msg, ptr::null_mut(), WM_USER, WM_USER, PM_NOREMOVE);
+
+                if RegisterHotKey(ptr::null_mut(), 1, modifiers, u32::from(code)) == 0 {
+                    let _ = tx.send(Err(OsError::last()));
+                    return;
+                }
+                let _ = tx.send(Ok(GetCurrentThreadId()));
+
+                while GetMessageW(&mut msg, ptr::null_mut(), 0, 0) > 0 {
+                    if msg.message == WM_HOTKEY {
+                        callback();
+                    }
+                }
+                UnregisterHotKey(ptr::null_mut(), 1);
+            })
+            .expect("failed to spawn hotkey thread");
+
+        match rx.recv() {
+            Ok(Ok(thread_id)) => Ok(Registration { thread_id, thread: Some(thread) }),
+            Ok(Err(err)) => {
+                let _ = thread.join();
+                Err(err.into())
+            },
+            Err(_) => panic!("hotkey thread exited during registration"),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use error::{Error, Result};
+    use events::{self, Listener};
+    use keyboard::{Key, Modifiers};
+    use super::{Callback, Chord};
+
+    pub struct Registration {
+        _listener: Listener,
+    }
+
+    /// Watches the keyboards for the chord, since input devices are read
+    /// without involving the display server.
+    pub fn register(modifiers: Modifiers, key: Key, mut callback: Callback) -> Result<Registration> {
+        key.native_code().ok_or(Error::Unsupported)?;
+        let mut chord = Chord::new(modifiers, key);
+        let listener = events::listen_devices(move |event| {
+            if chord.update(event) == Some(true) {
+                callback();
+            }
+        })?;
+        Ok(Registration { _listener: listener })
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+mod imp {
+    use error::{Error, Result};
+    use keyboard::{Key, Modifiers};
+    use super::Callback;
+
+    pub struct Registration;
+
+    pub fn register(_: Modifiers, _: Key, _: Callback) -> Result<Registration> {
+        Err(Error::Unsupported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use events::InputEvent::*;
+    use keyboard::{Key, Modifiers};
+    use super::Chord;
+
+    #[test]
+    fn chord_presses() {
+        let mut chord = Chord::new(Modifiers::CONTROL | Modifiers::ALT, Key::Escape);
+        let updates: Vec<_> = [
+            KeyDown(Key::Escape), KeyUp(Key::Escape),
+            KeyDown(Key::Control), KeyDown(Key::Alt), KeyDown(Key::Alt),
+            KeyDown(Key::Escape), KeyDown(Key::Escape), KeyUp(Key::Escape),
+            KeyUp(Key::Alt), KeyDown(Key::Escape), KeyUp(Key::Escape),
+        ].iter().map(|event| chord.update(event)).collect();
+
+        assert_eq!(updates, [
+            None, None,
+            None, None, None,
+            Some(true), Some(false), Some(false),
+            None, None, None,
+        ]);
+    }
+}
@@ -0,0 +1,103 @@
+//! 💤 Detecting when the user stops using the computer.
+//!
+//! Automation that takes over the mouse and keyboard while someone is using
+//! them fights with that person for control. These utilities let scripts wait
+//! politely until the user has stepped away.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use auto::{idle, mouse};
+//!
+//! // Only start once nobody has touched the computer for 30 seconds
+//! idle::wait_for_idle(Duration::from_secs(30)).unwrap();
+//! mouse::click(mouse::Button::Left).unwrap();
+//! ```
+
+use std::time::{Duration, Instant};
+
+use abort::Token;
+use error::Result;
+
+/// The shortest time to sleep between checks, which keeps waits from spinning
+/// when the user is almost idle.
+const MIN_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Returns the number of seconds since the last keyboard or mouse input.
+///
+/// On macOS, only input from the hardware counts, so events posted by this
+/// crate do not reset the time. On Windows, the system does not tell the two
+/// apart, and events sent by any application count as input.
+#[inline]
+pub fn seconds_since_last_input() -> Result<f64> {
+    imp::seconds_since_last_input()
+}
+
+/// Blocks until there has been no input for at least `idle`.
+///
+/// Since input cannot make the user idle any sooner, this sleeps until the
+/// earliest time the user could be idle rather than polling. It fails with
+/// [`Error::Aborted`](../error/enum.Error.html#variant.Aborted) if the
+/// [kill switch](../abort/index.html) is triggered while waiting.
+#[inline]
+pub fn wait_for_idle(idle: Duration) -> Result<()> {
+    wait(idle, None).map(|_| ())
+}
+
+/// Blocks for up to `timeout` until there has been no input for at least
+/// `idle`, returning whether the user became idle in time.
+///
+/// See [`wait_for_idle`](fn.wait_for_idle.html).
+#[inline]
+pub fn wait_for_idle_timeout(idle: Duration, timeout: Duration) -> Result<bool> {
+    wait(idle, Instant::now().checked_add(timeout))
+}
+
+fn wait(idle: Duration, deadline: Option<Instant>) -> Result<bool> {
+    let token = Token::new();
+    loop {
+        let since = Duration::from_secs_f64(seconds_since_last_input()?.max(0.0));
+        if since >= idle {
+            return Ok(true);
+        }
+        let mut wait = (idle - since).max(MIN_CHECK_INTERVAL);
+        if let Some(deadline) = deadline {
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(false);
+            }
+            wait = wait.min(deadline - now);
+        }
+        token.sleep(wait)?;
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use error::Result;
+    use os::source::SourceState;
+
+    pub fn seconds_since_last_input() -> Result<f64> {
+        Ok(SourceState::HidSystem.seconds_since_last_input())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use error::Result;
+    use os;
+
+    pub fn seconds_since_last_input() -> Result<f64> {
+        os::time_since_last_input().map(|time| time.as_secs_f64())
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod imp {
+    use error::{Error, Result};
+
+    pub fn seconds_since_last_input() -> Result<f64> {
+        Err(Error::Unsupported)
+    }
+}
@@ -0,0 +1,577 @@
+//! 🖼️ Owned image buffers.
+//!
+//! Screen captures and the templates they are matched against share the
+//! [`Image`](struct.Image.html) type. With the `image-io` feature, images can
+//! be saved to and loaded from PNG and BMP files.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[cfg(feature = "image-io")] {
+//! use auto::image::Image;
+//! use auto::screen;
+//!
+//! let capture = screen::capture().unwrap().into_image();
+//! let corner = capture.crop(0, 0, 200, 100).unwrap();
+//! corner.save_png("corner.png").unwrap();
+//!
+//! let template = Image::load_png("corner.png").unwrap();
+//! assert_eq!(template, corner);
+//! # }
+//! ```
+
+use std::fmt;
+use std::io::{self, Write};
+#[cfg(feature = "image-io")]
+use std::fs::File;
+#[cfg(feature = "image-io")]
+use std::io::{BufReader, BufWriter, Read};
+#[cfg(feature = "image-io")]
+use std::path::Path;
+
+use color::Rgb;
+
+#[cfg(feature = "image-io")]
+mod png;
+
+/// The largest width or height of an image read from a file.
+#[cfg(feature = "image-io")]
+const MAX_DIMENSION: usize = 1 << 16;
+
+/// The largest number of pixels read from an image.
+#[cfg(feature = "image-io")]
+const MAX_PIXELS: usize = 1 << 28;
+
+/// Returns whether an image read from a file may be `width` by `height`,
+/// which keeps corrupt headers from exhausting memory.
+#[cfg(feature = "image-io")]
+fn is_readable_size(width: usize, height: usize) -> bool {
+    let dimension = 1..=MAX_DIMENSION;
+    dimension.contains(&width)
+        && dimension.contains(&height)
+        && width.checked_mul(height).is_some_and(|n| n <= MAX_PIXELS)
+}
+
+/// An owned buffer of 8-bit RGBA pixels, stored row by row from the top.
+///
+/// Rows may be padded, so each row starts `stride` bytes after the previous
+/// one.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    stride: usize,
+    data: Vec<u8>,
+}
+
+impl fmt::Debug for Image {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Image")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("stride", &self.stride)
+            .finish()
+    }
+}
+
+impl Image {
+    /// Creates an image from rows of RGBA bytes that start `stride` bytes
+    /// apart.
+    ///
+    /// Returns `None` if `stride` is too small for `width` or if `data` is too
+    /// short to hold `height` rows.
+    pub fn new(width: usize, height: usize, stride: usize, data: Vec<u8>) -> Option<Image> {
+        let row_len = width.checked_mul(4)?;
+        if stride < row_len {
+            return None;
+        }
+        let len = match height {
+            0 => 0,
+            _ => stride.checked_mul(height - 1)?.checked_add(row_len)?,
+        };
+        if data.len() < len {
+            return None;
+        }
+        Some(Image { width, height, stride, data })
+    }
+
+    /// Creates an image from tightly packed RGBA bytes.
+    #[inline]
+    pub fn from_rgba(width: usize, height: usize, data: Vec<u8>) -> Option<Image> {
+        Image::new(width, height, width.checked_mul(4)?, data)
+    }
+
+    /// Returns the width of the image in pixels.
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the height of the image in pixels.
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the number of bytes from the start of one row to the next.
+    #[inline]
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Returns the raw RGBA bytes, including any row padding.
+    #[inline]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Consumes the image, returning its raw RGBA bytes.
+    #[inline]
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Returns the RGBA bytes of row `y`, excluding padding.
+    #[inline]
+    pub fn row(&self, y: usize) -> Option<&[u8]> {
+        if y < self.height {
+            let start = y * self.stride;
+            Some(&self.data[start..start + self.width * 4])
+        } else {
+            None
+        }
+    }
+
+    /// Returns the color of the pixel at (`x`, `y`), ignoring alpha.
+    pub fn pixel(&self, x: usize, y: usize) -> Option<Rgb> {
+        if x >= self.width {
+            return None;
+        }
+        self.row(y).map(|row| {
+            let px = &row[x * 4..];
+            Rgb { red: px[0], green: px[1], blue: px[2] }
+        })
+    }
+
+    /// Returns a copy of the image shrunk by `factor` in each dimension, with
+    /// each pixel the average of a block of up to `factor` × `factor` pixels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `factor` is zero.
+    pub fn downsample(&self, factor: usize) -> Image {
+        assert!(factor > 0, "downsample factor must be non-zero");
+        let width = self.width.div_ceil(factor);
+        let height = self.height.div_ceil(factor);
+        let mut data = Vec::with_capacity(width * height * 4);
+        for by in 0..height {
+            let rows = by * factor..((by + 1) * factor).min(self.height);
+            for bx in 0..width {
+                let cols = bx * factor..((bx + 1) * factor).min(self.width);
+                let mut sum = [0usize; 4];
+                for y in rows.clone() {
+                    let row = &self.data[y * self.stride..];
+                    for x in cols.clone() {
+                        for (total, &byte) in sum.iter_mut().zip(&row[x * 4..x * 4 + 4]) {
+                            *total += byte as usize;
+                        }
+                    }
+                }
+                let count = rows.len() * cols.len();
+                data.extend(sum.iter().map(|&total| (total / count) as u8));
+            }
+        }
+        Image { width, height, stride: width * 4, data }
+    }
+
+    /// Returns a copy of the `width` × `height` area of the image whose
+    /// upper-left corner is at (`x`, `y`), or `None` if the area doesn't fit
+    /// in the image.
+    pub fn crop(&self, x: usize, y: usize, width: usize, height: usize) -> Option<Image> {
+        if x.checked_add(width)? > self.width || y.checked_add(height)? > self.height {
+            return None;
+        }
+        let mut data = Vec::with_capacity(width * height * 4);
+        for row in y..y + height {
+            let start = row * self.stride + x * 4;
+            data.extend_from_slice(&self.data[start..start + width * 4]);
+        }
+        Some(Image { width, height, stride: width * 4, data })
+    }
+
+    /// Copies the `width` × `height` area whose upper-left corner is at
+    /// (`x`, `y`) out of rows of BGRX bytes that start `stride` bytes apart,
+    /// as captured from the screen, into an opaque image.
+    ///
+    /// Returns `None` if the area doesn't fit in `pixels`.
+    #[cfg(any(target_os = "macos", target_os = "windows", test))]
+    pub(crate) fn crop_bgrx(
+        pixels: &[u8],
+        stride: usize,
+        (x, y): (usize, usize),
+        (width, height): (usize, usize),
+    ) -> Option<Image> {
+        let row_len = x.checked_add(width)?.checked_mul(4)?;
+        let last = y.checked_add(height)?.saturating_sub(1);
+        if height > 0 && (row_len > stride || pixels.len() < stride.checked_mul(last)?.checked_add(row_len)?) {
+            return None;
+        }
+        let mut data = Vec::with_capacity(width * height * 4);
+        for row in y..y + height {
+            for px in pixels[row * stride + x * 4..][..width * 4].chunks(4) {
+                data.extend_from_slice(&[px[2], px[1], px[0], 255]);
+            }
+        }
+        Image::from_rgba(width, height, data)
+    }
+
+    /// Returns a copy of the image scaled to `width` × `height` pixels, with
+    /// each pixel interpolated between the four nearest pixels of the image.
+    ///
+    /// Shrinking by a whole factor is sharper with
+    /// [`downsample`](#method.downsample).
+    pub fn resize(&self, width: usize, height: usize) -> Image {
+        let mut data = Vec::with_capacity(width * height * 4);
+        if self.width == 0 || self.height == 0 {
+            data.resize(width * height * 4, 0);
+            return Image { width, height, stride: width * 4, data };
+        }
+        // The source position of each target pixel's center, as the lower
+        // neighbor, the upper neighbor, and the weight of the upper one
+        let sample = |target: usize, from: usize, to: usize| {
+            let pos = ((target as f64 + 0.5) * from as f64 / to as f64 - 0.5).max(0.0);
+            let low = (pos as usize).min(from - 1);
+            (low, (low + 1).min(from - 1), pos - low as f64)
+        };
+        for ty in 0..height {
+            let (y0, y1, fy) = sample(ty, self.height, height);
+            for tx in 0..width {
+                let (x0, x1, fx) = sample(tx, self.width, width);
+                let at = |x: usize, y: usize, c: usize| self.data[y * self.stride + x * 4 + c] as f64;
+                for c in 0..4 {
+                    let top = at(x0, y0, c) * (1.0 - fx) + at(x1, y0, c) * fx;
+                    let bottom = at(x0, y1, c) * (1.0 - fx) + at(x1, y1, c) * fx;
+                    data.push((top * (1.0 - fy) + bottom * fy).round() as u8);
+                }
+            }
+        }
+        Image { width, height, stride: width * 4, data }
+    }
+
+    /// Returns a copy of the image scaled by `factor` in each dimension, such
+    /// as 0.5 to match a template captured on a Retina display against a
+    /// capture from a standard one.
+    #[inline]
+    pub fn scale(&self, factor: f64) -> Image {
+        let size = |n: usize| (n as f64 * factor).round().max(0.0) as usize;
+        self.resize(size(self.width), size(self.height))
+    }
+
+    /// Writes the image as an uncompressed 32-bit BMP file.
+    pub(crate) fn write_bmp<W: Write>(&self, mut out: W) -> io::Result<()> {
+        const HEADER_LEN: u32 = 14 + 40;
+
+        let size = (self.width * self.height * 4) as u32;
+        let le16 = |n: u16| n.to_le_bytes();
+        let le32 = |n: u32| n.to_le_bytes();
+
+        // File header
+        out.write_all(b"BM")?;
+        out.write_all(&le32(HEADER_LEN + size))?;
+        out.write_all(&le32(0))?;
+        out.write_all(&le32(HEADER_LEN))?;
+
+        // BITMAPINFOHEADER, with a negative height for top-down rows
+        out.write_all(&le32(40))?;
+        out.write_all(&(self.width as i32).to_le_bytes())?;
+        out.write_all(&(-(self.height as i32)).to_le_bytes())?;
+        out.write_all(&le16(1))?;
+        out.write_all(&le16(32))?;
+        out.write_all(&le32(0))?;
+        out.write_all(&le32(size))?;
+        out.write_all(&le32(2835))?;
+        out.write_all(&le32(2835))?;
+        out.write_all(&le32(0))?;
+        out.write_all(&le32(0))?;
+
+        let mut bgra = Vec::with_capacity(self.width * 4);
+        for y in 0..self.height {
+            bgra.clear();
+            for px in self.row(y).unwrap_or_default().chunks(4) {
+                bgra.extend_from_slice(&[px[2], px[1], px[0], px[3]]);
+            }
+            out.write_all(&bgra)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "image-io")]
+impl Image {
+    /// Writes the image as a PNG image with 8-bit RGBA pixels.
+    #[inline]
+    pub fn write_png<W: Write>(&self, out: W) -> io::Result<()> {
+        png::write(self, out)
+    }
+
+    /// Reads a PNG image, converting its pixels to 8-bit RGBA.
+    ///
+    /// Fails with `InvalidData` if the image is malformed or interlaced.
+    #[inline]
+    pub fn read_png<R: Read>(input: R) -> io::Result<Image> {
+        png::read(input)
+    }
+
+    /// Saves the image as a PNG file at `path`.
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_png(&mut out)?;
+        out.flush()
+    }
+
+    /// Loads the PNG file at `path`.
+    pub fn load_png<P: AsRef<Path>>(path: P) -> io::Result<Image> {
+        Image::read_png(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads an uncompressed 24-bit or 32-bit BMP image.
+    ///
+    /// Fails with `InvalidData` for other kinds of BMP images.
+    pub fn read_bmp<R: Read>(mut input: R) -> io::Result<Image> {
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut data = Vec::new();
+        input.read_to_end(&mut data)?;
+        if data.len() < 54 || &data[..2] != b"BM" {
+            return Err(invalid("not a BMP image"));
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]);
+        let u32_at = |i: usize| u32::from_le_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+        let offset = u32_at(10) as usize;
+        let width = u32_at(18) as i32;
+        let height = u32_at(22) as i32;
+        let depth = u16_at(28);
+        // BI_RGB, or BI_BITFIELDS as written for 32-bit images by some tools
+        if width < 0 || !(depth == 24 || depth == 32) || !(u32_at(30) == 0 || u32_at(30) == 3) {
+            return Err(invalid("unsupported BMP image"));
+        }
+
+        let (width, rows) = (width as usize, height.unsigned_abs() as usize);
+        if !is_readable_size(width, rows) {
+            return Err(invalid("invalid BMP image size"));
+        }
+        let bytes = depth as usize / 8;
+        // Rows are padded to a multiple of 4 bytes
+        let stride = (width * bytes).div_ceil(4) * 4;
+        let len = stride.checked_mul(rows).ok_or_else(|| invalid("BMP image too large"))?;
+        let pixels = data.get(offset..).and_then(|d| d.get(..len))
+            .ok_or_else(|| invalid("truncated BMP image"))?;
+
+        let mut rgba = Vec::with_capacity(width * rows * 4);
+        for y in 0..rows {
+            // Rows are stored from the bottom unless the height is negative
+            let row = if height < 0 { y } else { rows - 1 - y };
+            for px in pixels[row * stride..][..width * bytes].chunks(bytes) {
+                let alpha = if bytes == 4 { px[3] } else { 255 };
+                rgba.extend_from_slice(&[px[2], px[1], px[0], alpha]);
+            }
+        }
+        // Images whose alpha channel is unused have it zeroed
+        if bytes == 4 && rgba.chunks(4).all(|px| px[3] == 0) {
+            for px in rgba.chunks_mut(4) {
+                px[3] = 255;
+            }
+        }
+        Ok(Image::from_rgba(width, rows, rgba).expect("invalid BMP image size"))
+    }
+
+    /// Saves the image as an uncompressed 32-bit BMP file at `path`.
+    pub fn save_bmp<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_bmp(&mut out)?;
+        out.flush()
+    }
+
+    /// Loads the BMP file at `path`, as with [`read_bmp`](#method.read_bmp).
+    pub fn load_bmp<P: AsRef<Path>>(path: P) -> io::Result<Image> {
+        Image::read_bmp(BufReader::new(File::open(path)?))
+    }
+}
+
+/// The colors of a region of the screen, read from a single capture.
+///
+/// Reading each color from the grid is a lookup into memory, so a grid is far
+/// cheaper than reading the pixels of a region one at a time from the
+/// screen.
+///
+/// Locations are relative to the upper-left corner of the region and in the
+/// same units as the screen, so on high-resolution displays each location
+/// covers several pixels.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorGrid {
+    image: Image,
+    scale: f64,
+}
+
+impl ColorGrid {
+    /// Creates a grid from `image`, which has `scale` pixels along each unit
+    /// of the screen.
+    #[inline]
+    pub fn new(image: Image, scale: f64) -> ColorGrid {
+        ColorGrid { image, scale }
+    }
+
+    /// Returns the width and height of the grid in pixels.
+    #[inline]
+    pub fn pixels(&self) -> (usize, usize) {
+        (self.image.width, self.image.height)
+    }
+
+    /// Returns the number of pixels along each unit of the screen.
+    #[inline]
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// Returns the color at `pos`, relative to the upper-left corner of the
+    /// region, or `None` if it lies outside of the grid.
+    pub fn color_at(&self, (x, y): (f64, f64)) -> Option<Rgb> {
+        let (px, py) = ((x * self.scale).floor(), (y * self.scale).floor());
+        if px >= 0.0 && py >= 0.0 {
+            self.image.pixel(px as usize, py as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the color of the pixel at (`x`, `y`).
+    #[inline]
+    pub fn pixel(&self, x: usize, y: usize) -> Option<Rgb> {
+        self.image.pixel(x, y)
+    }
+
+    /// Returns an iterator over the pixel coordinates and colors of the grid,
+    /// row by row from the top.
+    pub fn iter(&self) -> impl Iterator<Item = ((usize, usize), Rgb)> + '_ {
+        (0..self.image.height).flat_map(move |y| {
+            let row = self.image.row(y).unwrap_or_default();
+            row.chunks(4).enumerate().map(move |(x, px)| {
+                ((x, y), Rgb { red: px[0], green: px[1], blue: px[2] })
+            })
+        })
+    }
+
+    /// Returns the captured image.
+    #[inline]
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    /// Returns the captured image, consuming the grid.
+    #[inline]
+    pub fn into_image(self) -> Image {
+        self.image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downsample() {
+        let data = vec![
+            0, 0, 0, 255,     100, 10, 0, 255,   7, 7, 7, 255,
+            200, 20, 0, 255,  100, 10, 4, 255,   9, 9, 9, 255,
+        ];
+        let image = Image::from_rgba(3, 2, data).unwrap().downsample(2);
+        assert_eq!((image.width(), image.height()), (2, 1));
+        assert_eq!(image.pixel(0, 0), Some(Rgb { red: 100, green: 10, blue: 1 }));
+        assert_eq!(image.pixel(1, 0), Some(Rgb { red: 8, green: 8, blue: 8 }));
+    }
+
+    #[test]
+    fn padded_rows() {
+        let data = vec![
+            1, 2, 3, 255,   4, 5, 6, 255,   0, 0,
+            7, 8, 9, 255,  10, 11, 12, 255,
+        ];
+        let image = Image::new(2, 2, 10, data.clone()).unwrap();
+        assert_eq!(image.pixel(1, 1), Some(Rgb { red: 10, green: 11, blue: 12 }));
+        assert_eq!(image.pixel(2, 0), None);
+        assert_eq!(image.row(1), Some(&data[10..18]));
+
+        let grid = ColorGrid::new(image.clone(), 2.0);
+        assert_eq!(grid.color_at((0.5, 0.5)), Some(Rgb { red: 10, green: 11, blue: 12 }));
+        assert_eq!(grid.color_at((1.0, 0.0)), None);
+        assert_eq!(grid.iter().count(), 4);
+        assert_eq!(grid.iter().nth(1), Some(((1, 0), Rgb { red: 4, green: 5, blue: 6 })));
+        assert!(Image::new(2, 2, 7, data.clone()).is_none());
+        assert!(Image::new(2, 3, 10, data).is_none());
+
+        let mut bmp = Vec::new();
+        image.write_bmp(&mut bmp).unwrap();
+        assert_eq!(bmp.len(), 54 + 16);
+        assert_eq!(&bmp[54..58], &[3, 2, 1, 255]);
+    }
+
+    #[test]
+    fn crop_and_resize() {
+        let data = vec![
+            0, 0, 0, 255,   100, 0, 0, 255,   200, 0, 0, 255,
+            0, 50, 0, 255,  100, 50, 0, 255,  200, 50, 0, 255,
+        ];
+        let image = Image::from_rgba(3, 2, data).unwrap();
+        let cropped = image.crop(1, 1, 2, 1).unwrap();
+        assert_eq!(cropped.data(), &[100, 50, 0, 255, 200, 50, 0, 255]);
+        assert!(image.crop(2, 0, 2, 1).is_none());
+
+        assert_eq!(image.resize(3, 2), image);
+        let wide = image.resize(6, 1);
+        assert_eq!(wide.pixel(0, 0), Some(Rgb { red: 0, green: 25, blue: 0 }));
+        assert_eq!(wide.pixel(1, 0), Some(Rgb { red: 25, green: 25, blue: 0 }));
+        assert_eq!(image.scale(2.0).width(), 6);
+    }
+
+    #[test]
+    fn crop_bgrx() {
+        let pixels = vec![
+            1, 2, 3, 0,   4, 5, 6, 0,   0, 0,
+            7, 8, 9, 0,  10, 11, 12, 0,
+        ];
+        let image = Image::crop_bgrx(&pixels, 10, (1, 0), (1, 2)).unwrap();
+        assert_eq!(image.data(), &[6, 5, 4, 255, 12, 11, 10, 255]);
+        assert!(Image::crop_bgrx(&pixels, 10, (1, 0), (2, 1)).is_none());
+        assert!(Image::crop_bgrx(&pixels, 10, (0, 1), (2, 2)).is_none());
+    }
+
+    #[cfg(feature = "image-io")]
+    #[test]
+    fn png_and_bmp_round_trip() {
+        let data = (0..40 * 30 * 4).map(|n| (n * 7 % 251) as u8).collect();
+        let image = Image::from_rgba(40, 30, data).unwrap();
+
+        let mut png = Vec::new();
+        image.write_png(&mut png).unwrap();
+        assert_eq!(Image::read_png(&png[..]).unwrap(), image);
+        png[40] ^= 1;
+        assert_eq!(Image::read_png(&png[..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let opaque = Image::from_rgba(3, 1, vec![1, 2, 3, 255, 4, 5, 6, 255, 7, 8, 9, 255]).unwrap();
+        let mut bmp = Vec::new();
+        opaque.write_bmp(&mut bmp).unwrap();
+        assert_eq!(Image::read_bmp(&bmp[..]).unwrap(), opaque);
+    }
+
+    #[cfg(feature = "image-io")]
+    #[test]
+    fn malformed_bmp_sizes() {
+        let mut bmp = Vec::new();
+        Image::from_rgba(1, 1, vec![1, 2, 3, 255]).unwrap().write_bmp(&mut bmp).unwrap();
+        for &(width, height) in &[(0, i32::MIN), (0, 1), (1, 0), (1 << 17, 1), (1, -(1 << 30))] {
+            bmp[18..22].copy_from_slice(&(width as u32).to_le_bytes());
+            bmp[22..26].copy_from_slice(&height.to_le_bytes());
+            let error = Image::read_bmp(&bmp[..]).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+}
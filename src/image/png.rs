@@ -0,0 +1,666 @@
+//! PNG encoding and decoding, with a small DEFLATE implementation of its own.
+//!
+//! Images are written as 8-bit RGBA with fixed Huffman codes, which is fast
+//! and compresses screenshots well. Reading supports every non-interlaced
+//! PNG image.
+
+use std::io::{self, Read, Write};
+
+use super::{is_readable_size, Image};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+fn invalid(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+lazy_static! {
+    static ref CRC_TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        for (n, entry) in table.iter_mut().enumerate() {
+            let mut c = n as u32;
+            for _ in 0..8 {
+                c = if c & 1 != 0 { 0xEDB8_8320 ^ (c >> 1) } else { c >> 1 };
+            }
+            *entry = c;
+        }
+        table
+    };
+}
+
+fn crc32(parts: &[&[u8]]) -> u32 {
+    let mut crc = !0u32;
+    for &part in parts {
+        for &byte in part {
+            crc = CRC_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // Sums can't overflow within 5552 bytes
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+// DEFLATE tables, from RFC 1951
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+/// The order in which code length code lengths are stored.
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Reads bits from the least significant end of each byte.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buf: u32,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> BitReader<'a> {
+        BitReader { data, pos: 0, buf: 0, count: 0 }
+    }
+
+    fn bits(&mut self, n: u32) -> io::Result<u32> {
+        while self.count < n {
+            let byte = *self.data.get(self.pos).ok_or_else(|| invalid("truncated image data"))?;
+            self.pos += 1;
+            self.buf |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buf & ((1u64 << n) - 1) as u32;
+        self.buf >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    /// Skips to the next byte boundary.
+    fn align(&mut self) {
+        self.buf = 0;
+        self.count = 0;
+    }
+}
+
+/// A canonical Huffman code, decoded a bit at a time.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, bits: &mut BitReader) -> io::Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= bits.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("invalid Huffman code"))
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    for (n, len) in lengths.iter_mut().enumerate() {
+        *len = match n {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(bits: &mut BitReader) -> io::Result<(Huffman, Huffman)> {
+    let literals = bits.bits(5)? as usize + 257;
+    let distances = bits.bits(5)? as usize + 1;
+    let code_lengths = bits.bits(4)? as usize + 4;
+
+    let mut lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[index] = bits.bits(3)? as u8;
+    }
+    let code = Huffman::new(&lengths);
+
+    let mut lengths = vec![0u8; literals + distances];
+    let mut n = 0;
+    while n < lengths.len() {
+        let (value, repeat) = match code.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths[..n].last().ok_or_else(|| invalid("invalid code lengths"))?;
+                (previous, 3 + bits.bits(2)? as usize)
+            },
+            17 => (0, 3 + bits.bits(3)? as usize),
+            _ => (0, 11 + bits.bits(7)? as usize),
+        };
+        if n + repeat > lengths.len() {
+            return Err(invalid("invalid code lengths"));
+        }
+        for length in &mut lengths[n..n + repeat] {
+            *length = value;
+        }
+        n += repeat;
+    }
+    Ok((Huffman::new(&lengths[..literals]), Huffman::new(&lengths[literals..])))
+}
+
+/// Decompresses a zlib stream, stopping once `limit` bytes are produced.
+fn inflate(data: &[u8], limit: usize) -> io::Result<Vec<u8>> {
+    if data.len() < 2 || data[0] & 0x0F != 8 || !(data[0] as u16 * 256 + data[1] as u16).is_multiple_of(31) {
+        return Err(invalid("invalid zlib header"));
+    }
+    let mut bits = BitReader::new(&data[2..]);
+    let mut out = Vec::new();
+    loop {
+        let last = bits.bits(1)? != 0;
+        match bits.bits(2)? {
+            0 => {
+                bits.align();
+                let start = bits.pos;
+                let header = bits.data.get(start..start + 4).ok_or_else(|| invalid("truncated image data"))?;
+                let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+                let block = bits.data.get(start + 4..start + 4 + len)
+                    .ok_or_else(|| invalid("truncated image data"))?;
+                out.extend_from_slice(block);
+                bits.pos = start + 4 + len;
+            },
+            1 => {
+                let (literals, distances) = fixed_codes();
+                inflate_block(&mut bits, &literals, &distances, &mut out, limit)?;
+            },
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut bits)?;
+                inflate_block(&mut bits, &literals, &distances, &mut out, limit)?;
+            },
+            _ => return Err(invalid("invalid block type")),
+        }
+        if last || out.len() >= limit {
+            return Ok(out);
+        }
+    }
+}
+
+fn inflate_block(
+    bits: &mut BitReader,
+    literals: &Huffman,
+    distances: &Huffman,
+    out: &mut Vec<u8>,
+    limit: usize,
+) -> io::Result<()> {
+    loop {
+        let symbol = literals.decode(bits)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let code = symbol - 257;
+                if code >= 29 {
+                    return Err(invalid("invalid length code"));
+                }
+                let len = LENGTH_BASE[code] as usize + bits.bits(LENGTH_EXTRA[code] as u32)? as usize;
+                let code = distances.decode(bits)? as usize;
+                if code >= 30 {
+                    return Err(invalid("invalid distance code"));
+                }
+                let dist = DIST_BASE[code] as usize + bits.bits(DIST_EXTRA[code] as u32)? as usize;
+                if dist > out.len() {
+                    return Err(invalid("invalid distance"));
+                }
+                let start = out.len() - dist;
+                for i in 0..len {
+                    let byte = out[start + i];
+                    out.push(byte);
+                }
+            },
+        }
+        if out.len() > limit {
+            return Err(invalid("too much image data"));
+        }
+    }
+}
+
+/// Writes bits from the least significant end of each byte.
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    buf: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn bits(&mut self, value: u32, n: u32) {
+        self.buf |= value << self.count;
+        self.count += n;
+        while self.count >= 8 {
+            self.out.push(self.buf as u8);
+            self.buf >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, which is stored from its most significant bit.
+    fn code(&mut self, code: u32, n: u32) {
+        self.bits(code.reverse_bits() >> (32 - n), n);
+    }
+
+    fn literal(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.code(0x30 + symbol, 8),
+            144..=255 => self.code(0x190 + symbol - 144, 9),
+            256..=279 => self.code(symbol - 256, 7),
+            _ => self.code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.buf as u8);
+        }
+        self.out
+    }
+}
+
+/// How far back matches are looked for.
+const WINDOW: usize = 32 * 1024;
+/// The most earlier positions tried for each match.
+const MAX_CHAIN: usize = 32;
+const HASH_BITS: u32 = 15;
+
+/// Compresses `data` into a zlib stream of one block with fixed Huffman
+/// codes.
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    writer.out.extend_from_slice(&[0x78, 0x9C]);
+    writer.bits(1, 1);
+    writer.bits(1, 2);
+
+    let hash = |i: usize| {
+        let n = (data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32;
+        (n.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+    };
+    // The last position of each hash, and the position before each one with
+    // the same hash
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; WINDOW];
+    let insert = |i: usize, head: &mut [usize], prev: &mut [usize]| {
+        if i + 3 <= data.len() {
+            let h = hash(i);
+            prev[i % WINDOW] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let (mut best_len, mut best_dist) = (0, 0);
+        if i + 3 <= data.len() {
+            let max_len = (data.len() - i).min(258);
+            let mut candidate = head[hash(i)];
+            let mut chain = 0;
+            while candidate != usize::MAX && i - candidate <= WINDOW && chain < MAX_CHAIN {
+                let len = data[candidate..].iter()
+                    .zip(&data[i..i + max_len])
+                    .take_while(|&(a, b)| a == b)
+                    .count();
+                if len > best_len {
+                    best_len = len;
+                    best_dist = i - candidate;
+                    if len == max_len {
+                        break;
+                    }
+                }
+                let next = prev[candidate % WINDOW];
+                if next == usize::MAX || next >= candidate {
+                    break;
+                }
+                candidate = next;
+                chain += 1;
+            }
+        }
+        if best_len >= 3 {
+            let code = LENGTH_BASE.iter().rposition(|&base| base as usize <= best_len).unwrap();
+            writer.literal(257 + code as u32);
+            writer.bits((best_len - LENGTH_BASE[code] as usize) as u32, LENGTH_EXTRA[code] as u32);
+            let code = DIST_BASE.iter().rposition(|&base| base as usize <= best_dist).unwrap();
+            writer.code(code as u32, 5);
+            writer.bits((best_dist - DIST_BASE[code] as usize) as u32, DIST_EXTRA[code] as u32);
+            for j in i..i + best_len {
+                insert(j, &mut head, &mut prev);
+            }
+            i += best_len;
+        } else {
+            writer.literal(data[i] as u32);
+            insert(i, &mut head, &mut prev);
+            i += 1;
+        }
+    }
+    writer.literal(256);
+
+    let mut out = writer.finish();
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Reverses the filter of `row` given the previous row, with `bpp` bytes
+/// per pixel.
+fn unfilter(filter: u8, row: &mut [u8], previous: &[u8], bpp: usize) -> io::Result<()> {
+    for i in 0..row.len() {
+        let left = if i >= bpp { row[i - bpp] } else { 0 };
+        let up = previous[i];
+        let up_left = if i >= bpp { previous[i - bpp] } else { 0 };
+        row[i] = row[i].wrapping_add(match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            _ => return Err(invalid("invalid filter type")),
+        });
+    }
+    Ok(())
+}
+
+/// Filters `row` given the previous row with each filter type, keeping the
+/// one whose output is smallest as signed bytes.
+fn filter(row: &[u8], previous: &[u8], out: &mut Vec<u8>) {
+    const BPP: usize = 4;
+    let mut best = Vec::new();
+    let mut best_cost = usize::MAX;
+    let mut filtered = Vec::with_capacity(row.len() + 1);
+    for filter in 0..5u8 {
+        filtered.clear();
+        filtered.push(filter);
+        for i in 0..row.len() {
+            let left = if i >= BPP { row[i - BPP] } else { 0 };
+            let up = previous[i];
+            let up_left = if i >= BPP { previous[i - BPP] } else { 0 };
+            filtered.push(row[i].wrapping_sub(match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                _ => paeth(left, up, up_left),
+            }));
+        }
+        let cost = filtered[1..].iter().map(|&b| (b as i8).unsigned_abs() as usize).sum();
+        if cost < best_cost {
+            best_cost = cost;
+            ::std::mem::swap(&mut best, &mut filtered);
+        }
+    }
+    out.extend_from_slice(&best);
+}
+
+fn write_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    out.write_all(&crc32(&[kind, data]).to_be_bytes())
+}
+
+/// Writes `image` as an 8-bit RGBA PNG image.
+pub fn write<W: Write>(image: &Image, mut out: W) -> io::Result<()> {
+    if image.width > u32::MAX as usize || image.height > u32::MAX as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "image too large for PNG"));
+    }
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(image.width as u32).to_be_bytes());
+    header.extend_from_slice(&(image.height as u32).to_be_bytes());
+    // 8 bits per channel, RGBA, default compression, filtering, and no
+    // interlacing
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let row_len = image.width * 4;
+    let mut raw = Vec::with_capacity((row_len + 1) * image.height);
+    let empty = vec![0; row_len];
+    let mut previous: &[u8] = &empty;
+    for y in 0..image.height {
+        let row = image.row(y).unwrap_or_default();
+        filter(row, previous, &mut raw);
+        previous = row;
+    }
+
+    out.write_all(&SIGNATURE)?;
+    write_chunk(&mut out, b"IHDR", &header)?;
+    write_chunk(&mut out, b"IDAT", &deflate(&raw))?;
+    write_chunk(&mut out, b"IEND", &[])
+}
+
+/// The header of a PNG image.
+struct Header {
+    width: usize,
+    height: usize,
+    depth: u8,
+    color: u8,
+}
+
+impl Header {
+    fn channels(&self) -> usize {
+        match self.color {
+            0 | 3 => 1,
+            4 => 2,
+            2 => 3,
+            _ => 4,
+        }
+    }
+
+    /// The number of bytes in a row of samples, excluding the filter type.
+    fn row_len(&self) -> usize {
+        (self.width * self.channels() * self.depth as usize).div_ceil(8)
+    }
+}
+
+fn parse_header(data: &[u8]) -> io::Result<Header> {
+    if data.len() != 13 {
+        return Err(invalid("invalid PNG header"));
+    }
+    let width = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+    let height = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as usize;
+    let (depth, color) = (data[8], data[9]);
+    let valid = match color {
+        0 => [1, 2, 4, 8, 16].contains(&depth),
+        3 => [1, 2, 4, 8].contains(&depth),
+        2 | 4 | 6 => [8, 16].contains(&depth),
+        _ => false,
+    };
+    if !valid || data[10] != 0 || data[11] != 0 {
+        return Err(invalid("invalid PNG header"));
+    }
+    if data[12] != 0 {
+        return Err(invalid("interlaced PNG images are not supported"));
+    }
+    if !is_readable_size(width, height) {
+        return Err(invalid("invalid PNG image size"));
+    }
+    Ok(Header { width, height, depth, color })
+}
+
+/// Reads a PNG image.
+pub fn read<R: Read>(mut input: R) -> io::Result<Image> {
+    let mut data = Vec::new();
+    input.read_to_end(&mut data)?;
+    if !data.starts_with(&SIGNATURE) {
+        return Err(invalid("not a PNG image"));
+    }
+
+    let mut header = None;
+    let mut palette: Vec<[u8; 4]> = Vec::new();
+    let mut transparent: Option<Vec<u8>> = None;
+    let mut compressed = Vec::new();
+    let mut pos = SIGNATURE.len();
+    loop {
+        let len = data.get(pos..pos + 4).ok_or_else(|| invalid("truncated PNG image"))?;
+        let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+        let end = pos.checked_add(12 + len).ok_or_else(|| invalid("truncated PNG image"))?;
+        let chunk = data.get(pos + 4..end).ok_or_else(|| invalid("truncated PNG image"))?;
+        let (kind, body, crc) = (&chunk[..4], &chunk[4..4 + len], &chunk[4 + len..]);
+        if crc32(&[kind, body]).to_be_bytes() != crc {
+            return Err(invalid("corrupt PNG chunk"));
+        }
+        match kind {
+            b"IHDR" => header = Some(parse_header(body)?),
+            b"PLTE" => {
+                palette = body.chunks_exact(3).map(|c| [c[0], c[1], c[2], 255]).collect();
+            },
+            b"tRNS" => transparent = Some(body.to_vec()),
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {},
+        }
+        pos = end;
+    }
+    let header = header.ok_or_else(|| invalid("missing PNG header"))?;
+
+    let row_len = header.row_len();
+    let mut raw = inflate(&compressed, (row_len + 1) * header.height)?;
+    if raw.len() < (row_len + 1) * header.height {
+        return Err(invalid("truncated image data"));
+    }
+
+    if header.color == 3 {
+        if let Some(ref alpha) = transparent {
+            for (entry, &a) in palette.iter_mut().zip(alpha) {
+                entry[3] = a;
+            }
+        }
+    }
+    // The gray or RGB value that is transparent, as 16-bit samples
+    let key: Option<Vec<u16>> = match (header.color, &transparent) {
+        (0, &Some(ref t)) | (2, &Some(ref t)) => {
+            Some(t.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect())
+        },
+        _ => None,
+    };
+
+    let bpp = (header.channels() * header.depth as usize).div_ceil(8);
+    let empty = vec![0; row_len];
+    let mut rgba = Vec::with_capacity(header.width * header.height * 4);
+    let mut samples = Vec::with_capacity(header.width * header.channels());
+    for y in 0..header.height {
+        let (done, rest) = raw.split_at_mut(y * (row_len + 1));
+        let previous = if y == 0 { &empty[..] } else { &done[done.len() - row_len..] };
+        let (filter, row) = rest[..row_len + 1].split_first_mut().unwrap();
+        unfilter(*filter, row, previous, bpp)?;
+
+        samples.clear();
+        match header.depth {
+            16 => samples.extend(row.chunks(2).map(|c| u16::from_be_bytes([c[0], c[1]]))),
+            8 => samples.extend(row.iter().map(|&b| b as u16)),
+            depth => {
+                let per_byte = 8 / depth;
+                let mask = (1u16 << depth) - 1;
+                for &byte in row.iter() {
+                    for n in (0..per_byte).rev() {
+                        samples.push((byte as u16 >> (n * depth)) & mask);
+                    }
+                }
+            },
+        }
+        let max = (1u32 << header.depth) - 1;
+        let scale = |s: u16| (s as u32 * 255 / max) as u8;
+        for pixel in samples.chunks(header.channels()).take(header.width) {
+            let transparent = key.as_ref().is_some_and(|k| k.as_slice() == pixel);
+            let alpha = if transparent { 0 } else { 255 };
+            match header.color {
+                0 => rgba.extend_from_slice(&[scale(pixel[0]), scale(pixel[0]), scale(pixel[0]), alpha]),
+                2 => rgba.extend_from_slice(&[scale(pixel[0]), scale(pixel[1]), scale(pixel[2]), alpha]),
+                3 => {
+                    let entry = palette.get(pixel[0] as usize).ok_or_else(|| invalid("invalid palette index"))?;
+                    rgba.extend_from_slice(entry);
+                },
+                4 => {
+                    let gray = scale(pixel[0]);
+                    rgba.extend_from_slice(&[gray, gray, gray, scale(pixel[1])]);
+                },
+                _ => rgba.extend(pixel.iter().map(|&s| scale(s))),
+            }
+        }
+    }
+    Ok(Image::from_rgba(header.width, header.height, rgba).expect("invalid PNG image size"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_sizes() {
+        let header = |width: u32, height: u32| {
+            let mut data = [0; 13];
+            data[..4].copy_from_slice(&width.to_be_bytes());
+            data[4..8].copy_from_slice(&height.to_be_bytes());
+            // 8-bit RGBA
+            data[8..10].copy_from_slice(&[8, 6]);
+            parse_header(&data).map(|header| (header.width, header.height))
+        };
+        assert_eq!(header(640, 480).unwrap(), (640, 480));
+        for &(width, height) in &[(0xFFFF_FFF0, 0), (0, 1), (1 << 17, 1), (1, u32::MAX), (1 << 16, 1 << 16)] {
+            let error = header(width, height).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+}
@@ -0,0 +1,49 @@
+//! Minimal JSON serialization helpers for reports and traces.
+
+use std::fmt::Write;
+
+/// Writes `s` as a quoted JSON string.
+pub fn write_str(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"'  => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            },
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Writes `s` as a quoted JSON string, or `null`.
+pub fn write_opt_str(out: &mut String, s: Option<&str>) {
+    match s {
+        Some(s) => write_str(out, s),
+        None => out.push_str("null"),
+    }
+}
+
+/// Writes `n` as a JSON number, or `null` if it is not finite.
+pub fn write_f64(out: &mut String, n: f64) {
+    if n.is_finite() {
+        let _ = write!(out, "{}", n);
+    } else {
+        out.push_str("null");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn write_str() {
+        let mut out = String::new();
+        super::write_str(&mut out, "a\"b\\c\n\u{1}");
+        assert_eq!(out, r#""a\"b\\c\n\u0001""#);
+    }
+}
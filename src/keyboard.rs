@@ -0,0 +1,1244 @@
+//! ⌨️ Cross-platform keyboard types and helpers.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use abort::Token;
+use dispatch;
+use error::{self, Error};
+use events::{self, InputEvent};
+use settings;
+use verify;
+
+macro_rules! keys {
+    ($($(#[$attr:meta])* $key:ident = $name:expr, $mac:expr, $win:expr, $linux:expr;)+) => {
+        /// A physical key, independent of the current operating system.
+        ///
+        /// Keys are identified by their position on a US keyboard; the
+        /// character they produce depends on the active keyboard layout.
+        #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        pub enum Key {
+            $($(#[$attr])* $key,)+
+            /// A key without a portable name, identified by its native key
+            /// code on the current operating system.
+            Other(u32),
+        }
+
+        /// Every named key.
+        const NAMED: &[Key] = &[$(Key::$key),+];
+
+        impl Key {
+            /// Returns the name of the key, or `None` for
+            /// [`Other`](#variant.Other).
+            pub fn name(self) -> Option<&'static str> {
+                match self {
+                    $(Key::$key => Some($name),)+
+                    Key::Other(_) => None,
+                }
+            }
+
+            #[allow(dead_code)]
+            fn mac_code(self) -> Option<u16> {
+                match self {
+                    $(Key::$key => $mac,)+
+                    Key::Other(code) => u16::try_from(code).ok(),
+                }
+            }
+
+            #[allow(dead_code)]
+            fn win_code(self) -> Option<u16> {
+                match self {
+                    $(Key::$key => $win,)+
+                    Key::Other(code) => u16::try_from(code).ok(),
+                }
+            }
+
+            #[allow(dead_code)]
+            fn linux_code(self) -> Option<u16> {
+                match self {
+                    $(Key::$key => $linux,)+
+                    Key::Other(code) => u16::try_from(code).ok(),
+                }
+            }
+        }
+    };
+}
+
+keys! {
+    /// The A key.
+    A = "a", Some(0x00), Some(0x41), Some(0x1E);
+    /// The B key.
+    B = "b", Some(0x0B), Some(0x42), Some(0x30);
+    /// The C key.
+    C = "c", Some(0x08), Some(0x43), Some(0x2E);
+    /// The D key.
+    D = "d", Some(0x02), Some(0x44), Some(0x20);
+    /// The E key.
+    E = "e", Some(0x0E), Some(0x45), Some(0x12);
+    /// The F key.
+    F = "f", Some(0x03), Some(0x46), Some(0x21);
+    /// The G key.
+    G = "g", Some(0x05), Some(0x47), Some(0x22);
+    /// The H key.
+    H = "h", Some(0x04), Some(0x48), Some(0x23);
+    /// The I key.
+    I = "i", Some(0x22), Some(0x49), Some(0x17);
+    /// The J key.
+    J = "j", Some(0x26), Some(0x4A), Some(0x24);
+    /// The K key.
+    K = "k", Some(0x28), Some(0x4B), Some(0x25);
+    /// The L key.
+    L = "l", Some(0x25), Some(0x4C), Some(0x26);
+    /// The M key.
+    M = "m", Some(0x2E), Some(0x4D), Some(0x32);
+    /// The N key.
+    N = "n", Some(0x2D), Some(0x4E), Some(0x31);
+    /// The O key.
+    O = "o", Some(0x1F), Some(0x4F), Some(0x18);
+    /// The P key.
+    P = "p", Some(0x23), Some(0x50), Some(0x19);
+    /// The Q key.
+    Q = "q", Some(0x0C), Some(0x51), Some(0x10);
+    /// The R key.
+    R = "r", Some(0x0F), Some(0x52), Some(0x13);
+    /// The S key.
+    S = "s", Some(0x01), Some(0x53), Some(0x1F);
+    /// The T key.
+    T = "t", Some(0x11), Some(0x54), Some(0x14);
+    /// The U key.
+    U = "u", Some(0x20), Some(0x55), Some(0x16);
+    /// The V key.
+    V = "v", Some(0x09), Some(0x56), Some(0x2F);
+    /// The W key.
+    W = "w", Some(0x0D), Some(0x57), Some(0x11);
+    /// The X key.
+    X = "x", Some(0x07), Some(0x58), Some(0x2D);
+    /// The Y key.
+    Y = "y", Some(0x10), Some(0x59), Some(0x15);
+    /// The Z key.
+    Z = "z", Some(0x06), Some(0x5A), Some(0x2C);
+
+    /// The 0 key on the main keyboard.
+    Num0 = "0", Some(0x1D), Some(0x30), Some(0x0B);
+    /// The 1 key on the main keyboard.
+    Num1 = "1", Some(0x12), Some(0x31), Some(0x02);
+    /// The 2 key on the main keyboard.
+    Num2 = "2", Some(0x13), Some(0x32), Some(0x03);
+    /// The 3 key on the main keyboard.
+    Num3 = "3", Some(0x14), Some(0x33), Some(0x04);
+    /// The 4 key on the main keyboard.
+    Num4 = "4", Some(0x15), Some(0x34), Some(0x05);
+    /// The 5 key on the main keyboard.
+    Num5 = "5", Some(0x17), Some(0x35), Some(0x06);
+    /// The 6 key on the main keyboard.
+    Num6 = "6", Some(0x16), Some(0x36), Some(0x07);
+    /// The 7 key on the main keyboard.
+    Num7 = "7", Some(0x1A), Some(0x37), Some(0x08);
+    /// The 8 key on the main keyboard.
+    Num8 = "8", Some(0x1C), Some(0x38), Some(0x09);
+    /// The 9 key on the main keyboard.
+    Num9 = "9", Some(0x19), Some(0x39), Some(0x0A);
+
+    /// The minus (`-`) key.
+    Minus = "minus", Some(0x1B), Some(0xBD), Some(0x0C);
+    /// The equal (`=`) key.
+    Equal = "equal", Some(0x18), Some(0xBB), Some(0x0D);
+    /// The left bracket (`[`) key.
+    LeftBracket = "left-bracket", Some(0x21), Some(0xDB), Some(0x1A);
+    /// The right bracket (`]`) key.
+    RightBracket = "right-bracket", Some(0x1E), Some(0xDD), Some(0x1B);
+    /// The backslash (`\`) key.
+    Backslash = "backslash", Some(0x2A), Some(0xDC), Some(0x2B);
+    /// The semicolon (`;`) key.
+    Semicolon = "semicolon", Some(0x29), Some(0xBA), Some(0x27);
+    /// The quote (`'`) key.
+    Quote = "quote", Some(0x27), Some(0xDE), Some(0x28);
+    /// The grave accent (`` ` ``) key.
+    Grave = "grave", Some(0x32), Some(0xC0), Some(0x29);
+    /// The comma (`,`) key.
+    Comma = "comma", Some(0x2B), Some(0xBC), Some(0x33);
+    /// The period (`.`) key.
+    Period = "period", Some(0x2F), Some(0xBE), Some(0x34);
+    /// The slash (`/`) key.
+    Slash = "slash", Some(0x2C), Some(0xBF), Some(0x35);
+
+    /// The Return or Enter key on the main keyboard.
+    Return = "return", Some(0x24), Some(0x0D), Some(0x1C);
+    /// The Tab key.
+    Tab = "tab", Some(0x30), Some(0x09), Some(0x0F);
+    /// The space bar.
+    Space = "space", Some(0x31), Some(0x20), Some(0x39);
+    /// The key that deletes backwards, labeled Delete on Apple keyboards.
+    Backspace = "backspace", Some(0x33), Some(0x08), Some(0x0E);
+    /// The key that deletes forwards.
+    Delete = "delete", Some(0x75), Some(0x2E), Some(0x6F);
+    /// The Escape key.
+    Escape = "escape", Some(0x35), Some(0x1B), Some(0x01);
+    /// The Insert key.
+    Insert = "insert", None, Some(0x2D), Some(0x6E);
+    /// The Help key.
+    Help = "help", Some(0x72), Some(0x2F), Some(0x8A);
+    /// The Home key.
+    Home = "home", Some(0x73), Some(0x24), Some(0x66);
+    /// The End key.
+    End = "end", Some(0x77), Some(0x23), Some(0x6B);
+    /// The Page Up key.
+    PageUp = "page-up", Some(0x74), Some(0x21), Some(0x68);
+    /// The Page Down key.
+    PageDown = "page-down", Some(0x79), Some(0x22), Some(0x6D);
+    /// The left arrow key.
+    LeftArrow = "left", Some(0x7B), Some(0x25), Some(0x69);
+    /// The right arrow key.
+    RightArrow = "right", Some(0x7C), Some(0x27), Some(0x6A);
+    /// The up arrow key.
+    UpArrow = "up", Some(0x7E), Some(0x26), Some(0x67);
+    /// The down arrow key.
+    DownArrow = "down", Some(0x7D), Some(0x28), Some(0x6C);
+    /// The Print Screen key.
+    PrintScreen = "print-screen", None, Some(0x2C), Some(0x63);
+
+    /// The left Shift key.
+    Shift = "shift", Some(0x38), Some(0xA0), Some(0x2A);
+    /// The right Shift key.
+    RightShift = "right-shift", Some(0x3C), Some(0xA1), Some(0x36);
+    /// The left Control key.
+    Control = "control", Some(0x3B), Some(0xA2), Some(0x1D);
+    /// The right Control key.
+    RightControl = "right-control", Some(0x3E), Some(0xA3), Some(0x61);
+    /// The left Alt key, labeled Option on Apple keyboards.
+    Alt = "alt", Some(0x3A), Some(0xA4), Some(0x38);
+    /// The right Alt key, labeled Option on Apple keyboards.
+    RightAlt = "right-alt", Some(0x3D), Some(0xA5), Some(0x64);
+    /// The left Command key on Apple keyboards, or Windows key otherwise.
+    Meta = "meta", Some(0x37), Some(0x5B), Some(0x7D);
+    /// The right Command key on Apple keyboards, or Windows key otherwise.
+    RightMeta = "right-meta", Some(0x36), Some(0x5C), Some(0x7E);
+    /// The Fn key found on Apple laptop keyboards.
+    Function = "function", Some(0x3F), None, Some(0x1D0);
+    /// The Caps Lock key.
+    CapsLock = "caps-lock", Some(0x39), Some(0x14), Some(0x3A);
+    /// The Num Lock key.
+    NumLock = "num-lock", None, Some(0x90), Some(0x45);
+    /// The Scroll Lock key.
+    ScrollLock = "scroll-lock", None, Some(0x91), Some(0x46);
+
+    /// The F1 key.
+    F1 = "f1", Some(0x7A), Some(0x70), Some(0x3B);
+    /// The F2 key.
+    F2 = "f2", Some(0x78), Some(0x71), Some(0x3C);
+    /// The F3 key.
+    F3 = "f3", Some(0x63), Some(0x72), Some(0x3D);
+    /// The F4 key.
+    F4 = "f4", Some(0x76), Some(0x73), Some(0x3E);
+    /// The F5 key.
+    F5 = "f5", Some(0x60), Some(0x74), Some(0x3F);
+    /// The F6 key.
+    F6 = "f6", Some(0x61), Some(0x75), Some(0x40);
+    /// The F7 key.
+    F7 = "f7", Some(0x62), Some(0x76), Some(0x41);
+    /// The F8 key.
+    F8 = "f8", Some(0x64), Some(0x77), Some(0x42);
+    /// The F9 key.
+    F9 = "f9", Some(0x65), Some(0x78), Some(0x43);
+    /// The F10 key.
+    F10 = "f10", Some(0x6D), Some(0x79), Some(0x44);
+    /// The F11 key.
+    F11 = "f11", Some(0x67), Some(0x7A), Some(0x57);
+    /// The F12 key.
+    F12 = "f12", Some(0x6F), Some(0x7B), Some(0x58);
+    /// The F13 key.
+    F13 = "f13", Some(0x69), Some(0x7C), Some(0xB7);
+    /// The F14 key.
+    F14 = "f14", Some(0x6B), Some(0x7D), Some(0xB8);
+    /// The F15 key.
+    F15 = "f15", Some(0x71), Some(0x7E), Some(0xB9);
+    /// The F16 key.
+    F16 = "f16", Some(0x6A), Some(0x7F), Some(0xBA);
+    /// The F17 key.
+    F17 = "f17", Some(0x40), Some(0x80), Some(0xBB);
+    /// The F18 key.
+    F18 = "f18", Some(0x4F), Some(0x81), Some(0xBC);
+    /// The F19 key.
+    F19 = "f19", Some(0x50), Some(0x82), Some(0xBD);
+    /// The F20 key.
+    F20 = "f20", Some(0x5A), Some(0x83), Some(0xBE);
+
+    /// The 0 key on the numeric keypad.
+    Keypad0 = "keypad-0", Some(0x52), Some(0x60), Some(0x52);
+    /// The 1 key on the numeric keypad.
+    Keypad1 = "keypad-1", Some(0x53), Some(0x61), Some(0x4F);
+    /// The 2 key on the numeric keypad.
+    Keypad2 = "keypad-2", Some(0x54), Some(0x62), Some(0x50);
+    /// The 3 key on the numeric keypad.
+    Keypad3 = "keypad-3", Some(0x55), Some(0x63), Some(0x51);
+    /// The 4 key on the numeric keypad.
+    Keypad4 = "keypad-4", Some(0x56), Some(0x64), Some(0x4B);
+    /// The 5 key on the numeric keypad.
+    Keypad5 = "keypad-5", Some(0x57), Some(0x65), Some(0x4C);
+    /// The 6 key on the numeric keypad.
+    Keypad6 = "keypad-6", Some(0x58), Some(0x66), Some(0x4D);
+    /// The 7 key on the numeric keypad.
+    Keypad7 = "keypad-7", Some(0x59), Some(0x67), Some(0x47);
+    /// The 8 key on the numeric keypad.
+    Keypad8 = "keypad-8", Some(0x5B), Some(0x68), Some(0x48);
+    /// The 9 key on the numeric keypad.
+    Keypad9 = "keypad-9", Some(0x5C), Some(0x69), Some(0x49);
+    /// The decimal point key on the numeric keypad.
+    KeypadDecimal = "keypad-decimal", Some(0x41), Some(0x6E), Some(0x53);
+    /// The plus key on the numeric keypad.
+    KeypadPlus = "keypad-plus", Some(0x45), Some(0x6B), Some(0x4E);
+    /// The minus key on the numeric keypad.
+    KeypadMinus = "keypad-minus", Some(0x4E), Some(0x6D), Some(0x4A);
+    /// The multiply key on the numeric keypad.
+    KeypadMultiply = "keypad-multiply", Some(0x43), Some(0x6A), Some(0x37);
+    /// The divide key on the numeric keypad.
+    KeypadDivide = "keypad-divide", Some(0x4B), Some(0x6F), Some(0x62);
+    /// The Enter key on the numeric keypad.
+    KeypadEnter = "keypad-enter", Some(0x4C), None, Some(0x60);
+    /// The equals key on the numeric keypad.
+    KeypadEquals = "keypad-equals", Some(0x51), None, Some(0x75);
+    /// The Clear key on the numeric keypad.
+    KeypadClear = "keypad-clear", Some(0x47), Some(0x0C), Some(0x163);
+
+    /// The volume up key.
+    VolumeUp = "volume-up", Some(0x48), Some(0xAF), Some(0x73);
+    /// The volume down key.
+    VolumeDown = "volume-down", Some(0x49), Some(0xAE), Some(0x72);
+    /// The mute key.
+    Mute = "mute", Some(0x4A), Some(0xAD), Some(0x71);
+}
+
+impl Key {
+    /// Returns an iterator over every key that has a portable name.
+    pub fn all() -> impl Iterator<Item = Key> {
+        NAMED.iter().cloned()
+    }
+
+    /// Returns the native virtual key code of `self` on the current operating
+    /// system, or `None` if the key does not exist there.
+    ///
+    /// On Linux, codes are evdev key codes, as in `linux/input-event-codes.h`.
+    pub fn native_code(self) -> Option<u16> {
+        if cfg!(target_os = "macos") {
+            self.mac_code()
+        } else if cfg!(target_os = "windows") {
+            self.win_code()
+        } else if cfg!(target_os = "linux") {
+            self.linux_code()
+        } else {
+            match self {
+                Key::Other(code) => u16::try_from(code).ok(),
+                _ => None,
+            }
+        }
+    }
+
+    /// Returns the key for the native virtual key code on the current
+    /// operating system.
+    ///
+    /// Codes without a portable name are returned as
+    /// [`Other`](#variant.Other).
+    pub fn from_native_code(code: u16) -> Key {
+        if cfg!(target_os = "windows") {
+            // Side-agnostic modifiers are reported by some Windows APIs
+            match code {
+                0x10 => return Key::Shift,
+                0x11 => return Key::Control,
+                0x12 => return Key::Alt,
+                _ => {},
+            }
+        }
+        Key::all()
+            .find(|key| key.native_code() == Some(code))
+            .unwrap_or(Key::Other(u32::from(code)))
+    }
+
+    /// Returns the modifier that `self` applies while held, or `None` if it
+    /// is not a Shift, Control, Alt, or Meta key.
+    pub fn modifier(self) -> Option<Modifiers> {
+        match self {
+            Key::Shift | Key::RightShift => Some(Modifiers::SHIFT),
+            Key::Control | Key::RightControl => Some(Modifiers::CONTROL),
+            Key::Alt | Key::RightAlt => Some(Modifiers::ALT),
+            Key::Meta | Key::RightMeta => Some(Modifiers::META),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the key is a modifier such as Shift or Control.
+    pub fn is_modifier(self) -> bool {
+        matches!(self,
+            Key::Shift | Key::RightShift |
+            Key::Control | Key::RightControl |
+            Key::Alt | Key::RightAlt |
+            Key::Meta | Key::RightMeta |
+            Key::Function)
+    }
+}
+
+bitflags! {
+    /// A set of modifier keys, independent of which side of the keyboard they
+    /// are on.
+    #[derive(Default)]
+    pub struct Modifiers: u8 {
+        /// Either Shift key.
+        const SHIFT   = 1 << 0;
+        /// Either Control key.
+        const CONTROL = 1 << 1;
+        /// Either Alt key, labeled Option on Apple keyboards.
+        const ALT     = 1 << 2;
+        /// Either Command key on Apple keyboards, or Windows key otherwise.
+        const META    = 1 << 3;
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Key::Other(code) => write!(f, "other-{}", code),
+            key => f.write_str(key.name().unwrap_or_default()),
+        }
+    }
+}
+
+/// An error returned when parsing a [`Key`](enum.Key.html) from its name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseKeyError(String);
+
+impl fmt::Display for ParseKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown key name {:?}", self.0)
+    }
+}
+
+impl ::std::error::Error for ParseKeyError {}
+
+impl FromStr for Key {
+    type Err = ParseKeyError;
+
+    fn from_str(s: &str) -> Result<Key, ParseKeyError> {
+        if let Some(code) = s.strip_prefix("other-") {
+            if let Ok(code) = code.parse() {
+                return Ok(Key::Other(code));
+            }
+        }
+        Key::all()
+            .find(|key| key.name() == Some(s))
+            .ok_or_else(|| ParseKeyError(s.into()))
+    }
+}
+
+/// Presses `keys` as a shortcut, such as `[Key::Meta, Key::Shift, Key::S]` for
+/// ⌘⇧S.
+///
+/// Every key but the last is held down in order, the last key is tapped, and
+/// the held keys are released in reverse order. Modifiers held this way are
+/// applied to the flags of the tapped key, as they are for any event sent
+/// through [`events::post`](../events/fn.post.html).
+///
+/// Keys that were pressed are released even if sending a later event fails.
+///
+/// # Examples
+///
+/// ```no_run
+/// use auto::keyboard::{self, Key};
+///
+/// keyboard::send_shortcut(&[Key::Meta, Key::Shift, Key::S]).unwrap();
+/// ```
+pub fn send_shortcut(keys: &[Key]) -> error::Result<()> {
+    let (&last, held) = match keys.split_last() {
+        Some(split) => split,
+        None => return Ok(()),
+    };
+    settings::paced(|| verify::verified("shortcut", || {
+        let mut pressed = 0;
+        let mut result = Ok(());
+        for &key in held {
+            result = events::post(&InputEvent::KeyDown(key));
+            if result.is_err() {
+                break;
+            }
+            pressed += 1;
+        }
+        if result.is_ok() {
+            result = events::post(&InputEvent::KeyDown(last))
+                .and_then(|()| events::post(&InputEvent::KeyUp(last)));
+        }
+        for &key in held[..pressed].iter().rev() {
+            let released = events::post(&InputEvent::KeyUp(key));
+            if result.is_ok() {
+                result = released;
+            }
+        }
+        result
+    }))
+}
+
+/// Holds `key` down for `duration`, repeating it as a physically held key
+/// would.
+///
+/// After the key-down, autorepeat events are sent at the user's key repeat
+/// delay and rate, and the key is released once `duration` has passed. On
+/// macOS, repeats are marked as autorepeat so applications can tell them from
+/// separate presses. Modifiers held through
+/// [`events::post`](../events/fn.post.html) apply to every repeat.
+///
+/// The key is released even if sending a repeat fails or the
+/// [kill switch](../abort/index.html) is pressed.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use auto::keyboard::{self, Key};
+///
+/// keyboard::hold(Key::Delete, Duration::from_secs(2)).unwrap();
+/// ```
+pub fn hold(key: Key, duration: Duration) -> error::Result<()> {
+    let (delay, interval) = repeat_timing();
+    settings::paced(|| verify::verified("hold", || {
+        let token = Token::new();
+        let start = Instant::now();
+        events::post(&InputEvent::KeyDown(key))?;
+        let mut result = Ok(());
+        let mut next = delay;
+        while next < duration {
+            result = token
+                .sleep(next.checked_sub(start.elapsed()).unwrap_or_default())
+                .and_then(|_| events::post_repeat(key));
+            if result.is_err() {
+                break;
+            }
+            next += interval;
+        }
+        if result.is_ok() {
+            result = token.sleep(duration.checked_sub(start.elapsed()).unwrap_or_default());
+        }
+        let released = events::post(&InputEvent::KeyUp(key));
+        result.and(released)
+    }))
+}
+
+/// Types `text` with the
+/// [`typing_delay`](../settings/struct.Settings.html#structfield.typing_delay)
+/// of the current settings between characters.
+///
+/// The delay is zero by default, which types as fast as events can be
+/// posted.
+///
+/// # Examples
+///
+/// ```no_run
+/// use auto::keyboard;
+///
+/// keyboard::type_text("hello, world\n").unwrap();
+/// ```
+pub fn type_text(text: &str) -> error::Result<()> {
+    let zero = Duration::from_secs(0);
+    Typing::new(settings::current().typing_delay).hesitations(0.0, zero..zero).send(text)
+}
+
+/// Types `text` with a pause of `base_delay`, give or take up to `jitter`,
+/// between characters, and an occasional longer hesitation.
+///
+/// This is a shorthand for [`Typing`](struct.Typing.html) with its default
+/// hesitations.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use auto::keyboard;
+///
+/// let base = Duration::from_millis(120);
+/// let jitter = Duration::from_millis(60);
+/// keyboard::type_text_with_delay("hello, world", base, jitter).unwrap();
+/// ```
+pub fn type_text_with_delay(text: &str, base_delay: Duration, jitter: Duration) -> error::Result<()> {
+    Typing::new(base_delay).jitter(jitter).send(text)
+}
+
+/// Typing of text at a human-like, uneven pace.
+///
+/// Each character is typed as its own key press, independent of the keyboard
+/// layout, except for newlines and tabs, which press Return and Tab.
+/// Characters are posted as [`InputEvent::Char`](../events/enum.InputEvent.html#variant.Char)
+/// through [`events::post`](../events/fn.post.html), so the deny list and
+/// rate limit apply to them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Typing {
+    base_delay: Duration,
+    jitter: Duration,
+    hesitation_chance: f64,
+    hesitation: Range<Duration>,
+}
+
+impl Typing {
+    /// The default chance that a character is followed by a hesitation.
+    pub const DEFAULT_HESITATION_CHANCE: f64 = 0.03;
+
+    /// Creates typing that pauses for `base_delay` between characters, without
+    /// jitter, and with the default hesitations of a quarter to three quarters
+    /// of a second.
+    pub fn new(base_delay: Duration) -> Typing {
+        Typing {
+            base_delay,
+            jitter: Duration::from_secs(0),
+            hesitation_chance: Typing::DEFAULT_HESITATION_CHANCE,
+            hesitation: Duration::from_millis(250)..Duration::from_millis(750),
+        }
+    }
+
+    /// Sets how far each pause may randomly fall short of or exceed the base
+    /// delay.
+    pub fn jitter(&mut self, jitter: Duration) -> &mut Typing {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Sets the chance from `0.0` to `1.0` that a character is followed by an
+    /// extra pause within `pause`, as when a person stops to think.
+    ///
+    /// A chance of zero disables hesitations.
+    pub fn hesitations(&mut self, chance: f64, pause: Range<Duration>) -> &mut Typing {
+        self.hesitation_chance = chance;
+        self.hesitation = pause;
+        self
+    }
+
+    /// Types `text`, returning once the last character is typed.
+    ///
+    /// Stops early if the [kill switch](../abort/index.html) is pressed.
+    pub fn send(&self, text: &str) -> error::Result<()> {
+        settings::paced(|| verify::verified("type_text", || {
+            let token = Token::new();
+            for (i, c) in text.chars().enumerate() {
+                if i > 0 {
+                    token.sleep(self.next_delay())?;
+                }
+                match c {
+                    '\n' => send_shortcut(&[Key::Return])?,
+                    '\t' => send_shortcut(&[Key::Tab])?,
+                    c => events::post(&InputEvent::Char(c))?,
+                }
+            }
+            Ok(())
+        }))
+    }
+
+    /// Returns a randomly chosen pause to take before the next character.
+    fn next_delay(&self) -> Duration {
+        let min = self.base_delay.checked_sub(self.jitter).unwrap_or_default();
+        let mut delay = dispatch::random_between(min, self.base_delay + self.jitter);
+        if dispatch::random_chance(self.hesitation_chance) {
+            delay += dispatch::random_between(self.hesitation.start, self.hesitation.end);
+        }
+        delay
+    }
+}
+
+/// A control character as a terminal receives it, such as the interrupt sent
+/// by Ctrl+C.
+///
+/// Control characters have no portable text form, so they are sent as the
+/// key combination that a terminal translates into them.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum ControlChar {
+    /// Control held with a key, such as Ctrl+C to interrupt or Ctrl+Z to
+    /// suspend.
+    Ctrl(Key),
+    /// The escape character that begins escape sequences, from the Escape
+    /// key.
+    Escape,
+    /// A horizontal tab, from the Tab key.
+    Tab,
+    /// A newline, from the Return key.
+    Newline,
+    /// The character that erases backwards, from the Backspace key.
+    Backspace,
+}
+
+impl ControlChar {
+    /// Returns the control character for an ASCII control code, or `None` if
+    /// it cannot be typed without Shift on a US keyboard.
+    ///
+    /// Codes that a key sends on its own, such as `\t` and `\x1b`, are
+    /// mapped to that key rather than to their equivalent Control
+    /// combination. Both `\n` and `\r` are mapped to
+    /// [`Newline`](#variant.Newline).
+    pub fn from_ascii(code: u8) -> Option<ControlChar> {
+        match code {
+            b'\t' => Some(ControlChar::Tab),
+            b'\n' | b'\r' => Some(ControlChar::Newline),
+            0x1B => Some(ControlChar::Escape),
+            0x7F => Some(ControlChar::Backspace),
+            0x00 => Some(ControlChar::Ctrl(Key::Space)),
+            0x01..=0x1A => {
+                let letter = char::from(b'a' + code - 1).to_string();
+                letter.parse().ok().map(ControlChar::Ctrl)
+            },
+            0x1C => Some(ControlChar::Ctrl(Key::Backslash)),
+            0x1D => Some(ControlChar::Ctrl(Key::RightBracket)),
+            _ => None,
+        }
+    }
+
+    /// Returns the key that is tapped to send the character.
+    pub fn key(self) -> Key {
+        match self {
+            ControlChar::Ctrl(key) => key,
+            ControlChar::Escape => Key::Escape,
+            ControlChar::Tab => Key::Tab,
+            ControlChar::Newline => Key::Return,
+            ControlChar::Backspace => Key::Backspace,
+        }
+    }
+
+    /// Returns the modifiers held while the key is tapped.
+    pub fn modifiers(self) -> Modifiers {
+        match self {
+            ControlChar::Ctrl(_) => Modifiers::CONTROL,
+            _ => Modifiers::empty(),
+        }
+    }
+
+    /// Returns the keys to send as a shortcut.
+    fn shortcut(self) -> Vec<Key> {
+        if self.modifiers().contains(Modifiers::CONTROL) {
+            vec![Key::Control, self.key()]
+        } else {
+            vec![self.key()]
+        }
+    }
+}
+
+/// Types `chars` into the focused terminal, one key combination each.
+///
+/// Control combinations use the Control key on every platform, including
+/// macOS, where terminals do not treat Command as Control.
+///
+/// # Examples
+///
+/// Interrupt the running command, then send an Escape-prefixed `b`, which
+/// many shells interpret as Meta+B:
+///
+/// ```no_run
+/// use auto::keyboard::{self, ControlChar, Key};
+///
+/// keyboard::type_raw(&[ControlChar::Ctrl(Key::C)]).unwrap();
+///
+/// keyboard::type_raw(&[ControlChar::Escape]).unwrap();
+/// keyboard::send_shortcut(&[Key::B]).unwrap();
+/// ```
+pub fn type_raw(chars: &[ControlChar]) -> error::Result<()> {
+    settings::paced(|| verify::verified("type_raw", || {
+        for &c in chars {
+            send_shortcut(&c.shortcut())?;
+        }
+        Ok(())
+    }))
+}
+
+/// A chain of shortcuts and control characters sent one step at a time.
+///
+/// A sequence can be made to stop as soon as keyboard focus moves to another
+/// application or element, so that the rest of a command is not typed into
+/// whatever took focus.
+///
+/// # Examples
+///
+/// Clear the shell's line and run `ls -la`, unless a notification or dialog
+/// takes focus first:
+///
+/// ```no_run
+/// use auto::keyboard::{ControlChar, Key, Sequence};
+///
+/// Sequence::new()
+///     .raw(&[ControlChar::Ctrl(Key::U)])
+///     .text("ls -la\n")
+///     .abort_on_focus_change(true)
+///     .send()
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Sequence {
+    steps: Vec<Step>,
+    abort_on_focus_change: bool,
+}
+
+/// A single step of a [`Sequence`](struct.Sequence.html).
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Step {
+    Keys(Vec<Key>),
+    Char(char),
+}
+
+impl Sequence {
+    /// Creates an empty sequence that does not watch focus.
+    #[inline]
+    pub fn new() -> Sequence {
+        Sequence::default()
+    }
+
+    /// Appends a tap of `key`.
+    pub fn key(&mut self, key: Key) -> &mut Sequence {
+        self.steps.push(Step::Keys(vec![key]));
+        self
+    }
+
+    /// Appends a shortcut, sent as by [`send_shortcut`](fn.send_shortcut.html).
+    pub fn shortcut(&mut self, keys: &[Key]) -> &mut Sequence {
+        if !keys.is_empty() {
+            self.steps.push(Step::Keys(keys.to_vec()));
+        }
+        self
+    }
+
+    /// Appends control characters, sent as by [`type_raw`](fn.type_raw.html).
+    pub fn raw(&mut self, chars: &[ControlChar]) -> &mut Sequence {
+        self.steps.extend(chars.iter().map(|c| Step::Keys(c.shortcut())));
+        self
+    }
+
+    /// Appends `text`, typed as by [`type_text`](fn.type_text.html) with one
+    /// step per character, so that focus is checked before each of them.
+    pub fn text(&mut self, text: &str) -> &mut Sequence {
+        self.steps.extend(text.chars().map(|c| match c {
+            '\n' => Step::Keys(vec![Key::Return]),
+            '\t' => Step::Keys(vec![Key::Tab]),
+            c => Step::Char(c),
+        }));
+        self
+    }
+
+    /// Sets whether the sequence stops with
+    /// [`Error::FocusChanged`](../error/enum.Error.html#variant.FocusChanged)
+    /// if the frontmost application or the element with keyboard focus
+    /// changes between steps.
+    ///
+    /// Focus is compared to where it was when the sequence started, so steps
+    /// that are meant to move focus belong in a separate sequence. On macOS,
+    /// changes within an application are only noticed if the process is
+    /// trusted for accessibility.
+    pub fn abort_on_focus_change(&mut self, abort: bool) -> &mut Sequence {
+        self.abort_on_focus_change = abort;
+        self
+    }
+
+    /// Sends each step in order, stopping at the first error.
+    ///
+    /// Characters of [`text`](#method.text) are separated by the
+    /// [`typing_delay`](../settings/struct.Settings.html#structfield.typing_delay)
+    /// of the current settings.
+    pub fn send(&self) -> error::Result<()> {
+        settings::paced(|| verify::verified("sequence", || {
+            let token = Token::new();
+            let delay = settings::current().typing_delay;
+            let mut typed = false;
+            self.run(verify::focus_state, |step| match *step {
+                Step::Keys(ref keys) => {
+                    typed = false;
+                    send_shortcut(keys)
+                },
+                Step::Char(c) => {
+                    if typed {
+                        token.sleep(delay)?;
+                    }
+                    typed = true;
+                    events::post(&InputEvent::Char(c))
+                },
+            })
+        }))
+    }
+
+    /// Sends each step with `send`, first comparing `focus` to where it was
+    /// at the start if the sequence aborts on focus changes.
+    fn run<S, F, P>(&self, mut focus: F, mut send: P) -> error::Result<()>
+        where S: PartialEq, F: FnMut() -> error::Result<S>, P: FnMut(&Step) -> error::Result<()>
+    {
+        let start = if self.abort_on_focus_change {
+            Some(focus()?)
+        } else {
+            None
+        };
+        for step in &self.steps {
+            if let Some(ref start) = start {
+                if focus()? != *start {
+                    return Err(Error::FocusChanged);
+                }
+            }
+            send(step)?;
+        }
+        Ok(())
+    }
+}
+
+/// A key that controls media playback or the system rather than typing.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum MediaKey {
+    /// Plays or pauses the current media.
+    PlayPause,
+    /// Skips to the next track.
+    Next,
+    /// Goes back to the previous track.
+    Previous,
+    /// Raises the volume.
+    VolumeUp,
+    /// Lowers the volume.
+    VolumeDown,
+    /// Mutes or unmutes the sound.
+    Mute,
+    /// Raises the display brightness. Only supported on macOS.
+    BrightnessUp,
+    /// Lowers the display brightness. Only supported on macOS.
+    BrightnessDown,
+    /// Shows Mission Control. Only supported on macOS.
+    MissionControl,
+}
+
+/// Every media key.
+const MEDIA_KEYS: &[MediaKey] = &[
+    MediaKey::PlayPause,
+    MediaKey::Next,
+    MediaKey::Previous,
+    MediaKey::VolumeUp,
+    MediaKey::VolumeDown,
+    MediaKey::Mute,
+    MediaKey::BrightnessUp,
+    MediaKey::BrightnessDown,
+    MediaKey::MissionControl,
+];
+
+impl MediaKey {
+    /// Returns the name of the key, such as `"play-pause"`.
+    pub fn name(self) -> &'static str {
+        match self {
+            MediaKey::PlayPause => "play-pause",
+            MediaKey::Next => "next",
+            MediaKey::Previous => "previous",
+            MediaKey::VolumeUp => "volume-up",
+            MediaKey::VolumeDown => "volume-down",
+            MediaKey::Mute => "mute",
+            MediaKey::BrightnessUp => "brightness-up",
+            MediaKey::BrightnessDown => "brightness-down",
+            MediaKey::MissionControl => "mission-control",
+        }
+    }
+}
+
+impl fmt::Display for MediaKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for MediaKey {
+    type Err = ParseKeyError;
+
+    fn from_str(s: &str) -> Result<MediaKey, ParseKeyError> {
+        MEDIA_KEYS.iter()
+            .copied()
+            .find(|key| key.name() == s)
+            .ok_or_else(|| ParseKeyError(s.into()))
+    }
+}
+
+/// Presses and releases the media or system key `key`, by posting
+/// [`InputEvent::Media`](../events/enum.InputEvent.html#variant.Media).
+///
+/// On macOS, most of these keys are not key events at all but
+/// system-defined events, which is what keyboards with media keys send. On
+/// Windows, they are sent as `VK_MEDIA_*` and `VK_VOLUME_*` virtual keys.
+///
+/// Returns [`Error::Unsupported`](../error/enum.Error.html#variant.Unsupported)
+/// for keys that the current platform lacks.
+///
+/// # Examples
+///
+/// ```no_run
+/// use auto::keyboard::{self, MediaKey};
+///
+/// keyboard::media(MediaKey::PlayPause).unwrap();
+/// ```
+pub fn media(key: MediaKey) -> error::Result<()> {
+    settings::paced(|| verify::verified("media", || events::post(&InputEvent::Media(key))))
+}
+
+/// Returns whether `key` is currently held down, whether by the user or by
+/// events that any application posted.
+///
+/// Returns [`Error::Unsupported`](../error/enum.Error.html#variant.Unsupported)
+/// for keys that the current platform lacks.
+///
+/// # Examples
+///
+/// Wait for the user to let go of the modifiers before sending a shortcut,
+/// so that they do not combine with it:
+///
+/// ```no_run
+/// use std::thread;
+/// use std::time::Duration;
+/// use auto::keyboard::{self, Key};
+///
+/// let modifiers = [Key::Shift, Key::Control, Key::Alt, Key::Meta];
+/// while modifiers.iter().any(|&key| keyboard::is_pressed(key).unwrap_or(false)) {
+///     thread::sleep(Duration::from_millis(20));
+/// }
+/// keyboard::send_shortcut(&[Key::Meta, Key::S]).unwrap();
+/// ```
+pub fn is_pressed(key: Key) -> error::Result<bool> {
+    let code = key.native_code().ok_or(Error::Unsupported)?;
+    imp::is_pressed(code)
+}
+
+/// Returns whether Caps Lock is on.
+///
+/// Text typed with [`Typing`](struct.Typing.html) is unaffected by Caps
+/// Lock, but shortcuts and key presses produce uppercase letters while it is
+/// on.
+///
+/// # Examples
+///
+/// ```no_run
+/// use auto::keyboard;
+///
+/// if keyboard::is_caps_lock_on().unwrap() {
+///     keyboard::set_caps_lock(false).unwrap();
+/// }
+/// ```
+#[inline]
+pub fn is_caps_lock_on() -> error::Result<bool> {
+    imp::is_lock_on(Key::CapsLock)
+}
+
+/// Turns Caps Lock on or off.
+#[inline]
+pub fn set_caps_lock(on: bool) -> error::Result<()> {
+    imp::set_lock(Key::CapsLock, on)
+}
+
+/// Returns whether Num Lock is on, which makes the numeric keypad type
+/// digits rather than move the cursor.
+///
+/// Apple keyboards have no Num Lock, so on macOS this returns
+/// [`Error::Unsupported`](../error/enum.Error.html#variant.Unsupported)
+/// unless a keyboard with one is connected.
+#[inline]
+pub fn is_num_lock_on() -> error::Result<bool> {
+    imp::is_lock_on(Key::NumLock)
+}
+
+/// Turns Num Lock on or off.
+#[inline]
+pub fn set_num_lock(on: bool) -> error::Result<()> {
+    imp::set_lock(Key::NumLock, on)
+}
+
+/// Returns whether Scroll Lock is on. Only supported on Windows.
+#[inline]
+pub fn is_scroll_lock_on() -> error::Result<bool> {
+    imp::is_lock_on(Key::ScrollLock)
+}
+
+/// Turns Scroll Lock on or off. Only supported on Windows.
+#[inline]
+pub fn set_scroll_lock(on: bool) -> error::Result<()> {
+    imp::set_lock(Key::ScrollLock, on)
+}
+
+/// The repeat delay and interval used when the system's cannot be read.
+const DEFAULT_REPEAT: (Duration, Duration) = (Duration::from_millis(500), Duration::from_millis(33));
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn repeat_timing() -> (Duration, Duration) {
+    use os::keyboard;
+    match (keyboard::repeat_delay(), keyboard::repeat_interval()) {
+        (Ok(delay), Ok(interval)) if delay > 0.0 && interval > 0.0 => {
+            (Duration::from_secs_f64(delay), Duration::from_secs_f64(interval))
+        },
+        _ => DEFAULT_REPEAT,
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn repeat_timing() -> (Duration, Duration) {
+    DEFAULT_REPEAT
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use error::{Error, Result};
+    use os::keyboard::{self, ModifierLock};
+    use os::source::SourceState;
+    use super::Key;
+
+    pub fn is_pressed(code: u16) -> Result<bool> {
+        Ok(SourceState::CombinedSession.is_key_down(code))
+    }
+
+    fn modifier_lock(key: Key) -> Result<ModifierLock> {
+        match key {
+            Key::CapsLock => Ok(ModifierLock::CapsLock),
+            Key::NumLock => Ok(ModifierLock::NumLock),
+            _ => Err(Error::Unsupported),
+        }
+    }
+
+    pub fn is_lock_on(key: Key) -> Result<bool> {
+        keyboard::modifier_lock_state(modifier_lock(key)?)
+    }
+
+    pub fn set_lock(key: Key, on: bool) -> Result<()> {
+        keyboard::set_modifier_lock_state(modifier_lock(key)?, on)
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use error::{Error, Result};
+    use os::keyboard::{self, Event};
+    use super::Key;
+
+    pub fn is_pressed(code: u16) -> Result<bool> {
+        Ok(keyboard::is_pressed(code))
+    }
+
+    pub fn is_lock_on(key: Key) -> Result<bool> {
+        let vk = key.win_code().ok_or(Error::Unsupported)?;
+        Ok(keyboard::is_toggled(vk))
+    }
+
+    pub fn set_lock(key: Key, on: bool) -> Result<()> {
+        if is_lock_on(key)? == on {
+            return Ok(());
+        }
+        // The lock can only be toggled, by pressing its key
+        let vk = key.win_code().ok_or(Error::Unsupported)?;
+        keyboard::send(&[Event::new(vk, true), Event::new(vk, false)])
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod imp {
+    use error::{Error, Result};
+    use super::Key;
+
+    pub fn is_pressed(_: u16) -> Result<bool> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn is_lock_on(_: Key) -> Result<bool> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn set_lock(_: Key, _: bool) -> Result<()> {
+        Err(Error::Unsupported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_round_trip() {
+        for key in Key::all().chain(Some(Key::Other(300))) {
+            assert_eq!(key.to_string().parse::<Key>(), Ok(key));
+        }
+        assert!("bogus".parse::<Key>().is_err());
+    }
+
+    #[test]
+    fn media_name_round_trip() {
+        for &key in MEDIA_KEYS {
+            assert_eq!(key.to_string().parse::<MediaKey>(), Ok(key));
+        }
+        assert!("play".parse::<MediaKey>().is_err());
+    }
+
+    #[test]
+    fn unique_codes() {
+        for a in Key::all() {
+            for b in Key::all().filter(|&b| b != a) {
+                assert!(a.name() != b.name());
+                assert!(a.mac_code().is_none() || a.mac_code() != b.mac_code(), "{} {}", a, b);
+                assert!(a.win_code().is_none() || a.win_code() != b.win_code(), "{} {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn other_codes() {
+        assert_eq!(Key::Other(300).native_code(), Some(300));
+        // Codes beyond what the platforms take are rejected rather than cut
+        // down to another key
+        let a = Key::A.native_code().map_or(0, u32::from);
+        for key in &[Key::Other(0x1_0000 + a), Key::Other(u32::MAX)] {
+            assert_eq!(key.native_code(), None);
+            assert_eq!((key.mac_code(), key.win_code(), key.linux_code()), (None, None, None));
+        }
+    }
+
+    #[test]
+    fn control_chars() {
+        assert_eq!(ControlChar::from_ascii(0x03), Some(ControlChar::Ctrl(Key::C)));
+        assert_eq!(ControlChar::from_ascii(0x1A), Some(ControlChar::Ctrl(Key::Z)));
+        assert_eq!(ControlChar::from_ascii(b'\t'), Some(ControlChar::Tab));
+        assert_eq!(ControlChar::from_ascii(b'\r'), Some(ControlChar::Newline));
+        assert_eq!(ControlChar::from_ascii(0x1B), Some(ControlChar::Escape));
+        assert_eq!(ControlChar::from_ascii(0x1E), None);
+        assert_eq!(ControlChar::from_ascii(b'a'), None);
+        assert_eq!(ControlChar::Newline.key(), Key::Return);
+        assert!(ControlChar::Tab.modifiers().is_empty());
+    }
+
+    #[test]
+    fn sequence_steps() {
+        let mut sequence = Sequence::new();
+        sequence.raw(&[ControlChar::Ctrl(Key::U)]).text("ls\n").shortcut(&[]);
+        assert_eq!(sequence.steps, [
+            Step::Keys(vec![Key::Control, Key::U]),
+            Step::Char('l'),
+            Step::Char('s'),
+            Step::Keys(vec![Key::Return]),
+        ]);
+    }
+
+    #[test]
+    fn sequence_focus_change() {
+        let mut sequence = Sequence::new();
+        sequence.text("abcd");
+
+        // Focus moves after the second character is typed
+        let run = |sequence: &Sequence| {
+            let sent = ::std::cell::Cell::new(0);
+            let result = sequence.run(|| Ok(sent.get() >= 2), |_| {
+                sent.set(sent.get() + 1);
+                Ok(())
+            });
+            (result, sent.get())
+        };
+        assert_eq!(run(&sequence), (Ok(()), 4));
+
+        sequence.abort_on_focus_change(true);
+        assert_eq!(run(&sequence), (Err(Error::FocusChanged), 2));
+    }
+
+    #[test]
+    fn typing_delays() {
+        let ms = Duration::from_millis;
+        let mut typing = Typing::new(ms(20));
+        typing.jitter(ms(30)).hesitations(0.0, ms(0)..ms(0));
+        for _ in 0..100 {
+            assert!(typing.next_delay() < ms(50));
+        }
+        typing.jitter(ms(0)).hesitations(1.0, ms(100)..ms(200));
+        for _ in 0..100 {
+            let delay = typing.next_delay();
+            assert!(delay >= ms(120) && delay < ms(220), "{:?}", delay);
+        }
+    }
+}
@@ -33,24 +33,29 @@
 //!
 //! [crate]: https://crates.io/crates/auto
 
-#![cfg_attr(all(test, nightly), feature(test))]
-
 #![allow(unknown_lints)]
 #![deny(missing_docs)]
 
-#[cfg(all(test, nightly))]
-extern crate test;
-
 #[macro_use]
 extern crate bitflags;
 
+#[cfg(target_os = "macos")]
 #[macro_use]
 extern crate cfg_if;
 
 #[macro_use]
 extern crate lazy_static;
 
-#[cfg(target_os = "macos")]
+#[cfg(feature = "tokio")]
+extern crate futures_core;
+
+#[cfg(feature = "metrics")]
+extern crate metrics as metrics_crate;
+
+#[cfg(feature = "tokio")]
+extern crate tokio;
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
 extern crate libc;
 
 #[cfg(target_os = "macos")]
@@ -58,11 +63,56 @@ extern crate libc;
 extern crate objc;
 
 #[cfg(target_os = "windows")]
+#[macro_use]
 extern crate winapi;
 
+pub mod abort;
+#[cfg(feature = "agent")]
+pub mod agent;
+pub mod annotate;
+pub mod audio;
+#[cfg(feature = "cluster")]
+pub mod cluster;
 pub mod color;
+pub mod dispatch;
+pub mod error;
+pub mod events;
+pub mod hotkey;
+pub mod idle;
+pub mod image;
+pub mod keyboard;
+pub mod metrics;
+pub mod mirror;
+pub mod mouse;
 pub mod os;
+pub mod power;
+pub mod record;
+pub mod safety;
+pub mod schedule;
+pub mod screen;
+pub mod script;
+pub mod session;
+pub mod session_recorder;
+pub mod settings;
+pub mod trace;
+pub mod transform;
+pub mod ui;
+pub mod verify;
+pub mod vision;
+
+pub use error::{Error, Result};
+pub use mouse::scroll;
+pub use settings::Settings;
 
+mod json;
+#[cfg(any(feature = "agent", feature = "cluster"))]
+mod protocol;
+
+#[cfg(feature = "bench-internals")]
+#[doc(hidden)]
+pub mod bench_internals;
+
+#[cfg(target_os = "macos")]
 mod private {
     pub trait Priv {}
 }
@@ -0,0 +1,142 @@
+//! 📊 Instrumentation hooks for monitoring the crate in production.
+//!
+//! Install a [`Recorder`](trait.Recorder.html) with
+//! [`set_recorder`](fn.set_recorder.html) to be told about events posted,
+//! screens captured, and event tap callbacks run. Until one is installed,
+//! nothing is measured.
+//!
+//! With the `metrics` feature enabled,
+//! [`MetricsRecorder`](struct.MetricsRecorder.html) forwards everything to
+//! the recorder installed for the [`metrics`](https://docs.rs/metrics) crate,
+//! so it shows up alongside the rest of an application's metrics.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::atomic::{AtomicU64, Ordering};
+//! use auto::metrics::{self, Recorder};
+//!
+//! struct Posted(AtomicU64);
+//!
+//! impl Recorder for Posted {
+//!     fn events_posted(&self, count: u64) {
+//!         self.0.fetch_add(count, Ordering::Relaxed);
+//!     }
+//! }
+//!
+//! metrics::set_recorder(Posted(AtomicU64::new(0)));
+//! # metrics::clear_recorder();
+//! ```
+
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// Receives measurements from the crate.
+///
+/// Every method does nothing by default, so implementations only need to
+/// override the measurements they are interested in. Methods are called on
+/// whichever thread made the measurement and should return quickly.
+pub trait Recorder: Send + Sync {
+    /// Called after `count` events are posted through
+    /// [`events::post`](../events/fn.post.html) or its variants.
+    fn events_posted(&self, count: u64) {
+        let _ = count;
+    }
+
+    /// Called with the time the operating system took to accept a posted
+    /// event.
+    fn post_latency(&self, duration: Duration) {
+        let _ = duration;
+    }
+
+    /// Called with the time taken to capture the screen or part of it.
+    fn capture_duration(&self, duration: Duration) {
+        let _ = duration;
+    }
+
+    /// Called with the time spent in the callback of an event tap, which
+    /// delays every event passing through the tap.
+    fn tap_callback_duration(&self, duration: Duration) {
+        let _ = duration;
+    }
+}
+
+/// A recorder that ignores every measurement.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NoopRecorder;
+
+impl Recorder for NoopRecorder {}
+
+lazy_static! {
+    static ref RECORDER: RwLock<Option<Arc<dyn Recorder>>> = RwLock::new(None);
+}
+
+/// Installs `recorder` to receive measurements, replacing any previous one.
+pub fn set_recorder<R: Recorder + 'static>(recorder: R) {
+    *RECORDER.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(recorder));
+}
+
+/// Removes the installed recorder, if any.
+pub fn clear_recorder() {
+    *RECORDER.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+fn recorder() -> Option<Arc<dyn Recorder>> {
+    RECORDER.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Calls `f` with the installed recorder, if any.
+#[inline]
+pub(crate) fn record<F: FnOnce(&dyn Recorder)>(f: F) {
+    if let Some(recorder) = recorder() {
+        f(&*recorder);
+    }
+}
+
+/// Runs `f`, then calls `report` with the installed recorder and how long `f`
+/// took. Nothing is timed if no recorder is installed.
+#[inline]
+pub(crate) fn timed<T, F, R>(report: R, f: F) -> T
+    where F: FnOnce() -> T, R: FnOnce(&dyn Recorder, Duration)
+{
+    let recorder = match recorder() {
+        Some(recorder) => recorder,
+        None => return f(),
+    };
+    let start = Instant::now();
+    let value = f();
+    report(&*recorder, start.elapsed());
+    value
+}
+
+/// A recorder that forwards measurements to the
+/// [`metrics`](https://docs.rs/metrics) crate.
+///
+/// Counts are reported as counters and durations as histograms in seconds:
+///
+/// - `auto_events_posted_total`
+/// - `auto_post_latency_seconds`
+/// - `auto_capture_duration_seconds`
+/// - `auto_tap_callback_duration_seconds`
+#[cfg(feature = "metrics")]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MetricsRecorder;
+
+#[cfg(feature = "metrics")]
+impl Recorder for MetricsRecorder {
+    fn events_posted(&self, count: u64) {
+        metrics_crate::counter!("auto_events_posted_total").increment(count);
+    }
+
+    fn post_latency(&self, duration: Duration) {
+        metrics_crate::histogram!("auto_post_latency_seconds").record(duration);
+    }
+
+    fn capture_duration(&self, duration: Duration) {
+        metrics_crate::histogram!("auto_capture_duration_seconds").record(duration);
+    }
+
+    fn tap_callback_duration(&self, duration: Duration) {
+        metrics_crate::histogram!("auto_tap_callback_duration_seconds").record(duration);
+    }
+}
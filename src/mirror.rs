@@ -0,0 +1,304 @@
+//! 🪞 Mirroring events between displays with different layouts.
+//!
+//! A [`Mapping`](struct.Mapping.html) carries events from one display's
+//! coordinate space to another's, accounting for differences in position,
+//! resolution, and rotation. A point keeps its position relative to the
+//! displayed content, so a click at the center of a window's title bar on one
+//! display lands at the same spot on the other.
+//!
+//! Mappings can be applied to events as they are replayed, or to a whole
+//! recording before it is saved or sent to another machine.
+//!
+//! # Examples
+//!
+//! ```
+//! use auto::mirror::{Mapping, Rotation, Space};
+//!
+//! let laptop = Space::new((0.0, 0.0), (1440.0, 900.0));
+//! let portrait = Space::new((1440.0, 0.0), (1080.0, 1920.0))
+//!     .rotated(Rotation::Quarter);
+//!
+//! let mapping = Mapping::new(laptop, portrait);
+//! assert_eq!(mapping.map_location((0.0, 0.0)), (2520.0, 0.0));
+//! ```
+
+use events::{Gesture, InputEvent, Location};
+use record::Recording;
+
+/// A clockwise rotation of a display's content by a multiple of 90 degrees.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Rotation {
+    /// The content is upright.
+    None,
+    /// The content is rotated by 90 degrees.
+    Quarter,
+    /// The content is upside down.
+    Half,
+    /// The content is rotated by 270 degrees.
+    ThreeQuarters,
+}
+
+impl Default for Rotation {
+    #[inline]
+    fn default() -> Rotation {
+        Rotation::None
+    }
+}
+
+impl Rotation {
+    /// Returns the rotation for `degrees` clockwise, such as the value of
+    /// `Display::rotation` on macOS, or `None` if it is not a multiple of 90.
+    pub fn from_degrees(degrees: f64) -> Option<Rotation> {
+        let quarters = degrees / 90.0;
+        if quarters.fract() != 0.0 {
+            return None;
+        }
+        match (quarters as i64).rem_euclid(4) {
+            0 => Some(Rotation::None),
+            1 => Some(Rotation::Quarter),
+            2 => Some(Rotation::Half),
+            _ => Some(Rotation::ThreeQuarters),
+        }
+    }
+
+    /// Returns the rotation in degrees clockwise.
+    #[inline]
+    pub fn degrees(self) -> u32 {
+        self.quarters() * 90
+    }
+
+    #[inline]
+    fn quarters(self) -> u32 {
+        match self {
+            Rotation::None => 0,
+            Rotation::Quarter => 1,
+            Rotation::Half => 2,
+            Rotation::ThreeQuarters => 3,
+        }
+    }
+
+    /// Returns the rotation that undoes `self`.
+    #[inline]
+    pub fn inverse(self) -> Rotation {
+        match self {
+            Rotation::Quarter => Rotation::ThreeQuarters,
+            Rotation::ThreeQuarters => Rotation::Quarter,
+            other => other,
+        }
+    }
+
+    /// Rotates a point within the unit square.
+    fn apply_unit(self, (u, v): (f64, f64)) -> (f64, f64) {
+        match self {
+            Rotation::None => (u, v),
+            Rotation::Quarter => (1.0 - v, u),
+            Rotation::Half => (1.0 - u, 1.0 - v),
+            Rotation::ThreeQuarters => (v, 1.0 - u),
+        }
+    }
+
+    /// Rotates a vector whose y axis points down.
+    fn apply_vector(self, (x, y): (i32, i32)) -> (i32, i32) {
+        match self {
+            Rotation::None => (x, y),
+            Rotation::Quarter => (-y, x),
+            Rotation::Half => (-x, -y),
+            Rotation::ThreeQuarters => (y, -x),
+        }
+    }
+}
+
+/// The coordinate space of a display: where it is in global coordinates, its
+/// size, and how its content is rotated.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Space {
+    /// Global coordinates of the upper-left corner of the display.
+    pub origin: Location,
+    /// Width and height of the display as laid out, after rotation.
+    pub size: (f64, f64),
+    /// The rotation of the display's content.
+    pub rotation: Rotation,
+}
+
+impl Space {
+    /// Creates an upright space.
+    #[inline]
+    pub fn new(origin: Location, size: (f64, f64)) -> Space {
+        Space { origin, size, rotation: Rotation::None }
+    }
+
+    /// Returns `self` with its content rotated by `rotation`.
+    #[inline]
+    pub fn rotated(self, rotation: Rotation) -> Space {
+        Space { rotation, ..self }
+    }
+
+    /// Returns the position of `location` relative to the upright content, in
+    /// the unit square.
+    fn unit_of(&self, (x, y): Location) -> (f64, f64) {
+        let (ox, oy) = self.origin;
+        let (w, h) = self.size;
+        self.rotation.inverse().apply_unit(((x - ox) / w, (y - oy) / h))
+    }
+
+    /// Returns the global location of `unit` relative to the upright content.
+    fn at_unit(&self, unit: (f64, f64)) -> Location {
+        let (u, v) = self.rotation.apply_unit(unit);
+        let (ox, oy) = self.origin;
+        let (w, h) = self.size;
+        (ox + u * w, oy + v * h)
+    }
+}
+
+/// A mapping of events from one display's space to another's.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mapping {
+    from: Space,
+    to: Space,
+}
+
+impl Mapping {
+    /// Creates a mapping of events that happened in `from` into `to`.
+    #[inline]
+    pub fn new(from: Space, to: Space) -> Mapping {
+        Mapping { from, to }
+    }
+
+    /// Returns the space that events are mapped from.
+    #[inline]
+    pub fn source(&self) -> Space {
+        self.from
+    }
+
+    /// Returns the space that events are mapped into.
+    #[inline]
+    pub fn target(&self) -> Space {
+        self.to
+    }
+
+    /// Returns the mapping in the opposite direction.
+    #[inline]
+    pub fn inverse(&self) -> Mapping {
+        Mapping { from: self.to, to: self.from }
+    }
+
+    /// Maps a global location.
+    ///
+    /// Locations outside of the source display are extrapolated.
+    #[inline]
+    pub fn map_location(&self, location: Location) -> Location {
+        self.to.at_unit(self.from.unit_of(location))
+    }
+
+    /// Maps the locations in `event` and rotates its scroll direction.
+    ///
+    /// Scroll distances are in lines, so they are not scaled.
+    pub fn map_event(&self, event: &InputEvent) -> InputEvent {
+        match *event {
+            InputEvent::ButtonDown(button, location) => {
+                InputEvent::ButtonDown(button, self.map_location(location))
+            },
+            InputEvent::ButtonUp(button, location) => {
+                InputEvent::ButtonUp(button, self.map_location(location))
+            },
+            InputEvent::MouseMoved(location) => {
+                InputEvent::MouseMoved(self.map_location(location))
+            },
+            InputEvent::Scroll { dx, dy } => {
+                let upright = self.from.rotation.inverse().apply_vector((dx, -dy));
+                let (dx, dy) = self.to.rotation.apply_vector(upright);
+                InputEvent::Scroll { dx, dy: -dy }
+            },
+            InputEvent::Gesture(gesture, phase, location) => {
+                let gesture = match gesture {
+                    // Map both ends of the movement so it is scaled and rotated
+                    Gesture::Pan { dx, dy } => {
+                        let (x0, y0) = self.map_location(location);
+                        let (x1, y1) = self.map_location((location.0 + dx, location.1 + dy));
+                        Gesture::Pan { dx: x1 - x0, dy: y1 - y0 }
+                    },
+                    pinch => pinch,
+                };
+                InputEvent::Gesture(gesture, phase, self.map_location(location))
+            },
+            key => key,
+        }
+    }
+
+    /// Returns a copy of `recording` with every event mapped.
+    pub fn map_recording(&self, recording: &Recording) -> Recording {
+        let mut mapped = recording.clone();
+        for timed in &mut mapped.events {
+            timed.event = self.map_event(&timed.event);
+        }
+        mapped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use events::Phase;
+    use mouse::Button;
+
+    fn assert_near((x, y): Location, (ex, ey): Location) {
+        assert!((x - ex).abs() < 1e-9 && (y - ey).abs() < 1e-9,
+                "({}, {}) != ({}, {})", x, y, ex, ey);
+    }
+
+    #[test]
+    fn scaled_display() {
+        let hd = Space::new((0.0, 0.0), (1920.0, 1080.0));
+        let retina = Space::new((-1280.0, 100.0), (1280.0, 720.0));
+        let mapping = Mapping::new(hd, retina);
+
+        assert_near(mapping.map_location((960.0, 540.0)), (-640.0, 460.0));
+        assert_near(mapping.map_location((1920.0, 0.0)), (0.0, 100.0));
+        assert_near(mapping.inverse().map_location((-640.0, 460.0)), (960.0, 540.0));
+
+        let event = InputEvent::ButtonDown(Button::Left, (480.0, 270.0));
+        assert_eq!(
+            mapping.map_event(&event),
+            InputEvent::ButtonDown(Button::Left, (-960.0, 280.0)),
+        );
+    }
+
+    #[test]
+    fn rotated_display() {
+        let upright = Space::new((0.0, 0.0), (1600.0, 900.0));
+        let portrait = Space::new((0.0, 0.0), (900.0, 1600.0)).rotated(Rotation::Quarter);
+        let mapping = Mapping::new(upright, portrait);
+
+        // The upright top-left corner is the rotated top-right corner
+        assert_near(mapping.map_location((0.0, 0.0)), (900.0, 0.0));
+        assert_near(mapping.map_location((1600.0, 0.0)), (900.0, 1600.0));
+        assert_near(mapping.map_location((400.0, 225.0)), (675.0, 400.0));
+
+        let flipped = upright.rotated(Rotation::Half);
+        let mapping = Mapping::new(portrait, flipped);
+        assert_near(mapping.map_location((900.0, 0.0)), (1600.0, 900.0));
+
+        // Scrolling right on upright content scrolls down once rotated
+        let mapping = Mapping::new(upright, portrait);
+        let scroll = InputEvent::Scroll { dx: 3, dy: 0 };
+        assert_eq!(mapping.map_event(&scroll), InputEvent::Scroll { dx: 0, dy: -3 });
+        assert_eq!(mapping.inverse().map_event(&mapping.map_event(&scroll)), scroll);
+
+        let pan = InputEvent::Gesture(Gesture::Pan { dx: 16.0, dy: 0.0 }, Phase::Changed, (0.0, 0.0));
+        match mapping.map_event(&pan) {
+            InputEvent::Gesture(Gesture::Pan { dx, dy }, Phase::Changed, location) => {
+                assert_near((dx, dy), (0.0, 16.0));
+                assert_near(location, (900.0, 0.0));
+            },
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rotation_degrees() {
+        assert_eq!(Rotation::from_degrees(-90.0), Some(Rotation::ThreeQuarters));
+        assert_eq!(Rotation::from_degrees(450.0), Some(Rotation::Quarter));
+        assert_eq!(Rotation::from_degrees(45.0), None);
+        assert_eq!(Rotation::Half.degrees(), 180);
+    }
+}
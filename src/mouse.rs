@@ -0,0 +1,846 @@
+//! 🖱️ Cross-platform mouse types and helpers.
+
+use std::fmt;
+#[cfg(feature = "tokio")]
+use std::future::Future;
+#[cfg(feature = "tokio")]
+use std::pin::Pin;
+use std::str::FromStr;
+#[cfg(feature = "tokio")]
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "tokio")]
+use tokio::time::{self, Sleep};
+
+use abort::Token;
+use error::Result;
+use events::{self, InputEvent, Location};
+use image::Image;
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+use os;
+use settings;
+use transform;
+use verify;
+
+/// How long a button is held down during a click.
+const CLICK_HOLD: Duration = Duration::from_millis(15);
+
+/// How long to wait between the clicks of a multiple click, well within the
+/// default double-click interval of every supported platform.
+const CLICK_GAP: Duration = Duration::from_millis(60);
+
+/// The evdev code of the left button, after which the other buttons follow.
+const BTN_LEFT: u16 = 0x110;
+
+/// A button on the mouse, independent of the current operating system.
+///
+/// The back and forward buttons are the extra buttons on the side of many
+/// mice: buttons 3 and 4 on macOS, `XBUTTON1` and `XBUTTON2` on Windows, and
+/// `BTN_SIDE` and `BTN_EXTRA` on Linux.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum Button {
+    /// The primary button, typically on the left.
+    Left,
+    /// The secondary button, typically on the right.
+    Right,
+    /// The middle button, often the scroll wheel itself.
+    Middle,
+    /// The button that navigates back, typically on the side.
+    Back,
+    /// The button that navigates forward, typically on the side.
+    Forward,
+    /// Any other button, identified by its zero-based button number.
+    Other(u8),
+}
+
+impl Button {
+    /// Returns the zero-based button number, where left is 0, right is 1,
+    /// middle is 2, back is 3, and forward is 4.
+    #[inline]
+    pub fn number(self) -> u8 {
+        match self {
+            Button::Left => 0,
+            Button::Right => 1,
+            Button::Middle => 2,
+            Button::Back => 3,
+            Button::Forward => 4,
+            Button::Other(n) => n,
+        }
+    }
+
+    /// Returns the button for the zero-based button number.
+    #[inline]
+    pub fn from_number(number: u8) -> Button {
+        match number {
+            0 => Button::Left,
+            1 => Button::Right,
+            2 => Button::Middle,
+            3 => Button::Back,
+            4 => Button::Forward,
+            n => Button::Other(n),
+        }
+    }
+
+    /// Returns the Linux evdev code of the button, such as `BTN_SIDE` for
+    /// back, or `None` if it has none.
+    #[inline]
+    pub fn evdev_code(self) -> Option<u16> {
+        match self.number() {
+            n @ 0..=4 => Some(BTN_LEFT + n as u16),
+            _ => None,
+        }
+    }
+
+    /// Returns the button for the Linux evdev code, or `None` if the code is
+    /// not one of `BTN_LEFT`, `BTN_RIGHT`, `BTN_MIDDLE`, `BTN_SIDE`, or
+    /// `BTN_EXTRA`.
+    #[inline]
+    pub fn from_evdev_code(code: u16) -> Option<Button> {
+        match code.checked_sub(BTN_LEFT)? {
+            n @ 0..=4 => Some(Button::from_number(n as u8)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl From<Button> for os::mouse::Button {
+    #[inline]
+    fn from(button: Button) -> os::mouse::Button {
+        os::mouse::Button::from_number(button.number())
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl From<os::mouse::Button> for Button {
+    #[inline]
+    fn from(button: os::mouse::Button) -> Button {
+        Button::from_number(button.number())
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl ::std::convert::TryFrom<Button> for os::mouse::Button {
+    type Error = ::error::Error;
+
+    /// Fails with `Error::Unsupported` for buttons Windows has no input for.
+    fn try_from(button: Button) -> Result<os::mouse::Button> {
+        match button {
+            Button::Left => Ok(os::mouse::Button::Left),
+            Button::Right => Ok(os::mouse::Button::Right),
+            Button::Middle => Ok(os::mouse::Button::Middle),
+            Button::Back => Ok(os::mouse::Button::X1),
+            Button::Forward => Ok(os::mouse::Button::X2),
+            Button::Other(_) => Err(::error::Error::Unsupported),
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl From<os::mouse::Button> for Button {
+    #[inline]
+    fn from(button: os::mouse::Button) -> Button {
+        match button {
+            os::mouse::Button::Left => Button::Left,
+            os::mouse::Button::Right => Button::Right,
+            os::mouse::Button::Middle => Button::Middle,
+            os::mouse::Button::X1 => Button::Back,
+            os::mouse::Button::X2 => Button::Forward,
+        }
+    }
+}
+
+impl fmt::Display for Button {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Button::Left => f.write_str("left"),
+            Button::Right => f.write_str("right"),
+            Button::Middle => f.write_str("middle"),
+            Button::Back => f.write_str("back"),
+            Button::Forward => f.write_str("forward"),
+            Button::Other(n) => write!(f, "other-{}", n),
+        }
+    }
+}
+
+/// An error returned when parsing a [`Button`](enum.Button.html) from its
+/// name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseButtonError(String);
+
+impl fmt::Display for ParseButtonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown mouse button {:?}", self.0)
+    }
+}
+
+impl ::std::error::Error for ParseButtonError {}
+
+impl FromStr for Button {
+    type Err = ParseButtonError;
+
+    fn from_str(s: &str) -> ::std::result::Result<Button, ParseButtonError> {
+        match s {
+            "left" => Ok(Button::Left),
+            "right" => Ok(Button::Right),
+            "middle" => Ok(Button::Middle),
+            "back" => Ok(Button::Back),
+            "forward" => Ok(Button::Forward),
+            _ => s.strip_prefix("other-")
+                .and_then(|n| n.parse().ok())
+                .map(Button::Other)
+                .ok_or_else(|| ParseButtonError(s.into())),
+        }
+    }
+}
+
+/// Returns the current location of the mouse, passed through the incoming
+/// [transform](../transform/index.html) if one is set.
+///
+/// Wayland clients can't ask where the mouse is, so in Wayland sessions this
+/// is where posted events last moved it, and fails with
+/// `Error::Unsupported` until they have.
+#[inline]
+pub fn location() -> Result<Location> {
+    imp::location().map(transform::incoming)
+}
+
+/// Returns the buttons that are currently held down, whether by the user or
+/// by events that any application posted.
+///
+/// # Examples
+///
+/// Wait for the user to finish a drag before taking over the mouse:
+///
+/// ```no_run
+/// use std::thread;
+/// use std::time::Duration;
+/// use auto::mouse;
+///
+/// while !mouse::buttons_pressed().unwrap().is_empty() {
+///     thread::sleep(Duration::from_millis(20));
+/// }
+/// ```
+#[inline]
+pub fn buttons_pressed() -> Result<Vec<Button>> {
+    imp::buttons_pressed()
+}
+
+/// Moves the mouse to `location`, taking the
+/// [`move_duration`](../settings/struct.Settings.html#structfield.move_duration)
+/// of the current settings.
+///
+/// The mouse jumps straight there if the duration is zero, which it is by
+/// default, and otherwise glides there as with
+/// [`move_smooth`](fn.move_smooth.html).
+///
+/// `location` is in global [screen coordinates](../screen/index.html), which
+/// span every display whatever its scale factor, and is negative on displays
+/// above or left of the main display.
+///
+/// # Examples
+///
+/// ```no_run
+/// use auto::mouse::{self, Button};
+/// use auto::screen::Display;
+///
+/// mouse::move_to((640.0, 480.0)).unwrap();
+/// mouse::click(Button::Left).unwrap();
+///
+/// // Click the middle of a display left of the main display
+/// let display = Display::at_point((-1.0, 0.0)).unwrap().expect("no display");
+/// mouse::move_to(display.center()).unwrap();
+/// mouse::click(Button::Left).unwrap();
+/// ```
+pub fn move_to(location: Location) -> Result<()> {
+    let duration = settings::current().move_duration;
+    settings::paced(|| {
+        if duration > Duration::from_secs(0) {
+            SmoothMove::new(self::location()?, location, duration).easing(Easing::EaseInOut).run()
+        } else {
+            events::post(&InputEvent::MouseMoved(location))
+        }
+    })
+}
+
+fn click_times(action: &str, button: Button, count: u32) -> Result<()> {
+    settings::paced(|| verify::verified(action, || {
+        let location = location()?;
+        for n in 0..count {
+            if n > 0 {
+                thread::sleep(CLICK_GAP);
+            }
+            events::post(&InputEvent::ButtonDown(button, location))?;
+            thread::sleep(CLICK_HOLD);
+            events::post(&InputEvent::ButtonUp(button, location))?;
+        }
+        Ok(())
+    }))
+}
+
+/// Presses and releases `button` at the current mouse location.
+///
+/// The click is checked against the installed
+/// [verification policy](../verify/index.html).
+#[inline]
+pub fn click(button: Button) -> Result<()> {
+    click_times("click", button, 1)
+}
+
+/// Clicks `button` twice at the current mouse location, such that the
+/// clicks register as a double click.
+///
+/// On macOS, synthesized presses only form a double click if they carry a
+/// click count, which [`events::post`](../events/fn.post.html) sets for
+/// presses in quick succession at the same location.
+#[inline]
+pub fn double_click(button: Button) -> Result<()> {
+    click_times("double-click", button, 2)
+}
+
+/// Clicks `button` three times at the current mouse location, such that the
+/// clicks register as a triple click, which typically selects a paragraph.
+#[inline]
+pub fn triple_click(button: Button) -> Result<()> {
+    click_times("triple-click", button, 3)
+}
+
+/// Scrolls by `dx` lines horizontally and `dy` lines vertically, where
+/// positive values scroll right and up.
+///
+/// This is re-exported at the crate root as `auto::scroll`.
+///
+/// # Examples
+///
+/// ```no_run
+/// // Scroll down three lines, then back up
+/// auto::scroll(0, -3).unwrap();
+/// auto::scroll(0, 3).unwrap();
+/// ```
+pub fn scroll(dx: i32, dy: i32) -> Result<()> {
+    settings::paced(|| {
+        verify::verified("scroll", || events::post(&InputEvent::Scroll { dx, dy }))
+    })
+}
+
+/// A standard shape of the mouse cursor, independent of the current operating
+/// system.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum CursorShape {
+    /// The normal arrow.
+    Arrow,
+    /// The I-beam shown over text.
+    IBeam,
+    /// The hourglass or spinner shown while busy. macOS draws its spinning
+    /// wait cursor outside of any application, so it is never reported there.
+    Busy,
+    /// The crosshair.
+    Crosshair,
+    /// The pointing hand shown over links.
+    PointingHand,
+    /// The arrows for resizing left and right.
+    ResizeHorizontal,
+    /// The arrows for resizing up and down.
+    ResizeVertical,
+    /// The slashed circle shown when an operation is not allowed.
+    NotAllowed,
+}
+
+/// Hides the mouse cursor until [`show_cursor`](fn.show_cursor.html) is
+/// called.
+///
+/// Hiding the cursor keeps it out of screenshots taken for verification. On
+/// macOS, the cursor only stays hidden while the calling application is
+/// frontmost, and calls are counted, so each needs a matching call to
+/// `show_cursor`. On Windows, the system cursors are replaced with blank ones,
+/// which lasts until `show_cursor` is called even if the process exits.
+#[inline]
+pub fn hide_cursor() -> Result<()> {
+    imp::hide_cursor()
+}
+
+/// Shows the mouse cursor after a call to
+/// [`hide_cursor`](fn.hide_cursor.html).
+#[inline]
+pub fn show_cursor() -> Result<()> {
+    imp::show_cursor()
+}
+
+/// Returns the image of the mouse cursor currently shown.
+///
+/// Fails with [`Error::Failed`](../error/enum.Error.html#variant.Failed) if
+/// the cursor is hidden.
+#[inline]
+pub fn cursor_image() -> Result<Image> {
+    imp::cursor_image()
+}
+
+/// Returns the shape of the mouse cursor currently shown, or `None` if it is
+/// hidden or is not one of the standard shapes.
+///
+/// This lets automation check that, for example, the pointer is over a link
+/// or a text field before clicking.
+///
+/// # Examples
+///
+/// ```no_run
+/// use auto::mouse::{self, CursorShape};
+///
+/// if mouse::cursor_shape().unwrap() == Some(CursorShape::IBeam) {
+///     println!("Over text");
+/// }
+/// ```
+#[inline]
+pub fn cursor_shape() -> Result<Option<CursorShape>> {
+    imp::cursor_shape()
+}
+
+/// How movement speeds up and slows down over its duration.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Easing {
+    /// Constant speed.
+    Linear,
+    /// Starts slowly and speeds up.
+    EaseIn,
+    /// Starts quickly and slows down.
+    EaseOut,
+    /// Starts and ends slowly, like a human hand.
+    EaseInOut,
+}
+
+impl Default for Easing {
+    #[inline]
+    fn default() -> Easing {
+        Easing::EaseInOut
+    }
+}
+
+impl Easing {
+    /// Returns the fraction of the distance covered after `t`, the fraction of
+    /// time elapsed, both between 0 and 1.
+    pub fn apply(self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Moves the mouse from `from` to `to` over `duration`, following `easing`.
+///
+/// See [`SmoothMove`](struct.SmoothMove.html) for curved paths and for
+/// setting how often the mouse is moved.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use auto::mouse::{self, Easing};
+///
+/// let duration = Duration::from_millis(400);
+/// mouse::move_smooth((10.0, 10.0), (640.0, 480.0), duration, Easing::EaseInOut).unwrap();
+/// ```
+#[inline]
+pub fn move_smooth(from: Location, to: Location, duration: Duration, easing: Easing) -> Result<()> {
+    SmoothMove::new(from, to, duration).easing(easing).run()
+}
+
+/// Returns a future that moves the mouse like
+/// [`move_smooth`](fn.move_smooth.html), waiting between moves on the Tokio
+/// timer rather than by blocking.
+#[cfg(feature = "tokio")]
+#[inline]
+pub fn move_smooth_async(
+    from: Location,
+    to: Location,
+    duration: Duration,
+    easing: Easing,
+) -> SmoothMoveFuture {
+    SmoothMove::new(from, to, duration).easing(easing).run_async()
+}
+
+/// Options for moving the mouse gradually along a path, rather than warping
+/// it instantly, since many applications and games ignore or flag warps.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SmoothMove {
+    from: Location,
+    to: Location,
+    duration: Duration,
+    easing: Easing,
+    rate: u32,
+    curvature: f64,
+}
+
+impl SmoothMove {
+    /// Creates options for moving in a straight line from `from` to `to` over
+    /// `duration`, easing in and out, at 120 moves per second.
+    #[inline]
+    pub fn new(from: Location, to: Location, duration: Duration) -> SmoothMove {
+        SmoothMove {
+            from,
+            to,
+            duration,
+            easing: Easing::default(),
+            rate: 120,
+            curvature: 0.0,
+        }
+    }
+
+    /// Sets how movement speeds up and slows down.
+    pub fn easing(&mut self, easing: Easing) -> &mut SmoothMove {
+        self.easing = easing;
+        self
+    }
+
+    /// Sets how many times per second the mouse is moved.
+    pub fn rate(&mut self, rate: u32) -> &mut SmoothMove {
+        self.rate = rate.max(1);
+        self
+    }
+
+    /// Bends the path into a Bézier curve whose control point is offset from
+    /// the midpoint by `curvature` times the distance moved.
+    ///
+    /// Positive values bend to the left of the direction of movement and
+    /// negative values to the right.
+    pub fn curvature(&mut self, curvature: f64) -> &mut SmoothMove {
+        self.curvature = curvature;
+        self
+    }
+
+    /// Returns the time and location of every move, ending at the
+    /// destination.
+    pub fn path(&self) -> Vec<(Duration, Location)> {
+        let steps = (self.duration.as_secs_f64() * self.rate as f64).ceil().max(1.0) as u32;
+
+        let (x0, y0) = self.from;
+        let (x2, y2) = self.to;
+        let (dx, dy) = (x2 - x0, y2 - y0);
+        // Perpendicular to the direction of movement, scaled by its length
+        let control = (
+            (x0 + x2) / 2.0 + dy * self.curvature,
+            (y0 + y2) / 2.0 - dx * self.curvature,
+        );
+
+        (1..=steps).map(|step| {
+            let t = step as f64 / steps as f64;
+            let s = self.easing.apply(t);
+            let location = if step == steps {
+                self.to
+            } else {
+                let a = (1.0 - s) * (1.0 - s);
+                let b = 2.0 * (1.0 - s) * s;
+                let c = s * s;
+                (a * x0 + b * control.0 + c * x2, a * y0 + b * control.1 + c * y2)
+            };
+            (self.duration.mul_f64(t), location)
+        }).collect()
+    }
+
+    /// Moves the mouse along the path, blocking until it reaches the
+    /// destination.
+    ///
+    /// Stops at the first move that fails to post, or when the
+    /// [kill switch](../abort/index.html) is pressed.
+    pub fn run(&self) -> Result<()> {
+        let token = Token::new();
+        events::post(&InputEvent::MouseMoved(self.from))?;
+        let start = Instant::now();
+        for (time, location) in self.path() {
+            token.sleep(time.checked_sub(start.elapsed()).unwrap_or_default())?;
+            events::post(&InputEvent::MouseMoved(location))?;
+        }
+        Ok(())
+    }
+
+    /// Returns a future that moves the mouse along the path, waiting between
+    /// moves on the Tokio timer.
+    ///
+    /// The future must be polled within a Tokio runtime with the time driver
+    /// enabled. Movement starts when it is first polled, and stops if it is
+    /// dropped.
+    #[cfg(feature = "tokio")]
+    pub fn run_async(&self) -> SmoothMoveFuture {
+        SmoothMoveFuture {
+            token: Token::new(),
+            path: self.path().into_iter(),
+            next: Some(self.from),
+            start: None,
+            sleep: None,
+        }
+    }
+}
+
+/// A future returned by [`SmoothMove::run_async`](struct.SmoothMove.html#method.run_async)
+/// that resolves once the mouse reaches the destination.
+#[cfg(feature = "tokio")]
+pub struct SmoothMoveFuture {
+    token: Token,
+    path: ::std::vec::IntoIter<(Duration, Location)>,
+    next: Option<Location>,
+    start: Option<Instant>,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+#[cfg(feature = "tokio")]
+impl fmt::Debug for SmoothMoveFuture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SmoothMoveFuture")
+            .field("next", &self.next)
+            .field("remaining", &self.path.len())
+            .finish()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Future for SmoothMoveFuture {
+    type Output = Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        let this = &mut *self;
+        let start = *this.start.get_or_insert_with(Instant::now);
+        loop {
+            if let Some(ref mut sleep) = this.sleep {
+                if sleep.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+            }
+            this.sleep = None;
+            this.token.check()?;
+            if let Some(location) = this.next.take() {
+                events::post(&InputEvent::MouseMoved(location))?;
+            }
+            match this.path.next() {
+                Some((time, location)) => {
+                    this.next = Some(location);
+                    this.sleep = Some(Box::pin(time::sleep_until((start + time).into())));
+                },
+                None => return Poll::Ready(Ok(())),
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use super::{Button, CursorShape};
+    use error::Result;
+    use events::Location;
+    use image::Image;
+    use os::mouse::{self, SystemCursor};
+    use os::source::SourceState;
+
+    /// The number of mouse buttons that Quartz tracks.
+    const BUTTON_COUNT: u8 = 32;
+
+    pub fn location() -> Result<Location> {
+        mouse::cursor_location()
+    }
+
+    pub fn buttons_pressed() -> Result<Vec<Button>> {
+        Ok((0..BUTTON_COUNT)
+            .filter(|&n| SourceState::CombinedSession.is_button_down(n as u32))
+            .map(Button::from_number)
+            .collect())
+    }
+
+    pub fn hide_cursor() -> Result<()> {
+        mouse::hide_cursor()
+    }
+
+    pub fn show_cursor() -> Result<()> {
+        mouse::show_cursor()
+    }
+
+    pub fn cursor_image() -> Result<Image> {
+        mouse::cursor_image()
+    }
+
+    pub fn cursor_shape() -> Result<Option<CursorShape>> {
+        Ok(SystemCursor::current()?.map(|cursor| match cursor {
+            SystemCursor::Arrow => CursorShape::Arrow,
+            SystemCursor::IBeam => CursorShape::IBeam,
+            SystemCursor::Crosshair => CursorShape::Crosshair,
+            SystemCursor::PointingHand => CursorShape::PointingHand,
+            SystemCursor::ResizeLeftRight => CursorShape::ResizeHorizontal,
+            SystemCursor::ResizeUpDown => CursorShape::ResizeVertical,
+            SystemCursor::OperationNotAllowed => CursorShape::NotAllowed,
+        }))
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use super::{Button, CursorShape};
+    use error::{OsError, Result};
+    use events::Location;
+    use image::Image;
+    use os::mouse::{self, SystemCursor};
+
+    pub fn location() -> Result<Location> {
+        match mouse::location() {
+            Some((x, y)) => Ok((x as f64, y as f64)),
+            None => Err(OsError::last().into()),
+        }
+    }
+
+    pub fn buttons_pressed() -> Result<Vec<Button>> {
+        let buttons = [
+            mouse::Button::Left,
+            mouse::Button::Right,
+            mouse::Button::Middle,
+            mouse::Button::X1,
+            mouse::Button::X2,
+        ];
+        Ok(buttons.iter().cloned().filter(|&b| mouse::is_pressed(b)).map(Button::from).collect())
+    }
+
+    pub fn hide_cursor() -> Result<()> {
+        mouse::hide_cursor()
+    }
+
+    pub fn show_cursor() -> Result<()> {
+        mouse::show_cursor()
+    }
+
+    pub fn cursor_image() -> Result<Image> {
+        mouse::cursor_image()
+    }
+
+    pub fn cursor_shape() -> Result<Option<CursorShape>> {
+        Ok(SystemCursor::current()?.map(|cursor| match cursor {
+            SystemCursor::Arrow => CursorShape::Arrow,
+            SystemCursor::IBeam => CursorShape::IBeam,
+            SystemCursor::Wait | SystemCursor::AppStarting => CursorShape::Busy,
+            SystemCursor::Cross => CursorShape::Crosshair,
+            SystemCursor::Hand => CursorShape::PointingHand,
+            SystemCursor::SizeWE => CursorShape::ResizeHorizontal,
+            SystemCursor::SizeNS => CursorShape::ResizeVertical,
+            SystemCursor::No => CursorShape::NotAllowed,
+        }))
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::env;
+
+    use super::{Button, CursorShape};
+    use error::{Error, Result};
+    use events::{self, Location};
+    use image::Image;
+    use os;
+
+    /// Wayland clients can't ask where the pointer is, so it's taken to be
+    /// where posted events last moved it.
+    pub fn location() -> Result<Location> {
+        if let Some(location) = events::posted_location() {
+            return Ok(location);
+        }
+        match env::var_os("WAYLAND_DISPLAY") {
+            Some(_) => Err(Error::Unsupported),
+            None => os::x11_pointer(),
+        }
+    }
+
+    pub fn buttons_pressed() -> Result<Vec<Button>> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn hide_cursor() -> Result<()> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn show_cursor() -> Result<()> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn cursor_image() -> Result<Image> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn cursor_shape() -> Result<Option<CursorShape>> {
+        Err(Error::Unsupported)
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+mod imp {
+    use super::{Button, CursorShape};
+    use error::{Error, Result};
+    use events::Location;
+    use image::Image;
+
+    pub fn location() -> Result<Location> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn buttons_pressed() -> Result<Vec<Button>> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn hide_cursor() -> Result<()> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn show_cursor() -> Result<()> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn cursor_image() -> Result<Image> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn cursor_shape() -> Result<Option<CursorShape>> {
+        Err(Error::Unsupported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn button_conversions() {
+        for n in 0..8 {
+            let button = Button::from_number(n);
+            assert_eq!(button.number(), n);
+            assert_eq!(button.to_string().parse(), Ok(button));
+            if let Some(code) = button.evdev_code() {
+                assert_eq!(Button::from_evdev_code(code), Some(button));
+            }
+        }
+        assert_eq!(Button::Back.evdev_code(), Some(0x113));
+        assert_eq!(Button::from_evdev_code(0x114), Some(Button::Forward));
+        assert_eq!(Button::from_evdev_code(0x10F), None);
+        assert_eq!(Button::Other(5).evdev_code(), None);
+    }
+
+    #[test]
+    fn smooth_path() {
+        let duration = Duration::from_millis(100);
+        let path = SmoothMove::new((0.0, 0.0), (100.0, 0.0), duration)
+            .rate(100)
+            .curvature(0.25)
+            .path();
+
+        assert_eq!(path.len(), 10);
+        assert_eq!(path.last(), Some(&(duration, (100.0, 0.0))));
+
+        // Bends to the left of rightward movement, which is up the screen
+        let (_, (x, y)) = path[4];
+        assert!((x - 50.0).abs() < 1e-9);
+        assert!(y < -10.0);
+        assert!(path.windows(2).all(|w| w[0].0 < w[1].0 && (w[0].1).0 < (w[1].1).0));
+
+        for &easing in &[Easing::Linear, Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut] {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(1.0), 1.0);
+        }
+    }
+}
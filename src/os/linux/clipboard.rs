@@ -0,0 +1,518 @@
+//! 📋 Clipboard utilities.
+//!
+//! Text is exchanged through the `CLIPBOARD` selection, which applications
+//! fill when copying, or the `PRIMARY` selection, which holds the text last
+//! selected with the mouse and is pasted with a middle click.
+//!
+//! In Wayland sessions whose compositor supports the data-control protocol,
+//! such as Sway, Hyprland, and KDE Plasma, selections are read and set
+//! through the compositor. Otherwise they go through the X server at
+//! `DISPLAY`, which in other Wayland sessions is Xwayland, whose selections
+//! the compositor keeps in sync with its own.
+//!
+//! Applications paste by asking the owner of a selection for its contents,
+//! so text set here is served by a background thread until another
+//! application takes over the selection. It can no longer be pasted once the
+//! process exits.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use auto::os::clipboard::{self, Selection};
+//!
+//! clipboard::set_text(Selection::Clipboard, "copied").unwrap();
+//! let text = clipboard::text(Selection::Clipboard).unwrap();
+//! assert_eq!(text.as_deref(), Some("copied"));
+//! ```
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::unix::io::FromRawFd;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use error::{Error, Result};
+use super::wayland::{self, Arg};
+use super::{io_error, poll_readable, xcb};
+
+/// How long to wait for the owner of a selection to send each part of it,
+/// and for a client pasting it to ask for each part.
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Text types offered and accepted through Wayland, from most preferred.
+const MIME_TYPES: &[&str] = &["text/plain;charset=utf-8", "UTF8_STRING", "TEXT", "STRING", "text/plain"];
+
+/// `SelectionClear`
+const SELECTION_CLEAR: u8 = 29;
+
+/// `SelectionRequest`
+const SELECTION_REQUEST: u8 = 30;
+
+/// `SelectionNotify`
+const SELECTION_NOTIFY: u8 = 31;
+
+/// `PropertyNotify`
+const PROPERTY_NOTIFY: u8 = 28;
+
+/// `PropertyDelete`, the state of a property notification for a deletion.
+const PROPERTY_DELETE: u8 = 1;
+
+/// A selection that holds text to paste.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum Selection {
+    /// The selection filled by copying and pasted with Control-V.
+    Clipboard,
+    /// The selection filled by selecting text and pasted with a middle click.
+    Primary,
+}
+
+/// Returns the text of `selection`, or `None` if it is empty or doesn't
+/// hold text.
+///
+/// Fails with [`Error::TimedOut`](../../error/enum.Error.html#variant.TimedOut)
+/// if the owner of the selection stops responding.
+pub fn text(selection: Selection) -> Result<Option<String>> {
+    match DataControl::connect(selection) {
+        Some(control) => control.text(selection),
+        None => x11_text(selection),
+    }
+}
+
+/// Replaces the contents of `selection` with `text`.
+///
+/// Large text is sent incrementally to applications that paste it, so it may
+/// be of any size.
+pub fn set_text(selection: Selection, text: &str) -> Result<()> {
+    match DataControl::connect(selection) {
+        Some(control) => control.set_text(selection, text),
+        None => x11_set_text(selection, text),
+    }
+}
+
+/// A connection to a compositor that supports either `ext-data-control-v1`
+/// or `wlr-data-control-unstable-v1`, which share their requests and events.
+struct DataControl {
+    connection: wayland::Connection,
+    manager: u32,
+    device: u32,
+}
+
+impl DataControl {
+    /// Connects to the compositor, returning `None` outside of Wayland or if
+    /// the compositor can't control `selection`.
+    fn connect(selection: Selection) -> Option<DataControl> {
+        let mut connection = wayland::Connection::connect().ok()?;
+        let manager = connection.global(&["ext_data_control_manager_v1", "zwlr_data_control_manager_v1"])?;
+        // The primary selection came in version 2 of the wlr protocol
+        let version = if manager.interface.starts_with("ext") { 1 } else { 2 };
+        if selection == Selection::Primary && manager.version < version {
+            return None;
+        }
+        let seat = connection.global(&["wl_seat"])?;
+        let manager = connection.bind(&manager, version).ok()?;
+        let seat = connection.bind(&seat, 1).ok()?;
+        let device = connection.new_id();
+        // get_data_device
+        connection.send(manager, 1, &[Arg::Uint(device), Arg::Uint(seat)]).ok()?;
+        Some(DataControl { connection, manager, device })
+    }
+
+    fn text(mut self, selection: Selection) -> Result<Option<String>> {
+        // The device announces the current offers as soon as it is created
+        let mut offers: Vec<(u32, Vec<String>)> = Vec::new();
+        let mut current = 0;
+        for mut message in self.connection.roundtrip()? {
+            match (message.object == self.device, message.opcode) {
+                // data_offer
+                (true, 0) => offers.push((message.uint(), Vec::new())),
+                // selection
+                (true, 1) if selection == Selection::Clipboard => current = message.uint(),
+                // primary_selection
+                (true, 3) if selection == Selection::Primary => current = message.uint(),
+                // offer.offer
+                (false, 0) => {
+                    let object = message.object;
+                    if let Some(offer) = offers.iter_mut().find(|offer| offer.0 == object) {
+                        offer.1.push(message.string());
+                    }
+                },
+                _ => {},
+            }
+        }
+        let types = match offers.iter().find(|offer| current != 0 && offer.0 == current) {
+            Some(offer) => &offer.1,
+            None => return Ok(None),
+        };
+        let mime = match MIME_TYPES.iter().find(|&&mime| types.iter().any(|t| t == mime)) {
+            Some(mime) => mime,
+            None => return Ok(None),
+        };
+
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+            return Err(io_error(::std::io::Error::last_os_error()));
+        }
+        let (reader, writer) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+        // offer.receive
+        self.connection.send(current, 0, &[Arg::Str(mime), Arg::Fd(fds[1])])?;
+        // The owner holds the only other copy, so the pipe ends when it's done
+        drop(writer);
+        read_until_closed(reader).map(|bytes| Some(String::from_utf8_lossy(&bytes).into_owned()))
+    }
+
+    fn set_text(mut self, selection: Selection, text: &str) -> Result<()> {
+        let source = self.connection.new_id();
+        // create_data_source
+        self.connection.send(self.manager, 0, &[Arg::Uint(source)])?;
+        for mime in MIME_TYPES {
+            // source.offer
+            self.connection.send(source, 0, &[Arg::Str(mime)])?;
+        }
+        // set_selection or set_primary_selection
+        let opcode = match selection {
+            Selection::Clipboard => 0,
+            Selection::Primary => 2,
+        };
+        self.connection.send(self.device, opcode, &[Arg::Uint(source)])?;
+        self.connection.roundtrip()?;
+
+        let text = text.to_owned();
+        let mut connection = self.connection;
+        thread::Builder::new()
+            .name("auto-data-source".into())
+            .spawn(move || {
+                while let Ok(Some(mut message)) = connection.next_message(None) {
+                    match (message.object == source, message.opcode) {
+                        // source.send
+                        (true, 0) => {
+                            let mime = message.string();
+                            if let Some(fd) = connection.take_fd() {
+                                let mut file = unsafe { File::from_raw_fd(fd) };
+                                if let Some(bytes) = encode(&mime, &text) {
+                                    let _ = file.write_all(&bytes);
+                                }
+                            }
+                        },
+                        // source.cancelled, once another client sets the selection
+                        (true, 1) => break,
+                        _ => {},
+                    }
+                }
+            })
+            .map_err(io_error)?;
+        Ok(())
+    }
+}
+
+/// Returns `text` encoded as the Wayland `mime` type, or `None` if it isn't
+/// one that was offered.
+fn encode(mime: &str, text: &str) -> Option<Vec<u8>> {
+    match mime {
+        "STRING" => Some(latin1(text)),
+        mime if MIME_TYPES.contains(&mime) => Some(text.as_bytes().to_vec()),
+        _ => None,
+    }
+}
+
+/// Reads `reader` until the writer closes it, waiting up to `TIMEOUT` for
+/// each part.
+fn read_until_closed(mut reader: File) -> Result<Vec<u8>> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut bytes = Vec::new();
+    let mut buffer = [0; 8192];
+    loop {
+        if !poll_readable(reader.as_raw_fd(), Some(Instant::now() + TIMEOUT))? {
+            return Err(Error::TimedOut);
+        }
+        match reader.read(&mut buffer) {
+            Ok(0) => return Ok(bytes),
+            Ok(read) => bytes.extend_from_slice(&buffer[..read]),
+            Err(ref error) if error.kind() == ::std::io::ErrorKind::Interrupted => {},
+            Err(error) => return Err(io_error(error)),
+        }
+    }
+}
+
+/// The atoms used to exchange selections with X clients.
+struct Atoms {
+    selection: u32,
+    property: u32,
+    targets: u32,
+    utf8: u32,
+    text: u32,
+    incr: u32,
+}
+
+impl Atoms {
+    fn new(connection: &xcb::Connection, selection: Selection) -> Result<Atoms> {
+        Ok(Atoms {
+            selection: match selection {
+                Selection::Clipboard => connection.atom("CLIPBOARD")?,
+                Selection::Primary => xcb::ATOM_PRIMARY,
+            },
+            property: connection.atom("AUTO_SELECTION")?,
+            targets: connection.atom("TARGETS")?,
+            utf8: connection.atom("UTF8_STRING")?,
+            text: connection.atom("TEXT")?,
+            incr: connection.atom("INCR")?,
+        })
+    }
+}
+
+/// Converts `text` to Latin-1 for clients that only accept `STRING`.
+fn latin1(text: &str) -> Vec<u8> {
+    text.chars().map(|c| if (c as u32) < 0x100 { c as u8 } else { b'?' }).collect()
+}
+
+fn x11_text(selection: Selection) -> Result<Option<String>> {
+    let connection = xcb::Connection::open()?;
+    let atoms = Atoms::new(&connection, selection)?;
+    if connection.selection_owner(atoms.selection)? == 0 {
+        return Ok(None);
+    }
+    let window = connection.create_window(xcb::PROPERTY_CHANGE_MASK);
+    let result = x11_convert(&connection, &atoms, window);
+    connection.destroy_window(window);
+    let _ = connection.flush();
+    result
+}
+
+/// Asks the owner of the selection for its text, stored in a property of
+/// `window`.
+fn x11_convert(connection: &xcb::Connection, atoms: &Atoms, window: u32) -> Result<Option<String>> {
+    for &target in &[atoms.utf8, xcb::ATOM_STRING] {
+        connection.convert_selection(window, atoms.selection, target, atoms.property);
+        let deadline = Instant::now() + TIMEOUT;
+        let converted = loop {
+            let event = connection.next_event(Some(deadline))?.ok_or(Error::TimedOut)?;
+            if event.kind() == SELECTION_NOTIFY && event.u32_at(8) == window {
+                // The property is none if the owner can't convert to `target`
+                break event.u32_at(20) != 0;
+            }
+        };
+        if !converted {
+            continue;
+        }
+        let property = connection.take_property(window, atoms.property)?;
+        let bytes = if property.kind == atoms.incr {
+            x11_receive_incrementally(connection, atoms, window)?
+        } else {
+            property.value
+        };
+        let text = if target == atoms.utf8 {
+            String::from_utf8_lossy(&bytes).into_owned()
+        } else {
+            bytes.iter().map(|&b| b as char).collect()
+        };
+        return Ok(Some(text));
+    }
+    Ok(None)
+}
+
+/// Receives a selection sent in parts, each of which the owner writes to the
+/// property once the previous one has been deleted.
+fn x11_receive_incrementally(connection: &xcb::Connection, atoms: &Atoms, window: u32) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    loop {
+        let event = connection.next_event(Some(Instant::now() + TIMEOUT))?.ok_or(Error::TimedOut)?;
+        let new_value = event.kind() == PROPERTY_NOTIFY && event.0[16] != PROPERTY_DELETE;
+        if !new_value || event.u32_at(4) != window || event.u32_at(8) != atoms.property {
+            continue;
+        }
+        // An empty part ends the transfer
+        let part = connection.take_property(window, atoms.property)?.value;
+        if part.is_empty() {
+            return Ok(bytes);
+        }
+        bytes.extend_from_slice(&part);
+    }
+}
+
+fn x11_set_text(selection: Selection, text: &str) -> Result<()> {
+    let (sender, receiver) = mpsc::channel();
+    let text = text.to_owned();
+    thread::Builder::new()
+        .name("auto-selection-owner".into())
+        .spawn(move || {
+            let owner = x11_own(selection);
+            let ok = owner.as_ref().map(|_| ()).map_err(|e| e.clone());
+            let _ = sender.send(ok);
+            if let Ok((connection, atoms, window)) = owner {
+                x11_serve(&connection, &atoms, window, &text);
+                connection.destroy_window(window);
+                let _ = connection.flush();
+            }
+        })
+        .map_err(io_error)?;
+    receiver.recv().unwrap_or(Err(Error::Failed))
+}
+
+/// Takes over `selection` with a new window.
+fn x11_own(selection: Selection) -> Result<(xcb::Connection, Atoms, u32)> {
+    let connection = xcb::Connection::open()?;
+    let atoms = Atoms::new(&connection, selection)?;
+    let window = connection.create_window(0);
+    connection.set_selection_owner(window, atoms.selection);
+    if connection.selection_owner(atoms.selection)? != window {
+        return Err(Error::Failed);
+    }
+    Ok((connection, atoms, window))
+}
+
+/// A selection being sent in parts to the property of a requestor.
+#[derive(Debug)]
+struct Transfer {
+    requestor: u32,
+    property: u32,
+    kind: u32,
+    offset: usize,
+    /// When the transfer is given up if the requestor doesn't ask for the
+    /// next part.
+    deadline: Instant,
+}
+
+impl Transfer {
+    fn new(requestor: u32, property: u32, kind: u32) -> Transfer {
+        Transfer { requestor, property, kind, offset: 0, deadline: Instant::now() + TIMEOUT }
+    }
+
+    /// Returns the next part of `bytes`, of up to `chunk` bytes, and whether
+    /// it is the empty part that ends the transfer.
+    fn next_part<'a>(&mut self, bytes: &'a [u8], chunk: usize) -> (&'a [u8], bool) {
+        let start = self.offset.min(bytes.len());
+        let end = (start + chunk).min(bytes.len());
+        self.offset = end;
+        self.deadline = Instant::now() + TIMEOUT;
+        (&bytes[start..end], start == end)
+    }
+}
+
+/// Answers requests for the selection until another client owns it and every
+/// transfer in progress is done.
+fn x11_serve(connection: &xcb::Connection, atoms: &Atoms, window: u32, text: &str) {
+    let (utf8, latin1) = (text.as_bytes(), latin1(text));
+    let data = |kind: u32| if kind == xcb::ATOM_STRING { &latin1[..] } else { utf8 };
+    // Leave room for the rest of the request
+    let chunk = connection.max_request_bytes().saturating_sub(1024).clamp(4096, 1 << 20);
+    let mut transfers: Vec<Transfer> = Vec::new();
+    let mut owned = true;
+
+    while owned || !transfers.is_empty() {
+        // Give up on requestors that stopped asking for parts
+        let now = Instant::now();
+        transfers.retain(|transfer| transfer.deadline > now);
+        if !owned && transfers.is_empty() {
+            return;
+        }
+        let deadline = transfers.iter().map(|transfer| transfer.deadline).min();
+        let event = match connection.next_event(deadline) {
+            Ok(Some(event)) => event,
+            Ok(None) => continue,
+            Err(_) => return,
+        };
+        match event.kind() {
+            SELECTION_REQUEST if event.u32_at(8) == window => {
+                let (requestor, target) = (event.u32_at(12), event.u32_at(20));
+                // Obsolete clients leave the property as none
+                let property = match event.u32_at(24) {
+                    0 => target,
+                    property => property,
+                };
+                let kind = if target == atoms.utf8 || target == atoms.text {
+                    Some(atoms.utf8)
+                } else if target == xcb::ATOM_STRING {
+                    Some(xcb::ATOM_STRING)
+                } else {
+                    None
+                };
+                let served = if !owned {
+                    false
+                } else if target == atoms.targets {
+                    let targets: Vec<u8> = [atoms.targets, atoms.utf8, atoms.text, xcb::ATOM_STRING]
+                        .iter()
+                        .flat_map(|atom| atom.to_ne_bytes())
+                        .collect();
+                    connection.set_property(requestor, property, xcb::ATOM_ATOM, 32, &targets);
+                    true
+                } else if let Some(kind) = kind {
+                    let bytes = data(kind);
+                    if bytes.len() > chunk {
+                        // The requestor deletes the property to ask for each part
+                        connection.select_events(requestor, xcb::PROPERTY_CHANGE_MASK);
+                        let len = (bytes.len() as u32).to_ne_bytes();
+                        connection.set_property(requestor, property, atoms.incr, 32, &len);
+                        transfers.push(Transfer::new(requestor, property, kind));
+                    } else {
+                        connection.set_property(requestor, property, kind, 8, bytes);
+                    }
+                    true
+                } else {
+                    false
+                };
+
+                let mut notify = xcb::Event([0; 32]);
+                notify.0[0] = SELECTION_NOTIFY;
+                notify.set_u32_at(4, event.u32_at(4));
+                notify.set_u32_at(8, requestor);
+                notify.set_u32_at(12, event.u32_at(16));
+                notify.set_u32_at(16, target);
+                notify.set_u32_at(20, if served { property } else { 0 });
+                connection.send_event(requestor, &notify);
+            },
+            PROPERTY_NOTIFY if event.0[16] == PROPERTY_DELETE => {
+                let (requestor, property) = (event.u32_at(4), event.u32_at(8));
+                let index = transfers.iter().position(|t| t.requestor == requestor && t.property == property);
+                if let Some(index) = index {
+                    let transfer = &mut transfers[index];
+                    let (part, last) = transfer.next_part(data(transfer.kind), chunk);
+                    connection.set_property(requestor, property, transfer.kind, 8, part);
+                    if last {
+                        transfers.remove(index);
+                    }
+                }
+            },
+            SELECTION_CLEAR if event.u32_at(8) == window => owned = false,
+            _ => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latin1() {
+        assert_eq!(super::latin1("café €5"), b"caf\xe9 ?5");
+    }
+
+    #[test]
+    fn encode_mime_types() {
+        assert_eq!(encode("text/plain;charset=utf-8", "café"), Some("café".as_bytes().to_vec()));
+        assert_eq!(encode("STRING", "café"), Some(b"caf\xe9".to_vec()));
+        assert_eq!(encode("image/png", "café"), None);
+    }
+
+    #[test]
+    fn incremental_parts() {
+        let bytes: Vec<u8> = (0..10).collect();
+        let mut transfer = Transfer::new(1, 2, xcb::ATOM_STRING);
+        let mut parts = Vec::new();
+        loop {
+            let (part, last) = transfer.next_part(&bytes, 4);
+            parts.push(part.to_vec());
+            if last {
+                break;
+            }
+        }
+        assert_eq!(parts, [vec![0, 1, 2, 3], vec![4, 5, 6, 7], vec![8, 9], vec![]]);
+
+        // Each part pushes the deadline back
+        let deadline = transfer.deadline;
+        thread::sleep(Duration::from_millis(5));
+        transfer.next_part(&bytes, 4);
+        assert!(transfer.deadline > deadline);
+    }
+}
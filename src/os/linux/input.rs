@@ -0,0 +1,809 @@
+//! 🎛️ Global input from evdev devices.
+//!
+//! [`listen`](fn.listen.html) reads every keyboard, mouse, touchpad,
+//! touchscreen, and tablet at `/dev/input/event*`, below the display server,
+//! so input is observed the same way under X11, Wayland, and the console.
+//! Devices are found through udev, which also reports devices as they are
+//! plugged in and removed. Where libudev is missing, `/dev/input` is rescanned
+//! every few seconds instead.
+//!
+//! Reading input devices requires root or membership in the `input` group.
+//!
+//! Devices report how they moved rather than where the cursor is, so under
+//! X11 pointer locations are asked of the X server after each movement. The
+//! server may not have applied the movement yet, in which case the location
+//! trails by one report. Wayland doesn't tell clients where the cursor is, so
+//! in Wayland sessions and without a display server, pointer locations start
+//! at the center of the main display and follow relative motion without the
+//! pointer acceleration of the desktop, which makes them drift from the
+//! cursor. Touchscreens and tablets are mapped onto the displays.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use auto::os;
+//!
+//! let listener = os::listen(|event, device| {
+//!     println!("{:?} from {}", event, device.name());
+//! }).unwrap();
+//! # drop(listener);
+//! ```
+
+use std::env;
+use std::ffi::{CStr, OsStr};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read};
+use std::os::raw::{c_char, c_int, c_void};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use std::{mem, ptr, slice};
+
+use error::{Error, Result};
+use events::{InputEvent, Location};
+use keyboard::Key;
+use mouse::Button;
+use super::io_error;
+use super::screen::{Bounds, Display};
+use super::xcb;
+
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_REL: u16 = 0x02;
+const EV_ABS: u16 = 0x03;
+
+const SYN_REPORT: u16 = 0;
+const SYN_DROPPED: u16 = 3;
+
+const REL_X: u16 = 0x00;
+const REL_Y: u16 = 0x01;
+const REL_HWHEEL: u16 = 0x06;
+const REL_WHEEL: u16 = 0x08;
+
+const ABS_X: u16 = 0x00;
+const ABS_Y: u16 = 0x01;
+
+const BTN_MOUSE: u16 = 0x110;
+const BTN_JOYSTICK: u16 = 0x120;
+const BTN_GAMEPAD: u16 = 0x130;
+const BTN_TOOL_PEN: u16 = 0x140;
+const BTN_TOOL_FINGER: u16 = 0x145;
+const BTN_TOUCH: u16 = 0x14A;
+const KEY_OK: u16 = 0x160;
+const KEY_MAX: u16 = 0x2FF;
+
+const INPUT_PROP_DIRECT: u16 = 0x01;
+const INPUT_PROP_ACCELEROMETER: u16 = 0x06;
+
+const BUS_VIRTUAL: u16 = 0x06;
+
+/// How often `/dev/input` is rescanned for new devices without udev.
+const RESCAN: Duration = Duration::from_secs(2);
+
+/// Pixels moved per millimeter that a finger moves on a touchpad.
+const TOUCHPAD_SPEED: f64 = 4.0;
+
+dynamic_library! {
+    /// The functions of libudev.
+    struct Libudev("libudev.so.1\0", "libudev.so\0") {
+        fn udev_new() -> *mut c_void;
+        fn udev_unref(udev: *mut c_void) -> *mut c_void;
+        fn udev_enumerate_new(udev: *mut c_void) -> *mut c_void;
+        fn udev_enumerate_add_match_subsystem(enumerate: *mut c_void, subsystem: *const c_char) -> c_int;
+        fn udev_enumerate_scan_devices(enumerate: *mut c_void) -> c_int;
+        fn udev_enumerate_get_list_entry(enumerate: *mut c_void) -> *mut c_void;
+        fn udev_enumerate_unref(enumerate: *mut c_void) -> *mut c_void;
+        fn udev_list_entry_get_next(entry: *mut c_void) -> *mut c_void;
+        fn udev_list_entry_get_name(entry: *mut c_void) -> *const c_char;
+        fn udev_device_new_from_syspath(udev: *mut c_void, path: *const c_char) -> *mut c_void;
+        fn udev_device_get_devnode(device: *mut c_void) -> *const c_char;
+        fn udev_device_get_action(device: *mut c_void) -> *const c_char;
+        fn udev_device_unref(device: *mut c_void) -> *mut c_void;
+        fn udev_monitor_new_from_netlink(udev: *mut c_void, name: *const c_char) -> *mut c_void;
+        fn udev_monitor_filter_add_match_subsystem_devtype(
+            monitor: *mut c_void,
+            subsystem: *const c_char,
+            devtype: *const c_char,
+        ) -> c_int;
+        fn udev_monitor_enable_receiving(monitor: *mut c_void) -> c_int;
+        fn udev_monitor_get_fd(monitor: *mut c_void) -> c_int;
+        fn udev_monitor_receive_device(monitor: *mut c_void) -> *mut c_void;
+        fn udev_monitor_unref(monitor: *mut c_void) -> *mut c_void;
+    }
+}
+
+lazy_static! {
+    static ref LIBUDEV: Option<Libudev> = Libudev::load();
+}
+
+/// Returns the request of the `ioctl` that reads `size` bytes of the evdev
+/// attribute `nr`.
+fn eviocg(nr: u32, size: usize) -> u32 {
+    // _IOC(_IOC_READ, 'E', nr, size)
+    (2 << 30) | ((size as u32) << 16) | (u32::from(b'E') << 8) | nr
+}
+
+/// Reads the evdev attribute `nr` of `file` into `value`.
+fn read_attribute<T>(file: &File, nr: u32, value: &mut T) -> bool {
+    let request = eviocg(nr, mem::size_of::<T>());
+    unsafe { libc::ioctl(file.as_raw_fd(), request as _, value as *mut T) >= 0 }
+}
+
+/// Returns whether bit `n` of the evdev bitmask `bits` is set.
+fn has_bit(bits: &[u8], n: u16) -> bool {
+    bits.get(usize::from(n / 8)).is_some_and(|byte| byte & (1 << (n % 8)) != 0)
+}
+
+/// An input device read by a listener.
+#[derive(Clone, Debug)]
+pub struct Device {
+    path: PathBuf,
+    name: String,
+    is_virtual: bool,
+}
+
+impl Device {
+    /// Returns the path of the device node, such as `/dev/input/event3`.
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the name the device reports, such as its product name.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns whether the device was created by software through `uinput`
+    /// rather than being hardware.
+    #[inline]
+    pub fn is_virtual(&self) -> bool {
+        self.is_virtual
+    }
+}
+
+/// The range of values of an absolute axis.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Axis {
+    min: i32,
+    max: i32,
+}
+
+impl Axis {
+    /// Returns where `value` lies between the ends of the axis, from 0 to 1.
+    fn fraction(&self, value: i32) -> f64 {
+        let span = f64::from(self.max) - f64::from(self.min);
+        if span <= 0.0 {
+            return 0.0;
+        }
+        ((f64::from(value) - f64::from(self.min)) / span).clamp(0.0, 1.0)
+    }
+}
+
+/// How a device moves the pointer.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Pointing {
+    /// The device doesn't move the pointer.
+    None,
+    /// The device reports motion, like a mouse.
+    Relative,
+    /// The device reports finger positions whose changes move the pointer,
+    /// scaled to pixels.
+    Touchpad(f64),
+    /// The device reports positions on the displays, like a touchscreen.
+    Absolute(Axis, Axis),
+}
+
+/// The current pointer location, shared by every device.
+struct Pointer {
+    location: Location,
+    bounds: Option<Bounds>,
+    /// The X server, which is asked where the cursor is instead of following
+    /// the devices, outside of Wayland sessions.
+    x11: Option<xcb::Connection>,
+}
+
+impl Pointer {
+    /// Starts where the X server has the cursor or, failing that, at the
+    /// center of the main display, if displays can be found.
+    fn new() -> Pointer {
+        let displays: Vec<Bounds> = Display::active()
+            .map(|displays| displays.iter().map(Display::bounds).collect())
+            .unwrap_or_default();
+        let bounds = displays.iter().copied().reduce(|a, b| a.union(&b));
+        let location = displays.first().map_or((0.0, 0.0), |main| {
+            (main.origin.0 + main.size.0 / 2.0, main.origin.1 + main.size.1 / 2.0)
+        });
+        // Xwayland only learns where the cursor is while it's over one of its
+        // windows
+        let x11 = match env::var_os("WAYLAND_DISPLAY") {
+            Some(_) => None,
+            None => xcb::Connection::open().ok(),
+        };
+        let mut pointer = Pointer { location, bounds, x11 };
+        pointer.sync();
+        pointer
+    }
+
+    /// Takes the location of the cursor from the X server, if there is one.
+    fn sync(&mut self) {
+        if let Some(Ok((x, y))) = self.x11.as_ref().map(xcb::Connection::pointer) {
+            self.location = (f64::from(x), f64::from(y));
+        }
+    }
+
+    /// Moves by `(dx, dy)`, staying on the displays.
+    fn move_by(&mut self, (dx, dy): (f64, f64)) {
+        let (x, y) = (self.location.0 + dx, self.location.1 + dy);
+        self.location = match self.bounds {
+            Some(Bounds { origin: (ox, oy), size: (w, h) }) => {
+                (x.clamp(ox, ox + (w - 1.0).max(0.0)), y.clamp(oy, oy + (h - 1.0).max(0.0)))
+            },
+            None => (x, y),
+        };
+        self.sync();
+    }
+
+    /// Moves to the point at `position` on absolute axes, which span every
+    /// display or, if no display is known, a pixel each.
+    fn place(&mut self, (x, y): (&Axis, &Axis), position: (i32, i32)) {
+        self.location = match self.bounds {
+            Some(Bounds { origin: (ox, oy), size: (w, h) }) => {
+                (ox + x.fraction(position.0) * w, oy + y.fraction(position.1) * h)
+            },
+            None => (f64::from(position.0 - x.min), f64::from(position.1 - y.min)),
+        };
+        self.sync();
+    }
+}
+
+/// A key or button that changed within a frame.
+#[derive(Copy, Clone, Debug)]
+enum Press {
+    Key(Key),
+    Button(Button),
+}
+
+/// The changes reported by a device before the next `SYN_REPORT`, which are
+/// applied together.
+#[derive(Default)]
+struct Frame {
+    motion: (f64, f64),
+    positioned: bool,
+    scroll: (i32, i32),
+    presses: Vec<(Press, bool)>,
+    dropped: bool,
+}
+
+/// Turns the events of a device into input events.
+struct Decoder {
+    pointing: Pointing,
+    frame: Frame,
+    /// The latest values of the absolute axes.
+    position: (i32, i32),
+    touching: bool,
+    /// The touchpad position that motion is measured from, while touching.
+    anchor: Option<(i32, i32)>,
+}
+
+impl Decoder {
+    fn new(pointing: Pointing) -> Decoder {
+        Decoder { pointing, frame: Frame::default(), position: (0, 0), touching: false, anchor: None }
+    }
+
+    /// Handles an event of type `kind`, calling `emit` with the input events
+    /// of each completed frame.
+    fn decode<F>(&mut self, (kind, code, value): (u16, u16, i32), pointer: &mut Pointer, emit: &mut F)
+        where F: FnMut(&InputEvent)
+    {
+        match (kind, code) {
+            (EV_SYN, SYN_REPORT) => {
+                let frame = mem::take(&mut self.frame);
+                // The events before dropped ones are incomplete, so they're
+                // discarded up to the next report
+                if !frame.dropped {
+                    self.apply(frame, pointer, emit);
+                }
+            },
+            (EV_SYN, SYN_DROPPED) => self.frame.dropped = true,
+            (EV_KEY, BTN_TOUCH) => match self.pointing {
+                Pointing::Touchpad(_) => self.touching = value != 0,
+                Pointing::Absolute(..) => self.frame.presses.push((Press::Button(Button::Left), value != 0)),
+                _ => {},
+            },
+            // Auto-repeats have a value of 2 and are reported as presses
+            (EV_KEY, code) => if let Some(press) = press(code) {
+                self.frame.presses.push((press, value != 0));
+            },
+            (EV_REL, REL_X) => self.frame.motion.0 += f64::from(value),
+            (EV_REL, REL_Y) => self.frame.motion.1 += f64::from(value),
+            (EV_REL, REL_HWHEEL) => self.frame.scroll.0 += value,
+            (EV_REL, REL_WHEEL) => self.frame.scroll.1 += value,
+            (EV_ABS, ABS_X) => {
+                self.position.0 = value;
+                self.frame.positioned = true;
+            },
+            (EV_ABS, ABS_Y) => {
+                self.position.1 = value;
+                self.frame.positioned = true;
+            },
+            _ => {},
+        }
+    }
+
+    fn apply<F>(&mut self, frame: Frame, pointer: &mut Pointer, emit: &mut F)
+        where F: FnMut(&InputEvent)
+    {
+        let previous = pointer.location;
+        match self.pointing {
+            Pointing::Relative => pointer.move_by(frame.motion),
+            Pointing::Touchpad(scale) => {
+                if !self.touching {
+                    self.anchor = None;
+                } else if let Some((x, y)) = self.anchor.replace(self.position) {
+                    let (dx, dy) = (self.position.0 - x, self.position.1 - y);
+                    pointer.move_by((f64::from(dx) * scale, f64::from(dy) * scale));
+                }
+            },
+            Pointing::Absolute(ref x, ref y) if frame.positioned => pointer.place((x, y), self.position),
+            _ => {},
+        }
+        if pointer.location != previous {
+            emit(&InputEvent::MouseMoved(pointer.location));
+        }
+        for (press, down) in frame.presses {
+            emit(&match (press, down) {
+                (Press::Key(key), true) => InputEvent::KeyDown(key),
+                (Press::Key(key), false) => InputEvent::KeyUp(key),
+                (Press::Button(button), true) => InputEvent::ButtonDown(button, pointer.location),
+                (Press::Button(button), false) => InputEvent::ButtonUp(button, pointer.location),
+            });
+        }
+        if frame.scroll != (0, 0) {
+            emit(&InputEvent::Scroll { dx: frame.scroll.0, dy: frame.scroll.1 });
+        }
+    }
+}
+
+/// Returns the key or mouse button with the evdev code `code`.
+fn press(code: u16) -> Option<Press> {
+    match code {
+        0..=0xFF | KEY_OK..=KEY_MAX => Some(Press::Key(Key::from_native_code(code))),
+        // BTN_FORWARD and BTN_BACK, which some mice send instead of
+        // BTN_EXTRA and BTN_SIDE
+        0x115 => Some(Press::Button(Button::Forward)),
+        0x116 => Some(Press::Button(Button::Back)),
+        _ => Button::from_evdev_code(code).map(Press::Button),
+    }
+}
+
+/// An open input device.
+struct Source {
+    device: Device,
+    file: File,
+    decoder: Decoder,
+}
+
+impl Source {
+    /// Opens the device at `path`, returning `None` if it isn't a keyboard or
+    /// pointing device.
+    fn open(path: &Path) -> io::Result<Option<Source>> {
+        let file = OpenOptions::new().read(true).custom_flags(libc::O_NONBLOCK).open(path)?;
+
+        let mut types = [0u8; 4];
+        let mut keys = [0u8; KEY_MAX as usize / 8 + 1];
+        let mut relative = [0u8; 2];
+        let mut absolute = [0u8; 8];
+        let mut properties = [0u8; 4];
+        read_attribute(&file, 0x20, &mut types);
+        read_attribute(&file, 0x20 + u32::from(EV_KEY), &mut keys);
+        read_attribute(&file, 0x20 + u32::from(EV_REL), &mut relative);
+        read_attribute(&file, 0x20 + u32::from(EV_ABS), &mut absolute);
+        read_attribute(&file, 0x09, &mut properties);
+
+        // Game controllers and motion sensors don't move the pointer
+        let excluded = has_bit(&keys, BTN_JOYSTICK)
+            || has_bit(&keys, BTN_GAMEPAD)
+            || has_bit(&properties, INPUT_PROP_ACCELEROMETER);
+        if excluded {
+            return Ok(None);
+        }
+
+        let pointing = if has_bit(&types, EV_ABS) && has_bit(&absolute, ABS_X) && has_bit(&absolute, ABS_Y) {
+            let mut x: libc::input_absinfo = unsafe { mem::zeroed() };
+            let mut y: libc::input_absinfo = unsafe { mem::zeroed() };
+            read_attribute(&file, 0x40 + u32::from(ABS_X), &mut x);
+            read_attribute(&file, 0x40 + u32::from(ABS_Y), &mut y);
+            let touchpad = !has_bit(&properties, INPUT_PROP_DIRECT)
+                && has_bit(&keys, BTN_TOOL_FINGER)
+                && !has_bit(&keys, BTN_TOOL_PEN);
+            if touchpad {
+                let scale = if x.resolution > 0 { TOUCHPAD_SPEED / f64::from(x.resolution) } else { 1.0 };
+                Pointing::Touchpad(scale)
+            } else {
+                Pointing::Absolute(Axis { min: x.minimum, max: x.maximum }, Axis { min: y.minimum, max: y.maximum })
+            }
+        } else if has_bit(&types, EV_REL) && has_bit(&relative, REL_X) {
+            Pointing::Relative
+        } else {
+            Pointing::None
+        };
+
+        let has_keys = (1..BTN_MOUSE).any(|code| has_bit(&keys, code));
+        let scrolls = has_bit(&relative, REL_WHEEL) || has_bit(&relative, REL_HWHEEL);
+        if pointing == Pointing::None && !has_keys && !scrolls {
+            return Ok(None);
+        }
+
+        let mut name = [0u8; 256];
+        read_attribute(&file, 0x06, &mut name);
+        let name = CStr::from_bytes_until_nul(&name).map(|name| name.to_string_lossy().into_owned());
+        let mut id: libc::input_id = unsafe { mem::zeroed() };
+        read_attribute(&file, 0x02, &mut id);
+
+        let device = Device {
+            path: path.to_owned(),
+            name: name.unwrap_or_default(),
+            is_virtual: id.bustype == BUS_VIRTUAL,
+        };
+        Ok(Some(Source { device, file, decoder: Decoder::new(pointing) }))
+    }
+
+    /// Reads the pending events of the device, returning `false` once it has
+    /// been removed.
+    fn read<F>(&mut self, pointer: &mut Pointer, callback: &mut F) -> bool
+        where F: FnMut(&InputEvent, &Device)
+    {
+        let mut events: [libc::input_event; 64] = unsafe { mem::zeroed() };
+        loop {
+            let bytes = unsafe {
+                slice::from_raw_parts_mut(events.as_mut_ptr() as *mut u8, mem::size_of_val(&events))
+            };
+            match self.file.read(bytes) {
+                Ok(0) => return false,
+                Ok(read) => {
+                    let device = &self.device;
+                    let mut emit = |event: &InputEvent| callback(event, device);
+                    for event in &events[..read / mem::size_of::<libc::input_event>()] {
+                        self.decoder.decode((event.type_, event.code, event.value), pointer, &mut emit);
+                    }
+                },
+                Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => return true,
+                Err(ref error) if error.kind() == io::ErrorKind::Interrupted => {},
+                Err(_) => return false,
+            }
+        }
+    }
+}
+
+/// A udev monitor that reports input devices as they are added and removed.
+struct Udev {
+    lib: &'static Libudev,
+    udev: *mut c_void,
+    monitor: *mut c_void,
+}
+
+impl Drop for Udev {
+    fn drop(&mut self) {
+        unsafe {
+            (self.lib.udev_monitor_unref)(self.monitor);
+            (self.lib.udev_unref)(self.udev);
+        }
+    }
+}
+
+impl Udev {
+    fn new() -> Option<Udev> {
+        let lib = LIBUDEV.as_ref()?;
+        unsafe {
+            let udev = (lib.udev_new)();
+            if udev.is_null() {
+                return None;
+            }
+            let monitor = (lib.udev_monitor_new_from_netlink)(udev, b"udev\0".as_ptr() as _);
+            if monitor.is_null() {
+                (lib.udev_unref)(udev);
+                return None;
+            }
+            let udev = Udev { lib, udev, monitor };
+            (lib.udev_monitor_filter_add_match_subsystem_devtype)(monitor, b"input\0".as_ptr() as _, ptr::null());
+            if (lib.udev_monitor_enable_receiving)(monitor) < 0 {
+                return None;
+            }
+            Some(udev)
+        }
+    }
+
+    /// Returns the file descriptor that becomes readable when devices change.
+    fn fd(&self) -> c_int {
+        unsafe { (self.lib.udev_monitor_get_fd)(self.monitor) }
+    }
+
+    /// Returns the event device nodes of every input device.
+    fn devices(&self) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        unsafe {
+            let enumerate = (self.lib.udev_enumerate_new)(self.udev);
+            if enumerate.is_null() {
+                return paths;
+            }
+            (self.lib.udev_enumerate_add_match_subsystem)(enumerate, b"input\0".as_ptr() as _);
+            (self.lib.udev_enumerate_scan_devices)(enumerate);
+            let mut entry = (self.lib.udev_enumerate_get_list_entry)(enumerate);
+            while !entry.is_null() {
+                let device = (self.lib.udev_device_new_from_syspath)(
+                    self.udev,
+                    (self.lib.udev_list_entry_get_name)(entry),
+                );
+                if !device.is_null() {
+                    paths.extend(event_node((self.lib.udev_device_get_devnode)(device)));
+                    (self.lib.udev_device_unref)(device);
+                }
+                entry = (self.lib.udev_list_entry_get_next)(entry);
+            }
+            (self.lib.udev_enumerate_unref)(enumerate);
+        }
+        paths
+    }
+
+    /// Returns the next event device node that was added or removed, and
+    /// whether it was added.
+    fn receive(&self) -> Option<(PathBuf, bool)> {
+        loop {
+            unsafe {
+                let device = (self.lib.udev_monitor_receive_device)(self.monitor);
+                if device.is_null() {
+                    return None;
+                }
+                let action = (self.lib.udev_device_get_action)(device);
+                let added = !action.is_null() && CStr::from_ptr(action).to_bytes() == b"add";
+                let removed = !action.is_null() && CStr::from_ptr(action).to_bytes() == b"remove";
+                let path = event_node((self.lib.udev_device_get_devnode)(device));
+                (self.lib.udev_device_unref)(device);
+                if let Some(path) = path {
+                    if added || removed {
+                        return Some((path, added));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns the device node `node` if it is an event device.
+unsafe fn event_node(node: *const c_char) -> Option<PathBuf> {
+    if node.is_null() {
+        return None;
+    }
+    let path = Path::new(OsStr::from_bytes(CStr::from_ptr(node).to_bytes()));
+    let event = path.file_name().is_some_and(|name| name.as_bytes().starts_with(b"event"));
+    if event { Some(path.to_owned()) } else { None }
+}
+
+/// Returns the event device nodes in `/dev/input`.
+fn scan_directory() -> Vec<PathBuf> {
+    fs::read_dir("/dev/input")
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_name().as_bytes().starts_with(b"event"))
+                .map(|entry| entry.path())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The devices read by a listener.
+struct Reader {
+    udev: Option<Udev>,
+    sources: Vec<Source>,
+    pointer: Pointer,
+    scanned: Instant,
+}
+
+impl Reader {
+    fn new() -> Reader {
+        Reader { udev: Udev::new(), sources: Vec::new(), pointer: Pointer::new(), scanned: Instant::now() }
+    }
+
+    /// Opens every device that isn't open yet, failing if none can be opened
+    /// for lack of permission.
+    fn scan(&mut self) -> Result<()> {
+        let paths = match self.udev {
+            Some(ref udev) => udev.devices(),
+            None => scan_directory(),
+        };
+        self.scanned = Instant::now();
+        let mut denied = false;
+        for path in paths {
+            match self.add(&path) {
+                Err(ref error) if error.kind() == io::ErrorKind::PermissionDenied => denied = true,
+                _ => {},
+            }
+        }
+        if denied && self.sources.is_empty() {
+            return Err(Error::PermissionDenied);
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, path: &Path) -> io::Result<()> {
+        if self.sources.iter().all(|source| source.device.path != path) {
+            self.sources.extend(Source::open(path)?);
+        }
+        Ok(())
+    }
+
+    /// Reads devices until `stop` is closed.
+    fn run<F>(&mut self, stop: &File, callback: &mut F)
+        where F: FnMut(&InputEvent, &Device)
+    {
+        let poll = |fd| libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+        loop {
+            let mut fds = vec![poll(stop.as_raw_fd())];
+            fds.extend(self.udev.as_ref().map(|udev| poll(udev.fd())));
+            let first = fds.len();
+            fds.extend(self.sources.iter().map(|source| poll(source.file.as_raw_fd())));
+
+            let timeout = match self.udev {
+                Some(_) => -1,
+                None => RESCAN.saturating_sub(self.scanned.elapsed()).as_millis() as c_int,
+            };
+            if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as _, timeout) } < 0 {
+                if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return;
+            }
+            if fds[0].revents != 0 {
+                return;
+            }
+
+            // Sources are only removed after reading, so they line up with
+            // their descriptors until then
+            let mut removed = Vec::new();
+            for (index, source) in self.sources.iter_mut().enumerate() {
+                let revents = fds[first + index].revents;
+                let readable = revents & libc::POLLIN == 0 || source.read(&mut self.pointer, callback);
+                if !readable || revents & (libc::POLLHUP | libc::POLLERR | libc::POLLNVAL) != 0 {
+                    removed.push(source.device.path.clone());
+                }
+            }
+            self.sources.retain(|source| !removed.contains(&source.device.path));
+
+            let changes = match self.udev {
+                Some(ref udev) if fds[1].revents != 0 => udev.receive().into_iter().collect(),
+                _ => Vec::new(),
+            };
+            for (path, added) in changes {
+                if added {
+                    let _ = self.add(&path);
+                } else {
+                    self.sources.retain(|source| source.device.path != path);
+                }
+            }
+            if self.udev.is_none() && self.scanned.elapsed() >= RESCAN {
+                let _ = self.scan();
+            }
+        }
+    }
+}
+
+/// A handle to a thread reading input devices, which stops when the handle
+/// is dropped.
+#[derive(Debug)]
+pub struct Listener {
+    stop: Option<File>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        // Closing the pipe wakes the thread
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+/// Calls `callback` on a background thread with every event from keyboards
+/// and pointing devices, and the device it came from, until the returned
+/// listener is dropped.
+///
+/// Devices plugged in later are read as they appear.
+///
+/// Fails with
+/// [`Error::PermissionDenied`](../error/enum.Error.html#variant.PermissionDenied)
+/// if there are input devices but none can be opened.
+pub fn listen<F>(mut callback: F) -> Result<Listener>
+    where F: FnMut(&InputEvent, &Device) + Send + 'static
+{
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(io_error(io::Error::last_os_error()));
+    }
+    let (stop, stopper) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+
+    let (sender, receiver) = mpsc::channel();
+    let thread = thread::Builder::new()
+        .name("auto-input-devices".into())
+        .spawn(move || {
+            let mut reader = Reader::new();
+            let result = reader.scan();
+            let started = result.is_ok();
+            let _ = sender.send(result);
+            if started {
+                reader.run(&stop, &mut callback);
+            }
+        })
+        .map_err(io_error)?;
+
+    match receiver.recv() {
+        Ok(Ok(())) => Ok(Listener { stop: Some(stopper), thread: Some(thread) }),
+        Ok(Err(error)) => {
+            let _ = thread.join();
+            Err(error)
+        },
+        Err(_) => Err(Error::Failed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use events::InputEvent;
+    use keyboard::Key;
+    use mouse::Button;
+    use super::*;
+
+    #[test]
+    fn decode_frames() {
+        let mut pointer = Pointer {
+            location: (50.0, 50.0),
+            bounds: Some(Bounds { origin: (0.0, 0.0), size: (100.0, 100.0) }),
+            x11: None,
+        };
+        let mut events = Vec::new();
+        let mut emit = |event: &InputEvent| events.push(*event);
+
+        let mut mouse = Decoder::new(Pointing::Relative);
+        for &event in &[
+            (EV_REL, REL_X, 10), (EV_REL, REL_Y, -80), (EV_KEY, 0x110, 1), (EV_SYN, SYN_REPORT, 0),
+            (EV_REL, REL_WHEEL, -1), (EV_KEY, 0x110, 0), (EV_SYN, SYN_REPORT, 0),
+            // Discarded up to the next report
+            (EV_REL, REL_X, 5), (EV_SYN, SYN_DROPPED, 0), (EV_SYN, SYN_REPORT, 0),
+        ] {
+            mouse.decode(event, &mut pointer, &mut emit);
+        }
+
+        let mut keyboard = Decoder::new(Pointing::None);
+        for &event in &[(EV_KEY, 30, 1), (EV_KEY, 30, 2), (EV_KEY, 30, 0), (EV_SYN, SYN_REPORT, 0)] {
+            keyboard.decode(event, &mut pointer, &mut emit);
+        }
+
+        let axis = Axis { min: 0, max: 1000 };
+        let mut screen = Decoder::new(Pointing::Absolute(axis, axis));
+        for &event in &[(EV_ABS, ABS_X, 250), (EV_ABS, ABS_Y, 500), (EV_KEY, BTN_TOUCH, 1), (EV_SYN, SYN_REPORT, 0)] {
+            screen.decode(event, &mut pointer, &mut emit);
+        }
+
+        assert_eq!(events, [
+            InputEvent::MouseMoved((60.0, 0.0)),
+            InputEvent::ButtonDown(Button::Left, (60.0, 0.0)),
+            InputEvent::ButtonUp(Button::Left, (60.0, 0.0)),
+            InputEvent::Scroll { dx: 0, dy: -1 },
+            InputEvent::KeyDown(Key::A),
+            InputEvent::KeyDown(Key::A),
+            InputEvent::KeyUp(Key::A),
+            InputEvent::MouseMoved((25.0, 50.0)),
+            InputEvent::ButtonDown(Button::Left, (25.0, 50.0)),
+        ]);
+    }
+}
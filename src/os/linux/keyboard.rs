@@ -0,0 +1,618 @@
+//! ⌨️ Keyboard layout utilities.
+//!
+//! Characters are mapped to keys with xkbcommon, which is loaded the first
+//! time it is needed, so that typing follows the user's layout rather than
+//! assuming US QWERTY. Keys are evdev key codes, as in
+//! `linux/input-event-codes.h`, which are 8 less than the XKB key codes of
+//! the same keys.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use auto::os::keyboard::Keymap;
+//!
+//! let keymap = Keymap::current().unwrap();
+//! for stroke in keymap.keys_for_char('é').unwrap() {
+//!     println!("key {} with {:?}", stroke.key, stroke.modifiers);
+//! }
+//! ```
+
+use std::env;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr::{self, NonNull};
+use std::slice;
+
+use error::{Error, Result};
+use super::xcb;
+
+/// The amount that XKB key codes are offset from evdev key codes.
+const EVDEV_OFFSET: u32 = 8;
+
+/// `XKB_KEYMAP_FORMAT_TEXT_V1`
+const FORMAT_TEXT_V1: c_int = 1;
+
+/// `XKB_STATE_MODS_DEPRESSED`
+const MODS_DEPRESSED: c_int = 1 << 0;
+
+/// `XKB_STATE_MODS_LATCHED`
+const MODS_LATCHED: c_int = 1 << 1;
+
+/// `XKB_STATE_MODS_LOCKED`
+const MODS_LOCKED: c_int = 1 << 2;
+
+/// `XKB_STATE_LAYOUT_LOCKED`
+const LAYOUT_LOCKED: c_int = 1 << 6;
+
+/// `XKB_STATE_LAYOUT_EFFECTIVE`
+const LAYOUT_EFFECTIVE: c_int = 1 << 7;
+
+/// Names of the keys that only exist in keymaps to hold modifiers, without a
+/// physical key behind them.
+const VIRTUAL_KEYS: &[&[u8]] = &[b"LVL3", b"MDSW", b"ALT", b"META", b"SUPR", b"HYPR"];
+
+/// Dead keysyms, the spacing accent each types before a space, and the
+/// characters each composes with the base characters in the same order.
+const DEAD_KEYS: &[(u32, char, &str, &str)] = &[
+    // dead_grave
+    (0xfe50, '`', "aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+    // dead_acute
+    (0xfe51, '´', "aceinosuyzACEINOSUYZ", "áćéíńóśúýźÁĆÉÍŃÓŚÚÝŹ"),
+    // dead_circumflex
+    (0xfe52, '^', "aeiouAEIOU", "âêîôûÂÊÎÔÛ"),
+    // dead_tilde
+    (0xfe53, '~', "anoANO", "ãñõÃÑÕ"),
+    // dead_diaeresis
+    (0xfe57, '¨', "aeiouyAEIOUY", "äëïöüÿÄËÏÖÜŸ"),
+    // dead_abovering
+    (0xfe58, '°', "auAU", "åůÅŮ"),
+    // dead_caron
+    (0xfe5a, 'ˇ', "cdenrstzCDENRSTZ", "čďěňřšťžČĎĚŇŘŠŤŽ"),
+    // dead_cedilla
+    (0xfe5b, '¸', "csCS", "çşÇŞ"),
+];
+
+/// `struct xkb_rule_names`
+#[repr(C)]
+struct RuleNames {
+    rules: *const c_char,
+    model: *const c_char,
+    layout: *const c_char,
+    variant: *const c_char,
+    options: *const c_char,
+}
+
+dynamic_library! {
+    /// The functions of xkbcommon.
+    struct Xkb("libxkbcommon.so.0\0", "libxkbcommon.so\0") {
+        fn xkb_context_new(flags: c_int) -> *mut c_void;
+        fn xkb_context_unref(context: *mut c_void);
+        fn xkb_keymap_new_from_names(context: *mut c_void, names: *const RuleNames, flags: c_int) -> *mut c_void;
+        fn xkb_keymap_new_from_string(
+            context: *mut c_void,
+            string: *const c_char,
+            format: c_int,
+            flags: c_int,
+        ) -> *mut c_void;
+        fn xkb_keymap_get_as_string(keymap: *mut c_void, format: c_int) -> *mut c_char;
+        fn xkb_keymap_unref(keymap: *mut c_void);
+        fn xkb_keymap_min_keycode(keymap: *mut c_void) -> u32;
+        fn xkb_keymap_max_keycode(keymap: *mut c_void) -> u32;
+        fn xkb_keymap_num_layouts(keymap: *mut c_void) -> u32;
+        fn xkb_keymap_layout_get_name(keymap: *mut c_void, layout: u32) -> *const c_char;
+        fn xkb_keymap_key_get_name(keymap: *mut c_void, key: u32) -> *const c_char;
+        fn xkb_keymap_num_layouts_for_key(keymap: *mut c_void, key: u32) -> u32;
+        fn xkb_keymap_num_levels_for_key(keymap: *mut c_void, key: u32, layout: u32) -> u32;
+        fn xkb_keymap_key_get_syms_by_level(
+            keymap: *mut c_void,
+            key: u32,
+            layout: u32,
+            level: u32,
+            syms: *mut *const u32,
+        ) -> c_int;
+        fn xkb_keymap_key_get_mods_for_level(
+            keymap: *mut c_void,
+            key: u32,
+            layout: u32,
+            level: u32,
+            masks: *mut u32,
+            size: usize,
+        ) -> usize;
+        fn xkb_state_new(keymap: *mut c_void) -> *mut c_void;
+        fn xkb_state_update_key(state: *mut c_void, key: u32, direction: c_int) -> c_int;
+        fn xkb_state_update_mask(
+            state: *mut c_void,
+            depressed_mods: u32,
+            latched_mods: u32,
+            locked_mods: u32,
+            depressed_layout: u32,
+            latched_layout: u32,
+            locked_layout: u32,
+        ) -> c_int;
+        fn xkb_state_serialize_mods(state: *mut c_void, components: c_int) -> u32;
+        fn xkb_state_serialize_layout(state: *mut c_void, components: c_int) -> u32;
+        fn xkb_state_unref(state: *mut c_void);
+        fn xkb_utf32_to_keysym(c: u32) -> u32;
+    }
+}
+
+dynamic_library! {
+    /// The functions of xkbcommon-x11, which reads keymaps from X servers.
+    struct XkbX11("libxkbcommon-x11.so.0\0", "libxkbcommon-x11.so\0") {
+        fn xkb_x11_setup_xkb_extension(
+            connection: *mut c_void,
+            major: u16,
+            minor: u16,
+            flags: c_int,
+            major_out: *mut u16,
+            minor_out: *mut u16,
+            base_event: *mut u8,
+            base_error: *mut u8,
+        ) -> c_int;
+        fn xkb_x11_get_core_keyboard_device_id(connection: *mut c_void) -> i32;
+        fn xkb_x11_keymap_new_from_device(
+            context: *mut c_void,
+            connection: *mut c_void,
+            device: i32,
+            flags: c_int,
+        ) -> *mut c_void;
+        fn xkb_x11_state_new_from_device(keymap: *mut c_void, connection: *mut c_void, device: i32) -> *mut c_void;
+    }
+}
+
+lazy_static! {
+    static ref XKB: Option<Xkb> = Xkb::load();
+    static ref XKB_X11: Option<XkbX11> = XkbX11::load();
+}
+
+fn xkb() -> Result<&'static Xkb> {
+    XKB.as_ref().ok_or(Error::Unsupported)
+}
+
+bitflags! {
+    /// Modifiers held while pressing a key, as X11 core modifier masks.
+    pub struct Modifiers: u32 {
+        /// Shift.
+        const SHIFT = 1;
+        /// Caps Lock.
+        const CAPS_LOCK = 1 << 1;
+        /// Control.
+        const CONTROL = 1 << 2;
+        /// Alt, which is `Mod1`.
+        const ALT = 1 << 3;
+        /// Num Lock, which is `Mod2`.
+        const NUM_LOCK = 1 << 4;
+        /// `Mod3`, which is usually unassigned.
+        const MOD3 = 1 << 5;
+        /// The Super or Windows key, which is `Mod4`.
+        const SUPER = 1 << 6;
+        /// The third shift level, such as AltGr, which is `Mod5`.
+        const LEVEL3 = 1 << 7;
+    }
+}
+
+/// A key to press while holding modifiers.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Keystroke {
+    /// The evdev key code of the key.
+    pub key: u16,
+    /// The modifiers to hold while pressing the key.
+    pub modifiers: Modifiers,
+}
+
+/// A keyboard mapping between keys and the symbols they type, which may hold
+/// several layouts that the user switches between.
+pub struct Keymap {
+    keymap: NonNull<c_void>,
+    layout: u32,
+}
+
+// Keymaps can't change once compiled, and each holds its own reference
+unsafe impl Send for Keymap {}
+
+impl Drop for Keymap {
+    fn drop(&mut self) {
+        if let Some(xkb) = XKB.as_ref() {
+            unsafe { (xkb.xkb_keymap_unref)(self.keymap.as_ptr()) };
+        }
+    }
+}
+
+impl fmt::Debug for Keymap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Keymap")
+            .field("layout", &self.layout_name(self.layout))
+            .finish()
+    }
+}
+
+impl Keymap {
+    /// Returns the keymap of the X server with its active layout selected,
+    /// or the default keymap if there is no X server to connect to.
+    ///
+    /// Wayland sessions usually run Xwayland, whose keymap follows that of
+    /// the compositor. The default keymap is set by the `XKB_DEFAULT_LAYOUT`
+    /// and related environment variables, or else is US QWERTY.
+    pub fn current() -> Result<Keymap> {
+        let xkb = xkb()?;
+        match unsafe { x11_keymap(xkb) } {
+            Some(keymap) => Ok(keymap),
+            None => Keymap::from_names("", ""),
+        }
+    }
+
+    /// Compiles the keymap for the XKB `layout`, such as `"de"` or `"us,ru"`,
+    /// and `variant`, such as `"nodeadkeys"`.
+    ///
+    /// Either may be empty to use the default, as with
+    /// [`current`](#method.current) without an X server.
+    pub fn from_names(layout: &str, variant: &str) -> Result<Keymap> {
+        let xkb = xkb()?;
+        let name = |name: &str| match name {
+            "" => Ok(None),
+            _ => CString::new(name).map(Some).map_err(|_| Error::Failed),
+        };
+        let (layout, variant) = (name(layout)?, name(variant)?);
+        let as_ptr = |name: &Option<CString>| name.as_ref().map_or(ptr::null(), |name| name.as_ptr());
+        let names = RuleNames {
+            rules: ptr::null(),
+            model: ptr::null(),
+            layout: as_ptr(&layout),
+            variant: as_ptr(&variant),
+            options: ptr::null(),
+        };
+        compile(xkb, |context| unsafe { (xkb.xkb_keymap_new_from_names)(context, &names, 0) })
+    }
+
+    /// Parses a keymap in the XKB text format, such as one received from a
+    /// Wayland compositor.
+    pub fn from_string(keymap: &str) -> Result<Keymap> {
+        let xkb = xkb()?;
+        let keymap = CString::new(keymap).map_err(|_| Error::Failed)?;
+        compile(xkb, |context| unsafe {
+            (xkb.xkb_keymap_new_from_string)(context, keymap.as_ptr(), FORMAT_TEXT_V1, 0)
+        })
+    }
+
+    /// Returns the keymap in the XKB text format.
+    pub fn text(&self) -> Result<String> {
+        let xkb = xkb()?;
+        unsafe {
+            let text = (xkb.xkb_keymap_get_as_string)(self.keymap.as_ptr(), FORMAT_TEXT_V1);
+            if text.is_null() {
+                return Err(Error::Failed);
+            }
+            let string = CStr::from_ptr(text).to_string_lossy().into_owned();
+            libc::free(text as *mut c_void);
+            Ok(string)
+        }
+    }
+
+    /// Returns the number of layouts in the keymap.
+    pub fn num_layouts(&self) -> u32 {
+        xkb().map_or(0, |xkb| unsafe { (xkb.xkb_keymap_num_layouts)(self.keymap.as_ptr()) })
+    }
+
+    /// Returns the name of the layout at `index`, such as "German".
+    pub fn layout_name(&self, index: u32) -> Option<String> {
+        let xkb = xkb().ok()?;
+        unsafe {
+            let name = (xkb.xkb_keymap_layout_get_name)(self.keymap.as_ptr(), index);
+            if name.is_null() {
+                None
+            } else {
+                Some(CStr::from_ptr(name).to_string_lossy().into_owned())
+            }
+        }
+    }
+
+    /// Returns the index of the layout that keys are looked up in.
+    #[inline]
+    pub fn layout(&self) -> u32 {
+        self.layout
+    }
+
+    /// Sets the index of the layout that keys are looked up in, which should
+    /// be the layout active when the keys are pressed.
+    #[inline]
+    pub fn set_layout(&mut self, index: u32) {
+        self.layout = index;
+    }
+
+    /// Returns the key and modifiers that type the keysym `keysym`, or `None`
+    /// if no key in the layout produces it.
+    ///
+    /// Keys needing fewer modifiers are preferred, and Caps Lock or Num Lock
+    /// are only used if nothing else produces the keysym. Keys that the
+    /// keymap only defines to hold modifiers, which have no physical key, are
+    /// skipped.
+    pub fn key_for_keysym(&self, keysym: u32) -> Option<Keystroke> {
+        let xkb = xkb().ok()?;
+        let keymap = self.keymap.as_ptr();
+        let mut best: Option<((bool, u32), Keystroke)> = None;
+        unsafe {
+            let (min, max) = ((xkb.xkb_keymap_min_keycode)(keymap), (xkb.xkb_keymap_max_keycode)(keymap));
+            for code in min.max(EVDEV_OFFSET)..=max {
+                let layouts = (xkb.xkb_keymap_num_layouts_for_key)(keymap, code);
+                let name = (xkb.xkb_keymap_key_get_name)(keymap, code);
+                if layouts == 0 || !name.is_null() && VIRTUAL_KEYS.contains(&CStr::from_ptr(name).to_bytes()) {
+                    continue;
+                }
+                // Keys with fewer layouts wrap around, as XKB does by default
+                let layout = self.layout % layouts;
+                for level in 0..(xkb.xkb_keymap_num_levels_for_key)(keymap, code, layout) {
+                    let mut syms = ptr::null();
+                    let count = (xkb.xkb_keymap_key_get_syms_by_level)(keymap, code, layout, level, &mut syms);
+                    if count <= 0 || !slice::from_raw_parts(syms, count as usize).contains(&keysym) {
+                        continue;
+                    }
+                    let mut masks = [0u32; 16];
+                    let count = (xkb.xkb_keymap_key_get_mods_for_level)(
+                        keymap, code, layout, level, masks.as_mut_ptr(), masks.len(),
+                    );
+                    // The first level needs no modifiers even when no entry says so
+                    let masks = match count.min(masks.len()) {
+                        0 if level == 0 => &[0][..],
+                        count => &masks[..count],
+                    };
+                    for &mask in masks {
+                        let modifiers = Modifiers::from_bits_truncate(mask);
+                        let locks = modifiers.intersects(Modifiers::CAPS_LOCK | Modifiers::NUM_LOCK);
+                        let rank = (locks, mask.count_ones());
+                        if best.as_ref().is_none_or(|&(best, _)| rank < best) {
+                            let key = (code - EVDEV_OFFSET) as u16;
+                            best = Some((rank, Keystroke { key, modifiers }));
+                        }
+                    }
+                }
+            }
+        }
+        best.map(|(_, stroke)| stroke)
+    }
+
+    /// Returns the key and modifiers that type `c`, or `None` if no single key
+    /// press produces it.
+    ///
+    /// Characters that need dead keys are typed with
+    /// [`keys_for_char`](#method.keys_for_char).
+    pub fn key_for_char(&self, c: char) -> Option<Keystroke> {
+        let keysym = unsafe { (xkb().ok()?.xkb_utf32_to_keysym)(c as u32) };
+        if keysym == 0 {
+            return None;
+        }
+        self.key_for_keysym(keysym)
+    }
+
+    /// Returns the keystrokes that type `c` in order, or `None` if the layout
+    /// can't produce it.
+    ///
+    /// Accented letters missing from the layout, such as "é" on a German
+    /// layout, are typed as a dead key followed by the base letter. Spacing
+    /// accents held only by dead keys, such as "^", are typed as the dead key
+    /// followed by a space.
+    pub fn keys_for_char(&self, c: char) -> Option<Vec<Keystroke>> {
+        if let Some(stroke) = self.key_for_char(c) {
+            return Some(vec![stroke]);
+        }
+        let (dead, base) = dead_key_for(c)?;
+        Some(vec![self.key_for_keysym(dead)?, self.key_for_char(base)?])
+    }
+
+    /// Returns the presses and releases of evdev keys that type `c` from a
+    /// keyboard with no modifiers active, or `None` if the layout can't
+    /// produce it.
+    ///
+    /// Modifiers are held around each of the
+    /// [keystrokes](#method.keys_for_char) that need them, and locks such as
+    /// Caps Lock are turned on before the keystroke and off after it.
+    pub fn presses_for_char(&self, c: char) -> Option<Vec<(u16, bool)>> {
+        const LOCKS: Modifiers = Modifiers::CAPS_LOCK.union(Modifiers::NUM_LOCK);
+        let mut presses = Vec::new();
+        for stroke in self.keys_for_char(c)? {
+            let mut modifiers = Vec::new();
+            for bit in 0..8 {
+                let modifier = Modifiers::from_bits_truncate(1 << bit);
+                if stroke.modifiers.contains(modifier) {
+                    modifiers.push((self.modifier_key(modifier)?, LOCKS.contains(modifier)));
+                }
+            }
+            for &(key, lock) in &modifiers {
+                presses.push((key, true));
+                if lock {
+                    presses.push((key, false));
+                }
+            }
+            presses.extend_from_slice(&[(stroke.key, true), (stroke.key, false)]);
+            for &(key, lock) in modifiers.iter().rev() {
+                if lock {
+                    presses.push((key, true));
+                }
+                presses.push((key, false));
+            }
+        }
+        Some(presses)
+    }
+
+    /// Returns the key that holds `modifier`, such as the AltGr key for
+    /// `Modifiers::LEVEL3`, or `None` if the layout has none.
+    pub fn modifier_key(&self, modifier: Modifiers) -> Option<u16> {
+        let keysyms: &[u32] = match modifier {
+            // Shift_L, Shift_R
+            Modifiers::SHIFT => &[0xffe1, 0xffe2],
+            // Caps_Lock
+            Modifiers::CAPS_LOCK => &[0xffe5],
+            // Control_L, Control_R
+            Modifiers::CONTROL => &[0xffe3, 0xffe4],
+            // Alt_L, Alt_R, Meta_L
+            Modifiers::ALT => &[0xffe9, 0xffea, 0xffe7],
+            // Num_Lock
+            Modifiers::NUM_LOCK => &[0xff7f],
+            // Super_L, Super_R
+            Modifiers::SUPER => &[0xffeb, 0xffec],
+            // ISO_Level3_Shift, Mode_switch
+            Modifiers::LEVEL3 => &[0xfe03, 0xff7e],
+            _ => &[],
+        };
+        keysyms.iter()
+            .filter_map(|&keysym| self.key_for_keysym(keysym))
+            .find(|stroke| stroke.modifiers.is_empty())
+            .map(|stroke| stroke.key)
+    }
+}
+
+/// Compiles a keymap with `new` in a fresh context.
+fn compile<F>(xkb: &Xkb, new: F) -> Result<Keymap>
+    where F: FnOnce(*mut c_void) -> *mut c_void
+{
+    unsafe {
+        let context = (xkb.xkb_context_new)(0);
+        if context.is_null() {
+            return Err(Error::Failed);
+        }
+        // The keymap holds its own reference to the context
+        let keymap = new(context);
+        (xkb.xkb_context_unref)(context);
+        NonNull::new(keymap).map(|keymap| Keymap { keymap, layout: 0 }).ok_or(Error::Failed)
+    }
+}
+
+/// The modifiers and layout in effect as keys of a keymap are pressed and
+/// released, which a Wayland client reports along with its keys.
+pub(super) struct KeyState {
+    state: NonNull<c_void>,
+}
+
+// The state is only used through `&mut self`, and xkbcommon keeps no
+// thread-local data
+unsafe impl Send for KeyState {}
+
+impl Drop for KeyState {
+    fn drop(&mut self) {
+        if let Some(xkb) = XKB.as_ref() {
+            unsafe { (xkb.xkb_state_unref)(self.state.as_ptr()) };
+        }
+    }
+}
+
+impl KeyState {
+    /// Starts with no keys held and the selected layout of `keymap` locked.
+    pub fn new(keymap: &Keymap) -> Result<KeyState> {
+        let xkb = xkb()?;
+        unsafe {
+            let state = NonNull::new((xkb.xkb_state_new)(keymap.keymap.as_ptr())).ok_or(Error::Failed)?;
+            (xkb.xkb_state_update_mask)(state.as_ptr(), 0, 0, 0, 0, 0, keymap.layout);
+            Ok(KeyState { state })
+        }
+    }
+
+    /// Presses or releases the evdev key `key`, returning whether the
+    /// modifiers or layout changed.
+    pub fn update(&mut self, key: u16, down: bool) -> bool {
+        xkb().is_ok_and(|xkb| unsafe {
+            (xkb.xkb_state_update_key)(self.state.as_ptr(), u32::from(key) + EVDEV_OFFSET, down as c_int) != 0
+        })
+    }
+
+    /// Returns the depressed, latched, and locked modifier masks and the
+    /// locked layout, in the order of `wl_keyboard.modifiers`.
+    pub fn serialize(&self) -> [u32; 4] {
+        let xkb = match xkb() {
+            Ok(xkb) => xkb,
+            Err(_) => return [0; 4],
+        };
+        let state = self.state.as_ptr();
+        unsafe {
+            [
+                (xkb.xkb_state_serialize_mods)(state, MODS_DEPRESSED),
+                (xkb.xkb_state_serialize_mods)(state, MODS_LATCHED),
+                (xkb.xkb_state_serialize_mods)(state, MODS_LOCKED),
+                (xkb.xkb_state_serialize_layout)(state, LAYOUT_LOCKED),
+            ]
+        }
+    }
+}
+
+/// Returns the keymap of the X server at `DISPLAY` with its active layout
+/// selected, if there is one.
+unsafe fn x11_keymap(xkb: &Xkb) -> Option<Keymap> {
+    env::var_os("DISPLAY")?;
+    let x11 = XKB_X11.as_ref()?;
+    let server = xcb::Connection::open().ok()?;
+    let connection = server.raw();
+    let null = ptr::null_mut();
+    let setup = (x11.xkb_x11_setup_xkb_extension)(connection, 1, 0, 0, null, null, null as _, null as _);
+    let device = (x11.xkb_x11_get_core_keyboard_device_id)(connection);
+    if setup == 0 || device < 0 {
+        return None;
+    }
+    let mut keymap = compile(xkb, |context| {
+        (x11.xkb_x11_keymap_new_from_device)(context, connection, device, 0)
+    }).ok()?;
+    let state = (x11.xkb_x11_state_new_from_device)(keymap.keymap.as_ptr(), connection, device);
+    if !state.is_null() {
+        keymap.layout = (xkb.xkb_state_serialize_layout)(state, LAYOUT_EFFECTIVE);
+        (xkb.xkb_state_unref)(state);
+    }
+    Some(keymap)
+}
+
+/// Returns the dead keysym and base character that compose `c`.
+fn dead_key_for(c: char) -> Option<(u32, char)> {
+    DEAD_KEYS.iter().find_map(|&(dead, spacing, bases, composed)| {
+        if c == spacing {
+            return Some((dead, ' '));
+        }
+        let index = composed.chars().position(|x| x == c)?;
+        bases.chars().nth(index).map(|base| (dead, base))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Keymap, Keystroke, Modifiers, DEAD_KEYS};
+
+    #[test]
+    fn dead_keys() {
+        for &(_, _, bases, composed) in DEAD_KEYS {
+            assert_eq!(bases.chars().count(), composed.chars().count(), "{}", composed);
+        }
+        assert_eq!(super::dead_key_for('é'), Some((0xfe51, 'e')));
+        assert_eq!(super::dead_key_for('^'), Some((0xfe52, ' ')));
+        assert_eq!(super::dead_key_for('e'), None);
+    }
+
+    #[test]
+    fn keys_for_char() {
+        // Skipped where xkbcommon or its layouts aren't installed
+        let (us, de) = match (Keymap::from_names("us", ""), Keymap::from_names("de", "")) {
+            (Ok(us), Ok(de)) => (us, de),
+            _ => return,
+        };
+        let stroke = |key, modifiers| Some(Keystroke { key, modifiers });
+
+        assert_eq!(us.key_for_char('a'), stroke(30, Modifiers::empty()));
+        assert_eq!(us.key_for_char('A'), stroke(30, Modifiers::SHIFT));
+        assert_eq!(us.key_for_char('é'), None);
+
+        assert_eq!(de.key_for_char('z'), stroke(21, Modifiers::empty()));
+        assert_eq!(de.key_for_char('@'), stroke(16, Modifiers::LEVEL3));
+        assert_eq!(de.modifier_key(Modifiers::LEVEL3), Some(100));
+        assert_eq!(
+            de.keys_for_char('é'),
+            Some(vec![Keystroke { key: 13, modifiers: Modifiers::empty() }, Keystroke { key: 18, modifiers: Modifiers::empty() }]),
+        );
+
+        assert_eq!(us.presses_for_char('a'), Some(vec![(30, true), (30, false)]));
+        assert_eq!(us.presses_for_char('A'), Some(vec![(42, true), (30, true), (30, false), (42, false)]));
+        assert_eq!(us.presses_for_char('é'), None);
+        assert_eq!(de.presses_for_char('@'), Some(vec![(100, true), (16, true), (16, false), (100, false)]));
+        assert_eq!(
+            de.presses_for_char('é'),
+            Some(vec![(13, true), (13, false), (18, true), (18, false)]),
+        );
+
+        let parsed = Keymap::from_string(&de.text().unwrap()).unwrap();
+        assert_eq!(parsed.key_for_char('@'), de.key_for_char('@'));
+    }
+}
@@ -0,0 +1,36 @@
+/// Declares a table of functions loaded from the first shared library of a
+/// list that opens, so that the crate still runs where it isn't installed.
+///
+/// The generated `load` function returns `None` if no library opens or if
+/// any function is missing.
+macro_rules! dynamic_library {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident($($library:expr),+) {
+            $(fn $f:ident($($arg:ident: $ty:ty),* $(,)*) $(-> $ret:ty)?;)+
+        }
+    ) => {
+        $(#[$meta])*
+        #[allow(non_snake_case)]
+        $vis struct $name {
+            $($vis $f: unsafe extern "C" fn($($ty),*) $(-> $ret)?,)+
+        }
+
+        impl $name {
+            $vis fn load() -> Option<$name> {
+                let library = $crate::os::imp::open_library(&[$($library),+])?;
+                unsafe {
+                    Some($name {
+                        $($f: {
+                            let symbol = $crate::os::imp::symbol(library, concat!(stringify!($f), "\0"))?;
+                            ::std::mem::transmute::<
+                                *mut ::std::os::raw::c_void,
+                                unsafe extern "C" fn($($ty),*) $(-> $ret)?,
+                            >(symbol)
+                        },)+
+                    })
+                }
+            }
+        }
+    }
+}
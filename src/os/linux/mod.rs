@@ -0,0 +1,79 @@
+#[macro_use]
+mod macros;
+
+mod input;
+pub use self::input::*;
+
+pub mod clipboard;
+pub mod keyboard;
+pub mod screen;
+pub mod virtual_input;
+
+mod wayland;
+mod xcb;
+
+use std::io;
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr::NonNull;
+use std::time::Instant;
+
+use error::{Error, OsError, Result};
+
+/// Opens the first of the nul-terminated library `names` that loads, which
+/// stays loaded for the life of the process.
+///
+/// Libraries are loaded rather than linked so that functionality whose
+/// library is missing fails with `Error::Unsupported` instead of keeping the
+/// crate from running.
+fn open_library(names: &[&str]) -> Option<NonNull<c_void>> {
+    names.iter().find_map(|name| unsafe {
+        NonNull::new(libc::dlopen(name.as_ptr() as *const c_char, libc::RTLD_LAZY))
+    })
+}
+
+/// Returns the address of the nul-terminated symbol `name` in `library`.
+fn symbol(library: NonNull<c_void>, name: &str) -> Option<*mut c_void> {
+    let address = unsafe { libc::dlsym(library.as_ptr(), name.as_ptr() as *const c_char) };
+    if address.is_null() {
+        None
+    } else {
+        Some(address)
+    }
+}
+
+/// Returns where the X server at `DISPLAY` has the pointer.
+///
+/// In Wayland sessions, this is Xwayland, which only learns where the pointer
+/// is while it's over one of its windows.
+pub fn x11_pointer() -> Result<(f64, f64)> {
+    let (x, y) = xcb::Connection::open()?.pointer()?;
+    Ok((f64::from(x), f64::from(y)))
+}
+
+/// Converts an I/O error into the crate's error type.
+fn io_error(error: io::Error) -> Error {
+    error.raw_os_error().map_or(Error::Failed, |code| OsError::from_code(code).into())
+}
+
+/// Waits until `fd` can be read without blocking, returning `false` if
+/// `deadline` passes first.
+fn poll_readable(fd: c_int, deadline: Option<Instant>) -> Result<bool> {
+    loop {
+        // Rounded up so that the deadline has passed when the wait times out
+        let timeout = deadline.map_or(-1, |deadline| {
+            let left = deadline.saturating_duration_since(Instant::now());
+            left.as_micros().div_ceil(1000).min(c_int::MAX as u128) as c_int
+        });
+        let mut fds = libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+        match unsafe { libc::poll(&mut fds, 1, timeout) } {
+            0 => return Ok(false),
+            -1 => {
+                let error = io::Error::last_os_error();
+                if error.kind() != io::ErrorKind::Interrupted {
+                    return Err(io_error(error));
+                }
+            },
+            _ => return Ok(true),
+        }
+    }
+}
@@ -0,0 +1,468 @@
+//! 📺 Screen information utilities.
+//!
+//! Displays are the outputs of the X server's RandR extension, read through
+//! Xlib and libXrandr, which are loaded the first time they are needed. In a
+//! Wayland session, these are the outputs that Xwayland mirrors from the
+//! compositor.
+//!
+//! Bounds are in pixels from the upper-left corner of the X screen, which
+//! spans every display.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use auto::os::screen::Display;
+//!
+//! for display in Display::active().unwrap() {
+//!     let model = display.edid().and_then(|edid| edid.model.clone());
+//!     println!("{} {:?} at {:?}", display.name(), model, display.bounds());
+//! }
+//! ```
+
+use std::os::raw::{c_char, c_int, c_long, c_uchar, c_uint, c_ulong, c_ushort, c_void};
+use std::ptr;
+use std::slice;
+
+use error::{Error, Result};
+
+/// `RR_Connected`
+const CONNECTED: c_ushort = 0;
+
+/// `RR_Rotate_0 | RR_Rotate_90 | RR_Rotate_180 | RR_Rotate_270`
+const ROTATIONS: c_ushort = 0xf;
+
+/// `AnyPropertyType`
+const ANY_PROPERTY_TYPE: c_ulong = 0;
+
+/// The number of bytes of an EDID base block.
+const EDID_LENGTH: usize = 128;
+
+/// Connector name prefixes of panels built into laptops and tablets.
+const BUILTIN_CONNECTORS: &[&str] = &["eDP", "LVDS", "DSI"];
+
+/// `XRRScreenResources`
+#[repr(C)]
+struct ScreenResources {
+    timestamp: c_ulong,
+    config_timestamp: c_ulong,
+    ncrtc: c_int,
+    crtcs: *mut c_ulong,
+    noutput: c_int,
+    outputs: *mut c_ulong,
+    nmode: c_int,
+    modes: *mut c_void,
+}
+
+/// `XRROutputInfo`
+#[repr(C)]
+struct OutputInfo {
+    timestamp: c_ulong,
+    crtc: c_ulong,
+    name: *mut c_char,
+    name_len: c_int,
+    mm_width: c_ulong,
+    mm_height: c_ulong,
+    connection: c_ushort,
+    subpixel_order: c_ushort,
+    ncrtc: c_int,
+    crtcs: *mut c_ulong,
+    nclone: c_int,
+    clones: *mut c_ulong,
+    nmode: c_int,
+    npreferred: c_int,
+    modes: *mut c_ulong,
+}
+
+/// `XRRCrtcInfo`
+#[repr(C)]
+struct CrtcInfo {
+    timestamp: c_ulong,
+    x: c_int,
+    y: c_int,
+    width: c_uint,
+    height: c_uint,
+    mode: c_ulong,
+    rotation: c_ushort,
+    noutput: c_int,
+    outputs: *mut c_ulong,
+    rotations: c_ushort,
+    npossible: c_int,
+    possible: *mut c_ulong,
+}
+
+dynamic_library! {
+    /// The functions of Xlib.
+    struct Xlib("libX11.so.6\0", "libX11.so\0") {
+        fn XOpenDisplay(name: *const c_char) -> *mut c_void;
+        fn XCloseDisplay(display: *mut c_void) -> c_int;
+        fn XDefaultRootWindow(display: *mut c_void) -> c_ulong;
+        fn XInternAtom(display: *mut c_void, name: *const c_char, only_if_exists: c_int) -> c_ulong;
+        fn XFree(data: *mut c_void) -> c_int;
+    }
+}
+
+dynamic_library! {
+    /// The functions of libXrandr.
+    struct Xrandr("libXrandr.so.2\0", "libXrandr.so\0") {
+        fn XRRQueryExtension(display: *mut c_void, event_base: *mut c_int, error_base: *mut c_int) -> c_int;
+        fn XRRGetScreenResourcesCurrent(display: *mut c_void, window: c_ulong) -> *mut ScreenResources;
+        fn XRRFreeScreenResources(resources: *mut ScreenResources);
+        fn XRRGetOutputInfo(
+            display: *mut c_void,
+            resources: *mut ScreenResources,
+            output: c_ulong,
+        ) -> *mut OutputInfo;
+        fn XRRFreeOutputInfo(info: *mut OutputInfo);
+        fn XRRGetCrtcInfo(display: *mut c_void, resources: *mut ScreenResources, crtc: c_ulong) -> *mut CrtcInfo;
+        fn XRRFreeCrtcInfo(info: *mut CrtcInfo);
+        fn XRRGetOutputPrimary(display: *mut c_void, window: c_ulong) -> c_ulong;
+        fn XRRGetOutputProperty(
+            display: *mut c_void,
+            output: c_ulong,
+            property: c_ulong,
+            offset: c_long,
+            length: c_long,
+            delete: c_int,
+            pending: c_int,
+            req_type: c_ulong,
+            actual_type: *mut c_ulong,
+            actual_format: *mut c_int,
+            nitems: *mut c_ulong,
+            bytes_after: *mut c_ulong,
+            prop: *mut *mut c_uchar,
+        ) -> c_int;
+    }
+}
+
+lazy_static! {
+    static ref XLIB: Option<Xlib> = Xlib::load();
+    static ref XRANDR: Option<Xrandr> = Xrandr::load();
+}
+
+/// The location and dimensions of a display.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bounds {
+    /// Coordinates of the origin.
+    pub origin: (f64, f64),
+    /// Height and width of the bounds.
+    pub size: (f64, f64),
+}
+
+impl Bounds {
+    /// Returns whether `point` lies within the bounds.
+    #[inline]
+    pub fn contains(&self, (x, y): (f64, f64)) -> bool {
+        let (ox, oy) = self.origin;
+        let (w, h) = self.size;
+        x >= ox && y >= oy && x < ox + w && y < oy + h
+    }
+    /// Returns the smallest bounds that contain both `self` and `other`.
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        let (left, top) = (self.origin.0.min(other.origin.0), self.origin.1.min(other.origin.1));
+        let right = (self.origin.0 + self.size.0).max(other.origin.0 + other.size.0);
+        let bottom = (self.origin.1 + self.size.1).max(other.origin.1 + other.size.1);
+        Bounds { origin: (left, top), size: (right - left, bottom - top) }
+    }
+}
+
+/// The identity of a monitor, read from its Extended Display Identification
+/// Data (EDID).
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Edid {
+    /// The three-letter PNP ID of the manufacturer, such as "DEL" for Dell.
+    pub vendor: String,
+    /// The product code the manufacturer assigned to the model.
+    pub product: u16,
+    /// The serial number, or 0 if the manufacturer didn't encode one.
+    pub serial: u32,
+    /// The name of the model, such as "DELL U2720Q", if the monitor has one.
+    pub model: Option<String>,
+}
+
+impl Edid {
+    /// Parses the base block of the EDID `data`, returning `None` if it is
+    /// too short or lacks the EDID header.
+    pub fn parse(data: &[u8]) -> Option<Edid> {
+        if data.len() < EDID_LENGTH || data[..8] != [0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0] {
+            return None;
+        }
+        // Three letters of five bits each, with 'A' as 1
+        let id = u16::from_be_bytes([data[8], data[9]]);
+        let vendor = [10, 5, 0].iter()
+            .map(|&shift| (b'A' - 1 + ((id >> shift) & 0x1f) as u8) as char)
+            .collect();
+        let product = u16::from_le_bytes([data[10], data[11]]);
+        let serial = u32::from_le_bytes([data[12], data[13], data[14], data[15]]);
+        // Display descriptors start with three zero bytes and then the tag,
+        // which is 0xfc for the product name
+        let model = data[54..EDID_LENGTH].chunks(18)
+            .find(|d| d[..3] == [0, 0, 0] && d[3] == 0xfc)
+            .map(|d| {
+                let text = d[5..].split(|&b| b == b'\n').next().unwrap_or(&[]);
+                String::from_utf8_lossy(text).trim_end().to_owned()
+            });
+        Some(Edid { vendor, product, serial, model })
+    }
+}
+
+/// A monitor display.
+///
+/// Displays are read all at once, so a display keeps the state it had when
+/// it was returned.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Display {
+    id: u64,
+    name: String,
+    bounds: Bounds,
+    rotation: c_ushort,
+    physical_size: (u32, u32),
+    is_main: bool,
+    in_mirror_set: bool,
+    edid: Option<Edid>,
+}
+
+impl Display {
+    /// Returns the main display.
+    ///
+    /// This is the primary output set with `xrandr --primary`, or else the
+    /// display at the origin of the X screen.
+    pub fn main() -> Result<Display> {
+        Display::active()?.into_iter().find(|d| d.is_main).ok_or(Error::Failed)
+    }
+
+    /// Returns all displays that are active, which are those connected and
+    /// showing part of the screen.
+    ///
+    /// The first entry is the main display.
+    pub fn active() -> Result<Vec<Display>> {
+        let mut displays: Vec<Display> = Display::online()?
+            .into_iter()
+            .filter(|d| d.bounds.size != (0.0, 0.0))
+            .collect();
+        displays.sort_by_key(|d| !d.is_main);
+        Ok(displays)
+    }
+
+    /// Returns all displays with a monitor connected, including those turned
+    /// off with `xrandr --off`, whose bounds are empty.
+    pub fn online() -> Result<Vec<Display>> {
+        let (xlib, xrandr) = match (XLIB.as_ref(), XRANDR.as_ref()) {
+            (Some(xlib), Some(xrandr)) => (xlib, xrandr),
+            _ => return Err(Error::Unsupported),
+        };
+        unsafe {
+            let display = (xlib.XOpenDisplay)(ptr::null());
+            if display.is_null() {
+                return Err(Error::Unsupported);
+            }
+            let result = read_displays(xlib, xrandr, display);
+            (xlib.XCloseDisplay)(display);
+            result
+        }
+    }
+
+    /// Returns the RandR output identifier of the display.
+    #[inline]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns the name of the connector the display is attached to, such as
+    /// "HDMI-1" or "eDP-1".
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the bounds of the display in pixels, which are empty if the
+    /// display is turned off.
+    #[inline]
+    pub fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
+    /// Returns whether this display is the main display.
+    #[inline]
+    pub fn is_main(&self) -> bool {
+        self.is_main
+    }
+
+    /// Returns whether the display is built-in, such as the internal display
+    /// of a laptop, judging by its connector.
+    pub fn is_builtin(&self) -> bool {
+        BUILTIN_CONNECTORS.iter().any(|prefix| self.name.starts_with(prefix))
+    }
+
+    /// Returns whether the display shows the same part of the screen as
+    /// another display.
+    #[inline]
+    pub fn in_mirror_set(&self) -> bool {
+        self.in_mirror_set
+    }
+
+    /// Returns the clockwise rotation angle of the display in degrees.
+    pub fn rotation(&self) -> f64 {
+        match self.rotation & ROTATIONS {
+            2 => 90.0,
+            4 => 180.0,
+            8 => 270.0,
+            _ => 0.0,
+        }
+    }
+
+    /// Returns the width and height of the monitor in millimeters, or zeros
+    /// if the monitor doesn't report them, such as for projectors.
+    #[inline]
+    pub fn physical_size(&self) -> (u32, u32) {
+        self.physical_size
+    }
+
+    /// Returns the identity of the monitor, if it provides EDID.
+    #[inline]
+    pub fn edid(&self) -> Option<&Edid> {
+        self.edid.as_ref()
+    }
+}
+
+/// Reads the connected outputs of `display`.
+unsafe fn read_displays(xlib: &Xlib, xrandr: &Xrandr, display: *mut c_void) -> Result<Vec<Display>> {
+    // Requests to a server without RandR would end the process
+    let (mut event_base, mut error_base) = (0, 0);
+    if (xrandr.XRRQueryExtension)(display, &mut event_base, &mut error_base) == 0 {
+        return Err(Error::Unsupported);
+    }
+    let root = (xlib.XDefaultRootWindow)(display);
+    let resources = (xrandr.XRRGetScreenResourcesCurrent)(display, root);
+    if resources.is_null() {
+        return Err(Error::Failed);
+    }
+    let primary = (xrandr.XRRGetOutputPrimary)(display, root);
+    let edid_atom = (xlib.XInternAtom)(display, b"EDID\0".as_ptr() as *const c_char, 1);
+
+    let outputs = slice::from_raw_parts((*resources).outputs, (*resources).noutput.max(0) as usize);
+    let mut displays = Vec::new();
+    let mut crtcs = Vec::new();
+    for &output in outputs {
+        let info = (xrandr.XRRGetOutputInfo)(display, resources, output);
+        if info.is_null() {
+            continue;
+        }
+        if (*info).connection == CONNECTED {
+            let name = slice::from_raw_parts((*info).name as *const u8, (*info).name_len.max(0) as usize);
+            let mut bounds = Bounds { origin: (0.0, 0.0), size: (0.0, 0.0) };
+            let mut rotation = 1;
+            let crtc = (*info).crtc;
+            if crtc != 0 {
+                let crtc_info = (xrandr.XRRGetCrtcInfo)(display, resources, crtc);
+                if !crtc_info.is_null() {
+                    let c = &*crtc_info;
+                    bounds = Bounds {
+                        origin: (c.x as f64, c.y as f64),
+                        size: (c.width as f64, c.height as f64),
+                    };
+                    rotation = c.rotation;
+                    (xrandr.XRRFreeCrtcInfo)(crtc_info);
+                }
+            }
+            crtcs.push(crtc);
+            displays.push(Display {
+                // XIDs are 32 bits on the wire, whatever the size of `c_ulong`
+                id: output as _,
+                name: String::from_utf8_lossy(name).into_owned(),
+                bounds,
+                rotation,
+                physical_size: ((*info).mm_width as u32, (*info).mm_height as u32),
+                is_main: output == primary,
+                in_mirror_set: false,
+                edid: if edid_atom == 0 { None } else { read_edid(xlib, xrandr, display, output, edid_atom) },
+            });
+        }
+        (xrandr.XRRFreeOutputInfo)(info);
+    }
+    (xrandr.XRRFreeScreenResources)(resources);
+
+    // Outputs driven by one CRTC, or placed over each other, mirror each other
+    let mirrored: Vec<bool> = displays.iter().zip(&crtcs).map(|(d, &crtc)| {
+        d.bounds.size != (0.0, 0.0) && displays.iter().zip(&crtcs).any(|(other, &other_crtc)| {
+            other.id != d.id && (other_crtc == crtc || other.bounds == d.bounds)
+        })
+    }).collect();
+    for (d, mirrored) in displays.iter_mut().zip(mirrored) {
+        d.in_mirror_set = mirrored;
+    }
+    if !displays.iter().any(|d| d.is_main) {
+        let active = |d: &&mut Display| d.bounds.size != (0.0, 0.0);
+        let main = displays.iter_mut()
+            .filter(active)
+            .min_by(|a, b| {
+                let distance = |d: &Display| d.bounds.origin.0.abs() + d.bounds.origin.1.abs();
+                distance(a).total_cmp(&distance(b))
+            });
+        if let Some(main) = main {
+            main.is_main = true;
+        }
+    }
+    Ok(displays)
+}
+
+/// Reads the EDID property of `output`.
+unsafe fn read_edid(
+    xlib: &Xlib,
+    xrandr: &Xrandr,
+    display: *mut c_void,
+    output: c_ulong,
+    atom: c_ulong,
+) -> Option<Edid> {
+    let (mut actual_type, mut format, mut count, mut after) = (0, 0, 0, 0);
+    let mut data = ptr::null_mut();
+    let status = (xrandr.XRRGetOutputProperty)(
+        display,
+        output,
+        atom,
+        0,
+        // The length is in units of 32 bits
+        (EDID_LENGTH / 4) as c_long,
+        0,
+        0,
+        ANY_PROPERTY_TYPE,
+        &mut actual_type,
+        &mut format,
+        &mut count,
+        &mut after,
+        &mut data,
+    );
+    if status != 0 || data.is_null() {
+        return None;
+    }
+    let edid = if format == 8 {
+        Edid::parse(slice::from_raw_parts(data, count as usize))
+    } else {
+        None
+    };
+    (xlib.XFree)(data as *mut c_void);
+    edid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Edid;
+
+    #[test]
+    fn parse_edid() {
+        let mut data = [0u8; 128];
+        data[..8].copy_from_slice(&[0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0]);
+        // "DEL", product 0xa0f1, serial 0x12345678
+        data[8..16].copy_from_slice(&[0x10, 0xac, 0xf1, 0xa0, 0x78, 0x56, 0x34, 0x12]);
+        data[72..77].copy_from_slice(&[0, 0, 0, 0xfc, 0]);
+        data[77..90].copy_from_slice(b"DELL U2720Q\n ");
+
+        let edid = Edid::parse(&data).unwrap();
+        assert_eq!(edid, Edid {
+            vendor: "DEL".into(),
+            product: 0xa0f1,
+            serial: 0x1234_5678,
+            model: Some("DELL U2720Q".into()),
+        });
+        assert_eq!(Edid::parse(&data[..100]), None);
+    }
+}
@@ -0,0 +1,550 @@
+//! 🤖 Input synthesized through the Wayland compositor.
+//!
+//! Compositors based on wlroots, such as Sway and Hyprland, let clients
+//! create a virtual keyboard through `zwp_virtual_keyboard_v1` and a virtual
+//! pointer through `zwlr_virtual_pointer_v1`. Their input reaches
+//! applications as if it came from a device, without the root privileges
+//! that creating devices through `uinput` needs.
+//!
+//! The virtual keyboard types with the [current](../keyboard/struct.Keymap.html#method.current)
+//! keymap, which it sends to the compositor, so the keys of a character are
+//! found with the same keymap. The compositor removes both devices once the
+//! connection is dropped.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use auto::os::virtual_input::VirtualInput;
+//!
+//! let mut input = VirtualInput::connect().unwrap();
+//! input.move_to((200.0, 100.0)).unwrap();
+//! // BTN_LEFT
+//! input.button(0x110, true).unwrap();
+//! input.button(0x110, false).unwrap();
+//! ```
+
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::time::Instant;
+
+use error::{Error, Result};
+use super::io_error;
+use super::keyboard::{KeyState, Keymap};
+use super::screen::Bounds;
+use super::wayland::{self, Arg};
+
+/// `WL_KEYBOARD_KEYMAP_FORMAT_XKB_V1`
+const KEYMAP_FORMAT_XKB_V1: u32 = 1;
+
+/// `WL_POINTER_AXIS_SOURCE_WHEEL`
+const AXIS_SOURCE_WHEEL: u32 = 0;
+
+/// The distance in surface coordinates of a wheel step, as libinput reports
+/// it.
+const STEP_DISTANCE: f64 = 15.0;
+
+/// `WL_OUTPUT_MODE_CURRENT`
+const MODE_CURRENT: u32 = 1;
+
+/// A virtual keyboard and pointer created through the compositor.
+pub struct VirtualInput {
+    connection: wayland::Connection,
+    keyboard: Option<(u32, Keymap, KeyState)>,
+    pointer: Option<u32>,
+    /// The bounds of every output in the compositor's layout.
+    layout: Option<Bounds>,
+    /// Where the pointer was last moved to, which the compositor doesn't
+    /// tell clients.
+    location: Option<(f64, f64)>,
+    start: Instant,
+}
+
+impl VirtualInput {
+    /// Connects to the compositor and creates whichever virtual devices it
+    /// supports.
+    ///
+    /// Fails with [`Error::Unsupported`](../../error/enum.Error.html#variant.Unsupported)
+    /// outside of Wayland sessions or if the compositor supports neither
+    /// protocol.
+    pub fn connect() -> Result<VirtualInput> {
+        let mut connection = wayland::Connection::connect()?;
+        let keyboards = connection.global(&["zwp_virtual_keyboard_manager_v1"]);
+        let pointers = connection.global(&["zwlr_virtual_pointer_manager_v1"]);
+        if keyboards.is_none() && pointers.is_none() {
+            return Err(Error::Unsupported);
+        }
+        let seat = connection.global(&["wl_seat"]).ok_or(Error::Unsupported)?;
+        let seat = connection.bind(&seat, 1)?;
+
+        let keyboard = match keyboards {
+            Some(manager) => {
+                let manager = connection.bind(&manager, 1)?;
+                let keyboard = connection.new_id();
+                // create_virtual_keyboard
+                connection.send(manager, 0, &[Arg::Uint(seat), Arg::Uint(keyboard)])?;
+                let keymap = Keymap::current()?;
+                send_keymap(&mut connection, keyboard, &keymap)?;
+                let state = KeyState::new(&keymap)?;
+                Some((keyboard, keymap, state))
+            },
+            None => None,
+        };
+        let pointer = match pointers {
+            Some(manager) => {
+                let manager = connection.bind(&manager, 1)?;
+                let pointer = connection.new_id();
+                // create_virtual_pointer
+                connection.send(manager, 0, &[Arg::Uint(seat), Arg::Uint(pointer)])?;
+                Some(pointer)
+            },
+            None => None,
+        };
+
+        let layout = output_layout(&mut connection)?;
+        Ok(VirtualInput { connection, keyboard, pointer, layout, location: None, start: Instant::now() })
+    }
+
+    /// Returns whether the compositor supports virtual keyboards.
+    #[inline]
+    pub fn has_keyboard(&self) -> bool {
+        self.keyboard.is_some()
+    }
+
+    /// Returns whether the compositor supports virtual pointers.
+    #[inline]
+    pub fn has_pointer(&self) -> bool {
+        self.pointer.is_some()
+    }
+
+    /// Returns the keymap that the virtual keyboard types with, or `None` if
+    /// the compositor doesn't support virtual keyboards.
+    #[inline]
+    pub fn keymap(&self) -> Option<&Keymap> {
+        self.keyboard.as_ref().map(|(_, keymap, _)| keymap)
+    }
+
+    /// Returns where the pointer was last moved to through
+    /// [`move_to`](#method.move_to), or `None` if it hasn't been or has since
+    /// been moved by an amount.
+    ///
+    /// The user may have moved the pointer since.
+    #[inline]
+    pub fn location(&self) -> Option<(f64, f64)> {
+        self.location
+    }
+
+    /// Returns the milliseconds since the devices were created, which stamp
+    /// their events.
+    fn time(&self) -> u32 {
+        self.start.elapsed().as_millis() as u32
+    }
+
+    /// Presses or releases the evdev key `key`.
+    pub fn key(&mut self, key: u16, down: bool) -> Result<()> {
+        let time = self.time();
+        let (keyboard, _, ref mut state) = *self.keyboard.as_mut().ok_or(Error::Unsupported)?;
+        // key
+        self.connection.send(keyboard, 1, &[Arg::Uint(time), Arg::Uint(u32::from(key)), Arg::Uint(down as u32)])?;
+        // The compositor takes the modifiers from the client rather than
+        // working them out from the keys
+        if state.update(key, down) {
+            let [depressed, latched, locked, layout] = state.serialize();
+            self.connection.send(keyboard, 2, &[
+                Arg::Uint(depressed),
+                Arg::Uint(latched),
+                Arg::Uint(locked),
+                Arg::Uint(layout),
+            ])?;
+        }
+        Ok(())
+    }
+
+    /// Types `c` with the keys that produce it in the virtual keyboard's
+    /// keymap.
+    ///
+    /// Fails with [`Error::Unsupported`](../../error/enum.Error.html#variant.Unsupported)
+    /// if the keymap can't produce `c`.
+    pub fn type_char(&mut self, c: char) -> Result<()> {
+        let presses = match self.keyboard {
+            Some((_, ref keymap, _)) => keymap.presses_for_char(c).ok_or(Error::Unsupported)?,
+            None => return Err(Error::Unsupported),
+        };
+        for (key, down) in presses {
+            self.key(key, down)?;
+        }
+        Ok(())
+    }
+
+    fn pointer(&self) -> Result<u32> {
+        self.pointer.ok_or(Error::Unsupported)
+    }
+
+    /// Moves the pointer to `location` in the compositor's layout, whose
+    /// origin is usually the upper-left corner of the leftmost output.
+    pub fn move_to(&mut self, (x, y): (f64, f64)) -> Result<()> {
+        let pointer = self.pointer()?;
+        let layout = self.layout.ok_or(Error::Unsupported)?;
+        let (width, height) = (layout.size.0.max(1.0) as u32, layout.size.1.max(1.0) as u32);
+        let x = (x - layout.origin.0).round().clamp(0.0, f64::from(width - 1)) as u32;
+        let y = (y - layout.origin.1).round().clamp(0.0, f64::from(height - 1)) as u32;
+        let time = self.time();
+        // motion_absolute
+        self.connection.send(pointer, 1, &[
+            Arg::Uint(time),
+            Arg::Uint(x),
+            Arg::Uint(y),
+            Arg::Uint(width),
+            Arg::Uint(height),
+        ])?;
+        self.location = Some((f64::from(x) + layout.origin.0, f64::from(y) + layout.origin.1));
+        self.frame(pointer)
+    }
+
+    /// Moves the pointer by `(dx, dy)`, which the compositor may accelerate.
+    pub fn move_by(&mut self, dx: f64, dy: f64) -> Result<()> {
+        let pointer = self.pointer()?;
+        let time = self.time();
+        // motion
+        self.connection.send(pointer, 0, &[Arg::Uint(time), Arg::Fixed(dx), Arg::Fixed(dy)])?;
+        self.location = None;
+        self.frame(pointer)
+    }
+
+    /// Presses or releases the evdev button `button`, such as `0x110` for
+    /// `BTN_LEFT`.
+    pub fn button(&mut self, button: u16, down: bool) -> Result<()> {
+        let pointer = self.pointer()?;
+        let time = self.time();
+        // button
+        self.connection.send(pointer, 2, &[Arg::Uint(time), Arg::Uint(u32::from(button)), Arg::Uint(down as u32)])?;
+        self.frame(pointer)
+    }
+
+    /// Turns the scroll wheel by whole steps, where positive values scroll
+    /// right and up.
+    pub fn scroll(&mut self, dx: i32, dy: i32) -> Result<()> {
+        let pointer = self.pointer()?;
+        let time = self.time();
+        // axis_source
+        self.connection.send(pointer, 5, &[Arg::Uint(AXIS_SOURCE_WHEEL)])?;
+        // Wayland axes point down and right
+        for &(axis, steps) in &[(0, -dy), (1, dx)] {
+            if steps != 0 {
+                // axis_discrete
+                self.connection.send(pointer, 7, &[
+                    Arg::Uint(time),
+                    Arg::Uint(axis),
+                    Arg::Fixed(f64::from(steps) * STEP_DISTANCE),
+                    Arg::Int(steps),
+                ])?;
+            }
+        }
+        self.frame(pointer)
+    }
+
+    /// Ends a group of pointer events that the compositor applies together.
+    fn frame(&mut self, pointer: u32) -> Result<()> {
+        self.connection.send(pointer, 4, &[])
+    }
+
+    /// Wraps `socket` as a virtual pointer whose compositor lays out outputs
+    /// in `layout`, for talking to a stand-in compositor.
+    #[cfg(test)]
+    pub(crate) fn with_pointer(socket: ::std::os::unix::net::UnixStream, layout: Bounds) -> VirtualInput {
+        VirtualInput {
+            connection: wayland::Connection::new(socket),
+            keyboard: None,
+            pointer: Some(5),
+            layout: Some(layout),
+            location: None,
+            start: Instant::now(),
+        }
+    }
+}
+
+/// Sends `keymap` to the virtual keyboard `keyboard` through a memory file,
+/// which the compositor maps.
+fn send_keymap(connection: &mut wayland::Connection, keyboard: u32, keymap: &Keymap) -> Result<()> {
+    let mut text = keymap.text()?.into_bytes();
+    text.push(0);
+    let fd = unsafe { libc::memfd_create(b"auto-keymap\0".as_ptr() as _, libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(io_error(::std::io::Error::last_os_error()));
+    }
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    file.write_all(&text).map_err(io_error)?;
+    // keymap, whose file descriptor the compositor receives a copy of
+    connection.send(keyboard, 0, &[
+        Arg::Uint(KEYMAP_FORMAT_XKB_V1),
+        Arg::Fd(file.as_raw_fd()),
+        Arg::Uint(text.len() as u32),
+    ])
+}
+
+/// What the compositor reports about an output.
+struct Output {
+    id: u32,
+    /// The `zxdg_output_v1` of the output, if the compositor supports them.
+    xdg_id: Option<u32>,
+    position: (i32, i32),
+    mode: (i32, i32),
+    scale: i32,
+    /// Whether the output is rotated by a quarter turn.
+    sideways: bool,
+    logical_position: Option<(i32, i32)>,
+    logical_size: Option<(i32, i32)>,
+}
+
+impl Output {
+    /// Returns the bounds of the output in the compositor's layout, which
+    /// `zxdg_output_v1` reports directly, accounting for fractional scales.
+    fn bounds(&self) -> Bounds {
+        let (x, y) = self.logical_position.unwrap_or(self.position);
+        let (width, height) = match self.logical_size {
+            Some((width, height)) => (f64::from(width), f64::from(height)),
+            None => {
+                let (width, height) = if self.sideways {
+                    (self.mode.1, self.mode.0)
+                } else {
+                    self.mode
+                };
+                let scale = f64::from(self.scale);
+                (f64::from(width) / scale, f64::from(height) / scale)
+            },
+        };
+        Bounds { origin: (f64::from(x), f64::from(y)), size: (width, height) }
+    }
+}
+
+/// Returns the bounds of every output in the compositor's layout, in logical
+/// pixels, or `None` if there are no outputs.
+fn output_layout(connection: &mut wayland::Connection) -> Result<Option<Bounds>> {
+    let mut outputs = Vec::new();
+    for global in connection.globals("wl_output") {
+        // Version 2 adds the scale
+        let id = connection.bind(&global, 2)?;
+        outputs.push(Output {
+            id,
+            xdg_id: None,
+            position: (0, 0),
+            mode: (0, 0),
+            scale: 1,
+            sideways: false,
+            logical_position: None,
+            logical_size: None,
+        });
+    }
+    if let Some(manager) = connection.global(&["zxdg_output_manager_v1"]) {
+        let manager = connection.bind(&manager, 2)?;
+        for output in &mut outputs {
+            let xdg_id = connection.new_id();
+            // get_xdg_output
+            connection.send(manager, 1, &[Arg::Uint(xdg_id), Arg::Uint(output.id)])?;
+            output.xdg_id = Some(xdg_id);
+        }
+    }
+    for mut message in connection.roundtrip()? {
+        let object = message.object;
+        let output = match outputs.iter_mut().find(|o| o.id == object || o.xdg_id == Some(object)) {
+            Some(output) => output,
+            None => continue,
+        };
+        match (object == output.id, message.opcode) {
+            // wl_output.geometry
+            (true, 0) => {
+                output.position = (message.int(), message.int());
+                let _ = (message.int(), message.int(), message.int(), message.string(), message.string());
+                output.sideways = message.int() % 2 == 1;
+            },
+            // wl_output.mode
+            (true, 1) => {
+                let (flags, size) = (message.uint(), (message.int(), message.int()));
+                if flags & MODE_CURRENT != 0 {
+                    output.mode = size;
+                }
+            },
+            // wl_output.scale
+            (true, 3) => output.scale = message.int().max(1),
+            // zxdg_output_v1.logical_position
+            (false, 0) => output.logical_position = Some((message.int(), message.int())),
+            // zxdg_output_v1.logical_size
+            (false, 1) => output.logical_size = Some((message.int(), message.int())),
+            _ => {},
+        }
+    }
+    for xdg_id in outputs.iter().filter_map(|output| output.xdg_id) {
+        // zxdg_output_v1.destroy
+        connection.send(xdg_id, 0, &[])?;
+    }
+    Ok(outputs.iter().map(Output::bounds).reduce(|a, b| a.union(&b)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::os::unix::net::UnixStream;
+    use std::thread::{self, JoinHandle};
+    use std::time::Instant;
+
+    use super::*;
+    use super::super::wayland::{Connection, Global};
+
+    /// Reads the next request sent through `stream`, as its object, opcode,
+    /// and arguments as words.
+    fn request(stream: &mut UnixStream) -> (u32, u16, Vec<u32>) {
+        let word = |bytes: &[u8]| u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let mut header = [0; 8];
+        stream.read_exact(&mut header).unwrap();
+        let (object, header) = (word(&header[..4]), word(&header[4..]));
+        let mut args = vec![0; (header >> 16) as usize - 8];
+        stream.read_exact(&mut args).unwrap();
+        (object, header as u16, args.chunks(4).map(word).collect())
+    }
+
+    fn global(name: u32, interface: &str) -> Global {
+        Global { name, interface: interface.into(), version: 3 }
+    }
+
+    /// Answers the requests of `output_layout` as a compositor with two
+    /// outputs, a scaled one and one to its right rotated a quarter turn,
+    /// whose reported logical positions and sizes differ.
+    fn serve_outputs(mut stream: UnixStream) -> JoinHandle<(UnixStream, Connection)> {
+        thread::spawn(move || {
+            let mut events = Connection::new(stream.try_clone().unwrap());
+            let (mut outputs, mut manager, mut xdg_outputs) = (Vec::new(), None, Vec::new());
+            loop {
+                match request(&mut stream) {
+                    // wl_registry.bind, whose new ID is the last argument
+                    (0, 0, args) if args[0] == 3 => manager = args.last().cloned(),
+                    (0, 0, args) => outputs.push(*args.last().unwrap()),
+                    (object, 1, args) if Some(object) == manager => xdg_outputs.push((args[0], args[1])),
+                    // wl_display.sync
+                    (1, 0, args) => {
+                        for (&output, &(x, transform, mode, scale)) in outputs.iter().zip(&[
+                            (0, 0, (3840, 2160), 2),
+                            (1920, 1, (1920, 1080), 1),
+                        ]) {
+                            events.send(output, 0, &[
+                                Arg::Int(x),
+                                Arg::Int(0),
+                                Arg::Int(600),
+                                Arg::Int(340),
+                                Arg::Int(0),
+                                Arg::Str("Make"),
+                                Arg::Str("Model"),
+                                Arg::Int(transform),
+                            ]).unwrap();
+                            let other_mode = [Arg::Uint(0), Arg::Int(640), Arg::Int(480), Arg::Int(0)];
+                            events.send(output, 1, &other_mode).unwrap();
+                            events.send(output, 1, &[
+                                Arg::Uint(MODE_CURRENT),
+                                Arg::Int(mode.0),
+                                Arg::Int(mode.1),
+                                Arg::Int(60000),
+                            ]).unwrap();
+                            events.send(output, 3, &[Arg::Int(scale)]).unwrap();
+                        }
+                        for &(xdg_output, output) in &xdg_outputs {
+                            let (x, size) = if output == outputs[0] {
+                                (0, (2560, 1440))
+                            } else {
+                                (2560, (1080, 1920))
+                            };
+                            events.send(xdg_output, 0, &[Arg::Int(x), Arg::Int(0)]).unwrap();
+                            events.send(xdg_output, 1, &[Arg::Int(size.0), Arg::Int(size.1)]).unwrap();
+                        }
+                        // wl_callback.done
+                        events.send(args[0], 0, &[Arg::Uint(0)]).unwrap();
+                        return (stream, events);
+                    },
+                    request => panic!("unexpected request {:?}", request),
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn output_layout() {
+        let outputs = vec![global(1, "wl_output"), global(2, "wl_output")];
+        let (client, server) = UnixStream::pair().unwrap();
+        let mut connection = Connection::with_globals(client, outputs.clone());
+        let compositor = serve_outputs(server);
+        let layout = super::output_layout(&mut connection).unwrap();
+        assert_eq!(layout, Some(Bounds { origin: (0.0, 0.0), size: (3000.0, 1920.0) }));
+        drop(compositor.join().unwrap());
+
+        let mut globals = outputs;
+        globals.push(global(3, "zxdg_output_manager_v1"));
+        let (client, server) = UnixStream::pair().unwrap();
+        let mut connection = Connection::with_globals(client, globals);
+        let compositor = serve_outputs(server);
+        let layout = super::output_layout(&mut connection).unwrap();
+        assert_eq!(layout, Some(Bounds { origin: (0.0, 0.0), size: (3640.0, 1920.0) }));
+        let (mut stream, _) = compositor.join().unwrap();
+        // The xdg outputs are destroyed once read
+        assert_eq!(request(&mut stream).1, 0);
+        assert_eq!(request(&mut stream).1, 0);
+
+        let (client, mut server) = UnixStream::pair().unwrap();
+        let mut connection = Connection::with_globals(client, Vec::new());
+        let sync = thread::spawn(move || {
+            let (_, _, args) = request(&mut server);
+            Connection::new(server).send(args[0], 0, &[Arg::Uint(0)]).unwrap();
+        });
+        assert_eq!(super::output_layout(&mut connection).unwrap(), None);
+        sync.join().unwrap();
+    }
+
+    fn pointer_input(connection: Connection, layout: Option<Bounds>) -> VirtualInput {
+        VirtualInput { connection, keyboard: None, pointer: Some(5), layout, location: None, start: Instant::now() }
+    }
+
+    #[test]
+    fn scroll() {
+        let (client, mut server) = UnixStream::pair().unwrap();
+        let mut input = pointer_input(Connection::new(client), None);
+        input.scroll(1, -2).unwrap();
+
+        assert_eq!(request(&mut server), (5, 5, vec![AXIS_SOURCE_WHEEL]));
+        // Scrolling down is positive on the vertical axis
+        let (_, opcode, args) = request(&mut server);
+        assert_eq!((opcode, &args[1..]), (7, &[0, 30 * 256, 2][..]));
+        let (_, opcode, args) = request(&mut server);
+        assert_eq!((opcode, &args[1..]), (7, &[1, 15 * 256, 1][..]));
+        assert_eq!(request(&mut server), (5, 4, vec![]));
+
+        input.scroll(0, 1).unwrap();
+        request(&mut server);
+        let (_, _, args) = request(&mut server);
+        assert_eq!(args[1..], [0, (-15 * 256) as u32, -1i32 as u32]);
+        assert_eq!(request(&mut server).1, 4);
+    }
+
+    #[test]
+    fn move_to() {
+        let (client, mut server) = UnixStream::pair().unwrap();
+        let layout = Bounds { origin: (-100.0, 0.0), size: (300.0, 200.0) };
+        let mut input = pointer_input(Connection::new(client), Some(layout));
+
+        input.move_to((50.0, 20.4)).unwrap();
+        let (_, opcode, args) = request(&mut server);
+        assert_eq!((opcode, &args[1..]), (1, &[150, 20, 300, 200][..]));
+        assert_eq!(request(&mut server).1, 4);
+        assert_eq!(input.location(), Some((50.0, 20.0)));
+
+        // Locations outside the layout are moved onto its edge
+        input.move_to((-500.0, 900.0)).unwrap();
+        let (_, _, args) = request(&mut server);
+        assert_eq!(args[1..], [0, 199, 300, 200]);
+        assert_eq!(input.location(), Some((-100.0, 199.0)));
+        request(&mut server);
+
+        input.move_by(1.0, 1.0).unwrap();
+        assert_eq!(input.location(), None);
+
+        let (client, _server) = UnixStream::pair().unwrap();
+        let mut input = pointer_input(Connection::new(client), None);
+        assert_eq!(input.move_to((0.0, 0.0)), Err(Error::Unsupported));
+    }
+}
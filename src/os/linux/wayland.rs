@@ -0,0 +1,428 @@
+//! A minimal client of the Wayland wire protocol.
+//!
+//! Only the requests and events of `wl_display` and `wl_registry` are known
+//! here; callers encode the requests and decode the events of the protocols
+//! they use. Object IDs are never reused, which suits the short-lived or
+//! quiet connections of this crate.
+
+use std::collections::VecDeque;
+use std::env;
+use std::mem;
+use std::io::{self, Write};
+use std::os::raw::{c_int, c_void};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::ptr;
+use std::time::Instant;
+
+use error::{Error, Result};
+use super::{io_error, poll_readable};
+
+/// The ID of the `wl_display` singleton.
+const DISPLAY: u32 = 1;
+
+/// The most file descriptors received along with a single read.
+const MAX_FDS: usize = 28;
+
+/// An argument of a request.
+#[derive(Copy, Clone, Debug)]
+pub(super) enum Arg<'a> {
+    /// An unsigned integer, object ID, or new ID of a known interface.
+    Uint(u32),
+    /// A signed integer.
+    Int(i32),
+    /// A signed 24.8 fixed-point number.
+    Fixed(f64),
+    /// A string.
+    Str(&'a str),
+    /// A file descriptor, which is duplicated by the compositor.
+    Fd(RawFd),
+}
+
+/// An event received from the compositor, whose arguments are read in order.
+#[derive(Debug)]
+pub(super) struct Message {
+    /// The object the event is for.
+    pub object: u32,
+    /// The opcode of the event in the interface of the object.
+    pub opcode: u16,
+    data: Vec<u8>,
+    offset: usize,
+}
+
+impl Message {
+    /// Reads an unsigned integer or object ID.
+    pub fn uint(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        if let Some(data) = self.data.get(self.offset..self.offset + 4) {
+            bytes.copy_from_slice(data);
+        }
+        self.offset += 4;
+        u32::from_ne_bytes(bytes)
+    }
+
+    /// Reads a signed integer.
+    #[inline]
+    pub fn int(&mut self) -> i32 {
+        self.uint() as i32
+    }
+
+    /// Reads an array of bytes.
+    pub fn array(&mut self) -> Vec<u8> {
+        let len = self.uint() as usize;
+        let start = self.offset.min(self.data.len());
+        let array = self.data[start..(start + len).min(self.data.len())].to_vec();
+        self.offset += len.next_multiple_of(4);
+        array
+    }
+
+    /// Reads a string, which is empty if null.
+    pub fn string(&mut self) -> String {
+        let mut bytes = self.array();
+        // Drop the nul terminator
+        bytes.pop();
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+/// A global object advertised by the compositor.
+#[derive(Clone, Debug)]
+pub(super) struct Global {
+    /// The numeric name to bind the global with.
+    pub name: u32,
+    /// The interface of the global, such as `wl_seat`.
+    pub interface: String,
+    /// The latest version of the interface that the compositor supports.
+    pub version: u32,
+}
+
+/// A connection to the compositor at `WAYLAND_DISPLAY`.
+pub(super) struct Connection {
+    socket: UnixStream,
+    next_id: u32,
+    registry: u32,
+    globals: Vec<Global>,
+    incoming: Vec<u8>,
+    fds: VecDeque<RawFd>,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        for &fd in &self.fds {
+            unsafe { libc::close(fd) };
+        }
+    }
+}
+
+impl Connection {
+    /// Wraps `socket` without fetching the globals.
+    pub fn new(socket: UnixStream) -> Connection {
+        Connection {
+            socket,
+            next_id: DISPLAY + 1,
+            registry: 0,
+            globals: Vec::new(),
+            incoming: Vec::new(),
+            fds: VecDeque::new(),
+        }
+    }
+
+    /// Wraps `socket` as if the compositor had advertised `globals`, for
+    /// talking to a stand-in compositor.
+    #[cfg(test)]
+    pub fn with_globals(socket: UnixStream, globals: Vec<Global>) -> Connection {
+        let mut connection = Connection::new(socket);
+        connection.globals = globals;
+        connection
+    }
+
+    /// Connects to the compositor at `WAYLAND_DISPLAY` and fetches its
+    /// globals.
+    ///
+    /// Fails with `Error::Unsupported` outside of Wayland sessions.
+    pub fn connect() -> Result<Connection> {
+        let display = PathBuf::from(env::var_os("WAYLAND_DISPLAY").ok_or(Error::Unsupported)?);
+        let path = if display.is_absolute() {
+            display
+        } else {
+            PathBuf::from(env::var_os("XDG_RUNTIME_DIR").ok_or(Error::Unsupported)?).join(display)
+        };
+        let socket = UnixStream::connect(path).map_err(|_| Error::Unsupported)?;
+        let mut connection = Connection::new(socket);
+
+        connection.registry = connection.new_id();
+        let registry = connection.registry;
+        // wl_display.get_registry
+        connection.send(DISPLAY, 1, &[Arg::Uint(registry)])?;
+        for mut message in connection.roundtrip()? {
+            // wl_registry.global
+            if message.object == registry && message.opcode == 0 {
+                let name = message.uint();
+                let interface = message.string();
+                let version = message.uint();
+                connection.globals.push(Global { name, interface, version });
+            }
+        }
+        Ok(connection)
+    }
+
+    /// Returns a fresh ID for a new object.
+    #[inline]
+    pub fn new_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Returns the first global that implements one of `interfaces`, trying
+    /// them in order.
+    pub fn global(&self, interfaces: &[&str]) -> Option<Global> {
+        interfaces.iter().find_map(|&interface| {
+            self.globals.iter().find(|g| g.interface == interface).cloned()
+        })
+    }
+
+    /// Returns every global that implements `interface`, such as one per
+    /// output for `wl_output`.
+    pub fn globals(&self, interface: &str) -> Vec<Global> {
+        self.globals.iter().filter(|g| g.interface == interface).cloned().collect()
+    }
+
+    /// Binds `global` at `version`, or at its latest version if that is
+    /// older, returning the ID of the new object.
+    pub fn bind(&mut self, global: &Global, version: u32) -> Result<u32> {
+        let id = self.new_id();
+        let version = version.min(global.version);
+        let registry = self.registry;
+        // wl_registry.bind, whose new ID is untyped
+        self.send(registry, 0, &[
+            Arg::Uint(global.name),
+            Arg::Str(&global.interface),
+            Arg::Uint(version),
+            Arg::Uint(id),
+        ])?;
+        Ok(id)
+    }
+
+    /// Sends the request `opcode` of `object` with `args`.
+    pub fn send(&mut self, object: u32, opcode: u16, args: &[Arg]) -> Result<()> {
+        let mut data = vec![0; 8];
+        let mut fds = Vec::new();
+        for arg in args {
+            match *arg {
+                Arg::Uint(value) => data.extend_from_slice(&value.to_ne_bytes()),
+                Arg::Int(value) => data.extend_from_slice(&value.to_ne_bytes()),
+                Arg::Fixed(value) => data.extend_from_slice(&((value * 256.0).round() as i32).to_ne_bytes()),
+                Arg::Str(string) => {
+                    // Strings are sent as arrays that include the terminating NUL
+                    let len = string.len() + 1;
+                    data.extend_from_slice(&(len as u32).to_ne_bytes());
+                    data.extend_from_slice(string.as_bytes());
+                    data.resize(data.len() + len.next_multiple_of(4) - string.len(), 0);
+                },
+                Arg::Fd(fd) => fds.push(fd),
+            }
+        }
+        let header = ((data.len() as u32) << 16) | opcode as u32;
+        data[..4].copy_from_slice(&object.to_ne_bytes());
+        data[4..8].copy_from_slice(&header.to_ne_bytes());
+
+        let sent = self.send_with_fds(&data, &fds).map_err(io_error)?;
+        self.socket.write_all(&data[sent..]).map_err(io_error)
+    }
+
+    /// Writes the start of `data` along with `fds`, returning the number of
+    /// bytes written.
+    fn send_with_fds(&self, data: &[u8], fds: &[RawFd]) -> io::Result<usize> {
+        let mut control = [0u64; 32];
+        let mut iov = libc::iovec { iov_base: data.as_ptr() as *mut c_void, iov_len: data.len() };
+        loop {
+            unsafe {
+                let mut msg: libc::msghdr = mem::zeroed();
+                msg.msg_iov = &mut iov;
+                msg.msg_iovlen = 1;
+                if !fds.is_empty() {
+                    let size = mem::size_of_val(fds) as u32;
+                    msg.msg_control = control.as_mut_ptr() as *mut c_void;
+                    msg.msg_controllen = libc::CMSG_SPACE(size) as _;
+                    let cmsg = libc::CMSG_FIRSTHDR(&msg);
+                    (*cmsg).cmsg_level = libc::SOL_SOCKET;
+                    (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+                    (*cmsg).cmsg_len = libc::CMSG_LEN(size) as _;
+                    ptr::copy_nonoverlapping(fds.as_ptr(), libc::CMSG_DATA(cmsg) as *mut RawFd, fds.len());
+                }
+                match libc::sendmsg(self.socket.as_raw_fd(), &msg, libc::MSG_NOSIGNAL) {
+                    -1 => {
+                        let error = io::Error::last_os_error();
+                        if error.kind() != io::ErrorKind::Interrupted {
+                            return Err(error);
+                        }
+                    },
+                    sent => return Ok(sent as usize),
+                }
+            }
+        }
+    }
+
+    /// Reads from the socket once it has data, returning `false` if
+    /// `deadline` passes first.
+    fn receive(&mut self, deadline: Option<Instant>) -> Result<bool> {
+        if !poll_readable(self.socket.as_raw_fd(), deadline)? {
+            return Ok(false);
+        }
+        let mut buffer = [0u8; 4096];
+        let mut control = [0u64; 32];
+        let mut iov = libc::iovec { iov_base: buffer.as_mut_ptr() as *mut c_void, iov_len: buffer.len() };
+        let received = unsafe {
+            let mut msg: libc::msghdr = mem::zeroed();
+            msg.msg_iov = &mut iov;
+            msg.msg_iovlen = 1;
+            msg.msg_control = control.as_mut_ptr() as *mut c_void;
+            msg.msg_controllen = mem::size_of_val(&control) as _;
+            let received = libc::recvmsg(self.socket.as_raw_fd(), &mut msg, libc::MSG_CMSG_CLOEXEC);
+            if received < 0 {
+                let error = io::Error::last_os_error();
+                return match error.kind() {
+                    io::ErrorKind::Interrupted => Ok(true),
+                    _ => Err(io_error(error)),
+                };
+            }
+            let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+            while !cmsg.is_null() {
+                if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                    let len = (*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize;
+                    let data = libc::CMSG_DATA(cmsg) as *const c_int;
+                    for i in 0..(len / mem::size_of::<c_int>()).min(MAX_FDS) {
+                        self.fds.push_back(ptr::read_unaligned(data.add(i)));
+                    }
+                }
+                cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            }
+            received as usize
+        };
+        if received == 0 {
+            // The compositor closed the connection
+            return Err(Error::Failed);
+        }
+        self.incoming.extend_from_slice(&buffer[..received]);
+        Ok(true)
+    }
+
+    /// Waits for the next event until `deadline`, returning `None` if it
+    /// passes first.
+    ///
+    /// Fails if the compositor reports a protocol error, which also ends the
+    /// connection.
+    pub fn next_message(&mut self, deadline: Option<Instant>) -> Result<Option<Message>> {
+        loop {
+            if self.incoming.len() >= 8 {
+                let word = |i: usize| {
+                    let mut bytes = [0; 4];
+                    bytes.copy_from_slice(&self.incoming[i..i + 4]);
+                    u32::from_ne_bytes(bytes)
+                };
+                let (object, header) = (word(0), word(4));
+                let size = (header >> 16) as usize;
+                if size < 8 {
+                    return Err(Error::Failed);
+                }
+                if self.incoming.len() >= size {
+                    let data = self.incoming.drain(..size).skip(8).collect();
+                    let message = Message { object, opcode: header as u16, data, offset: 0 };
+                    match (object, message.opcode) {
+                        // wl_display.error
+                        (DISPLAY, 0) => return Err(Error::Failed),
+                        // wl_display.delete_id
+                        (DISPLAY, _) => continue,
+                        _ => return Ok(Some(message)),
+                    }
+                }
+            }
+            if !self.receive(deadline)? {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Waits until the compositor has handled every request sent so far,
+    /// returning the events received meanwhile.
+    pub fn roundtrip(&mut self) -> Result<Vec<Message>> {
+        let callback = self.new_id();
+        // wl_display.sync
+        self.send(DISPLAY, 0, &[Arg::Uint(callback)])?;
+        let mut messages = Vec::new();
+        loop {
+            let message = self.next_message(None)?.ok_or(Error::Failed)?;
+            if message.object == callback {
+                return Ok(messages);
+            }
+            messages.push(message);
+        }
+    }
+
+    /// Takes the next file descriptor received, which events with file
+    /// descriptor arguments carry in order.
+    ///
+    /// The caller must close it.
+    #[inline]
+    pub fn take_fd(&mut self) -> Option<RawFd> {
+        self.fds.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::os::unix::io::FromRawFd;
+    use std::os::unix::net::UnixStream;
+
+    use super::{Arg, Connection};
+
+    #[test]
+    fn send_and_receive() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let (mut a, mut b) = (Connection::new(a), Connection::new(b));
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+
+        a.send(7, 3, &[
+            Arg::Uint(42),
+            Arg::Str("text/plain"),
+            Arg::Fd(fds[1]),
+            Arg::Uint(7),
+        ]).unwrap();
+        unsafe { libc::close(fds[1]) };
+
+        let mut message = b.next_message(None).unwrap().unwrap();
+        assert_eq!((message.object, message.opcode), (7, 3));
+        assert_eq!(message.uint(), 42);
+        assert_eq!(message.string(), "text/plain");
+        assert_eq!(message.uint(), 7);
+
+        // The received descriptor refers to the same pipe
+        let mut writer = unsafe { File::from_raw_fd(b.take_fd().unwrap()) };
+        writer.write_all(b"hi").unwrap();
+        drop(writer);
+        let mut read = String::new();
+        unsafe { File::from_raw_fd(fds[0]) }.read_to_string(&mut read).unwrap();
+        assert_eq!(read, "hi");
+    }
+
+    #[test]
+    fn signed_numbers() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let (mut a, mut b) = (Connection::new(a), Connection::new(b));
+
+        a.send(7, 0, &[Arg::Fixed(-1.5), Arg::Fixed(0.1), Arg::Int(-2)]).unwrap();
+
+        let mut message = b.next_message(None).unwrap().unwrap();
+        assert_eq!(message.int(), -384);
+        assert_eq!(message.int(), 26);
+        assert_eq!(message.int(), -2);
+        // Reading past the end gives zeros rather than panicking
+        assert_eq!(message.uint(), 0);
+        assert_eq!(message.string(), "");
+    }
+}
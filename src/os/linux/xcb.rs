@@ -0,0 +1,489 @@
+//! Connections to X servers through xcb.
+//!
+//! Unlike Xlib, xcb reports protocol errors as events rather than ending the
+//! process, so requests about windows of other clients, which may be
+//! destroyed at any time, are safe to make.
+
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int, c_uint, c_void};
+use std::ptr;
+use std::time::Instant;
+
+use error::{Error, Result};
+
+/// `XCB_WINDOW_CLASS_INPUT_OUTPUT`
+const INPUT_OUTPUT: u16 = 1;
+
+/// `XCB_COPY_FROM_PARENT`
+const COPY_FROM_PARENT: u8 = 0;
+
+/// `XCB_CW_EVENT_MASK`
+const CW_EVENT_MASK: u32 = 1 << 11;
+
+/// `XCB_EVENT_MASK_PROPERTY_CHANGE`
+pub(super) const PROPERTY_CHANGE_MASK: u32 = 1 << 22;
+
+/// `XCB_PROP_MODE_REPLACE`
+const PROP_MODE_REPLACE: u8 = 0;
+
+/// `XCB_GET_PROPERTY_TYPE_ANY`
+const ANY_PROPERTY_TYPE: u32 = 0;
+
+/// `XCB_ATOM_ATOM`
+pub(super) const ATOM_ATOM: u32 = 4;
+
+/// `XCB_ATOM_PRIMARY`
+pub(super) const ATOM_PRIMARY: u32 = 1;
+
+/// `XCB_ATOM_STRING`
+pub(super) const ATOM_STRING: u32 = 31;
+
+/// `XCB_CURRENT_TIME`
+const CURRENT_TIME: u32 = 0;
+
+/// `xcb_void_cookie_t` and the cookies of requests with replies.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub(super) struct Cookie {
+    sequence: c_uint,
+}
+
+/// The start of `xcb_screen_t`.
+#[repr(C)]
+pub(super) struct Screen {
+    root: u32,
+    default_colormap: u32,
+    white_pixel: u32,
+    black_pixel: u32,
+    current_input_masks: u32,
+    width_in_pixels: u16,
+    height_in_pixels: u16,
+    width_in_millimeters: u16,
+    height_in_millimeters: u16,
+    min_installed_maps: u16,
+    max_installed_maps: u16,
+    root_visual: u32,
+}
+
+/// `xcb_screen_iterator_t`
+#[repr(C)]
+pub(super) struct ScreenIterator {
+    data: *mut Screen,
+    rem: c_int,
+    index: c_int,
+}
+
+/// `xcb_intern_atom_reply_t`
+#[repr(C)]
+pub(super) struct InternAtomReply {
+    response_type: u8,
+    pad: u8,
+    sequence: u16,
+    length: u32,
+    atom: u32,
+}
+
+/// `xcb_get_selection_owner_reply_t`
+#[repr(C)]
+pub(super) struct SelectionOwnerReply {
+    response_type: u8,
+    pad: u8,
+    sequence: u16,
+    length: u32,
+    owner: u32,
+}
+
+/// `xcb_get_property_reply_t`
+#[repr(C)]
+pub(super) struct PropertyReply {
+    response_type: u8,
+    format: u8,
+    sequence: u16,
+    length: u32,
+    type_: u32,
+    bytes_after: u32,
+    value_len: u32,
+    pad: [u8; 12],
+}
+
+/// `xcb_query_pointer_reply_t`
+#[repr(C)]
+pub(super) struct QueryPointerReply {
+    response_type: u8,
+    same_screen: u8,
+    sequence: u16,
+    length: u32,
+    root: u32,
+    child: u32,
+    root_x: i16,
+    root_y: i16,
+    win_x: i16,
+    win_y: i16,
+    mask: u16,
+    pad: [u8; 2],
+}
+
+dynamic_library! {
+    /// The functions of xcb.
+    pub(super) struct Xcb("libxcb.so.1\0", "libxcb.so\0") {
+        fn xcb_connect(display: *const c_char, screen: *mut c_int) -> *mut c_void;
+        fn xcb_connection_has_error(connection: *mut c_void) -> c_int;
+        fn xcb_disconnect(connection: *mut c_void);
+        fn xcb_flush(connection: *mut c_void) -> c_int;
+        fn xcb_get_file_descriptor(connection: *mut c_void) -> c_int;
+        fn xcb_get_maximum_request_length(connection: *mut c_void) -> u32;
+        fn xcb_get_setup(connection: *mut c_void) -> *const c_void;
+        fn xcb_setup_roots_iterator(setup: *const c_void) -> ScreenIterator;
+        fn xcb_screen_next(iterator: *mut ScreenIterator);
+        fn xcb_generate_id(connection: *mut c_void) -> u32;
+        fn xcb_poll_for_event(connection: *mut c_void) -> *mut u8;
+        fn xcb_create_window(
+            connection: *mut c_void,
+            depth: u8,
+            window: u32,
+            parent: u32,
+            x: i16,
+            y: i16,
+            width: u16,
+            height: u16,
+            border_width: u16,
+            class: u16,
+            visual: u32,
+            value_mask: u32,
+            values: *const u32,
+        ) -> Cookie;
+        fn xcb_destroy_window(connection: *mut c_void, window: u32) -> Cookie;
+        fn xcb_change_window_attributes(
+            connection: *mut c_void,
+            window: u32,
+            value_mask: u32,
+            values: *const u32,
+        ) -> Cookie;
+        fn xcb_intern_atom(connection: *mut c_void, only_if_exists: u8, name_len: u16, name: *const c_char) -> Cookie;
+        fn xcb_intern_atom_reply(
+            connection: *mut c_void,
+            cookie: Cookie,
+            error: *mut *mut c_void,
+        ) -> *mut InternAtomReply;
+        fn xcb_get_selection_owner(connection: *mut c_void, selection: u32) -> Cookie;
+        fn xcb_get_selection_owner_reply(
+            connection: *mut c_void,
+            cookie: Cookie,
+            error: *mut *mut c_void,
+        ) -> *mut SelectionOwnerReply;
+        fn xcb_query_pointer(connection: *mut c_void, window: u32) -> Cookie;
+        fn xcb_query_pointer_reply(
+            connection: *mut c_void,
+            cookie: Cookie,
+            error: *mut *mut c_void,
+        ) -> *mut QueryPointerReply;
+        fn xcb_set_selection_owner(connection: *mut c_void, owner: u32, selection: u32, time: u32) -> Cookie;
+        fn xcb_convert_selection(
+            connection: *mut c_void,
+            requestor: u32,
+            selection: u32,
+            target: u32,
+            property: u32,
+            time: u32,
+        ) -> Cookie;
+        fn xcb_get_property(
+            connection: *mut c_void,
+            delete: u8,
+            window: u32,
+            property: u32,
+            type_: u32,
+            long_offset: u32,
+            long_length: u32,
+        ) -> Cookie;
+        fn xcb_get_property_reply(
+            connection: *mut c_void,
+            cookie: Cookie,
+            error: *mut *mut c_void,
+        ) -> *mut PropertyReply;
+        fn xcb_get_property_value(reply: *const PropertyReply) -> *mut c_void;
+        fn xcb_get_property_value_length(reply: *const PropertyReply) -> c_int;
+        fn xcb_change_property(
+            connection: *mut c_void,
+            mode: u8,
+            window: u32,
+            property: u32,
+            type_: u32,
+            format: u8,
+            data_len: u32,
+            data: *const c_void,
+        ) -> Cookie;
+        fn xcb_send_event(
+            connection: *mut c_void,
+            propagate: u8,
+            destination: u32,
+            event_mask: u32,
+            event: *const c_char,
+        ) -> Cookie;
+    }
+}
+
+lazy_static! {
+    static ref XCB: Option<Xcb> = Xcb::load();
+}
+
+/// An event in the 32 bytes of its wire format, in native byte order.
+#[derive(Copy, Clone)]
+pub(super) struct Event(pub(super) [u8; 32]);
+
+impl Event {
+    /// Returns the type of the event, without the bit set for events sent by
+    /// other clients.
+    #[inline]
+    pub fn kind(&self) -> u8 {
+        self.0[0] & 0x7f
+    }
+
+    /// Returns the 32-bit field at the byte `offset`.
+    #[inline]
+    pub fn u32_at(&self, offset: usize) -> u32 {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&self.0[offset..offset + 4]);
+        u32::from_ne_bytes(bytes)
+    }
+
+    /// Sets the 32-bit field at the byte `offset`.
+    #[inline]
+    pub fn set_u32_at(&mut self, offset: usize, value: u32) {
+        self.0[offset..offset + 4].copy_from_slice(&value.to_ne_bytes());
+    }
+}
+
+/// A property of a window, as read by
+/// [`Connection::take_property`](struct.Connection.html#method.take_property).
+pub(super) struct Property {
+    /// The type of the property, or 0 if the property doesn't exist.
+    pub kind: u32,
+    /// The raw bytes of the property.
+    pub value: Vec<u8>,
+}
+
+/// A connection to the X server at `DISPLAY`.
+pub(super) struct Connection {
+    xcb: &'static Xcb,
+    raw: *mut c_void,
+    root: u32,
+    root_visual: u32,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        unsafe { (self.xcb.xcb_disconnect)(self.raw) };
+    }
+}
+
+impl Connection {
+    /// Connects to the X server at `DISPLAY`.
+    pub fn open() -> Result<Connection> {
+        let xcb = XCB.as_ref().ok_or(Error::Unsupported)?;
+        unsafe {
+            let mut screen = 0;
+            // An error connection is returned rather than null on failure,
+            // which must still be disconnected
+            let raw = (xcb.xcb_connect)(ptr::null(), &mut screen);
+            let mut connection = Connection { xcb, raw, root: 0, root_visual: 0 };
+            if raw.is_null() || (xcb.xcb_connection_has_error)(raw) != 0 {
+                return Err(Error::Unsupported);
+            }
+            let mut roots = (xcb.xcb_setup_roots_iterator)((xcb.xcb_get_setup)(raw));
+            for _ in 0..screen {
+                (xcb.xcb_screen_next)(&mut roots);
+            }
+            if roots.data.is_null() {
+                return Err(Error::Failed);
+            }
+            connection.root = (*roots.data).root;
+            connection.root_visual = (*roots.data).root_visual;
+            Ok(connection)
+        }
+    }
+
+    /// Returns the `xcb_connection_t` of the connection.
+    #[inline]
+    pub fn raw(&self) -> *mut c_void {
+        self.raw
+    }
+
+    /// Returns the most bytes a single request may hold.
+    pub fn max_request_bytes(&self) -> usize {
+        unsafe { (self.xcb.xcb_get_maximum_request_length)(self.raw) as usize * 4 }
+    }
+
+    /// Returns the atom named `name`, creating it if needed.
+    pub fn atom(&self, name: &str) -> Result<u32> {
+        let name = CString::new(name).map_err(|_| Error::Failed)?;
+        unsafe {
+            let len = name.as_bytes().len() as u16;
+            let cookie = (self.xcb.xcb_intern_atom)(self.raw, 0, len, name.as_ptr());
+            let reply = (self.xcb.xcb_intern_atom_reply)(self.raw, cookie, ptr::null_mut());
+            if reply.is_null() {
+                return Err(Error::Failed);
+            }
+            let atom = (*reply).atom;
+            libc::free(reply as *mut c_void);
+            Ok(atom)
+        }
+    }
+
+    /// Creates an unmapped window that receives `event_mask` events, for
+    /// owning selections and receiving properties.
+    pub fn create_window(&self, event_mask: u32) -> u32 {
+        unsafe {
+            let window = (self.xcb.xcb_generate_id)(self.raw);
+            (self.xcb.xcb_create_window)(
+                self.raw,
+                COPY_FROM_PARENT,
+                window,
+                self.root,
+                0,
+                0,
+                1,
+                1,
+                0,
+                INPUT_OUTPUT,
+                self.root_visual,
+                CW_EVENT_MASK,
+                &event_mask,
+            );
+            window
+        }
+    }
+
+    /// Destroys `window`.
+    pub fn destroy_window(&self, window: u32) {
+        unsafe { (self.xcb.xcb_destroy_window)(self.raw, window) };
+    }
+
+    /// Selects the `event_mask` events of `window`, which may belong to
+    /// another client.
+    pub fn select_events(&self, window: u32, event_mask: u32) {
+        unsafe { (self.xcb.xcb_change_window_attributes)(self.raw, window, CW_EVENT_MASK, &event_mask) };
+    }
+
+    /// Returns the window that owns `selection`, or 0 if there is none.
+    pub fn selection_owner(&self, selection: u32) -> Result<u32> {
+        unsafe {
+            let cookie = (self.xcb.xcb_get_selection_owner)(self.raw, selection);
+            let reply = (self.xcb.xcb_get_selection_owner_reply)(self.raw, cookie, ptr::null_mut());
+            if reply.is_null() {
+                return Err(Error::Failed);
+            }
+            let owner = (*reply).owner;
+            libc::free(reply as *mut c_void);
+            Ok(owner)
+        }
+    }
+
+    /// Returns the location of the pointer on the root window.
+    pub fn pointer(&self) -> Result<(i16, i16)> {
+        unsafe {
+            let cookie = (self.xcb.xcb_query_pointer)(self.raw, self.root);
+            let reply = (self.xcb.xcb_query_pointer_reply)(self.raw, cookie, ptr::null_mut());
+            if reply.is_null() {
+                return Err(Error::Failed);
+            }
+            let location = ((*reply).root_x, (*reply).root_y);
+            libc::free(reply as *mut c_void);
+            Ok(location)
+        }
+    }
+
+    /// Makes `window` the owner of `selection`.
+    pub fn set_selection_owner(&self, window: u32, selection: u32) {
+        unsafe { (self.xcb.xcb_set_selection_owner)(self.raw, window, selection, CURRENT_TIME) };
+    }
+
+    /// Asks the owner of `selection` to store it as `target` in `property`
+    /// of `window`.
+    pub fn convert_selection(&self, window: u32, selection: u32, target: u32, property: u32) {
+        unsafe {
+            (self.xcb.xcb_convert_selection)(self.raw, window, selection, target, property, CURRENT_TIME)
+        };
+    }
+
+    /// Reads and deletes `property` of `window`.
+    pub fn take_property(&self, window: u32, property: u32) -> Result<Property> {
+        unsafe {
+            let cookie = (self.xcb.xcb_get_property)(
+                self.raw, 1, window, property, ANY_PROPERTY_TYPE, 0, u32::MAX / 4,
+            );
+            let reply = (self.xcb.xcb_get_property_reply)(self.raw, cookie, ptr::null_mut());
+            if reply.is_null() {
+                return Err(Error::Failed);
+            }
+            let len = (self.xcb.xcb_get_property_value_length)(reply).max(0) as usize;
+            let data = (self.xcb.xcb_get_property_value)(reply) as *const u8;
+            let value = if len == 0 { Vec::new() } else { ::std::slice::from_raw_parts(data, len).to_vec() };
+            let kind = (*reply).type_;
+            libc::free(reply as *mut c_void);
+            Ok(Property { kind, value })
+        }
+    }
+
+    /// Replaces `property` of `window` with `data` of `kind`, whose items
+    /// are `format` bits wide.
+    pub fn set_property(&self, window: u32, property: u32, kind: u32, format: u8, data: &[u8]) {
+        let items = data.len() / (format as usize / 8);
+        unsafe {
+            (self.xcb.xcb_change_property)(
+                self.raw,
+                PROP_MODE_REPLACE,
+                window,
+                property,
+                kind,
+                format,
+                items as u32,
+                data.as_ptr() as *const c_void,
+            )
+        };
+    }
+
+    /// Sends `event` to `window` without selecting any event mask, as
+    /// selection owners reply to requests.
+    pub fn send_event(&self, window: u32, event: &Event) {
+        unsafe { (self.xcb.xcb_send_event)(self.raw, 0, window, 0, event.0.as_ptr() as *const c_char) };
+    }
+
+    /// Sends all buffered requests to the server.
+    pub fn flush(&self) -> Result<()> {
+        if unsafe { (self.xcb.xcb_flush)(self.raw) } > 0 {
+            Ok(())
+        } else {
+            Err(Error::Failed)
+        }
+    }
+
+    /// Waits for the next event until `deadline`, returning `None` if it
+    /// passes first.
+    ///
+    /// Errors about requests are skipped.
+    pub fn next_event(&self, deadline: Option<Instant>) -> Result<Option<Event>> {
+        self.flush()?;
+        loop {
+            unsafe {
+                let event = (self.xcb.xcb_poll_for_event)(self.raw);
+                if !event.is_null() {
+                    let mut bytes = [0; 32];
+                    ptr::copy_nonoverlapping(event, bytes.as_mut_ptr(), bytes.len());
+                    libc::free(event as *mut c_void);
+                    // Type 0 is an error
+                    if bytes[0] != 0 {
+                        return Ok(Some(Event(bytes)));
+                    }
+                    continue;
+                }
+                if (self.xcb.xcb_connection_has_error)(self.raw) != 0 {
+                    return Err(Error::Failed);
+                }
+            }
+            let fd = unsafe { (self.xcb.xcb_get_file_descriptor)(self.raw) };
+            if !super::poll_readable(fd, deadline)? {
+                return Ok(None);
+            }
+        }
+    }
+}
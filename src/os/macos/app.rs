@@ -1,37 +1,96 @@
 //! 🍎 Application-specific utilities.
 
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
+use std::io;
+use std::os::raw::c_char;
+use std::ptr;
+use std::process::{self, ChildStderr, ChildStdout, Command, ExitStatus, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use libc::pid_t;
 use objc::runtime::{Class, Object};
 
-use super::NSObject;
+use super::{class, NSObject, NSObjectRef};
+use super::ax::Element;
+use super::run_loop;
+use error::{Error, OsError, Result};
 
 lazy_static! {
-    static ref NS_RUNNING_APPLICATION: &'static Class = {
-        Class::get("NSRunningApplication").unwrap()
+    static ref NS_RUNNING_APPLICATION: Option<&'static Class> = {
+        Class::get("NSRunningApplication")
     };
 
-    static ref NS_STRING: &'static Class = {
-        Class::get("NSString").unwrap()
+    static ref NS_BUNDLE: Option<&'static Class> = {
+        Class::get("NSBundle")
     };
 
-    static ref NS_WORKSPACE_SHARED: &'static Object = {
-        let cls = Class::get("NSWorkspace").unwrap();
+    static ref NS_STRING: Option<&'static Class> = {
+        Class::get("NSString")
+    };
+
+    static ref NS_URL: Option<&'static Class> = {
+        Class::get("NSURL")
+    };
+
+    static ref NS_ARRAY: Option<&'static Class> = {
+        Class::get("NSArray")
+    };
+
+    static ref NS_DICTIONARY: Option<&'static Class> = {
+        Class::get("NSDictionary")
+    };
+
+    static ref NS_WORKSPACE_SHARED: Option<&'static Object> = {
+        let cls = Class::get("NSWorkspace")?;
         unsafe { msg_send![cls, sharedWorkspace] }
     };
 
-    static ref CURRENT_APPLICATION: App = {
-        let cls: &Class = &NS_RUNNING_APPLICATION;
+    static ref CURRENT_APPLICATION: Option<App> = {
+        let cls = (*NS_RUNNING_APPLICATION)?;
         unsafe { msg_send![cls, currentApplication] }
     };
 }
 
-fn str_to_ns_string(s: String) -> NSObject {
-    let ns_string: &Class = &NS_STRING;
-    let s = CString::new(s).unwrap();
+extern {
+    static NSWorkspaceLaunchConfigurationArguments: *mut Object;
+
+    static NSWorkspaceLaunchConfigurationEnvironment: *mut Object;
+}
+
+/// `NSWorkspaceLaunchDefault`, which launches asynchronously.
+const LAUNCH_DEFAULT: usize = 0x0001_0000;
+
+/// `NSWorkspaceLaunchWithoutActivation`
+const LAUNCH_WITHOUT_ACTIVATION: usize = 0x0000_0200;
+
+/// `NSWorkspaceLaunchNewInstance`
+const LAUNCH_NEW_INSTANCE: usize = 0x0008_0000;
+
+/// How often [`App::launch`](struct.App.html#method.launch) checks whether the
+/// application is ready.
+const LAUNCH_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+fn workspace() -> Result<&'static Object> {
+    NS_WORKSPACE_SHARED.ok_or(Error::Unsupported)
+}
+
+/// Returns `Error::Failed` if a method reported failure by returning `NO`.
+fn succeeded(success: bool) -> Result<()> {
+    if success {
+        Ok(())
+    } else {
+        Err(Error::Failed)
+    }
+}
+
+fn str_to_ns_string(s: String) -> Result<NSObject> {
+    let ns_string = class(&NS_STRING)?;
+    // Strings with interior nul bytes cannot name files or applications
+    let s = CString::new(s).map_err(|_| Error::Failed)?;
     let utf8 = s.as_ptr();
-    unsafe { msg_send![ns_string, stringWithUTF8String:utf8] }
+    let ns_string: Option<NSObject> = unsafe { msg_send![ns_string, stringWithUTF8String:utf8] };
+    ns_string.ok_or(Error::Failed)
 }
 
 /// Opens a file using the specified app.
@@ -39,25 +98,344 @@ fn str_to_ns_string(s: String) -> NSObject {
 /// The `appName` parameter need not be specified with a full path and, in the
 /// case of an app wrapper, may be specified with or without the .app extension.
 /// The sending app is deactivated before the request is sent.
-pub fn open_file<'a, 'b, S>(path: &'a str, app_name: S)
+///
+/// Returns [`Error::Failed`](../../error/enum.Error.html#variant.Failed) if
+/// the file could not be opened.
+pub fn open_file<'a, 'b, S>(path: &'a str, app_name: S) -> Result<()>
     where S: Into<Option<&'b str>>
 {
-    let file = str_to_ns_string(path.into());
-    let app  = app_name.into().map(|s| str_to_ns_string(s.into()));
+    let file = str_to_ns_string(path.into())?;
+    let app  = match app_name.into() {
+        Some(name) => Some(str_to_ns_string(name.into())?),
+        None => None,
+    };
 
-    let workspace: &Object = &NS_WORKSPACE_SHARED;
-    unsafe { msg_send![workspace, openFile:file withApplication:app] }
+    let workspace = workspace()?;
+    succeeded(unsafe { msg_send![workspace, openFile:file withApplication:app] })
 }
 
-/// Launches the specified app, returning `true` on success or if it was already
-/// running.
+/// Launches the specified app, succeeding if it was already running.
 ///
 /// The appName parameter need not be specified with a full path and, in the
 /// case of an app wrapper, may be specified with or without the .app extension.
-pub fn launch(app: &str) -> bool {
-    let app = str_to_ns_string(app.into());
-    let workspace: &Object = &NS_WORKSPACE_SHARED;
-    unsafe { msg_send![workspace, launchApplication:app] }
+///
+/// This returns as soon as the launch is requested. Use
+/// [`App::launch`](struct.App.html#method.launch) to wait until the app is
+/// ready for input.
+pub fn launch(app: &str) -> Result<()> {
+    let app = str_to_ns_string(app.into())?;
+    let workspace = workspace()?;
+    succeeded(unsafe { msg_send![workspace, launchApplication:app] })
+}
+
+/// Options for launching an application, either through
+/// [`App::launch`](struct.App.html#method.launch) or as a child process that
+/// gives access to its output and exit status.
+///
+/// # Examples
+///
+/// ```no_run
+/// use auto::os::app::LaunchOptions;
+///
+/// let child = LaunchOptions::new()
+///     .arg("--version")
+///     .capture_output(true)
+///     .launch("TextEdit")
+///     .unwrap();
+/// let output = child.wait_with_output().unwrap();
+/// println!("{}", String::from_utf8_lossy(&output.stdout));
+/// ```
+#[derive(Clone, Debug)]
+pub struct LaunchOptions {
+    args: Vec<String>,
+    capture_output: bool,
+    activate: bool,
+    timeout: Duration,
+}
+
+impl Default for LaunchOptions {
+    fn default() -> LaunchOptions {
+        LaunchOptions {
+            args: Vec::new(),
+            capture_output: false,
+            activate: true,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl LaunchOptions {
+    /// Creates options that launch without arguments, let the application
+    /// inherit this process's standard output and error, and wait up to 30
+    /// seconds for it to become frontmost.
+    #[inline]
+    pub fn new() -> LaunchOptions {
+        LaunchOptions::default()
+    }
+
+    /// Adds a command line argument to pass to the application.
+    pub fn arg<S: Into<String>>(&mut self, arg: S) -> &mut LaunchOptions {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Sets whether the application's standard output and error are captured
+    /// through pipes available from the [`Child`](struct.Child.html).
+    pub fn capture_output(&mut self, capture: bool) -> &mut LaunchOptions {
+        self.capture_output = capture;
+        self
+    }
+
+    /// Sets whether [`App::launch`](struct.App.html#method.launch) brings the
+    /// application to the front and waits for it to get there.
+    pub fn activate(&mut self, activate: bool) -> &mut LaunchOptions {
+        self.activate = activate;
+        self
+    }
+
+    /// Sets how long [`App::launch`](struct.App.html#method.launch) waits for
+    /// the application to be ready.
+    pub fn timeout(&mut self, timeout: Duration) -> &mut LaunchOptions {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Launches a new instance of `app` as a child of this process.
+    ///
+    /// As with [`launch`](fn.launch.html), `app` need not be specified with a
+    /// full path and may be specified with or without the .app extension. It
+    /// may also be a bundle identifier. Unlike `launch`, a new instance is
+    /// started even if the application is already running.
+    pub fn launch(&self, app: &str) -> io::Result<Child> {
+        let executable = executable_path(app).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("application {:?} not found", app))
+        })?;
+
+        let mut command = Command::new(executable);
+        command.args(&self.args);
+        if self.capture_output {
+            command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+        command.spawn().map(|process| Child { process })
+    }
+}
+
+/// Returns whether `app` looks like a bundle identifier, such as
+/// `com.apple.TextEdit`, rather than a name or path.
+fn is_bundle_identifier(app: &str) -> bool {
+    !app.contains('/') && app.contains('.') && !app.ends_with(".app")
+}
+
+/// Returns the full path to the bundle of `app`, which is not owned by us.
+unsafe fn application_path(app: &str) -> Option<*mut Object> {
+    let ns_string = (*NS_STRING)?;
+    let workspace = (*NS_WORKSPACE_SHARED)?;
+    let utf8 = CString::new(app).ok()?;
+
+    let name: *mut Object = msg_send![ns_string, stringWithUTF8String:utf8.as_ptr()];
+    let path: *mut Object = if is_bundle_identifier(app) {
+        msg_send![workspace, absolutePathForAppBundleWithIdentifier:name]
+    } else {
+        msg_send![workspace, fullPathForApplication:name]
+    };
+    if path.is_null() {
+        None
+    } else {
+        Some(path)
+    }
+}
+
+/// Returns the path to the executable within the bundle of `app`.
+fn executable_path(app: &str) -> Option<String> {
+    let ns_bundle = (*NS_BUNDLE)?;
+
+    // None of these objects are owned by us, so they are not wrapped
+    unsafe {
+        let path = application_path(app)?;
+        let bundle: *mut Object = msg_send![ns_bundle, bundleWithPath:path];
+        if bundle.is_null() {
+            return None;
+        }
+        let executable: *mut Object = msg_send![bundle, executablePath];
+        if executable.is_null() {
+            return None;
+        }
+        let utf8: *const c_char = msg_send![executable, UTF8String];
+        Some(CStr::from_ptr(utf8).to_string_lossy().into_owned())
+    }
+}
+
+/// Returns an autoreleased `NSString` copy of `s`, which is not owned by us.
+unsafe fn autoreleased_string(s: &str) -> Result<*mut Object> {
+    let ns_string = class(&NS_STRING)?;
+    // Strings with interior nul bytes cannot be passed to applications
+    let s = CString::new(s).map_err(|_| Error::Failed)?;
+    Ok(msg_send![ns_string, stringWithUTF8String:s.as_ptr()])
+}
+
+/// Returns the `NSWorkspace` launch configuration that passes `args` and adds
+/// `env` to the environment inherited from this process, which is not owned by
+/// us.
+unsafe fn launch_configuration(args: &[&str], env: &[(&str, &str)]) -> Result<*mut Object> {
+    let ns_array = class(&NS_ARRAY)?;
+    let ns_dictionary = class(&NS_DICTIONARY)?;
+
+    let mut strings = Vec::with_capacity(args.len());
+    for arg in args {
+        strings.push(autoreleased_string(arg)?);
+    }
+    let array: *mut Object = msg_send![ns_array, arrayWithObjects:strings.as_ptr() count:strings.len()];
+    let mut keys = vec![NSWorkspaceLaunchConfigurationArguments];
+    let mut values = vec![array];
+
+    // The configured environment replaces the inherited one rather than
+    // adding to it, so it starts from a copy of this process's environment
+    if !env.is_empty() {
+        let mut vars: Vec<(String, String)> = ::std::env::vars()
+            .filter(|(name, _)| !env.iter().any(|&(n, _)| n == name))
+            .collect();
+        vars.extend(env.iter().map(|&(name, value)| (name.to_owned(), value.to_owned())));
+
+        let mut names = Vec::with_capacity(vars.len());
+        let mut contents = Vec::with_capacity(vars.len());
+        for (name, value) in &vars {
+            names.push(autoreleased_string(name)?);
+            contents.push(autoreleased_string(value)?);
+        }
+        let environment: *mut Object = msg_send![
+            ns_dictionary,
+            dictionaryWithObjects:contents.as_ptr()
+            forKeys:names.as_ptr()
+            count:vars.len()
+        ];
+        keys.push(NSWorkspaceLaunchConfigurationEnvironment);
+        values.push(environment);
+    }
+
+    Ok(msg_send![
+        ns_dictionary,
+        dictionaryWithObjects:values.as_ptr()
+        forKeys:keys.as_ptr()
+        count:keys.len()
+    ])
+}
+
+/// Asks the workspace to launch the bundle at `path` with `options`, one of
+/// the `LAUNCH_*` constants, returning as soon as the process exists.
+unsafe fn launch_bundle(
+    path: *mut Object,
+    args: &[&str],
+    env: &[(&str, &str)],
+    options: usize,
+) -> Result<App> {
+    let ns_url = class(&NS_URL)?;
+    let workspace = workspace()?;
+
+    let url: *mut Object = msg_send![ns_url, fileURLWithPath:path];
+    let configuration = launch_configuration(args, env)?;
+    let mut error: *mut Object = ptr::null_mut();
+    let app: Option<NSObjectRef> = msg_send![
+        workspace,
+        launchApplicationAtURL:url
+        options:options
+        configuration:configuration
+        error:&mut error
+    ];
+    // The workspace does not transfer ownership of the app or the error,
+    // whose code is a Launch Services `OSStatus` for most failures
+    let app = match app {
+        Some(app) => app,
+        None if error.is_null() => return Err(Error::Failed),
+        None => {
+            let code: isize = msg_send![error, code];
+            return Err(OsError::from_code(code as i32).into());
+        },
+    };
+    Ok(App(NSObject(msg_send![app.as_ptr(), retain])))
+}
+
+/// An application launched as a child process with
+/// [`LaunchOptions`](struct.LaunchOptions.html).
+#[derive(Debug)]
+pub struct Child {
+    process: process::Child,
+}
+
+impl Child {
+    /// Returns the process identifier of the application.
+    #[inline]
+    pub fn pid(&self) -> Pid {
+        self.process.id() as Pid
+    }
+
+    /// Returns the running application, or `None` if it has not finished
+    /// launching or has exited.
+    #[inline]
+    pub fn app(&self) -> Option<App> {
+        App::from_pid(self.pid())
+    }
+
+    /// Waits up to `timeout` for the application to finish launching and
+    /// returns it.
+    pub fn wait_for_app(&self, timeout: Duration) -> Option<App> {
+        let deadline = Instant::now().checked_add(timeout);
+        loop {
+            if let Some(app) = self.app() {
+                return Some(app);
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return None;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    /// Returns the top-level accessibility element of the application, which
+    /// gives access to its windows.
+    #[inline]
+    pub fn element(&self) -> Element {
+        Element::application(self.pid())
+    }
+
+    /// Returns the captured standard output, if it was captured and has not
+    /// been taken.
+    #[inline]
+    pub fn stdout(&mut self) -> Option<&mut ChildStdout> {
+        self.process.stdout.as_mut()
+    }
+
+    /// Returns the captured standard error, if it was captured and has not
+    /// been taken.
+    #[inline]
+    pub fn stderr(&mut self) -> Option<&mut ChildStderr> {
+        self.process.stderr.as_mut()
+    }
+
+    /// Returns the exit status if the application has exited, without
+    /// blocking.
+    #[inline]
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        self.process.try_wait()
+    }
+
+    /// Waits for the application to exit and returns its exit status.
+    #[inline]
+    pub fn wait(&mut self) -> io::Result<ExitStatus> {
+        self.process.wait()
+    }
+
+    /// Waits for the application to exit and collects its exit status along
+    /// with any captured output.
+    #[inline]
+    pub fn wait_with_output(self) -> io::Result<Output> {
+        self.process.wait_with_output()
+    }
+
+    /// Forcefully terminates the application.
+    #[inline]
+    pub fn kill(&mut self) -> io::Result<()> {
+        self.process.kill()
+    }
 }
 
 /// Terminates invisibly running, auto-terminable applications as if triggered
@@ -65,9 +443,10 @@ pub fn launch(app: &str) -> bool {
 ///
 /// This method corresponds to
 /// `NSRunningApplication.terminateAutomaticallyTerminableApplications()`.
-pub fn auto_terminate() {
-    let cls: &Class = &NS_RUNNING_APPLICATION;
-    unsafe { msg_send![cls, terminateAutomaticallyTerminableApplications] }
+pub fn auto_terminate() -> Result<()> {
+    let cls = class(&NS_RUNNING_APPLICATION)?;
+    let _: () = unsafe { msg_send![cls, terminateAutomaticallyTerminableApplications] };
+    Ok(())
 }
 
 /// A process identifier.
@@ -78,15 +457,127 @@ pub type Pid = pid_t;
 pub struct App(NSObject);
 
 impl App {
+    /// Launches `app` and waits until it has finished launching and, unless
+    /// disabled with [`activate`](struct.LaunchOptions.html#method.activate),
+    /// is frontmost.
+    ///
+    /// `app` may be a bundle identifier such as `com.apple.TextEdit`, a path
+    /// to a bundle, or a name with or without the .app extension. If the app
+    /// is already running, the running instance is brought forward instead.
+    /// The arguments of `options` are passed to the app if it is started, and
+    /// its output is not captured.
+    ///
+    /// Fails with [`Error::TimedOut`](../../error/enum.Error.html#variant.TimedOut)
+    /// if the app is not ready within the timeout of `options`, with
+    /// [`Error::Failed`](../../error/enum.Error.html#variant.Failed) if it
+    /// cannot be found or quits while launching, and with the Launch Services
+    /// error code if the workspace refuses to launch it.
+    ///
+    /// The current thread's run loop runs while waiting, since the properties
+    /// of `NSRunningApplication` only update as their notifications are
+    /// delivered on the main thread. Readiness is also checked through
+    /// accessibility when the process is trusted, which works from any thread.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use auto::os::app::{App, LaunchOptions};
+    ///
+    /// let app = App::launch(
+    ///     "com.apple.TextEdit",
+    ///     LaunchOptions::new().timeout(Duration::from_secs(10)),
+    /// ).unwrap();
+    /// assert!(app.is_active());
+    /// ```
+    pub fn launch(app: &str, options: &LaunchOptions) -> Result<App> {
+        // A timeout too long to represent waits indefinitely
+        let deadline = Instant::now().checked_add(options.timeout);
+        let mut launch_options = LAUNCH_DEFAULT;
+        if !options.activate {
+            launch_options |= LAUNCH_WITHOUT_ACTIVATION;
+        }
+        let args: Vec<&str> = options.args.iter().map(String::as_str).collect();
+        let app = unsafe {
+            let path = application_path(app).ok_or(Error::Failed)?;
+            launch_bundle(path, &args, &[], launch_options)?
+        };
+
+        let element = app.pid().map(Element::application);
+        let mut activated = false;
+        loop {
+            if app.is_terminated() {
+                return Err(Error::Failed);
+            }
+            // An app that answers accessibility requests is running its event
+            // loop, even if its `NSRunningApplication` has not caught up
+            let frontmost = element.as_ref().and_then(|e| e.bool_attribute("AXFrontmost"));
+            if app.is_finished_launching() || frontmost.is_some() {
+                if !options.activate || frontmost == Some(true) || app.is_active() {
+                    return Ok(app);
+                }
+                if !activated {
+                    app.activate(ActivationOptions::IGNORING_OTHER_APPS)?;
+                    activated = true;
+                }
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(Error::TimedOut);
+            }
+            run_loop::run_for(LAUNCH_POLL_INTERVAL);
+        }
+    }
+
+    /// Starts a new instance of the app at `path` with `args` and with `env`
+    /// added to the environment inherited from this process, returning as
+    /// soon as the process exists.
+    ///
+    /// Unlike [`launch`](#method.launch), a new instance is started even if
+    /// the app is already running, so the returned app and its
+    /// [`pid`](#method.pid) identify the process that was just started. Use
+    /// its accessibility [`Element`](../ax/struct.Element.html) to target
+    /// windows and input at it.
+    ///
+    /// As with `launch`, `path` may also be a bundle identifier or a name.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use auto::os::app::App;
+    ///
+    /// let app = App::launch_with_arguments(
+    ///     "/System/Applications/TextEdit.app",
+    ///     &["/tmp/notes.txt"],
+    ///     &[("LANG", "en_US.UTF-8")],
+    /// ).unwrap();
+    /// println!("started TextEdit as {:?}", app.pid());
+    /// ```
+    pub fn launch_with_arguments(path: &str, args: &[&str], env: &[(&str, &str)]) -> Result<App> {
+        unsafe {
+            let bundle = application_path(path).ok_or(Error::Failed)?;
+            launch_bundle(bundle, args, env, LAUNCH_DEFAULT | LAUNCH_NEW_INSTANCE)
+        }
+    }
+
     /// Returns the instance representing this application.
-    pub fn current() -> &'static App {
-        &CURRENT_APPLICATION
+    pub fn current() -> Result<&'static App> {
+        CURRENT_APPLICATION.as_ref().ok_or(Error::Unsupported)
+    }
+
+    /// Returns the frontmost application, which receives key events.
+    pub fn frontmost() -> Option<App> {
+        let workspace = (*NS_WORKSPACE_SHARED)?;
+        unsafe {
+            let app: Option<NSObjectRef> = msg_send![workspace, frontmostApplication];
+            // The workspace does not transfer ownership of the app
+            app.map(|app| App(NSObject(msg_send![app.as_ptr(), retain])))
+        }
     }
 
     /// Returns the running application with the given process identifier, or
     /// `None` if no application has that pid.
     pub fn from_pid(pid: Pid) -> Option<App> {
-        let cls: &Class = &NS_RUNNING_APPLICATION;
+        let cls = (*NS_RUNNING_APPLICATION)?;
         unsafe { msg_send![cls, runningApplicationWithProcessIdentifier:pid] }
     }
 
@@ -108,16 +599,16 @@ impl App {
         unsafe { msg_send![self.0.inner(), isHidden] }
     }
 
-    /// Attempts to hide or unhide the application, returning whether the
-    /// operation is successful. Returns `false` if the app has already quit, or
-    /// if it is a type that is unable to be hidden.
-    pub fn set_hidden(&self, hide: bool) -> bool {
+    /// Attempts to hide or unhide the application. Returns
+    /// [`Error::Failed`](../../error/enum.Error.html#variant.Failed) if the app
+    /// has already quit, or if it is a type that is unable to be hidden.
+    pub fn set_hidden(&self, hide: bool) -> Result<()> {
         let app = self.0.inner();
-        if hide {
+        succeeded(if hide {
             unsafe { msg_send![app, hide] }
         } else {
             unsafe { msg_send![app, unhide] }
-        }
+        })
     }
 
     /// Returns whether the application is currently frontmost.
@@ -125,10 +616,9 @@ impl App {
         unsafe { msg_send![self.0.inner(), isActive] }
     }
 
-    /// Attempts to activate the application using the specified options,
-    /// returning whether or not it was successful.
-    pub fn activate(&self, options: ActivationOptions) -> bool {
-        unsafe { msg_send![self.0.inner(), activateWithOptions:options] }
+    /// Attempts to activate the application using the specified options.
+    pub fn activate(&self, options: ActivationOptions) -> Result<()> {
+        succeeded(unsafe { msg_send![self.0.inner(), activateWithOptions:options] })
     }
 
     /// Returns the `CFBundleIdentifier` of the application, or `None` if the
@@ -165,20 +655,27 @@ impl App {
         }
     }
 
+    /// Returns whether the application has finished launching.
+    pub fn is_finished_launching(&self) -> bool {
+        unsafe { msg_send![self.0.inner(), isFinishedLaunching] }
+    }
+
     /// Returns whether the application has terminated.
     pub fn is_terminated(&self) -> bool {
         unsafe { msg_send![self.0.inner(), isTerminated] }
     }
 
-    /// Attempts to quit the application either forcefully or normally,
-    /// returning whether the request is successful.
-    pub fn terminate(&self, force: bool) -> bool {
+    /// Attempts to quit the application either forcefully or normally.
+    ///
+    /// Succeeding only means that the request was sent; the application may
+    /// still refuse to quit.
+    pub fn terminate(&self, force: bool) -> Result<()> {
         let app = self.0.inner();
-        if force {
+        succeeded(if force {
             unsafe { msg_send![app, forceTerminate] }
         } else {
             unsafe { msg_send![app, terminate] }
-        }
+        })
     }
 
     /// Returns whether the application owns the current menu bar.
@@ -0,0 +1,203 @@
+//! 🔊 Audio output utilities.
+//!
+//! The volume of an audio device is its virtual main volume, which is what
+//! the volume slider in the menu bar shows. It covers every channel of the
+//! device at once and keeps their balance.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use auto::os::audio::Device;
+//!
+//! let speakers = Device::default_output().unwrap();
+//! speakers.set_volume(0.25).unwrap();
+//! speakers.set_muted(false).unwrap();
+//! ```
+
+use std::mem;
+use std::os::raw::c_void;
+use std::ptr;
+
+use error::{Error, OsError, Result};
+
+/// `AudioObjectPropertyAddress`
+#[repr(C)]
+struct PropertyAddress {
+    selector: u32,
+    scope: u32,
+    element: u32,
+}
+
+#[link(name = "CoreAudio", kind = "framework")]
+extern {
+    fn AudioObjectGetPropertyData(
+        object: u32,
+        address: *const PropertyAddress,
+        qualifier_size: u32,
+        qualifier: *const c_void,
+        size: *mut u32,
+        data: *mut c_void,
+    ) -> i32;
+
+    fn AudioObjectSetPropertyData(
+        object: u32,
+        address: *const PropertyAddress,
+        qualifier_size: u32,
+        qualifier: *const c_void,
+        size: u32,
+        data: *const c_void,
+    ) -> i32;
+}
+
+#[link(name = "AudioToolbox", kind = "framework")]
+extern {
+    fn AudioHardwareServiceHasProperty(object: u32, address: *const PropertyAddress) -> u8;
+
+    fn AudioHardwareServiceGetPropertyData(
+        object: u32,
+        address: *const PropertyAddress,
+        qualifier_size: u32,
+        qualifier: *const c_void,
+        size: *mut u32,
+        data: *mut c_void,
+    ) -> i32;
+
+    fn AudioHardwareServiceSetPropertyData(
+        object: u32,
+        address: *const PropertyAddress,
+        qualifier_size: u32,
+        qualifier: *const c_void,
+        size: u32,
+        data: *const c_void,
+    ) -> i32;
+}
+
+/// `kAudioObjectSystemObject`
+const SYSTEM_OBJECT: u32 = 1;
+
+/// `kAudioHardwarePropertyDefaultOutputDevice`
+const DEFAULT_OUTPUT_DEVICE: u32 = 0x644f_7574; // 'dOut'
+
+/// `kAudioHardwareServiceDeviceProperty_VirtualMainVolume`
+const VIRTUAL_MAIN_VOLUME: u32 = 0x766d_7663; // 'vmvc'
+
+/// `kAudioDevicePropertyMute`
+const MUTE: u32 = 0x6d75_7465; // 'mute'
+
+/// `kAudioObjectPropertyScopeGlobal`
+const SCOPE_GLOBAL: u32 = 0x676c_6f62; // 'glob'
+
+/// `kAudioDevicePropertyScopeOutput`
+const SCOPE_OUTPUT: u32 = 0x6f75_7470; // 'outp'
+
+/// `kAudioObjectPropertyElementMain`
+const ELEMENT_MAIN: u32 = 0;
+
+/// `kAudioHardwareUnknownPropertyError`
+const UNKNOWN_PROPERTY_ERROR: i32 = 0x7768_6f3f; // 'who?'
+
+/// Converts an `OSStatus` into a `Result`.
+fn check(status: i32) -> Result<()> {
+    match status {
+        0 => Ok(()),
+        UNKNOWN_PROPERTY_ERROR => Err(Error::Unsupported),
+        _ => Err(OsError::from_code(status).into()),
+    }
+}
+
+/// Reads the property of `object` at `address` with `get`.
+unsafe fn get<T>(
+    get: unsafe extern "C" fn(u32, *const PropertyAddress, u32, *const c_void, *mut u32, *mut c_void) -> i32,
+    object: u32,
+    address: &PropertyAddress,
+) -> Result<T> {
+    let mut value: T = mem::zeroed();
+    let mut size = mem::size_of::<T>() as u32;
+    check(get(object, address, 0, ptr::null(), &mut size, &mut value as *mut T as *mut c_void))?;
+    Ok(value)
+}
+
+/// Writes `value` to the property of `object` at `address` with `set`.
+unsafe fn set<T>(
+    set: unsafe extern "C" fn(u32, *const PropertyAddress, u32, *const c_void, u32, *const c_void) -> i32,
+    object: u32,
+    address: &PropertyAddress,
+    value: T,
+) -> Result<()> {
+    let size = mem::size_of::<T>() as u32;
+    check(set(object, address, 0, ptr::null(), size, &value as *const T as *const c_void))
+}
+
+/// An audio device, identified by its `AudioObjectID`.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Device(u32);
+
+impl Device {
+    /// Wraps the audio object identifier `id`.
+    #[inline]
+    pub fn from_raw(id: u32) -> Device {
+        Device(id)
+    }
+
+    /// Returns the audio object identifier of the device.
+    #[inline]
+    pub fn raw(&self) -> u32 {
+        self.0
+    }
+
+    /// Returns the device that plays system and media sounds.
+    pub fn default_output() -> Result<Device> {
+        let address = PropertyAddress {
+            selector: DEFAULT_OUTPUT_DEVICE,
+            scope: SCOPE_GLOBAL,
+            element: ELEMENT_MAIN,
+        };
+        match unsafe { get(AudioObjectGetPropertyData, SYSTEM_OBJECT, &address)? } {
+            // `kAudioObjectUnknown`, when there is no output device
+            0 => Err(Error::Unsupported),
+            id => Ok(Device(id)),
+        }
+    }
+
+    fn output_address(selector: u32) -> PropertyAddress {
+        PropertyAddress { selector, scope: SCOPE_OUTPUT, element: ELEMENT_MAIN }
+    }
+
+    /// Returns the volume of the device, ranging from 0 to 1.
+    ///
+    /// Returns [`Error::Unsupported`](../../error/enum.Error.html#variant.Unsupported)
+    /// for devices without a volume control, such as most HDMI displays.
+    pub fn volume(&self) -> Result<f32> {
+        let address = Device::output_address(VIRTUAL_MAIN_VOLUME);
+        unsafe {
+            if AudioHardwareServiceHasProperty(self.0, &address) == 0 {
+                return Err(Error::Unsupported);
+            }
+            get(AudioHardwareServiceGetPropertyData, self.0, &address)
+        }
+    }
+
+    /// Sets the volume of the device, ranging from 0 to 1.
+    pub fn set_volume(&self, volume: f32) -> Result<()> {
+        let address = Device::output_address(VIRTUAL_MAIN_VOLUME);
+        unsafe {
+            if AudioHardwareServiceHasProperty(self.0, &address) == 0 {
+                return Err(Error::Unsupported);
+            }
+            set(AudioHardwareServiceSetPropertyData, self.0, &address, volume.clamp(0.0, 1.0))
+        }
+    }
+
+    /// Returns whether the device is muted.
+    pub fn is_muted(&self) -> Result<bool> {
+        let address = Device::output_address(MUTE);
+        let muted: u32 = unsafe { get(AudioObjectGetPropertyData, self.0, &address)? };
+        Ok(muted != 0)
+    }
+
+    /// Mutes the device if `muted` is `true`, or unmutes it otherwise.
+    pub fn set_muted(&self, muted: bool) -> Result<()> {
+        let address = Device::output_address(MUTE);
+        unsafe { set(AudioObjectSetPropertyData, self.0, &address, muted as u32) }
+    }
+}
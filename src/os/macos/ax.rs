@@ -0,0 +1,772 @@
+//! ♿ Accessibility utilities.
+//!
+//! The process must be trusted for accessibility to inspect the user
+//! interfaces of other applications.
+//!
+//! Windows are elements with the `AXWindow` role, which can be moved,
+//! resized, minimized, focused, and closed.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use auto::os::app::App;
+//! use auto::os::ax::Element;
+//!
+//! let pid = App::frontmost().and_then(|app| app.pid()).unwrap();
+//! for window in Element::application(pid).windows() {
+//!     println!("{:?}", window.title());
+//!     window.set_position((0.0, 25.0)).unwrap();
+//!     window.set_size((800.0, 600.0)).unwrap();
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::num::ParseIntError;
+use std::os::raw::c_void;
+use std::str::FromStr;
+
+use super::{cf, CFObject, CFObjectRef, CGPoint, CGSize};
+use super::app::{App, Pid};
+use super::run_loop::{CFRunLoopAddSource, CFRunLoopGetCurrent, kCFRunLoopCommonModes, LoopThread};
+use error::{Error, OsError, Result};
+
+#[link(name = "ApplicationServices", kind = "framework")]
+extern {
+    fn AXUIElementCreateSystemWide() -> CFObject;
+
+    fn AXUIElementCreateApplication(pid: Pid) -> CFObject;
+
+    fn AXUIElementCopyElementAtPosition(
+        application: CFObjectRef,
+        x: f32,
+        y: f32,
+        element: *mut Option<CFObject>,
+    ) -> AXError;
+
+    fn AXUIElementCopyAttributeValue(
+        element: CFObjectRef,
+        attribute: CFObjectRef,
+        value: *mut Option<CFObject>,
+    ) -> AXError;
+
+    fn AXUIElementSetAttributeValue(
+        element: CFObjectRef,
+        attribute: CFObjectRef,
+        value: CFObjectRef,
+    ) -> AXError;
+
+    fn AXUIElementPerformAction(element: CFObjectRef, action: CFObjectRef) -> AXError;
+
+    fn AXUIElementGetPid(element: CFObjectRef, pid: *mut Pid) -> AXError;
+
+    fn AXValueCreate(value_type: AXValueType, value: *const c_void) -> Option<CFObject>;
+
+    fn AXValueGetType(value: CFObjectRef) -> AXValueType;
+
+    fn AXValueGetValue(value: CFObjectRef, value_type: AXValueType, ptr: *mut c_void) -> bool;
+
+    fn AXValueGetTypeID() -> usize;
+
+    fn AXUIElementGetTypeID() -> usize;
+
+    fn AXObserverCreate(
+        application: Pid,
+        callback: unsafe extern fn(CFObjectRef, CFObjectRef, CFObjectRef, *mut c_void),
+        observer: *mut Option<CFObject>,
+    ) -> AXError;
+
+    fn AXObserverAddNotification(
+        observer: CFObjectRef,
+        element: CFObjectRef,
+        notification: CFObjectRef,
+        refcon: *mut c_void,
+    ) -> AXError;
+
+    fn AXObserverRemoveNotification(
+        observer: CFObjectRef,
+        element: CFObjectRef,
+        notification: CFObjectRef,
+    ) -> AXError;
+
+    fn AXObserverGetRunLoopSource(observer: CFObjectRef) -> CFObjectRef;
+}
+
+type AXError = i32;
+
+type AXValueType = u32;
+
+/// `kAXValueCGPointType`
+const AX_VALUE_CG_POINT: AXValueType = 1;
+
+/// `kAXValueCGSizeType`
+const AX_VALUE_CG_SIZE: AXValueType = 2;
+
+/// `kAXErrorAPIDisabled`
+const AX_ERROR_API_DISABLED: AXError = -25211;
+
+/// `kAXErrorActionUnsupported`
+const AX_ERROR_ACTION_UNSUPPORTED: AXError = -25206;
+
+/// `kAXErrorNotificationUnsupported`
+const AX_ERROR_NOTIFICATION_UNSUPPORTED: AXError = -25207;
+
+fn ax_result(err: AXError) -> Result<()> {
+    match err {
+        0 => Ok(()),
+        AX_ERROR_API_DISABLED => Err(Error::PermissionDenied),
+        AX_ERROR_ACTION_UNSUPPORTED => Err(Error::Unsupported),
+        _ => Err(OsError::from_code(err).into()),
+    }
+}
+
+/// An element of an application's user interface, such as a window or button.
+pub struct Element(CFObject);
+
+impl fmt::Debug for Element {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Element")
+            .field("role", &self.role())
+            .field("title", &self.title())
+            .finish()
+    }
+}
+
+impl Clone for Element {
+    #[inline]
+    fn clone(&self) -> Element {
+        Element(cf::retain((self.0).0))
+    }
+}
+
+impl PartialEq for Element {
+    #[inline]
+    fn eq(&self, other: &Element) -> bool {
+        cf::equal(&self.0, &other.0)
+    }
+}
+
+impl Element {
+    /// Returns the element representing all applications, which can be used
+    /// to find elements by position.
+    #[inline]
+    pub fn system_wide() -> Element {
+        unsafe { Element(AXUIElementCreateSystemWide()) }
+    }
+
+    /// Returns the top-level element of the application with `pid`.
+    #[inline]
+    pub fn application(pid: Pid) -> Element {
+        unsafe { Element(AXUIElementCreateApplication(pid)) }
+    }
+
+    /// Returns the frontmost element at `point` in global screen coordinates,
+    /// searching within `self`.
+    pub fn element_at(&self, (x, y): (f64, f64)) -> Result<Element> {
+        let mut element = None;
+        unsafe {
+            let err = AXUIElementCopyElementAtPosition(
+                (self.0).0,
+                x as f32,
+                y as f32,
+                &mut element,
+            );
+            ax_result(err)?;
+        }
+        element.map(Element).ok_or(Error::Unsupported)
+    }
+
+    fn attribute(&self, name: &str) -> Option<CFObject> {
+        let name = cf::string(name);
+        let mut value = None;
+        let err = unsafe {
+            AXUIElementCopyAttributeValue((self.0).0, name.0, &mut value)
+        };
+        match err {
+            0 => value,
+            _ => None,
+        }
+    }
+
+    /// Sets the attribute `name` to `value`.
+    fn set_attribute(&self, name: &str, value: CFObjectRef) -> Result<()> {
+        let name = cf::string(name);
+        ax_result(unsafe { AXUIElementSetAttributeValue((self.0).0, name.0, value) })
+    }
+
+    /// Returns the value of the attribute `name` if it is an `AXValue` of
+    /// `value_type`, which must match `T`.
+    fn ax_value_attribute<T: Copy>(&self, name: &str, value_type: AXValueType, zero: T) -> Option<T> {
+        let value = self.attribute(name)?;
+        unsafe {
+            if cf::type_id(&value) != AXValueGetTypeID() || AXValueGetType(value.0) != value_type {
+                return None;
+            }
+            let mut out = zero;
+            if AXValueGetValue(value.0, value_type, &mut out as *mut T as *mut c_void) {
+                Some(out)
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Sets the attribute `name` to an `AXValue` of `value_type`, which must
+    /// match `T`.
+    fn set_ax_value_attribute<T>(&self, name: &str, value_type: AXValueType, value: &T) -> Result<()> {
+        let value = unsafe { AXValueCreate(value_type, value as *const T as *const c_void) };
+        let value = value.ok_or(Error::Failed)?;
+        self.set_attribute(name, value.0)
+    }
+
+    /// Returns the value of the attribute `name` if it is a boolean.
+    pub fn bool_attribute(&self, name: &str) -> Option<bool> {
+        let value = self.attribute(name)?;
+        if value.0 == cf::boolean(true) {
+            Some(true)
+        } else if value.0 == cf::boolean(false) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    /// Sets the attribute `name` to a boolean.
+    #[inline]
+    pub fn set_bool_attribute(&self, name: &str, value: bool) -> Result<()> {
+        self.set_attribute(name, cf::boolean(value))
+    }
+
+    /// Performs the action `name`, such as `AXPress` or `AXRaise`.
+    pub fn perform_action(&self, name: &str) -> Result<()> {
+        let name = cf::string(name);
+        ax_result(unsafe { AXUIElementPerformAction((self.0).0, name.0) })
+    }
+
+    /// Returns the value of the attribute `name` if it is a string.
+    pub fn string_attribute(&self, name: &str) -> Option<String> {
+        self.attribute(name).and_then(|value| cf::to_string(&value))
+    }
+
+    /// Returns the value of the attribute `name` if it is a list of elements.
+    pub fn elements_attribute(&self, name: &str) -> Vec<Element> {
+        self.attribute(name)
+            .and_then(|value| cf::to_vec(&value))
+            .map(|values| values.into_iter().map(Element).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the value of the attribute `name` if it is an element.
+    pub fn element_attribute(&self, name: &str) -> Option<Element> {
+        self.attribute(name)
+            .filter(|value| cf::type_id(value) == unsafe { AXUIElementGetTypeID() })
+            .map(Element)
+    }
+
+    /// Returns the role of the element, such as `AXButton` or `AXWindow`.
+    #[inline]
+    pub fn role(&self) -> Option<String> {
+        self.string_attribute("AXRole")
+    }
+
+    /// Returns the title of the element.
+    #[inline]
+    pub fn title(&self) -> Option<String> {
+        self.string_attribute("AXTitle")
+    }
+
+    /// Returns the description of the element.
+    #[inline]
+    pub fn description(&self) -> Option<String> {
+        self.string_attribute("AXDescription")
+    }
+
+    /// Returns the help text of the element, which is typically shown as its
+    /// tooltip.
+    #[inline]
+    pub fn help(&self) -> Option<String> {
+        self.string_attribute("AXHelp")
+    }
+
+    /// Returns the value of the element if it is a string.
+    #[inline]
+    pub fn value(&self) -> Option<String> {
+        self.string_attribute("AXValue")
+    }
+
+    /// Returns the element that contains the element.
+    #[inline]
+    pub fn parent(&self) -> Option<Element> {
+        self.element_attribute("AXParent")
+    }
+
+    /// Returns the children of the element.
+    #[inline]
+    pub fn children(&self) -> Vec<Element> {
+        self.elements_attribute("AXChildren")
+    }
+
+    /// Returns the windows of the element if it is an application.
+    #[inline]
+    pub fn windows(&self) -> Vec<Element> {
+        self.elements_attribute("AXWindows")
+    }
+
+    /// Returns the focused application if the element is system-wide.
+    #[inline]
+    pub fn focused_application(&self) -> Option<Element> {
+        self.element_attribute("AXFocusedApplication")
+    }
+
+    /// Returns the element with keyboard focus within the element, which must
+    /// be system-wide or an application.
+    #[inline]
+    pub fn focused_element(&self) -> Option<Element> {
+        self.element_attribute("AXFocusedUIElement")
+    }
+
+    /// Returns the identifier of the process that owns the element.
+    pub fn pid(&self) -> Option<Pid> {
+        let mut pid = 0;
+        match unsafe { AXUIElementGetPid((self.0).0, &mut pid) } {
+            0 => Some(pid),
+            _ => None,
+        }
+    }
+}
+
+/// Window management, for elements with the `AXWindow` role.
+impl Element {
+    /// Returns the location of the upper-left corner of the element in global
+    /// screen coordinates.
+    pub fn position(&self) -> Option<(f64, f64)> {
+        let zero = CGPoint { x: 0.0, y: 0.0 };
+        self.ax_value_attribute("AXPosition", AX_VALUE_CG_POINT, zero).map(Into::into)
+    }
+
+    /// Moves the element so that its upper-left corner is at `position` in
+    /// global screen coordinates.
+    #[inline]
+    pub fn set_position(&self, position: (f64, f64)) -> Result<()> {
+        let point: CGPoint = position.into();
+        self.set_ax_value_attribute("AXPosition", AX_VALUE_CG_POINT, &point)
+    }
+
+    /// Returns the width and height of the element.
+    pub fn size(&self) -> Option<(f64, f64)> {
+        let zero = CGSize { width: 0.0, height: 0.0 };
+        self.ax_value_attribute("AXSize", AX_VALUE_CG_SIZE, zero)
+            .map(|size| (size.width as _, size.height as _))
+    }
+
+    /// Resizes the element, which the application may adjust to fit its
+    /// minimum and maximum sizes.
+    pub fn set_size(&self, (width, height): (f64, f64)) -> Result<()> {
+        let size = CGSize { width: width as _, height: height as _ };
+        self.set_ax_value_attribute("AXSize", AX_VALUE_CG_SIZE, &size)
+    }
+
+    /// Returns whether the window is minimized into the Dock.
+    #[inline]
+    pub fn is_minimized(&self) -> bool {
+        self.bool_attribute("AXMinimized").unwrap_or(false)
+    }
+
+    /// Minimizes the window into the Dock, or restores it.
+    #[inline]
+    pub fn set_minimized(&self, minimized: bool) -> Result<()> {
+        self.set_bool_attribute("AXMinimized", minimized)
+    }
+
+    /// Brings the window in front of the other windows of its application
+    /// without activating the application.
+    #[inline]
+    pub fn raise(&self) -> Result<()> {
+        self.perform_action("AXRaise")
+    }
+
+    /// Brings the window to the front, makes it the main window of its
+    /// application, and activates the application so that the window
+    /// receives keyboard input.
+    pub fn focus(&self) -> Result<()> {
+        self.raise()?;
+        self.set_bool_attribute("AXMain", true)?;
+        let pid = self.pid().ok_or(Error::Failed)?;
+        Element::application(pid).set_bool_attribute("AXFrontmost", true)
+    }
+
+    /// Closes the window by pressing its close button, which the
+    /// application may respond to by asking to save changes.
+    ///
+    /// Returns [`Error::Unsupported`](../../error/enum.Error.html#variant.Unsupported)
+    /// if the window has no close button.
+    pub fn close(&self) -> Result<()> {
+        self.element_attribute("AXCloseButton")
+            .ok_or(Error::Unsupported)?
+            .perform_action("AXPress")
+    }
+}
+
+type ObserverCallback = Box<dyn FnMut(&Element, &str)>;
+
+unsafe extern fn observer_callback(
+    _observer: CFObjectRef,
+    element: CFObjectRef,
+    notification: CFObjectRef,
+    refcon: *mut c_void,
+) {
+    let callback = &mut *(refcon as *mut ObserverCallback);
+    let element = Element(cf::retain(element));
+    let notification = cf::to_string(&cf::retain(notification)).unwrap_or_default();
+    callback(&element, &notification);
+}
+
+/// Receives accessibility notifications, such as `AXWindowCreated` or
+/// `AXFocusedUIElementChanged`, from the elements of one application.
+///
+/// Notifications are delivered through the run loop that the observer is
+/// added to.
+pub struct Observer {
+    observer: CFObject,
+    callback: Box<ObserverCallback>,
+}
+
+impl fmt::Debug for Observer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Observer").field("observer", &self.observer).finish()
+    }
+}
+
+impl Observer {
+    /// Creates an observer for the application with `pid` that calls
+    /// `callback` with the element and name of each notification.
+    pub fn new<F>(pid: Pid, callback: F) -> Result<Observer>
+        where F: FnMut(&Element, &str) + 'static
+    {
+        let mut observer = None;
+        ax_result(unsafe { AXObserverCreate(pid, observer_callback, &mut observer) })?;
+        Ok(Observer {
+            observer: observer.ok_or(Error::Unsupported)?,
+            callback: Box::new(Box::new(callback)),
+        })
+    }
+
+    /// Starts delivering `notification` for `element` and its descendants.
+    pub fn add_notification(&mut self, element: &Element, notification: &str) -> Result<()> {
+        let name = cf::string(notification);
+        let refcon = &mut *self.callback as *mut ObserverCallback as *mut c_void;
+        ax_result(unsafe {
+            AXObserverAddNotification(self.observer.0, (element.0).0, name.0, refcon)
+        })
+    }
+
+    /// Stops delivering `notification` for `element`.
+    pub fn remove_notification(&mut self, element: &Element, notification: &str) -> Result<()> {
+        let name = cf::string(notification);
+        ax_result(unsafe {
+            AXObserverRemoveNotification(self.observer.0, (element.0).0, name.0)
+        })
+    }
+
+    /// Adds the observer to the current thread's run loop, which must run for
+    /// notifications to be delivered.
+    pub fn add_to_current_run_loop(&self) {
+        unsafe {
+            let source = AXObserverGetRunLoopSource(self.observer.0);
+            CFRunLoopAddSource(CFRunLoopGetCurrent(), source, kCFRunLoopCommonModes);
+        }
+    }
+
+    /// Spawns a thread that observes `notifications` from the application
+    /// with `pid` and calls `callback` for each one until the returned handle
+    /// is dropped.
+    ///
+    /// Notifications that the application does not support are skipped.
+    pub fn spawn<F>(pid: Pid, notifications: &[&str], callback: F) -> Result<ObserverHandle>
+        where F: FnMut(&Element, &str) + Send + 'static
+    {
+        let notifications: Vec<String> = notifications.iter().map(|&n| n.into()).collect();
+        let thread = LoopThread::spawn(move || -> Result<Observer> {
+            let mut observer = Observer::new(pid, callback)?;
+            let app = Element::application(pid);
+            for notification in &notifications {
+                match observer.add_notification(&app, notification) {
+                    Err(Error::Os(ref err)) if err.code() == AX_ERROR_NOTIFICATION_UNSUPPORTED => {},
+                    result => result?,
+                }
+            }
+            observer.add_to_current_run_loop();
+            Ok(observer)
+        })?;
+        Ok(ObserverHandle { thread })
+    }
+}
+
+/// A handle to an [`Observer`](struct.Observer.html) running on a dedicated
+/// thread, which stops when dropped.
+#[derive(Debug)]
+pub struct ObserverHandle {
+    thread: LoopThread,
+}
+
+impl ObserverHandle {
+    /// Stops the observer and waits for its thread to exit.
+    #[inline]
+    pub fn stop(mut self) {
+        self.thread.stop();
+    }
+}
+
+/// The greatest depth below an application that stable identifiers are
+/// searched for.
+const MAX_STABLE_DEPTH: usize = 64;
+
+/// A best-effort identifier for an element that stays the same across
+/// launches of its application, so that it can be stored and looked up later
+/// with [`Element::resolve_stable_id`](struct.Element.html#method.resolve_stable_id).
+///
+/// The identifier is a hash of the [stable path](struct.Element.html#method.stable_path)
+/// of the element. Elements with the same role and label in the same place,
+/// such as unlabeled groups, share an identifier; use
+/// [`Element::stable_id_collisions`](struct.Element.html#method.stable_id_collisions)
+/// to find them.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StableId(u64);
+
+impl StableId {
+    /// Returns the identifier with the raw value `raw`.
+    #[inline]
+    pub fn from_raw(raw: u64) -> StableId {
+        StableId(raw)
+    }
+
+    /// Returns the raw value of the identifier.
+    #[inline]
+    pub fn raw(self) -> u64 {
+        self.0
+    }
+
+    /// Returns the identifier of the stable path `path`.
+    pub(super) fn of_path(path: &str) -> StableId {
+        // FNV-1a, whose output does not change between Rust versions
+        let hash = path.bytes().fold(0xcbf2_9ce4_8422_2325, |hash: u64, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+        StableId(hash)
+    }
+}
+
+impl fmt::Display for StableId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl FromStr for StableId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> ::std::result::Result<StableId, ParseIntError> {
+        u64::from_str_radix(s, 16).map(StableId)
+    }
+}
+
+/// The outcome of looking up an element by its
+/// [`StableId`](struct.StableId.html).
+#[derive(Debug)]
+pub enum Resolution {
+    /// Exactly one element has the identifier.
+    Found(Element),
+    /// No element has the identifier.
+    Missing,
+    /// Several elements share the identifier.
+    Ambiguous(Vec<Element>),
+}
+
+/// Returns `label` with runs of digits replaced by `#`, so that labels like
+/// "Untitled 3" survive renumbering.
+fn label_pattern(label: &str) -> String {
+    let mut pattern = String::with_capacity(label.len());
+    for ch in label.trim().chars() {
+        if !ch.is_ascii_digit() {
+            pattern.push(ch);
+        } else if !pattern.ends_with('#') {
+            pattern.push('#');
+        }
+    }
+    pattern
+}
+
+/// Returns the part of a stable path that identifies an element with `role`
+/// and `label` within its parent.
+pub(super) fn stable_step(role: &str, label: &str) -> String {
+    match label_pattern(label) {
+        ref pattern if pattern.is_empty() => role.to_owned(),
+        pattern => format!("{} {:?}", role, pattern),
+    }
+}
+
+/// Returns the part of a stable path that identifies `element` within its
+/// parent, labeled by its title or description.
+fn path_step(element: &Element) -> String {
+    let label = element.title()
+        .filter(|title| !title.is_empty())
+        .or_else(|| element.description())
+        .unwrap_or_default();
+    stable_step(&element.role().unwrap_or_default(), &label)
+}
+
+/// Groups the items of `paths` that share a path, keeping only the groups of
+/// several items, ordered by path.
+fn colliding_paths<T>(paths: Vec<(String, T)>) -> Vec<(String, Vec<T>)> {
+    let mut groups: HashMap<String, Vec<T>> = HashMap::new();
+    for (path, item) in paths {
+        groups.entry(path).or_default().push(item);
+    }
+    let mut collisions: Vec<_> = groups.into_iter()
+        .filter(|(_, items)| items.len() > 1)
+        .collect();
+    collisions.sort_by(|a, b| a.0.cmp(&b.0));
+    collisions
+}
+
+/// Returns the part of a stable path that identifies the application.
+fn path_owner(app: &Element) -> Option<String> {
+    app.pid()
+        .and_then(App::from_pid)
+        .and_then(|app| app.bundle_identifier())
+        .or_else(|| app.title())
+}
+
+impl Element {
+    /// Returns the readable path that the [stable identifier](#method.stable_id)
+    /// of the element is a hash of.
+    ///
+    /// The path starts with the bundle identifier of the owning application,
+    /// followed by the role and label of each element down to `self`, as in
+    /// `com.apple.TextEdit/AXWindow "Untitled #"/AXButton`. Digits in labels
+    /// are replaced by `#`.
+    ///
+    /// Returns `None` if the element is not within an application.
+    pub fn stable_path(&self) -> Option<String> {
+        let mut steps = vec![path_step(self)];
+        let mut ancestor = self.parent();
+        let mut depth = 0;
+        while let Some(element) = ancestor {
+            if element.role().as_deref() == Some("AXApplication") {
+                steps.push(path_owner(&element)?);
+                steps.reverse();
+                return Some(steps.join("/"));
+            }
+            depth += 1;
+            if depth > MAX_STABLE_DEPTH {
+                return None;
+            }
+            steps.push(path_step(&element));
+            ancestor = element.parent();
+        }
+        None
+    }
+
+    /// Returns a best-effort identifier for the element that survives
+    /// restarts of its application.
+    ///
+    /// See [`StableId`](struct.StableId.html) for its limitations.
+    #[inline]
+    pub fn stable_id(&self) -> Option<StableId> {
+        self.stable_path().map(|path| StableId::of_path(&path))
+    }
+
+    /// Calls `f` with every element below `self`, which must be an
+    /// application, along with its stable path.
+    fn visit_stable_paths<F: FnMut(Element, &str)>(&self, mut f: F) {
+        fn visit<F: FnMut(Element, &str)>(element: Element, path: String, depth: usize, f: &mut F) {
+            if depth < MAX_STABLE_DEPTH {
+                for child in element.children() {
+                    let child_path = format!("{}/{}", path, path_step(&child));
+                    visit(child, child_path, depth + 1, f);
+                }
+            }
+            f(element, &path);
+        }
+        if let Some(owner) = path_owner(self) {
+            for child in self.children() {
+                let path = format!("{}/{}", owner, path_step(&child));
+                visit(child, path, 1, &mut f);
+            }
+        }
+    }
+
+    /// Finds the elements below `self`, which must be an application, whose
+    /// [stable identifier](#method.stable_id) is `id`.
+    pub fn resolve_stable_id(&self, id: StableId) -> Resolution {
+        let mut found = Vec::new();
+        self.visit_stable_paths(|element, path| {
+            if StableId::of_path(path) == id {
+                found.push(element);
+            }
+        });
+        match found.len() {
+            0 => Resolution::Missing,
+            1 => Resolution::Found(found.remove(0)),
+            _ => Resolution::Ambiguous(found),
+        }
+    }
+
+    /// Returns the groups of elements below `self`, which must be an
+    /// application, that share a [stable identifier](#method.stable_id), along
+    /// with their shared stable path.
+    ///
+    /// Elements in these groups cannot be told apart by their identifiers, so
+    /// selectors for them should use something else.
+    pub fn stable_id_collisions(&self) -> Vec<(String, Vec<Element>)> {
+        let mut paths = Vec::new();
+        self.visit_stable_paths(|element, path| paths.push((path.to_owned(), element)));
+        colliding_paths(paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn label_patterns() {
+        assert_eq!(label_pattern("Untitled 3"), "Untitled #");
+        assert_eq!(label_pattern(" Page 12 of 340 "), "Page # of #");
+        assert_eq!(label_pattern("2024"), "#");
+        assert_eq!(label_pattern(""), "");
+
+        assert_eq!(stable_step("AXWindow", "Untitled 3"), "AXWindow \"Untitled #\"");
+        assert_eq!(stable_step("AXGroup", "  "), "AXGroup");
+    }
+
+    #[test]
+    fn stable_ids() {
+        // FNV-1a values must never change, or stored identifiers go stale
+        assert_eq!(StableId::of_path("").raw(), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(StableId::of_path("a").raw(), 0xaf63_dc4c_8601_ec8c);
+
+        let id = StableId::of_path("com.apple.TextEdit/AXWindow \"Untitled #\"");
+        assert_eq!(id.to_string(), "e284c5672f1aa1b1");
+        assert_eq!("e284c5672f1aa1b1".parse(), Ok(id));
+        assert_eq!(StableId::from_raw(1).to_string(), "0000000000000001");
+        assert_eq!("1".parse(), Ok(StableId::from_raw(1)));
+        assert!("xyz".parse::<StableId>().is_err());
+    }
+
+    #[test]
+    fn collisions() {
+        let paths = vec![
+            ("app/AXWindow/AXGroup".to_owned(), 1),
+            ("app/AXWindow/AXButton \"OK\"".to_owned(), 2),
+            ("app/AXWindow/AXGroup".to_owned(), 3),
+            ("app/AXWindow".to_owned(), 4),
+            ("app/AXWindow/AXButton \"OK\"".to_owned(), 5),
+        ];
+        assert_eq!(colliding_paths(paths), [
+            ("app/AXWindow/AXButton \"OK\"".to_owned(), vec![2, 5]),
+            ("app/AXWindow/AXGroup".to_owned(), vec![1, 3]),
+        ]);
+        assert!(colliding_paths(vec![("app".to_owned(), 1)]).is_empty());
+    }
+}
@@ -0,0 +1,196 @@
+//! Core Foundation conversions shared across modules.
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+
+use super::{CFObject, CFObjectRef};
+
+extern {
+    fn CFRetain(obj: CFObjectRef) -> CFObjectRef;
+
+    fn CFGetTypeID(obj: CFObjectRef) -> usize;
+
+    fn CFEqual(a: CFObjectRef, b: CFObjectRef) -> bool;
+
+    fn CFStringGetTypeID() -> usize;
+
+    fn CFStringCreateWithBytes(
+        allocator: *const c_void,
+        bytes: *const u8,
+        len: isize,
+        encoding: u32,
+        is_external_representation: bool,
+    ) -> Option<CFObject>;
+
+    fn CFStringGetLength(string: CFObjectRef) -> isize;
+
+    fn CFStringGetMaximumSizeForEncoding(len: isize, encoding: u32) -> isize;
+
+    fn CFStringGetCString(
+        string: CFObjectRef,
+        buffer: *mut c_char,
+        buffer_size: isize,
+        encoding: u32,
+    ) -> bool;
+
+    fn CFNumberGetTypeID() -> usize;
+
+    fn CFNumberGetValue(number: CFObjectRef, number_type: isize, value: *mut c_void) -> bool;
+
+    fn CFNumberCreate(
+        allocator: *const c_void,
+        number_type: isize,
+        value: *const c_void,
+    ) -> Option<CFObject>;
+
+    fn CFDataCreate(allocator: *const c_void, bytes: *const u8, len: isize) -> Option<CFObject>;
+
+    fn CFArrayGetTypeID() -> usize;
+
+    fn CFArrayGetCount(array: CFObjectRef) -> isize;
+
+    fn CFArrayGetValueAtIndex(array: CFObjectRef, index: isize) -> Option<CFObjectRef>;
+
+    fn CFDictionaryCreate(
+        allocator: *const c_void,
+        keys: *const CFObjectRef,
+        values: *const CFObjectRef,
+        count: isize,
+        key_callbacks: *const c_void,
+        value_callbacks: *const c_void,
+    ) -> Option<CFObject>;
+
+    fn CFDictionaryGetValue(dict: CFObjectRef, key: CFObjectRef) -> Option<CFObjectRef>;
+
+    static kCFTypeDictionaryKeyCallBacks: c_void;
+
+    static kCFTypeDictionaryValueCallBacks: c_void;
+
+    static kCFBooleanTrue: CFObjectRef;
+
+    static kCFBooleanFalse: CFObjectRef;
+}
+
+/// `kCFStringEncodingUTF8`
+const UTF8: u32 = 0x0800_0100;
+
+/// `kCFNumberSInt64Type`
+const SINT64: isize = 4;
+
+/// Returns a new reference to `obj`.
+#[inline]
+pub fn retain(obj: CFObjectRef) -> CFObject {
+    unsafe { CFObject(CFRetain(obj)) }
+}
+
+/// Returns whether `a` and `b` are equal.
+#[inline]
+pub fn equal(a: &CFObject, b: &CFObject) -> bool {
+    unsafe { CFEqual(a.0, b.0) }
+}
+
+/// Returns the type identifier of `obj`.
+#[inline]
+pub fn type_id(obj: &CFObject) -> usize {
+    unsafe { CFGetTypeID(obj.0) }
+}
+
+/// Creates a `CFString` from `s`.
+pub fn string(s: &str) -> CFObject {
+    unsafe {
+        CFStringCreateWithBytes(ptr::null(), s.as_ptr(), s.len() as isize, UTF8, false)
+            .expect("failed to create CFString")
+    }
+}
+
+/// Returns the contents of `obj` if it is a `CFString`.
+pub fn to_string(obj: &CFObject) -> Option<String> {
+    unsafe {
+        if type_id(obj) != CFStringGetTypeID() {
+            return None;
+        }
+        let len = CFStringGetLength(obj.0);
+        let size = CFStringGetMaximumSizeForEncoding(len, UTF8) + 1;
+        let mut buf = vec![0 as c_char; size as usize];
+        if !CFStringGetCString(obj.0, buf.as_mut_ptr(), size, UTF8) {
+            return None;
+        }
+        Some(CStr::from_ptr(buf.as_ptr()).to_string_lossy().into_owned())
+    }
+}
+
+/// Returns the value of `obj` if it is a `CFNumber` that fits in an `i64`.
+pub fn to_i64(obj: &CFObject) -> Option<i64> {
+    unsafe {
+        if type_id(obj) != CFNumberGetTypeID() {
+            return None;
+        }
+        let mut value = 0i64;
+        if CFNumberGetValue(obj.0, SINT64, &mut value as *mut i64 as *mut c_void) {
+            Some(value)
+        } else {
+            None
+        }
+    }
+}
+
+/// Creates a `CFNumber` from `value`.
+pub fn number(value: i64) -> CFObject {
+    unsafe {
+        CFNumberCreate(ptr::null(), SINT64, &value as *const i64 as *const c_void)
+            .expect("failed to create CFNumber")
+    }
+}
+
+/// Creates a `CFData` holding a copy of `bytes`.
+pub fn data(bytes: &[u8]) -> CFObject {
+    unsafe {
+        CFDataCreate(ptr::null(), bytes.as_ptr(), bytes.len() as isize)
+            .expect("failed to create CFData")
+    }
+}
+
+/// Returns retained references to the values of `obj` if it is a `CFArray`.
+pub fn to_vec(obj: &CFObject) -> Option<Vec<CFObject>> {
+    unsafe {
+        if type_id(obj) != CFArrayGetTypeID() {
+            return None;
+        }
+        let count = CFArrayGetCount(obj.0);
+        let values = (0..count)
+            .filter_map(|i| CFArrayGetValueAtIndex(obj.0, i))
+            .map(retain)
+            .collect();
+        Some(values)
+    }
+}
+
+/// Returns the shared `CFBoolean` for `value`.
+#[inline]
+pub fn boolean(value: bool) -> CFObjectRef {
+    unsafe { if value { kCFBooleanTrue } else { kCFBooleanFalse } }
+}
+
+/// Creates a `CFDictionary` that retains its keys and values.
+pub fn dictionary(pairs: &[(CFObjectRef, CFObjectRef)]) -> CFObject {
+    let keys: Vec<CFObjectRef> = pairs.iter().map(|&(key, _)| key).collect();
+    let values: Vec<CFObjectRef> = pairs.iter().map(|&(_, value)| value).collect();
+    unsafe {
+        CFDictionaryCreate(
+            ptr::null(),
+            keys.as_ptr(),
+            values.as_ptr(),
+            pairs.len() as isize,
+            &kCFTypeDictionaryKeyCallBacks,
+            &kCFTypeDictionaryValueCallBacks,
+        ).expect("failed to create CFDictionary")
+    }
+}
+
+/// Returns the value for `key` in `dict`, which must be a `CFDictionary`,
+/// without retaining it.
+#[inline]
+pub fn get(dict: &CFObject, key: CFObjectRef) -> Option<CFObjectRef> {
+    unsafe { CFDictionaryGetValue(dict.0, key) }
+}
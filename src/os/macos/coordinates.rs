@@ -0,0 +1,95 @@
+//! 📐 Converting locations between coordinate spaces.
+//!
+//! macOS has two conventions for global locations. Quartz, which includes
+//! events, displays, and windows, puts the origin at the upper-left corner of
+//! the main display with y growing downward. Cocoa, which includes
+//! [`mouse::location`](../mouse/fn.location.html), puts it at the lower-left
+//! corner with y growing upward. Passing a location from one to the other
+//! mirrors it vertically around the middle of the main display.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use auto::os::coordinates::CoordinateSpace;
+//! use auto::os::mouse::{self, Button, Event, EventKind};
+//!
+//! let cocoa = mouse::location().unwrap();
+//! let global = CoordinateSpace::Cocoa.convert(cocoa, CoordinateSpace::Global);
+//!
+//! let event = Event::new(Button::Left, EventKind::Down, global).unwrap();
+//! ```
+
+use super::mouse::Location;
+use super::screen::{Bounds, Display};
+
+/// A system of coordinates for locations on the screen.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum CoordinateSpace {
+    /// Quartz global coordinates, with the origin at the upper-left corner of
+    /// the main display and y growing downward.
+    Global,
+    /// Cocoa screen coordinates, with the origin at the lower-left corner of
+    /// the main display and y growing upward.
+    Cocoa,
+    /// Coordinates with the origin at the upper-left corner of the display
+    /// and y growing downward, as used by
+    /// [`Display::color_at`](../screen/struct.Display.html#method.color_at)
+    /// and [`Display::capture_rect`](../screen/struct.Display.html#method.capture_rect).
+    Display(Display),
+}
+
+impl CoordinateSpace {
+    /// Converts `location` from this space into `space`.
+    #[inline]
+    pub fn convert(self, location: Location, space: CoordinateSpace) -> Location {
+        space.from_global(self.to_global(location))
+    }
+
+    /// Converts `location` from this space into global coordinates.
+    pub fn to_global(self, (x, y): Location) -> Location {
+        match self {
+            CoordinateSpace::Global => (x, y),
+            CoordinateSpace::Cocoa => (x, flip(y, Display::main().bounds())),
+            CoordinateSpace::Display(display) => {
+                let (ox, oy) = display.bounds().origin;
+                (x + ox, y + oy)
+            },
+        }
+    }
+
+    /// Converts `location` from global coordinates into this space.
+    pub fn from_global(self, (x, y): Location) -> Location {
+        match self {
+            CoordinateSpace::Global => (x, y),
+            // Flipping is its own inverse
+            CoordinateSpace::Cocoa => (x, flip(y, Display::main().bounds())),
+            CoordinateSpace::Display(display) => {
+                let (ox, oy) = display.bounds().origin;
+                (x - ox, y - oy)
+            },
+        }
+    }
+}
+
+/// Mirrors `y` between Quartz and Cocoa coordinates, given the bounds of the
+/// main display.
+#[inline]
+fn flip(y: f64, main: Bounds) -> f64 {
+    main.size.1 - y
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flip_round_trip() {
+        let main = Bounds { origin: (0.0, 0.0), size: (1440.0, 900.0) };
+        assert_eq!(flip(0.0, main), 900.0);
+        assert_eq!(flip(flip(123.5, main), main), 123.5);
+
+        let pos = (200.0, 300.0);
+        let cocoa = CoordinateSpace::Global.convert(pos, CoordinateSpace::Cocoa);
+        assert_eq!(CoordinateSpace::Cocoa.convert(cocoa, CoordinateSpace::Global), pos);
+    }
+}
@@ -0,0 +1,288 @@
+use super::EventType;
+
+/// A field of a Quartz event that can be read or written through
+/// [`Event::integer_field`](struct.Event.html#method.integer_field) and
+/// [`Event::double_field`](struct.Event.html#method.double_field).
+///
+/// Each field is only meaningful for certain kinds of events. For example,
+/// [`KeyboardKeycode`](#variant.KeyboardKeycode) has no meaning for a mouse
+/// event. Use [`is_valid_for`](#method.is_valid_for) to check this at runtime.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum EventField {
+    /// The mouse button event number. Matching mouse-down and mouse-up events
+    /// will have the same event number.
+    MouseEventNumber = 0,
+    /// The mouse button click state. A click state of 1 represents a single
+    /// click, 2 a double click, and 3 a triple click.
+    MouseClickState = 1,
+    /// The mouse button pressure state, ranging from 0 (mouse being up) to 1.
+    MousePressure = 2,
+    /// The mouse button number. For an `OtherMouse` event, this field
+    /// identifies the button that generated the event.
+    MouseButtonNumber = 3,
+    /// The horizontal mouse delta since the last mouse movement event.
+    MouseDeltaX = 4,
+    /// The vertical mouse delta since the last mouse movement event.
+    MouseDeltaY = 5,
+    /// Non-zero if the event should be ignored by the Inkwell subsystem.
+    MouseInstantMouser = 6,
+    /// The mouse event subtype: default, tablet point, or tablet proximity.
+    MouseSubtype = 7,
+    /// The window number of the window under the mouse pointer.
+    MouseWindowUnderPointer = 91,
+    /// The window number of the window under the mouse pointer that can handle
+    /// this event.
+    MouseWindowUnderPointerThatCanHandle = 92,
+    /// The horizontal pointer movement before acceleration is applied.
+    UnacceleratedPointerMovementX = 170,
+    /// The vertical pointer movement before acceleration is applied.
+    UnacceleratedPointerMovementY = 171,
+
+    /// Non-zero when this is an autorepeat of a key-down event.
+    KeyboardAutorepeat = 8,
+    /// The virtual keycode of the key-down or key-up event.
+    KeyboardKeycode = 9,
+    /// The keyboard type identifier.
+    KeyboardType = 10,
+
+    /// The line-based scrolling delta of the vertical wheel.
+    ScrollDeltaAxis1 = 11,
+    /// The line-based scrolling delta of the horizontal wheel.
+    ScrollDeltaAxis2 = 12,
+    /// The line-based scrolling delta of the third wheel.
+    ScrollDeltaAxis3 = 13,
+    /// Non-zero if the event should be ignored by the Inkwell subsystem.
+    ScrollInstantMouser = 14,
+    /// Non-zero if the scrolling data is pixel-based rather than line-based.
+    ScrollIsContinuous = 88,
+    /// The fixed-point line delta of the vertical wheel.
+    ScrollFixedPtDeltaAxis1 = 93,
+    /// The fixed-point line delta of the horizontal wheel.
+    ScrollFixedPtDeltaAxis2 = 94,
+    /// The fixed-point line delta of the third wheel.
+    ScrollFixedPtDeltaAxis3 = 95,
+    /// The pixel-based scrolling delta of the vertical wheel.
+    ScrollPointDeltaAxis1 = 96,
+    /// The pixel-based scrolling delta of the horizontal wheel.
+    ScrollPointDeltaAxis2 = 97,
+    /// The pixel-based scrolling delta of the third wheel.
+    ScrollPointDeltaAxis3 = 98,
+    /// The phase of a trackpad scroll gesture.
+    ScrollPhase = 99,
+    /// The number of consecutive scroll gestures.
+    ScrollCount = 100,
+    /// The phase of the momentum scrolling that follows a trackpad gesture.
+    ScrollMomentumPhase = 123,
+
+    /// The absolute tablet X coordinate in tablet space.
+    TabletPointX = 15,
+    /// The absolute tablet Y coordinate in tablet space.
+    TabletPointY = 16,
+    /// The absolute tablet Z coordinate in tablet space.
+    TabletPointZ = 17,
+    /// The tablet button state as a bit field.
+    TabletPointButtons = 18,
+    /// The tablet pen pressure, ranging from 0 to 1.
+    TabletPressure = 19,
+    /// The horizontal tablet pen tilt, ranging from -1 to 1.
+    TabletTiltX = 20,
+    /// The vertical tablet pen tilt, ranging from -1 to 1.
+    TabletTiltY = 21,
+    /// The tablet pen rotation.
+    TabletRotation = 22,
+    /// The tangential pressure on the device, ranging from -1 to 1.
+    TabletTangentialPressure = 23,
+    /// The system-assigned unique device identifier.
+    TabletDeviceId = 24,
+    /// The first vendor-specified value.
+    TabletVendor1 = 25,
+    /// The second vendor-specified value.
+    TabletVendor2 = 26,
+    /// The third vendor-specified value.
+    TabletVendor3 = 27,
+
+    /// The vendor-defined identifier of the tablet.
+    ProximityVendorId = 28,
+    /// The vendor-defined tablet identifier.
+    ProximityTabletId = 29,
+    /// The vendor-defined identifier of the pointing device.
+    ProximityPointerId = 30,
+    /// The system-assigned device identifier.
+    ProximityDeviceId = 31,
+    /// The system-assigned unique tablet identifier.
+    ProximitySystemTabletId = 32,
+    /// The vendor-assigned pointer type.
+    ProximityVendorPointerType = 33,
+    /// The vendor-defined pointer serial number.
+    ProximityVendorPointerSerialNumber = 34,
+    /// The vendor-defined unique identifier.
+    ProximityVendorUniqueId = 35,
+    /// The device capabilities mask.
+    ProximityCapabilityMask = 36,
+    /// The pointer type.
+    ProximityPointerType = 37,
+    /// Non-zero if the pen is in proximity to the tablet.
+    ProximityEnterProximity = 38,
+
+    /// The event target process serial number.
+    TargetProcessSerialNumber = 39,
+    /// The event target Unix process identifier.
+    TargetUnixProcessId = 40,
+    /// The Unix process identifier of the event source.
+    SourceUnixProcessId = 41,
+    /// Event source user-supplied data, up to 64 bits.
+    SourceUserData = 42,
+    /// The Unix effective user identifier of the event source.
+    SourceUserId = 43,
+    /// The Unix effective group identifier of the event source.
+    SourceGroupId = 44,
+    /// The state identifier used to create the event source.
+    SourceStateId = 45,
+}
+
+/// The category of events that an [`EventField`](enum.EventField.html) is
+/// meaningful for.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum EventFieldKind {
+    /// Mouse button and movement events.
+    Mouse,
+    /// Key-down, key-up, and flags-changed events.
+    Keyboard,
+    /// Scroll wheel events.
+    ScrollWheel,
+    /// Tablet pointer events, or mouse events with a tablet point subtype.
+    TabletPointer,
+    /// Tablet proximity events, or mouse events with a tablet proximity
+    /// subtype.
+    TabletProximity,
+    /// Any event.
+    Any,
+}
+
+impl EventField {
+    /// Returns the category of events that `self` is meaningful for.
+    pub fn kind(self) -> EventFieldKind {
+        use self::EventField::*;
+        match self {
+            MouseEventNumber |
+            MouseClickState |
+            MousePressure |
+            MouseButtonNumber |
+            MouseDeltaX |
+            MouseDeltaY |
+            MouseInstantMouser |
+            MouseSubtype |
+            MouseWindowUnderPointer |
+            MouseWindowUnderPointerThatCanHandle |
+            UnacceleratedPointerMovementX |
+            UnacceleratedPointerMovementY => EventFieldKind::Mouse,
+
+            KeyboardAutorepeat |
+            KeyboardKeycode |
+            KeyboardType => EventFieldKind::Keyboard,
+
+            ScrollDeltaAxis1 |
+            ScrollDeltaAxis2 |
+            ScrollDeltaAxis3 |
+            ScrollInstantMouser |
+            ScrollIsContinuous |
+            ScrollFixedPtDeltaAxis1 |
+            ScrollFixedPtDeltaAxis2 |
+            ScrollFixedPtDeltaAxis3 |
+            ScrollPointDeltaAxis1 |
+            ScrollPointDeltaAxis2 |
+            ScrollPointDeltaAxis3 |
+            ScrollPhase |
+            ScrollCount |
+            ScrollMomentumPhase => EventFieldKind::ScrollWheel,
+
+            TabletPointX |
+            TabletPointY |
+            TabletPointZ |
+            TabletPointButtons |
+            TabletPressure |
+            TabletTiltX |
+            TabletTiltY |
+            TabletRotation |
+            TabletTangentialPressure |
+            TabletDeviceId |
+            TabletVendor1 |
+            TabletVendor2 |
+            TabletVendor3 => EventFieldKind::TabletPointer,
+
+            ProximityVendorId |
+            ProximityTabletId |
+            ProximityPointerId |
+            ProximityDeviceId |
+            ProximitySystemTabletId |
+            ProximityVendorPointerType |
+            ProximityVendorPointerSerialNumber |
+            ProximityVendorUniqueId |
+            ProximityCapabilityMask |
+            ProximityPointerType |
+            ProximityEnterProximity => EventFieldKind::TabletProximity,
+
+            TargetProcessSerialNumber |
+            TargetUnixProcessId |
+            SourceUnixProcessId |
+            SourceUserData |
+            SourceUserId |
+            SourceGroupId |
+            SourceStateId => EventFieldKind::Any,
+        }
+    }
+
+    /// Returns whether the field holds a floating point value, and should
+    /// therefore be accessed with
+    /// [`Event::double_field`](struct.Event.html#method.double_field).
+    pub fn is_double(self) -> bool {
+        use self::EventField::*;
+        matches!(self,
+            MousePressure |
+            ScrollFixedPtDeltaAxis1 |
+            ScrollFixedPtDeltaAxis2 |
+            ScrollFixedPtDeltaAxis3 |
+            ScrollPointDeltaAxis1 |
+            ScrollPointDeltaAxis2 |
+            ScrollPointDeltaAxis3 |
+            TabletPressure |
+            TabletTiltX |
+            TabletTiltY |
+            TabletRotation |
+            TabletTangentialPressure)
+    }
+
+    /// Returns whether the field is meaningful for `event`.
+    #[inline]
+    pub fn is_valid_for(self, event: &super::Event) -> bool {
+        self.applies_to(event.event_type())
+    }
+
+    pub(crate) fn applies_to(self, ty: EventType) -> bool {
+        use super::EventType::*;
+
+        let mouse = matches!(ty,
+            LeftMouseDown | LeftMouseUp |
+            RightMouseDown | RightMouseUp |
+            OtherMouseDown | OtherMouseUp |
+            MouseMoved |
+            LeftMouseDragged | RightMouseDragged | OtherMouseDragged |
+            Pressure);
+
+        match self.kind() {
+            EventFieldKind::Any => true,
+            EventFieldKind::Mouse => mouse,
+            EventFieldKind::Keyboard => matches!(ty, KeyDown | KeyUp | FlagsChanged),
+            EventFieldKind::ScrollWheel => matches!(ty, ScrollWheel),
+            EventFieldKind::TabletPointer => match ty {
+                TabletPointer => true,
+                _ => mouse,
+            },
+            EventFieldKind::TabletProximity => match ty {
+                TabletProximity => true,
+                _ => mouse,
+            },
+        }
+    }
+}
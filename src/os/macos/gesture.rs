@@ -0,0 +1,154 @@
+//! 🤌 Trackpad gesture automation utilities.
+//!
+//! macOS has no public API for creating gesture events, so these are built
+//! from undocumented fields of Quartz events, matching the events that the
+//! window server creates for trackpads. They may stop working in a future
+//! version of macOS. Two-finger scrolling does not need them; use
+//! [continuous](../wheel/struct.Event.html#method.set_continuous) scroll
+//! wheel events with a phase instead.
+//!
+//! Gestures go to the window under the mouse cursor.
+//!
+//! # Examples
+//!
+//! Zoom in by half:
+//!
+//! ```no_run
+//! use auto::os::EventLocation;
+//! use auto::os::gesture::Event;
+//! use auto::os::wheel::ScrollPhase;
+//!
+//! let steps = [(ScrollPhase::Began, 0.0), (ScrollPhase::Changed, 0.5), (ScrollPhase::Ended, 0.0)];
+//! for &(phase, magnification) in &steps {
+//!     Event::magnify(phase, magnification).unwrap().post(EventLocation::Hid);
+//! }
+//! ```
+
+use std::os::raw;
+use std::ptr;
+
+use super::{CGEvent, CGEventSource};
+use super::wheel::ScrollPhase;
+use error::Result;
+
+extern {
+    fn CGEventCreate(source: CGEventSource) -> Option<CGEvent>;
+}
+
+/// The field holding the type of the event, which may be a type that
+/// `CGEventType` does not list.
+const TYPE_FIELD: raw::c_uint = 55;
+
+/// The event type of gestures.
+const GESTURE_EVENT_TYPE: i64 = 29;
+
+/// The field holding the `IOHIDEventType` of the gesture.
+const HID_TYPE_FIELD: raw::c_uint = 110;
+
+/// The field holding the magnification of a zoom gesture.
+const ZOOM_VALUE_FIELD: raw::c_uint = 113;
+
+/// The field holding the rotation of a rotation gesture, in degrees.
+const ROTATION_VALUE_FIELD: raw::c_uint = 114;
+
+/// The field holding the direction of a swipe gesture.
+const SWIPE_MOTION_FIELD: raw::c_uint = 123;
+
+/// The field holding the progress of a swipe gesture.
+const SWIPE_PROGRESS_FIELD: raw::c_uint = 124;
+
+/// The field holding the phase of the gesture.
+const PHASE_FIELD: raw::c_uint = 132;
+
+/// `kIOHIDEventTypeRotation`
+const HID_ROTATION: i64 = 5;
+
+/// `kIOHIDEventTypeZoom`
+const HID_ZOOM: i64 = 8;
+
+/// `kIOHIDEventTypeNavigationSwipe`
+const HID_NAVIGATION_SWIPE: i64 = 16;
+
+/// `kIOHIDEventTypeZoomToggle`
+const HID_ZOOM_TOGGLE: i64 = 22;
+
+/// The direction of a [swipe](struct.Event.html#method.swipe).
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum SwipeDirection {
+    /// The fingers moved up.
+    Up = 1,
+    /// The fingers moved down.
+    Down = 2,
+    /// The fingers moved left.
+    Left = 4,
+    /// The fingers moved right.
+    Right = 8,
+}
+
+declare_event!("A trackpad gesture event that can be posted into the Quartz event stream.");
+
+/// Returns the phase and magnification of `event` if it is a step of a
+/// pinch, such as one observed by an [event tap](../tap/index.html) from a
+/// trackpad.
+///
+/// The magnification is the change in scale since the previous step, as
+/// taken by [`Event::magnify`](struct.Event.html#method.magnify).
+pub fn magnification(event: &super::Event) -> Option<(ScrollPhase, f64)> {
+    if event.raw_integer_field(TYPE_FIELD) != GESTURE_EVENT_TYPE
+        || event.raw_integer_field(HID_TYPE_FIELD) != HID_ZOOM
+    {
+        return None;
+    }
+    let phase = ScrollPhase::from_raw(event.raw_integer_field(PHASE_FIELD))?;
+    Some((phase, event.raw_double_field(ZOOM_VALUE_FIELD)))
+}
+
+impl Event {
+    fn create(hid_type: i64, phase: Option<ScrollPhase>) -> Result<Event> {
+        let mut event = super::created(unsafe { CGEventCreate(ptr::null()) })?;
+        event.set_raw_integer_field(TYPE_FIELD, GESTURE_EVENT_TYPE);
+        event.set_raw_integer_field(HID_TYPE_FIELD, hid_type);
+        if let Some(phase) = phase {
+            event.set_raw_integer_field(PHASE_FIELD, phase as i64);
+        }
+        Ok(Event(event))
+    }
+
+    /// Creates a step of a pinch, where `magnification` is the change in
+    /// scale since the previous step, such as `0.1` to zoom in by a tenth.
+    ///
+    /// A pinch is a `Began` step, any number of `Changed` steps, and an
+    /// `Ended` step.
+    pub fn magnify(phase: ScrollPhase, magnification: f64) -> Result<Event> {
+        let mut event = Event::create(HID_ZOOM, Some(phase))?;
+        event.set_raw_double_field(ZOOM_VALUE_FIELD, magnification);
+        Ok(event)
+    }
+
+    /// Creates a step of a rotation, where `degrees` is the change in angle
+    /// since the previous step and positive values turn counterclockwise.
+    ///
+    /// A rotation is a `Began` step, any number of `Changed` steps, and an
+    /// `Ended` step.
+    pub fn rotate(phase: ScrollPhase, degrees: f64) -> Result<Event> {
+        let mut event = Event::create(HID_ROTATION, Some(phase))?;
+        event.set_raw_double_field(ROTATION_VALUE_FIELD, degrees);
+        Ok(event)
+    }
+
+    /// Creates a completed three-finger swipe in `direction`, which
+    /// applications such as Safari use to navigate back and forward.
+    pub fn swipe(direction: SwipeDirection) -> Result<Event> {
+        let mut event = Event::create(HID_NAVIGATION_SWIPE, Some(ScrollPhase::Ended))?;
+        event.set_raw_integer_field(SWIPE_MOTION_FIELD, direction as i64);
+        event.set_raw_double_field(SWIPE_PROGRESS_FIELD, 1.0);
+        Ok(event)
+    }
+
+    /// Creates a two-finger double tap, which applications use to toggle
+    /// smart zoom.
+    #[inline]
+    pub fn smart_magnify() -> Result<Event> {
+        Event::create(HID_ZOOM_TOGGLE, None)
+    }
+}
@@ -0,0 +1,374 @@
+//! 🎛️ Virtual HID devices.
+//!
+//! Quartz events enter the system above the HID layer, and some applications
+//! tell them apart from real input, such as games that read devices directly.
+//! The devices created here are seen by the system as USB keyboards and mice,
+//! so their input arrives the same way as input from hardware.
+//!
+//! Creating a virtual device requires the
+//! `com.apple.developer.hid.virtual.device` entitlement. Input sent right
+//! after creation may be lost while the system attaches the device.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use auto::os::hid::{self, VirtualKeyboard};
+//!
+//! let mut keyboard = VirtualKeyboard::new().unwrap();
+//! keyboard.type_text("Hello from hardware\n").unwrap();
+//!
+//! keyboard.press(hid::LEFT_GUI).unwrap();
+//! keyboard.tap(hid::usage_for_char('s').unwrap().0).unwrap();
+//! keyboard.release(hid::LEFT_GUI).unwrap();
+//! ```
+
+use std::os::raw::c_void;
+use std::ptr;
+
+use super::{cf, CFObject, CFObjectRef, EventFlags};
+use super::keyboard::{self, key_code};
+use super::mouse::Button;
+use error::{Error, OsError, Result};
+
+#[link(name = "IOKit", kind = "framework")]
+extern {
+    fn IOHIDUserDeviceCreate(allocator: *const c_void, properties: CFObjectRef) -> Option<CFObject>;
+
+    fn IOHIDUserDeviceHandleReport(device: CFObjectRef, report: *const u8, len: isize) -> i32;
+}
+
+/// The report descriptor of a boot keyboard: a byte of modifiers, a reserved
+/// byte, and six bytes of pressed keys.
+const KEYBOARD_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01, 0x09, 0x06, 0xA1, 0x01,
+    0x05, 0x07, 0x19, 0xE0, 0x29, 0xE7, 0x15, 0x00, 0x25, 0x01,
+    0x75, 0x01, 0x95, 0x08, 0x81, 0x02,
+    0x95, 0x01, 0x75, 0x08, 0x81, 0x01,
+    0x95, 0x06, 0x75, 0x08, 0x15, 0x00, 0x26, 0xFF, 0x00,
+    0x05, 0x07, 0x19, 0x00, 0x2A, 0xFF, 0x00, 0x81, 0x00,
+    0xC0,
+];
+
+/// The report descriptor of a mouse: a byte of five buttons, then relative
+/// motion along X and Y and of the wheel as signed bytes.
+const MOUSE_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01, 0x09, 0x02, 0xA1, 0x01, 0x09, 0x01, 0xA1, 0x00,
+    0x05, 0x09, 0x19, 0x01, 0x29, 0x05, 0x15, 0x00, 0x25, 0x01,
+    0x95, 0x05, 0x75, 0x01, 0x81, 0x02,
+    0x95, 0x01, 0x75, 0x03, 0x81, 0x01,
+    0x05, 0x01, 0x09, 0x30, 0x09, 0x31, 0x09, 0x38, 0x15, 0x81, 0x25, 0x7F,
+    0x75, 0x08, 0x95, 0x03, 0x81, 0x06,
+    0xC0, 0xC0,
+];
+
+/// The most keys besides modifiers that a boot keyboard reports at once.
+const MAX_KEYS: usize = 6;
+
+/// The number of buttons of a [`VirtualMouse`](struct.VirtualMouse.html).
+const MOUSE_BUTTONS: u8 = 5;
+
+/// The usage of the left Control key.
+pub const LEFT_CONTROL: u8 = 0xE0;
+/// The usage of the left Shift key.
+pub const LEFT_SHIFT: u8 = 0xE1;
+/// The usage of the left Option key.
+pub const LEFT_ALT: u8 = 0xE2;
+/// The usage of the left Command key.
+pub const LEFT_GUI: u8 = 0xE3;
+/// The usage of the right Control key.
+pub const RIGHT_CONTROL: u8 = 0xE4;
+/// The usage of the right Shift key.
+pub const RIGHT_SHIFT: u8 = 0xE5;
+/// The usage of the right Option key.
+pub const RIGHT_ALT: u8 = 0xE6;
+/// The usage of the right Command key.
+pub const RIGHT_GUI: u8 = 0xE7;
+
+/// The usages of the keys with the virtual key codes 0 through 50, which
+/// the system translates through the current keyboard layout.
+const USAGES: [u8; 51] = [
+    0x04, 0x16, 0x07, 0x09, 0x0B, 0x0A, 0x1D, 0x1B, 0x06, 0x19,
+    0x64, 0x05, 0x14, 0x1A, 0x08, 0x15, 0x1C, 0x17, 0x1E, 0x1F,
+    0x20, 0x21, 0x23, 0x22, 0x2E, 0x26, 0x24, 0x2D, 0x25, 0x27,
+    0x30, 0x12, 0x18, 0x2F, 0x0C, 0x13, 0x28, 0x0F, 0x0D, 0x34,
+    0x0E, 0x33, 0x31, 0x36, 0x38, 0x11, 0x10, 0x37, 0x2B, 0x2C,
+    0x35,
+];
+
+/// Returns the usage of the key with the virtual key code `key`, if it types
+/// characters.
+fn usage_for_key(key: u16) -> Option<u8> {
+    match key {
+        key_code::JIS_YEN => Some(0x89),
+        key_code::JIS_UNDERSCORE => Some(0x87),
+        _ => USAGES.get(key as usize).cloned(),
+    }
+}
+
+/// Returns the usage of the key that types `c` in the current keyboard
+/// layout, and the usages of the modifiers that must be held.
+///
+/// Characters that need dead keys or are only on the keypad have no usage.
+pub fn usage_for_char(c: char) -> Option<(u8, &'static [u8])> {
+    // Return types a carriage return
+    let c = if c == '\n' { '\r' } else { c };
+    let (key, flags) = keyboard::key_for_char(c)?;
+    let shift = flags.contains(EventFlags::SHIFT);
+    let modifiers: &[u8] = match (shift, flags.contains(EventFlags::ALTERNATE)) {
+        (false, false) => &[],
+        (true, false) => &[LEFT_SHIFT],
+        (false, true) => &[LEFT_ALT],
+        (true, true) => &[LEFT_SHIFT, LEFT_ALT],
+    };
+    Some((usage_for_key(key)?, modifiers))
+}
+
+/// A virtual HID device, which is removed from the system when dropped.
+#[derive(Debug)]
+pub struct VirtualDevice(CFObject);
+
+impl VirtualDevice {
+    /// Creates a device named `product` that sends reports in the format of
+    /// the HID report `descriptor`.
+    ///
+    /// Returns [`Error::PermissionDenied`](../../error/enum.Error.html#variant.PermissionDenied)
+    /// if the process lacks the entitlement to create virtual devices.
+    pub fn new(descriptor: &[u8], product: &str) -> Result<VirtualDevice> {
+        let keys = [
+            cf::string("ReportDescriptor"),
+            cf::string("Product"),
+            cf::string("Transport"),
+            cf::string("VendorID"),
+            cf::string("ProductID"),
+        ];
+        let values = [
+            cf::data(descriptor),
+            cf::string(product),
+            cf::string("Virtual"),
+            cf::number(0),
+            cf::number(0),
+        ];
+        let pairs: Vec<_> = keys.iter().zip(&values).map(|(key, value)| (key.0, value.0)).collect();
+        let properties = cf::dictionary(&pairs);
+        unsafe { IOHIDUserDeviceCreate(ptr::null(), properties.0) }
+            .map(VirtualDevice)
+            .ok_or(Error::PermissionDenied)
+    }
+
+    /// Sends `report` from the device as input.
+    pub fn send_report(&self, report: &[u8]) -> Result<()> {
+        let err = unsafe {
+            IOHIDUserDeviceHandleReport((self.0).0, report.as_ptr(), report.len() as isize)
+        };
+        match err {
+            0 => Ok(()),
+            _ => Err(OsError::from_code(err).into()),
+        }
+    }
+}
+
+/// A virtual keyboard that presses keys by their HID usage, from the keyboard
+/// usage page.
+#[derive(Debug)]
+pub struct VirtualKeyboard {
+    device: VirtualDevice,
+    modifiers: u8,
+    keys: Vec<u8>,
+}
+
+impl VirtualKeyboard {
+    /// Creates a virtual keyboard.
+    pub fn new() -> Result<VirtualKeyboard> {
+        Ok(VirtualKeyboard {
+            device: VirtualDevice::new(KEYBOARD_DESCRIPTOR, "Auto Virtual Keyboard")?,
+            modifiers: 0,
+            keys: Vec::with_capacity(MAX_KEYS),
+        })
+    }
+
+    fn send(&self) -> Result<()> {
+        let mut report = [0; 2 + MAX_KEYS];
+        report[0] = self.modifiers;
+        report[2..2 + self.keys.len()].copy_from_slice(&self.keys);
+        self.device.send_report(&report)
+    }
+
+    /// Presses the key with `usage` and keeps it held.
+    ///
+    /// Returns [`Error::Unsupported`](../../error/enum.Error.html#variant.Unsupported)
+    /// if six keys other than modifiers are already held.
+    pub fn press(&mut self, usage: u8) -> Result<()> {
+        if (LEFT_CONTROL..=RIGHT_GUI).contains(&usage) {
+            self.modifiers |= 1 << (usage - LEFT_CONTROL);
+        } else if !self.keys.contains(&usage) {
+            if self.keys.len() == MAX_KEYS {
+                return Err(Error::Unsupported);
+            }
+            self.keys.push(usage);
+        }
+        self.send()
+    }
+
+    /// Releases the key with `usage`.
+    pub fn release(&mut self, usage: u8) -> Result<()> {
+        if (LEFT_CONTROL..=RIGHT_GUI).contains(&usage) {
+            self.modifiers &= !(1 << (usage - LEFT_CONTROL));
+        } else {
+            self.keys.retain(|&key| key != usage);
+        }
+        self.send()
+    }
+
+    /// Presses and releases the key with `usage`.
+    pub fn tap(&mut self, usage: u8) -> Result<()> {
+        self.press(usage)?;
+        self.release(usage)
+    }
+
+    /// Releases every held key.
+    pub fn release_all(&mut self) -> Result<()> {
+        self.modifiers = 0;
+        self.keys.clear();
+        self.send()
+    }
+
+    /// Types `text` by pressing the keys that produce it in the current
+    /// keyboard layout.
+    ///
+    /// Modifiers held for a character are released even if typing it fails.
+    ///
+    /// Returns [`Error::Unsupported`](../../error/enum.Error.html#variant.Unsupported)
+    /// before typing anything if `text` has a character without a key.
+    pub fn type_text(&mut self, text: &str) -> Result<()> {
+        let keys = text.chars()
+            .map(usage_for_char)
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Error::Unsupported)?;
+        for (usage, modifiers) in keys {
+            let mut result = modifiers.iter()
+                .try_for_each(|&modifier| self.press(modifier))
+                .and_then(|()| self.tap(usage));
+            for &modifier in modifiers.iter().rev() {
+                let released = self.release(modifier);
+                result = result.and(released);
+            }
+            result?;
+        }
+        Ok(())
+    }
+}
+
+/// A virtual mouse with five buttons and a scroll wheel.
+///
+/// Like a real mouse, it reports relative motion, which the system scales by
+/// the pointer speed and acceleration settings.
+#[derive(Debug)]
+pub struct VirtualMouse {
+    device: VirtualDevice,
+    buttons: u8,
+}
+
+impl VirtualMouse {
+    /// Creates a virtual mouse.
+    pub fn new() -> Result<VirtualMouse> {
+        Ok(VirtualMouse {
+            device: VirtualDevice::new(MOUSE_DESCRIPTOR, "Auto Virtual Mouse")?,
+            buttons: 0,
+        })
+    }
+
+    fn send(&self, dx: i8, dy: i8, wheel: i8) -> Result<()> {
+        self.device.send_report(&[self.buttons, dx as u8, dy as u8, wheel as u8])
+    }
+
+    /// Moves the pointer by `dx` and `dy` counts, split into as many reports
+    /// as needed.
+    pub fn move_by(&mut self, mut dx: i32, mut dy: i32) -> Result<()> {
+        while dx != 0 || dy != 0 {
+            let step_x = dx.clamp(-127, 127);
+            let step_y = dy.clamp(-127, 127);
+            self.send(step_x as i8, step_y as i8, 0)?;
+            dx -= step_x;
+            dy -= step_y;
+        }
+        Ok(())
+    }
+
+    /// Scrolls the wheel by `lines`, where positive values scroll up.
+    pub fn scroll(&mut self, mut lines: i32) -> Result<()> {
+        while lines != 0 {
+            let step = lines.clamp(-127, 127);
+            self.send(0, 0, step as i8)?;
+            lines -= step;
+        }
+        Ok(())
+    }
+
+    fn button_bit(button: Button) -> Result<u8> {
+        match button.number() {
+            n if n < MOUSE_BUTTONS => Ok(1 << n),
+            _ => Err(Error::Unsupported),
+        }
+    }
+
+    /// Presses `button` and keeps it held.
+    ///
+    /// Returns [`Error::Unsupported`](../../error/enum.Error.html#variant.Unsupported)
+    /// for buttons beyond the fifth.
+    pub fn press(&mut self, button: Button) -> Result<()> {
+        self.buttons |= VirtualMouse::button_bit(button)?;
+        self.send(0, 0, 0)
+    }
+
+    /// Releases `button`.
+    pub fn release(&mut self, button: Button) -> Result<()> {
+        self.buttons &= !VirtualMouse::button_bit(button)?;
+        self.send(0, 0, 0)
+    }
+
+    /// Presses and releases `button`.
+    pub fn click(&mut self, button: Button) -> Result<()> {
+        self.press(button)?;
+        self.release(button)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use os::keyboard::key_code::*;
+
+    #[test]
+    fn key_usages() {
+        let letters = [
+            ANSI_ALPHA_A, ANSI_ALPHA_B, ANSI_ALPHA_C, ANSI_ALPHA_D, ANSI_ALPHA_E, ANSI_ALPHA_F,
+            ANSI_ALPHA_G, ANSI_ALPHA_H, ANSI_ALPHA_I, ANSI_ALPHA_J, ANSI_ALPHA_K, ANSI_ALPHA_L,
+            ANSI_ALPHA_M, ANSI_ALPHA_N, ANSI_ALPHA_O, ANSI_ALPHA_P, ANSI_ALPHA_Q, ANSI_ALPHA_R,
+            ANSI_ALPHA_S, ANSI_ALPHA_T, ANSI_ALPHA_U, ANSI_ALPHA_V, ANSI_ALPHA_W, ANSI_ALPHA_X,
+            ANSI_ALPHA_Y, ANSI_ALPHA_Z,
+        ];
+        for (i, &key) in letters.iter().enumerate() {
+            assert_eq!(usage_for_key(key), Some(0x04 + i as u8));
+        }
+        let digits = [ANSI_1, ANSI_2, ANSI_3, ANSI_4, ANSI_5, ANSI_6, ANSI_7, ANSI_8, ANSI_9, ANSI_0];
+        for (i, &key) in digits.iter().enumerate() {
+            assert_eq!(usage_for_key(key), Some(0x1E + i as u8));
+        }
+        assert_eq!(usage_for_key(RETURN), Some(0x28));
+        assert_eq!(usage_for_key(ANSI_GRAVE), Some(0x35));
+        assert_eq!(usage_for_key(JIS_YEN), Some(0x89));
+        assert_eq!(usage_for_key(ANSI_KEYPAD_0), None);
+    }
+
+    #[test]
+    fn char_usages() {
+        // Whatever the layout, characters map to the key that types them
+        for c in "aZ1!\n ".chars() {
+            let translated = if c == '\n' { '\r' } else { c };
+            if let Some((key, _)) = keyboard::key_for_char(translated) {
+                assert_eq!(usage_for_char(c).map(|(usage, _)| usage), usage_for_key(key));
+            }
+        }
+        assert_eq!(usage_for_char('\u{1F600}'), None);
+    }
+}
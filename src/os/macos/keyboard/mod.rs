@@ -1,8 +1,14 @@
 //! ⌨️ Keyboard automation utilities.
 
+use std::os::raw::{c_char, c_int};
 use std::ptr;
 
-use super::{CGEvent, CGEventSource};
+use objc::rc::autoreleasepool;
+use objc::runtime::Object;
+
+use super::{cf, CFObject, CFObjectRef, CGEvent, CGEventSource, CGPoint, EventFlags, NS_EVENT};
+use super::source::EventSource;
+use error::{Error, OsError, Result};
 
 pub mod key_code;
 
@@ -11,19 +17,325 @@ extern {
         source: CGEventSource,
         virtual_key: CGKeyCode,
         key_down: bool,
-    ) -> CGEvent;
+    ) -> Option<CGEvent>;
+
+    fn CGEventKeyboardSetUnicodeString(
+        event: CFObjectRef,
+        length: usize,
+        string: *const u16,
+    );
+
+    fn CFDataGetBytePtr(data: CFObjectRef) -> *const u8;
 }
 
+#[link(name = "Carbon", kind = "framework")]
+extern {
+    fn TISCopyCurrentKeyboardInputSource() -> Option<CFObject>;
+
+    fn TISCopyCurrentKeyboardLayoutInputSource() -> Option<CFObject>;
+
+    fn TISGetInputSourceProperty(
+        source: CFObjectRef,
+        key: CFObjectRef,
+    ) -> Option<CFObjectRef>;
+
+    fn LMGetKbdType() -> u8;
+
+    fn UCKeyTranslate(
+        key_layout: *const u8,
+        virtual_key_code: u16,
+        key_action: u16,
+        modifier_key_state: u32,
+        keyboard_type: u32,
+        key_translate_options: u32,
+        dead_key_state: *mut u32,
+        max_string_length: usize,
+        actual_string_length: *mut usize,
+        unicode_string: *mut u16,
+    ) -> i32;
+
+    static kTISPropertyUnicodeKeyLayoutData: CFObjectRef;
+}
+
+#[link(name = "IOKit", kind = "framework")]
+extern {
+    fn IOServiceMatching(name: *const c_char) -> CFObjectRef;
+
+    fn IOServiceGetMatchingService(main_port: u32, matching: CFObjectRef) -> u32;
+
+    fn IOServiceOpen(service: u32, owning_task: u32, kind: u32, connect: *mut u32) -> i32;
+
+    fn IOServiceClose(connect: u32) -> i32;
+
+    fn IOObjectRelease(object: u32) -> i32;
+
+    fn IOHIDGetModifierLockState(handle: u32, selector: c_int, state: *mut bool) -> i32;
+
+    fn IOHIDSetModifierLockState(handle: u32, selector: c_int, state: bool) -> i32;
+
+    static mach_task_self_: u32;
+}
+
+/// `kIOHIDParamConnectType`
+const HID_PARAM_CONNECT_TYPE: u32 = 1;
+
+/// `kIOReturnUnsupported`
+const IO_RETURN_UNSUPPORTED: i32 = 0xE000_02C7_u32 as i32;
+
 type CGKeyCode = u16;
 
+/// `NSEventTypeSystemDefined`
+const NS_EVENT_TYPE_SYSTEM_DEFINED: u64 = 14;
+
+/// `NX_SUBTYPE_AUX_CONTROL_BUTTONS`
+const AUX_CONTROL_BUTTONS: i16 = 8;
+
+const UC_KEY_ACTION_DOWN: u16 = 0;
+const UC_KEY_TRANSLATE_NO_DEAD_KEYS: u32 = 1 << 0;
+
+// Carbon modifier key states, shifted right by 8 as `UCKeyTranslate` expects
+const SHIFT_KEY_STATE: u32 = 1 << 1;
+const OPTION_KEY_STATE: u32 = 1 << 3;
+
+/// Returns the virtual key and modifier flags that type `c` in the current
+/// keyboard layout, or `None` if no single key press produces it.
+///
+/// Unlike the `ANSI_` constants in [`key_code`](key_code/index.html), this
+/// accounts for the user's keyboard layout. Combinations requiring dead keys
+/// are not considered.
+///
+/// # Examples
+///
+/// ```
+/// use auto::os::keyboard::{self, Event};
+///
+/// if let Some((key, flags)) = keyboard::key_for_char('@') {
+///     let mut event = Event::new(key, true).unwrap();
+///     event.set_flags(flags);
+///     # return;
+///     event.post(auto::os::EventLocation::Session);
+/// }
+/// ```
+pub fn key_for_char(c: char) -> Option<(u16, EventFlags)> {
+    let mut target = [0u16; 2];
+    let target = c.encode_utf16(&mut target);
+
+    let source = unsafe {
+        // Input methods (e.g. for CJK languages) may not provide layout data,
+        // in which case the underlying keyboard layout is used instead
+        let current = TISCopyCurrentKeyboardInputSource()?;
+        if layout_data(&current).is_some() {
+            current
+        } else {
+            TISCopyCurrentKeyboardLayoutInputSource()?
+        }
+    };
+    let layout = layout_data(&source)?;
+    let kbd_type = unsafe { LMGetKbdType() } as u32;
+
+    let modifiers = [
+        (0, EventFlags::empty()),
+        (SHIFT_KEY_STATE, EventFlags::SHIFT),
+        (OPTION_KEY_STATE, EventFlags::ALTERNATE),
+        (SHIFT_KEY_STATE | OPTION_KEY_STATE, EventFlags::SHIFT | EventFlags::ALTERNATE),
+    ];
+
+    for &(state, flags) in &modifiers {
+        for key in 0..128u16 {
+            let mut dead_key_state = 0u32;
+            let mut buf = [0u16; 4];
+            let mut len = 0usize;
+            let status = unsafe {
+                UCKeyTranslate(
+                    layout,
+                    key,
+                    UC_KEY_ACTION_DOWN,
+                    state,
+                    kbd_type,
+                    UC_KEY_TRANSLATE_NO_DEAD_KEYS,
+                    &mut dead_key_state,
+                    buf.len(),
+                    &mut len,
+                    buf.as_mut_ptr(),
+                )
+            };
+            if status == 0 && buf[..len] == *target {
+                return Some((key, flags));
+            }
+        }
+    }
+    None
+}
+
+/// Returns the time in seconds a key is held before it starts repeating, as
+/// set by the user.
+pub fn repeat_delay() -> Result<f64> {
+    let ns_event = super::class(&NS_EVENT)?;
+    unsafe { Ok(msg_send![ns_event, keyRepeatDelay]) }
+}
+
+/// Returns the time in seconds between repeats of a held key, as set by the
+/// user.
+pub fn repeat_interval() -> Result<f64> {
+    let ns_event = super::class(&NS_EVENT)?;
+    unsafe { Ok(msg_send![ns_event, keyRepeatInterval]) }
+}
+
+fn layout_data(source: &CFObject) -> Option<*const u8> {
+    unsafe {
+        let key = kTISPropertyUnicodeKeyLayoutData;
+        let data = TISGetInputSourceProperty(source.0, key)?;
+        let ptr = CFDataGetBytePtr(data);
+        if ptr.is_null() { None } else { Some(ptr) }
+    }
+}
+
 declare_event!("A keyboard event that can be posted into the Quartz event stream.");
 
 impl Event {
     /// Creates a new event for the virtual key.
     #[inline]
-    pub fn new(key: u16, down: bool) -> Event {
-        Event(super::Event(unsafe {
+    pub fn new(key: u16, down: bool) -> Result<Event> {
+        super::created(unsafe {
             CGEventCreateKeyboardEvent(ptr::null(), key, down)
-        }))
+        }).map(Event)
     }
+
+    /// Creates a new event for the virtual key from `source`.
+    #[inline]
+    pub fn new_with_source(source: &EventSource, key: u16, down: bool) -> Result<Event> {
+        super::created(unsafe {
+            CGEventCreateKeyboardEvent(source.as_raw(), key, down)
+        }).map(Event)
+    }
+
+    /// Sets the text that the event types, which applications use instead of
+    /// translating the virtual key.
+    pub fn set_unicode_string(&mut self, string: &str) {
+        let units: Vec<u16> = string.encode_utf16().collect();
+        unsafe {
+            CGEventKeyboardSetUnicodeString(((self.0).0).0, units.len(), units.as_ptr());
+        }
+    }
+}
+
+/// A media or system key that is sent as a system-defined event rather than
+/// a keyboard event, identified by its `NX_KEYTYPE` value.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum AuxKey {
+    /// The volume up key.
+    SoundUp = 0,
+    /// The volume down key.
+    SoundDown = 1,
+    /// The brightness up key.
+    BrightnessUp = 2,
+    /// The brightness down key.
+    BrightnessDown = 3,
+    /// The mute key.
+    Mute = 7,
+    /// The play/pause key.
+    Play = 16,
+    /// The next track key.
+    Next = 17,
+    /// The previous track key.
+    Previous = 18,
+    /// The fast-forward key.
+    Fast = 19,
+    /// The rewind key.
+    Rewind = 20,
+}
+
+/// Creates a system-defined event that presses or releases `key`.
+///
+/// These events can only be created through `NSEvent`, so the returned event
+/// is a copy of the one that `NSEvent` creates.
+pub fn aux_key_event(key: AuxKey, down: bool) -> Result<super::Event> {
+    let ns_event = super::class(&NS_EVENT)?;
+    let state: isize = if down { 0xA } else { 0xB };
+    let data1 = (key as isize) << 16 | state << 8;
+    autoreleasepool(|| unsafe {
+        let event: *mut Object = msg_send![
+            ns_event,
+            otherEventWithType: NS_EVENT_TYPE_SYSTEM_DEFINED
+            location: CGPoint { x: 0.0, y: 0.0 }
+            modifierFlags: (state as u64) << 8
+            timestamp: 0.0f64
+            windowNumber: 0isize
+            context: ptr::null_mut::<Object>()
+            subtype: AUX_CONTROL_BUTTONS
+            data1: data1
+            data2: -1isize
+        ];
+        if event.is_null() {
+            return Err(Error::Failed);
+        }
+        let cg_event: Option<CFObjectRef> = msg_send![event, CGEvent];
+        cg_event.map(|cg_event| super::Event(cf::retain(cg_event))).ok_or(Error::Failed)
+    })
+}
+
+/// A lock that a key toggles, identified by its `kIOHIDCapsLockState`-style
+/// selector.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum ModifierLock {
+    /// The lock toggled by the Caps Lock key.
+    CapsLock = 1,
+    /// The lock toggled by the Num Lock key, which only some keyboards have.
+    NumLock = 2,
+}
+
+/// A connection to the HID system, closed when dropped.
+struct HidSystem(u32);
+
+impl Drop for HidSystem {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { IOServiceClose(self.0) };
+    }
+}
+
+impl HidSystem {
+    fn open() -> Result<HidSystem> {
+        unsafe {
+            let matching = IOServiceMatching(b"IOHIDSystem\0".as_ptr() as *const c_char);
+            // The matching dictionary is consumed by the lookup
+            let service = IOServiceGetMatchingService(0, matching);
+            if service == 0 {
+                return Err(Error::Unsupported);
+            }
+            let mut connect = 0;
+            let err = IOServiceOpen(service, mach_task_self_, HID_PARAM_CONNECT_TYPE, &mut connect);
+            IOObjectRelease(service);
+            match err {
+                0 => Ok(HidSystem(connect)),
+                _ => Err(OsError::from_code(err).into()),
+            }
+        }
+    }
+}
+
+/// Converts an `IOReturn` into a `Result`.
+fn io_result(err: i32) -> Result<()> {
+    match err {
+        0 => Ok(()),
+        IO_RETURN_UNSUPPORTED => Err(Error::Unsupported),
+        _ => Err(OsError::from_code(err).into()),
+    }
+}
+
+/// Returns whether `lock` is on.
+pub fn modifier_lock_state(lock: ModifierLock) -> Result<bool> {
+    let system = HidSystem::open()?;
+    let mut state = false;
+    io_result(unsafe { IOHIDGetModifierLockState(system.0, lock as c_int, &mut state) })?;
+    Ok(state)
+}
+
+/// Turns `lock` on or off, along with the light on the keyboard for it.
+///
+/// Unlike posting a key event for the key, this sets the lock directly, so
+/// it cannot toggle the lock the wrong way.
+pub fn set_modifier_lock_state(lock: ModifierLock, state: bool) -> Result<()> {
+    let system = HidSystem::open()?;
+    io_result(unsafe { IOHIDSetModifierLockState(system.0, lock as c_int, state) })
 }
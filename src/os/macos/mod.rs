@@ -5,34 +5,91 @@ use std::fmt;
 use std::ffi::CStr;
 use std::os::raw;
 use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use objc::runtime::{Class, Object};
 use objc::{Encode, Encoding};
 
+use error::{Error, Result};
+use self::app::Pid;
+
 #[link(name = "Cocoa", kind = "framework")]
 extern {
     fn CFRelease(_: CFObjectRef);
 
     fn CGEventPost(tap_location: raw::c_int, event: CFObjectRef);
 
+    fn CGEventPostToPid(pid: Pid, event: CFObjectRef);
+
     fn CGEventCreateCopy(event: CFObjectRef) -> CFObject;
 
     fn CGEventGetFlags(event: CFObjectRef) -> EventFlags;
 
     fn CGEventSetFlags(event: CFObjectRef, flags: EventFlags);
+
+    fn CGEventGetType(event: CFObjectRef) -> u32;
+
+    fn CGEventGetIntegerValueField(event: CFObjectRef, field: raw::c_uint) -> i64;
+
+    fn CGEventSetIntegerValueField(event: CFObjectRef, field: raw::c_uint, value: i64);
+
+    fn CGEventGetDoubleValueField(event: CFObjectRef, field: raw::c_uint) -> f64;
+
+    fn CGEventSetDoubleValueField(event: CFObjectRef, field: raw::c_uint, value: f64);
+
+    fn CGEventGetTimestamp(event: CFObjectRef) -> u64;
+
+    fn CGEventSetTimestamp(event: CFObjectRef, timestamp: u64);
+
+    fn CGSessionCopyCurrentDictionary() -> Option<CFObject>;
+
+    static kCGSessionOnConsoleKey: CFObjectRef;
 }
 
 #[macro_use]
 mod macros;
 
+mod cf;
+mod field;
+pub use self::field::*;
+
+mod run_loop;
+
 pub mod app;
+pub mod audio;
+pub mod ax;
+pub mod coordinates;
+#[cfg(feature = "macos-gestures")]
+pub mod gesture;
+pub mod hid;
 pub mod keyboard;
 pub mod mouse;
+pub mod pen;
+pub mod permissions;
+pub mod power;
 pub mod screen;
+pub mod source;
+pub mod tap;
+pub mod text;
 pub mod wheel;
+pub mod window;
 
 lazy_static! {
-    static ref NS_EVENT: &'static Class = Class::get("NSEvent").unwrap();
+    static ref NS_EVENT: Option<&'static Class> = Class::get("NSEvent");
+}
+
+/// Returns a class looked up once, which is only missing if the running
+/// version of macOS does not provide it.
+#[inline]
+fn class(cls: &Option<&'static Class>) -> Result<&'static Class> {
+    cls.ok_or(Error::Unsupported)
+}
+
+/// Returns the event created by a Quartz function, which returns null if it
+/// fails.
+#[inline]
+fn created(event: Option<CGEvent>) -> Result<Event> {
+    event.map(Event).ok_or(Error::Failed)
 }
 
 unsafe fn ns_url_encode_utf8(ns_url: Option<NSObject>) -> Option<String> {
@@ -42,11 +99,11 @@ unsafe fn ns_url_encode_utf8(ns_url: Option<NSObject>) -> Option<String> {
 }
 
 unsafe fn ns_string_encode_utf8(ns_string: Option<NSObject>) -> Option<String> {
-    if let Some(s) = ns_string {
-        let s = CStr::from_ptr(msg_send![s.inner(), UTF8String]);
-        Some(s.to_string_lossy().into())
-    } else {
+    let utf8: *const raw::c_char = msg_send![ns_string?.inner(), UTF8String];
+    if utf8.is_null() {
         None
+    } else {
+        Some(CStr::from_ptr(utf8).to_string_lossy().into())
     }
 }
 
@@ -82,7 +139,7 @@ impl_object!(CFObject, CFObjectRef, fn drop(&mut self) {
 
 impl_object!(NSObject, NSObjectRef, fn drop(&mut self) {
     let ptr = self.0.as_ptr();
-    unsafe { msg_send![ptr, release] };
+    let _: () = unsafe { msg_send![ptr, release] };
 });
 
 impl NSObject {
@@ -133,39 +190,87 @@ impl CGRect {
 type CGEvent = CFObject;
 type CGEventSource = *const Object;
 
-#[repr(C)]
-#[derive(Copy, Clone)]
-#[allow(dead_code)]
-enum CGEventType {
-    // The null event.
-    Null,
-
-    // Mouse events.
-    LeftMouseDown,
-    LeftMouseUp,
-    RightMouseDown,
-    RightMouseUp,
-    MouseMoved,
-    LeftMouseDragged,
-    RightMouseDragged,
-
-    // Keyboard events.
-    KeyDown,
-    KeyUp,
-    FlagsChanged,
-
-    // Specialized control devices.
-    ScrollWheel,
-    TabletPointer,
-    TabletProximity,
-    OtherMouseDown,
-    OtherMouseUp,
-    OtherMouseDragged,
-
-    // Out of band event types. These are delivered to the event tap callback
-    // to notify it of unusual conditions that disable the event tap.
-    TapDisabledByTimeout,
-    TapDisabledByUserInput,
+/// The type of a Quartz event.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum EventType {
+    /// The null event.
+    Null = 0,
+
+    /// The left mouse button was pressed.
+    LeftMouseDown = 1,
+    /// The left mouse button was released.
+    LeftMouseUp = 2,
+    /// The right mouse button was pressed.
+    RightMouseDown = 3,
+    /// The right mouse button was released.
+    RightMouseUp = 4,
+    /// The mouse moved.
+    MouseMoved = 5,
+    /// The mouse moved while the left button was pressed.
+    LeftMouseDragged = 6,
+    /// The mouse moved while the right button was pressed.
+    RightMouseDragged = 7,
+
+    /// A key was pressed.
+    KeyDown = 10,
+    /// A key was released.
+    KeyUp = 11,
+    /// A modifier key was pressed or released.
+    FlagsChanged = 12,
+
+    /// The scroll wheel position changed.
+    ScrollWheel = 22,
+    /// A tablet pointer moved or was pressed.
+    TabletPointer = 23,
+    /// A tablet pointer entered or left proximity.
+    TabletProximity = 24,
+    /// A mouse button other than left or right was pressed.
+    OtherMouseDown = 25,
+    /// A mouse button other than left or right was released.
+    OtherMouseUp = 26,
+    /// The mouse moved while a button other than left or right was pressed.
+    OtherMouseDragged = 27,
+
+    /// The pressure on a Force Touch trackpad changed.
+    Pressure = 34,
+
+    /// An event tap was disabled because its callback took too long.
+    ///
+    /// Only delivered to event tap callbacks.
+    TapDisabledByTimeout = 0xFFFFFFFE,
+    /// An event tap was disabled by user input.
+    ///
+    /// Only delivered to event tap callbacks.
+    TapDisabledByUserInput = 0xFFFFFFFF,
+}
+
+impl EventType {
+    pub(crate) fn from_raw(raw: u32) -> EventType {
+        use self::EventType::*;
+        match raw {
+            1  => LeftMouseDown,
+            2  => LeftMouseUp,
+            3  => RightMouseDown,
+            4  => RightMouseUp,
+            5  => MouseMoved,
+            6  => LeftMouseDragged,
+            7  => RightMouseDragged,
+            10 => KeyDown,
+            11 => KeyUp,
+            12 => FlagsChanged,
+            22 => ScrollWheel,
+            23 => TabletPointer,
+            24 => TabletProximity,
+            25 => OtherMouseDown,
+            26 => OtherMouseUp,
+            27 => OtherMouseDragged,
+            34 => Pressure,
+            0xFFFFFFFE => TapDisabledByTimeout,
+            0xFFFFFFFF => TapDisabledByUserInput,
+            _  => Null,
+        }
+    }
 }
 
 unsafe impl Encode for CGPoint {
@@ -194,6 +299,18 @@ impl Event {
         unsafe { CGEventPost(location as raw::c_int, (self.0).0) };
     }
 
+    /// Posts `self` directly to the application with `pid`, whether or not it
+    /// is frontmost.
+    ///
+    /// The event bypasses the global event stream, so it neither moves the
+    /// cursor nor activates the application, and no other application or
+    /// event tap sees it. This allows automating several background
+    /// applications at once, though some ignore input while inactive.
+    #[inline]
+    pub fn post_to_pid(&self, pid: Pid) {
+        unsafe { CGEventPostToPid(pid, (self.0).0) };
+    }
+
     /// Returns the flags of the inner Quartz event.
     #[inline]
     pub fn flags(&self) -> EventFlags {
@@ -212,6 +329,98 @@ impl Event {
         let prev = self.flags();
         self.set_flags(prev | flags);
     }
+
+    /// Returns the integer value of `field`, or `None` if the field is not
+    /// meaningful for this kind of event.
+    #[inline]
+    pub fn integer_field(&self, field: EventField) -> Option<i64> {
+        if field.is_valid_for(self) {
+            Some(self.raw_integer_field(field as raw::c_uint))
+        } else {
+            None
+        }
+    }
+
+    /// Sets the integer value of `field`, returning `false` if the field is
+    /// not meaningful for this kind of event.
+    #[inline]
+    pub fn set_integer_field(&mut self, field: EventField, value: i64) -> bool {
+        let valid = field.is_valid_for(self);
+        if valid {
+            self.set_raw_integer_field(field as raw::c_uint, value);
+        }
+        valid
+    }
+
+    /// Returns the integer value of the field numbered `field`, including
+    /// undocumented fields that `EventField` does not list.
+    #[inline]
+    fn raw_integer_field(&self, field: raw::c_uint) -> i64 {
+        unsafe { CGEventGetIntegerValueField((self.0).0, field) }
+    }
+
+    /// Sets the integer value of the field numbered `field`, including
+    /// undocumented fields that `EventField` does not list.
+    #[inline]
+    fn set_raw_integer_field(&mut self, field: raw::c_uint, value: i64) {
+        unsafe { CGEventSetIntegerValueField((self.0).0, field, value) };
+    }
+
+    /// Returns the floating point value of `field`, or `None` if the field is
+    /// not meaningful for this kind of event.
+    #[inline]
+    pub fn double_field(&self, field: EventField) -> Option<f64> {
+        if field.is_valid_for(self) {
+            Some(self.raw_double_field(field as raw::c_uint))
+        } else {
+            None
+        }
+    }
+
+    /// Sets the floating point value of `field`, returning `false` if the
+    /// field is not meaningful for this kind of event.
+    #[inline]
+    pub fn set_double_field(&mut self, field: EventField, value: f64) -> bool {
+        let valid = field.is_valid_for(self);
+        if valid {
+            self.set_raw_double_field(field as raw::c_uint, value);
+        }
+        valid
+    }
+
+    /// Returns the floating point value of the field numbered `field`,
+    /// including undocumented fields that `EventField` does not list.
+    #[inline]
+    fn raw_double_field(&self, field: raw::c_uint) -> f64 {
+        unsafe { CGEventGetDoubleValueField((self.0).0, field) }
+    }
+
+    /// Sets the floating point value of the field numbered `field`, including
+    /// undocumented fields that `EventField` does not list.
+    #[inline]
+    fn set_raw_double_field(&mut self, field: raw::c_uint, value: f64) {
+        unsafe { CGEventSetDoubleValueField((self.0).0, field, value) };
+    }
+
+    /// Returns the time at which the inner Quartz event occurred, in
+    /// nanoseconds since the system started.
+    #[inline]
+    pub fn timestamp(&self) -> u64 {
+        unsafe { CGEventGetTimestamp((self.0).0) }
+    }
+
+    /// Sets the time at which the inner Quartz event occurred, in nanoseconds
+    /// since the system started.
+    #[inline]
+    pub fn set_timestamp(&mut self, timestamp: u64) {
+        unsafe { CGEventSetTimestamp((self.0).0, timestamp) };
+    }
+
+    /// Returns the type of the inner Quartz event.
+    #[inline]
+    pub fn event_type(&self) -> EventType {
+        EventType::from_raw(unsafe { CGEventGetType((self.0).0) })
+    }
 }
 
 bitflags! {
@@ -261,3 +470,56 @@ pub enum EventLocation {
     /// annotated to flow to an application.
     AnnotatedSession,
 }
+
+static DEFAULT_LOCATION: AtomicUsize = AtomicUsize::new(EventLocation::Hid as usize);
+
+impl EventLocation {
+    /// Returns the location that the high-level post helpers, such as
+    /// [`events::post`](../../events/fn.post.html), post events at.
+    ///
+    /// This is [`Hid`](#variant.Hid) unless changed with
+    /// [`set_default`](#method.set_default).
+    #[inline]
+    pub fn default_location() -> EventLocation {
+        match DEFAULT_LOCATION.load(Ordering::Relaxed) {
+            1 => EventLocation::Session,
+            2 => EventLocation::AnnotatedSession,
+            _ => EventLocation::Hid,
+        }
+    }
+
+    /// Sets the location that the high-level post helpers post events at for
+    /// the whole process.
+    #[inline]
+    pub fn set_default(location: EventLocation) {
+        DEFAULT_LOCATION.store(location as usize, Ordering::Relaxed);
+    }
+
+    /// Returns the location that posted events should work at in the session
+    /// that the process is running in.
+    ///
+    /// Events posted at [`Hid`](#variant.Hid) go to whichever login session
+    /// is using the console, while events posted at
+    /// [`Session`](#variant.Session) go to the session of the process. So:
+    ///
+    /// - A process in the login session on the console gets `Hid`, which
+    ///   behaves most like physical input.
+    /// - A process outside of any login session, such as one started over
+    ///   SSH by a user with access to the window server, gets `Hid`, since it
+    ///   has no session of its own to post to.
+    /// - A process in a login session that is not on the console, such as
+    ///   one switched out with fast user switching, gets `Session`, so that
+    ///   its events do not reach the user on the console.
+    pub fn detect() -> EventLocation {
+        let session = match unsafe { CGSessionCopyCurrentDictionary() } {
+            Some(session) => session,
+            None => return EventLocation::Hid,
+        };
+        let on_console = unsafe { cf::get(&session, kCGSessionOnConsoleKey) };
+        if on_console == Some(cf::boolean(true)) {
+            EventLocation::Hid
+        } else {
+            EventLocation::Session
+        }
+    }
+}
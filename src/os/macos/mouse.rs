@@ -1,19 +1,23 @@
 //! 🖱️ Mouse automation utilities.
 
-use std::{fmt, mem, ptr};
+use std::{fmt, ptr};
 use std::os::raw;
 
-use objc::runtime::Class;
+use objc::runtime::{Class, Object};
 
-use super::{CFObjectRef, CGEventType, CGEventSource, CGPoint, NS_EVENT};
+use super::screen::{self, CGError, CGImageRef};
+use super::source::EventSource;
+use super::{CFObjectRef, CGEvent, CGEventSource, CGPoint, CGRect, EventField, EventLocation, EventType, NS_EVENT};
+use error::{Error, Result};
+use image::Image;
 
 extern {
     fn CGEventCreateMouseEvent(
         source: CGEventSource,
-        mouse_type: CGEventType,
+        mouse_type: EventType,
         mouse_cursor_position: CGPoint,
         mouse_button: raw::c_int,
-    ) -> *mut raw::c_void;
+    ) -> Option<CGEvent>;
 
     fn CGEventGetLocation(event: CFObjectRef) -> CGPoint;
 
@@ -22,20 +26,52 @@ extern {
     fn CGEventSetLocation(event: CFObjectRef, location: CGPoint);
 
     fn CGWarpMouseCursorPosition(new_cursor_position: CGPoint) -> CGPoint;
+
+    fn CGEventCreate(source: CGEventSource) -> Option<CGEvent>;
+
+    fn CGEventSetType(event: CFObjectRef, event_type: EventType);
+
+    fn CGDisplayHideCursor(display: u32) -> CGError;
+
+    fn CGDisplayShowCursor(display: u32) -> CGError;
+}
+
+lazy_static! {
+    static ref NS_CURSOR: Option<&'static Class> = Class::get("NSCursor");
 }
 
 unsafe fn location_from(ns_event: &Class) -> Location {
     From::<CGPoint>::from(msg_send![ns_event, mouseLocation])
 }
 
-/// Returns the current mouse location.
-pub fn location() -> Location {
-    unsafe { location_from(&NS_EVENT) }
+/// Returns the current mouse location in Cocoa coordinates, relative to the
+/// lower-left corner of the main display.
+///
+/// Quartz events use global coordinates instead; see
+/// [`coordinates`](../coordinates/index.html) for converting between the two,
+/// or use [`cursor_location`](fn.cursor_location.html).
+pub fn location() -> Result<Location> {
+    let ns_event = super::class(&NS_EVENT)?;
+    unsafe { Ok(location_from(ns_event)) }
 }
 
-/// Returns an iterator over current mouse locations.
-pub fn location_iter() -> LocationIter {
-    LocationIter { ns_event: &NS_EVENT }
+/// Returns an iterator over current mouse locations in Cocoa coordinates.
+pub fn location_iter() -> Result<LocationIter> {
+    super::class(&NS_EVENT).map(|ns_event| LocationIter { ns_event })
+}
+
+/// Returns the current mouse location relative to the upper-left corner of
+/// the main display, like the locations of Quartz events.
+pub fn cursor_location() -> Result<Location> {
+    let event = super::created(unsafe { CGEventCreate(ptr::null()) })?;
+    Ok(event_location(&event))
+}
+
+/// Returns the longest time in seconds between clicks that form a double
+/// click, as set by the user.
+pub fn double_click_interval() -> Result<f64> {
+    let ns_event = super::class(&NS_EVENT)?;
+    unsafe { Ok(msg_send![ns_event, doubleClickInterval]) }
 }
 
 /// Moves the mouse cursor without generating events.
@@ -44,6 +80,181 @@ pub fn warp_location(location: Location) {
     unsafe { CGWarpMouseCursorPosition(location.into()) };
 }
 
+/// Returns the location of a Quartz event relative to the upper-left corner of
+/// the main display.
+#[inline]
+pub(crate) fn event_location(event: &super::Event) -> Location {
+    unsafe { CGEventGetLocation((event.0).0).into() }
+}
+
+/// The stage of a Force Touch click.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum ForceStage {
+    /// A normal click.
+    Click = 1,
+    /// A deep click, pressing past the first click.
+    Deep = 2,
+}
+
+/// Returns a new Quartz pressure event at `location`.
+fn pressure_event(location: Location, stage: ForceStage, pressure: f64) -> Result<Event> {
+    let mut event = super::created(unsafe { CGEventCreate(ptr::null()) }).map(Event)?;
+    unsafe { CGEventSetType(((event.0).0).0, EventType::Pressure) };
+    event.set_location(location);
+    event.set_integer_field(EventField::MouseClickState, stage as i64);
+    event.set_double_field(EventField::MousePressure, pressure);
+    Ok(event)
+}
+
+/// Posts a Force Touch click with the left button at `location`, pressing
+/// through to `stage` before releasing.
+///
+/// Quartz provides no public way to set the stage of a synthesized event, so
+/// the stage is conveyed through the click state and the pressure reported by
+/// a sequence of pressure events. Applications that query the stage of the
+/// hardware itself may not recognize the deep click.
+pub fn force_click(location: Location, stage: ForceStage) -> Result<()> {
+    let post = |event: &Event| event.post(EventLocation::default_location());
+
+    let mut down = Event::new(Button::Left, EventKind::Down, location)?;
+    down.set_double_field(EventField::MousePressure, 1.0);
+    post(&down);
+
+    // Ramp the pressure through each stage so that applications observe the
+    // transitions between them
+    let stages: &[ForceStage] = match stage {
+        ForceStage::Click => &[ForceStage::Click],
+        ForceStage::Deep => &[ForceStage::Click, ForceStage::Deep],
+    };
+    for &stage in stages {
+        for &pressure in &[0.25, 0.5, 0.75, 1.0] {
+            post(&pressure_event(location, stage, pressure)?);
+        }
+    }
+    post(&pressure_event(location, ForceStage::Click, 0.0)?);
+
+    let mut up = Event::new(Button::Left, EventKind::Up, location)?;
+    up.set_double_field(EventField::MousePressure, 0.0);
+    post(&up);
+    Ok(())
+}
+
+/// Hides the mouse cursor.
+///
+/// Calls are counted, so the cursor only reappears once
+/// [`show_cursor`](fn.show_cursor.html) has been called as many times. The
+/// cursor is only hidden while the calling application is frontmost, and
+/// reappears when the application exits.
+#[inline]
+pub fn hide_cursor() -> Result<()> {
+    screen::cg_result(unsafe { CGDisplayHideCursor(0) })
+}
+
+/// Shows the mouse cursor after a call to
+/// [`hide_cursor`](fn.hide_cursor.html).
+#[inline]
+pub fn show_cursor() -> Result<()> {
+    screen::cg_result(unsafe { CGDisplayShowCursor(0) })
+}
+
+/// Returns the image of the cursor currently shown by the system, whichever
+/// application set it.
+///
+/// Requires macOS 10.6 or later.
+pub fn cursor_image() -> Result<Image> {
+    let ns_cursor = super::class(&NS_CURSOR)?;
+    unsafe {
+        let cursor: *mut Object = msg_send![ns_cursor, currentSystemCursor];
+        let cursor = if cursor.is_null() {
+            msg_send![ns_cursor, currentCursor]
+        } else {
+            cursor
+        };
+        cursor_image_of(cursor)
+    }
+}
+
+/// Draws the image of an `NSCursor`, which may be null.
+unsafe fn cursor_image_of(cursor: *mut Object) -> Result<Image> {
+    if cursor.is_null() {
+        return Err(Error::Failed);
+    }
+    let image: *mut Object = msg_send![cursor, image];
+    if image.is_null() {
+        return Err(Error::Failed);
+    }
+    let cg_image: Option<CGImageRef> = msg_send![
+        image,
+        CGImageForProposedRect: ptr::null_mut::<CGRect>()
+        context: ptr::null_mut::<Object>()
+        hints: ptr::null_mut::<Object>()
+    ];
+    screen::draw_image(cg_image.ok_or(Error::Failed)?)
+}
+
+/// A standard cursor provided by `NSCursor`.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum SystemCursor {
+    /// The arrow cursor.
+    Arrow,
+    /// The I-beam cursor for selecting text.
+    IBeam,
+    /// The crosshair cursor.
+    Crosshair,
+    /// The pointing hand cursor over links.
+    PointingHand,
+    /// The cursor for resizing left and right.
+    ResizeLeftRight,
+    /// The cursor for resizing up and down.
+    ResizeUpDown,
+    /// The cursor for an operation that is not allowed.
+    OperationNotAllowed,
+}
+
+impl SystemCursor {
+    /// All standard cursors.
+    pub const ALL: [SystemCursor; 7] = [
+        SystemCursor::Arrow,
+        SystemCursor::IBeam,
+        SystemCursor::Crosshair,
+        SystemCursor::PointingHand,
+        SystemCursor::ResizeLeftRight,
+        SystemCursor::ResizeUpDown,
+        SystemCursor::OperationNotAllowed,
+    ];
+
+    /// Returns the image of the cursor.
+    pub fn image(self) -> Result<Image> {
+        let ns_cursor = super::class(&NS_CURSOR)?;
+        unsafe {
+            let cursor: *mut Object = match self {
+                SystemCursor::Arrow => msg_send![ns_cursor, arrowCursor],
+                SystemCursor::IBeam => msg_send![ns_cursor, IBeamCursor],
+                SystemCursor::Crosshair => msg_send![ns_cursor, crosshairCursor],
+                SystemCursor::PointingHand => msg_send![ns_cursor, pointingHandCursor],
+                SystemCursor::ResizeLeftRight => msg_send![ns_cursor, resizeLeftRightCursor],
+                SystemCursor::ResizeUpDown => msg_send![ns_cursor, resizeUpDownCursor],
+                SystemCursor::OperationNotAllowed => msg_send![ns_cursor, operationNotAllowedCursor],
+            };
+            cursor_image_of(cursor)
+        }
+    }
+
+    /// Returns the standard cursor currently shown, if any.
+    ///
+    /// `NSCursor` provides no identity for the system cursor, so this compares
+    /// the image of the current cursor against each standard cursor.
+    pub fn current() -> Result<Option<SystemCursor>> {
+        let current = cursor_image()?;
+        for &cursor in &SystemCursor::ALL {
+            if cursor.image()? == current {
+                return Ok(Some(cursor));
+            }
+        }
+        Ok(None)
+    }
+}
+
 /// A location on the screen.
 pub type Location = (f64, f64);
 
@@ -68,20 +279,52 @@ pub enum Button {
     Left,
     /// Right button where the middle finger might press.
     Right,
+    /// Middle button, often the scroll wheel.
+    Middle,
+    /// Another button, identified by its number, where 3 and above are extra
+    /// buttons such as back and forward.
+    Other(u8),
+}
+
+impl Button {
+    /// Returns the button with the `mouseButtonNumber` of a Quartz event.
+    #[inline]
+    pub fn from_number(number: u8) -> Button {
+        match number {
+            0 => Button::Left,
+            1 => Button::Right,
+            2 => Button::Middle,
+            n => Button::Other(n),
+        }
+    }
+
+    /// Returns the `mouseButtonNumber` that Quartz events use for the button.
+    #[inline]
+    pub fn number(self) -> u8 {
+        match self {
+            Button::Left => 0,
+            Button::Right => 1,
+            Button::Middle => 2,
+            Button::Other(n) => n,
+        }
+    }
 }
 
-impl From<(Button, EventKind)> for super::CGEventType {
-    fn from(pair: (Button, EventKind)) -> Self {
-        use super::CGEventType::*;
-        match pair {
-            (Button::Left,   EventKind::Down)    => LeftMouseDown,
-            (Button::Left,   EventKind::Up)      => LeftMouseUp,
-            (Button::Left,   EventKind::Moved)   => MouseMoved,
-            (Button::Left,   EventKind::Dragged) => LeftMouseDragged,
-            (Button::Right,  EventKind::Down)    => RightMouseDown,
-            (Button::Right,  EventKind::Up)      => RightMouseUp,
-            (Button::Right,  EventKind::Moved)   => MouseMoved,
-            (Button::Right,  EventKind::Dragged) => RightMouseDragged,
+impl From<(Button, EventKind)> for super::EventType {
+    fn from((button, kind): (Button, EventKind)) -> Self {
+        use super::EventType::*;
+        // Quartz has dedicated types for the left and right buttons only
+        match (button.number(), kind) {
+            (_, EventKind::Moved)   => MouseMoved,
+            (0, EventKind::Down)    => LeftMouseDown,
+            (0, EventKind::Up)      => LeftMouseUp,
+            (0, EventKind::Dragged) => LeftMouseDragged,
+            (1, EventKind::Down)    => RightMouseDown,
+            (1, EventKind::Up)      => RightMouseUp,
+            (1, EventKind::Dragged) => RightMouseDragged,
+            (_, EventKind::Down)    => OtherMouseDown,
+            (_, EventKind::Up)      => OtherMouseUp,
+            (_, EventKind::Dragged) => OtherMouseDragged,
         }
     }
 }
@@ -89,25 +332,51 @@ impl From<(Button, EventKind)> for super::CGEventType {
 declare_event!("A mouse event that can be posted into the Quartz event stream.");
 
 impl Event {
-    /// Creates a new mouse event for `button` of `kind` at `location`.
+    /// Creates a new mouse event for `button` of `kind` at `location`, in
+    /// global coordinates.
     ///
     /// This function allocates a new `CGEvent`.
-    pub fn new(button: Button, kind: EventKind, location: Location) -> Event {
-        unsafe {
-            let event = CGEventCreateMouseEvent(
-                ptr::null(),
+    pub fn new(button: Button, kind: EventKind, location: Location) -> Result<Event> {
+        Event::create(ptr::null(), button, kind, location)
+    }
+
+    /// Creates a new mouse event for `button` of `kind` at `location` from
+    /// `source`.
+    #[inline]
+    pub fn new_with_source(
+        source: &EventSource,
+        button: Button,
+        kind: EventKind,
+        location: Location,
+    ) -> Result<Event> {
+        Event::create(source.as_raw(), button, kind, location)
+    }
+
+    fn create(
+        source: CGEventSource,
+        button: Button,
+        kind: EventKind,
+        location: Location,
+    ) -> Result<Event> {
+        let event = unsafe {
+            CGEventCreateMouseEvent(
+                source,
                 (button, kind).into(),
                 location.into(),
-                button as raw::c_int,
-            );
-            mem::transmute(event)
-        }
+                button.number() as raw::c_int,
+            )
+        };
+        let mut event = super::created(event).map(Event)?;
+        // Quartz only takes the button number from other-button types
+        event.set_integer_field(EventField::MouseButtonNumber, button.number() as i64);
+        Ok(event)
     }
 
-    /// Returns the location of the inner Quartz mouse event.
+    /// Returns the location of the inner Quartz mouse event in global
+    /// coordinates, relative to the upper-left corner of the main display.
     #[inline]
     pub fn location(&self) -> Location {
-        unsafe { CGEventGetLocation(((self.0).0).0).into() }
+        event_location(&self.0)
     }
 
     /// Returns the location of the inner Quartz mouse event relative to the
@@ -122,6 +391,55 @@ impl Event {
     pub fn set_location(&mut self, location: Location) {
         unsafe { CGEventSetLocation(((self.0).0).0, location.into()) }
     }
+
+    /// Returns the button that the event is for.
+    #[inline]
+    pub fn button(&self) -> Button {
+        let number = self.integer_field(EventField::MouseButtonNumber).unwrap_or(0);
+        Button::from_number(number as u8)
+    }
+
+    /// Returns the click count: 1 for a single click, 2 for a double click,
+    /// and so on.
+    #[inline]
+    pub fn click_count(&self) -> i64 {
+        self.integer_field(EventField::MouseClickState).unwrap_or(0)
+    }
+
+    /// Sets the click count, such as 2 to make a press part of a double
+    /// click.
+    #[inline]
+    pub fn set_click_count(&mut self, count: i64) {
+        self.set_integer_field(EventField::MouseClickState, count);
+    }
+
+    /// Returns the button pressure, ranging from 0 (released) to 1.
+    #[inline]
+    pub fn pressure(&self) -> f64 {
+        self.double_field(EventField::MousePressure).unwrap_or(0.0)
+    }
+
+    /// Sets the button pressure, ranging from 0 (released) to 1.
+    #[inline]
+    pub fn set_pressure(&mut self, pressure: f64) {
+        self.set_double_field(EventField::MousePressure, pressure);
+    }
+
+    /// Returns the distance moved since the previous mouse event.
+    #[inline]
+    pub fn delta(&self) -> (f64, f64) {
+        (
+            self.double_field(EventField::MouseDeltaX).unwrap_or(0.0),
+            self.double_field(EventField::MouseDeltaY).unwrap_or(0.0),
+        )
+    }
+
+    /// Sets the distance moved since the previous mouse event.
+    #[inline]
+    pub fn set_delta(&mut self, (dx, dy): (f64, f64)) {
+        self.set_double_field(EventField::MouseDeltaX, dx);
+        self.set_double_field(EventField::MouseDeltaY, dy);
+    }
 }
 
 /// The kind of operation being performed by the mouse event.
@@ -162,30 +480,6 @@ impl Iterator for LocationIter {
 
     #[inline]
     fn size_hint(&self) -> (usize, Option<usize>) {
-        (usize::max_value(), None)
-    }
-}
-
-#[cfg(all(test, nightly))]
-mod benches {
-    use super::*;
-    use test::{Bencher, black_box};
-
-    #[bench]
-    fn location_100(b: &mut Bencher) {
-        b.iter(|| {
-            for _ in 0..100 {
-                black_box(location());
-            }
-        });
-    }
-
-    #[bench]
-    fn location_iter_100(b: &mut Bencher) {
-        b.iter(|| {
-            for loc in location_iter().take(100) {
-                black_box(loc);
-            }
-        })
+        (usize::MAX, None)
     }
 }
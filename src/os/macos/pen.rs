@@ -0,0 +1,151 @@
+//! ✒️ Tablet pen automation utilities.
+//!
+//! A pen stroke is a proximity event as the pen approaches the tablet, mouse
+//! events carrying tablet data while it moves and touches, and another
+//! proximity event as it leaves. Drawing applications read the pressure,
+//! tilt, and rotation of the pen from the tablet data.
+//!
+//! # Examples
+//!
+//! Draw a line that presses harder towards its end:
+//!
+//! ```no_run
+//! use auto::os::EventLocation;
+//! use auto::os::mouse::EventKind;
+//! use auto::os::pen::{Event, PointerType};
+//!
+//! let post = |event: Event| event.post(EventLocation::Hid);
+//! post(Event::proximity(PointerType::Pen, true).unwrap());
+//! post(Event::point(EventKind::Down, (100.0, 100.0)).unwrap());
+//! for i in 1..=50 {
+//!     let mut point = Event::point(EventKind::Dragged, (100.0 + i as f64 * 4.0, 100.0)).unwrap();
+//!     point.set_pressure(i as f64 / 50.0);
+//!     post(point);
+//! }
+//! post(Event::point(EventKind::Up, (300.0, 100.0)).unwrap());
+//! post(Event::proximity(PointerType::Pen, false).unwrap());
+//! ```
+
+use std::ptr;
+
+use super::{CFObjectRef, CGEvent, CGEventSource, EventField, EventType};
+use super::mouse::{self, Button, EventKind, Location};
+use error::Result;
+
+extern {
+    fn CGEventCreate(source: CGEventSource) -> Option<CGEvent>;
+
+    fn CGEventSetType(event: CFObjectRef, event_type: EventType);
+}
+
+/// `kCGEventMouseSubtypeTabletPoint`
+const SUBTYPE_TABLET_POINT: i64 = 1;
+
+/// The device identifier that ties the points of synthesized strokes to
+/// their proximity events.
+const DEVICE_ID: i64 = 0x4155;
+
+/// The capabilities reported for the synthesized pen: position, buttons,
+/// tilt, pressure, and rotation.
+const CAPABILITIES: i64 = 0x1 | 0x2 | 0x8 | 0x10 | 0x20 | 0x80 | 0x400;
+
+/// The end of a pointing device in proximity to a tablet.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum PointerType {
+    /// The tip of a pen.
+    Pen = 1,
+    /// A puck, which is used like a mouse.
+    Cursor = 2,
+    /// The eraser end of a pen.
+    Eraser = 3,
+}
+
+declare_event!("A tablet event that can be posted into the Quartz event stream.");
+
+impl Event {
+    /// Creates an event for `pointer` entering proximity of the tablet if
+    /// `entering` is `true`, or leaving it otherwise.
+    pub fn proximity(pointer: PointerType, entering: bool) -> Result<Event> {
+        let mut event = super::created(unsafe { CGEventCreate(ptr::null()) })?;
+        unsafe { CGEventSetType((event.0).0, EventType::TabletProximity) };
+        event.set_integer_field(EventField::ProximityEnterProximity, entering as i64);
+        event.set_integer_field(EventField::ProximityPointerType, pointer as i64);
+        event.set_integer_field(EventField::ProximityCapabilityMask, CAPABILITIES);
+        event.set_integer_field(EventField::ProximityDeviceId, DEVICE_ID);
+        Ok(Event(event))
+    }
+
+    /// Creates a mouse event of `kind` at `location` that carries tablet
+    /// data, as the pen moves, touches down, drags, and lifts.
+    ///
+    /// The pressure starts at 1 for presses and drags and at 0 otherwise.
+    pub fn point(kind: EventKind, location: Location) -> Result<Event> {
+        let mut event: super::Event = mouse::Event::new(Button::Left, kind, location)?.into();
+        event.set_integer_field(EventField::MouseSubtype, SUBTYPE_TABLET_POINT);
+        event.set_integer_field(EventField::TabletDeviceId, DEVICE_ID);
+        let mut event = Event(event);
+        event.set_pressure(match kind {
+            EventKind::Down | EventKind::Dragged => 1.0,
+            EventKind::Up | EventKind::Moved => 0.0,
+        });
+        Ok(event)
+    }
+
+    /// Returns whether the event is for a pointer entering proximity.
+    #[inline]
+    pub fn is_entering_proximity(&self) -> bool {
+        self.integer_field(EventField::ProximityEnterProximity).unwrap_or(0) != 0
+    }
+
+    /// Returns the pen pressure, ranging from 0 to 1.
+    #[inline]
+    pub fn pressure(&self) -> f64 {
+        self.double_field(EventField::TabletPressure).unwrap_or(0.0)
+    }
+
+    /// Sets the pen pressure, ranging from 0 to 1.
+    ///
+    /// Applications that only understand mouse events see it as the button
+    /// pressure.
+    pub fn set_pressure(&mut self, pressure: f64) {
+        self.set_double_field(EventField::TabletPressure, pressure);
+        self.set_double_field(EventField::MousePressure, pressure);
+    }
+
+    /// Returns the horizontal and vertical tilt of the pen, each ranging from
+    /// -1 to 1.
+    #[inline]
+    pub fn tilt(&self) -> (f64, f64) {
+        (
+            self.double_field(EventField::TabletTiltX).unwrap_or(0.0),
+            self.double_field(EventField::TabletTiltY).unwrap_or(0.0),
+        )
+    }
+
+    /// Sets the horizontal and vertical tilt of the pen, each ranging from -1
+    /// to 1, where `(0, 0)` is upright.
+    #[inline]
+    pub fn set_tilt(&mut self, (x, y): (f64, f64)) {
+        self.set_double_field(EventField::TabletTiltX, x);
+        self.set_double_field(EventField::TabletTiltY, y);
+    }
+
+    /// Returns the rotation of the pen about its axis, in degrees.
+    #[inline]
+    pub fn rotation(&self) -> f64 {
+        self.double_field(EventField::TabletRotation).unwrap_or(0.0)
+    }
+
+    /// Sets the rotation of the pen about its axis, in degrees.
+    #[inline]
+    pub fn set_rotation(&mut self, degrees: f64) {
+        self.set_double_field(EventField::TabletRotation, degrees);
+    }
+
+    /// Sets the pressure on the barrel of the pen, such as from the wheel of
+    /// an airbrush, ranging from -1 to 1.
+    #[inline]
+    pub fn set_tangential_pressure(&mut self, pressure: f64) {
+        self.set_double_field(EventField::TabletTangentialPressure, pressure);
+    }
+}
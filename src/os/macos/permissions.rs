@@ -0,0 +1,59 @@
+//! 🔐 Permission utilities.
+//!
+//! Since macOS 10.14, the user must allow a process to control the computer
+//! before its event taps receive events or the events it posts take effect,
+//! and since macOS 10.15, to record the screen before its captures show the
+//! contents of other applications' windows. Neither failure is reported by
+//! the operations themselves, so these checks let the process explain what
+//! is missing instead.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use auto::os::permissions;
+//!
+//! if !permissions::accessibility_granted() {
+//!     // Shows the system prompt that leads to System Settings
+//!     permissions::request_accessibility();
+//! }
+//! ```
+
+use super::{cf, CFObjectRef};
+
+#[link(name = "ApplicationServices", kind = "framework")]
+extern {
+    fn AXIsProcessTrusted() -> bool;
+
+    fn AXIsProcessTrustedWithOptions(options: CFObjectRef) -> bool;
+
+    static kAXTrustedCheckOptionPrompt: CFObjectRef;
+
+    fn CGPreflightScreenCaptureAccess() -> bool;
+}
+
+/// Returns whether the process is trusted for accessibility, which is needed
+/// to post events, tap them, and inspect other applications.
+#[inline]
+pub fn accessibility_granted() -> bool {
+    unsafe { AXIsProcessTrusted() }
+}
+
+/// Returns whether the process may record the screen, without prompting the
+/// user.
+#[inline]
+pub fn screen_recording_granted() -> bool {
+    unsafe { CGPreflightScreenCaptureAccess() }
+}
+
+/// Returns whether the process is trusted for accessibility, asking the user
+/// to grant access if it is not.
+///
+/// The system shows its prompt at most once per process and returns without
+/// waiting for the user to respond, so the permission is only granted after a
+/// later check succeeds.
+pub fn request_accessibility() -> bool {
+    unsafe {
+        let options = cf::dictionary(&[(kAXTrustedCheckOptionPrompt, cf::boolean(true))]);
+        AXIsProcessTrustedWithOptions(options.0)
+    }
+}
@@ -0,0 +1,75 @@
+//! 🔋 Power management utilities.
+
+use super::{cf, CFObjectRef};
+use error::{OsError, Result};
+
+#[link(name = "IOKit", kind = "framework")]
+extern {
+    fn IOPMAssertionCreateWithName(
+        assertion_type: CFObjectRef,
+        level: u32,
+        name: CFObjectRef,
+        id: *mut u32,
+    ) -> i32;
+
+    fn IOPMAssertionRelease(id: u32) -> i32;
+}
+
+/// `kIOPMAssertionLevelOn`
+const ASSERTION_LEVEL_ON: u32 = 255;
+
+/// A kind of sleep that a [`PowerAssertion`](struct.PowerAssertion.html)
+/// prevents.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum AssertionType {
+    /// Keeps the display from sleeping or dimming when the user is idle,
+    /// which also keeps the system awake.
+    PreventUserIdleDisplaySleep,
+    /// Keeps the system from sleeping when the user is idle, while allowing
+    /// the display to sleep.
+    PreventUserIdleSystemSleep,
+}
+
+impl AssertionType {
+    /// Returns the name that IOKit uses for the type.
+    pub fn name(self) -> &'static str {
+        match self {
+            AssertionType::PreventUserIdleDisplaySleep => "PreventUserIdleDisplaySleep",
+            AssertionType::PreventUserIdleSystemSleep => "PreventUserIdleSystemSleep",
+        }
+    }
+}
+
+/// An IOKit power assertion, which is released when dropped.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct PowerAssertion(u32);
+
+impl Drop for PowerAssertion {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { IOPMAssertionRelease(self.0) };
+    }
+}
+
+impl PowerAssertion {
+    /// Creates an assertion of `kind`, with `name` shown to the user as the
+    /// reason, such as by `pmset -g assertions`.
+    pub fn new(kind: AssertionType, name: &str) -> Result<PowerAssertion> {
+        let kind = cf::string(kind.name());
+        let name = cf::string(name);
+        let mut id = 0;
+        let err = unsafe {
+            IOPMAssertionCreateWithName(kind.0, ASSERTION_LEVEL_ON, name.0, &mut id)
+        };
+        match err {
+            0 => Ok(PowerAssertion(id)),
+            _ => Err(OsError::from_code(err).into()),
+        }
+    }
+
+    /// Returns the identifier of the assertion.
+    #[inline]
+    pub fn id(&self) -> u32 {
+        self.0
+    }
+}
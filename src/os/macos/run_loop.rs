@@ -0,0 +1,114 @@
+//! Core Foundation run loop helpers.
+
+use std::thread;
+use std::time::{Duration, Instant};
+use std::os::raw;
+use std::sync::{mpsc, Arc};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::CFObjectRef;
+
+pub type CFRunLoopRef = *mut raw::c_void;
+
+extern {
+    pub fn CFRunLoopGetCurrent() -> CFRunLoopRef;
+
+    pub fn CFRunLoopAddSource(rl: CFRunLoopRef, source: CFObjectRef, mode: CFObjectRef);
+
+    pub fn CFRunLoopRemoveSource(rl: CFRunLoopRef, source: CFObjectRef, mode: CFObjectRef);
+
+    pub fn CFRunLoopRun();
+
+    fn CFRunLoopRunInMode(mode: CFObjectRef, seconds: f64, return_after_source: u8) -> i32;
+
+    fn CFRunLoopStop(rl: CFRunLoopRef);
+
+    pub static kCFRunLoopCommonModes: CFObjectRef;
+
+    static kCFRunLoopDefaultMode: CFObjectRef;
+}
+
+/// `kCFRunLoopRunFinished`, returned when the mode has no sources or timers
+const RUN_FINISHED: i32 = 1;
+
+/// Runs the current thread's run loop in the default mode for `duration`,
+/// delivering the notifications and callbacks that arrive meanwhile.
+///
+/// The thread sleeps instead if its run loop has nothing to run.
+pub fn run_for(duration: Duration) {
+    let start = Instant::now();
+    let result = unsafe { CFRunLoopRunInMode(kCFRunLoopDefaultMode, duration.as_secs_f64(), 0) };
+    if result == RUN_FINISHED {
+        thread::sleep(duration.saturating_sub(start.elapsed()));
+    }
+}
+
+/// A thread that runs its run loop until stopped or dropped.
+#[derive(Debug)]
+pub struct LoopThread {
+    run_loop: usize,
+    stopped: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl LoopThread {
+    /// Spawns a new thread that calls `setup` and then runs its run loop.
+    ///
+    /// The value returned by `setup` lives on the thread until the loop is
+    /// stopped, which makes it suitable for owning run loop sources. If `setup`
+    /// fails, its error is returned and the thread exits.
+    pub fn spawn<F, T, E>(setup: F) -> Result<LoopThread, E>
+        where F: FnOnce() -> Result<T, E> + Send + 'static,
+              E: Send + 'static
+    {
+        let (tx, rx) = mpsc::channel();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let flag = stopped.clone();
+
+        let thread = thread::spawn(move || {
+            let _guard = match setup() {
+                Ok(guard) => guard,
+                Err(err) => {
+                    let _ = tx.send(Err(err));
+                    return;
+                },
+            };
+            let run_loop = unsafe { CFRunLoopGetCurrent() };
+            if tx.send(Ok(run_loop as usize)).is_ok() {
+                // Stopping may race with entering the loop, so check the flag
+                // periodically rather than relying on `CFRunLoopStop` alone
+                while !flag.load(Ordering::SeqCst) {
+                    unsafe { CFRunLoopRunInMode(kCFRunLoopDefaultMode, 0.25, 0) };
+                }
+            }
+        });
+
+        match rx.recv() {
+            Ok(Ok(run_loop)) => Ok(LoopThread {
+                run_loop,
+                stopped,
+                thread: Some(thread),
+            }),
+            Ok(Err(err)) => {
+                let _ = thread.join();
+                Err(err)
+            },
+            Err(_) => panic!("run loop thread panicked during setup"),
+        }
+    }
+
+    /// Stops the run loop and waits for the thread to exit.
+    pub fn stop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            self.stopped.store(true, Ordering::SeqCst);
+            unsafe { CFRunLoopStop(self.run_loop as CFRunLoopRef) };
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for LoopThread {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
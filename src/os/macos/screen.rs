@@ -1,13 +1,27 @@
 //! 📺 Screen information utilities.
 
+use std::{mem, slice};
 use std::os::raw;
 use std::ptr;
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
 
 use libc::{boolean_t, size_t};
 use objc::runtime::Class;
 
-use super::{CGRect, CGSize, NSObject, NSObjectRef};
+use super::{cf, CFObject, CFObjectRef, CGRect, CGSize, NSObject, NSObjectRef};
+use super::run_loop::{
+    CFRunLoopAddSource,
+    CFRunLoopGetCurrent,
+    CFRunLoopRemoveSource,
+    kCFRunLoopCommonModes,
+    LoopThread,
+};
 use color::Rgb;
+use error::{OsError, Result};
+use image::{ColorGrid, Image};
+use metrics;
 
 extern {
     fn CGMainDisplayID() -> Display;
@@ -62,20 +76,73 @@ extern {
 
     fn CGDisplayRotation(display: Display) -> f64;
 
+    fn CGDisplayCopyDisplayMode(display: Display) -> *mut raw::c_void;
+
+    fn CGDisplayModeGetWidth(mode: *mut raw::c_void) -> size_t;
+
+    fn CGDisplayModeGetPixelWidth(mode: *mut raw::c_void) -> size_t;
+
+    fn CGDisplayModeRelease(mode: *mut raw::c_void);
+
     fn CGDisplayCreateImageForRect(display: Display, rect: CGRect) -> Option<CGImage>;
 
     fn CGImageRelease(image: CGImageRef);
+
+    fn CGImageGetWidth(image: CGImageRef) -> size_t;
+
+    fn CGImageGetHeight(image: CGImageRef) -> size_t;
+
+    fn CGColorSpaceCreateDeviceRGB() -> *mut raw::c_void;
+
+    fn CGColorSpaceRelease(space: *mut raw::c_void);
+
+    fn CGBitmapContextCreate(
+        data: *mut raw::c_void,
+        width: size_t,
+        height: size_t,
+        bits_per_component: size_t,
+        bytes_per_row: size_t,
+        space: *mut raw::c_void,
+        bitmap_info: u32,
+    ) -> *mut raw::c_void;
+
+    fn CGContextDrawImage(context: *mut raw::c_void, rect: CGRect, image: CGImageRef);
+
+    fn CGContextRelease(context: *mut raw::c_void);
+
+    fn CGDisplayRegisterReconfigurationCallback(
+        callback: CGDisplayReconfigurationCallBack,
+        user_info: *mut raw::c_void,
+    ) -> CGError;
+
+    fn CGDisplayRemoveReconfigurationCallback(
+        callback: CGDisplayReconfigurationCallBack,
+        user_info: *mut raw::c_void,
+    ) -> CGError;
 }
 
 lazy_static! {
-    static ref NS_BITMAP: &'static Class = Class::get("NSBitmapImageRep").unwrap();
+    static ref NS_BITMAP: Option<&'static Class> = Class::get("NSBitmapImageRep");
 }
 
-type CGError = i32;
+pub(super) type CGError = i32;
+
+/// `kCGErrorIllegalArgument`
+const CG_ERROR_ILLEGAL_ARGUMENT: CGError = 1001;
+
+/// `kCGImageAlphaPremultipliedLast | kCGBitmapByteOrder32Big`, which lays out
+/// pixels as RGBA bytes
+const RGBA_BITMAP_INFO: u32 = 1 | (4 << 12);
 
 type CGDisplayListGetter = unsafe extern fn(u32, *mut Display, *mut u32) -> CGError;
 
-type CGImageRef = ptr::NonNull<raw::c_void>;
+pub(super) type CGImageRef = ptr::NonNull<raw::c_void>;
+
+type CGDisplayReconfigurationCallBack = unsafe extern fn(
+    display: Display,
+    flags: ReconfigureFlags,
+    user_info: *mut raw::c_void,
+);
 
 struct CGImage(CGImageRef);
 
@@ -104,21 +171,83 @@ impl From<CGRect> for Bounds {
     }
 }
 
+impl From<Bounds> for CGRect {
+    #[inline]
+    fn from(bounds: Bounds) -> CGRect {
+        let Bounds { origin: (x, y), size: (width, height) } = bounds;
+        CGRect::new(x as _, y as _, width as _, height as _)
+    }
+}
+
+impl Bounds {
+    /// Returns whether `point` lies within the bounds.
+    #[inline]
+    pub fn contains(&self, (x, y): (f64, f64)) -> bool {
+        let (ox, oy) = self.origin;
+        let (w, h) = self.size;
+        x >= ox && y >= oy && x < ox + w && y < oy + h
+    }
+}
+
+impl CGImage {
+    /// Draws the image into a new RGBA buffer.
+    #[inline]
+    fn to_image(&self) -> Result<Image> {
+        unsafe { draw_image(self.0) }
+    }
+}
+
+/// Draws `image`, which must be a valid `CGImageRef`, into a new RGBA buffer.
+pub(super) unsafe fn draw_image(image: CGImageRef) -> Result<Image> {
+    let (width, height) = (CGImageGetWidth(image), CGImageGetHeight(image));
+    let stride = width * 4;
+    let mut data = vec![0u8; stride * height];
+
+    let space = CGColorSpaceCreateDeviceRGB();
+    let context = CGBitmapContextCreate(
+        data.as_mut_ptr() as *mut raw::c_void,
+        width,
+        height,
+        8,
+        stride,
+        space,
+        RGBA_BITMAP_INFO,
+    );
+    CGColorSpaceRelease(space);
+    if context.is_null() {
+        return Err(OsError::from_code(CG_ERROR_ILLEGAL_ARGUMENT).into());
+    }
+    let rect = CGRect::new(0.0, 0.0, width as _, height as _);
+    CGContextDrawImage(context, rect, image);
+    CGContextRelease(context);
+
+    Image::new(width, height, stride, data)
+        .ok_or_else(|| OsError::from_code(CG_ERROR_ILLEGAL_ARGUMENT).into())
+}
+
 /// A monitor display.
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
 pub struct Display(u32);
 
-unsafe fn write_displays(get: CGDisplayListGetter, buf: &mut Vec<Display>) {
+pub(super) fn cg_result(err: CGError) -> Result<()> {
+    match err {
+        0 => Ok(()),
+        _ => Err(OsError::from_code(err).into()),
+    }
+}
+
+unsafe fn write_displays(
+    get: CGDisplayListGetter,
+    buf: &mut Vec<Display>,
+) -> Result<()> {
     buf.clear();
     let mut count = 0u32;
-    if get(0, ptr::null_mut(), &mut count) == 0 {
-        let len = count as usize;
-        buf.reserve(len);
-        if get(count, buf.as_mut_ptr(), &mut count) == 0 {
-            buf.set_len(len);
-        }
-    }
+    cg_result(get(0, ptr::null_mut(), &mut count))?;
+    buf.reserve(count as usize);
+    cg_result(get(count, buf.as_mut_ptr(), &mut count))?;
+    buf.set_len(count as usize);
+    Ok(())
 }
 
 impl Display {
@@ -149,10 +278,10 @@ impl Display {
     /// need access to all displays, including hardware mirrors, which are not
     /// drawable.
     #[inline]
-    pub fn online() -> Vec<Display> {
+    pub fn online() -> Result<Vec<Display>> {
         let mut buf = Vec::new();
-        Self::write_online(&mut buf);
-        buf
+        Self::write_online(&mut buf)?;
+        Ok(buf)
     }
 
     /// Returns all displays that are active (or drawable).
@@ -166,21 +295,21 @@ impl Display {
     /// mirroring is being used, all the mirrored displays are active and appear
     /// in the list.
     #[inline]
-    pub fn active() -> Vec<Display> {
+    pub fn active() -> Result<Vec<Display>> {
         let mut buf = Vec::new();
-        Self::write_active(&mut buf);
-        buf
+        Self::write_active(&mut buf)?;
+        Ok(buf)
     }
 
     /// Writes all online (active, mirrored, or sleeping) displays to `buf`
     /// after clearing it.
-    pub fn write_online(buf: &mut Vec<Display>) {
-        unsafe { write_displays(CGGetOnlineDisplayList, buf) };
+    pub fn write_online(buf: &mut Vec<Display>) -> Result<()> {
+        unsafe { write_displays(CGGetOnlineDisplayList, buf) }
     }
 
     /// Writes all active (or drawable) displays to `buf` after clearing it.
-    pub fn write_active(buf: &mut Vec<Display>) {
-        unsafe { write_displays(CGGetActiveDisplayList, buf) };
+    pub fn write_active(buf: &mut Vec<Display>) -> Result<()> {
+        unsafe { write_displays(CGGetActiveDisplayList, buf) }
     }
 
     /// Returns whether the display is active.
@@ -355,6 +484,49 @@ impl Display {
         self.colors(pos).next()
     }
 
+    /// Captures the contents of the entire display.
+    ///
+    /// The image is in pixels, which may be larger than the display's bounds
+    /// in points on high-resolution displays.
+    ///
+    /// Since macOS 10.15, the process must be allowed to record the screen;
+    /// otherwise only the desktop background and menu bar are captured.
+    #[inline]
+    pub fn capture(self) -> Result<Image> {
+        let Bounds { size, .. } = self.bounds();
+        self.capture_rect(Bounds { origin: (0.0, 0.0), size })
+    }
+
+    /// Captures the contents of `rect`, relative to the origin of the display.
+    pub fn capture_rect(self, rect: Bounds) -> Result<Image> {
+        metrics::timed(|r, d| r.capture_duration(d), || {
+            match unsafe { CGDisplayCreateImageForRect(self, rect.into()) } {
+                Some(image) => image.to_image(),
+                None => Err(OsError::from_code(CG_ERROR_ILLEGAL_ARGUMENT).into()),
+            }
+        })
+    }
+
+    /// Reads the colors of `rect`, relative to the origin of the display, from
+    /// a single capture.
+    ///
+    /// This is much faster than reading each location with
+    /// [`color_at`](#method.color_at), which captures the screen every time.
+    pub fn colors_in_rect(self, rect: Bounds) -> Result<ColorGrid> {
+        let image = self.capture_rect(rect)?;
+        let (width, _) = self.pixels();
+        let Bounds { size: (points, _), .. } = self.bounds();
+        let scale = if points > 0.0 { width as f64 / points } else { 1.0 };
+        Ok(ColorGrid::new(image, scale))
+    }
+
+    /// Returns a sampler for repeatedly reading the color at `pos`, relative
+    /// to the origin of the display.
+    #[inline]
+    pub fn sampler(self, pos: (f64, f64)) -> Result<Sampler> {
+        Sampler::new(self, pos)
+    }
+
     /// Returns an iterator over all colors at the location relative to the
     /// origin of the display.
     pub fn colors(self, pos: (f64, f64)) -> Colors {
@@ -384,6 +556,22 @@ impl Display {
         ) }
     }
 
+    /// Returns the number of pixels along each point of the display, such as 2
+    /// on a Retina display, which is the `backingScaleFactor` of its screen.
+    ///
+    /// Returns 1 if the display is not valid.
+    pub fn scale_factor(self) -> f64 {
+        unsafe {
+            let mode = CGDisplayCopyDisplayMode(self);
+            if mode.is_null() {
+                return 1.0;
+            }
+            let (points, pixels) = (CGDisplayModeGetWidth(mode), CGDisplayModeGetPixelWidth(mode));
+            CGDisplayModeRelease(mode);
+            if points > 0 { pixels as f64 / points as f64 } else { 1.0 }
+        }
+    }
+
     /// Returns the primary display in a hardware mirroring set.
     #[inline]
     pub fn primary(self) -> Display {
@@ -393,8 +581,8 @@ impl Display {
 
 /// An iterator over colors on a display.
 pub struct Colors {
-    /// An `NSBitmapImageRep` instance.
-    bitmap: NSObject,
+    /// An `NSBitmapImageRep` instance, if the class is available.
+    bitmap: Option<NSObject>,
     /// The display whose origin
     pub display: Display,
     /// An x-y position pair.
@@ -413,13 +601,13 @@ impl Iterator for Colors {
         let disp   = self.display;
         let rect   = CGRect::new(x as _, y as _, 1.0, 1.0);
         let image  = unsafe { CGDisplayCreateImageForRect(disp, rect) }?;
-        let bitmap = self.bitmap.inner();
+        let bitmap = self.bitmap.as_ref()?.inner();
 
         unsafe {
             let _: NSObjectRef = msg_send![bitmap, initWithCGImage:image];
 
             let mut pixel = [0usize; 4];
-            msg_send![bitmap, getPixel:pixel.as_mut_ptr() atX:0usize y:0usize];
+            let _: () = msg_send![bitmap, getPixel:pixel.as_mut_ptr() atX:0usize y:0usize];
 
             Some(Rgb {
                 red:   pixel[1] as _,
@@ -430,6 +618,73 @@ impl Iterator for Colors {
     }
 }
 
+/// Reads the color at a location on a display over and over, reusing the
+/// bitmap that the color is drawn into.
+///
+/// Each sample still captures the screen, but unlike
+/// [`Display::color_at`](struct.Display.html#method.color_at) it allocates
+/// nothing else.
+pub struct Sampler {
+    /// The display whose origin `pos` is relative to.
+    pub display: Display,
+    /// An x-y position pair.
+    pub pos: (f64, f64),
+    context: ptr::NonNull<raw::c_void>,
+    // Boxed so that the context's pointer to it stays valid when moved
+    pixel: Box<[u8; 4]>,
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        unsafe { CGContextRelease(self.context.as_ptr()) };
+    }
+}
+
+impl Sampler {
+    /// Creates a sampler for the display and position.
+    pub fn new(display: Display, pos: (f64, f64)) -> Result<Sampler> {
+        let mut pixel = Box::new([0u8; 4]);
+        let context = unsafe {
+            let space = CGColorSpaceCreateDeviceRGB();
+            let context = CGBitmapContextCreate(
+                pixel.as_mut_ptr() as *mut raw::c_void,
+                1,
+                1,
+                8,
+                4,
+                space,
+                RGBA_BITMAP_INFO,
+            );
+            CGColorSpaceRelease(space);
+            context
+        };
+        match ptr::NonNull::new(context) {
+            Some(context) => Ok(Sampler { display, pos, context, pixel }),
+            None => Err(OsError::from_code(CG_ERROR_ILLEGAL_ARGUMENT).into()),
+        }
+    }
+
+    /// Returns the current color at the position, or `None` if it can't be
+    /// read.
+    pub fn sample(&mut self) -> Option<Rgb> {
+        let (x, y) = self.pos;
+        if !(x.is_finite() && y.is_finite()) {
+            return None;
+        }
+        let rect = CGRect::new(x as _, y as _, 1.0, 1.0);
+        let image = unsafe { CGDisplayCreateImageForRect(self.display, rect) }?;
+        unsafe {
+            let (width, height) = (CGImageGetWidth(image.0), CGImageGetHeight(image.0));
+            // The image has a pixel per point per unit of scale; align its
+            // upper-left pixel with the context's only pixel
+            let rect = CGRect::new(0.0, 1.0 - height as f64, width as _, height as _);
+            CGContextDrawImage(self.context.as_ptr(), rect, image.0);
+        }
+        let [red, green, blue, _] = *self.pixel;
+        Some(Rgb { red, green, blue })
+    }
+}
+
 impl From<(f64, f64)> for Colors {
     #[inline]
     fn from(pos: (f64, f64)) -> Colors {
@@ -439,12 +694,457 @@ impl From<(f64, f64)> for Colors {
 
 impl Colors {
     /// Creates a new instance for the display and position.
+    ///
+    /// The iterator is empty if colors cannot be read on the running version
+    /// of macOS.
     pub fn new(display: Display, pos: (f64, f64)) -> Colors {
-        let bitmap = NSObject::alloc(&NS_BITMAP);
+        let bitmap = NS_BITMAP.map(NSObject::alloc);
         Colors { bitmap, display, pos }
     }
 }
 
+extern {
+    fn CGDisplayStreamCreate(
+        display: Display,
+        output_width: size_t,
+        output_height: size_t,
+        pixel_format: i32,
+        properties: Option<CFObjectRef>,
+        handler: *mut FrameHandler,
+    ) -> Option<CFObject>;
+
+    fn CGDisplayStreamGetRunLoopSource(stream: CFObjectRef) -> CFObjectRef;
+
+    fn CGDisplayStreamStart(stream: CFObjectRef) -> CGError;
+
+    fn CGDisplayStreamStop(stream: CFObjectRef) -> CGError;
+
+    static _NSConcreteGlobalBlock: raw::c_void;
+}
+
+#[link(name = "IOSurface", kind = "framework")]
+extern {
+    fn IOSurfaceLock(surface: CFObjectRef, options: u32, seed: *mut u32) -> i32;
+
+    fn IOSurfaceUnlock(surface: CFObjectRef, options: u32, seed: *mut u32) -> i32;
+
+    fn IOSurfaceGetBaseAddress(surface: CFObjectRef) -> *const u8;
+
+    fn IOSurfaceGetBytesPerRow(surface: CFObjectRef) -> size_t;
+
+    fn IOSurfaceGetWidth(surface: CFObjectRef) -> size_t;
+
+    fn IOSurfaceGetHeight(surface: CFObjectRef) -> size_t;
+
+    fn IOSurfaceIncrementUseCount(surface: CFObjectRef);
+
+    fn IOSurfaceDecrementUseCount(surface: CFObjectRef);
+}
+
+/// `kCGDisplayStreamFrameStatusFrameComplete`
+const FRAME_COMPLETE: i32 = 0;
+
+/// The `'BGRA'` pixel format
+const PIXEL_FORMAT_BGRA: i32 = 0x4247_5241;
+
+/// `kIOSurfaceLockReadOnly`
+const LOCK_READ_ONLY: u32 = 1;
+
+/// `BLOCK_IS_GLOBAL`, which makes copying a block return the block itself
+const BLOCK_IS_GLOBAL: i32 = 1 << 28;
+
+/// A surface delivered by a display stream, which the stream doesn't draw
+/// into again while it is held.
+#[derive(Debug)]
+struct Surface(CFObject);
+
+impl Surface {
+    unsafe fn hold(surface: CFObjectRef) -> Surface {
+        IOSurfaceIncrementUseCount(surface);
+        Surface(cf::retain(surface))
+    }
+}
+
+impl Drop for Surface {
+    fn drop(&mut self) {
+        unsafe { IOSurfaceDecrementUseCount((self.0).0) };
+    }
+}
+
+type LatestSurface = Arc<Mutex<Option<Surface>>>;
+
+#[repr(C)]
+struct BlockDescriptor {
+    reserved: libc::c_ulong,
+    size: libc::c_ulong,
+}
+
+/// A block literal for a `CGDisplayStreamFrameAvailableHandler`.
+///
+/// The block is flagged as global so that the stream's copy of it is the
+/// block itself, which the stream's owner keeps alive for as long as the
+/// stream.
+#[repr(C)]
+struct FrameHandler {
+    isa: *const raw::c_void,
+    flags: i32,
+    reserved: i32,
+    invoke: unsafe extern fn(*mut FrameHandler, i32, u64, Option<CFObjectRef>, *const raw::c_void),
+    descriptor: *const BlockDescriptor,
+    latest: LatestSurface,
+}
+
+static FRAME_HANDLER_DESCRIPTOR: BlockDescriptor = BlockDescriptor {
+    reserved: 0,
+    size: mem::size_of::<FrameHandler>() as libc::c_ulong,
+};
+
+unsafe extern fn frame_available(
+    handler: *mut FrameHandler,
+    status: i32,
+    _: u64,
+    surface: Option<CFObjectRef>,
+    _: *const raw::c_void,
+) {
+    if let (FRAME_COMPLETE, Some(surface)) = (status, surface) {
+        let surface = Surface::hold(surface);
+        *lock(&(*handler).latest) = Some(surface);
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A running display stream and the handler it calls, which live on the
+/// stream's run loop thread.
+struct StreamRegistration {
+    stream: CFObject,
+    _handler: Box<FrameHandler>,
+}
+
+impl Drop for StreamRegistration {
+    fn drop(&mut self) {
+        unsafe {
+            CGDisplayStreamStop(self.stream.0);
+            let source = CGDisplayStreamGetRunLoopSource(self.stream.0);
+            CFRunLoopRemoveSource(CFRunLoopGetCurrent(), source, kCFRunLoopCommonModes);
+        }
+    }
+}
+
+/// A stream of captures of an area of a display, which the window server
+/// delivers whenever the display's contents change.
+///
+/// Created by [`Display::stream`](struct.Display.html#method.stream).
+#[derive(Debug)]
+pub struct DisplayStream {
+    latest: LatestSurface,
+    rect: ((usize, usize), (usize, usize)),
+    _thread: LoopThread,
+}
+
+impl Display {
+    /// Starts streaming the contents of `rect`, relative to the origin of the
+    /// display.
+    ///
+    /// Like [`capture_rect`](#method.capture_rect), this requires permission
+    /// to record the screen since macOS 10.15.
+    pub fn stream(self, rect: Bounds) -> Result<DisplayStream> {
+        let (width, height) = self.pixels();
+        let Bounds { size: (points, _), .. } = self.bounds();
+        let scale = if points > 0.0 { width as f64 / points } else { 1.0 };
+        // Pixels partly inside the area are included, as when capturing it
+        let x = (rect.origin.0 * scale).floor().max(0.0).min(width as f64) as usize;
+        let y = (rect.origin.1 * scale).floor().max(0.0).min(height as f64) as usize;
+        let right = ((rect.origin.0 + rect.size.0) * scale).ceil().min(width as f64) as usize;
+        let bottom = ((rect.origin.1 + rect.size.1) * scale).ceil().min(height as f64) as usize;
+
+        let latest = LatestSurface::default();
+        let delivered = latest.clone();
+        let thread = LoopThread::spawn(move || -> Result<StreamRegistration> {
+            let mut handler = Box::new(FrameHandler {
+                isa: unsafe { &_NSConcreteGlobalBlock as *const raw::c_void },
+                flags: BLOCK_IS_GLOBAL,
+                reserved: 0,
+                invoke: frame_available,
+                descriptor: &FRAME_HANDLER_DESCRIPTOR,
+                latest: delivered,
+            });
+            let stream = unsafe {
+                CGDisplayStreamCreate(self, width, height, PIXEL_FORMAT_BGRA, None, &mut *handler)
+            }.ok_or_else(|| OsError::from_code(CG_ERROR_ILLEGAL_ARGUMENT))?;
+            unsafe {
+                let source = CGDisplayStreamGetRunLoopSource(stream.0);
+                CFRunLoopAddSource(CFRunLoopGetCurrent(), source, kCFRunLoopCommonModes);
+            }
+            let registration = StreamRegistration { stream, _handler: handler };
+            cg_result(unsafe { CGDisplayStreamStart(registration.stream.0) })?;
+            Ok(registration)
+        })?;
+        Ok(DisplayStream {
+            latest,
+            rect: ((x, y), (right.saturating_sub(x), bottom.saturating_sub(y))),
+            _thread: thread,
+        })
+    }
+}
+
+impl DisplayStream {
+    /// Returns the most recent contents of the area in pixels, or `None` if
+    /// the stream hasn't delivered any yet.
+    pub fn latest(&self) -> Option<Image> {
+        let latest = lock(&self.latest);
+        let surface = (latest.as_ref()?.0).0;
+        let ((x, y), (width, height)) = self.rect;
+        unsafe {
+            if IOSurfaceLock(surface, LOCK_READ_ONLY, ptr::null_mut()) != 0 {
+                return None;
+            }
+            let rows = IOSurfaceGetHeight(surface);
+            let image = if x + width <= IOSurfaceGetWidth(surface) && y + height <= rows {
+                let stride = IOSurfaceGetBytesPerRow(surface);
+                let pixels = slice::from_raw_parts(IOSurfaceGetBaseAddress(surface), stride * rows);
+                Image::crop_bgrx(pixels, stride, (x, y), (width, height))
+            } else {
+                None
+            };
+            IOSurfaceUnlock(surface, LOCK_READ_ONLY, ptr::null_mut());
+            image
+        }
+    }
+}
+
+bitflags! {
+    /// Flags describing how a display changed during reconfiguration.
+    #[repr(C)]
+    pub struct ReconfigureFlags: u32 {
+        /// The display is about to be reconfigured. Every other flag is
+        /// reported after the reconfiguration completes.
+        const BEGIN_CONFIGURATION = 1 << 0;
+        /// The location of the display's origin has changed.
+        const MOVED               = 1 << 1;
+        /// The display is now the main display.
+        const SET_MAIN            = 1 << 2;
+        /// The display mode has changed.
+        const SET_MODE            = 1 << 3;
+        /// The display has been added to the active display list.
+        const ADD                 = 1 << 4;
+        /// The display has been removed from the active display list.
+        const REMOVE              = 1 << 5;
+        /// The display has been enabled.
+        const ENABLED             = 1 << 8;
+        /// The display has been disabled.
+        const DISABLED            = 1 << 9;
+        /// The display is now mirroring another display.
+        const MIRROR              = 1 << 10;
+        /// The display is no longer mirroring another display.
+        const UNMIRROR            = 1 << 11;
+        /// The shape of the desktop (the union of display areas) has changed.
+        const DESKTOP_SHAPE_CHANGED = 1 << 12;
+    }
+}
+
+type ReconfigureCallback = Box<dyn FnMut(Display, ReconfigureFlags) + Send>;
+
+unsafe extern fn reconfigure_callback(
+    display: Display,
+    flags: ReconfigureFlags,
+    user_info: *mut raw::c_void,
+) {
+    let callback = &mut *(user_info as *mut ReconfigureCallback);
+    callback(display, flags);
+}
+
+struct Registration(Box<ReconfigureCallback>);
+
+impl Registration {
+    fn user_info(&mut self) -> *mut raw::c_void {
+        &mut *self.0 as *mut ReconfigureCallback as *mut raw::c_void
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let user_info = self.user_info();
+        unsafe {
+            CGDisplayRemoveReconfigurationCallback(reconfigure_callback, user_info)
+        };
+    }
+}
+
+/// A handle to a display reconfiguration callback.
+///
+/// The callback is unregistered when the handle is dropped.
+#[derive(Debug)]
+pub struct ReconfigureHandle {
+    _thread: LoopThread,
+}
+
+/// Registers `callback` to be called for every display that is reconfigured.
+///
+/// Quartz calls the callback once before and once after each display is
+/// reconfigured, which may happen many times for a single user-visible change
+/// such as plugging in a monitor. Use [`topology_events`](fn.topology_events.html)
+/// for a coalesced view.
+///
+/// The callback is called on a dedicated thread for as long as the returned
+/// handle lives.
+pub fn on_reconfigure<F>(callback: F) -> Result<ReconfigureHandle>
+    where F: FnMut(Display, ReconfigureFlags) + Send + 'static
+{
+    let thread = LoopThread::spawn(move || -> Result<Registration> {
+        let mut callback: Box<ReconfigureCallback> = Box::new(Box::new(callback));
+        let user_info = &mut *callback as *mut ReconfigureCallback as *mut raw::c_void;
+        cg_result(unsafe {
+            CGDisplayRegisterReconfigurationCallback(reconfigure_callback, user_info)
+        })?;
+        Ok(Registration(callback))
+    })?;
+    Ok(ReconfigureHandle { _thread: thread })
+}
+
+/// The location and size of each active display.
+pub type Layout = Vec<(Display, Bounds)>;
+
+fn current_layout() -> Layout {
+    Display::active()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|d| (d, d.bounds()))
+        .collect()
+}
+
+/// A stable change to a single display, as reported by
+/// [`topology_events`](fn.topology_events.html).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TopologyEvent {
+    /// A display became active.
+    Added(Display, Bounds),
+    /// A display is no longer active.
+    Removed(Display),
+    /// The origin of a display moved.
+    Moved {
+        /// The display that moved.
+        display: Display,
+        /// The previous origin.
+        from: (f64, f64),
+        /// The new origin.
+        to: (f64, f64),
+    },
+    /// The size of a display changed.
+    Resized {
+        /// The display that was resized.
+        display: Display,
+        /// The previous size.
+        from: (f64, f64),
+        /// The new size.
+        to: (f64, f64),
+    },
+}
+
+/// A coalesced set of display changes along with the resulting layout.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TopologyChange {
+    /// The changes since the previous layout.
+    pub events: Vec<TopologyEvent>,
+    /// The layout of the active displays after the changes.
+    pub layout: Layout,
+}
+
+fn diff_layouts(old: &[(Display, Bounds)], new: &[(Display, Bounds)]) -> Vec<TopologyEvent> {
+    let mut events = Vec::new();
+    for &(display, bounds) in new {
+        match old.iter().find(|&&(d, _)| d == display) {
+            None => events.push(TopologyEvent::Added(display, bounds)),
+            Some(&(_, prev)) => {
+                if prev.origin != bounds.origin {
+                    events.push(TopologyEvent::Moved {
+                        display,
+                        from: prev.origin,
+                        to: bounds.origin,
+                    });
+                }
+                if prev.size != bounds.size {
+                    events.push(TopologyEvent::Resized {
+                        display,
+                        from: prev.size,
+                        to: bounds.size,
+                    });
+                }
+            },
+        }
+    }
+    for &(display, _) in old {
+        if !new.iter().any(|&(d, _)| d == display) {
+            events.push(TopologyEvent::Removed(display));
+        }
+    }
+    events
+}
+
+/// A blocking iterator over coalesced display topology changes.
+///
+/// Created by [`topology_events`](fn.topology_events.html).
+#[derive(Debug)]
+pub struct TopologyEvents {
+    changes: mpsc::Receiver<TopologyChange>,
+    _handle: ReconfigureHandle,
+}
+
+impl Iterator for TopologyEvents {
+    type Item = TopologyChange;
+
+    #[inline]
+    fn next(&mut self) -> Option<TopologyChange> {
+        self.changes.recv().ok()
+    }
+}
+
+impl TopologyEvents {
+    /// Returns the next change if one is available without blocking.
+    #[inline]
+    pub fn try_next(&mut self) -> Option<TopologyChange> {
+        self.changes.try_recv().ok()
+    }
+}
+
+/// Returns a stream of display topology changes.
+///
+/// Raw reconfiguration notifications are coalesced until none have arrived
+/// for `debounce`, at which point the display layout is compared against the
+/// previous stable layout. Bursts that leave the layout unchanged produce no
+/// item.
+pub fn topology_events(debounce: Duration) -> Result<TopologyEvents> {
+    let (signal_tx, signal_rx) = mpsc::channel();
+    let (changes_tx, changes) = mpsc::channel();
+
+    let handle = on_reconfigure(move |_, _| {
+        let _ = signal_tx.send(());
+    })?;
+
+    let mut layout = current_layout();
+    thread::spawn(move || {
+        while signal_rx.recv().is_ok() {
+            while signal_rx.recv_timeout(debounce).is_ok() {}
+
+            let new_layout = current_layout();
+            let events = diff_layouts(&layout, &new_layout);
+            layout = new_layout;
+
+            if events.is_empty() {
+                continue;
+            }
+            let change = TopologyChange { events, layout: layout.clone() };
+            if changes_tx.send(change).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(TopologyEvents { changes, _handle: handle })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -460,6 +1160,22 @@ mod tests {
         assert_eq!(main, main.primary());
     }
 
+    #[test]
+    fn diff_layouts() {
+        let bounds = |x, w| Bounds { origin: (x, 0.0), size: (w, 100.0) };
+        let (a, b, c) = (Display(1), Display(2), Display(3));
+
+        let old = vec![(a, bounds(0.0, 100.0)), (b, bounds(100.0, 100.0))];
+        let new = vec![(a, bounds(0.0, 200.0)), (c, bounds(200.0, 50.0))];
+
+        assert_eq!(super::diff_layouts(&old, &new), vec![
+            TopologyEvent::Resized { display: a, from: (100.0, 100.0), to: (200.0, 100.0) },
+            TopologyEvent::Added(c, bounds(200.0, 50.0)),
+            TopologyEvent::Removed(b),
+        ]);
+        assert!(super::diff_layouts(&new, &new).is_empty());
+    }
+
     #[test]
     fn color_at() {
         use std::f64;
@@ -474,59 +1190,3 @@ mod tests {
         }
     }
 }
-
-#[cfg(all(test, nightly))]
-mod benches {
-    use super::*;
-    use test::{Bencher, black_box};
-
-    #[bench]
-    fn main_100(b: &mut Bencher) {
-        b.iter(|| {
-            for _ in 0..100 {
-                black_box(Display::main());
-            }
-        })
-    }
-
-    #[bench]
-    fn size_100(b: &mut Bencher) {
-        let display = Display::main();
-
-        b.iter(|| {
-            for _ in 0..100 {
-                let (x, y) = black_box(display).size();
-                black_box(x);
-                black_box(y);
-            }
-        })
-    }
-
-    #[bench]
-    fn online_100(b: &mut Bencher) {
-        b.iter(|| {
-            for _ in 0..100 {
-                black_box(Display::online());
-            }
-        });
-    }
-
-    #[bench]
-    fn write_online_100(b: &mut Bencher) {
-        b.iter(|| {
-            let mut buf = Vec::new();
-            for _ in 0..100 {
-                Display::write_online(black_box(&mut buf));
-            }
-        });
-    }
-
-    #[bench]
-    fn color_at(b: &mut Bencher) {
-        let display = Display::main();
-        let loc = (0.0, 0.0);
-        b.iter(|| {
-            black_box(display.color_at(black_box(loc)));
-        });
-    }
-}
@@ -0,0 +1,139 @@
+//! 🎛️ Event source utilities.
+//!
+//! Events created without a source are marked as synthetic in a way that some
+//! applications ignore. Creating them from an [`EventSource`](struct.EventSource.html)
+//! makes them carry the state of a real input source instead.
+
+use std::fmt;
+
+use objc::runtime::Object;
+
+use super::{CFObject, CFObjectRef, CGEventSource};
+use error::{Error, Result};
+
+extern {
+    fn CGEventSourceCreate(state: SourceState) -> Option<CFObject>;
+
+    fn CGEventSourceGetUserData(source: CFObjectRef) -> i64;
+
+    fn CGEventSourceSetUserData(source: CFObjectRef, user_data: i64);
+
+    fn CGEventSourceGetPixelsPerLine(source: CFObjectRef) -> f64;
+
+    fn CGEventSourceSetPixelsPerLine(source: CFObjectRef, pixels_per_line: f64);
+
+    fn CGEventSourceSecondsSinceLastEventType(state: SourceState, event_type: u32) -> f64;
+
+    fn CGEventSourceKeyState(state: SourceState, key: u16) -> bool;
+
+    fn CGEventSourceButtonState(state: SourceState, button: u32) -> bool;
+}
+
+/// `kCGAnyInputEventType`
+const ANY_INPUT_EVENT_TYPE: u32 = !0;
+
+/// The input state that an [`EventSource`](struct.EventSource.html) shares
+/// with other sources.
+#[repr(i32)]
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum SourceState {
+    /// State private to the source.
+    Private = -1,
+    /// The combined state of all sources posting to the current session.
+    CombinedSession = 0,
+    /// The state of the hardware devices attached to the system.
+    HidSystem = 1,
+}
+
+impl SourceState {
+    /// Returns the number of seconds since the last input event of any kind
+    /// reached the state.
+    ///
+    /// Use [`HidSystem`](#variant.HidSystem) to only count input from the
+    /// hardware, ignoring events posted by applications.
+    #[inline]
+    pub fn seconds_since_last_input(self) -> f64 {
+        unsafe { CGEventSourceSecondsSinceLastEventType(self, ANY_INPUT_EVENT_TYPE) }
+    }
+
+    /// Returns whether the virtual key `key` is held down in the state.
+    #[inline]
+    pub fn is_key_down(self, key: u16) -> bool {
+        unsafe { CGEventSourceKeyState(self, key) }
+    }
+
+    /// Returns whether the mouse button numbered `button` is held down in the
+    /// state, where left is 0, right is 1, and middle is 2.
+    #[inline]
+    pub fn is_button_down(self, button: u32) -> bool {
+        unsafe { CGEventSourceButtonState(self, button) }
+    }
+}
+
+/// A source that Quartz events can be created from.
+pub struct EventSource(CFObject);
+
+impl fmt::Debug for EventSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EventSource")
+            .field("user_data", &self.user_data())
+            .finish()
+    }
+}
+
+impl EventSource {
+    /// Creates a source that shares `state`.
+    ///
+    /// Returns [`Error::Unsupported`](../../error/enum.Error.html#variant.Unsupported)
+    /// if Quartz could not create the source.
+    pub fn new(state: SourceState) -> Result<EventSource> {
+        unsafe { CGEventSourceCreate(state) }.map(EventSource).ok_or(Error::Unsupported)
+    }
+
+    /// Creates a source that shares the state of the hardware devices, so its
+    /// events look like they came from them.
+    #[inline]
+    pub fn hid() -> Result<EventSource> {
+        EventSource::new(SourceState::HidSystem)
+    }
+
+    /// Creates a source that shares the combined state of the current
+    /// session.
+    #[inline]
+    pub fn session() -> Result<EventSource> {
+        EventSource::new(SourceState::CombinedSession)
+    }
+
+    /// Returns the value stored in the `SourceUserData` field of events
+    /// created from the source.
+    #[inline]
+    pub fn user_data(&self) -> i64 {
+        unsafe { CGEventSourceGetUserData((self.0).0) }
+    }
+
+    /// Sets the value stored in the `SourceUserData` field of events created
+    /// from the source, which can be used to recognize them in an event tap.
+    #[inline]
+    pub fn set_user_data(&mut self, data: i64) {
+        unsafe { CGEventSourceSetUserData((self.0).0, data) };
+    }
+
+    /// Returns how many pixels line-based scroll wheel events from the source
+    /// scroll by.
+    #[inline]
+    pub fn pixels_per_line(&self) -> f64 {
+        unsafe { CGEventSourceGetPixelsPerLine((self.0).0) }
+    }
+
+    /// Sets how many pixels line-based scroll wheel events from the source
+    /// scroll by.
+    #[inline]
+    pub fn set_pixels_per_line(&mut self, pixels: f64) {
+        unsafe { CGEventSourceSetPixelsPerLine((self.0).0, pixels) };
+    }
+
+    #[inline]
+    pub(crate) fn as_raw(&self) -> CGEventSource {
+        (self.0).0.as_ptr() as *const Object
+    }
+}
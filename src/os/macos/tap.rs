@@ -0,0 +1,529 @@
+//! 👂 Event tap utilities for observing and filtering input events.
+//!
+//! Event taps require the process to be trusted for accessibility, otherwise
+//! [`Tap::new`](struct.Tap.html#method.new) returns `None`.
+//!
+//! The system disables a tap whose callback takes too long, after which it
+//! silently stops receiving events. Each tap has a
+//! [`Watchdog`](struct.Watchdog.html) that turns it back on and reports when
+//! this happens or is about to.
+
+use std::{fmt, mem, ptr, thread};
+use std::os::raw;
+use std::time::{Duration, Instant};
+
+use super::{cf, CFObject, CFObjectRef, Event, EventLocation, EventType};
+use super::run_loop::*;
+use metrics;
+
+type CGEventTapCallBack = unsafe extern fn(
+    proxy: *mut raw::c_void,
+    event_type: u32,
+    event: CFObjectRef,
+    user_info: *mut raw::c_void,
+) -> *mut raw::c_void;
+
+extern {
+    fn CGEventTapCreate(
+        tap: u32,
+        place: u32,
+        options: u32,
+        events_of_interest: EventMask,
+        callback: CGEventTapCallBack,
+        user_info: *mut raw::c_void,
+    ) -> Option<CFObject>;
+
+    fn CGEventTapEnable(tap: CFObjectRef, enable: bool);
+
+    fn CGEventTapIsEnabled(tap: CFObjectRef) -> bool;
+
+    fn CFMachPortCreateRunLoopSource(
+        allocator: *const raw::c_void,
+        port: CFObjectRef,
+        order: isize,
+    ) -> Option<CFObject>;
+
+    fn CFMachPortInvalidate(port: CFObjectRef);
+
+    fn CFRetain(obj: CFObjectRef) -> CFObjectRef;
+}
+
+bitflags! {
+    /// A mask identifying the set of events to be observed by an event tap.
+    #[repr(C)]
+    pub struct EventMask: u64 {
+        /// Left mouse button presses.
+        const LEFT_MOUSE_DOWN     = 1 << 1;
+        /// Left mouse button releases.
+        const LEFT_MOUSE_UP       = 1 << 2;
+        /// Right mouse button presses.
+        const RIGHT_MOUSE_DOWN    = 1 << 3;
+        /// Right mouse button releases.
+        const RIGHT_MOUSE_UP      = 1 << 4;
+        /// Mouse movements.
+        const MOUSE_MOVED         = 1 << 5;
+        /// Mouse movements with the left button pressed.
+        const LEFT_MOUSE_DRAGGED  = 1 << 6;
+        /// Mouse movements with the right button pressed.
+        const RIGHT_MOUSE_DRAGGED = 1 << 7;
+        /// Key presses.
+        const KEY_DOWN            = 1 << 10;
+        /// Key releases.
+        const KEY_UP              = 1 << 11;
+        /// Modifier key changes.
+        const FLAGS_CHANGED       = 1 << 12;
+        /// Scroll wheel changes.
+        const SCROLL_WHEEL        = 1 << 22;
+        /// Tablet pointer events.
+        const TABLET_POINTER      = 1 << 23;
+        /// Tablet proximity events.
+        const TABLET_PROXIMITY    = 1 << 24;
+        /// Presses of mouse buttons other than left or right.
+        const OTHER_MOUSE_DOWN    = 1 << 25;
+        /// Releases of mouse buttons other than left or right.
+        const OTHER_MOUSE_UP      = 1 << 26;
+        /// Mouse movements with a button other than left or right pressed.
+        const OTHER_MOUSE_DRAGGED = 1 << 27;
+        /// Trackpad gestures, such as pinches, whose fields are
+        /// undocumented.
+        const GESTURE             = 1 << 29;
+        /// Force Touch pressure changes.
+        const PRESSURE            = 1 << 34;
+
+        /// All keyboard events.
+        const KEYBOARD = Self::KEY_DOWN.bits
+                       | Self::KEY_UP.bits
+                       | Self::FLAGS_CHANGED.bits;
+
+        /// All mouse button, movement, and scroll wheel events.
+        const MOUSE = Self::LEFT_MOUSE_DOWN.bits
+                    | Self::LEFT_MOUSE_UP.bits
+                    | Self::RIGHT_MOUSE_DOWN.bits
+                    | Self::RIGHT_MOUSE_UP.bits
+                    | Self::MOUSE_MOVED.bits
+                    | Self::LEFT_MOUSE_DRAGGED.bits
+                    | Self::RIGHT_MOUSE_DRAGGED.bits
+                    | Self::SCROLL_WHEEL.bits
+                    | Self::OTHER_MOUSE_DOWN.bits
+                    | Self::OTHER_MOUSE_UP.bits
+                    | Self::OTHER_MOUSE_DRAGGED.bits;
+    }
+}
+
+impl From<EventType> for EventMask {
+    #[inline]
+    fn from(ty: EventType) -> EventMask {
+        match ty {
+            EventType::Null |
+            EventType::TapDisabledByTimeout |
+            EventType::TapDisabledByUserInput => EventMask::empty(),
+            ty => EventMask::from_bits_truncate(1 << ty as u64),
+        }
+    }
+}
+
+/// Where a new event tap is inserted into the list of active taps.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum Placement {
+    /// The tap is inserted before any pre-existing taps at the same location.
+    Head,
+    /// The tap is inserted after any pre-existing taps at the same location.
+    Tail,
+}
+
+/// Whether an event tap is able to modify events.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum Mode {
+    /// The tap is an active filter that may modify or discard events.
+    Filter,
+    /// The tap is a passive listener; the callback's return value is ignored.
+    ListenOnly,
+}
+
+/// What an event tap does with an event after its callback returns.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum Action {
+    /// Passes the (possibly modified) event on to the event system.
+    Keep,
+    /// Discards the event. Has no effect for listen-only taps.
+    Discard,
+}
+
+type Callback = Box<dyn FnMut(&mut Event) -> Action + Send>;
+
+/// Why the system disabled an event tap.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum DisableReason {
+    /// The tap's callback took too long to return.
+    Timeout,
+    /// The user enabled secure input, such as by focusing a password field.
+    UserInput,
+}
+
+/// The delay before re-enabling a tap that was disabled again shortly after
+/// being re-enabled, which doubles with each repeat.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(10);
+
+/// How long a tap must stay enabled for its backoff to start over.
+const BACKOFF_RESET: Duration = Duration::from_secs(30);
+
+/// How an event tap recovers from being disabled by the system and reports
+/// callbacks that risk it.
+///
+/// By default, a disabled tap is re-enabled right away the first time, and
+/// after a delay that doubles up to 5 seconds if it keeps being disabled.
+/// Callbacks taking longer than 100 milliseconds are reported, well before
+/// the system's own timeout of about a second.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use auto::os::EventLocation;
+/// use auto::os::tap::{Action, EventMask, Mode, Placement, Tap, Watchdog};
+///
+/// let mut watchdog = Watchdog::new();
+/// watchdog
+///     .budget(Duration::from_millis(20))
+///     .on_slow_callback(|took| eprintln!("tap callback took {:?}", took))
+///     .on_disabled(|reason| eprintln!("tap disabled: {:?}", reason));
+///
+/// let handle = Tap::spawn_watched(
+///     EventLocation::Session,
+///     Placement::Head,
+///     Mode::ListenOnly,
+///     EventMask::KEYBOARD,
+///     watchdog,
+///     |_event| Action::Keep,
+/// );
+/// ```
+pub struct Watchdog {
+    reenable: bool,
+    max_backoff: Duration,
+    budget: Duration,
+    on_disabled: Option<Box<dyn FnMut(DisableReason) + Send>>,
+    on_slow_callback: Option<Box<dyn FnMut(Duration) + Send>>,
+}
+
+impl Default for Watchdog {
+    fn default() -> Watchdog {
+        Watchdog {
+            reenable: true,
+            max_backoff: Duration::from_secs(5),
+            budget: Duration::from_millis(100),
+            on_disabled: None,
+            on_slow_callback: None,
+        }
+    }
+}
+
+impl fmt::Debug for Watchdog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Watchdog")
+            .field("reenable", &self.reenable)
+            .field("max_backoff", &self.max_backoff)
+            .field("budget", &self.budget)
+            .finish()
+    }
+}
+
+impl Watchdog {
+    /// Creates the default watchdog.
+    #[inline]
+    pub fn new() -> Watchdog {
+        Watchdog::default()
+    }
+
+    /// Sets whether a disabled tap is re-enabled.
+    pub fn reenable(&mut self, reenable: bool) -> &mut Watchdog {
+        self.reenable = reenable;
+        self
+    }
+
+    /// Sets the longest delay before re-enabling a tap that keeps being
+    /// disabled.
+    pub fn max_backoff(&mut self, max: Duration) -> &mut Watchdog {
+        self.max_backoff = max;
+        self
+    }
+
+    /// Sets how long a callback may take before it is reported as slow.
+    pub fn budget(&mut self, budget: Duration) -> &mut Watchdog {
+        self.budget = budget;
+        self
+    }
+
+    /// Calls `f` on the tap's thread whenever the system disables the tap.
+    pub fn on_disabled<F>(&mut self, f: F) -> &mut Watchdog
+        where F: FnMut(DisableReason) + Send + 'static
+    {
+        self.on_disabled = Some(Box::new(f));
+        self
+    }
+
+    /// Calls `f` on the tap's thread with the duration of each callback that
+    /// exceeds the budget.
+    pub fn on_slow_callback<F>(&mut self, f: F) -> &mut Watchdog
+        where F: FnMut(Duration) + Send + 'static
+    {
+        self.on_slow_callback = Some(Box::new(f));
+        self
+    }
+}
+
+/// What the callback of a tap works with, which lives at a stable address.
+struct State {
+    callback: Callback,
+    watchdog: Watchdog,
+    port: Option<CFObjectRef>,
+    disables: u32,
+    last_disable: Option<Instant>,
+}
+
+impl State {
+    fn disabled(&mut self, reason: DisableReason) {
+        let now = Instant::now();
+        if self.last_disable.is_none_or(|last| now.duration_since(last) > BACKOFF_RESET) {
+            self.disables = 0;
+        }
+        self.disables += 1;
+        self.last_disable = Some(now);
+
+        if let Some(ref mut on_disabled) = self.watchdog.on_disabled {
+            on_disabled(reason);
+        }
+        let port = match self.port {
+            Some(port) if self.watchdog.reenable => port,
+            _ => return,
+        };
+        if self.disables == 1 {
+            unsafe { CGEventTapEnable(port, true) };
+            return;
+        }
+        let doublings = (self.disables - 2).min(16);
+        let delay = (INITIAL_BACKOFF * (1 << doublings)).min(self.watchdog.max_backoff);
+        // Waiting here would hold up everything else on the run loop
+        let port = cf::retain(port);
+        thread::spawn(move || {
+            thread::sleep(delay);
+            unsafe { CGEventTapEnable(port.0, true) };
+        });
+    }
+
+    fn call(&mut self, event: &mut Event) -> Action {
+        let start = Instant::now();
+        let action = (self.callback)(event);
+        let took = start.elapsed();
+        metrics::record(|r| r.tap_callback_duration(took));
+        if took > self.watchdog.budget {
+            if let Some(ref mut on_slow_callback) = self.watchdog.on_slow_callback {
+                on_slow_callback(took);
+            }
+        }
+        action
+    }
+}
+
+/// An event tap that calls back into Rust for every event matching its mask.
+///
+/// The tap only receives events while it is attached to a running run loop.
+/// Use [`spawn`](#method.spawn) to run it on a dedicated thread.
+pub struct Tap {
+    port: CFObject,
+    source: CFObject,
+    // Boxed so that the pointer given to Quartz stays stable
+    state: Box<State>,
+}
+
+impl fmt::Debug for Tap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Tap").field("port", &self.port).finish()
+    }
+}
+
+unsafe extern fn tap_callback(
+    _proxy: *mut raw::c_void,
+    event_type: u32,
+    event: CFObjectRef,
+    user_info: *mut raw::c_void,
+) -> *mut raw::c_void {
+    let state = &mut *(user_info as *mut State);
+
+    let reason = match EventType::from_raw(event_type) {
+        EventType::TapDisabledByTimeout => Some(DisableReason::Timeout),
+        EventType::TapDisabledByUserInput => Some(DisableReason::UserInput),
+        _ => None,
+    };
+    if let Some(reason) = reason {
+        state.disabled(reason);
+        return event.as_ptr();
+    }
+
+    // Hold our own reference so that the callback may freely replace or drop
+    // the event it is handed
+    let mut wrapped = Event(CFObject(CFRetain(event)));
+    let action = state.call(&mut wrapped);
+    let result = (wrapped.0).0;
+
+    match action {
+        Action::Discard => ptr::null_mut(),
+        Action::Keep if result == event => result.as_ptr(),
+        Action::Keep => {
+            // A new event is released by the event system after being posted
+            mem::forget(wrapped);
+            result.as_ptr()
+        },
+    }
+}
+
+impl Tap {
+    /// Creates a new event tap at `location` for events within `mask`.
+    ///
+    /// Returns `None` if the tap could not be created, which happens when the
+    /// process is not trusted for accessibility or if `location` is
+    /// [`Hid`](../enum.EventLocation.html#variant.Hid) and the process is not
+    /// running as root.
+    ///
+    /// The tap is watched by the default [`Watchdog`](struct.Watchdog.html).
+    pub fn new<F>(
+        location: EventLocation,
+        placement: Placement,
+        mode: Mode,
+        mask: EventMask,
+        callback: F,
+    ) -> Option<Tap>
+        where F: FnMut(&mut Event) -> Action + Send + 'static
+    {
+        let mut state = Box::new(State {
+            callback: Box::new(callback),
+            watchdog: Watchdog::default(),
+            port: None,
+            disables: 0,
+            last_disable: None,
+        });
+        let user_info = &mut *state as *mut State as *mut raw::c_void;
+        unsafe {
+            let port = CGEventTapCreate(
+                location as u32,
+                placement as u32,
+                mode as u32,
+                mask,
+                tap_callback,
+                user_info,
+            )?;
+            let source = CFMachPortCreateRunLoopSource(ptr::null(), port.0, 0)?;
+            state.port = Some(port.0);
+            Some(Tap { port, source, state })
+        }
+    }
+
+    /// Replaces the watchdog of the tap.
+    #[inline]
+    pub fn set_watchdog(&mut self, watchdog: Watchdog) {
+        self.state.watchdog = watchdog;
+    }
+
+    /// Creates a new event tap and runs it on a new thread until the returned
+    /// handle is stopped or dropped.
+    #[inline]
+    pub fn spawn<F>(
+        location: EventLocation,
+        placement: Placement,
+        mode: Mode,
+        mask: EventMask,
+        callback: F,
+    ) -> Option<TapHandle>
+        where F: FnMut(&mut Event) -> Action + Send + 'static
+    {
+        Tap::spawn_watched(location, placement, mode, mask, Watchdog::default(), callback)
+    }
+
+    /// Creates a new event tap watched by `watchdog` and runs it on a new
+    /// thread until the returned handle is stopped or dropped.
+    pub fn spawn_watched<F>(
+        location: EventLocation,
+        placement: Placement,
+        mode: Mode,
+        mask: EventMask,
+        watchdog: Watchdog,
+        callback: F,
+    ) -> Option<TapHandle>
+        where F: FnMut(&mut Event) -> Action + Send + 'static
+    {
+        let thread: Result<_, ()> = LoopThread::spawn(move || {
+            let tap = Tap::new(location, placement, mode, mask, callback);
+            let mut tap = tap.ok_or(())?;
+            tap.set_watchdog(watchdog);
+            tap.add_to_current_run_loop();
+            Ok(tap)
+        });
+        thread.ok().map(|thread| TapHandle { thread })
+    }
+
+    /// Returns whether the tap is enabled.
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        unsafe { CGEventTapIsEnabled(self.port.0) }
+    }
+
+    /// Enables or disables the tap.
+    #[inline]
+    pub fn set_enabled(&self, enabled: bool) {
+        unsafe { CGEventTapEnable(self.port.0, enabled) };
+    }
+
+    /// Attaches the tap to the current thread's run loop.
+    ///
+    /// Events are only delivered while the run loop is running, such as within
+    /// [`run_current_loop`](fn.run_current_loop.html).
+    pub fn add_to_current_run_loop(&self) {
+        unsafe {
+            CFRunLoopAddSource(
+                CFRunLoopGetCurrent(),
+                self.source.0,
+                kCFRunLoopCommonModes,
+            );
+        }
+    }
+
+    /// Detaches the tap from the current thread's run loop.
+    pub fn remove_from_current_run_loop(&self) {
+        unsafe {
+            CFRunLoopRemoveSource(
+                CFRunLoopGetCurrent(),
+                self.source.0,
+                kCFRunLoopCommonModes,
+            );
+        }
+    }
+}
+
+impl Drop for Tap {
+    fn drop(&mut self) {
+        unsafe { CFMachPortInvalidate(self.port.0) };
+    }
+}
+
+/// Runs the current thread's run loop until it is stopped.
+///
+/// Returns immediately if no sources are attached to the run loop.
+#[inline]
+pub fn run_current_loop() {
+    unsafe { CFRunLoopRun() };
+}
+
+/// A handle to an event tap running on its own thread.
+///
+/// The tap is stopped when the handle is dropped.
+#[derive(Debug)]
+pub struct TapHandle {
+    thread: LoopThread,
+}
+
+impl TapHandle {
+    /// Stops the tap and waits for its thread to exit.
+    #[inline]
+    pub fn stop(mut self) {
+        self.thread.stop();
+    }
+}
@@ -0,0 +1,304 @@
+//! 🔤 Text recognition utilities.
+//!
+//! Text is recognized with the Vision framework, which is loaded the first
+//! time it is needed and requires macOS 10.15 or later. Bounds are in pixels
+//! from the upper-left corner of the image.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use auto::os::screen::Display;
+//! use auto::os::text;
+//!
+//! let image = Display::main().capture().unwrap();
+//! for line in text::recognize(&image).unwrap() {
+//!     println!("{:?} at {:?}", line.text, line.bounds);
+//! }
+//! ```
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+
+use libc::size_t;
+use objc::rc::autoreleasepool;
+use objc::runtime::{Class, Object, BOOL, NO, YES};
+
+use super::{cf, CGFloat, CGRect};
+use super::screen::{Bounds, CGImageRef};
+use error::{Error, Result};
+use image::Image;
+
+extern {
+    fn CGDataProviderCreateWithCFData(data: *mut c_void) -> *mut c_void;
+
+    fn CGDataProviderRelease(provider: *mut c_void);
+
+    fn CGColorSpaceCreateDeviceRGB() -> *mut c_void;
+
+    fn CGColorSpaceRelease(space: *mut c_void);
+
+    fn CGImageCreate(
+        width: size_t,
+        height: size_t,
+        bits_per_component: size_t,
+        bits_per_pixel: size_t,
+        bytes_per_row: size_t,
+        space: *mut c_void,
+        bitmap_info: u32,
+        provider: *mut c_void,
+        decode: *const CGFloat,
+        should_interpolate: bool,
+        intent: i32,
+    ) -> Option<CGImageRef>;
+
+    fn CGImageRelease(image: CGImageRef);
+}
+
+/// `kCGImageAlphaPremultipliedLast | kCGBitmapByteOrder32Big`, which lays out
+/// pixels as RGBA bytes
+const RGBA_BITMAP_INFO: u32 = 1 | (4 << 12);
+
+/// `VNRequestTextRecognitionLevelAccurate`
+const RECOGNITION_LEVEL_ACCURATE: isize = 0;
+
+/// `NSRange`
+#[repr(C)]
+struct NSRange {
+    location: usize,
+    length: usize,
+}
+
+lazy_static! {
+    /// The Vision classes used, which are missing before macOS 10.15.
+    static ref VISION: Option<(&'static Class, &'static Class)> = unsafe {
+        let path = b"/System/Library/Frameworks/Vision.framework/Vision\0";
+        libc::dlopen(path.as_ptr() as *const c_char, libc::RTLD_LAZY);
+        Some((Class::get("VNImageRequestHandler")?, Class::get("VNRecognizeTextRequest")?))
+    };
+}
+
+/// A line of text recognized in an image.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    /// The text of the line.
+    pub text: String,
+    /// The area of the image covered by the line.
+    pub bounds: Bounds,
+    /// How confident the recognition is, ranging from 0 to 1.
+    pub confidence: f32,
+}
+
+/// Creates a `CGImage` from a copy of the pixels of `image`.
+unsafe fn cg_image(image: &Image) -> Result<CGImageRef> {
+    let data = cf::data(image.data());
+    let provider = CGDataProviderCreateWithCFData((data.0).as_ptr());
+    if provider.is_null() {
+        return Err(Error::Failed);
+    }
+    let space = CGColorSpaceCreateDeviceRGB();
+    let created = CGImageCreate(
+        image.width(),
+        image.height(),
+        8,
+        32,
+        image.stride(),
+        space,
+        RGBA_BITMAP_INFO,
+        provider,
+        ptr::null(),
+        false,
+        0,
+    );
+    CGColorSpaceRelease(space);
+    CGDataProviderRelease(provider);
+    created.ok_or(Error::Failed)
+}
+
+/// Converts a normalized rectangle with its origin at the lower-left corner
+/// of `image` into bounds in pixels.
+fn pixel_bounds(rect: CGRect, image: &Image) -> Bounds {
+    let (width, height) = (image.width() as f64, image.height() as f64);
+    let (x, y): (f64, f64) = (rect.origin.x as _, rect.origin.y as _);
+    let (w, h): (f64, f64) = (rect.size.width as _, rect.size.height as _);
+    Bounds {
+        origin: (x * width, (1.0 - y - h) * height),
+        size: (w * width, h * height),
+    }
+}
+
+/// Recognizes the text in `image`, calling `visit` with the best candidate
+/// of each line, its text, and the bounds of the line until it returns
+/// `false`.
+fn each_line<F>(image: &Image, mut visit: F) -> Result<()>
+    where F: FnMut(&Object, String, Bounds) -> bool
+{
+    let (handler_class, request_class) = VISION.ok_or(Error::Unsupported)?;
+    if image.width() == 0 || image.height() == 0 {
+        return Ok(());
+    }
+    unsafe {
+        let cg_image = cg_image(image)?;
+        let result = autoreleasepool(|| {
+            let options: *mut Object = msg_send![class!(NSDictionary), dictionary];
+            let handler: *mut Object = msg_send![handler_class, alloc];
+            let handler: *mut Object = msg_send![handler, initWithCGImage:cg_image options:options];
+            let request: *mut Object = msg_send![request_class, new];
+            if handler.is_null() || request.is_null() {
+                return Err(Error::Failed);
+            }
+            let _: () = msg_send![request, setRecognitionLevel:RECOGNITION_LEVEL_ACCURATE];
+            let _: () = msg_send![request, setUsesLanguageCorrection:YES];
+            let requests: *mut Object = msg_send![class!(NSArray), arrayWithObject:request];
+            let mut error: *mut Object = ptr::null_mut();
+            let ok: BOOL = msg_send![handler, performRequests:requests error:&mut error];
+
+            let result = if ok == NO {
+                Err(Error::Failed)
+            } else {
+                let observations: *mut Object = msg_send![request, results];
+                let count: usize = if observations.is_null() { 0 } else { msg_send![observations, count] };
+                for i in 0..count {
+                    let observation: *mut Object = msg_send![observations, objectAtIndex:i];
+                    let candidates: *mut Object = msg_send![observation, topCandidates:1usize];
+                    let candidate: *mut Object = msg_send![candidates, firstObject];
+                    if candidate.is_null() {
+                        continue;
+                    }
+                    let string: *mut Object = msg_send![candidate, string];
+                    if string.is_null() {
+                        continue;
+                    }
+                    // Strings that can't be converted have no UTF-8 form
+                    let utf8: *const c_char = msg_send![string, UTF8String];
+                    if utf8.is_null() {
+                        continue;
+                    }
+                    let text = CStr::from_ptr(utf8).to_string_lossy().into_owned();
+                    let rect: CGRect = msg_send![observation, boundingBox];
+                    if !visit(&*candidate, text, pixel_bounds(rect, image)) {
+                        break;
+                    }
+                }
+                Ok(())
+            };
+            let _: () = msg_send![request, release];
+            let _: () = msg_send![handler, release];
+            result
+        });
+        CGImageRelease(cg_image);
+        result
+    }
+}
+
+/// Sorts `lines` from top to bottom, and lines with the same top from left
+/// to right.
+fn sort_lines(lines: &mut [Line]) {
+    lines.sort_by(|a, b| {
+        let (a, b) = (a.bounds.origin, b.bounds.origin);
+        a.1.total_cmp(&b.1).then(a.0.total_cmp(&b.0))
+    });
+}
+
+/// Returns the lines of text recognized in `image`, from top to bottom.
+pub fn recognize(image: &Image) -> Result<Vec<Line>> {
+    let mut lines = Vec::new();
+    each_line(image, |candidate, text, bounds| {
+        let confidence: f32 = unsafe { msg_send![candidate, confidence] };
+        lines.push(Line { text, bounds, confidence });
+        true
+    })?;
+    // Vision doesn't order its results by position
+    sort_lines(&mut lines);
+    Ok(lines)
+}
+
+/// Returns the byte range of the first occurrence of `needle` in `haystack`,
+/// ignoring case.
+fn find_ignoring_case(haystack: &str, needle: &str) -> Option<(usize, usize)> {
+    let needle: Vec<char> = needle.chars().flat_map(char::to_lowercase).collect();
+    if needle.is_empty() {
+        return None;
+    }
+    haystack.char_indices().find_map(|(start, _)| {
+        let mut matched = 0;
+        for (offset, c) in haystack[start..].char_indices() {
+            for lower in c.to_lowercase() {
+                if needle.get(matched) != Some(&lower) {
+                    return None;
+                }
+                matched += 1;
+            }
+            if matched == needle.len() {
+                return Some((start, start + offset + c.len_utf8()));
+            }
+        }
+        None
+    })
+}
+
+/// Returns the bounds of the first occurrence of `text` in `image`, ignoring
+/// case, or `None` if it wasn't recognized.
+///
+/// When `text` is part of a longer line, the bounds only cover that part.
+pub fn find(image: &Image, text: &str) -> Result<Option<Bounds>> {
+    let mut found = None;
+    each_line(image, |candidate, line, bounds| {
+        let (start, end) = match find_ignoring_case(&line, text) {
+            Some(range) => range,
+            None => return true,
+        };
+        // Ranges within the string are in UTF-16 code units
+        let range = NSRange {
+            location: line[..start].encode_utf16().count(),
+            length: line[start..end].encode_utf16().count(),
+        };
+        let mut error: *mut Object = ptr::null_mut();
+        let part: *mut Object = unsafe { msg_send![candidate, boundingBoxForRange:range error:&mut error] };
+        found = Some(if part.is_null() {
+            bounds
+        } else {
+            let rect: CGRect = unsafe { msg_send![part, boundingBox] };
+            pixel_bounds(rect, image)
+        });
+        false
+    })?;
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bounds, CGRect, Image, Line};
+
+    #[test]
+    fn find_ignoring_case() {
+        assert_eq!(super::find_ignoring_case("Click Submit now", "submit"), Some((6, 12)));
+        assert_eq!(super::find_ignoring_case("Über", "über"), Some((0, 5)));
+        assert_eq!(super::find_ignoring_case("Cancel", "Submit"), None);
+        assert_eq!(super::find_ignoring_case("Cancel", ""), None);
+        assert_eq!(super::find_ignoring_case("STRASSE", "straße"), None);
+        assert_eq!(super::find_ignoring_case("ab", "abc"), None);
+    }
+
+    #[test]
+    fn pixel_bounds() {
+        let image = Image::from_rgba(200, 100, vec![0; 200 * 100 * 4]).unwrap();
+        let bounds = super::pixel_bounds(CGRect::new(0.25, 0.1, 0.5, 0.2), &image);
+        assert_eq!(bounds, Bounds { origin: (50.0, 70.0), size: (100.0, 20.0) });
+        let whole = super::pixel_bounds(CGRect::new(0.0, 0.0, 1.0, 1.0), &image);
+        assert_eq!(whole, Bounds { origin: (0.0, 0.0), size: (200.0, 100.0) });
+    }
+
+    #[test]
+    fn sort_lines() {
+        let line = |text: &str, x, y| Line {
+            text: text.into(),
+            bounds: Bounds { origin: (x, y), size: (10.0, 10.0) },
+            confidence: 1.0,
+        };
+        let mut lines = vec![line("c", 0.0, 40.0), line("b", 30.0, 10.0), line("a", 5.0, 10.0)];
+        super::sort_lines(&mut lines);
+        let texts: Vec<&str> = lines.iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(texts, ["a", "b", "c"]);
+    }
+}
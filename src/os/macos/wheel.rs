@@ -3,7 +3,9 @@
 use std::os::raw;
 use std::ptr;
 
-use super::{CGEvent, CGEventSource};
+use super::{CGEvent, CGEventSource, EventField};
+use super::source::EventSource;
+use error::Result;
 
 extern {
     fn CGEventCreateScrollWheelEvent(
@@ -12,7 +14,7 @@ extern {
         wheelCount: u32,
         wheel1: i32,
         ...
-    ) -> CGEvent;
+    ) -> Option<CGEvent>;
 }
 
 /// The unit of measurement for a scroll wheel event.
@@ -26,6 +28,59 @@ pub enum ScrollUnit {
     Line,
 }
 
+/// The stage of a trackpad scroll gesture while the fingers are down.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum ScrollPhase {
+    /// The fingers touched down and started scrolling.
+    Began = 1,
+    /// The fingers moved.
+    Changed = 2,
+    /// The fingers lifted off.
+    Ended = 4,
+    /// The gesture was cancelled.
+    Cancelled = 8,
+    /// The fingers touched down and may start scrolling.
+    MayBegin = 128,
+}
+
+impl ScrollPhase {
+    /// Returns the phase for the value of a `ScrollPhase` field.
+    pub fn from_raw(raw: i64) -> Option<ScrollPhase> {
+        match raw {
+            1 => Some(ScrollPhase::Began),
+            2 => Some(ScrollPhase::Changed),
+            4 => Some(ScrollPhase::Ended),
+            8 => Some(ScrollPhase::Cancelled),
+            128 => Some(ScrollPhase::MayBegin),
+            _ => None,
+        }
+    }
+}
+
+/// The stage of the scrolling that continues after the fingers lift off a
+/// trackpad.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum MomentumPhase {
+    /// Momentum scrolling started.
+    Begin = 1,
+    /// Momentum scrolling continued.
+    Continue = 2,
+    /// Momentum scrolling stopped.
+    End = 3,
+}
+
+impl MomentumPhase {
+    /// Returns the phase for the value of a `ScrollMomentumPhase` field.
+    pub fn from_raw(raw: i64) -> Option<MomentumPhase> {
+        match raw {
+            1 => Some(MomentumPhase::Begin),
+            2 => Some(MomentumPhase::Continue),
+            3 => Some(MomentumPhase::End),
+            _ => None,
+        }
+    }
+}
+
 declare_event!("A scroll wheel event that can be posted into the Quartz event stream.");
 
 impl Event {
@@ -34,6 +89,11 @@ impl Event {
     /// The number of wheels must be between 1 and 3, inclusive. Type checking
     /// statically ensures an input within the valid range.
     ///
+    /// The first wheel is vertical, where positive values scroll up, and the
+    /// second is horizontal, where positive values scroll left. Use
+    /// [`vertical`](#method.vertical) and [`horizontal`](#method.horizontal)
+    /// to scroll along a single axis.
+    ///
     /// This function allocates a new `CGEvent`.
     ///
     /// # Examples
@@ -44,28 +104,151 @@ impl Event {
     /// use auto::os::{wheel, EventLocation};
     ///
     /// let unit  = wheel::ScrollUnit::Line;
-    /// let event = wheel::Event::new(unit, [-5, 20]);
+    /// let event = wheel::Event::new(unit, [-5, 20]).unwrap();
     /// # return;
     /// event.post(EventLocation::Session);
     /// ```
-    pub fn new<W: Wheels>(unit: ScrollUnit, wheels: W) -> Event {
-        let slice = wheels.as_ref();
-        let count = slice.len() as u32;
+    pub fn new<W: Wheels>(unit: ScrollUnit, wheels: W) -> Result<Event> {
+        Event::create(ptr::null(), unit, wheels.as_ref())
+    }
+
+    /// Creates a new event that scrolls vertically by `lines`, where positive
+    /// values scroll up.
+    #[inline]
+    pub fn vertical(lines: i32) -> Result<Event> {
+        Event::new(ScrollUnit::Line, [lines])
+    }
+
+    /// Creates a new event that scrolls horizontally by `lines`, where
+    /// positive values scroll right.
+    ///
+    /// Quartz itself treats positive horizontal offsets as scrolling left;
+    /// this function takes care of the difference.
+    #[inline]
+    pub fn horizontal(lines: i32) -> Result<Event> {
+        Event::new(ScrollUnit::Line, [0, -lines])
+    }
 
-        let null = ptr::null();
+    /// Creates a new scroll wheel event from `source` with `unit`-sized
+    /// offsets in `wheels`.
+    ///
+    /// Line-based offsets are scaled by the
+    /// [pixels per line](../source/struct.EventSource.html#method.pixels_per_line)
+    /// of `source`.
+    #[inline]
+    pub fn new_with_source<W: Wheels>(
+        source: &EventSource,
+        unit: ScrollUnit,
+        wheels: W,
+    ) -> Result<Event> {
+        Event::create(source.as_raw(), unit, wheels.as_ref())
+    }
+
+    fn create(source: CGEventSource, unit: ScrollUnit, slice: &[i32]) -> Result<Event> {
+        let count = slice.len() as u32;
         let unit = unit as raw::c_int;
 
-        Event(super::Event(unsafe { match count {
+        super::created(unsafe { match count {
             1 => CGEventCreateScrollWheelEvent(
-                null, unit, count, slice[0]
+                source, unit, count, slice[0]
             ),
             2 => CGEventCreateScrollWheelEvent(
-                null, unit, count, slice[0], slice[1]
+                source, unit, count, slice[0], slice[1]
             ),
             _ => CGEventCreateScrollWheelEvent(
-                null, unit, count, slice[0], slice[1], slice[2]
+                source, unit, count, slice[0], slice[1], slice[2]
             ),
-        } }))
+        } }).map(Event)
+    }
+
+    /// Returns the line-based offsets of the vertical and horizontal wheels.
+    #[inline]
+    pub fn line_delta(&self) -> (i64, i64) {
+        (
+            self.integer_field(EventField::ScrollDeltaAxis1).unwrap_or(0),
+            self.integer_field(EventField::ScrollDeltaAxis2).unwrap_or(0),
+        )
+    }
+
+    /// Returns the pixel-based offsets of the vertical and horizontal wheels.
+    #[inline]
+    pub fn point_delta(&self) -> (f64, f64) {
+        (
+            self.double_field(EventField::ScrollPointDeltaAxis1).unwrap_or(0.0),
+            self.double_field(EventField::ScrollPointDeltaAxis2).unwrap_or(0.0),
+        )
+    }
+
+    /// Returns whether the offsets are pixel-based, as for trackpads, rather
+    /// than line-based.
+    #[inline]
+    pub fn is_continuous(&self) -> bool {
+        self.integer_field(EventField::ScrollIsContinuous).unwrap_or(0) != 0
+    }
+
+    /// Sets whether the offsets are pixel-based, as for trackpads.
+    #[inline]
+    pub fn set_continuous(&mut self, continuous: bool) {
+        self.set_integer_field(EventField::ScrollIsContinuous, continuous as i64);
+    }
+
+    /// Returns the stage of the trackpad gesture that the event belongs to,
+    /// if any.
+    #[inline]
+    pub fn scroll_phase(&self) -> Option<ScrollPhase> {
+        ScrollPhase::from_raw(self.integer_field(EventField::ScrollPhase)?)
+    }
+
+    /// Sets the stage of the trackpad gesture that the event belongs to.
+    ///
+    /// Some applications, such as Safari and Xcode, only treat scrolling as
+    /// a trackpad gesture, for example to navigate back by swiping, if its
+    /// events are continuous and have phases. A gesture is a `Began` event,
+    /// any number of `Changed` events, and an `Ended` event, optionally
+    /// followed by momentum events with no scroll phase.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use auto::os::EventLocation;
+    /// use auto::os::wheel::{Event, MomentumPhase, ScrollPhase, ScrollUnit};
+    ///
+    /// let post = |dy, phase, momentum| {
+    ///     let mut event = Event::new(ScrollUnit::Pixel, [dy]).unwrap();
+    ///     event.set_continuous(true);
+    ///     event.set_scroll_phase(phase);
+    ///     event.set_momentum_phase(momentum);
+    ///     event.post(EventLocation::Session);
+    /// };
+    ///
+    /// post(0, Some(ScrollPhase::Began), None);
+    /// for _ in 0..10 {
+    ///     post(-12, Some(ScrollPhase::Changed), None);
+    /// }
+    /// post(0, Some(ScrollPhase::Ended), None);
+    ///
+    /// post(-8, None, Some(MomentumPhase::Begin));
+    /// post(-4, None, Some(MomentumPhase::Continue));
+    /// post(0, None, Some(MomentumPhase::End));
+    /// ```
+    #[inline]
+    pub fn set_scroll_phase(&mut self, phase: Option<ScrollPhase>) {
+        let raw = phase.map_or(0, |phase| phase as i64);
+        self.set_integer_field(EventField::ScrollPhase, raw);
+    }
+
+    /// Returns the stage of momentum scrolling that the event belongs to, if
+    /// any.
+    #[inline]
+    pub fn momentum_phase(&self) -> Option<MomentumPhase> {
+        MomentumPhase::from_raw(self.integer_field(EventField::ScrollMomentumPhase)?)
+    }
+
+    /// Sets the stage of momentum scrolling that the event belongs to.
+    #[inline]
+    pub fn set_momentum_phase(&mut self, phase: Option<MomentumPhase>) {
+        let raw = phase.map_or(0, |phase| phase as i64);
+        self.set_integer_field(EventField::ScrollMomentumPhase, raw);
     }
 }
 
@@ -0,0 +1,194 @@
+//! 🪟 Window utilities.
+//!
+//! Windows are listed from the window server, so they include those of every
+//! application without requiring accessibility access. Since macOS 10.15,
+//! window titles are only available if the process may record the screen.
+//!
+//! # Examples
+//!
+//! Click into the frontmost TextEdit window:
+//!
+//! ```no_run
+//! use auto::os::window::Window;
+//!
+//! let windows = Window::on_screen().unwrap();
+//! let window = windows.iter()
+//!     .find(|w| w.owner_name() == Some("TextEdit") && w.layer() == 0)
+//!     .unwrap();
+//!
+//! let (x, y) = window.bounds().origin;
+//! let click = (x + 100.0, y + 100.0);
+//! # let _ = click;
+//! ```
+
+use super::{cf, CFObject, CFObjectRef, CGRect};
+use super::app::{App, Pid};
+use super::ax::{self, StableId};
+use super::screen::Bounds;
+use error::{Error, Result};
+
+#[link(name = "CoreGraphics", kind = "framework")]
+extern {
+    fn CGWindowListCopyWindowInfo(option: u32, relative_to_window: u32) -> Option<CFObject>;
+
+    fn CGRectMakeWithDictionaryRepresentation(dict: CFObjectRef, rect: *mut CGRect) -> bool;
+
+    static kCGWindowNumber: CFObjectRef;
+    static kCGWindowOwnerPID: CFObjectRef;
+    static kCGWindowOwnerName: CFObjectRef;
+    static kCGWindowName: CFObjectRef;
+    static kCGWindowLayer: CFObjectRef;
+    static kCGWindowBounds: CFObjectRef;
+    static kCGWindowIsOnscreen: CFObjectRef;
+}
+
+/// `kCGWindowListOptionAll`
+const LIST_ALL: u32 = 0;
+
+/// `kCGWindowListOptionOnScreenOnly`
+const LIST_ON_SCREEN_ONLY: u32 = 1 << 0;
+
+/// `kCGWindowListExcludeDesktopElements`
+const LIST_EXCLUDE_DESKTOP_ELEMENTS: u32 = 1 << 4;
+
+/// `kCGNullWindowID`
+const NULL_WINDOW_ID: u32 = 0;
+
+/// A window identifier, unique for the lifetime of the window server.
+pub type WindowId = u32;
+
+/// A snapshot of a window's properties at the time it was listed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Window {
+    id: WindowId,
+    owner_pid: Pid,
+    owner_name: Option<String>,
+    title: Option<String>,
+    layer: i32,
+    bounds: Bounds,
+    is_on_screen: bool,
+}
+
+impl Window {
+    /// Returns every window, ordered from front to back, excluding desktop
+    /// elements such as the wallpaper and icons.
+    #[inline]
+    pub fn list() -> Result<Vec<Window>> {
+        list(LIST_ALL | LIST_EXCLUDE_DESKTOP_ELEMENTS)
+    }
+
+    /// Returns the windows that are currently on screen, ordered from front to
+    /// back, excluding desktop elements.
+    #[inline]
+    pub fn on_screen() -> Result<Vec<Window>> {
+        list(LIST_ON_SCREEN_ONLY | LIST_EXCLUDE_DESKTOP_ELEMENTS)
+    }
+
+    /// Returns the window's identifier.
+    #[inline]
+    pub fn id(&self) -> WindowId {
+        self.id
+    }
+
+    /// Returns the process identifier of the application that owns the
+    /// window.
+    #[inline]
+    pub fn owner_pid(&self) -> Pid {
+        self.owner_pid
+    }
+
+    /// Returns the name of the application that owns the window.
+    #[inline]
+    pub fn owner_name(&self) -> Option<&str> {
+        self.owner_name.as_deref()
+    }
+
+    /// Returns the title of the window, or `None` if it has none or the
+    /// process may not record the screen.
+    #[inline]
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// Returns the layer that the window is drawn in, where normal
+    /// application windows are in layer 0 and higher layers are drawn above
+    /// lower ones.
+    #[inline]
+    pub fn layer(&self) -> i32 {
+        self.layer
+    }
+
+    /// Returns the bounds of the window in the global coordinate space,
+    /// relative to the upper-left corner of the main display.
+    #[inline]
+    pub fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
+    /// Returns whether the window was on screen when it was listed.
+    #[inline]
+    pub fn is_on_screen(&self) -> bool {
+        self.is_on_screen
+    }
+
+    /// Returns whether `location` is within the bounds of the window.
+    #[inline]
+    pub fn contains(&self, location: (f64, f64)) -> bool {
+        self.bounds.contains(location)
+    }
+
+    /// Returns the readable path that the [stable identifier](#method.stable_id)
+    /// of the window is a hash of.
+    ///
+    /// The path is the bundle identifier of the owning application, or its
+    /// name if it has none, followed by the title with digits replaced by
+    /// `#`, as in `com.apple.TextEdit/AXWindow "Untitled #"`. This is the
+    /// [stable path](../ax/struct.Element.html#method.stable_path) of the
+    /// window's accessibility element when the title is its label.
+    pub fn stable_path(&self) -> Option<String> {
+        let owner = App::from_pid(self.owner_pid)
+            .and_then(|app| app.bundle_identifier())
+            .or_else(|| self.owner_name.clone())?;
+        let step = ax::stable_step("AXWindow", self.title.as_deref().unwrap_or_default());
+        Some(format!("{}/{}", owner, step))
+    }
+
+    /// Returns a best-effort identifier for the window that survives
+    /// restarts of its application.
+    ///
+    /// Windows of the same application whose titles differ only in their
+    /// digits share an identifier.
+    #[inline]
+    pub fn stable_id(&self) -> Option<StableId> {
+        self.stable_path().map(|path| StableId::of_path(&path))
+    }
+}
+
+fn list(option: u32) -> Result<Vec<Window>> {
+    let info = unsafe { CGWindowListCopyWindowInfo(option, NULL_WINDOW_ID) };
+    let info = info.and_then(|info| cf::to_vec(&info)).ok_or(Error::Failed)?;
+    Ok(info.iter().filter_map(|dict| unsafe { window(dict) }).collect())
+}
+
+unsafe fn window(dict: &CFObject) -> Option<Window> {
+    let get = |key| cf::get(dict, key).map(cf::retain);
+    let number = |key| get(key).and_then(|n| cf::to_i64(&n));
+    let string = |key| get(key).and_then(|s| cf::to_string(&s));
+
+    let mut rect = CGRect::new(0.0, 0.0, 0.0, 0.0);
+    let bounds = get(kCGWindowBounds)?;
+    if !CGRectMakeWithDictionaryRepresentation(bounds.0, &mut rect) {
+        return None;
+    }
+
+    Some(Window {
+        id: number(kCGWindowNumber)? as WindowId,
+        owner_pid: number(kCGWindowOwnerPID)? as Pid,
+        owner_name: string(kCGWindowOwnerName),
+        title: string(kCGWindowName),
+        layer: number(kCGWindowLayer).unwrap_or(0) as i32,
+        bounds: rect.into(),
+        // The key is absent for windows that are not on screen
+        is_on_screen: cf::get(dict, kCGWindowIsOnscreen) == Some(cf::boolean(true)),
+    })
+}
@@ -0,0 +1,100 @@
+//! 📱 Application utilities.
+
+use std::io;
+use std::os::windows::io::AsRawHandle;
+use std::process::{self, Command, ExitStatus};
+use std::time::Duration;
+
+use winapi::shared::winerror::WAIT_TIMEOUT;
+use winapi::um::winuser;
+
+use error::{Error, OsError, Result};
+
+/// A process identifier.
+pub type Pid = u32;
+
+/// An application started by this process.
+#[derive(Debug)]
+pub struct App {
+    process: process::Child,
+}
+
+/// Converts an error from the standard library into this crate's error.
+fn from_io(err: io::Error) -> Error {
+    match err.raw_os_error() {
+        Some(code) => OsError::from_code(code).into(),
+        None => Error::Failed,
+    }
+}
+
+impl App {
+    /// Starts the program at `path` with `args` and with `env` added to the
+    /// environment inherited from this process, returning as soon as the
+    /// process exists.
+    ///
+    /// The program is started with `CreateProcess`, so the returned app and
+    /// its [`pid`](#method.pid) identify the process that was just started.
+    /// Use [`wait_for_input_idle`](#method.wait_for_input_idle) to wait until
+    /// it is ready for input.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use auto::os::app::App;
+    ///
+    /// let app = App::launch_with_arguments(
+    ///     r"C:\Windows\System32\notepad.exe",
+    ///     &[r"C:\Temp\notes.txt"],
+    ///     &[("LANG", "en_US")],
+    /// ).unwrap();
+    /// println!("started Notepad as {}", app.pid());
+    /// ```
+    pub fn launch_with_arguments(path: &str, args: &[&str], env: &[(&str, &str)]) -> Result<App> {
+        Command::new(path)
+            .args(args)
+            .envs(env.iter().cloned())
+            .spawn()
+            .map(|process| App { process })
+            .map_err(from_io)
+    }
+
+    /// Returns the process identifier of the application.
+    #[inline]
+    pub fn pid(&self) -> Pid {
+        self.process.id()
+    }
+
+    /// Waits up to `timeout` for the application to finish processing its
+    /// initial input and wait for more, returning whether it did in time.
+    ///
+    /// Returns immediately for console programs, which have no message queue
+    /// to wait on.
+    pub fn wait_for_input_idle(&self, timeout: Duration) -> Result<bool> {
+        let millis = timeout.as_millis().min(u32::MAX as u128 - 1) as u32;
+        let handle = self.process.as_raw_handle() as _;
+        match unsafe { winuser::WaitForInputIdle(handle, millis) } {
+            0 => Ok(true),
+            WAIT_TIMEOUT => Ok(false),
+            _ => Err(OsError::last().into()),
+        }
+    }
+
+    /// Returns the exit status if the application has exited, without
+    /// blocking.
+    #[inline]
+    pub fn try_wait(&mut self) -> Result<Option<ExitStatus>> {
+        self.process.try_wait().map_err(from_io)
+    }
+
+    /// Waits for the application to exit and returns its exit status.
+    #[inline]
+    pub fn wait(&mut self) -> Result<ExitStatus> {
+        self.process.wait().map_err(from_io)
+    }
+
+    /// Forcefully terminates the application.
+    #[inline]
+    pub fn kill(&mut self) -> Result<()> {
+        self.process.kill().map_err(from_io)
+    }
+}
@@ -0,0 +1,97 @@
+//! 🔊 Audio output utilities.
+//!
+//! The volume of an audio endpoint is its master volume in the Windows
+//! volume mixer. The COM library is initialized for the multithreaded
+//! apartment on each thread that uses an endpoint, unless the thread already
+//! initialized it.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use auto::os::audio::Endpoint;
+//!
+//! let speakers = Endpoint::default_output().unwrap();
+//! speakers.set_volume(0.25).unwrap();
+//! speakers.set_muted(false).unwrap();
+//! ```
+
+use std::ptr;
+
+use winapi::ctypes::c_void;
+use winapi::shared::winerror::{HRESULT, SUCCEEDED};
+use winapi::shared::wtypesbase::CLSCTX_INPROC_SERVER;
+use winapi::um::combaseapi::{CoCreateInstance, CLSCTX_ALL};
+use winapi::um::endpointvolume::IAudioEndpointVolume;
+use winapi::um::mmdeviceapi::{eConsole, eRender, IMMDevice, IMMDeviceEnumerator, MMDeviceEnumerator};
+use winapi::{Class, Interface};
+
+use error::{OsError, Result};
+use super::Com;
+
+/// Converts the result of a COM method into a `Result`.
+fn check(hr: HRESULT) -> Result<()> {
+    if SUCCEEDED(hr) {
+        Ok(())
+    } else {
+        Err(OsError::from_code(hr).into())
+    }
+}
+
+/// An audio endpoint device, such as speakers or headphones.
+pub struct Endpoint(Com<IAudioEndpointVolume>);
+
+impl Endpoint {
+    /// Returns the endpoint that plays system and media sounds.
+    pub fn default_output() -> Result<Endpoint> {
+        super::initialize_com()?;
+        unsafe {
+            let mut out: *mut IMMDeviceEnumerator = ptr::null_mut();
+            let hr = CoCreateInstance(
+                &MMDeviceEnumerator::uuidof(),
+                ptr::null_mut(),
+                CLSCTX_INPROC_SERVER,
+                &IMMDeviceEnumerator::uuidof(),
+                &mut out as *mut _ as *mut *mut c_void,
+            );
+            let enumerator = Com::required(hr, out)?;
+
+            let mut out: *mut IMMDevice = ptr::null_mut();
+            let hr = enumerator.get().GetDefaultAudioEndpoint(eRender, eConsole, &mut out);
+            let device = Com::required(hr, out)?;
+
+            let mut out: *mut IAudioEndpointVolume = ptr::null_mut();
+            let hr = device.get().Activate(
+                &IAudioEndpointVolume::uuidof(),
+                CLSCTX_ALL,
+                ptr::null_mut(),
+                &mut out as *mut _ as *mut *mut c_void,
+            );
+            Com::required(hr, out).map(Endpoint)
+        }
+    }
+
+    /// Returns the volume of the endpoint, ranging from 0 to 1.
+    pub fn volume(&self) -> Result<f32> {
+        let mut volume = 0.0;
+        check(unsafe { self.0.get().GetMasterVolumeLevelScalar(&mut volume) })?;
+        Ok(volume)
+    }
+
+    /// Sets the volume of the endpoint, ranging from 0 to 1.
+    pub fn set_volume(&self, volume: f32) -> Result<()> {
+        let volume = volume.clamp(0.0, 1.0);
+        check(unsafe { self.0.get().SetMasterVolumeLevelScalar(volume, ptr::null()) })
+    }
+
+    /// Returns whether the endpoint is muted.
+    pub fn is_muted(&self) -> Result<bool> {
+        let mut muted = 0;
+        check(unsafe { self.0.get().GetMute(&mut muted) })?;
+        Ok(muted != 0)
+    }
+
+    /// Mutes the endpoint if `muted` is `true`, or unmutes it otherwise.
+    pub fn set_muted(&self, muted: bool) -> Result<()> {
+        check(unsafe { self.0.get().SetMute(muted as i32, ptr::null()) })
+    }
+}
@@ -0,0 +1,346 @@
+//! 🪝 Low-level hooks for observing and suppressing keyboard and mouse input.
+//!
+//! A [`Hook`](struct.Hook.html) sees every keyboard and mouse event before it
+//! reaches any application, including events injected by other processes.
+//!
+//! The system skips a hook whose callback takes longer than the
+//! `LowLevelHooksTimeout` registry setting, and eventually removes it without
+//! notice, so callbacks should return quickly.
+
+use std::cell::RefCell;
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+use std::{fmt, mem, ptr};
+
+use winapi::ctypes::c_int;
+use winapi::shared::minwindef::{DWORD, HIWORD, LPARAM, LRESULT, WPARAM};
+use winapi::shared::windef::HHOOK;
+use winapi::um::libloaderapi::GetModuleHandleW;
+use winapi::um::processthreadsapi::GetCurrentThreadId;
+use winapi::um::winuser::{
+    self,
+    CallNextHookEx,
+    GetMessageW,
+    PeekMessageW,
+    PostThreadMessageW,
+    SetWindowsHookExW,
+    UnhookWindowsHookEx,
+    KBDLLHOOKSTRUCT,
+    MSLLHOOKSTRUCT,
+    PM_NOREMOVE,
+    WH_KEYBOARD_LL,
+    WH_MOUSE_LL,
+    WM_QUIT,
+    WM_USER,
+};
+
+use error::{OsError, Result};
+use super::mouse::Button;
+
+bitflags! {
+    /// The kinds of input a hook observes.
+    pub struct HookMask: u8 {
+        /// Key presses and releases.
+        const KEYBOARD = 1 << 0;
+        /// Mouse movement, buttons, and wheels.
+        const MOUSE    = 1 << 1;
+    }
+}
+
+/// What happens to an event after a hook's callback returns.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum Action {
+    /// Passes the event on to the rest of the system.
+    Keep,
+    /// Discards the event before any application receives it.
+    Discard,
+}
+
+/// An event observed by a [`Hook`](struct.Hook.html).
+#[derive(Copy, Clone, Debug)]
+pub enum Event {
+    /// A key was pressed or released.
+    Keyboard(KeyboardEvent),
+    /// The mouse was moved, clicked, or scrolled.
+    Mouse(MouseEvent),
+}
+
+/// A key press or release observed by a hook.
+#[derive(Copy, Clone)]
+pub struct KeyboardEvent {
+    message: u32,
+    info: KBDLLHOOKSTRUCT,
+}
+
+impl fmt::Debug for KeyboardEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KeyboardEvent")
+            .field("message", &self.message)
+            .field("key", &self.key())
+            .field("scan_code", &self.scan_code())
+            .field("flags", &self.info.flags)
+            .finish()
+    }
+}
+
+impl KeyboardEvent {
+    /// Returns the window message, such as `WM_KEYDOWN` or `WM_SYSKEYUP`.
+    #[inline]
+    pub fn message(&self) -> u32 {
+        self.message
+    }
+
+    /// Returns the virtual key, which distinguishes left and right
+    /// modifiers.
+    #[inline]
+    pub fn key(&self) -> u16 {
+        self.info.vkCode as u16
+    }
+
+    /// Returns the hardware scan code of the key.
+    #[inline]
+    pub fn scan_code(&self) -> u32 {
+        self.info.scanCode
+    }
+
+    /// Returns whether the key was pressed rather than released.
+    #[inline]
+    pub fn is_down(&self) -> bool {
+        self.message == winuser::WM_KEYDOWN || self.message == winuser::WM_SYSKEYDOWN
+    }
+
+    /// Returns whether the key is on the extended part of the keyboard.
+    #[inline]
+    pub fn is_extended(&self) -> bool {
+        self.info.flags & winuser::LLKHF_EXTENDED != 0
+    }
+
+    /// Returns whether the event was injected, such as by `SendInput`,
+    /// rather than coming from a device.
+    #[inline]
+    pub fn is_injected(&self) -> bool {
+        self.info.flags & winuser::LLKHF_INJECTED != 0
+    }
+
+    /// Returns the extra information attached to the event, such as by
+    /// [`with_extra_info`](../fn.with_extra_info.html).
+    #[inline]
+    pub fn extra_info(&self) -> usize {
+        self.info.dwExtraInfo
+    }
+
+    /// Returns the time stamp of the event in milliseconds, as returned by
+    /// `GetTickCount`.
+    #[inline]
+    pub fn time(&self) -> u32 {
+        self.info.time
+    }
+}
+
+/// A mouse movement, button, or wheel event observed by a hook.
+#[derive(Copy, Clone)]
+pub struct MouseEvent {
+    message: u32,
+    info: MSLLHOOKSTRUCT,
+}
+
+impl fmt::Debug for MouseEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MouseEvent")
+            .field("message", &self.message)
+            .field("location", &self.location())
+            .field("data", &self.info.mouseData)
+            .field("flags", &self.info.flags)
+            .finish()
+    }
+}
+
+impl MouseEvent {
+    /// Returns the window message, such as `WM_MOUSEMOVE` or
+    /// `WM_LBUTTONDOWN`.
+    #[inline]
+    pub fn message(&self) -> u32 {
+        self.message
+    }
+
+    /// Returns the location of the cursor in physical screen coordinates.
+    #[inline]
+    pub fn location(&self) -> (i32, i32) {
+        (self.info.pt.x, self.info.pt.y)
+    }
+
+    /// Returns the button of an event for an extended button, or `None` for
+    /// other events.
+    pub fn x_button(&self) -> Option<Button> {
+        match self.message {
+            winuser::WM_XBUTTONDOWN | winuser::WM_XBUTTONUP => match HIWORD(self.info.mouseData) {
+                winuser::XBUTTON1 => Some(Button::X1),
+                winuser::XBUTTON2 => Some(Button::X2),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns the wheel movement of a wheel event, where `WHEEL_DELTA` is
+    /// one notch, or zero for other events.
+    ///
+    /// Positive values are away from the user for the vertical wheel and to
+    /// the right for the horizontal wheel.
+    pub fn wheel_delta(&self) -> i16 {
+        match self.message {
+            winuser::WM_MOUSEWHEEL | winuser::WM_MOUSEHWHEEL => HIWORD(self.info.mouseData) as i16,
+            _ => 0,
+        }
+    }
+
+    /// Returns whether the event was injected, such as by `SendInput`,
+    /// rather than coming from a device.
+    #[inline]
+    pub fn is_injected(&self) -> bool {
+        self.info.flags & winuser::LLMHF_INJECTED != 0
+    }
+
+    /// Returns the extra information attached to the event, such as by
+    /// [`with_extra_info`](../fn.with_extra_info.html).
+    #[inline]
+    pub fn extra_info(&self) -> usize {
+        self.info.dwExtraInfo
+    }
+
+    /// Returns the time stamp of the event in milliseconds, as returned by
+    /// `GetTickCount`.
+    #[inline]
+    pub fn time(&self) -> u32 {
+        self.info.time
+    }
+}
+
+type Callback = Box<dyn FnMut(&Event) -> Action + Send>;
+
+thread_local! {
+    static CALLBACK: RefCell<Option<Callback>> = const { RefCell::new(None) };
+}
+
+/// Calls the hook thread's callback, keeping events that arrive while it is
+/// already running, such as input it injects itself.
+fn call(event: Event) -> Action {
+    CALLBACK.with(|callback| match callback.try_borrow_mut() {
+        Ok(mut callback) => callback.as_mut().map_or(Action::Keep, |callback| callback(&event)),
+        Err(_) => Action::Keep,
+    })
+}
+
+fn next(code: c_int, wparam: WPARAM, lparam: LPARAM, action: Action) -> LRESULT {
+    match action {
+        Action::Keep => unsafe { CallNextHookEx(ptr::null_mut(), code, wparam, lparam) },
+        Action::Discard => 1,
+    }
+}
+
+unsafe extern "system" fn keyboard_proc(code: c_int, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    let mut action = Action::Keep;
+    if code == winuser::HC_ACTION {
+        let info = *(lparam as *const KBDLLHOOKSTRUCT);
+        action = call(Event::Keyboard(KeyboardEvent { message: wparam as u32, info }));
+    }
+    next(code, wparam, lparam, action)
+}
+
+unsafe extern "system" fn mouse_proc(code: c_int, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    let mut action = Action::Keep;
+    if code == winuser::HC_ACTION {
+        let info = *(lparam as *const MSLLHOOKSTRUCT);
+        action = call(Event::Mouse(MouseEvent { message: wparam as u32, info }));
+    }
+    next(code, wparam, lparam, action)
+}
+
+/// Global keyboard and mouse hooks.
+#[derive(Debug)]
+pub struct Hook;
+
+impl Hook {
+    /// Installs hooks for the input in `mask` on a new thread, which calls
+    /// `callback` with each event until the returned handle is stopped or
+    /// dropped.
+    ///
+    /// The thread pumps the messages that deliver events to the hooks, so
+    /// the caller need not run a message loop.
+    pub fn spawn<F>(mask: HookMask, callback: F) -> Result<HookHandle>
+        where F: FnMut(&Event) -> Action + Send + 'static
+    {
+        let callback: Callback = Box::new(callback);
+        let (tx, rx) = mpsc::channel();
+
+        // Low-level hooks are called on the thread that installed them
+        let thread = thread::Builder::new()
+            .name("auto-input-hook".into())
+            .spawn(move || unsafe {
+                let mut msg = mem::zeroed();
+
+                // Create the thread's message queue before anyone can post
+                // `WM_QUIT` to it
+                PeekMessageW(&mut msg, ptr::null_mut(), WM_USER, WM_USER, PM_NOREMOVE);
+
+                CALLBACK.with(|slot| *slot.borrow_mut() = Some(callback));
+                let module = GetModuleHandleW(ptr::null());
+                let mut hooks: Vec<HHOOK> = Vec::new();
+                let mut result = Ok(GetCurrentThreadId());
+                if mask.contains(HookMask::KEYBOARD) {
+                    hooks.push(SetWindowsHookExW(WH_KEYBOARD_LL, Some(keyboard_proc), module, 0));
+                }
+                if mask.contains(HookMask::MOUSE) {
+                    hooks.push(SetWindowsHookExW(WH_MOUSE_LL, Some(mouse_proc), module, 0));
+                }
+                if hooks.iter().any(|hook| hook.is_null()) {
+                    result = Err(OsError::last());
+                    hooks.retain(|hook| !hook.is_null());
+                }
+
+                let installed = result.is_ok();
+                let _ = tx.send(result);
+                if installed {
+                    while GetMessageW(&mut msg, ptr::null_mut(), 0, 0) > 0 {}
+                }
+                for hook in hooks {
+                    UnhookWindowsHookEx(hook);
+                }
+                CALLBACK.with(|slot| slot.borrow_mut().take());
+            })
+            .expect("failed to spawn input hook thread");
+
+        match rx.recv() {
+            Ok(Ok(thread_id)) => Ok(HookHandle { thread_id, thread: Some(thread) }),
+            Ok(Err(err)) => {
+                let _ = thread.join();
+                Err(err.into())
+            },
+            Err(_) => panic!("input hook thread exited during installation"),
+        }
+    }
+}
+
+/// A handle to hooks running on their own thread.
+///
+/// The hooks are removed when the handle is dropped.
+#[derive(Debug)]
+pub struct HookHandle {
+    thread_id: DWORD,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HookHandle {
+    /// Removes the hooks and waits for their thread to exit.
+    #[inline]
+    pub fn stop(self) {}
+}
+
+impl Drop for HookHandle {
+    fn drop(&mut self) {
+        unsafe { PostThreadMessageW(self.thread_id, WM_QUIT, 0, 0) };
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
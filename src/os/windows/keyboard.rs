@@ -0,0 +1,260 @@
+//! ⌨️ Keyboard automation utilities.
+
+use std::{fmt, mem};
+use std::sync::Mutex;
+
+use winapi::ctypes::c_int;
+use winapi::shared::minwindef::UINT;
+use winapi::um::winuser::{self, INPUT, INPUT_KEYBOARD, KEYBDINPUT};
+
+use error::{OsError, Result};
+
+/// A keyboard event that can be sent to the system input stream via
+/// [`send`](fn.send.html).
+#[derive(Copy, Clone)]
+pub struct Event(INPUT);
+
+impl fmt::Debug for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ki = self.inner();
+        f.debug_struct("Event")
+            .field("key", &ki.wVk)
+            .field("scan_code", &ki.wScan)
+            .field("flags", &ki.dwFlags)
+            .finish()
+    }
+}
+
+impl Event {
+    /// Creates a new event for the virtual key.
+    ///
+    /// Keys that live on the extended part of the keyboard (e.g. the arrow
+    /// keys or the right-hand Control and Alt keys) are automatically marked
+    /// as extended.
+    pub fn new(key: u16, down: bool) -> Event {
+        let mut event = Event::with_input(key, 0, 0, down);
+        event.set_extended(is_extended_key(key));
+        event
+    }
+
+    /// Creates a new event for the hardware scan code.
+    ///
+    /// The scan code identifies a physical key regardless of keyboard layout.
+    /// Codes prefixed with `0xE0` on the wire must be marked as `extended`.
+    pub fn from_scan_code(code: u16, extended: bool, down: bool) -> Event {
+        let flags = winuser::KEYEVENTF_SCANCODE;
+        let mut event = Event::with_input(0, code, flags, down);
+        event.set_extended(extended);
+        event
+    }
+
+    /// Creates a new event that types the UTF-16 code unit independent of the
+    /// virtual key it may be mapped to.
+    pub fn from_unicode(unit: u16, down: bool) -> Event {
+        Event::with_input(0, unit, winuser::KEYEVENTF_UNICODE, down)
+    }
+
+    fn with_input(key: u16, scan: u16, flags: u32, down: bool) -> Event {
+        unsafe {
+            let mut input: INPUT = mem::zeroed();
+            input.type_ = INPUT_KEYBOARD;
+            *input.u.ki_mut() = KEYBDINPUT {
+                wVk: key,
+                wScan: scan,
+                dwFlags: if down { flags } else { flags | winuser::KEYEVENTF_KEYUP },
+                time: 0,
+                dwExtraInfo: super::extra_info(),
+            };
+            Event(input)
+        }
+    }
+
+    #[inline]
+    fn inner(&self) -> &KEYBDINPUT {
+        unsafe { self.0.u.ki() }
+    }
+
+    #[inline]
+    fn inner_mut(&mut self) -> &mut KEYBDINPUT {
+        unsafe { self.0.u.ki_mut() }
+    }
+
+    /// Returns the virtual key of the event, or 0 if it was created from a
+    /// scan code or a Unicode character.
+    #[inline]
+    pub fn key(&self) -> u16 {
+        self.inner().wVk
+    }
+
+    /// Returns the hardware scan code of the event.
+    #[inline]
+    pub fn scan_code(&self) -> u16 {
+        self.inner().wScan
+    }
+
+    /// Returns whether the event presses the key down.
+    #[inline]
+    pub fn is_down(&self) -> bool {
+        self.inner().dwFlags & winuser::KEYEVENTF_KEYUP == 0
+    }
+
+    /// Returns whether the event is for a key on the extended part of the
+    /// keyboard.
+    #[inline]
+    pub fn is_extended(&self) -> bool {
+        self.inner().dwFlags & winuser::KEYEVENTF_EXTENDEDKEY != 0
+    }
+
+    /// Sets whether the event is for a key on the extended part of the
+    /// keyboard.
+    #[inline]
+    pub fn set_extended(&mut self, extended: bool) {
+        let flags = &mut self.inner_mut().dwFlags;
+        if extended {
+            *flags |= winuser::KEYEVENTF_EXTENDEDKEY;
+        } else {
+            *flags &= !winuser::KEYEVENTF_EXTENDEDKEY;
+        }
+    }
+
+    /// Sends `self` to the system input stream.
+    #[inline]
+    pub fn send(&self) -> Result<()> {
+        send(&[*self])
+    }
+}
+
+lazy_static! {
+    /// The virtual keys held down by events sent through `send`.
+    static ref SENT_DOWN: Mutex<Vec<u16>> = Mutex::new(Vec::new());
+}
+
+/// Sends `events` serially to the system input stream.
+///
+/// The events are not interspersed with other keyboard or mouse input events
+/// inserted either by the user or by other calls to `send`.
+///
+/// An error is returned if not all events were inserted, which happens when
+/// the input was blocked by another thread, or by User Interface Privilege
+/// Isolation (UIPI) when the target has a higher integrity level.
+pub fn send(events: &[Event]) -> Result<()> {
+    let len  = events.len() as u32;
+    let ptr  = events.as_ptr() as *mut INPUT;
+    let size = mem::size_of::<INPUT>() as c_int;
+    let sent = unsafe { winuser::SendInput(len, ptr, size) };
+    let error = OsError::last();
+    let mut down = SENT_DOWN.lock().unwrap_or_else(|e| e.into_inner());
+    for event in events[..sent as usize].iter().filter(|event| event.key() != 0) {
+        down.retain(|&key| key != event.key());
+        if event.is_down() {
+            down.push(event.key());
+        }
+    }
+    if sent == len {
+        Ok(())
+    } else {
+        Err(error.into())
+    }
+}
+
+/// Returns the time in seconds a key is held before it starts repeating, as
+/// set by the user.
+///
+/// The setting ranges from 250 ms to one second, in steps of 250 ms.
+pub fn repeat_delay() -> Result<f64> {
+    let delay = keyboard_parameter(winuser::SPI_GETKEYBOARDDELAY)?;
+    Ok((delay.min(3) + 1) as f64 * 0.25)
+}
+
+/// Returns the time in seconds between repeats of a held key, as set by the
+/// user.
+///
+/// The setting ranges from about 2.5 to about 30 repeats per second.
+pub fn repeat_interval() -> Result<f64> {
+    let speed = keyboard_parameter(winuser::SPI_GETKEYBOARDSPEED)?;
+    let rate = 2.5 + speed.min(31) as f64 * (27.5 / 31.0);
+    Ok(1.0 / rate)
+}
+
+/// Returns whether the virtual key `key` is held down, whether by the user
+/// or by input sent with `SendInput`.
+pub fn is_pressed(key: u16) -> bool {
+    unsafe { winuser::GetAsyncKeyState(key as c_int) as u16 & 0x8000 != 0 }
+}
+
+/// Returns whether the virtual key `key` was pressed and not yet released by
+/// events sent through [`send`](fn.send.html), rather than held by the user.
+///
+/// Events created from scan codes or Unicode characters aren't counted.
+pub fn is_sent_down(key: u16) -> bool {
+    SENT_DOWN.lock().unwrap_or_else(|e| e.into_inner()).contains(&key)
+}
+
+/// Returns whether the lock that `key` toggles is on, such as Caps Lock for
+/// `VK_CAPITAL`.
+pub fn is_toggled(key: u16) -> bool {
+    unsafe { winuser::GetKeyState(key as c_int) & 1 != 0 }
+}
+
+fn keyboard_parameter(action: UINT) -> Result<UINT> {
+    let mut value: UINT = 0;
+    let ok = unsafe {
+        winuser::SystemParametersInfoW(action, 0, &mut value as *mut UINT as *mut _, 0)
+    };
+    if ok == 0 {
+        Err(OsError::last().into())
+    } else {
+        Ok(value)
+    }
+}
+
+fn is_extended_key(key: u16) -> bool {
+    matches!(key as c_int,
+        winuser::VK_RMENU    |
+        winuser::VK_RCONTROL |
+        winuser::VK_INSERT   |
+        winuser::VK_DELETE   |
+        winuser::VK_HOME     |
+        winuser::VK_END      |
+        winuser::VK_PRIOR    |
+        winuser::VK_NEXT     |
+        winuser::VK_LEFT     |
+        winuser::VK_RIGHT    |
+        winuser::VK_UP       |
+        winuser::VK_DOWN     |
+        winuser::VK_NUMLOCK  |
+        winuser::VK_DIVIDE   |
+        winuser::VK_SNAPSHOT |
+        winuser::VK_LWIN     |
+        winuser::VK_RWIN     |
+        winuser::VK_APPS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extended_keys() {
+        let extended = [
+            winuser::VK_RMENU, winuser::VK_RCONTROL, winuser::VK_INSERT,
+            winuser::VK_DELETE, winuser::VK_HOME, winuser::VK_END,
+            winuser::VK_PRIOR, winuser::VK_NEXT, winuser::VK_LEFT,
+            winuser::VK_RIGHT, winuser::VK_UP, winuser::VK_DOWN,
+            winuser::VK_NUMLOCK, winuser::VK_DIVIDE, winuser::VK_SNAPSHOT,
+            winuser::VK_LWIN, winuser::VK_RWIN, winuser::VK_APPS,
+        ];
+        for &key in &extended {
+            assert!(is_extended_key(key as u16), "{:#x}", key);
+        }
+
+        let plain = [
+            winuser::VK_LMENU, winuser::VK_LCONTROL, winuser::VK_SHIFT,
+            winuser::VK_RETURN, winuser::VK_SPACE, winuser::VK_NUMPAD0,
+            winuser::VK_MULTIPLY, 0x41,
+        ];
+        for &key in &plain {
+            assert!(!is_extended_key(key as u16), "{:#x}", key);
+        }
+    }
+}
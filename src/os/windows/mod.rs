@@ -1,2 +1,180 @@
+pub mod app;
+pub mod audio;
+pub mod hook;
+pub mod keyboard;
 pub mod mouse;
+pub mod pen;
+pub mod power;
 pub mod screen;
+pub mod touch;
+pub mod touchpad;
+pub mod uia;
+pub mod wheel;
+pub mod window;
+
+use std::cell::Cell;
+use std::mem;
+use std::ptr::{self, NonNull};
+use std::time::Duration;
+
+use winapi::Interface;
+use winapi::ctypes::c_void;
+use winapi::shared::minwindef::FARPROC;
+use winapi::shared::windef::{DPI_AWARENESS_CONTEXT, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2};
+use winapi::shared::winerror::{HRESULT, RPC_E_CHANGED_MODE, SUCCEEDED};
+use winapi::um::combaseapi::CoInitializeEx;
+use winapi::um::libloaderapi::{GetModuleHandleA, GetProcAddress};
+use winapi::um::objbase::COINIT_MULTITHREADED;
+use winapi::um::unknwnbase::IUnknown;
+use winapi::um::{sysinfoapi, winuser};
+
+use error::{Error, OsError, Result};
+
+thread_local! {
+    static EXTRA_INFO: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Calls `f` with `info` attached as the extra information of every input it
+/// sends on the current thread.
+///
+/// Hooks can read the value back with
+/// [`KeyboardEvent::extra_info`](hook/struct.KeyboardEvent.html#method.extra_info)
+/// and [`MouseEvent::extra_info`](hook/struct.MouseEvent.html#method.extra_info)
+/// to recognize the input.
+pub fn with_extra_info<T, F: FnOnce() -> T>(info: usize, f: F) -> T {
+    let previous = EXTRA_INFO.with(|extra| extra.replace(info));
+    let result = f();
+    EXTRA_INFO.with(|extra| extra.set(previous));
+    result
+}
+
+/// Returns the extra information to attach to input sent from this thread.
+fn extra_info() -> usize {
+    EXTRA_INFO.with(Cell::get)
+}
+
+type SetThreadDpiAwarenessContext =
+    unsafe extern "system" fn(DPI_AWARENESS_CONTEXT) -> DPI_AWARENESS_CONTEXT;
+
+lazy_static! {
+    /// `SetThreadDpiAwarenessContext`, which user32.dll only exports since
+    /// Windows 10 version 1607, so that linking to it would keep the crate
+    /// from loading on earlier versions.
+    static ref SET_THREAD_DPI_AWARENESS_CONTEXT: Option<SetThreadDpiAwarenessContext> = unsafe {
+        let user32 = GetModuleHandleA(b"user32.dll\0".as_ptr() as *const _);
+        if user32.is_null() {
+            return None;
+        }
+        let function = GetProcAddress(user32, b"SetThreadDpiAwarenessContext\0".as_ptr() as *const _);
+        if function.is_null() {
+            None
+        } else {
+            Some(mem::transmute::<FARPROC, SetThreadDpiAwarenessContext>(function))
+        }
+    };
+}
+
+/// Calls `f` with the current thread aware of the DPI of each monitor, so
+/// that the screen coordinates it reads and sets are physical pixels.
+///
+/// Otherwise, unless the process declared itself DPI aware, coordinates are
+/// scaled for the DPI of the primary monitor and land on the wrong pixels of
+/// monitors with a different scale factor. Threads stay unaware on versions
+/// of Windows before Windows 10 version 1703.
+pub fn with_physical_pixels<T, F: FnOnce() -> T>(f: F) -> T {
+    let set_context = match *SET_THREAD_DPI_AWARENESS_CONTEXT {
+        Some(set_context) => set_context,
+        None => return f(),
+    };
+    let previous = unsafe { set_context(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2) };
+    let result = f();
+    if !previous.is_null() {
+        unsafe { set_context(previous) };
+    }
+    result
+}
+
+/// Returns the time since the last keyboard or mouse input in the current
+/// session, including input sent by applications with `SendInput`.
+pub fn time_since_last_input() -> Result<Duration> {
+    unsafe {
+        let mut info: winuser::LASTINPUTINFO = mem::zeroed();
+        info.cbSize = mem::size_of::<winuser::LASTINPUTINFO>() as u32;
+        if winuser::GetLastInputInfo(&mut info) == 0 {
+            return Err(OsError::last().into());
+        }
+        // Both tick counts wrap around every 49.7 days
+        let elapsed = sysinfoapi::GetTickCount().wrapping_sub(info.dwTime);
+        Ok(Duration::from_millis(elapsed as u64))
+    }
+}
+
+/// An owned reference to a COM object, released when dropped.
+struct Com<T>(NonNull<T>);
+
+impl<T> Drop for Com<T> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { (*(self.0.as_ptr() as *mut IUnknown)).Release() };
+    }
+}
+
+impl<T> Com<T> {
+    /// Takes ownership of the object written to `out` by a COM method that
+    /// returned `hr`, returning `None` if it succeeded without an object.
+    fn from_out(hr: HRESULT, out: *mut T) -> Result<Option<Com<T>>> {
+        if SUCCEEDED(hr) {
+            Ok(NonNull::new(out).map(Com))
+        } else {
+            Err(OsError::from_code(hr).into())
+        }
+    }
+
+    /// Like [`from_out`](#method.from_out), but fails if there is no object.
+    fn required(hr: HRESULT, out: *mut T) -> Result<Com<T>> {
+        Com::from_out(hr, out)?.ok_or(Error::Failed)
+    }
+
+    /// Returns the object's implementation of the interface `U`.
+    fn cast<U: Interface>(&self) -> Result<Com<U>> {
+        let mut out: *mut U = ptr::null_mut();
+        let hr = unsafe {
+            (*(self.as_ptr() as *mut IUnknown))
+                .QueryInterface(&U::uuidof(), &mut out as *mut _ as *mut *mut c_void)
+        };
+        Com::required(hr, out)
+    }
+
+    #[inline]
+    fn as_ptr(&self) -> *mut T {
+        self.0.as_ptr()
+    }
+
+    #[inline]
+    fn get(&self) -> &T {
+        unsafe { self.0.as_ref() }
+    }
+}
+
+impl<T> Clone for Com<T> {
+    fn clone(&self) -> Com<T> {
+        unsafe { (*(self.0.as_ptr() as *mut IUnknown)).AddRef() };
+        Com(self.0)
+    }
+}
+
+// Objects created in the multithreaded apartment may be used from any thread
+// in it, and each thread that uses them joins it
+unsafe impl<T> Send for Com<T> {}
+
+/// Initializes COM for the multithreaded apartment on the current thread,
+/// unless the thread already initialized it.
+fn initialize_com() -> Result<()> {
+    let hr = unsafe { CoInitializeEx(ptr::null_mut(), COINIT_MULTITHREADED) };
+    // A thread in a single-threaded apartment can still use COM objects,
+    // albeit with cross-apartment calls
+    if !SUCCEEDED(hr) && hr != RPC_E_CHANGED_MODE {
+        return Err(OsError::from_code(hr).into());
+    }
+    Ok(())
+}
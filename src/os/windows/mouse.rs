@@ -1,24 +1,32 @@
 //! 🖱️ Mouse automation utilities.
 
-use winapi::shared::windef::POINT;
-use winapi::um::winuser::{GetCursorPos, SetCursorPos};
+use std::{mem, ptr, slice};
 
-/// Returns the current mouse location.
+use winapi::ctypes::{c_int, c_void};
+use winapi::shared::windef::{HCURSOR, HDC, POINT};
+use winapi::um::winnt::LPCWSTR;
+use winapi::um::wingdi;
+use winapi::um::winuser::{self, GetCursorPos, SetCursorPos, INPUT, INPUT_MOUSE, MOUSEINPUT};
+
+use error::{Error, OsError, Result};
+use image::Image;
+
+/// Returns the current mouse location in physical pixels.
 ///
 /// The calling process must have `WINSTA_READATTRIBUTES` access to the window
 /// station.
 pub fn location() -> Option<Location> {
-    unsafe {
+    super::with_physical_pixels(|| unsafe {
         let mut point = POINT { x: 0, y: 0 };
         if GetCursorPos(&mut point) != 0 {
-            Some((point.x as _, point.y as _))
+            Some((point.x, point.y))
         } else {
             None
         }
-    }
+    })
 }
 
-/// Moves the cursor to the specified screen coordinates.
+/// Moves the cursor to the specified screen coordinates in physical pixels.
 ///
 /// If the new coordinates are not within the screen rectangle, the system
 /// automatically adjusts the coordinates so that the cursor stays within the
@@ -27,12 +35,380 @@ pub fn location() -> Option<Location> {
 /// The calling process must have `WINSTA_WRITEATTRIBUTES` access to the window
 /// station.
 #[inline]
-pub fn set_location((x, y): (usize, usize)) -> bool {
-    unsafe { SetCursorPos(x as _, y as _) != 0 }
+pub fn set_location((x, y): Location) -> bool {
+    super::with_physical_pixels(|| unsafe { SetCursorPos(x, y) != 0 })
+}
+
+/// A location on the screen, which is negative on monitors above or left of
+/// the primary monitor.
+pub type Location = (i32, i32);
+
+/// A mouse button that can be pressed and released with
+/// [`send_button`](fn.send_button.html).
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum Button {
+    /// The left button.
+    Left,
+    /// The right button.
+    Right,
+    /// The middle button.
+    Middle,
+    /// The first extended button, typically "back".
+    X1,
+    /// The second extended button, typically "forward".
+    X2,
+}
+
+/// Presses or releases `button` at the current cursor location.
+///
+/// As with keyboard events, this fails if the input was blocked by another
+/// thread or by User Interface Privilege Isolation (UIPI).
+pub fn send_button(button: Button, down: bool) -> Result<()> {
+    let (flags, data) = match (button, down) {
+        (Button::Left,   true)  => (winuser::MOUSEEVENTF_LEFTDOWN, 0),
+        (Button::Left,   false) => (winuser::MOUSEEVENTF_LEFTUP, 0),
+        (Button::Right,  true)  => (winuser::MOUSEEVENTF_RIGHTDOWN, 0),
+        (Button::Right,  false) => (winuser::MOUSEEVENTF_RIGHTUP, 0),
+        (Button::Middle, true)  => (winuser::MOUSEEVENTF_MIDDLEDOWN, 0),
+        (Button::Middle, false) => (winuser::MOUSEEVENTF_MIDDLEUP, 0),
+        (Button::X1,     true)  => (winuser::MOUSEEVENTF_XDOWN, winuser::XBUTTON1),
+        (Button::X1,     false) => (winuser::MOUSEEVENTF_XUP, winuser::XBUTTON1),
+        (Button::X2,     true)  => (winuser::MOUSEEVENTF_XDOWN, winuser::XBUTTON2),
+        (Button::X2,     false) => (winuser::MOUSEEVENTF_XUP, winuser::XBUTTON2),
+    };
+    unsafe {
+        let mut input: INPUT = mem::zeroed();
+        input.type_ = INPUT_MOUSE;
+        *input.u.mi_mut() = MOUSEINPUT {
+            dx: 0,
+            dy: 0,
+            mouseData: data as _,
+            dwFlags: flags,
+            time: 0,
+            dwExtraInfo: super::extra_info(),
+        };
+        let size = mem::size_of::<INPUT>() as c_int;
+        if winuser::SendInput(1, &mut input, size) == 1 {
+            Ok(())
+        } else {
+            Err(OsError::last().into())
+        }
+    }
+}
+
+/// Returns whether `button` is held down, whether by the user or by input
+/// sent with `SendInput`.
+///
+/// When the user swapped the primary and secondary buttons, the left button
+/// is the one that acts as the primary button, as with
+/// [`send_button`](fn.send_button.html).
+pub fn is_pressed(button: Button) -> bool {
+    // Virtual keys are those of the physical buttons, before any swap
+    let swapped = unsafe { winuser::GetSystemMetrics(winuser::SM_SWAPBUTTON) } != 0;
+    let vk = match button {
+        Button::Left if swapped => winuser::VK_RBUTTON,
+        Button::Right if swapped => winuser::VK_LBUTTON,
+        Button::Left => winuser::VK_LBUTTON,
+        Button::Right => winuser::VK_RBUTTON,
+        Button::Middle => winuser::VK_MBUTTON,
+        Button::X1 => winuser::VK_XBUTTON1,
+        Button::X2 => winuser::VK_XBUTTON2,
+    };
+    unsafe { winuser::GetAsyncKeyState(vk) as u16 & 0x8000 != 0 }
+}
+
+/// The `OCR_*` identifiers of the system cursors replaced by
+/// [`hide_cursor`](fn.hide_cursor.html), which winapi does not define:
+/// normal, I-beam, wait, cross, up, the four sizing cursors, no, hand, and
+/// app starting.
+const SYSTEM_CURSOR_IDS: [u32; 13] = [
+    32512, 32513, 32514, 32515, 32516, 32642, 32643, 32644, 32645, 32646, 32648, 32649, 32650,
+];
+
+/// `DI_NORMAL`, which draws an icon with its mask, and which winapi does not
+/// define.
+const DI_NORMAL: u32 = 0x0003;
+
+/// Hides the mouse cursor everywhere by replacing each system cursor with a
+/// blank one.
+///
+/// `ShowCursor` only affects windows of the calling thread, so this changes
+/// the system cursors instead. They stay hidden until
+/// [`show_cursor`](fn.show_cursor.html) is called, even after the process
+/// exits.
+pub fn hide_cursor() -> Result<()> {
+    // Each cursor has an AND mask of ones and an XOR mask of zeros, which
+    // leaves the screen unchanged
+    let and_mask = [0xFFu8; 32 * 32 / 8];
+    let xor_mask = [0u8; 32 * 32 / 8];
+    for &id in &SYSTEM_CURSOR_IDS {
+        unsafe {
+            let blank = winuser::CreateCursor(
+                ptr::null_mut(),
+                0,
+                0,
+                32,
+                32,
+                and_mask.as_ptr() as *const c_void,
+                xor_mask.as_ptr() as *const c_void,
+            );
+            if blank.is_null() {
+                return Err(OsError::last().into());
+            }
+            // The system takes ownership of the cursor when it succeeds
+            if winuser::SetSystemCursor(blank, id) == 0 {
+                let err = OsError::last();
+                winuser::DestroyCursor(blank);
+                return Err(err.into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Restores the system cursors hidden by
+/// [`hide_cursor`](fn.hide_cursor.html), reloading them from the user's
+/// settings.
+pub fn show_cursor() -> Result<()> {
+    let reloaded = unsafe {
+        winuser::SystemParametersInfoW(winuser::SPI_SETCURSORS, 0, ptr::null_mut(), 0)
+    };
+    if reloaded != 0 {
+        Ok(())
+    } else {
+        Err(OsError::last().into())
+    }
 }
 
-/// A location on the screen.
-pub type Location = (usize, usize);
+/// Returns the handle of the cursor currently shown, or `None` if the cursor
+/// is hidden.
+fn current_cursor() -> Result<Option<HCURSOR>> {
+    unsafe {
+        let mut info: winuser::CURSORINFO = mem::zeroed();
+        info.cbSize = mem::size_of::<winuser::CURSORINFO>() as u32;
+        if winuser::GetCursorInfo(&mut info) == 0 {
+            return Err(OsError::last().into());
+        }
+        if info.flags & winuser::CURSOR_SHOWING == 0 || info.hCursor.is_null() {
+            Ok(None)
+        } else {
+            Ok(Some(info.hCursor))
+        }
+    }
+}
+
+/// Returns the image of the cursor currently shown, with transparent pixels
+/// where the cursor leaves the screen unchanged.
+///
+/// Fails with [`Error::Failed`](../../error/enum.Error.html#variant.Failed)
+/// if the cursor is hidden.
+pub fn cursor_image() -> Result<Image> {
+    let cursor = current_cursor()?.ok_or(Error::Failed)?;
+    unsafe { draw_cursor(cursor) }
+}
+
+/// Draws `cursor` over black and over white to recover its colors and alpha.
+unsafe fn draw_cursor(cursor: HCURSOR) -> Result<Image> {
+    let mut icon: winuser::ICONINFO = mem::zeroed();
+    if winuser::GetIconInfo(cursor, &mut icon) == 0 {
+        return Err(OsError::last().into());
+    }
+    let monochrome = icon.hbmColor.is_null();
+    let source = if monochrome { icon.hbmMask } else { icon.hbmColor };
+    let mut bitmap: wingdi::BITMAP = mem::zeroed();
+    let read = wingdi::GetObjectW(
+        source as _,
+        mem::size_of::<wingdi::BITMAP>() as c_int,
+        &mut bitmap as *mut _ as *mut c_void,
+    );
+    if !icon.hbmColor.is_null() {
+        wingdi::DeleteObject(icon.hbmColor as _);
+    }
+    if !icon.hbmMask.is_null() {
+        wingdi::DeleteObject(icon.hbmMask as _);
+    }
+    if read == 0 {
+        return Err(OsError::last().into());
+    }
+
+    let width = bitmap.bmWidth as usize;
+    // The mask of a monochrome cursor stacks the AND mask over the XOR mask
+    let height = bitmap.bmHeight as usize / if monochrome { 2 } else { 1 };
+
+    let screen = winuser::GetDC(ptr::null_mut());
+    if screen.is_null() {
+        return Err(OsError::last().into());
+    }
+    let black = render(screen, cursor, width, height, wingdi::BLACKNESS);
+    let white = render(screen, cursor, width, height, wingdi::WHITENESS);
+    winuser::ReleaseDC(ptr::null_mut(), screen);
+    let (black, white) = (black?, white?);
+
+    // Pixels are stored as BGRX. A pixel drawn with alpha `a` over black is
+    // `a * c` and over white is `a * c + 255 - a`, so the difference gives the
+    // alpha. Inverted pixels come out negative and are treated as opaque.
+    let mut data = Vec::with_capacity(width * height * 4);
+    for (b, w) in black.chunks(4).zip(white.chunks(4)) {
+        let alpha = (255 - (w[1] as i32 - b[1] as i32)).clamp(0, 255);
+        let unpremultiply = |c: u8| match alpha {
+            0 => 0,
+            _ => (c as i32 * 255 / alpha).min(255) as u8,
+        };
+        data.extend_from_slice(&[
+            unpremultiply(b[2]),
+            unpremultiply(b[1]),
+            unpremultiply(b[0]),
+            alpha as u8,
+        ]);
+    }
+    Ok(Image::from_rgba(width, height, data).expect("invalid cursor image size"))
+}
+
+/// Draws `cursor` over a background filled with `fill`, a raster operation
+/// such as `BLACKNESS`, and returns the BGRX pixels.
+unsafe fn render(
+    screen: HDC,
+    cursor: HCURSOR,
+    width: usize,
+    height: usize,
+    fill: u32,
+) -> Result<Vec<u8>> {
+    let memory = wingdi::CreateCompatibleDC(screen);
+    if memory.is_null() {
+        return Err(OsError::last().into());
+    }
+
+    let mut info: wingdi::BITMAPINFO = mem::zeroed();
+    info.bmiHeader.biSize = mem::size_of::<wingdi::BITMAPINFOHEADER>() as u32;
+    info.bmiHeader.biWidth = width as i32;
+    info.bmiHeader.biHeight = -(height as i32);
+    info.bmiHeader.biPlanes = 1;
+    info.bmiHeader.biBitCount = 32;
+    info.bmiHeader.biCompression = wingdi::BI_RGB;
+
+    let mut bits: *mut c_void = ptr::null_mut();
+    let bitmap = wingdi::CreateDIBSection(
+        memory,
+        &info,
+        wingdi::DIB_RGB_COLORS,
+        &mut bits,
+        ptr::null_mut(),
+        0,
+    );
+    if bitmap.is_null() || bits.is_null() {
+        let err = OsError::last();
+        wingdi::DeleteDC(memory);
+        return Err(err.into());
+    }
+
+    let previous = wingdi::SelectObject(memory, bitmap as _);
+    let drawn = wingdi::PatBlt(memory, 0, 0, width as c_int, height as c_int, fill) != 0
+        && winuser::DrawIconEx(
+            memory,
+            0,
+            0,
+            cursor,
+            width as c_int,
+            height as c_int,
+            0,
+            ptr::null_mut(),
+            DI_NORMAL,
+        ) != 0;
+    let result = if drawn {
+        Ok(slice::from_raw_parts(bits as *const u8, width * height * 4).to_vec())
+    } else {
+        Err(OsError::last().into())
+    };
+
+    wingdi::SelectObject(memory, previous);
+    wingdi::DeleteObject(bitmap as _);
+    wingdi::DeleteDC(memory);
+    result
+}
+
+/// A standard cursor provided by the system.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum SystemCursor {
+    /// The normal arrow cursor, `IDC_ARROW`.
+    Arrow,
+    /// The I-beam cursor for selecting text, `IDC_IBEAM`.
+    IBeam,
+    /// The hourglass or spinner shown while busy, `IDC_WAIT`.
+    Wait,
+    /// The arrow with a spinner shown while an application starts,
+    /// `IDC_APPSTARTING`.
+    AppStarting,
+    /// The crosshair cursor, `IDC_CROSS`.
+    Cross,
+    /// The pointing hand cursor over links, `IDC_HAND`.
+    Hand,
+    /// The cursor for resizing left and right, `IDC_SIZEWE`.
+    SizeWE,
+    /// The cursor for resizing up and down, `IDC_SIZENS`.
+    SizeNS,
+    /// The slashed circle for an operation that is not allowed, `IDC_NO`.
+    No,
+}
+
+impl SystemCursor {
+    /// All standard cursors.
+    pub const ALL: [SystemCursor; 9] = [
+        SystemCursor::Arrow,
+        SystemCursor::IBeam,
+        SystemCursor::Wait,
+        SystemCursor::AppStarting,
+        SystemCursor::Cross,
+        SystemCursor::Hand,
+        SystemCursor::SizeWE,
+        SystemCursor::SizeNS,
+        SystemCursor::No,
+    ];
+
+    fn resource(self) -> LPCWSTR {
+        match self {
+            SystemCursor::Arrow => winuser::IDC_ARROW,
+            SystemCursor::IBeam => winuser::IDC_IBEAM,
+            SystemCursor::Wait => winuser::IDC_WAIT,
+            SystemCursor::AppStarting => winuser::IDC_APPSTARTING,
+            SystemCursor::Cross => winuser::IDC_CROSS,
+            SystemCursor::Hand => winuser::IDC_HAND,
+            SystemCursor::SizeWE => winuser::IDC_SIZEWE,
+            SystemCursor::SizeNS => winuser::IDC_SIZENS,
+            SystemCursor::No => winuser::IDC_NO,
+        }
+    }
+
+    /// Returns the shared handle of the cursor.
+    pub fn handle(self) -> Result<HCURSOR> {
+        let cursor = unsafe { winuser::LoadCursorW(ptr::null_mut(), self.resource()) };
+        if cursor.is_null() {
+            Err(OsError::last().into())
+        } else {
+            Ok(cursor)
+        }
+    }
+
+    /// Returns the image of the cursor.
+    pub fn image(self) -> Result<Image> {
+        let cursor = self.handle()?;
+        unsafe { draw_cursor(cursor) }
+    }
+
+    /// Returns the standard cursor currently shown, or `None` if the cursor
+    /// is hidden or was set by an application.
+    pub fn current() -> Result<Option<SystemCursor>> {
+        let cursor = match current_cursor()? {
+            Some(cursor) => cursor,
+            None => return Ok(None),
+        };
+        for &system in &SystemCursor::ALL {
+            if system.handle()? == cursor {
+                return Ok(Some(system));
+            }
+        }
+        Ok(None)
+    }
+}
 
 #[cfg(test)]
 mod tests {
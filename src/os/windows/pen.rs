@@ -0,0 +1,177 @@
+//! ✒️ Pen injection utilities.
+//!
+//! Pens are injected as `WM_POINTER` pen input from a synthetic pointer
+//! device, so drawing and note-taking applications see pressure, tilt, and
+//! rotation as they would from a real pen. Pen injection requires Windows 10
+//! version 1809 or later.
+//!
+//! # Examples
+//!
+//! Draw a line that presses harder towards its end:
+//!
+//! ```no_run
+//! use auto::os::pen::{Pen, PenState, Stage};
+//!
+//! let pen = Pen::new().unwrap();
+//! let mut state = PenState::default();
+//! pen.inject((100, 100), Stage::Hover, &state).unwrap();
+//! pen.inject((100, 100), Stage::Down, &state).unwrap();
+//! for i in 1..=50 {
+//!     state.pressure = i as f64 / 50.0;
+//!     pen.inject((100 + i * 4, 100), Stage::Drag, &state).unwrap();
+//! }
+//! pen.inject((300, 100), Stage::Up, &state).unwrap();
+//! pen.inject((300, 100), Stage::Leave, &state).unwrap();
+//! ```
+
+use std::mem;
+
+use winapi::shared::windef::POINT;
+use winapi::um::winuser::{
+    self,
+    CreateSyntheticPointerDevice,
+    DestroySyntheticPointerDevice,
+    InjectSyntheticPointerInput,
+    HSYNTHETICPOINTERDEVICE,
+    POINTER_FLAGS,
+    POINTER_TYPE_INFO,
+};
+
+use error::{OsError, Result};
+
+/// The largest pressure that Windows reports for a pen.
+const MAX_PRESSURE: f64 = 1024.0;
+
+/// A stage in the movement of an injected pen.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum Stage {
+    /// The pen moved while hovering within range of the screen.
+    Hover,
+    /// The pen touched down.
+    Down,
+    /// The pen moved while touching.
+    Drag,
+    /// The pen lifted off, and is still within range.
+    Up,
+    /// The pen left the range of the screen.
+    Leave,
+}
+
+impl Stage {
+    fn flags(self) -> POINTER_FLAGS {
+        match self {
+            Stage::Hover => winuser::POINTER_FLAG_UPDATE | winuser::POINTER_FLAG_INRANGE,
+            Stage::Down => {
+                winuser::POINTER_FLAG_DOWN
+                    | winuser::POINTER_FLAG_INRANGE
+                    | winuser::POINTER_FLAG_INCONTACT
+            },
+            Stage::Drag => {
+                winuser::POINTER_FLAG_UPDATE
+                    | winuser::POINTER_FLAG_INRANGE
+                    | winuser::POINTER_FLAG_INCONTACT
+            },
+            Stage::Up => winuser::POINTER_FLAG_UP | winuser::POINTER_FLAG_INRANGE,
+            Stage::Leave => winuser::POINTER_FLAG_UPDATE,
+        }
+    }
+
+    fn in_contact(self) -> bool {
+        match self {
+            Stage::Down | Stage::Drag => true,
+            Stage::Hover | Stage::Up | Stage::Leave => false,
+        }
+    }
+}
+
+/// The state of the pen at an injected point.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PenState {
+    /// The pressure on the tip while touching, ranging from 0 to 1.
+    pub pressure: f64,
+    /// The tilt of the pen to the right and towards the user, in degrees
+    /// ranging from -90 to 90.
+    pub tilt: (i32, i32),
+    /// The clockwise rotation of the pen about its axis, in degrees ranging
+    /// from 0 to 359.
+    pub rotation: u32,
+    /// Whether the barrel button is held.
+    pub barrel: bool,
+    /// Whether the eraser end of the pen is used.
+    pub eraser: bool,
+}
+
+impl Default for PenState {
+    #[inline]
+    fn default() -> PenState {
+        PenState { pressure: 1.0, tilt: (0, 0), rotation: 0, barrel: false, eraser: false }
+    }
+}
+
+/// A synthetic pen device, which is removed when dropped.
+#[derive(Debug)]
+pub struct Pen(HSYNTHETICPOINTERDEVICE);
+
+// The device may be used from any thread of the process
+unsafe impl Send for Pen {}
+
+impl Drop for Pen {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { DestroySyntheticPointerDevice(self.0) };
+    }
+}
+
+impl Pen {
+    /// Creates a synthetic pen device.
+    pub fn new() -> Result<Pen> {
+        let device = unsafe {
+            CreateSyntheticPointerDevice(winuser::PT_PEN, 1, winuser::POINTER_FEEDBACK_DEFAULT)
+        };
+        if device.is_null() {
+            Err(OsError::last().into())
+        } else {
+            Ok(Pen(device))
+        }
+    }
+
+    /// Injects the pen at `point` in screen coordinates, in `stage` and with
+    /// `state`.
+    ///
+    /// Strokes start with [`Hover`](enum.Stage.html#variant.Hover) or
+    /// [`Down`](enum.Stage.html#variant.Down) and end with
+    /// [`Leave`](enum.Stage.html#variant.Leave).
+    pub fn inject(&self, (x, y): (i32, i32), stage: Stage, state: &PenState) -> Result<()> {
+        unsafe {
+            let mut input: POINTER_TYPE_INFO = mem::zeroed();
+            input.type_ = winuser::PT_PEN;
+            {
+                let pen = input.u.penInfo_mut();
+                pen.pointerInfo.pointerType = winuser::PT_PEN;
+                pen.pointerInfo.pointerFlags = stage.flags();
+                pen.pointerInfo.ptPixelLocation = POINT { x, y };
+                pen.penMask = winuser::PEN_MASK_PRESSURE
+                    | winuser::PEN_MASK_ROTATION
+                    | winuser::PEN_MASK_TILT_X
+                    | winuser::PEN_MASK_TILT_Y;
+                if state.barrel {
+                    pen.penFlags |= winuser::PEN_FLAG_BARREL;
+                }
+                if state.eraser {
+                    pen.penFlags |= winuser::PEN_FLAG_ERASER | winuser::PEN_FLAG_INVERTED;
+                }
+                if stage.in_contact() {
+                    pen.pressure = (state.pressure.clamp(0.0, 1.0) * MAX_PRESSURE) as u32;
+                }
+                pen.rotation = state.rotation % 360;
+                pen.tiltX = state.tilt.0.clamp(-90, 90);
+                pen.tiltY = state.tilt.1.clamp(-90, 90);
+            }
+            if InjectSyntheticPointerInput(self.0, &input, 1) != 0 {
+                Ok(())
+            } else {
+                Err(OsError::last().into())
+            }
+        }
+    }
+}
@@ -0,0 +1,31 @@
+//! 🔋 Power management utilities.
+
+use winapi::um::{winbase, winnt};
+
+use error::{Error, Result};
+
+bitflags! {
+    /// Requirements that keep the system or display from sleeping when the
+    /// user is idle.
+    pub struct ExecutionState: u32 {
+        /// Keeps the system awake.
+        const SYSTEM_REQUIRED  = winnt::ES_SYSTEM_REQUIRED;
+        /// Keeps the display on, which also keeps the system awake.
+        const DISPLAY_REQUIRED = winnt::ES_DISPLAY_REQUIRED;
+        /// Makes the requirements last until they are set again, rather than
+        /// only resetting the idle timers once.
+        const CONTINUOUS       = winnt::ES_CONTINUOUS;
+    }
+}
+
+/// Sets the requirements of the calling thread with
+/// `SetThreadExecutionState`, returning the previous requirements.
+///
+/// The system combines the requirements of every thread, and drops those of a
+/// thread when it exits.
+pub fn set_thread_execution_state(state: ExecutionState) -> Result<ExecutionState> {
+    match unsafe { winbase::SetThreadExecutionState(state.bits()) } {
+        0 => Err(Error::Failed),
+        previous => Ok(ExecutionState::from_bits_truncate(previous)),
+    }
+}
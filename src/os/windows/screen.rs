@@ -1,43 +1,435 @@
 //! 📺 Screen information utilities.
 
-use std::ptr;
+use std::{mem, ptr, slice};
 
+use winapi::Interface;
+use winapi::ctypes::c_void;
+use winapi::shared::dxgi::{CreateDXGIFactory1, IDXGIAdapter, IDXGIFactory1, IDXGIResource};
+use winapi::shared::dxgi1_2::{IDXGIOutput1, IDXGIOutputDuplication};
+use winapi::shared::dxgitype::{DXGI_MODE_ROTATION_IDENTITY, DXGI_MODE_ROTATION_UNSPECIFIED};
+use winapi::shared::{minwindef, windef};
+use winapi::shared::winerror::{
+    DXGI_ERROR_NOT_FOUND,
+    DXGI_ERROR_WAIT_TIMEOUT,
+    HRESULT,
+    SUCCEEDED,
+};
+use winapi::um::d3d11::{
+    D3D11CreateDevice,
+    D3D11_CPU_ACCESS_READ,
+    D3D11_MAP_READ,
+    D3D11_SDK_VERSION,
+    D3D11_USAGE_STAGING,
+    ID3D11Device,
+    ID3D11DeviceContext,
+    ID3D11Resource,
+    ID3D11Texture2D,
+};
+use winapi::um::d3dcommon::D3D_DRIVER_TYPE_UNKNOWN;
+use winapi::um::shellscalingapi::{GetDpiForMonitor, MDT_EFFECTIVE_DPI};
 use winapi::um::wingdi;
 use winapi::um::winuser;
 
 use color::Rgb;
+use error::{Error, OsError, Result};
+use image::{ColorGrid, Image};
+use metrics;
+use super::Com;
 
-/// Returns the color on the screen at `x` and `y`.
-pub fn color_at((x, y): (usize, usize)) -> Option<Rgb> {
-    unsafe {
-        let hdc = winuser::GetDC(ptr::null_mut());
-        if hdc.is_null() {
-            return None;
+/// The location and dimensions of an area of the screen in pixels.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Bounds {
+    /// Coordinates of the upper-left corner.
+    pub origin: (i32, i32),
+    /// Width and height of the bounds.
+    pub size: (usize, usize),
+}
+
+/// Returns the bounds of the virtual screen, which spans all monitors.
+pub fn virtual_bounds() -> Bounds {
+    super::with_physical_pixels(|| unsafe {
+        Bounds {
+            origin: (
+                winuser::GetSystemMetrics(winuser::SM_XVIRTUALSCREEN),
+                winuser::GetSystemMetrics(winuser::SM_YVIRTUALSCREEN),
+            ),
+            size: (
+                winuser::GetSystemMetrics(winuser::SM_CXVIRTUALSCREEN) as usize,
+                winuser::GetSystemMetrics(winuser::SM_CYVIRTUALSCREEN) as usize,
+            ),
         }
+    })
+}
 
-        let color = wingdi::GetPixel(hdc, x as _, y as _);
-        if color as usize == 0xFFFFFFFF {
-            return None;
+/// A monitor attached to the computer.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Monitor {
+    /// The area of the virtual screen covered by the monitor.
+    pub bounds: Bounds,
+    /// Whether the monitor is the primary monitor, whose upper-left corner is
+    /// at (0,0).
+    pub is_primary: bool,
+    /// The effective DPI of the monitor, which is 96 at a scale of 100% in
+    /// the display settings.
+    pub dpi: u32,
+}
+
+/// The DPI of monitors at a scale of 100%.
+pub const DEFAULT_DPI: u32 = 96;
+
+unsafe extern "system" fn push_monitor(
+    monitor: windef::HMONITOR,
+    _: windef::HDC,
+    _: windef::LPRECT,
+    data: minwindef::LPARAM,
+) -> minwindef::BOOL {
+    let monitors = &mut *(data as *mut Vec<Monitor>);
+
+    let mut info: winuser::MONITORINFO = mem::zeroed();
+    info.cbSize = mem::size_of::<winuser::MONITORINFO>() as u32;
+    if winuser::GetMonitorInfoW(monitor, &mut info) != 0 {
+        let (mut dpi, mut dpi_y) = (0, 0);
+        if !SUCCEEDED(GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi, &mut dpi_y)) {
+            dpi = DEFAULT_DPI;
+        }
+        let rect = info.rcMonitor;
+        monitors.push(Monitor {
+            bounds: Bounds {
+                origin: (rect.left, rect.top),
+                size: ((rect.right - rect.left) as usize, (rect.bottom - rect.top) as usize),
+            },
+            is_primary: info.dwFlags & winuser::MONITORINFOF_PRIMARY != 0,
+            dpi,
+        });
+    }
+    minwindef::TRUE
+}
+
+/// Returns all monitors attached to the computer, with their bounds in
+/// physical pixels.
+pub fn monitors() -> Result<Vec<Monitor>> {
+    let mut monitors = Vec::<Monitor>::new();
+    let data = &mut monitors as *mut Vec<Monitor> as minwindef::LPARAM;
+    let ok = super::with_physical_pixels(|| unsafe {
+        winuser::EnumDisplayMonitors(ptr::null_mut(), ptr::null(), Some(push_monitor), data)
+    });
+    if ok == 0 {
+        return Err(OsError::last().into());
+    }
+    Ok(monitors)
+}
+
+/// Captures the contents of all monitors.
+#[inline]
+pub fn capture() -> Result<Image> {
+    capture_rect(virtual_bounds())
+}
+
+/// Captures the contents of `rect` on the screen.
+pub fn capture_rect(rect: Bounds) -> Result<Image> {
+    let Bounds { origin: (x, y), size: (width, height) } = rect;
+
+    metrics::timed(|r, d| r.capture_duration(d), || super::with_physical_pixels(|| unsafe {
+        let screen = winuser::GetDC(ptr::null_mut());
+        if screen.is_null() {
+            return Err(OsError::last().into());
+        }
+        let result = blit(screen, x, y, width, height);
+        winuser::ReleaseDC(ptr::null_mut(), screen);
+        result
+    }))
+}
+
+/// Reads the colors of `rect` on the screen with a single copy of the
+/// screen.
+///
+/// This is much faster than reading each pixel with
+/// [`color_at`](fn.color_at.html).
+#[inline]
+pub fn colors_in_rect(rect: Bounds) -> Result<ColorGrid> {
+    capture_rect(rect).map(|image| ColorGrid::new(image, 1.0))
+}
+
+/// Copies an area of `screen` into a top-down DIB section and converts it to
+/// RGBA.
+unsafe fn blit(
+    screen: ::winapi::shared::windef::HDC,
+    x: i32,
+    y: i32,
+    width: usize,
+    height: usize,
+) -> Result<Image> {
+    let memory = wingdi::CreateCompatibleDC(screen);
+    if memory.is_null() {
+        return Err(OsError::last().into());
+    }
+
+    let mut info: wingdi::BITMAPINFO = mem::zeroed();
+    info.bmiHeader.biSize = mem::size_of::<wingdi::BITMAPINFOHEADER>() as u32;
+    info.bmiHeader.biWidth = width as i32;
+    // A negative height makes the first row the top of the image
+    info.bmiHeader.biHeight = -(height as i32);
+    info.bmiHeader.biPlanes = 1;
+    info.bmiHeader.biBitCount = 32;
+    info.bmiHeader.biCompression = wingdi::BI_RGB;
+
+    let mut bits: *mut c_void = ptr::null_mut();
+    let bitmap = wingdi::CreateDIBSection(
+        memory,
+        &info,
+        wingdi::DIB_RGB_COLORS,
+        &mut bits,
+        ptr::null_mut(),
+        0,
+    );
+    if bitmap.is_null() || bits.is_null() {
+        let err = OsError::last();
+        wingdi::DeleteDC(memory);
+        return Err(err.into());
+    }
+
+    let previous = wingdi::SelectObject(memory, bitmap as _);
+    let copied = wingdi::BitBlt(
+        memory,
+        0,
+        0,
+        width as i32,
+        height as i32,
+        screen,
+        x,
+        y,
+        wingdi::SRCCOPY | wingdi::CAPTUREBLT,
+    );
+    let result = if copied != 0 {
+        // Pixels are stored as BGRX
+        let len = width * height * 4;
+        let mut data = slice::from_raw_parts(bits as *const u8, len).to_vec();
+        for px in data.chunks_mut(4) {
+            px.swap(0, 2);
+            px[3] = 255;
         }
+        Ok(Image::from_rgba(width, height, data).expect("invalid DIB section size"))
+    } else {
+        Err(OsError::last().into())
+    };
 
-        let r = wingdi::GetRValue(color) as u8;
-        let g = wingdi::GetGValue(color) as u8;
-        let b = wingdi::GetBValue(color) as u8;
-        winuser::ReleaseDC(winuser::GetDesktopWindow(), hdc);
+    wingdi::SelectObject(memory, previous);
+    wingdi::DeleteObject(bitmap as _);
+    wingdi::DeleteDC(memory);
+    result
+}
 
-        Some(Rgb { red: r, green: g, blue: b })
+/// Converts the result of a COM method into a `Result`.
+fn check(hr: HRESULT) -> Result<()> {
+    if SUCCEEDED(hr) {
+        Ok(())
+    } else {
+        Err(OsError::from_code(hr).into())
     }
 }
 
-#[cfg(all(test, nightly))]
-mod benches {
-    use test::{Bencher, black_box};
+/// A stream of captures of an area of a single monitor, copied from the
+/// frames that Desktop Duplication delivers as the monitor's contents change.
+pub struct Duplication {
+    duplication: Com<IDXGIOutputDuplication>,
+    device: Com<ID3D11Device>,
+    context: Com<ID3D11DeviceContext>,
+    staging: Option<Com<ID3D11Texture2D>>,
+    /// The area relative to the monitor's upper-left corner.
+    rect: ((usize, usize), (usize, usize)),
+    latest: Option<Image>,
+}
 
-    #[bench]
-    fn color_at(b: &mut Bencher) {
-        let loc = (0, 0);
-        b.iter(|| {
-            black_box(super::color_at(black_box(loc)));
-        });
+impl Duplication {
+    /// Starts duplicating the monitor that contains all of `rect`.
+    ///
+    /// Returns [`Error::Unsupported`](../../error/enum.Error.html#variant.Unsupported)
+    /// if `rect` spans several monitors or lies on a rotated one.
+    pub fn new(rect: Bounds) -> Result<Duplication> {
+        super::with_physical_pixels(|| unsafe {
+            let mut factory: *mut IDXGIFactory1 = ptr::null_mut();
+            let hr = CreateDXGIFactory1(&IDXGIFactory1::uuidof(), &mut factory as *mut _ as *mut *mut c_void);
+            let factory = Com::required(hr, factory)?;
+            for a in 0.. {
+                let mut adapter = ptr::null_mut();
+                let hr = factory.get().EnumAdapters1(a, &mut adapter);
+                if hr == DXGI_ERROR_NOT_FOUND {
+                    break;
+                }
+                let adapter = Com::required(hr, adapter)?;
+                for o in 0.. {
+                    let mut output = ptr::null_mut();
+                    let hr = adapter.get().EnumOutputs(o, &mut output);
+                    if hr == DXGI_ERROR_NOT_FOUND {
+                        break;
+                    }
+                    let output = Com::required(hr, output)?;
+                    let mut desc = mem::zeroed();
+                    check(output.get().GetDesc(&mut desc))?;
+                    let windef::RECT { left, top, right, bottom } = desc.DesktopCoordinates;
+                    let (x, y) = (rect.origin.0 - left, rect.origin.1 - top);
+                    let (width, height) = rect.size;
+                    if x < 0 || y < 0
+                        || x as usize + width > (right - left) as usize
+                        || y as usize + height > (bottom - top) as usize
+                    {
+                        continue;
+                    }
+                    if !matches!(desc.Rotation, DXGI_MODE_ROTATION_IDENTITY | DXGI_MODE_ROTATION_UNSPECIFIED) {
+                        return Err(Error::Unsupported);
+                    }
+                    let rect = ((x as usize, y as usize), (width, height));
+                    return Duplication::start(&adapter, &output.cast()?, rect);
+                }
+            }
+            Err(Error::Unsupported)
+        })
+    }
+
+    unsafe fn start<A>(
+        adapter: &Com<A>,
+        output: &Com<IDXGIOutput1>,
+        rect: ((usize, usize), (usize, usize)),
+    ) -> Result<Duplication> {
+        let mut device = ptr::null_mut();
+        let mut context = ptr::null_mut();
+        let hr = D3D11CreateDevice(
+            adapter.as_ptr() as *mut IDXGIAdapter,
+            D3D_DRIVER_TYPE_UNKNOWN,
+            ptr::null_mut(),
+            0,
+            ptr::null(),
+            0,
+            D3D11_SDK_VERSION,
+            &mut device,
+            ptr::null_mut(),
+            &mut context,
+        );
+        let (device, context) = (Com::required(hr, device)?, Com::required(hr, context)?);
+        let mut duplication = ptr::null_mut();
+        let hr = output.get().DuplicateOutput(device.as_ptr() as *mut _, &mut duplication);
+        Ok(Duplication {
+            duplication: Com::required(hr, duplication)?,
+            device,
+            context,
+            staging: None,
+            rect,
+            latest: None,
+        })
+    }
+
+    /// Returns the most recent contents of the area in pixels, without
+    /// waiting for the monitor to present a new frame.
+    ///
+    /// Returns `None` if no frame has been presented since duplication
+    /// started.
+    pub fn latest(&mut self) -> Result<Option<Image>> {
+        let mut info = unsafe { mem::zeroed() };
+        let mut resource = ptr::null_mut();
+        let hr = unsafe { self.duplication.get().AcquireNextFrame(0, &mut info, &mut resource) };
+        if hr == DXGI_ERROR_WAIT_TIMEOUT {
+            return Ok(self.latest.clone());
+        }
+        let resource: Com<IDXGIResource> = Com::required(hr, resource)?;
+        // Frames that only move the pointer leave the image as it was
+        let result = match info.AccumulatedFrames {
+            0 if self.latest.is_some() => Ok(()),
+            _ => resource.cast().and_then(|texture| self.copy(&texture)),
+        };
+        unsafe { self.duplication.get().ReleaseFrame() };
+        result.map(|()| self.latest.clone())
+    }
+
+    /// Copies the area out of `texture` through a texture the CPU can read.
+    fn copy(&mut self, texture: &Com<ID3D11Texture2D>) -> Result<()> {
+        unsafe {
+            let staging = match self.staging {
+                Some(ref staging) => staging.clone(),
+                None => {
+                    let mut desc = mem::zeroed();
+                    texture.get().GetDesc(&mut desc);
+                    desc.Usage = D3D11_USAGE_STAGING;
+                    desc.BindFlags = 0;
+                    desc.CPUAccessFlags = D3D11_CPU_ACCESS_READ;
+                    desc.MiscFlags = 0;
+                    let mut staging = ptr::null_mut();
+                    let hr = self.device.get().CreateTexture2D(&desc, ptr::null(), &mut staging);
+                    self.staging.insert(Com::required(hr, staging)?).clone()
+                },
+            };
+            let staging = staging.as_ptr() as *mut ID3D11Resource;
+            let context = self.context.get();
+            context.CopyResource(staging, texture.as_ptr() as *mut ID3D11Resource);
+
+            let mut mapped = mem::zeroed();
+            check(context.Map(staging, 0, D3D11_MAP_READ, 0, &mut mapped))?;
+            let mut desc = mem::zeroed();
+            texture.get().GetDesc(&mut desc);
+            let stride = mapped.RowPitch as usize;
+            let pixels = slice::from_raw_parts(mapped.pData as *const u8, stride * desc.Height as usize);
+            let (origin, size) = self.rect;
+            let image = Image::crop_bgrx(pixels, stride, origin, size);
+            context.Unmap(staging, 0);
+            self.latest = Some(image.ok_or(Error::Failed)?);
+            Ok(())
+        }
+    }
+}
+
+/// Reads colors on the screen over and over, holding on to the screen's
+/// device context rather than acquiring it for every read as
+/// [`color_at`](fn.color_at.html) does.
+pub struct Sampler {
+    screen: windef::HDC,
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        unsafe { winuser::ReleaseDC(ptr::null_mut(), self.screen) };
+    }
+}
+
+impl Sampler {
+    /// Acquires the screen's device context.
+    pub fn new() -> Result<Sampler> {
+        let screen = unsafe { winuser::GetDC(ptr::null_mut()) };
+        if screen.is_null() {
+            return Err(OsError::last().into());
+        }
+        Ok(Sampler { screen })
+    }
+
+    /// Returns the color on the screen at `x` and `y` in physical pixels, or
+    /// `None` if it can't be read.
+    pub fn sample(&self, (x, y): (i32, i32)) -> Option<Rgb> {
+        let color = super::with_physical_pixels(|| unsafe { wingdi::GetPixel(self.screen, x, y) });
+        rgb(color)
+    }
+}
+
+/// Returns the color of the `COLORREF` `color`, or `None` if it is
+/// `CLR_INVALID`.
+fn rgb(color: windef::COLORREF) -> Option<Rgb> {
+    if color == wingdi::CLR_INVALID {
+        return None;
     }
+    Some(Rgb {
+        red: wingdi::GetRValue(color),
+        green: wingdi::GetGValue(color),
+        blue: wingdi::GetBValue(color),
+    })
+}
+
+/// Returns the color on the screen at `x` and `y` in physical pixels, which
+/// are negative on monitors above or left of the primary monitor.
+pub fn color_at((x, y): (i32, i32)) -> Option<Rgb> {
+    super::with_physical_pixels(|| unsafe {
+        let hdc = winuser::GetDC(ptr::null_mut());
+        if hdc.is_null() {
+            return None;
+        }
+        let color = wingdi::GetPixel(hdc, x, y);
+        winuser::ReleaseDC(ptr::null_mut(), hdc);
+        rgb(color)
+    })
 }
@@ -0,0 +1,102 @@
+//! 👆 Touch injection utilities.
+//!
+//! Contacts are injected as `WM_POINTER` touch input, which applications and
+//! Direct Manipulation interpret as gestures in the same way as input from a
+//! touchscreen or precision touchpad. Touch injection requires Windows 8 or
+//! later.
+
+use std::mem;
+
+use winapi::shared::windef::{POINT, RECT};
+use winapi::um::winuser::{
+    self,
+    InitializeTouchInjection,
+    InjectTouchInput,
+    POINTER_FLAGS,
+    POINTER_TOUCH_INFO,
+};
+
+use error::{OsError, Result};
+
+/// The number of contacts that may be in contact at once.
+pub const MAX_CONTACTS: usize = 2;
+
+/// Half the width of the area reported for each contact, in pixels.
+const CONTACT_RADIUS: i32 = 2;
+
+/// A stage in the life of injected contacts.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum Stage {
+    /// The contacts touched down.
+    Down,
+    /// The contacts moved while touching.
+    Update,
+    /// The contacts lifted off.
+    Up,
+}
+
+impl Stage {
+    fn flags(self) -> POINTER_FLAGS {
+        match self {
+            Stage::Down => {
+                winuser::POINTER_FLAG_DOWN
+                    | winuser::POINTER_FLAG_INRANGE
+                    | winuser::POINTER_FLAG_INCONTACT
+            },
+            Stage::Update => {
+                winuser::POINTER_FLAG_UPDATE
+                    | winuser::POINTER_FLAG_INRANGE
+                    | winuser::POINTER_FLAG_INCONTACT
+            },
+            Stage::Up => winuser::POINTER_FLAG_UP,
+        }
+    }
+}
+
+lazy_static! {
+    /// Whether touch injection was initialized, or the error it failed with.
+    static ref INITIALIZED: ::std::result::Result<(), OsError> = unsafe {
+        let succeeded = InitializeTouchInjection(
+            MAX_CONTACTS as _,
+            winuser::TOUCH_FEEDBACK_DEFAULT,
+        ) != 0;
+        if succeeded { Ok(()) } else { Err(OsError::last()) }
+    };
+}
+
+fn initialize() -> Result<()> {
+    (*INITIALIZED).map_err(Into::into)
+}
+
+/// Injects contacts at the given screen coordinates, identified by their
+/// index, in a single frame.
+///
+/// Contacts that touched down must be lifted off in a later call with the same
+/// number of contacts. At most [`MAX_CONTACTS`](constant.MAX_CONTACTS.html)
+/// contacts may be injected at once.
+pub fn inject(points: &[(i32, i32)], stage: Stage) -> Result<()> {
+    assert!(points.len() <= MAX_CONTACTS, "too many touch contacts");
+    initialize()?;
+    unsafe {
+        let mut contacts: [POINTER_TOUCH_INFO; MAX_CONTACTS] = mem::zeroed();
+        for (id, (contact, &(x, y))) in contacts.iter_mut().zip(points).enumerate() {
+            contact.pointerInfo.pointerType = winuser::PT_TOUCH;
+            contact.pointerInfo.pointerId = id as _;
+            contact.pointerInfo.pointerFlags = stage.flags();
+            contact.pointerInfo.ptPixelLocation = POINT { x, y };
+            contact.touchFlags = winuser::TOUCH_FLAG_NONE;
+            contact.touchMask = winuser::TOUCH_MASK_CONTACTAREA;
+            contact.rcContact = RECT {
+                left: x - CONTACT_RADIUS,
+                top: y - CONTACT_RADIUS,
+                right: x + CONTACT_RADIUS,
+                bottom: y + CONTACT_RADIUS,
+            };
+        }
+        if InjectTouchInput(points.len() as _, contacts.as_ptr()) != 0 {
+            Ok(())
+        } else {
+            Err(OsError::last().into())
+        }
+    }
+}
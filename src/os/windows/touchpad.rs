@@ -0,0 +1,393 @@
+//! 🖐️ Precision touchpad contact utilities.
+//!
+//! Precision touchpads report every finger on them as a HID digitizer, which
+//! Windows turns into wheel and zoom input for applications. A
+//! [`Touchpad`](struct.Touchpad.html) reads the fingers themselves through
+//! raw input, so that gestures can be told apart from wheel clicks.
+//!
+//! Raw input registrations are per process, so reading touchpads replaces
+//! any touchpad registration made by the process's own windows.
+
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+use std::{mem, ptr, slice};
+
+use winapi::shared::hidpi::{
+    HidP_GetCaps,
+    HidP_GetUsageValue,
+    HidP_GetUsages,
+    HidP_GetValueCaps,
+    HidP_Input,
+    HIDP_CAPS,
+    HIDP_STATUS_SUCCESS,
+    HIDP_VALUE_CAPS,
+    PHIDP_PREPARSED_DATA,
+};
+use winapi::shared::minwindef::{DWORD, UINT};
+use winapi::shared::windef::HWND;
+use winapi::um::processthreadsapi::GetCurrentThreadId;
+use winapi::um::winnt::{HANDLE, PCHAR};
+use winapi::um::winuser::{
+    self,
+    CreateWindowExW,
+    DestroyWindow,
+    DispatchMessageW,
+    GetMessageW,
+    GetRawInputData,
+    GetRawInputDeviceInfoW,
+    HRAWINPUT,
+    PeekMessageW,
+    PostThreadMessageW,
+    RegisterRawInputDevices,
+    PM_NOREMOVE,
+    RAWINPUT,
+    RAWINPUTDEVICE,
+    RAWINPUTHEADER,
+    WM_QUIT,
+    WM_USER,
+};
+
+use error::{OsError, Result};
+
+/// The HID usage page of digitizers.
+const DIGITIZER_PAGE: u16 = 0x0D;
+
+/// The HID usage page of generic desktop controls, including axes.
+const GENERIC_DESKTOP_PAGE: u16 = 0x01;
+
+// HID usages on the digitizer page
+const TOUCH_PAD: u16      = 0x05;
+const TIP_SWITCH: u16     = 0x42;
+const CONTACT_ID: u16     = 0x51;
+const CONTACT_COUNT: u16  = 0x54;
+
+// HID usages on the generic desktop page
+const X: u16 = 0x30;
+const Y: u16 = 0x31;
+
+// HID units of length
+const CENTIMETER: u32 = 0x11;
+const INCH: u32       = 0x13;
+
+/// Device-independent pixels per inch.
+const PIXELS_PER_INCH: f64 = 96.0;
+
+/// A finger touching a touchpad.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Contact {
+    /// The number that identifies the finger for as long as it touches.
+    pub id: u32,
+    /// The location of the finger from the upper-left corner of the
+    /// touchpad, in device-independent pixels of 1/96 inch.
+    ///
+    /// Touchpads that don't report their size use their own units instead.
+    pub location: (f64, f64),
+}
+
+/// An axis of a finger's location.
+#[derive(Copy, Clone, Debug)]
+struct Axis {
+    min: i32,
+    /// Device-independent pixels per logical unit.
+    scale: f64,
+}
+
+impl Axis {
+    fn new(caps: &HIDP_VALUE_CAPS) -> Axis {
+        let logical = f64::from(caps.LogicalMax) - f64::from(caps.LogicalMin);
+        let physical = f64::from(caps.PhysicalMax) - f64::from(caps.PhysicalMin);
+        // The exponent is a signed nibble
+        let exponent = ((caps.UnitsExp as i32 & 0xF) ^ 8) - 8;
+        let inches = match caps.Units {
+            CENTIMETER => physical * 10f64.powi(exponent) / 2.54,
+            INCH => physical * 10f64.powi(exponent),
+            _ => 0.0,
+        };
+        let scale = if inches > 0.0 && logical > 0.0 {
+            inches * PIXELS_PER_INCH / logical
+        } else {
+            1.0
+        };
+        Axis { min: caps.LogicalMin, scale }
+    }
+
+    fn location(&self, value: u32) -> f64 {
+        (f64::from(value as i32) - f64::from(self.min)) * self.scale
+    }
+}
+
+/// The link collection that reports one finger, and its axes.
+#[derive(Copy, Clone, Debug)]
+struct Finger {
+    link: u16,
+    x: Axis,
+    y: Axis,
+}
+
+/// A touchpad's report descriptor and the fingers it reports.
+struct Device {
+    /// The preparsed report descriptor, kept aligned for the HID parser.
+    preparsed: Vec<u64>,
+    fingers: Vec<Finger>,
+}
+
+impl Device {
+    unsafe fn open(handle: HANDLE) -> Option<Device> {
+        let mut size: UINT = 0;
+        GetRawInputDeviceInfoW(handle, winuser::RIDI_PREPARSEDDATA, ptr::null_mut(), &mut size);
+        let mut preparsed = vec![0u64; (size as usize).div_ceil(8)];
+        let read = GetRawInputDeviceInfoW(
+            handle,
+            winuser::RIDI_PREPARSEDDATA,
+            preparsed.as_mut_ptr() as _,
+            &mut size,
+        );
+        if size == 0 || read == !0 {
+            return None;
+        }
+        let data = preparsed.as_mut_ptr() as PHIDP_PREPARSED_DATA;
+
+        let mut caps: HIDP_CAPS = mem::zeroed();
+        if HidP_GetCaps(data, &mut caps) != HIDP_STATUS_SUCCESS {
+            return None;
+        }
+        let mut count = caps.NumberInputValueCaps;
+        let mut values: Vec<HIDP_VALUE_CAPS> = vec![mem::zeroed(); count as usize];
+        if HidP_GetValueCaps(HidP_Input, values.as_mut_ptr(), &mut count, data) != HIDP_STATUS_SUCCESS {
+            return None;
+        }
+        values.truncate(count as usize);
+
+        // Each finger has its own link collection with an X and Y axis
+        let axis = |link: u16, usage: u16| values.iter().find(|caps| {
+            caps.UsagePage == GENERIC_DESKTOP_PAGE
+                && caps.LinkCollection == link
+                && caps.IsRange == 0
+                && caps.u.NotRange().Usage == usage
+        });
+        let mut fingers: Vec<Finger> = values.iter()
+            .filter_map(|caps| {
+                let (x, y) = (axis(caps.LinkCollection, X)?, axis(caps.LinkCollection, Y)?);
+                Some(Finger { link: caps.LinkCollection, x: Axis::new(x), y: Axis::new(y) })
+            })
+            .collect();
+        fingers.sort_by_key(|finger| finger.link);
+        fingers.dedup_by_key(|finger| finger.link);
+        Some(Device { preparsed, fingers })
+    }
+}
+
+/// Reads the reports of every touchpad.
+#[derive(Default)]
+struct Reader {
+    devices: HashMap<usize, Option<Device>>,
+    frame: Frame,
+}
+
+impl Reader {
+    unsafe fn read(&mut self, input: HRAWINPUT, callback: &mut Callback) {
+        let header = mem::size_of::<RAWINPUTHEADER>() as UINT;
+        let mut size: UINT = 0;
+        GetRawInputData(input, winuser::RID_INPUT, ptr::null_mut(), &mut size, header);
+        let len = (size as usize).max(mem::size_of::<RAWINPUT>());
+        let mut buffer = vec![0u64; len.div_ceil(8)];
+        let read = GetRawInputData(input, winuser::RID_INPUT, buffer.as_mut_ptr() as _, &mut size, header);
+        if read == !0 {
+            return;
+        }
+        let input = &*(buffer.as_ptr() as *const RAWINPUT);
+        if input.header.dwType != winuser::RIM_TYPEHID {
+            return;
+        }
+        let hid = input.data.hid();
+        let device = self.devices
+            .entry(input.header.hDevice as usize)
+            .or_insert_with(|| Device::open(input.header.hDevice));
+        let device = match *device {
+            Some(ref device) => device,
+            None => return,
+        };
+        let length = hid.dwSizeHid as usize;
+        let start = hid.bRawData.as_ptr() as usize - buffer.as_ptr() as usize;
+        if start + length * hid.dwCount as usize > read as usize {
+            return;
+        }
+        let reports = slice::from_raw_parts(hid.bRawData.as_ptr(), length * hid.dwCount as usize);
+        for report in reports.chunks(length.max(1)) {
+            if let Some(contacts) = self.frame.read(device, report) {
+                callback(contacts);
+            }
+        }
+    }
+}
+
+/// The fingers of a frame, which may span several reports.
+#[derive(Default)]
+struct Frame {
+    contacts: Vec<Contact>,
+    /// The number of fingers the frame reports, whether touching or not.
+    expected: usize,
+    /// The number of fingers read so far.
+    seen: usize,
+}
+
+impl Frame {
+    /// Reads the fingers in `report`, returning the frame once it has every
+    /// finger.
+    unsafe fn read(&mut self, device: &Device, report: &[u8]) -> Option<&[Contact]> {
+        let data = device.preparsed.as_ptr() as PHIDP_PREPARSED_DATA;
+        let (report, length) = (report.as_ptr() as PCHAR, report.len() as u32);
+        let value = |page: u16, link: u16, usage: u16| {
+            let mut value = 0;
+            let status = HidP_GetUsageValue(HidP_Input, page, link, usage, &mut value, data, report, length);
+            if status == HIDP_STATUS_SUCCESS { Some(value) } else { None }
+        };
+
+        // Other reports, such as for the buttons, have no contact count. In
+        // hybrid mode, a frame's later reports have a count of zero.
+        let count = value(DIGITIZER_PAGE, 0, CONTACT_COUNT)? as usize;
+        if count > 0 {
+            self.contacts.clear();
+            self.expected = count;
+            self.seen = 0;
+        }
+        for finger in &device.fingers {
+            if self.seen >= self.expected {
+                break;
+            }
+            let id = match value(DIGITIZER_PAGE, finger.link, CONTACT_ID) {
+                Some(id) => id,
+                None => continue,
+            };
+            self.seen += 1;
+
+            let mut usages = [0u16; 16];
+            let mut len = usages.len() as u32;
+            let status = HidP_GetUsages(
+                HidP_Input, DIGITIZER_PAGE, finger.link,
+                usages.as_mut_ptr(), &mut len, data, report, length,
+            );
+            let touching = status == HIDP_STATUS_SUCCESS && usages[..len as usize].contains(&TIP_SWITCH);
+            if let (true, Some(x), Some(y)) = (
+                touching,
+                value(GENERIC_DESKTOP_PAGE, finger.link, X),
+                value(GENERIC_DESKTOP_PAGE, finger.link, Y),
+            ) {
+                self.contacts.push(Contact { id, location: (finger.x.location(x), finger.y.location(y)) });
+            }
+        }
+        if self.expected > 0 && self.seen >= self.expected {
+            self.expected = 0;
+            Some(&self.contacts)
+        } else {
+            None
+        }
+    }
+}
+
+type Callback = Box<dyn FnMut(&[Contact]) + Send>;
+
+/// Registers `window` to receive input from touchpads, or removes the
+/// registration if it is null.
+unsafe fn register(window: HWND) -> bool {
+    let device = RAWINPUTDEVICE {
+        usUsagePage: DIGITIZER_PAGE,
+        usUsage: TOUCH_PAD,
+        dwFlags: if window.is_null() { winuser::RIDEV_REMOVE } else { winuser::RIDEV_INPUTSINK },
+        hwndTarget: window,
+    };
+    RegisterRawInputDevices(&device, 1, mem::size_of::<RAWINPUTDEVICE>() as UINT) != 0
+}
+
+/// A reader of the fingers on every precision touchpad.
+#[derive(Debug)]
+pub struct Touchpad;
+
+impl Touchpad {
+    /// Reads touchpads on a new thread, which calls `callback` with the
+    /// fingers touching in each frame that a touchpad reports, until the
+    /// returned handle is stopped or dropped.
+    ///
+    /// The callback is called with no fingers once the last one lifts off.
+    /// Input is read whichever window is in the foreground.
+    pub fn spawn<F>(callback: F) -> Result<TouchpadHandle>
+        where F: FnMut(&[Contact]) + Send + 'static
+    {
+        let mut callback: Callback = Box::new(callback);
+        let (tx, rx) = mpsc::channel();
+
+        let thread = thread::Builder::new()
+            .name("auto-touchpad".into())
+            .spawn(move || unsafe {
+                let mut msg = mem::zeroed();
+
+                // Create the thread's message queue before anyone can post
+                // `WM_QUIT` to it
+                PeekMessageW(&mut msg, ptr::null_mut(), WM_USER, WM_USER, PM_NOREMOVE);
+
+                // Raw input goes to a window, which needn't be visible
+                let class: Vec<u16> = "STATIC\0".encode_utf16().collect();
+                let window = CreateWindowExW(
+                    0, class.as_ptr(), ptr::null(), 0, 0, 0, 0, 0,
+                    winuser::HWND_MESSAGE, ptr::null_mut(), ptr::null_mut(), ptr::null_mut(),
+                );
+                let result = if window.is_null() || !register(window) {
+                    Err(OsError::last())
+                } else {
+                    Ok(GetCurrentThreadId())
+                };
+
+                let registered = result.is_ok();
+                let _ = tx.send(result);
+                if registered {
+                    let mut reader = Reader::default();
+                    while GetMessageW(&mut msg, ptr::null_mut(), 0, 0) > 0 {
+                        if msg.message == winuser::WM_INPUT {
+                            reader.read(msg.lParam as HRAWINPUT, &mut callback);
+                        }
+                        // Lets the system clean up after raw input
+                        DispatchMessageW(&msg);
+                    }
+                    register(ptr::null_mut());
+                }
+                if !window.is_null() {
+                    DestroyWindow(window);
+                }
+            })
+            .expect("failed to spawn touchpad thread");
+
+        match rx.recv() {
+            Ok(Ok(thread_id)) => Ok(TouchpadHandle { thread_id, thread: Some(thread) }),
+            Ok(Err(err)) => {
+                let _ = thread.join();
+                Err(err.into())
+            },
+            Err(_) => panic!("touchpad thread exited during registration"),
+        }
+    }
+}
+
+/// A handle to a touchpad reader running on its own thread.
+///
+/// Reading stops when the handle is dropped.
+#[derive(Debug)]
+pub struct TouchpadHandle {
+    thread_id: DWORD,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TouchpadHandle {
+    /// Stops reading and waits for the thread to exit.
+    #[inline]
+    pub fn stop(self) {}
+}
+
+impl Drop for TouchpadHandle {
+    fn drop(&mut self) {
+        unsafe { PostThreadMessageW(self.thread_id, WM_QUIT, 0, 0) };
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,1093 @@
+//! ♿ UI Automation utilities.
+//!
+//! UI Automation exposes the controls of other applications as a tree of
+//! elements, the counterpart of the accessibility API on macOS. The COM
+//! library is initialized for the multithreaded apartment on each thread
+//! that uses these elements, unless the thread already initialized it.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use auto::os::uia::{ControlType, Element};
+//!
+//! let window = Element::focused().unwrap();
+//! for element in window.descendants().unwrap() {
+//!     if element.control_type() == Some(ControlType::Button) {
+//!         println!("{:?}", element.name());
+//!     }
+//! }
+//! ```
+
+use std::cell::RefCell;
+use std::fmt;
+use std::mem;
+use std::ptr;
+use std::slice;
+
+use winapi::ctypes::{c_int, c_void};
+use winapi::shared::minwindef::BOOL;
+use winapi::shared::windef::{HWND, POINT, RECT};
+use winapi::shared::winerror::{HRESULT, SUCCEEDED};
+use winapi::shared::wtypes::{BSTR, VT_BSTR, VT_I4};
+use winapi::shared::wtypesbase::CLSCTX_INPROC_SERVER;
+use winapi::um::combaseapi::CoCreateInstance;
+use winapi::um::oaidl::VARIANT;
+use winapi::um::oleauto::{SysAllocStringLen, SysFreeString, SysStringLen, VariantClear};
+use winapi::{Class, Interface};
+
+use error::{Error, OsError, Result};
+use self::com::*;
+use super::Com;
+use super::app::Pid;
+
+/// Declarations of the UI Automation COM interfaces, which winapi does not
+/// provide.
+#[allow(missing_docs, non_snake_case)]
+mod com {
+    use winapi::ctypes::{c_int, c_void};
+    use winapi::shared::guiddef::GUID;
+    use winapi::shared::minwindef::BOOL;
+    use winapi::shared::windef::{HWND, POINT, RECT};
+    use winapi::shared::winerror::HRESULT;
+    use winapi::shared::wtypes::BSTR;
+    use winapi::um::oaidl::VARIANT;
+    use winapi::um::unknwnbase::{IUnknown, IUnknownVtbl};
+
+    RIDL!{#[uuid(0xff48dba4, 0x60ef, 0x4201, 0xaa, 0x87, 0x54, 0x10, 0x3e, 0xef, 0x59, 0x4e)]
+    class CUIAutomation;}
+
+    RIDL!{#[uuid(0x352ffba8, 0x0973, 0x437c, 0xa6, 0x1f, 0xf6, 0x4c, 0xaf, 0xd8, 0x1d, 0xf9)]
+    interface IUIAutomationCondition(IUIAutomationConditionVtbl): IUnknown(IUnknownVtbl) {}}
+
+    // Only the methods up to the last one used are declared, in the order of
+    // `UIAutomationClient.h`
+    RIDL!{#[uuid(0x30cbe57d, 0xd9d0, 0x452a, 0xab, 0x13, 0x7a, 0xc5, 0xac, 0x48, 0x25, 0xee)]
+    interface IUIAutomation(IUIAutomationVtbl): IUnknown(IUnknownVtbl) {
+        fn CompareElements(
+            el1: *mut IUIAutomationElement,
+            el2: *mut IUIAutomationElement,
+            areSame: *mut BOOL,
+        ) -> HRESULT,
+        fn CompareRuntimeIds(
+            runtimeId1: *mut c_void,
+            runtimeId2: *mut c_void,
+            areSame: *mut BOOL,
+        ) -> HRESULT,
+        fn GetRootElement(
+            root: *mut *mut IUIAutomationElement,
+        ) -> HRESULT,
+        fn ElementFromHandle(
+            hwnd: HWND,
+            element: *mut *mut IUIAutomationElement,
+        ) -> HRESULT,
+        fn ElementFromPoint(
+            pt: POINT,
+            element: *mut *mut IUIAutomationElement,
+        ) -> HRESULT,
+        fn GetFocusedElement(
+            element: *mut *mut IUIAutomationElement,
+        ) -> HRESULT,
+        fn GetRootElementBuildCache(
+            cacheRequest: *mut c_void,
+            root: *mut *mut IUIAutomationElement,
+        ) -> HRESULT,
+        fn ElementFromHandleBuildCache(
+            hwnd: HWND,
+            cacheRequest: *mut c_void,
+            element: *mut *mut IUIAutomationElement,
+        ) -> HRESULT,
+        fn ElementFromPointBuildCache(
+            pt: POINT,
+            cacheRequest: *mut c_void,
+            element: *mut *mut IUIAutomationElement,
+        ) -> HRESULT,
+        fn GetFocusedElementBuildCache(
+            cacheRequest: *mut c_void,
+            element: *mut *mut IUIAutomationElement,
+        ) -> HRESULT,
+        fn CreateTreeWalker(
+            pCondition: *mut IUIAutomationCondition,
+            walker: *mut *mut IUIAutomationTreeWalker,
+        ) -> HRESULT,
+        fn get_ControlViewWalker(
+            walker: *mut *mut IUIAutomationTreeWalker,
+        ) -> HRESULT,
+        fn get_ContentViewWalker(
+            walker: *mut *mut IUIAutomationTreeWalker,
+        ) -> HRESULT,
+        fn get_RawViewWalker(
+            walker: *mut *mut IUIAutomationTreeWalker,
+        ) -> HRESULT,
+        fn get_RawViewCondition(
+            condition: *mut *mut IUIAutomationCondition,
+        ) -> HRESULT,
+        fn get_ControlViewCondition(
+            condition: *mut *mut IUIAutomationCondition,
+        ) -> HRESULT,
+        fn get_ContentViewCondition(
+            condition: *mut *mut IUIAutomationCondition,
+        ) -> HRESULT,
+        fn CreateCacheRequest(
+            cacheRequest: *mut *mut c_void,
+        ) -> HRESULT,
+        fn CreateTrueCondition(
+            newCondition: *mut *mut IUIAutomationCondition,
+        ) -> HRESULT,
+        fn CreateFalseCondition(
+            newCondition: *mut *mut IUIAutomationCondition,
+        ) -> HRESULT,
+        fn CreatePropertyCondition(
+            propertyId: c_int,
+            value: VARIANT,
+            newCondition: *mut *mut IUIAutomationCondition,
+        ) -> HRESULT,
+        fn CreatePropertyConditionEx(
+            propertyId: c_int,
+            value: VARIANT,
+            flags: c_int,
+            newCondition: *mut *mut IUIAutomationCondition,
+        ) -> HRESULT,
+        fn CreateAndCondition(
+            condition1: *mut IUIAutomationCondition,
+            condition2: *mut IUIAutomationCondition,
+            newCondition: *mut *mut IUIAutomationCondition,
+        ) -> HRESULT,
+        fn CreateAndConditionFromArray(
+            conditions: *mut c_void,
+            newCondition: *mut *mut IUIAutomationCondition,
+        ) -> HRESULT,
+        fn CreateAndConditionFromNativeArray(
+            conditions: *mut *mut IUIAutomationCondition,
+            conditionCount: c_int,
+            newCondition: *mut *mut IUIAutomationCondition,
+        ) -> HRESULT,
+        fn CreateOrCondition(
+            condition1: *mut IUIAutomationCondition,
+            condition2: *mut IUIAutomationCondition,
+            newCondition: *mut *mut IUIAutomationCondition,
+        ) -> HRESULT,
+    }}
+
+    RIDL!{#[uuid(0xd22108aa, 0x8ac5, 0x49a5, 0x83, 0x7b, 0x37, 0xbb, 0xb3, 0xd7, 0x59, 0x1e)]
+    interface IUIAutomationElement(IUIAutomationElementVtbl): IUnknown(IUnknownVtbl) {
+        fn SetFocus() -> HRESULT,
+        fn GetRuntimeId(
+            runtimeId: *mut *mut c_void,
+        ) -> HRESULT,
+        fn FindFirst(
+            scope: c_int,
+            condition: *mut IUIAutomationCondition,
+            found: *mut *mut IUIAutomationElement,
+        ) -> HRESULT,
+        fn FindAll(
+            scope: c_int,
+            condition: *mut IUIAutomationCondition,
+            found: *mut *mut IUIAutomationElementArray,
+        ) -> HRESULT,
+        fn FindFirstBuildCache(
+            scope: c_int,
+            condition: *mut IUIAutomationCondition,
+            cacheRequest: *mut c_void,
+            found: *mut *mut IUIAutomationElement,
+        ) -> HRESULT,
+        fn FindAllBuildCache(
+            scope: c_int,
+            condition: *mut IUIAutomationCondition,
+            cacheRequest: *mut c_void,
+            found: *mut *mut IUIAutomationElementArray,
+        ) -> HRESULT,
+        fn BuildUpdatedCache(
+            cacheRequest: *mut c_void,
+            updatedElement: *mut *mut IUIAutomationElement,
+        ) -> HRESULT,
+        fn GetCurrentPropertyValue(
+            propertyId: c_int,
+            retVal: *mut c_void,
+        ) -> HRESULT,
+        fn GetCurrentPropertyValueEx(
+            propertyId: c_int,
+            ignoreDefaultValue: BOOL,
+            retVal: *mut c_void,
+        ) -> HRESULT,
+        fn GetCachedPropertyValue(
+            propertyId: c_int,
+            retVal: *mut c_void,
+        ) -> HRESULT,
+        fn GetCachedPropertyValueEx(
+            propertyId: c_int,
+            ignoreDefaultValue: BOOL,
+            retVal: *mut c_void,
+        ) -> HRESULT,
+        fn GetCurrentPatternAs(
+            patternId: c_int,
+            riid: *const GUID,
+            patternObject: *mut *mut c_void,
+        ) -> HRESULT,
+        fn GetCachedPatternAs(
+            patternId: c_int,
+            riid: *const GUID,
+            patternObject: *mut *mut c_void,
+        ) -> HRESULT,
+        fn GetCurrentPattern(
+            patternId: c_int,
+            patternObject: *mut *mut IUnknown,
+        ) -> HRESULT,
+        fn GetCachedPattern(
+            patternId: c_int,
+            patternObject: *mut *mut IUnknown,
+        ) -> HRESULT,
+        fn GetCachedParent(
+            parent: *mut *mut IUIAutomationElement,
+        ) -> HRESULT,
+        fn GetCachedChildren(
+            children: *mut *mut IUIAutomationElementArray,
+        ) -> HRESULT,
+        fn get_CurrentProcessId(
+            retVal: *mut c_int,
+        ) -> HRESULT,
+        fn get_CurrentControlType(
+            retVal: *mut c_int,
+        ) -> HRESULT,
+        fn get_CurrentLocalizedControlType(
+            retVal: *mut BSTR,
+        ) -> HRESULT,
+        fn get_CurrentName(
+            retVal: *mut BSTR,
+        ) -> HRESULT,
+        fn get_CurrentAcceleratorKey(
+            retVal: *mut BSTR,
+        ) -> HRESULT,
+        fn get_CurrentAccessKey(
+            retVal: *mut BSTR,
+        ) -> HRESULT,
+        fn get_CurrentHasKeyboardFocus(
+            retVal: *mut BOOL,
+        ) -> HRESULT,
+        fn get_CurrentIsKeyboardFocusable(
+            retVal: *mut BOOL,
+        ) -> HRESULT,
+        fn get_CurrentIsEnabled(
+            retVal: *mut BOOL,
+        ) -> HRESULT,
+        fn get_CurrentAutomationId(
+            retVal: *mut BSTR,
+        ) -> HRESULT,
+        fn get_CurrentClassName(
+            retVal: *mut BSTR,
+        ) -> HRESULT,
+        fn get_CurrentHelpText(
+            retVal: *mut BSTR,
+        ) -> HRESULT,
+        fn get_CurrentCulture(
+            retVal: *mut c_int,
+        ) -> HRESULT,
+        fn get_CurrentIsControlElement(
+            retVal: *mut BOOL,
+        ) -> HRESULT,
+        fn get_CurrentIsContentElement(
+            retVal: *mut BOOL,
+        ) -> HRESULT,
+        fn get_CurrentIsPassword(
+            retVal: *mut BOOL,
+        ) -> HRESULT,
+        fn get_CurrentNativeWindowHandle(
+            retVal: *mut HWND,
+        ) -> HRESULT,
+        fn get_CurrentItemType(
+            retVal: *mut BSTR,
+        ) -> HRESULT,
+        fn get_CurrentIsOffscreen(
+            retVal: *mut BOOL,
+        ) -> HRESULT,
+        fn get_CurrentOrientation(
+            retVal: *mut c_int,
+        ) -> HRESULT,
+        fn get_CurrentFrameworkId(
+            retVal: *mut BSTR,
+        ) -> HRESULT,
+        fn get_CurrentIsRequiredForForm(
+            retVal: *mut BOOL,
+        ) -> HRESULT,
+        fn get_CurrentItemStatus(
+            retVal: *mut BSTR,
+        ) -> HRESULT,
+        fn get_CurrentBoundingRectangle(
+            retVal: *mut RECT,
+        ) -> HRESULT,
+    }}
+
+    RIDL!{#[uuid(0x14314595, 0xb4bc, 0x4055, 0x95, 0xf2, 0x58, 0xf2, 0xe4, 0x2c, 0x98, 0x55)]
+    interface IUIAutomationElementArray(IUIAutomationElementArrayVtbl): IUnknown(IUnknownVtbl) {
+        fn get_Length(
+            length: *mut c_int,
+        ) -> HRESULT,
+        fn GetElement(
+            index: c_int,
+            element: *mut *mut IUIAutomationElement,
+        ) -> HRESULT,
+    }}
+
+    RIDL!{#[uuid(0x4042c624, 0x389c, 0x4afc, 0xa6, 0x30, 0x9d, 0xf8, 0x54, 0xa5, 0x41, 0xfc)]
+    interface IUIAutomationTreeWalker(IUIAutomationTreeWalkerVtbl): IUnknown(IUnknownVtbl) {
+        fn GetParentElement(
+            element: *mut IUIAutomationElement,
+            parent: *mut *mut IUIAutomationElement,
+        ) -> HRESULT,
+        fn GetFirstChildElement(
+            element: *mut IUIAutomationElement,
+            first: *mut *mut IUIAutomationElement,
+        ) -> HRESULT,
+        fn GetLastChildElement(
+            element: *mut IUIAutomationElement,
+            last: *mut *mut IUIAutomationElement,
+        ) -> HRESULT,
+        fn GetNextSiblingElement(
+            element: *mut IUIAutomationElement,
+            next: *mut *mut IUIAutomationElement,
+        ) -> HRESULT,
+    }}
+
+    RIDL!{#[uuid(0xfb377fbe, 0x8ea6, 0x46d5, 0x9c, 0x73, 0x64, 0x99, 0x64, 0x2d, 0x30, 0x59)]
+    interface IUIAutomationInvokePattern(IUIAutomationInvokePatternVtbl): IUnknown(IUnknownVtbl) {
+        fn Invoke() -> HRESULT,
+    }}
+
+    RIDL!{#[uuid(0xa94cd8b1, 0x0844, 0x4cd6, 0x9d, 0x2d, 0x64, 0x05, 0x37, 0xab, 0x39, 0xe9)]
+    interface IUIAutomationValuePattern(IUIAutomationValuePatternVtbl): IUnknown(IUnknownVtbl) {
+        fn SetValue(
+            val: BSTR,
+        ) -> HRESULT,
+        fn get_CurrentValue(
+            retVal: *mut BSTR,
+        ) -> HRESULT,
+        fn get_CurrentIsReadOnly(
+            retVal: *mut BOOL,
+        ) -> HRESULT,
+    }}
+
+    RIDL!{#[uuid(0xa8efa66a, 0x0fda, 0x421a, 0x91, 0x94, 0x38, 0x02, 0x1f, 0x35, 0x78, 0xea)]
+    interface IUIAutomationSelectionItemPattern(IUIAutomationSelectionItemPatternVtbl): IUnknown(IUnknownVtbl) {
+        fn Select() -> HRESULT,
+        fn AddToSelection() -> HRESULT,
+        fn RemoveFromSelection() -> HRESULT,
+        fn get_CurrentIsSelected(
+            retVal: *mut BOOL,
+        ) -> HRESULT,
+        fn get_CurrentSelectionContainer(
+            retVal: *mut *mut IUIAutomationElement,
+        ) -> HRESULT,
+    }}
+}
+
+/// `UIA_InvokePatternId`
+const INVOKE_PATTERN_ID: c_int = 10000;
+
+/// `UIA_ValuePatternId`
+const VALUE_PATTERN_ID: c_int = 10002;
+
+/// `UIA_SelectionItemPatternId`
+const SELECTION_ITEM_PATTERN_ID: c_int = 10010;
+
+/// `UIA_ProcessIdPropertyId`
+const PROCESS_ID_PROPERTY_ID: c_int = 30002;
+
+/// `UIA_ControlTypePropertyId`
+const CONTROL_TYPE_PROPERTY_ID: c_int = 30003;
+
+/// `UIA_NamePropertyId`
+const NAME_PROPERTY_ID: c_int = 30005;
+
+/// `TreeScope_Children`
+const TREE_SCOPE_CHILDREN: c_int = 2;
+
+/// `TreeScope_Descendants`
+const TREE_SCOPE_DESCENDANTS: c_int = 4;
+
+thread_local! {
+    static AUTOMATION: RefCell<Option<Com<IUIAutomation>>> = const { RefCell::new(None) };
+}
+
+/// Returns the UI Automation object of the current thread, initializing COM
+/// and creating the object the first time.
+fn automation() -> Result<Com<IUIAutomation>> {
+    AUTOMATION.with(|automation| {
+        if let Some(ref existing) = *automation.borrow() {
+            return Ok(existing.clone());
+        }
+        unsafe {
+            super::initialize_com()?;
+            let mut out: *mut IUIAutomation = ptr::null_mut();
+            let hr = CoCreateInstance(
+                &CUIAutomation::uuidof(),
+                ptr::null_mut(),
+                CLSCTX_INPROC_SERVER,
+                &IUIAutomation::uuidof(),
+                &mut out as *mut _ as *mut *mut c_void,
+            );
+            let created = Com::required(hr, out)?;
+            *automation.borrow_mut() = Some(created.clone());
+            Ok(created)
+        }
+    })
+}
+
+/// Takes ownership of a `BSTR`, converting it to a `String`.
+unsafe fn take_bstr(bstr: BSTR) -> Option<String> {
+    if bstr.is_null() {
+        return None;
+    }
+    let units = slice::from_raw_parts(bstr, SysStringLen(bstr) as usize);
+    let string = String::from_utf16_lossy(units);
+    SysFreeString(bstr);
+    Some(string)
+}
+
+/// A condition on the properties of elements, which UI Automation checks
+/// while it searches rather than returning every element to check.
+#[derive(Clone)]
+pub struct Condition(Com<IUIAutomationCondition>);
+
+impl fmt::Debug for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Condition").field(&self.0.as_ptr()).finish()
+    }
+}
+
+impl Condition {
+    /// Returns the condition that every element satisfies.
+    pub fn always() -> Result<Condition> {
+        let automation = automation()?;
+        let mut out = ptr::null_mut();
+        let hr = unsafe { automation.get().CreateTrueCondition(&mut out) };
+        Com::required(hr, out).map(Condition)
+    }
+
+    /// Takes ownership of `value` and returns the condition that the
+    /// property `id` is equal to it.
+    unsafe fn property(id: c_int, mut value: VARIANT) -> Result<Condition> {
+        let condition = automation().and_then(|automation| {
+            let mut out = ptr::null_mut();
+            let hr = automation.get().CreatePropertyCondition(id, value, &mut out);
+            Com::required(hr, out).map(Condition)
+        });
+        // The condition holds its own copy of the value
+        VariantClear(&mut value);
+        condition
+    }
+
+    /// Returns the condition that an element is of `control_type`.
+    pub fn control_type(control_type: ControlType) -> Result<Condition> {
+        unsafe {
+            let mut value: VARIANT = mem::zeroed();
+            value.n1.n2_mut().vt = VT_I4 as u16;
+            *value.n1.n2_mut().n3.lVal_mut() = control_type.id();
+            Condition::property(CONTROL_TYPE_PROPERTY_ID, value)
+        }
+    }
+
+    /// Returns the condition that the name of an element is exactly `name`.
+    pub fn name(name: &str) -> Result<Condition> {
+        let units: Vec<u16> = name.encode_utf16().collect();
+        unsafe {
+            let bstr = SysAllocStringLen(units.as_ptr(), units.len() as u32);
+            if bstr.is_null() {
+                return Err(Error::Failed);
+            }
+            let mut value: VARIANT = mem::zeroed();
+            value.n1.n2_mut().vt = VT_BSTR as u16;
+            *value.n1.n2_mut().n3.bstrVal_mut() = bstr;
+            Condition::property(NAME_PROPERTY_ID, value)
+        }
+    }
+
+    /// Returns the condition that an element belongs to the process with
+    /// `pid`.
+    pub fn process_id(pid: Pid) -> Result<Condition> {
+        unsafe {
+            let mut value: VARIANT = mem::zeroed();
+            value.n1.n2_mut().vt = VT_I4 as u16;
+            *value.n1.n2_mut().n3.lVal_mut() = pid as i32;
+            Condition::property(PROCESS_ID_PROPERTY_ID, value)
+        }
+    }
+
+    /// Returns the condition that an element satisfies both this condition
+    /// and `other`.
+    pub fn and(&self, other: &Condition) -> Result<Condition> {
+        let automation = automation()?;
+        let mut out = ptr::null_mut();
+        let hr = unsafe {
+            automation.get().CreateAndCondition(self.0.as_ptr(), other.0.as_ptr(), &mut out)
+        };
+        Com::required(hr, out).map(Condition)
+    }
+
+    /// Returns the condition that an element satisfies this condition or
+    /// `other`.
+    pub fn or(&self, other: &Condition) -> Result<Condition> {
+        let automation = automation()?;
+        let mut out = ptr::null_mut();
+        let hr = unsafe {
+            automation.get().CreateOrCondition(self.0.as_ptr(), other.0.as_ptr(), &mut out)
+        };
+        Com::required(hr, out).map(Condition)
+    }
+}
+
+/// The type of a control, which determines the patterns it supports.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum ControlType {
+    /// `UIA_ButtonControlTypeId`
+    Button,
+    /// `UIA_CalendarControlTypeId`
+    Calendar,
+    /// `UIA_CheckBoxControlTypeId`
+    CheckBox,
+    /// `UIA_ComboBoxControlTypeId`
+    ComboBox,
+    /// `UIA_EditControlTypeId`
+    Edit,
+    /// `UIA_HyperlinkControlTypeId`
+    Hyperlink,
+    /// `UIA_ImageControlTypeId`
+    Image,
+    /// `UIA_ListItemControlTypeId`
+    ListItem,
+    /// `UIA_ListControlTypeId`
+    List,
+    /// `UIA_MenuControlTypeId`
+    Menu,
+    /// `UIA_MenuBarControlTypeId`
+    MenuBar,
+    /// `UIA_MenuItemControlTypeId`
+    MenuItem,
+    /// `UIA_ProgressBarControlTypeId`
+    ProgressBar,
+    /// `UIA_RadioButtonControlTypeId`
+    RadioButton,
+    /// `UIA_ScrollBarControlTypeId`
+    ScrollBar,
+    /// `UIA_SliderControlTypeId`
+    Slider,
+    /// `UIA_SpinnerControlTypeId`
+    Spinner,
+    /// `UIA_StatusBarControlTypeId`
+    StatusBar,
+    /// `UIA_TabControlTypeId`
+    Tab,
+    /// `UIA_TabItemControlTypeId`
+    TabItem,
+    /// `UIA_TextControlTypeId`
+    Text,
+    /// `UIA_ToolBarControlTypeId`
+    ToolBar,
+    /// `UIA_ToolTipControlTypeId`
+    ToolTip,
+    /// `UIA_TreeControlTypeId`
+    Tree,
+    /// `UIA_TreeItemControlTypeId`
+    TreeItem,
+    /// `UIA_CustomControlTypeId`
+    Custom,
+    /// `UIA_GroupControlTypeId`
+    Group,
+    /// `UIA_ThumbControlTypeId`
+    Thumb,
+    /// `UIA_DataGridControlTypeId`
+    DataGrid,
+    /// `UIA_DataItemControlTypeId`
+    DataItem,
+    /// `UIA_DocumentControlTypeId`
+    Document,
+    /// `UIA_SplitButtonControlTypeId`
+    SplitButton,
+    /// `UIA_WindowControlTypeId`
+    Window,
+    /// `UIA_PaneControlTypeId`
+    Pane,
+    /// `UIA_HeaderControlTypeId`
+    Header,
+    /// `UIA_HeaderItemControlTypeId`
+    HeaderItem,
+    /// `UIA_TableControlTypeId`
+    Table,
+    /// `UIA_TitleBarControlTypeId`
+    TitleBar,
+    /// `UIA_SeparatorControlTypeId`
+    Separator,
+    /// A control type added after these, identified by its ID.
+    Other(i32),
+}
+
+impl ControlType {
+    /// The types in the order of their IDs, starting at 50000.
+    const KNOWN: [ControlType; 39] = [
+        ControlType::Button,
+        ControlType::Calendar,
+        ControlType::CheckBox,
+        ControlType::ComboBox,
+        ControlType::Edit,
+        ControlType::Hyperlink,
+        ControlType::Image,
+        ControlType::ListItem,
+        ControlType::List,
+        ControlType::Menu,
+        ControlType::MenuBar,
+        ControlType::MenuItem,
+        ControlType::ProgressBar,
+        ControlType::RadioButton,
+        ControlType::ScrollBar,
+        ControlType::Slider,
+        ControlType::Spinner,
+        ControlType::StatusBar,
+        ControlType::Tab,
+        ControlType::TabItem,
+        ControlType::Text,
+        ControlType::ToolBar,
+        ControlType::ToolTip,
+        ControlType::Tree,
+        ControlType::TreeItem,
+        ControlType::Custom,
+        ControlType::Group,
+        ControlType::Thumb,
+        ControlType::DataGrid,
+        ControlType::DataItem,
+        ControlType::Document,
+        ControlType::SplitButton,
+        ControlType::Window,
+        ControlType::Pane,
+        ControlType::Header,
+        ControlType::HeaderItem,
+        ControlType::Table,
+        ControlType::TitleBar,
+        ControlType::Separator,
+    ];
+
+    /// Returns the control type with the `CONTROLTYPEID` `id`.
+    pub fn from_id(id: i32) -> ControlType {
+        match id.checked_sub(50000) {
+            Some(index) if (index as usize) < ControlType::KNOWN.len() => {
+                ControlType::KNOWN[index as usize]
+            },
+            _ => ControlType::Other(id),
+        }
+    }
+
+    /// Returns the `CONTROLTYPEID` of the control type.
+    pub fn id(self) -> i32 {
+        match self {
+            ControlType::Other(id) => id,
+            known => 50000 + ControlType::KNOWN.iter().position(|&t| t == known).unwrap() as i32,
+        }
+    }
+}
+
+/// An element of the user interface, such as a window or a control.
+#[derive(Clone)]
+pub struct Element(Com<IUIAutomationElement>);
+
+impl fmt::Debug for Element {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Element")
+            .field("control_type", &self.control_type())
+            .field("name", &self.name())
+            .finish()
+    }
+}
+
+impl PartialEq for Element {
+    fn eq(&self, other: &Element) -> bool {
+        let automation = match automation() {
+            Ok(automation) => automation,
+            Err(_) => return false,
+        };
+        let mut same = 0;
+        let hr = unsafe {
+            automation.get().CompareElements(self.0.as_ptr(), other.0.as_ptr(), &mut same)
+        };
+        SUCCEEDED(hr) && same != 0
+    }
+}
+
+impl Element {
+    /// Returns the desktop, whose children are the top-level windows.
+    pub fn root() -> Result<Element> {
+        let automation = automation()?;
+        let mut out = ptr::null_mut();
+        let hr = unsafe { automation.get().GetRootElement(&mut out) };
+        Com::required(hr, out).map(Element)
+    }
+
+    /// Returns the element that has keyboard focus.
+    pub fn focused() -> Result<Element> {
+        let automation = automation()?;
+        let mut out = ptr::null_mut();
+        let hr = unsafe { automation.get().GetFocusedElement(&mut out) };
+        Com::required(hr, out).map(Element)
+    }
+
+    /// Returns the element at `(x, y)` in screen coordinates.
+    pub fn from_point((x, y): (i32, i32)) -> Result<Element> {
+        let automation = automation()?;
+        let mut out = ptr::null_mut();
+        let hr = unsafe { automation.get().ElementFromPoint(POINT { x, y }, &mut out) };
+        Com::required(hr, out).map(Element)
+    }
+
+    /// Returns the element for the window `hwnd`.
+    // Window handles are opaque, so an invalid one only makes this fail
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    pub fn from_window(hwnd: HWND) -> Result<Element> {
+        let automation = automation()?;
+        let mut out = ptr::null_mut();
+        let hr = unsafe { automation.get().ElementFromHandle(hwnd, &mut out) };
+        Com::required(hr, out).map(Element)
+    }
+
+    /// Returns the top-level windows of the process with `pid`, the closest
+    /// counterpart of an application element on macOS.
+    pub fn windows_of(pid: Pid) -> Result<Vec<Element>> {
+        Element::root()?.find_all(TREE_SCOPE_CHILDREN, &Condition::process_id(pid)?)
+    }
+
+    fn walk<F>(&self, step: F) -> Result<Option<Element>>
+        where F: FnOnce(&IUIAutomationTreeWalker, *mut *mut IUIAutomationElement) -> HRESULT
+    {
+        let automation = automation()?;
+        let mut walker = ptr::null_mut();
+        let hr = unsafe { automation.get().get_ControlViewWalker(&mut walker) };
+        let walker = Com::required(hr, walker)?;
+        let mut out = ptr::null_mut();
+        let hr = step(walker.get(), &mut out);
+        Ok(Com::from_out(hr, out)?.map(Element))
+    }
+
+    /// Returns the element that contains the element in the control view of
+    /// the tree, or `None` for the root.
+    pub fn parent(&self) -> Result<Option<Element>> {
+        let element = self.0.as_ptr();
+        self.walk(|walker, out| unsafe { walker.GetParentElement(element, out) })
+    }
+
+    /// Returns the first child of the element in the control view.
+    pub fn first_child(&self) -> Result<Option<Element>> {
+        let element = self.0.as_ptr();
+        self.walk(|walker, out| unsafe { walker.GetFirstChildElement(element, out) })
+    }
+
+    /// Returns the next sibling of the element in the control view.
+    pub fn next_sibling(&self) -> Result<Option<Element>> {
+        let element = self.0.as_ptr();
+        self.walk(|walker, out| unsafe { walker.GetNextSiblingElement(element, out) })
+    }
+
+    fn find_all(&self, scope: c_int, condition: &Condition) -> Result<Vec<Element>> {
+        unsafe {
+            let mut array = ptr::null_mut();
+            let hr = self.0.get().FindAll(scope, condition.0.as_ptr(), &mut array);
+            let array = match Com::from_out(hr, array)? {
+                Some(array) => array,
+                None => return Ok(Vec::new()),
+            };
+
+            let mut len = 0;
+            let hr = array.get().get_Length(&mut len);
+            if !SUCCEEDED(hr) {
+                return Err(OsError::from_code(hr).into());
+            }
+            let mut elements = Vec::with_capacity(len.max(0) as usize);
+            for index in 0..len {
+                let mut out = ptr::null_mut();
+                let hr = array.get().GetElement(index, &mut out);
+                elements.extend(Com::from_out(hr, out)?.map(Element));
+            }
+            Ok(elements)
+        }
+    }
+
+    /// Returns the children of the element.
+    #[inline]
+    pub fn children(&self) -> Result<Vec<Element>> {
+        self.find_all(TREE_SCOPE_CHILDREN, &Condition::always()?)
+    }
+
+    /// Returns every element below the element, in depth-first order.
+    ///
+    /// Searching the descendants of the desktop can take a long time, so
+    /// start from a window where possible.
+    #[inline]
+    pub fn descendants(&self) -> Result<Vec<Element>> {
+        self.find_all(TREE_SCOPE_DESCENDANTS, &Condition::always()?)
+    }
+
+    /// Returns every element below the element that satisfies `condition`,
+    /// in depth-first order.
+    #[inline]
+    pub fn descendants_where(&self, condition: &Condition) -> Result<Vec<Element>> {
+        self.find_all(TREE_SCOPE_DESCENDANTS, condition)
+    }
+
+    /// Returns the first element below the element that satisfies
+    /// `condition`, in depth-first order, without searching further.
+    pub fn first_descendant_where(&self, condition: &Condition) -> Result<Option<Element>> {
+        let mut out = ptr::null_mut();
+        let hr = unsafe {
+            self.0.get().FindFirst(TREE_SCOPE_DESCENDANTS, condition.0.as_ptr(), &mut out)
+        };
+        Ok(Com::from_out(hr, out)?.map(Element))
+    }
+
+    fn string_property<F>(&self, get: F) -> Option<String>
+        where F: FnOnce(&IUIAutomationElement, *mut BSTR) -> HRESULT
+    {
+        let mut bstr = ptr::null_mut();
+        let hr = get(self.0.get(), &mut bstr);
+        if SUCCEEDED(hr) {
+            unsafe { take_bstr(bstr) }
+        } else {
+            None
+        }
+    }
+
+    fn bool_property<F>(&self, get: F) -> Option<bool>
+        where F: FnOnce(&IUIAutomationElement, *mut BOOL) -> HRESULT
+    {
+        let mut value = 0;
+        if SUCCEEDED(get(self.0.get(), &mut value)) {
+            Some(value != 0)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the name of the element, such as the label of a button.
+    #[inline]
+    pub fn name(&self) -> Option<String> {
+        self.string_property(|e, out| unsafe { e.get_CurrentName(out) })
+    }
+
+    /// Returns the identifier the application gave the element, which is
+    /// stable across runs and languages.
+    #[inline]
+    pub fn automation_id(&self) -> Option<String> {
+        self.string_property(|e, out| unsafe { e.get_CurrentAutomationId(out) })
+    }
+
+    /// Returns the window class name of the element.
+    #[inline]
+    pub fn class_name(&self) -> Option<String> {
+        self.string_property(|e, out| unsafe { e.get_CurrentClassName(out) })
+    }
+
+    /// Returns the help text of the element, which is typically shown as its
+    /// tooltip.
+    #[inline]
+    pub fn help_text(&self) -> Option<String> {
+        self.string_property(|e, out| unsafe { e.get_CurrentHelpText(out) })
+    }
+
+    /// Returns the control type of the element.
+    pub fn control_type(&self) -> Option<ControlType> {
+        let mut id = 0;
+        let hr = unsafe { self.0.get().get_CurrentControlType(&mut id) };
+        if SUCCEEDED(hr) {
+            Some(ControlType::from_id(id))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the control type of the element as shown to the user, in the
+    /// language of the system.
+    #[inline]
+    pub fn localized_control_type(&self) -> Option<String> {
+        self.string_property(|e, out| unsafe { e.get_CurrentLocalizedControlType(out) })
+    }
+
+    /// Returns the identifier of the process that owns the element.
+    pub fn process_id(&self) -> Option<Pid> {
+        let mut pid = 0;
+        let hr = unsafe { self.0.get().get_CurrentProcessId(&mut pid) };
+        if SUCCEEDED(hr) {
+            Some(pid as Pid)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the window of the element, if it has one of its own.
+    pub fn window(&self) -> Option<HWND> {
+        let mut hwnd = ptr::null_mut();
+        let hr = unsafe { self.0.get().get_CurrentNativeWindowHandle(&mut hwnd) };
+        if SUCCEEDED(hr) && !hwnd.is_null() {
+            Some(hwnd)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the position and size of the element in screen coordinates.
+    pub fn bounds(&self) -> Option<((i32, i32), (i32, i32))> {
+        let mut rect = RECT { left: 0, top: 0, right: 0, bottom: 0 };
+        let hr = unsafe { self.0.get().get_CurrentBoundingRectangle(&mut rect) };
+        if SUCCEEDED(hr) {
+            Some(((rect.left, rect.top), (rect.right - rect.left, rect.bottom - rect.top)))
+        } else {
+            None
+        }
+    }
+
+    /// Returns whether the element is enabled.
+    #[inline]
+    pub fn is_enabled(&self) -> Option<bool> {
+        self.bool_property(|e, out| unsafe { e.get_CurrentIsEnabled(out) })
+    }
+
+    /// Returns whether the element is scrolled or clipped out of view.
+    #[inline]
+    pub fn is_offscreen(&self) -> Option<bool> {
+        self.bool_property(|e, out| unsafe { e.get_CurrentIsOffscreen(out) })
+    }
+
+    /// Returns whether the element has keyboard focus.
+    #[inline]
+    pub fn has_keyboard_focus(&self) -> Option<bool> {
+        self.bool_property(|e, out| unsafe { e.get_CurrentHasKeyboardFocus(out) })
+    }
+
+    /// Gives the element keyboard focus.
+    pub fn set_focus(&self) -> Result<()> {
+        let hr = unsafe { self.0.get().SetFocus() };
+        if SUCCEEDED(hr) {
+            Ok(())
+        } else {
+            Err(OsError::from_code(hr).into())
+        }
+    }
+
+    /// Returns the pattern `T` with the ID `id`, or `None` if the element does
+    /// not support it.
+    fn pattern<T: Interface>(&self, id: c_int) -> Result<Option<Com<T>>> {
+        let mut out: *mut T = ptr::null_mut();
+        let hr = unsafe {
+            self.0.get().GetCurrentPatternAs(
+                id,
+                &T::uuidof(),
+                &mut out as *mut _ as *mut *mut c_void,
+            )
+        };
+        Com::from_out(hr, out)
+    }
+
+    /// Performs the default action of the element, such as pressing a
+    /// button, through its Invoke pattern.
+    ///
+    /// Returns [`Error::Unsupported`](../../error/enum.Error.html#variant.Unsupported)
+    /// if the element does not support the pattern.
+    pub fn invoke(&self) -> Result<()> {
+        let pattern = self.pattern::<IUIAutomationInvokePattern>(INVOKE_PATTERN_ID)?;
+        let pattern = pattern.ok_or(Error::Unsupported)?;
+        let hr = unsafe { pattern.get().Invoke() };
+        if SUCCEEDED(hr) {
+            Ok(())
+        } else {
+            Err(OsError::from_code(hr).into())
+        }
+    }
+
+    /// Returns the value of the element through its Value pattern, such as
+    /// the text of an edit control.
+    pub fn value(&self) -> Option<String> {
+        let pattern = self.pattern::<IUIAutomationValuePattern>(VALUE_PATTERN_ID).ok()??;
+        let mut bstr = ptr::null_mut();
+        let hr = unsafe { pattern.get().get_CurrentValue(&mut bstr) };
+        if SUCCEEDED(hr) {
+            unsafe { take_bstr(bstr) }
+        } else {
+            None
+        }
+    }
+
+    /// Returns whether the value of the element is read-only, or `None` if
+    /// it does not support the Value pattern.
+    pub fn is_read_only(&self) -> Option<bool> {
+        let pattern = self.pattern::<IUIAutomationValuePattern>(VALUE_PATTERN_ID).ok()??;
+        let mut read_only = 0;
+        let hr = unsafe { pattern.get().get_CurrentIsReadOnly(&mut read_only) };
+        if SUCCEEDED(hr) {
+            Some(read_only != 0)
+        } else {
+            None
+        }
+    }
+
+    /// Sets the value of the element through its Value pattern.
+    ///
+    /// Returns [`Error::Unsupported`](../../error/enum.Error.html#variant.Unsupported)
+    /// if the element does not support the pattern.
+    pub fn set_value(&self, value: &str) -> Result<()> {
+        let pattern = self.pattern::<IUIAutomationValuePattern>(VALUE_PATTERN_ID)?;
+        let pattern = pattern.ok_or(Error::Unsupported)?;
+        let units: Vec<u16> = value.encode_utf16().collect();
+        unsafe {
+            let bstr = SysAllocStringLen(units.as_ptr(), units.len() as u32);
+            if bstr.is_null() {
+                return Err(Error::Failed);
+            }
+            let hr = pattern.get().SetValue(bstr);
+            SysFreeString(bstr);
+            if SUCCEEDED(hr) {
+                Ok(())
+            } else {
+                Err(OsError::from_code(hr).into())
+            }
+        }
+    }
+
+    /// Selects the element, deselecting any others in its container, through
+    /// its SelectionItem pattern.
+    ///
+    /// Returns [`Error::Unsupported`](../../error/enum.Error.html#variant.Unsupported)
+    /// if the element does not support the pattern.
+    pub fn select(&self) -> Result<()> {
+        let pattern = self.pattern::<IUIAutomationSelectionItemPattern>(SELECTION_ITEM_PATTERN_ID)?;
+        let pattern = pattern.ok_or(Error::Unsupported)?;
+        let hr = unsafe { pattern.get().Select() };
+        if SUCCEEDED(hr) {
+            Ok(())
+        } else {
+            Err(OsError::from_code(hr).into())
+        }
+    }
+
+    /// Returns whether the element is selected, or `None` if it does not
+    /// support the SelectionItem pattern.
+    pub fn is_selected(&self) -> Option<bool> {
+        let pattern = self
+            .pattern::<IUIAutomationSelectionItemPattern>(SELECTION_ITEM_PATTERN_ID)
+            .ok()??;
+        let mut selected = 0;
+        let hr = unsafe { pattern.get().get_CurrentIsSelected(&mut selected) };
+        if SUCCEEDED(hr) {
+            Some(selected != 0)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ControlType;
+
+    #[test]
+    fn control_type_ids() {
+        assert_eq!(ControlType::from_id(50000), ControlType::Button);
+        assert_eq!(ControlType::from_id(50032), ControlType::Window);
+        assert_eq!(ControlType::Separator.id(), 50038);
+        assert_eq!(ControlType::from_id(50100), ControlType::Other(50100));
+        for &control_type in &ControlType::KNOWN {
+            assert_eq!(ControlType::from_id(control_type.id()), control_type);
+        }
+    }
+}
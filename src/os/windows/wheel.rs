@@ -0,0 +1,134 @@
+//! 🎡 Scroll wheel automation utilities.
+
+use std::mem;
+
+use winapi::ctypes::c_int;
+use winapi::shared::minwindef::UINT;
+use winapi::um::winuser::{self, INPUT, INPUT_MOUSE, MOUSEINPUT};
+
+use error::{OsError, Result};
+
+/// The number of pixels scrolled per line, matching the default on macOS.
+const PIXELS_PER_LINE: i32 = 10;
+
+/// The unit of measurement for scroll wheel offsets.
+///
+/// Windows measures scrolling in fractions of a wheel notch, where a notch
+/// scrolls by the number of lines set by the user, which defaults to 3.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum ScrollUnit {
+    /// The offset is by pixels, at about ten pixels per line.
+    ///
+    /// Offsets smaller than a notch are sent as high-resolution scrolling,
+    /// which most modern applications scroll smoothly by.
+    Pixel,
+    /// The offset is by lines.
+    Line,
+}
+
+/// Returns the number of lines or characters scrolled per notch for the
+/// `SPI_GETWHEELSCROLL*` parameter `action`.
+fn per_notch(action: UINT) -> i32 {
+    let mut lines: UINT = 3;
+    let ok = unsafe {
+        winuser::SystemParametersInfoW(action, 0, &mut lines as *mut UINT as *mut _, 0)
+    };
+    // Zero disables scrolling, and `WHEEL_PAGESCROLL` scrolls by pages
+    match lines {
+        _ if ok == 0 => 3,
+        0 => 1,
+        n if n > 100 => 3,
+        n => n as i32,
+    }
+}
+
+/// Converts `amount` in `unit` into a wheel delta for notches of
+/// `per_notch` lines.
+fn wheel_delta(unit: ScrollUnit, amount: i32, per_notch: i32) -> i32 {
+    let notch = winuser::WHEEL_DELTA as i64;
+    let amount = amount as i64;
+    let delta = match unit {
+        ScrollUnit::Line => amount * notch / per_notch as i64,
+        ScrollUnit::Pixel => amount * notch / (per_notch * PIXELS_PER_LINE) as i64,
+    };
+    delta.clamp(i32::MIN as i64, i32::MAX as i64) as i32
+}
+
+fn wheel_input(flags: u32, delta: i32) -> INPUT {
+    unsafe {
+        let mut input: INPUT = mem::zeroed();
+        input.type_ = INPUT_MOUSE;
+        *input.u.mi_mut() = MOUSEINPUT {
+            dx: 0,
+            dy: 0,
+            mouseData: delta as _,
+            dwFlags: flags,
+            time: 0,
+            dwExtraInfo: super::extra_info(),
+        };
+        input
+    }
+}
+
+/// Scrolls by `vertical` and `horizontal` offsets in `unit`, where positive
+/// values scroll up and right.
+///
+/// As with button events, this fails if the input was blocked by another
+/// thread or by User Interface Privilege Isolation (UIPI).
+///
+/// # Examples
+///
+/// ```no_run
+/// use auto::os::wheel::{self, ScrollUnit};
+///
+/// // Scroll down five lines
+/// wheel::scroll(ScrollUnit::Line, -5, 0).unwrap();
+/// ```
+pub fn scroll(unit: ScrollUnit, vertical: i32, horizontal: i32) -> Result<()> {
+    let mut inputs = Vec::with_capacity(2);
+    if vertical != 0 {
+        let delta = wheel_delta(unit, vertical, per_notch(winuser::SPI_GETWHEELSCROLLLINES));
+        inputs.push(wheel_input(winuser::MOUSEEVENTF_WHEEL, delta));
+    }
+    if horizontal != 0 {
+        let delta = wheel_delta(unit, horizontal, per_notch(winuser::SPI_GETWHEELSCROLLCHARS));
+        inputs.push(wheel_input(winuser::MOUSEEVENTF_HWHEEL, delta));
+    }
+    if inputs.is_empty() {
+        return Ok(());
+    }
+
+    let count = inputs.len() as UINT;
+    let size = mem::size_of::<INPUT>() as c_int;
+    if unsafe { winuser::SendInput(count, inputs.as_mut_ptr(), size) } == count {
+        Ok(())
+    } else {
+        Err(OsError::last().into())
+    }
+}
+
+/// Scrolls vertically by `amount` in `unit`, where positive values scroll up.
+#[inline]
+pub fn vertical(unit: ScrollUnit, amount: i32) -> Result<()> {
+    scroll(unit, amount, 0)
+}
+
+/// Scrolls horizontally by `amount` in `unit`, where positive values scroll
+/// right.
+#[inline]
+pub fn horizontal(unit: ScrollUnit, amount: i32) -> Result<()> {
+    scroll(unit, 0, amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deltas() {
+        assert_eq!(wheel_delta(ScrollUnit::Line, 3, 3), 120);
+        assert_eq!(wheel_delta(ScrollUnit::Line, -1, 3), -40);
+        assert_eq!(wheel_delta(ScrollUnit::Pixel, 15, 3), 60);
+        assert_eq!(wheel_delta(ScrollUnit::Line, i32::MAX, 1), i32::MAX);
+    }
+}
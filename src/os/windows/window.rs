@@ -0,0 +1,295 @@
+//! 🪟 Window utilities.
+//!
+//! Input sent through a [`Window`](struct.Window.html) is posted to the
+//! message queue of the window instead of going through `SendInput`. It
+//! reaches the window even when another one is in the foreground, and
+//! neither moves the cursor nor changes focus, so several applications can be
+//! driven at once. Applications that read the state of the keyboard or mouse
+//! directly, such as games, do not see it.
+//!
+//! # Examples
+//!
+//! Type into Notepad while it is in the background:
+//!
+//! ```no_run
+//! use auto::os::window::Window;
+//!
+//! let notepad = Window::find("Notepad").unwrap();
+//! notepad.send_click((100, 100)).unwrap();
+//! notepad.send_text("Hello from the background\n").unwrap();
+//! ```
+
+use std::fmt;
+use std::mem;
+use std::num::ParseIntError;
+use std::ptr;
+use std::str::FromStr;
+
+use winapi::shared::minwindef::{FALSE, LPARAM, MAX_PATH, UINT, WPARAM};
+use winapi::shared::windef::{HWND, POINT};
+use winapi::um::{handleapi, processthreadsapi, winbase, winnt, winuser};
+
+use error::{OsError, Result};
+use super::app::Pid;
+
+/// A window, identified by its handle.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Window(HWND);
+
+// Window handles are valid in every thread of the session
+unsafe impl Send for Window {}
+unsafe impl Sync for Window {}
+
+/// Packs a point into the `LPARAM` of a mouse message.
+fn point_param(point: POINT) -> LPARAM {
+    let x = point.x as i16 as u16 as u32;
+    let y = point.y as i16 as u16 as u32;
+    (y << 16 | x) as LPARAM
+}
+
+impl Window {
+    /// Wraps the window handle `hwnd`.
+    #[inline]
+    pub fn from_raw(hwnd: HWND) -> Window {
+        Window(hwnd)
+    }
+
+    /// Returns the window handle.
+    #[inline]
+    pub fn raw(&self) -> HWND {
+        self.0
+    }
+
+    /// Returns the window the user is currently working in.
+    pub fn foreground() -> Option<Window> {
+        let hwnd = unsafe { winuser::GetForegroundWindow() };
+        if hwnd.is_null() {
+            None
+        } else {
+            Some(Window(hwnd))
+        }
+    }
+
+    /// Returns the top-level window whose window class is `class`, such as
+    /// `Notepad`.
+    pub fn find(class: &str) -> Option<Window> {
+        let class: Vec<u16> = class.encode_utf16().chain(Some(0)).collect();
+        let hwnd = unsafe { winuser::FindWindowW(class.as_ptr(), ptr::null()) };
+        if hwnd.is_null() {
+            None
+        } else {
+            Some(Window(hwnd))
+        }
+    }
+
+    /// Returns the title of the window.
+    pub fn title(&self) -> String {
+        let mut buf = [0u16; 1024];
+        let len = unsafe { winuser::GetWindowTextW(self.0, buf.as_mut_ptr(), buf.len() as i32) };
+        String::from_utf16_lossy(&buf[..len.max(0) as usize])
+    }
+
+    /// Returns the identifier of the process that created the window.
+    pub fn pid(&self) -> Pid {
+        let mut pid = 0;
+        unsafe { winuser::GetWindowThreadProcessId(self.0, &mut pid) };
+        pid
+    }
+
+    /// Returns the name of the window class, such as `Notepad`.
+    pub fn class(&self) -> String {
+        let mut buf = [0u16; 256];
+        let len = unsafe { winuser::GetClassNameW(self.0, buf.as_mut_ptr(), buf.len() as i32) };
+        String::from_utf16_lossy(&buf[..len.max(0) as usize])
+    }
+
+    /// Returns the readable path that the [stable identifier](#method.stable_id)
+    /// of the window is a hash of.
+    ///
+    /// The path is the file name of the executable that owns the window,
+    /// followed by the window class and the title with digits replaced by
+    /// `#`, as in `notepad.exe/Notepad "Untitled #"`.
+    ///
+    /// Returns `None` if the executable can't be found, such as for windows of
+    /// elevated processes.
+    pub fn stable_path(&self) -> Option<String> {
+        let owner = executable_name(self.pid())?;
+        let step = match title_pattern(&self.title()) {
+            ref pattern if pattern.is_empty() => self.class(),
+            pattern => format!("{} {:?}", self.class(), pattern),
+        };
+        Some(format!("{}/{}", owner, step))
+    }
+
+    /// Returns a best-effort identifier for the window that survives
+    /// restarts of its application.
+    ///
+    /// Windows of the same class and executable whose titles differ only in
+    /// their digits share an identifier.
+    #[inline]
+    pub fn stable_id(&self) -> Option<StableId> {
+        self.stable_path().map(|path| StableId::of_path(&path))
+    }
+
+    fn post(hwnd: HWND, message: UINT, wparam: WPARAM, lparam: LPARAM) -> Result<()> {
+        if unsafe { winuser::PostMessageW(hwnd, message, wparam, lparam) } == 0 {
+            Err(OsError::last().into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the innermost visible child window at `point` in the client
+    /// area of the window, along with `point` relative to that child.
+    fn child_at(&self, point: (i32, i32)) -> (HWND, POINT) {
+        let mut hwnd = self.0;
+        let mut point = POINT { x: point.0, y: point.1 };
+        loop {
+            let child = unsafe {
+                winuser::ChildWindowFromPointEx(
+                    hwnd,
+                    point,
+                    winuser::CWP_SKIPINVISIBLE | winuser::CWP_SKIPTRANSPARENT,
+                )
+            };
+            if child.is_null() || child == hwnd {
+                return (hwnd, point);
+            }
+            unsafe { winuser::MapWindowPoints(hwnd, child, &mut point, 1) };
+            hwnd = child;
+        }
+    }
+
+    /// Clicks the left mouse button at `point` in the client area of the
+    /// window.
+    ///
+    /// The click goes to the control at `point`, such as a button, rather
+    /// than to the window containing it.
+    pub fn send_click(&self, point: (i32, i32)) -> Result<()> {
+        let (hwnd, point) = self.child_at(point);
+        let lparam = point_param(point);
+        Window::post(hwnd, winuser::WM_MOUSEMOVE, 0, lparam)?;
+        Window::post(hwnd, winuser::WM_LBUTTONDOWN, winuser::MK_LBUTTON, lparam)?;
+        Window::post(hwnd, winuser::WM_LBUTTONUP, 0, lparam)
+    }
+
+    /// Returns the control of the window that has keyboard focus within its
+    /// application, or the window itself if none does.
+    fn focus(&self) -> HWND {
+        let thread = unsafe { winuser::GetWindowThreadProcessId(self.0, ptr::null_mut()) };
+        let mut info: winuser::GUITHREADINFO = unsafe { mem::zeroed() };
+        info.cbSize = mem::size_of::<winuser::GUITHREADINFO>() as u32;
+        let found = unsafe { winuser::GetGUIThreadInfo(thread, &mut info) } != 0;
+        if found && !info.hwndFocus.is_null() && unsafe { winuser::IsChild(self.0, info.hwndFocus) } != 0 {
+            info.hwndFocus
+        } else {
+            self.0
+        }
+    }
+
+    /// Types `text` into the control of the window that has keyboard focus.
+    ///
+    /// Each character is posted as a `WM_CHAR` message, with newlines sent as
+    /// the Enter key. To type into another control, click it first with
+    /// [`send_click`](#method.send_click).
+    pub fn send_text(&self, text: &str) -> Result<()> {
+        let hwnd = self.focus();
+        for unit in text.replace('\n', "\r").encode_utf16() {
+            Window::post(hwnd, winuser::WM_CHAR, unit as WPARAM, 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the file name of the executable of process `pid`, such as
+/// `notepad.exe`.
+fn executable_name(pid: Pid) -> Option<String> {
+    unsafe {
+        let process = processthreadsapi::OpenProcess(winnt::PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid);
+        if process.is_null() {
+            return None;
+        }
+        let mut buf = [0u16; MAX_PATH];
+        let mut len = buf.len() as u32;
+        let found = winbase::QueryFullProcessImageNameW(process, 0, buf.as_mut_ptr(), &mut len) != 0;
+        handleapi::CloseHandle(process);
+        if !found {
+            return None;
+        }
+        let path = String::from_utf16_lossy(&buf[..len as usize]);
+        path.rsplit('\\').next().map(str::to_owned)
+    }
+}
+
+/// Returns `title` with runs of digits replaced by `#`, so that titles like
+/// "Document 3" survive renumbering.
+fn title_pattern(title: &str) -> String {
+    let mut pattern = String::with_capacity(title.len());
+    for ch in title.trim().chars() {
+        if !ch.is_ascii_digit() {
+            pattern.push(ch);
+        } else if !pattern.ends_with('#') {
+            pattern.push('#');
+        }
+    }
+    pattern
+}
+
+/// A best-effort identifier for a window that stays the same across launches
+/// of its application, so that it can be stored and compared later.
+///
+/// The identifier is a hash of the [stable path](struct.Window.html#method.stable_path)
+/// of the window.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StableId(u64);
+
+impl StableId {
+    /// Returns the identifier with the raw value `raw`.
+    #[inline]
+    pub fn from_raw(raw: u64) -> StableId {
+        StableId(raw)
+    }
+
+    /// Returns the raw value of the identifier.
+    #[inline]
+    pub fn raw(self) -> u64 {
+        self.0
+    }
+
+    fn of_path(path: &str) -> StableId {
+        // FNV-1a, whose output does not change between Rust versions
+        let hash = path.bytes().fold(0xcbf2_9ce4_8422_2325, |hash: u64, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+        StableId(hash)
+    }
+}
+
+impl fmt::Display for StableId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl FromStr for StableId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> ::std::result::Result<StableId, ParseIntError> {
+        u64::from_str_radix(s, 16).map(StableId)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stable_ids() {
+        assert_eq!(title_pattern(" Untitled 12 - Notepad "), "Untitled # - Notepad");
+
+        // Identifiers match those of the same paths on macOS
+        let id = StableId::of_path("com.apple.TextEdit/AXWindow \"Untitled #\"");
+        assert_eq!(id.to_string(), "e284c5672f1aa1b1");
+        assert_eq!("e284c5672f1aa1b1".parse(), Ok(id));
+    }
+}
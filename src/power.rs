@@ -0,0 +1,169 @@
+//! 🔋 Keeping the computer awake during automation.
+//!
+//! Long unattended runs stop working when the display turns off or the
+//! system goes to sleep partway through. An [`Assertion`](struct.Assertion.html)
+//! keeps both awake for as long as it is held.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use auto::power::Assertion;
+//!
+//! let _awake = Assertion::prevent_sleep("Replaying the nightly smoke test").unwrap();
+//! // ... run the replay; sleep is allowed again once `_awake` is dropped
+//! ```
+
+use std::fmt;
+
+use error::Result;
+
+/// Keeps the computer from sleeping when the user is idle, until dropped.
+///
+/// Assertions only hold off sleep caused by inactivity. The system still
+/// sleeps if the user asks it to, such as by closing the lid of a laptop.
+pub struct Assertion {
+    reason: String,
+    display: bool,
+    _inner: imp::Assertion,
+}
+
+impl fmt::Debug for Assertion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Assertion")
+            .field("reason", &self.reason)
+            .field("display", &self.display)
+            .finish()
+    }
+}
+
+impl Assertion {
+    /// Keeps both the display and the system awake, which screen captures and
+    /// input need.
+    ///
+    /// On macOS, `reason` is shown to the user by `pmset -g assertions` and
+    /// Activity Monitor. Windows has no place to show it.
+    #[inline]
+    pub fn prevent_sleep(reason: &str) -> Result<Assertion> {
+        Assertion::new(reason, true)
+    }
+
+    /// Keeps the system awake while letting the display sleep, for work that
+    /// does not touch the screen.
+    #[inline]
+    pub fn prevent_system_sleep(reason: &str) -> Result<Assertion> {
+        Assertion::new(reason, false)
+    }
+
+    fn new(reason: &str, display: bool) -> Result<Assertion> {
+        Ok(Assertion {
+            reason: reason.into(),
+            display,
+            _inner: imp::Assertion::new(reason, display)?,
+        })
+    }
+
+    /// Returns the reason given for the assertion.
+    #[inline]
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    /// Returns whether the assertion keeps the display awake as well as the
+    /// system.
+    #[inline]
+    pub fn keeps_display_awake(&self) -> bool {
+        self.display
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use error::Result;
+    use os::power::{AssertionType, PowerAssertion};
+
+    pub struct Assertion {
+        _assertion: PowerAssertion,
+    }
+
+    impl Assertion {
+        pub fn new(reason: &str, display: bool) -> Result<Assertion> {
+            let kind = if display {
+                AssertionType::PreventUserIdleDisplaySleep
+            } else {
+                AssertionType::PreventUserIdleSystemSleep
+            };
+            PowerAssertion::new(kind, reason).map(|assertion| Assertion { _assertion: assertion })
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use std::sync::mpsc::{self, Sender};
+    use std::thread::{self, JoinHandle};
+
+    use error::Result;
+    use os::power::{self, ExecutionState};
+
+    pub struct Assertion {
+        stop: Option<Sender<()>>,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl Drop for Assertion {
+        fn drop(&mut self) {
+            drop(self.stop.take());
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    impl Assertion {
+        pub fn new(_reason: &str, display: bool) -> Result<Assertion> {
+            let mut state = ExecutionState::CONTINUOUS | ExecutionState::SYSTEM_REQUIRED;
+            if display {
+                state |= ExecutionState::DISPLAY_REQUIRED;
+            }
+            let (tx, rx) = mpsc::channel();
+            let (stop, stopped) = mpsc::channel::<()>();
+
+            // Execution states belong to the thread that sets them, so each
+            // assertion gets a thread that holds its state until dropped
+            let thread = thread::Builder::new()
+                .name("auto-power".into())
+                .spawn(move || {
+                    if let Err(err) = power::set_thread_execution_state(state) {
+                        let _ = tx.send(Err(err));
+                        return;
+                    }
+                    let _ = tx.send(Ok(()));
+                    let _ = stopped.recv();
+                    let _ = power::set_thread_execution_state(ExecutionState::CONTINUOUS);
+                })
+                .expect("failed to spawn power thread");
+
+            match rx.recv() {
+                Ok(Ok(())) => Ok(Assertion { stop: Some(stop), thread: Some(thread) }),
+                Ok(Err(err)) => {
+                    let _ = thread.join();
+                    Err(err)
+                },
+                Err(_) => panic!("power thread exited while setting its state"),
+            }
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod imp {
+    use error::{Error, Result};
+
+    pub struct Assertion;
+
+    impl Assertion {
+        pub fn new(_: &str, _: bool) -> Result<Assertion> {
+            Err(Error::Unsupported)
+        }
+    }
+}
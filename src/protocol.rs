@@ -0,0 +1,115 @@
+//! The line-based wire format shared by the `cluster` and `agent` features.
+//!
+//! Commands are lines of text. Recordings are sent as a command line ending
+//! with the length of the recording in bytes and its timing, followed by the
+//! recording in the [text format](../record/index.html#file-format).
+
+use std::error;
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+use record::{Recording, Timing};
+
+/// The largest recording accepted, in bytes.
+const MAX_RECORDING_LEN: usize = 64 * 1024 * 1024;
+
+pub fn invalid_data<E: Into<Box<dyn error::Error + Send + Sync>>>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// Reads a line without its terminator, failing at the end of the stream.
+pub fn read_line<R: BufRead>(reader: &mut R, line: &mut String) -> io::Result<()> {
+    line.clear();
+    if reader.read_line(line)? == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    let len = line.trim_end_matches(&['\r', '\n'][..]).len();
+    line.truncate(len);
+    Ok(())
+}
+
+/// Splits a line into its command and the rest of its arguments.
+pub fn split_command(line: &str) -> (&str, &str) {
+    match line.find(' ') {
+        Some(index) => (&line[..index], &line[index + 1..]),
+        None => (line, ""),
+    }
+}
+
+pub fn format_timing(timing: Timing) -> String {
+    match timing {
+        Timing::Original => "original".into(),
+        Timing::Immediate => "immediate".into(),
+        Timing::Scaled(factor) => format!("scaled {}", factor),
+    }
+}
+
+pub fn parse_timing(s: &str) -> Option<Timing> {
+    let mut words = s.split_whitespace();
+    let timing = match words.next()? {
+        "original" => Timing::Original,
+        "immediate" => Timing::Immediate,
+        "scaled" => {
+            // Factors are durations per second of recording
+            let factor = words.next()?.parse().ok()?;
+            Duration::try_from_secs_f64(factor).ok()?;
+            Timing::Scaled(factor)
+        },
+        _ => return None,
+    };
+    match words.next() {
+        None => Some(timing),
+        Some(_) => None,
+    }
+}
+
+/// Writes `command` followed by `recording` and `timing`.
+pub fn write_recording<W: Write>(
+    writer: &mut W,
+    command: &str,
+    recording: &Recording,
+    timing: Timing,
+) -> io::Result<()> {
+    let text = recording.to_string();
+    write!(writer, "{} {} {}\n{}", command, text.len(), format_timing(timing), text)
+}
+
+/// Reads the recording announced by the arguments `args` of a command.
+///
+/// The outer error means the stream is unusable, while the inner one
+/// describes an invalid recording or timing to report back.
+pub fn read_recording<R: BufRead>(reader: &mut R, args: &str)
+    -> io::Result<Result<(Recording, Timing), String>>
+{
+    let (len, timing) = split_command(args);
+    let len: usize = len.parse().map_err(invalid_data)?;
+    if len > MAX_RECORDING_LEN {
+        return Err(invalid_data("recording is too large"));
+    }
+    let mut text = vec![0; len];
+    reader.read_exact(&mut text)?;
+
+    let recording = String::from_utf8(text)
+        .map_err(|err| err.to_string())
+        .and_then(|text| text.parse::<Recording>().map_err(|err| err.to_string()));
+    let timing = parse_timing(timing)
+        .ok_or_else(|| format!("invalid timing {:?}", timing));
+    Ok(recording.and_then(|r| timing.map(|t| (r, t))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timing_text() {
+        for &timing in &[Timing::Original, Timing::Immediate, Timing::Scaled(1.5)] {
+            assert_eq!(parse_timing(&format_timing(timing)), Some(timing));
+        }
+        assert_eq!(parse_timing("scaled"), None);
+        assert_eq!(parse_timing("scaled -1"), None);
+        assert_eq!(parse_timing("scaled NaN"), None);
+        assert_eq!(parse_timing("scaled 1e300"), None);
+        assert_eq!(parse_timing("immediate now"), None);
+    }
+}
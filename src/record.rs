@@ -0,0 +1,1116 @@
+//! ⏺️ Recording and replaying timed input.
+//!
+//! A [`Recorder`](struct.Recorder.html) captures keyboard and mouse input as
+//! it happens into a [`Recording`](struct.Recording.html). Recordings can be
+//! saved to a plain text file, edited by hand or in code, and replayed with
+//! their original timing or sped up and slowed down.
+//!
+//! # File Format
+//!
+//! Each line holds one event, prefixed by its time in seconds since the start
+//! of the recording. Blank lines and lines starting with `#` are ignored,
+//! except for the first line, which declares the version of the format.
+//! Recordings in older versions are migrated as they are parsed; see
+//! [`migrate`](fn.migrate.html).
+//!
+//! ```text
+//! # auto recording v2
+//! 0.000000 key-down shift
+//! 0.084000 key-down a
+//! 0.130000 key-up a
+//! 0.152000 key-up shift
+//! 0.900000 move 412 300.5
+//! 1.020000 button-down left 412 300.5
+//! 1.100000 button-up left 412 300.5
+//! 1.500000 scroll 0 -3
+//! 1.700000 char U+00E9
+//! 1.800000 media play-pause
+//! 2.000000 pan began 640 400 0 0
+//! 2.016000 pan changed 640 400 0 -12.5
+//! 2.032000 pan ended 640 387.5 0 0
+//! 2.500000 pinch changed 640 400 1.05
+//! ```
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::thread;
+//! use std::time::Duration;
+//! use auto::record::{Recorder, Recording, Timing};
+//!
+//! let recorder = Recorder::start().unwrap();
+//! thread::sleep(Duration::from_secs(5));
+//! recorder.finish().save("login.rec").unwrap();
+//!
+//! let mut recording = Recording::load("login.rec").unwrap();
+//! recording.events.retain(|e| e.time < Duration::from_secs(3));
+//! recording.replay(Timing::Scaled(0.5)).unwrap();
+//! ```
+//!
+//! # Display Changes
+//!
+//! Recorded locations are only meaningful for the display layout they were
+//! recorded on. A [`Playback`](struct.Playback.html) can watch for monitors
+//! being added, removed, moved, or resized, and then pause at a
+//! [`Checkpoint`](struct.Checkpoint.html), abort, or remap the remaining
+//! locations onto the new layout, as chosen by its
+//! [`LayoutPolicy`](enum.LayoutPolicy.html). By default, it doesn't watch.
+//!
+//! ```no_run
+//! use auto::record::{LayoutPolicy, Progress, Recording};
+//!
+//! let recording = Recording::load("login.rec").unwrap();
+//! let mut playback = recording.playback();
+//! playback.on_layout_change(LayoutPolicy::Pause);
+//!
+//! while let Progress::Paused(checkpoint) = playback.run().unwrap() {
+//!     println!("displays changed; resuming at event {}", checkpoint.index());
+//!     playback.resume_from(checkpoint);
+//! }
+//! ```
+
+use std::fmt;
+use std::fs;
+#[cfg(feature = "tokio")]
+use std::future::Future;
+use std::io;
+use std::path::Path;
+#[cfg(feature = "tokio")]
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "tokio")]
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "tokio")]
+use tokio::time::{self, Sleep};
+
+use abort::Token;
+use error::{Error, Result};
+use events::{self, Gesture, InputEvent, Listener, Location, ModifierGuard};
+use keyboard::Key;
+use mouse::{self, Button};
+use screen::{self, Display};
+use transform;
+
+/// The version of the text format written by this crate.
+///
+/// Recordings saved in older versions are migrated when parsed, so that they
+/// keep replaying after keys or buttons are renamed.
+pub const VERSION: u32 = 2;
+
+/// The start of the first line of the text format, followed by the version.
+const HEADER_PREFIX: &str = "# auto recording v";
+
+/// Renames that turn a recording in one version of the text format into the
+/// next version.
+struct Migration {
+    /// The version migrated from, which becomes `from + 1`.
+    from: u32,
+    /// Old and new names of keys.
+    keys: &'static [(&'static str, &'static str)],
+    /// Old and new names of buttons.
+    buttons: &'static [(&'static str, &'static str)],
+}
+
+impl Migration {
+    fn apply(&self, line: &str) -> String {
+        let mut words: Vec<&str> = line.split_whitespace().collect();
+        let table = match words.get(1) {
+            Some(&"key-down") | Some(&"key-up") => self.keys,
+            Some(&"button-down") | Some(&"button-up") => self.buttons,
+            _ => return line.into(),
+        };
+        if let Some(name) = words.get_mut(2) {
+            if let Some(&(_, new)) = table.iter().find(|&&(old, _)| old == *name) {
+                *name = new;
+            }
+        }
+        words.join(" ")
+    }
+}
+
+/// Every migration, ordered by the version migrated from.
+///
+/// When a key or button is renamed, bump `VERSION` and add an entry here
+/// mapping the old names to the new ones.
+const MIGRATIONS: &[Migration] = &[
+    // Version 2 named the back and forward buttons
+    Migration {
+        from: 1,
+        keys: &[],
+        buttons: &[("other-3", "back"), ("other-4", "forward")],
+    },
+];
+
+/// Returns the format version declared by the header of `text`, if any.
+pub fn version_of(text: &str) -> Option<u32> {
+    let first = text.lines().map(str::trim).find(|line| !line.is_empty())?;
+    first.strip_prefix(HEADER_PREFIX)?.parse().ok()
+}
+
+/// Parses `text` as a recording saved in version `from_version` of the text
+/// format, renaming keys and buttons that have changed since.
+///
+/// Parsing a recording with `str::parse` migrates it from the version in
+/// its header automatically; this is for recordings whose header is missing
+/// or wrong.
+pub fn migrate(text: &str, from_version: u32) -> ::std::result::Result<Recording, ParseError> {
+    migrate_with(text, from_version, MIGRATIONS)
+}
+
+fn migrate_with(text: &str, from_version: u32, migrations: &[Migration])
+    -> ::std::result::Result<Recording, ParseError>
+{
+    if from_version == 0 || from_version > VERSION {
+        let message = format!("unsupported recording version {}", from_version);
+        return Err(ParseError { line: 1, message });
+    }
+    let migrations: Vec<&Migration> = migrations.iter()
+        .filter(|m| m.from >= from_version)
+        .collect();
+
+    let mut recording = Recording::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = migrations.iter().fold(line.to_owned(), |line, m| m.apply(&line));
+        let event = parse_line(&line).map_err(|message| {
+            ParseError { line: index + 1, message }
+        })?;
+        recording.events.push(event);
+    }
+    Ok(recording)
+}
+
+/// An input event and the time at which it occurred.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TimedEvent {
+    /// The time since the start of the recording.
+    pub time: Duration,
+    /// The event that occurred.
+    pub event: InputEvent,
+}
+
+/// How the delays between events are reproduced during replay.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Timing {
+    /// Events are posted with the delays they were recorded with.
+    Original,
+    /// Every delay is multiplied by the factor, so that `0.5` replays twice as
+    /// fast and `2.0` replays at half speed.
+    Scaled(f64),
+    /// Events are posted back to back without delay.
+    Immediate,
+}
+
+/// What [`Playback`](struct.Playback.html) does when the layout of the
+/// displays changes while it runs.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum LayoutPolicy {
+    /// Keeps going without watching the layout, which costs nothing while
+    /// playing.
+    Ignore,
+    /// Stops before the next event and returns a checkpoint to resume from.
+    Pause,
+    /// Stops with `Error::LayoutChanged`.
+    Abort,
+    /// Moves the locations of the remaining events from each display in the
+    /// old layout onto its counterpart in the new layout, and keeps going.
+    ///
+    /// The main display maps onto the new main display, and other displays
+    /// onto the display in the same position of the new display list, or the
+    /// main display if there is none. Locations keep their position relative
+    /// to the size of the display.
+    RemapAndContinue,
+}
+
+/// A point in a recording from which playback can resume.
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct Checkpoint {
+    index: usize,
+}
+
+impl Checkpoint {
+    /// Returns the index of the next event to post.
+    #[inline]
+    pub fn index(self) -> usize {
+        self.index
+    }
+}
+
+/// How far [`Playback::run`](struct.Playback.html#method.run) got.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum Progress {
+    /// Every event was posted.
+    Finished,
+    /// Playback paused because the layout of the displays changed.
+    Paused(Checkpoint),
+}
+
+/// Options for replaying a [`Recording`](struct.Recording.html).
+///
+/// Created by [`Recording::playback`](struct.Recording.html#method.playback).
+#[derive(Copy, Clone, Debug)]
+pub struct Playback<'a> {
+    recording: &'a Recording,
+    timing: Timing,
+    policy: LayoutPolicy,
+    from: Checkpoint,
+    compensate: bool,
+    isolate: bool,
+}
+
+impl<'a> Playback<'a> {
+    /// Creates options for replaying `recording` from the start with its
+    /// original timing, without watching the layout of the displays.
+    #[inline]
+    pub fn new(recording: &'a Recording) -> Playback<'a> {
+        Playback {
+            recording,
+            timing: Timing::Original,
+            policy: LayoutPolicy::Ignore,
+            from: Checkpoint::default(),
+            compensate: true,
+            isolate: false,
+        }
+    }
+
+    /// Sets how the delays between events are reproduced.
+    pub fn timing(&mut self, timing: Timing) -> &mut Playback<'a> {
+        self.timing = timing;
+        self
+    }
+
+    /// Sets what happens when the layout of the displays changes.
+    ///
+    /// Any policy but [`Ignore`](enum.LayoutPolicy.html#variant.Ignore)
+    /// watches the layout while playing, which on Windows and Linux means
+    /// reading it every 250 milliseconds.
+    pub fn on_layout_change(&mut self, policy: LayoutPolicy) -> &mut Playback<'a> {
+        self.policy = policy;
+        self
+    }
+
+    /// Sets whether each event is posted early by the
+    /// [latency compensation](fn.latency_compensation.html), which is enabled
+    /// by default but zero until [`calibrate`](fn.calibrate.html) has run.
+    pub fn compensate_latency(&mut self, compensate: bool) -> &mut Playback<'a> {
+        self.compensate = compensate;
+        self
+    }
+
+    /// Sets whether modifier keys that the user holds during playback are
+    /// kept from affecting the replayed events, as with
+    /// [`events::isolate_modifiers`](../events/fn.isolate_modifiers.html).
+    ///
+    /// Disabled by default. Where modifiers cannot be isolated, such as on
+    /// Linux, playback fails with
+    /// [`Error::Unsupported`](../error/enum.Error.html#variant.Unsupported).
+    pub fn isolate_modifiers(&mut self, isolate: bool) -> &mut Playback<'a> {
+        self.isolate = isolate;
+        self
+    }
+
+    /// Sets the checkpoint to start from, which must have come from playing
+    /// the same recording.
+    ///
+    /// Delays are measured from the event at the checkpoint, so the first
+    /// event is posted right away.
+    pub fn resume_from(&mut self, checkpoint: Checkpoint) -> &mut Playback<'a> {
+        self.from = checkpoint;
+        self
+    }
+
+    /// Posts the events in order, waiting between them according to the
+    /// timing.
+    ///
+    /// Playback stops at the first event that fails to post, or when the
+    /// [kill switch](../abort/index.html) is pressed, after releasing the
+    /// keys and buttons that the recording pressed but had not yet released.
+    /// They stay held when playback pauses or finishes.
+    pub fn run(&self) -> Result<Progress> {
+        let mut run = Run::new(*self)?;
+        let result = run.play();
+        if result.is_err() {
+            run.release();
+        }
+        result
+    }
+
+    /// Returns a future that posts the events like
+    /// [`run`](#method.run), waiting between them on the Tokio timer rather
+    /// than by blocking.
+    ///
+    /// The future must be polled within a Tokio runtime with the time driver
+    /// enabled. Playback starts when it is first polled, and stops if it is
+    /// dropped, releasing what it pressed as when it fails.
+    #[cfg(feature = "tokio")]
+    pub fn run_async(&self) -> PlaybackFuture<'a> {
+        PlaybackFuture { playback: *self, run: None, sleep: None }
+    }
+}
+
+/// A future returned by [`Playback::run_async`](struct.Playback.html#method.run_async)
+/// that resolves to how far playback got.
+#[cfg(feature = "tokio")]
+pub struct PlaybackFuture<'a> {
+    playback: Playback<'a>,
+    run: Option<Run<'a>>,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+#[cfg(feature = "tokio")]
+impl<'a> fmt::Debug for PlaybackFuture<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PlaybackFuture")
+            .field("playback", &self.playback)
+            .field("next", &self.run.as_ref().map(|run| run.from + run.next))
+            .finish()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<'a> Future for PlaybackFuture<'a> {
+    type Output = Result<Progress>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<Progress>> {
+        let this = &mut *self;
+        let playback = this.playback;
+        let run = match this.run {
+            Some(ref mut run) => run,
+            None => this.run.insert(Run::new(playback)?),
+        };
+        let result = loop {
+            let due = match run.due() {
+                Some(due) => due,
+                None => break Ok(Progress::Finished),
+            };
+            if this.sleep.is_none() && due > Instant::now() {
+                this.sleep = Some(Box::pin(time::sleep_until(due.into())));
+            }
+            if let Some(ref mut sleep) = this.sleep {
+                if sleep.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+            }
+            this.sleep = None;
+            match run.post_next() {
+                Ok(Some(progress)) => break Ok(progress),
+                Ok(None) => {},
+                Err(err) => {
+                    run.release();
+                    break Err(err);
+                },
+            }
+        };
+        this.run = None;
+        Poll::Ready(result)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<'a> Drop for PlaybackFuture<'a> {
+    fn drop(&mut self) {
+        if let Some(ref mut run) = self.run {
+            run.release();
+        }
+    }
+}
+
+/// The state of playback in progress.
+struct Run<'a> {
+    policy: LayoutPolicy,
+    timing: Timing,
+    events: &'a [TimedEvent],
+    from: usize,
+    next: usize,
+    offset: Duration,
+    latency: Duration,
+    /// When the previous event was due after the first, and when posting it
+    /// finished.
+    last: Option<(Duration, Instant)>,
+    watch: Option<LayoutWatch>,
+    start: Instant,
+    token: Token,
+    pressed: Pressed,
+    _modifiers: Option<ModifierGuard>,
+}
+
+impl<'a> Run<'a> {
+    fn new(playback: Playback<'a>) -> Result<Run<'a>> {
+        let events = playback.recording.events.get(playback.from.index..).unwrap_or(&[]);
+        let latency = match playback.timing {
+            Timing::Immediate => Duration::from_secs(0),
+            _ if playback.compensate => latency_compensation(),
+            _ => Duration::from_secs(0),
+        };
+        let modifiers = if playback.isolate { Some(events::isolate_modifiers()?) } else { None };
+        Ok(Run {
+            policy: playback.policy,
+            timing: playback.timing,
+            events,
+            from: playback.from.index,
+            next: 0,
+            offset: events.first().map(|e| e.time).unwrap_or_default(),
+            latency,
+            last: None,
+            watch: match playback.policy {
+                LayoutPolicy::Ignore => None,
+                _ => LayoutWatch::start(),
+            },
+            start: Instant::now(),
+            token: Token::new(),
+            pressed: Pressed::default(),
+            _modifiers: modifiers,
+        })
+    }
+
+    /// Posts the events in order until every one has been or playback stops.
+    fn play(&mut self) -> Result<Progress> {
+        while let Some(due) = self.due() {
+            self.token.sleep(due.saturating_duration_since(Instant::now()))?;
+            if let Some(progress) = self.post_next()? {
+                return Ok(progress);
+            }
+        }
+        Ok(Progress::Finished)
+    }
+
+    /// Posts `event`, keeping track of what it presses.
+    fn post(&mut self, event: &InputEvent) -> Result<()> {
+        events::post(event)?;
+        self.pressed.update(event);
+        Ok(())
+    }
+
+    /// Releases every key and button that playback pressed and hasn't
+    /// released.
+    fn release(&mut self) {
+        for event in self.pressed.releases() {
+            let _ = events::post(&event);
+        }
+        self.pressed = Pressed::default();
+    }
+
+    /// Returns how long after the first event the event at `index` is due.
+    fn scheduled(&self, index: usize) -> Duration {
+        let time = self.events[index].time - self.offset;
+        match self.timing {
+            Timing::Original => time,
+            Timing::Scaled(factor) => {
+                Duration::try_from_secs_f64(time.as_secs_f64() * factor.max(0.0))
+                    .unwrap_or(Duration::MAX)
+            },
+            Timing::Immediate => Duration::from_secs(0),
+        }
+    }
+
+    /// Returns when the next event should be posted, or `None` once every
+    /// event has been.
+    ///
+    /// Each event follows the previous one by their gap in the recording,
+    /// counted from when posting the previous one finished, less the time
+    /// posting takes. A slow post then delays the events after it rather
+    /// than squeezing the gap to the next one.
+    fn due(&self) -> Option<Instant> {
+        if self.next >= self.events.len() {
+            return None;
+        }
+        let due = self.scheduled(self.next);
+        Some(match self.last {
+            Some((previous, posted)) => {
+                let gap = due.saturating_sub(previous);
+                match gap.checked_sub(self.latency) {
+                    Some(gap) => posted + gap,
+                    None => posted,
+                }
+            },
+            None => self.start + due,
+        })
+    }
+
+    /// Moves on to the next event once the current one has been posted.
+    fn advance(&mut self) {
+        self.last = Some((self.scheduled(self.next), Instant::now()));
+        self.next += 1;
+    }
+
+    /// Posts the next event, unless playback was aborted or the layout of the
+    /// displays changed in a way that stops it.
+    fn post_next(&mut self) -> Result<Option<Progress>> {
+        self.token.check()?;
+        let event = &self.events[self.next].event;
+        let watch = match self.watch {
+            Some(ref mut watch) => watch,
+            None => {
+                self.post(event)?;
+                self.advance();
+                return Ok(None);
+            },
+        };
+        if watch.changed() {
+            match self.policy {
+                LayoutPolicy::Pause => {
+                    let index = self.from + self.next;
+                    return Ok(Some(Progress::Paused(Checkpoint { index })));
+                },
+                LayoutPolicy::Abort => return Err(Error::LayoutChanged),
+                LayoutPolicy::Ignore | LayoutPolicy::RemapAndContinue => {},
+            }
+        }
+        let event = if watch.initial == watch.current {
+            *event
+        } else {
+            let (old, new) = (&watch.initial, &watch.current);
+            transform::map_locations(event, &|pos| remap(old, new, pos))
+        };
+        self.post(&event)?;
+        self.advance();
+        Ok(None)
+    }
+}
+
+/// The keys and buttons that playback pressed and hasn't released.
+#[derive(Debug, Default)]
+struct Pressed {
+    keys: Vec<Key>,
+    buttons: Vec<Button>,
+    /// Where the mouse was last posted to.
+    location: Location,
+}
+
+impl Pressed {
+    fn update(&mut self, event: &InputEvent) {
+        match *event {
+            InputEvent::KeyDown(key) if !self.keys.contains(&key) => self.keys.push(key),
+            InputEvent::KeyUp(key) => self.keys.retain(|&k| k != key),
+            InputEvent::ButtonDown(button, location) => {
+                if !self.buttons.contains(&button) {
+                    self.buttons.push(button);
+                }
+                self.location = location;
+            },
+            InputEvent::ButtonUp(button, location) => {
+                self.buttons.retain(|&b| b != button);
+                self.location = location;
+            },
+            InputEvent::MouseMoved(location) | InputEvent::Gesture(_, _, location) => self.location = location,
+            _ => {},
+        }
+    }
+
+    /// Returns the events that release the buttons and then the keys, the
+    /// most recently pressed first.
+    fn releases(&self) -> Vec<InputEvent> {
+        let buttons = self.buttons.iter().rev().map(|&button| InputEvent::ButtonUp(button, self.location));
+        let keys = self.keys.iter().rev().map(|&key| InputEvent::KeyUp(key));
+        buttons.chain(keys).collect()
+    }
+}
+
+/// The number of events posted to measure the posting latency.
+const CALIBRATION_POSTS: u32 = 8;
+
+lazy_static! {
+    /// The measured time to post an event, once calibrated.
+    static ref LATENCY: Mutex<Option<Duration>> = Mutex::new(None);
+}
+
+/// Measures how long it takes to post an event on this machine, and uses the
+/// result for [latency compensation](fn.latency_compensation.html) from then
+/// on.
+///
+/// The mouse is moved to where it already is several times, which listeners
+/// will observe, so this is best called once at startup, before any
+/// [`Playback`](struct.Playback.html) runs.
+pub fn calibrate() -> Result<Duration> {
+    let location = mouse::location()?;
+    let event = InputEvent::MouseMoved(location);
+    let start = Instant::now();
+    for _ in 0..CALIBRATION_POSTS {
+        events::post(&event)?;
+    }
+    let latency = start.elapsed() / CALIBRATION_POSTS;
+    *LATENCY.lock().unwrap_or_else(|e| e.into_inner()) = Some(latency);
+    Ok(latency)
+}
+
+/// Returns how much earlier than scheduled
+/// [`Playback`](struct.Playback.html) posts each event, so that it takes
+/// effect on time.
+///
+/// This is the posting latency measured by [`calibrate`](fn.calibrate.html),
+/// or zero if it hasn't been measured.
+pub fn latency_compensation() -> Duration {
+    LATENCY.lock().unwrap_or_else(|e| e.into_inner()).unwrap_or_default()
+}
+
+/// How long display changes are coalesced before being reported.
+#[cfg(target_os = "macos")]
+const LAYOUT_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// How often the display layout is read where changes aren't reported.
+#[cfg(not(target_os = "macos"))]
+const LAYOUT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Tracks the layout of the displays during playback.
+struct LayoutWatch {
+    initial: Vec<Display>,
+    current: Vec<Display>,
+    #[cfg(target_os = "macos")]
+    topology: Option<::os::screen::TopologyEvents>,
+    #[cfg(not(target_os = "macos"))]
+    last_poll: Instant,
+}
+
+impl LayoutWatch {
+    /// Starts watching, or returns `None` if displays can't be listed.
+    fn start() -> Option<LayoutWatch> {
+        let initial = screen::displays().ok()?;
+        Some(LayoutWatch {
+            current: initial.clone(),
+            initial,
+            #[cfg(target_os = "macos")]
+            topology: ::os::screen::topology_events(LAYOUT_DEBOUNCE).ok(),
+            #[cfg(not(target_os = "macos"))]
+            last_poll: Instant::now(),
+        })
+    }
+
+    #[cfg(target_os = "macos")]
+    fn signalled(&mut self) -> bool {
+        match self.topology {
+            Some(ref mut topology) => {
+                let mut any = false;
+                while topology.try_next().is_some() {
+                    any = true;
+                }
+                any
+            },
+            None => false,
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    fn signalled(&mut self) -> bool {
+        if self.last_poll.elapsed() < LAYOUT_POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        true
+    }
+
+    /// Returns whether the layout changed since the last call.
+    fn changed(&mut self) -> bool {
+        if !self.signalled() {
+            return false;
+        }
+        match screen::displays() {
+            Ok(layout) if layout != self.current => {
+                self.current = layout;
+                true
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Moves `pos` from its display in the `old` layout onto the corresponding
+/// display in the `new` layout, as described by
+/// [`LayoutPolicy::RemapAndContinue`](enum.LayoutPolicy.html#variant.RemapAndContinue).
+fn remap(old: &[Display], new: &[Display], pos: Location) -> Location {
+    // Displays without an area contain no locations to divide their size by,
+    // nor any to move locations onto
+    let has_area = |d: &&Display| d.size.0 > 0.0 && d.size.1 > 0.0;
+    let (index, from) = match old.iter().enumerate().find(|&(_, d)| d.contains(pos)) {
+        Some(found) => found,
+        None => return pos,
+    };
+    let main = new.iter().filter(has_area).find(|d| d.is_main);
+    let to = if from.is_main { main } else { new.get(index).filter(has_area).or(main) };
+    let to = match to {
+        Some(to) => to,
+        None => return pos,
+    };
+    let fx = (pos.0 - from.origin.0) / from.size.0;
+    let fy = (pos.1 - from.origin.1) / from.size.1;
+    (to.origin.0 + fx * to.size.0, to.origin.1 + fy * to.size.1)
+}
+
+/// A timed sequence of input events.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Recording {
+    /// The events, ordered by time.
+    pub events: Vec<TimedEvent>,
+}
+
+impl Recording {
+    /// Creates an empty recording.
+    #[inline]
+    pub fn new() -> Recording {
+        Recording::default()
+    }
+
+    /// Returns the time of the last event.
+    pub fn duration(&self) -> Duration {
+        self.events.last().map(|e| e.time).unwrap_or_default()
+    }
+
+    /// Appends `event` at `time`.
+    pub fn push(&mut self, time: Duration, event: InputEvent) {
+        self.events.push(TimedEvent { time, event });
+    }
+
+    /// Shifts all events so that the first one occurs at time zero.
+    pub fn trim_start(&mut self) {
+        if let Some(start) = self.events.first().map(|e| e.time) {
+            for event in &mut self.events {
+                event.time -= start;
+            }
+        }
+    }
+
+    /// Posts every event in order, waiting between events according to
+    /// `timing`.
+    ///
+    /// Replay stops at the first event that fails to post. Use
+    /// [`playback`](#method.playback) to handle changes to the layout of the
+    /// displays.
+    pub fn replay(&self, timing: Timing) -> Result<()> {
+        self.playback().timing(timing).run().map(|_| ())
+    }
+
+    /// Returns options for replaying the recording.
+    #[inline]
+    pub fn playback(&self) -> Playback<'_> {
+        Playback::new(self)
+    }
+
+    /// Reads a recording from the file at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Recording> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Writes the recording to the file at `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+impl fmt::Display for Recording {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}{}", HEADER_PREFIX, VERSION)?;
+        for TimedEvent { time, event } in &self.events {
+            write!(f, "{:.6} ", time.as_secs_f64())?;
+            match *event {
+                InputEvent::KeyDown(key) => writeln!(f, "key-down {}", key)?,
+                InputEvent::KeyUp(key) => writeln!(f, "key-up {}", key)?,
+                InputEvent::ButtonDown(button, (x, y)) => {
+                    writeln!(f, "button-down {} {} {}", button, x, y)?
+                },
+                InputEvent::ButtonUp(button, (x, y)) => {
+                    writeln!(f, "button-up {} {} {}", button, x, y)?
+                },
+                InputEvent::MouseMoved((x, y)) => writeln!(f, "move {} {}", x, y)?,
+                InputEvent::Scroll { dx, dy } => writeln!(f, "scroll {} {}", dx, dy)?,
+                InputEvent::Char(c) => writeln!(f, "char U+{:04X}", u32::from(c))?,
+                InputEvent::Media(key) => writeln!(f, "media {}", key)?,
+                InputEvent::Gesture(Gesture::Pan { dx, dy }, phase, (x, y)) => {
+                    writeln!(f, "pan {} {} {} {} {}", phase, x, y, dx, dy)?
+                },
+                InputEvent::Gesture(Gesture::Pinch { scale }, phase, (x, y)) => {
+                    writeln!(f, "pinch {} {} {} {}", phase, x, y, scale)?
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An error returned when parsing a [`Recording`](struct.Recording.html) from
+/// text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    line: usize,
+    message: String,
+}
+
+impl ParseError {
+    /// Returns the one-based line number at which the error occurred.
+    #[inline]
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl ::std::error::Error for ParseError {}
+
+impl FromStr for Recording {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> ::std::result::Result<Recording, ParseError> {
+        migrate(s, version_of(s).unwrap_or(VERSION))
+    }
+}
+
+fn parse_line(line: &str) -> ::std::result::Result<TimedEvent, String> {
+    fn arg<T: FromStr>(args: &mut dyn Iterator<Item = &str>, what: &str)
+        -> ::std::result::Result<T, String>
+    {
+        let s = args.next().ok_or_else(|| format!("missing {}", what))?;
+        s.parse().map_err(|_| format!("invalid {} {:?}", what, s))
+    }
+
+    let mut args = line.split_whitespace();
+    let time: f64 = arg(&mut args, "time")?;
+    let time = Duration::try_from_secs_f64(time).map_err(|_| format!("invalid time {}", time))?;
+
+    let kind = args.next().ok_or("missing event")?;
+    let event = match kind {
+        "key-down" => InputEvent::KeyDown(arg(&mut args, "key")?),
+        "key-up" => InputEvent::KeyUp(arg(&mut args, "key")?),
+        "button-down" | "button-up" => {
+            let button = arg(&mut args, "button")?;
+            let location = (arg(&mut args, "x")?, arg(&mut args, "y")?);
+            if kind == "button-down" {
+                InputEvent::ButtonDown(button, location)
+            } else {
+                InputEvent::ButtonUp(button, location)
+            }
+        },
+        "move" => InputEvent::MouseMoved((arg(&mut args, "x")?, arg(&mut args, "y")?)),
+        "scroll" => InputEvent::Scroll {
+            dx: arg(&mut args, "dx")?,
+            dy: arg(&mut args, "dy")?,
+        },
+        "char" => {
+            let code = args.next().ok_or("missing char")?;
+            let c = code.strip_prefix("U+")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .and_then(char::from_u32)
+                .ok_or_else(|| format!("invalid char {:?}", code))?;
+            InputEvent::Char(c)
+        },
+        "media" => InputEvent::Media(arg(&mut args, "media key")?),
+        "pan" | "pinch" => {
+            let phase = arg(&mut args, "phase")?;
+            let location = (arg(&mut args, "x")?, arg(&mut args, "y")?);
+            let gesture = if kind == "pan" {
+                Gesture::Pan { dx: arg(&mut args, "dx")?, dy: arg(&mut args, "dy")? }
+            } else {
+                Gesture::Pinch { scale: arg(&mut args, "scale")? }
+            };
+            InputEvent::Gesture(gesture, phase, location)
+        },
+        _ => return Err(format!("unknown event {:?}", kind)),
+    };
+    if let Some(extra) = args.next() {
+        return Err(format!("unexpected {:?}", extra));
+    }
+    Ok(TimedEvent { time, event })
+}
+
+/// Captures input events into a [`Recording`](struct.Recording.html) until
+/// finished or dropped.
+#[derive(Debug)]
+pub struct Recorder {
+    recording: Arc<Mutex<Recording>>,
+    _listener: Listener,
+}
+
+impl Recorder {
+    /// Starts capturing keyboard and mouse input.
+    pub fn start() -> Result<Recorder> {
+        let recording = Arc::new(Mutex::new(Recording::new()));
+        let start = Instant::now();
+        let out = recording.clone();
+        let listener = events::listen(move |event| {
+            let mut out = out.lock().unwrap_or_else(|e| e.into_inner());
+            out.push(start.elapsed(), *event);
+        })?;
+        Ok(Recorder { recording, _listener: listener })
+    }
+
+    /// Returns the number of events captured so far.
+    pub fn len(&self) -> usize {
+        self.recording.lock().unwrap_or_else(|e| e.into_inner()).events.len()
+    }
+
+    /// Returns whether no events have been captured so far.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Stops capturing and returns the recording.
+    pub fn finish(self) -> Recording {
+        let recording = self.recording.lock().unwrap_or_else(|e| e.into_inner());
+        recording.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use events::Phase;
+    use keyboard::{Key, MediaKey};
+    use mouse::Button;
+
+    #[test]
+    fn text_round_trip() {
+        let mut recording = Recording::new();
+        recording.push(Duration::from_millis(0), InputEvent::KeyDown(Key::Shift));
+        recording.push(Duration::from_millis(84), InputEvent::KeyUp(Key::Other(300)));
+        recording.push(Duration::from_millis(900), InputEvent::MouseMoved((412.0, 300.5)));
+        recording.push(Duration::from_millis(1020), InputEvent::ButtonDown(Button::Middle, (1.0, 2.0)));
+        recording.push(Duration::from_millis(1100), InputEvent::ButtonUp(Button::Other(5), (1.0, 2.0)));
+        recording.push(Duration::from_millis(1500), InputEvent::Scroll { dx: 0, dy: -3 });
+        recording.push(Duration::from_millis(1700), InputEvent::Char('é'));
+        recording.push(Duration::from_millis(1800), InputEvent::Char(' '));
+        recording.push(Duration::from_millis(1900), InputEvent::Media(MediaKey::VolumeDown));
+        recording.push(
+            Duration::from_millis(2016),
+            InputEvent::Gesture(Gesture::Pan { dx: 0.0, dy: -12.5 }, Phase::Changed, (640.0, 400.0)),
+        );
+        recording.push(
+            Duration::from_millis(2500),
+            InputEvent::Gesture(Gesture::Pinch { scale: 1.05 }, Phase::Ended, (640.0, 400.0)),
+        );
+
+        let text = recording.to_string();
+        assert_eq!(version_of(&text), Some(VERSION));
+        assert_eq!(text.parse::<Recording>(), Ok(recording));
+    }
+
+    #[test]
+    fn compensated_gaps() {
+        let mut recording = Recording::new();
+        for &ms in &[500, 520, 900] {
+            recording.push(Duration::from_millis(ms), InputEvent::KeyDown(Key::Shift));
+        }
+        let start = Instant::now();
+        let mut run = Run {
+            policy: LayoutPolicy::Pause,
+            timing: Timing::Scaled(0.5),
+            events: &recording.events,
+            from: 0,
+            next: 0,
+            offset: Duration::from_millis(500),
+            latency: Duration::from_millis(15),
+            last: None,
+            watch: None,
+            start,
+            token: Token::new(),
+            pressed: Pressed::default(),
+            _modifiers: None,
+        };
+        let ms = Duration::from_millis;
+
+        assert_eq!(run.due(), Some(start));
+        // Posting the first event took 40ms
+        run.last = Some((run.scheduled(0), start + ms(40)));
+        run.next = 1;
+        assert_eq!(run.due(), Some(start + ms(40)));
+        run.last = Some((run.scheduled(1), start + ms(50)));
+        run.next = 2;
+        assert_eq!(run.due(), Some(start + ms(50 + 190 - 15)));
+        run.next = 3;
+        assert_eq!(run.due(), None);
+    }
+
+    #[test]
+    fn release_pressed() {
+        let mut pressed = Pressed::default();
+        for event in &[
+            InputEvent::KeyDown(Key::Shift),
+            InputEvent::KeyDown(Key::A),
+            InputEvent::KeyDown(Key::A),
+            InputEvent::KeyDown(Key::B),
+            InputEvent::KeyUp(Key::B),
+            InputEvent::ButtonDown(Button::Left, (10.0, 20.0)),
+            InputEvent::MouseMoved((30.0, 40.0)),
+        ] {
+            pressed.update(event);
+        }
+
+        assert_eq!(pressed.releases(), [
+            InputEvent::ButtonUp(Button::Left, (30.0, 40.0)),
+            InputEvent::KeyUp(Key::A),
+            InputEvent::KeyUp(Key::Shift),
+        ]);
+    }
+
+    #[test]
+    fn layout_remap() {
+        let display = |origin, size, is_main| Display { origin, size, scale: 1.0, is_main };
+        let old = [
+            display((0.0, 0.0), (1000.0, 800.0), true),
+            display((1000.0, 0.0), (500.0, 500.0), false),
+        ];
+        let new = [
+            display((0.0, 0.0), (2000.0, 1600.0), true),
+            display((-1000.0, 0.0), (1000.0, 800.0), false),
+        ];
+
+        assert_eq!(remap(&old, &new, (500.0, 400.0)), (1000.0, 800.0));
+        assert_eq!(remap(&old, &new, (1250.0, 100.0)), (-500.0, 160.0));
+        assert_eq!(remap(&old, &new[..1], (1250.0, 100.0)), (1000.0, 320.0));
+        assert_eq!(remap(&old, &new, (-5.0, 0.0)), (-5.0, 0.0));
+
+        let empty = [display((0.0, 0.0), (0.0, 0.0), true), display((0.0, 0.0), (0.0, 800.0), false)];
+        assert_eq!(remap(&empty, &new, (0.0, 0.0)), (0.0, 0.0));
+        assert_eq!(remap(&old, &empty, (500.0, 400.0)), (500.0, 400.0));
+        assert_eq!(remap(&old, &[new[0], empty[1]], (1250.0, 100.0)), (1000.0, 320.0));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = "# comment\n\n0.5 key-down a\n1.0 key-down nope\n"
+            .parse::<Recording>()
+            .unwrap_err();
+        assert_eq!(err.line(), 4);
+        assert!("1.0 move 1".parse::<Recording>().is_err());
+        assert!("1.0 move 1 2 3".parse::<Recording>().is_err());
+        assert!("-1 key-up a".parse::<Recording>().is_err());
+        assert!("1e300 key-up a".parse::<Recording>().is_err());
+        assert!("NaN key-up a".parse::<Recording>().is_err());
+        assert!("1.0 pan sideways 1 2 3 4".parse::<Recording>().is_err());
+        assert!("1.0 char U+D800".parse::<Recording>().is_err());
+        assert!("1.0 char e".parse::<Recording>().is_err());
+        assert_eq!(migrate("0.5 key-down a", VERSION + 1).unwrap_err().line(), 1);
+    }
+
+    #[test]
+    fn migration_round_trip() {
+        const RENAMES: &[Migration] = &[Migration {
+            from: 1,
+            keys: &[("enter", "return")],
+            buttons: &[("back", "other-3")],
+        }];
+        let old = "# auto recording v1\n0.5 key-down enter\n0.75 button-up back 1 2\n";
+
+        let recording = migrate_with(old, 1, RENAMES).unwrap();
+        assert_eq!(recording.events[0].event, InputEvent::KeyDown(Key::Return));
+        assert_eq!(recording.events[1].event, InputEvent::ButtonUp(Button::Other(3), (1.0, 2.0)));
+
+        let text = recording.to_string();
+        assert_eq!(migrate_with(&text, VERSION, &[]), Ok(recording));
+        assert!(old.parse::<Recording>().is_err());
+
+        let v1 = "# auto recording v1\n0.5 button-down other-3 1 2\n0.75 button-up other-4 1 2\n";
+        let recording = v1.parse::<Recording>().unwrap();
+        assert_eq!(recording.events[0].event, InputEvent::ButtonDown(Button::Back, (1.0, 2.0)));
+        assert_eq!(recording.events[1].event, InputEvent::ButtonUp(Button::Forward, (1.0, 2.0)));
+    }
+}
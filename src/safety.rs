@@ -0,0 +1,416 @@
+//! 🛡️ Guard rails for unattended automation.
+//!
+//! Installing a [`DenyList`](struct.DenyList.html) with
+//! [`deny`](fn.deny.html) makes [`events::post`](../events/fn.post.html)
+//! refuse to press key chords that could end the user's session or close
+//! important applications. A chord can still be pressed deliberately through
+//! [`events::post_allowing`](../events/fn.post_allowing.html).
+//!
+//! The deny list only applies to events synthesized through the
+//! [`events`](../events/index.html) module and the modules built on it, such
+//! as [`record`](../record/index.html). Events posted through the `os` module
+//! are not checked.
+//!
+//! # Examples
+//!
+//! ```
+//! use auto::keyboard::{Key, Modifiers};
+//! use auto::safety::{self, Chord, DenyList};
+//!
+//! safety::deny(DenyList::recommended().with(Chord::new(Modifiers::META, Key::W)));
+//! ```
+//!
+//! # Rate Limiting
+//!
+//! A [`RateLimit`](struct.RateLimit.html) installed with
+//! [`limit_rate`](fn.limit_rate.html) caps how fast events can be posted, so
+//! that a runaway loop cannot flood the system's event queue and leave the
+//! user unable to take back control. Posting an event over the limit blocks
+//! until the rate allows it, or fails with `Error::Aborted` if an
+//! [abort](../abort/index.html) is triggered meanwhile. Each character typed by
+//! [`keyboard::type_text`](../keyboard/fn.type_text.html) counts as one event.
+//!
+//! ```
+//! use std::time::Duration;
+//! use auto::safety::{self, RateLimit};
+//!
+//! safety::limit_rate(RateLimit::new(200, Duration::from_secs(1)));
+//! ```
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use abort::Token;
+use error::{Error, Result};
+use events::InputEvent;
+use keyboard::{Key, Modifiers};
+use session;
+
+lazy_static! {
+    static ref STATE: Mutex<State> = Mutex::default();
+
+    static ref BUCKET: Mutex<Option<Bucket>> = Mutex::default();
+}
+
+#[derive(Default)]
+struct State {
+    list: Option<DenyList>,
+    held: Modifiers,
+}
+
+/// A key pressed while holding a set of modifiers, optionally only while a
+/// particular application is frontmost.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Chord {
+    modifiers: Modifiers,
+    key: Key,
+    app: Option<String>,
+}
+
+impl Chord {
+    /// Creates a chord of `key` pressed while holding `modifiers`.
+    ///
+    /// The chord also matches when additional modifiers are held.
+    #[inline]
+    pub fn new(modifiers: Modifiers, key: Key) -> Chord {
+        Chord { modifiers, key, app: None }
+    }
+
+    /// Restricts the chord to when the application identified by `app` is
+    /// frontmost.
+    ///
+    /// On macOS, `app` is a bundle identifier such as `com.apple.finder`. If
+    /// the frontmost application cannot be determined, the chord matches
+    /// regardless of `app`.
+    pub fn in_app<S: Into<String>>(mut self, app: S) -> Chord {
+        self.app = Some(app.into());
+        self
+    }
+
+    /// Returns the modifiers of the chord.
+    #[inline]
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+    /// Returns the key of the chord.
+    #[inline]
+    pub fn key(&self) -> Key {
+        self.key
+    }
+
+    /// Returns the application the chord is restricted to, if any.
+    #[inline]
+    pub fn app(&self) -> Option<&str> {
+        self.app.as_deref()
+    }
+
+    fn matches(&self, held: Modifiers, key: Key, frontmost: Option<&str>) -> bool {
+        let app_matches = match (self.app(), frontmost) {
+            (Some(app), Some(frontmost)) => app == frontmost,
+            _ => true,
+        };
+        self.key == key && held.contains(self.modifiers) && app_matches
+    }
+}
+
+/// A set of key chords that must not be synthesized.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DenyList {
+    chords: Vec<Chord>,
+}
+
+impl DenyList {
+    /// Creates an empty deny list.
+    #[inline]
+    pub fn new() -> DenyList {
+        DenyList::default()
+    }
+
+    /// Creates a deny list of chords that lock or end the user's session or
+    /// quit the Finder:
+    ///
+    /// - Command+Q while the Finder is frontmost
+    ///
+    /// - Control+Alt+Delete
+    ///
+    /// - Windows+L, on Windows, where Command+L and Super+L are ordinary
+    ///   shortcuts elsewhere
+    ///
+    /// - Control+Command+Q and Shift+Command+Q
+    pub fn recommended() -> DenyList {
+        let list = DenyList::new()
+            .with(Chord::new(Modifiers::META, Key::Q).in_app("com.apple.finder"))
+            .with(Chord::new(Modifiers::CONTROL | Modifiers::META, Key::Q))
+            .with(Chord::new(Modifiers::SHIFT | Modifiers::META, Key::Q))
+            .with(Chord::new(Modifiers::CONTROL | Modifiers::ALT, Key::Delete));
+        #[cfg(target_os = "windows")]
+        let list = list.with(Chord::new(Modifiers::META, Key::L));
+        list
+    }
+
+    /// Adds `chord` to the list, returning the list.
+    pub fn with(mut self, chord: Chord) -> DenyList {
+        self.push(chord);
+        self
+    }
+
+    /// Adds `chord` to the list.
+    pub fn push(&mut self, chord: Chord) {
+        if !self.chords.contains(&chord) {
+            self.chords.push(chord);
+        }
+    }
+
+    /// Returns the chords in the list.
+    #[inline]
+    pub fn chords(&self) -> &[Chord] {
+        &self.chords
+    }
+
+    /// Returns the first chord in the list matched by pressing `key` while
+    /// holding `held` with `frontmost` as the frontmost application.
+    pub fn find(&self, held: Modifiers, key: Key, frontmost: Option<&str>) -> Option<&Chord> {
+        self.chords.iter().find(|c| c.matches(held, key, frontmost))
+    }
+}
+
+/// Installs `list` as the process-wide deny list, replacing any previous one.
+pub fn deny(list: DenyList) {
+    lock().list = Some(list);
+}
+
+/// Removes the process-wide deny list, allowing all chords again.
+pub fn clear() {
+    lock().list = None;
+}
+
+/// Returns a copy of the process-wide deny list, if one is installed.
+pub fn current() -> Option<DenyList> {
+    lock().list.clone()
+}
+
+/// A maximum rate at which events can be posted.
+///
+/// Up to `events` events can be posted in a burst, after which events are
+/// spaced out evenly so that no more than `events` are posted per `per`.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct RateLimit {
+    events: u32,
+    per: Duration,
+}
+
+impl RateLimit {
+    /// Creates a limit of `events` events per `per`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `events` or `per` is zero.
+    pub fn new(events: u32, per: Duration) -> RateLimit {
+        assert!(events > 0 && per > Duration::from_secs(0), "rate limit must be nonzero");
+        RateLimit { events, per }
+    }
+
+    /// Returns a limit that is well above the rate of any human, but low
+    /// enough that the system stays responsive.
+    #[inline]
+    pub fn recommended() -> RateLimit {
+        RateLimit::new(500, Duration::from_secs(1))
+    }
+
+    /// Returns the number of events allowed per [`per`](#method.per).
+    #[inline]
+    pub fn events(&self) -> u32 {
+        self.events
+    }
+
+    /// Returns the period over which [`events`](#method.events) are allowed.
+    #[inline]
+    pub fn per(&self) -> Duration {
+        self.per
+    }
+}
+
+/// A token bucket that refills at the rate of a limit.
+pub(crate) struct Bucket {
+    limit: RateLimit,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    pub(crate) fn new(limit: RateLimit, now: Instant) -> Bucket {
+        Bucket { limit, tokens: limit.events as f64, updated: now }
+    }
+
+    /// Takes a token, returning how long to wait before using it.
+    ///
+    /// Tokens can be taken ahead of time, so waiting callers are served in
+    /// the order they arrived.
+    pub(crate) fn take(&mut self, now: Instant) -> Duration {
+        let capacity = self.limit.events as f64;
+        let rate = capacity / self.limit.per.as_secs_f64();
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(capacity) - 1.0;
+        self.updated = now;
+        if self.tokens >= 0.0 {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate)
+        }
+    }
+
+    /// Returns the limit that the bucket refills at.
+    #[inline]
+    pub(crate) fn limit(&self) -> RateLimit {
+        self.limit
+    }
+}
+
+/// Installs `limit` as the process-wide rate limit, replacing any previous
+/// one.
+pub fn limit_rate(limit: RateLimit) {
+    *lock_bucket() = Some(Bucket::new(limit, Instant::now()));
+}
+
+/// Removes the process-wide rate limit.
+pub fn clear_rate_limit() {
+    *lock_bucket() = None;
+}
+
+/// Returns the process-wide rate limit, if one is installed.
+pub fn rate_limit() -> Option<RateLimit> {
+    lock_bucket().as_ref().map(Bucket::limit)
+}
+
+fn lock_bucket() -> ::std::sync::MutexGuard<'static, Option<Bucket>> {
+    BUCKET.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Blocks until the installed rate limit allows another event to be posted,
+/// failing with `Error::Aborted` if an [abort](../abort/index.html) is
+/// triggered while waiting.
+///
+/// The rate limit of the current [session](../session/index.html), if it
+/// has one, applies instead of the process-wide one.
+pub(crate) fn throttle() -> Result<()> {
+    let wait = match session::take_rate_token() {
+        Some(wait) => wait,
+        None => match *lock_bucket() {
+            Some(ref mut bucket) => bucket.take(Instant::now()),
+            None => return Ok(()),
+        },
+    };
+    if wait > Duration::from_secs(0) {
+        Token::new().sleep(wait)?;
+    }
+    Ok(())
+}
+
+fn lock() -> ::std::sync::MutexGuard<'static, State> {
+    STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Checks `event` against the installed deny list before it is posted,
+/// ignoring chords in `allowed`.
+pub(crate) fn check(event: &InputEvent, allowed: &[Chord]) -> Result<()> {
+    let mut state = lock();
+    let key = match *event {
+        InputEvent::KeyDown(key) => {
+            if let Some(modifier) = key.modifier() {
+                state.held.insert(modifier);
+                return Ok(());
+            }
+            key
+        },
+        InputEvent::KeyUp(key) => {
+            if let Some(modifier) = key.modifier() {
+                state.held.remove(modifier);
+            }
+            return Ok(());
+        },
+        // Held modifiers turn a typed character into the shortcut of the key
+        // that types it
+        InputEvent::Char(c) => match c.to_lowercase().to_string().parse() {
+            Ok(key) => key,
+            Err(_) => return Ok(()),
+        },
+        _ => return Ok(()),
+    };
+    if let Some(ref list) = state.list {
+        let frontmost = imp::frontmost_app();
+        let denied = list.chords.iter().any(|chord| {
+            !allowed.contains(chord)
+                && chord.matches(state.held, key, frontmost.as_deref())
+        });
+        if denied {
+            return Err(Error::Denied);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use os::app::App;
+
+    pub fn frontmost_app() -> Option<String> {
+        App::frontmost().and_then(|app| app.bundle_identifier())
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod imp {
+    pub fn frontmost_app() -> Option<String> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chord_matching() {
+        let list = DenyList::recommended();
+        let finder = Some("com.apple.finder");
+
+        assert!(list.find(Modifiers::META, Key::Q, finder).is_some());
+        assert!(list.find(Modifiers::META, Key::Q, Some("com.apple.Safari")).is_none());
+        assert!(list.find(Modifiers::META, Key::Q, None).is_some());
+        #[cfg(target_os = "windows")]
+        assert!(list.find(Modifiers::META | Modifiers::SHIFT, Key::L, None).is_some());
+        #[cfg(not(target_os = "windows"))]
+        assert!(list.find(Modifiers::META, Key::L, None).is_none());
+        assert!(list.find(Modifiers::CONTROL, Key::Delete, None).is_none());
+        assert!(list.find(Modifiers::empty(), Key::L, None).is_none());
+    }
+
+    #[test]
+    fn typed_chars_checked() {
+        deny(DenyList::new().with(Chord::new(Modifiers::CONTROL, Key::W)));
+        let typed = check(&InputEvent::Char('W'), &[]);
+        check(&InputEvent::KeyDown(Key::Control), &[]).unwrap();
+        let held = check(&InputEvent::Char('W'), &[]);
+        let other = check(&InputEvent::Char('é'), &[]);
+        check(&InputEvent::KeyUp(Key::Control), &[]).unwrap();
+        clear();
+
+        assert_eq!(typed, Ok(()));
+        assert_eq!(held, Err(Error::Denied));
+        assert_eq!(other, Ok(()));
+    }
+
+    #[test]
+    fn bucket_spacing() {
+        let start = Instant::now();
+        let mut bucket = Bucket::new(RateLimit::new(2, Duration::from_secs(1)), start);
+        let zero = Duration::from_secs(0);
+
+        assert_eq!(bucket.take(start), zero);
+        assert_eq!(bucket.take(start), zero);
+        assert!((bucket.take(start).as_secs_f64() - 0.5).abs() < 1e-6);
+        assert!((bucket.take(start).as_secs_f64() - 1.0).abs() < 1e-6);
+        assert_eq!(bucket.take(start + Duration::from_secs(5)), zero);
+    }
+}
@@ -0,0 +1,622 @@
+//! ⏰ Running automation on a schedule.
+//!
+//! A [`Scheduler`](struct.Scheduler.html) runs jobs, either closures or
+//! [scripts](../script/index.html), on a background thread at fixed intervals
+//! or at the times given by cron expressions. Each run can be delayed by a
+//! random amount of jitter so that it does not happen at exactly the same
+//! moment every time.
+//!
+//! Jobs that run unattended should be paired with a
+//! [rate limit](../safety/struct.RateLimit.html), so that a job stuck in a
+//! loop cannot flood the system with input.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use auto::keyboard::Key;
+//! use auto::safety::{self, RateLimit};
+//! use auto::schedule::{Schedule, Scheduler};
+//! use auto::script::Script;
+//!
+//! safety::limit_rate(RateLimit::recommended());
+//!
+//! let scheduler = Scheduler::new();
+//!
+//! // Nudge the session every five minutes, give or take thirty seconds
+//! let every = Schedule::every(Duration::from_secs(300)).jitter(Duration::from_secs(30));
+//! scheduler.add(every, || auto::keyboard::send_shortcut(&[Key::Shift]));
+//!
+//! // Save at the half hour on weekdays, in UTC
+//! let mut save = Script::new();
+//! save.shortcut(&[Key::Meta, Key::S]);
+//! scheduler.add_script(Schedule::cron("30 * * * 1-5").unwrap(), save);
+//! ```
+//!
+//! # Cron Expressions
+//!
+//! Cron expressions have five fields: minute (0–59), hour (0–23), day of the
+//! month (1–31), month (1–12), and day of the week (0–7, where both 0 and 7
+//! are Sunday). Each field is `*` or a comma-separated list of values, ranges
+//! such as `1-5`, and steps such as `*/15` or `0-30/10`. As in standard cron,
+//! a time matches if either day field matches when both are restricted.
+//!
+//! The shorthands `@hourly`, `@daily`, `@weekly`, `@monthly`, and `@yearly`
+//! are also accepted. Times are in UTC.
+//!
+//! Cron schedules follow the system clock, so their jobs run at the matching
+//! times even after the clock is adjusted or the machine wakes from sleep.
+//! Interval schedules follow the monotonic clock instead, so setting the
+//! clock back or forward doesn't stall or rush them.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::ops::Add;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use dispatch;
+use error::{Error, Result};
+use script::Script;
+
+type Task = Box<dyn FnMut() -> Result<()> + Send>;
+
+/// The longest the scheduler sleeps before checking the system clock again,
+/// in case it was changed.
+const MAX_WAIT: Duration = Duration::from_secs(60);
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The current time on both clocks that schedules follow.
+#[derive(Copy, Clone, Debug)]
+struct Now {
+    instant: Instant,
+    system: SystemTime,
+}
+
+impl Now {
+    fn get() -> Now {
+        Now { instant: Instant::now(), system: SystemTime::now() }
+    }
+}
+
+/// A time on the clock that a schedule follows.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Time {
+    Monotonic(Instant),
+    System(SystemTime),
+}
+
+impl Time {
+    /// Returns how long after `now` the time is, or zero if it has passed.
+    fn until(self, now: Now) -> Duration {
+        match self {
+            Time::Monotonic(time) => time.saturating_duration_since(now.instant),
+            Time::System(time) => time.duration_since(now.system).unwrap_or_default(),
+        }
+    }
+}
+
+impl Add<Duration> for Time {
+    type Output = Time;
+
+    fn add(self, duration: Duration) -> Time {
+        match self {
+            Time::Monotonic(time) => Time::Monotonic(time + duration),
+            Time::System(time) => Time::System(time + duration),
+        }
+    }
+}
+
+/// When a job runs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Schedule {
+    kind: Kind,
+    jitter: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Kind {
+    Every(Duration),
+    Cron(Cron),
+}
+
+impl Schedule {
+    /// Returns a schedule that runs every `interval`, starting one interval
+    /// after the job is added.
+    ///
+    /// Runs that are missed, such as while a previous run takes longer than
+    /// `interval`, are skipped rather than made up for.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    pub fn every(interval: Duration) -> Schedule {
+        assert!(interval > Duration::from_secs(0), "schedule interval must be nonzero");
+        Schedule { kind: Kind::Every(interval), jitter: Duration::from_secs(0) }
+    }
+
+    /// Returns a schedule that runs at the times matched by the cron
+    /// expression `expr`.
+    ///
+    /// See the [module documentation](index.html#cron-expressions) for the
+    /// syntax.
+    pub fn cron(expr: &str) -> ::std::result::Result<Schedule, ParseCronError> {
+        let cron = expr.parse()?;
+        Ok(Schedule { kind: Kind::Cron(cron), jitter: Duration::from_secs(0) })
+    }
+
+    /// Delays each run by a random duration of up to `jitter`.
+    #[inline]
+    pub fn jitter(mut self, jitter: Duration) -> Schedule {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Returns the time of the run after one scheduled at `previous`, before
+    /// jitter, or `None` if there are no more runs.
+    fn next(&self, previous: Option<Time>, now: Now) -> Option<Time> {
+        match self.kind {
+            Kind::Every(interval) => Some(Time::Monotonic(match previous {
+                Some(Time::Monotonic(previous)) if previous + interval > now.instant => previous + interval,
+                _ => now.instant + interval,
+            })),
+            Kind::Cron(ref cron) => cron.next_after(now.system).map(Time::System),
+        }
+    }
+}
+
+/// A parsed cron expression.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    /// Returns the first matching time strictly after `time`, or `None` if
+    /// there is none within the next few years, such as for February 30th.
+    pub fn next_after(&self, time: SystemTime) -> Option<SystemTime> {
+        const DAY: i64 = 86_400;
+        const LIMIT: i64 = 5 * 366 * DAY;
+
+        let start = time.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+        let mut t = (start / 60 + 1) * 60;
+        while t - start < LIMIT {
+            let days = t / DAY;
+            let (year, month, day) = civil_from_days(days);
+            let hour = (t % DAY) / 3600;
+            let minute = (t % 3600) / 60;
+            let weekday = (days + 4) % 7;
+
+            if !has(self.months, month) {
+                let (year, month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+                t = days_from_civil(year, month, 1) * DAY;
+            } else if !self.matches_day(day, weekday) {
+                t = (days + 1) * DAY;
+            } else if !has(self.hours, hour) {
+                t = days * DAY + (hour + 1) * 3600;
+            } else if !has(self.minutes, minute) {
+                t += 60;
+            } else {
+                return Some(UNIX_EPOCH + Duration::from_secs(t as u64));
+            }
+        }
+        None
+    }
+
+    fn matches_day(&self, day: i64, weekday: i64) -> bool {
+        let day = has(self.days, day);
+        let weekday = has(self.weekdays, weekday);
+        if self.any_day || self.any_weekday {
+            day && weekday
+        } else {
+            day || weekday
+        }
+    }
+}
+
+fn has(set: u64, value: i64) -> bool {
+    set & (1 << value) != 0
+}
+
+/// Returns the year, month, and day of a number of days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Returns the number of days since 1970-01-01 of a date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// An error returned when parsing a cron expression fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseCronError {
+    message: String,
+}
+
+impl fmt::Display for ParseCronError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid cron expression: {}", self.message)
+    }
+}
+
+impl ::std::error::Error for ParseCronError {}
+
+impl FromStr for Cron {
+    type Err = ParseCronError;
+
+    fn from_str(s: &str) -> ::std::result::Result<Cron, ParseCronError> {
+        let s = match s.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" => "0 0 1 1 *",
+            s => s,
+        };
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        if fields.len() != 5 {
+            let message = format!("expected 5 fields, found {}", fields.len());
+            return Err(ParseCronError { message });
+        }
+        let parse = |field, min, max| {
+            parse_field(field, min, max).map_err(|message| ParseCronError { message })
+        };
+
+        let mut weekdays = parse(fields[4], 0, 7)?;
+        if has(weekdays, 7) {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Cron {
+            minutes: parse(fields[0], 0, 59)?,
+            hours: parse(fields[1], 0, 23)?,
+            days: parse(fields[2], 1, 31)?,
+            months: parse(fields[3], 1, 12)?,
+            weekdays,
+            any_day: fields[2].starts_with('*'),
+            any_weekday: fields[4].starts_with('*'),
+        })
+    }
+}
+
+/// Parses a cron field into a set of values between `min` and `max`.
+fn parse_field(field: &str, min: i64, max: i64) -> ::std::result::Result<u64, String> {
+    let value = |s: &str| match s.parse::<i64>() {
+        Ok(n) if n >= min && n <= max => Ok(n),
+        _ => Err(format!("{:?} is not between {} and {}", s, min, max)),
+    };
+
+    let mut set = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.find('/') {
+            Some(i) => match part[i + 1..].parse::<i64>() {
+                Ok(step) if step > 0 => (&part[..i], step),
+                _ => return Err(format!("invalid step in {:?}", part)),
+            },
+            None => (part, 1),
+        };
+        let (start, end) = match range.find('-') {
+            _ if range == "*" => (min, max),
+            Some(i) => (value(&range[..i])?, value(&range[i + 1..])?),
+            // A single value with a step continues to the end of the range
+            None if step > 1 => (value(range)?, max),
+            None => {
+                let n = value(range)?;
+                (n, n)
+            },
+        };
+        if start > end {
+            return Err(format!("empty range {:?}", range));
+        }
+        let mut n = start;
+        while n <= end {
+            set |= 1 << n;
+            n += step;
+        }
+    }
+    Ok(set)
+}
+
+/// Runs jobs on a background thread according to their
+/// [`Schedule`](struct.Schedule.html)s.
+///
+/// Jobs run one at a time, so a slow job delays the others. Dropping the
+/// scheduler cancels all of its jobs, waiting for a running job to finish.
+pub struct Scheduler {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    entries: Vec<Entry>,
+    next_id: u64,
+    stopped: bool,
+}
+
+struct Entry {
+    id: u64,
+    schedule: Schedule,
+    /// The time of the next run before jitter.
+    base: Time,
+    due: Time,
+    /// The job, or `None` while it runs.
+    task: Option<Task>,
+    status: Arc<Mutex<Status>>,
+}
+
+#[derive(Default)]
+struct Status {
+    runs: u64,
+    last_error: Option<Error>,
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let jobs = lock(&self.shared.state).entries.len();
+        f.debug_struct("Scheduler").field("jobs", &jobs).finish()
+    }
+}
+
+impl Default for Scheduler {
+    #[inline]
+    fn default() -> Scheduler {
+        Scheduler::new()
+    }
+}
+
+impl Scheduler {
+    /// Creates a scheduler with no jobs, along with the thread they run on.
+    pub fn new() -> Scheduler {
+        let shared = Arc::new(Shared { state: Mutex::default(), changed: Condvar::new() });
+        let thread = {
+            let shared = shared.clone();
+            thread::Builder::new()
+                .name("auto-scheduler".into())
+                .spawn(move || run(&shared))
+                .expect("failed to spawn scheduler thread")
+        };
+        Scheduler { shared, thread: Some(thread) }
+    }
+
+    /// Adds a job that calls `task` according to `schedule`.
+    ///
+    /// Errors returned by `task` do not stop the job; the most recent one is
+    /// available from [`Job::last_error`](struct.Job.html#method.last_error).
+    /// A job whose schedule has no more runs is removed.
+    pub fn add<F>(&self, schedule: Schedule, task: F) -> Job
+        where F: FnMut() -> Result<()> + Send + 'static
+    {
+        let status = Arc::new(Mutex::default());
+        let mut state = lock(&self.shared.state);
+        let id = state.next_id;
+        state.next_id += 1;
+
+        if let Some(base) = schedule.next(None, Now::get()) {
+            let due = base + dispatch::random_between(Duration::from_secs(0), schedule.jitter);
+            state.entries.push(Entry {
+                id,
+                schedule,
+                base,
+                due,
+                task: Some(Box::new(task)),
+                status: status.clone(),
+            });
+            self.shared.changed.notify_all();
+        }
+        Job { id, shared: self.shared.clone(), status }
+    }
+
+    /// Adds a job that runs `script` according to `schedule`.
+    pub fn add_script(&self, schedule: Schedule, script: Script) -> Job {
+        self.add(schedule, move || script.run())
+    }
+
+    /// Returns the number of jobs that have runs remaining.
+    pub fn len(&self) -> usize {
+        lock(&self.shared.state).entries.len()
+    }
+
+    /// Returns whether there are no jobs with runs remaining.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        {
+            let mut state = lock(&self.shared.state);
+            state.stopped = true;
+            state.entries.clear();
+        }
+        self.shared.changed.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Runs due jobs until the scheduler is dropped.
+fn run(shared: &Shared) {
+    let mut state = lock(&shared.state);
+    while !state.stopped {
+        let now = Now::get();
+        let next = state.entries.iter()
+            .enumerate()
+            .map(|(index, entry)| (index, entry.due.until(now)))
+            .min_by_key(|&(_, wait)| wait);
+        let index = match next {
+            Some((index, wait)) if wait == Duration::from_secs(0) => index,
+            Some((_, wait)) => {
+                let wait = wait.min(MAX_WAIT);
+                state = shared.changed
+                    .wait_timeout(state, wait)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0;
+                continue;
+            },
+            None => {
+                state = shared.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+                continue;
+            },
+        };
+
+        let (id, mut task, status) = {
+            let entry = &mut state.entries[index];
+            match entry.task.take() {
+                Some(task) => (entry.id, task, entry.status.clone()),
+                None => continue,
+            }
+        };
+        drop(state);
+
+        let result = task();
+        {
+            let mut status = lock(&status);
+            status.runs += 1;
+            if let Err(err) = result {
+                status.last_error = Some(err);
+            }
+        }
+
+        state = lock(&shared.state);
+        let now = Now::get();
+        if let Some(index) = state.entries.iter().position(|entry| entry.id == id) {
+            let entry = &mut state.entries[index];
+            match entry.schedule.next(Some(entry.base), now) {
+                Some(base) => {
+                    let jitter = dispatch::random_between(Duration::from_secs(0), entry.schedule.jitter);
+                    entry.base = base;
+                    entry.due = base + jitter;
+                    entry.task = Some(task);
+                },
+                None => {
+                    state.entries.remove(index);
+                },
+            }
+        }
+    }
+}
+
+/// A handle to a job added to a [`Scheduler`](struct.Scheduler.html).
+///
+/// Dropping the handle does not cancel the job.
+pub struct Job {
+    id: u64,
+    shared: Arc<Shared>,
+    status: Arc<Mutex<Status>>,
+}
+
+impl fmt::Debug for Job {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Job").field("id", &self.id).field("runs", &self.runs()).finish()
+    }
+}
+
+impl Job {
+    /// Removes the job from its scheduler.
+    ///
+    /// A run that is in progress is allowed to finish.
+    pub fn cancel(&self) {
+        lock(&self.shared.state).entries.retain(|entry| entry.id != self.id);
+        self.shared.changed.notify_all();
+    }
+
+    /// Returns whether the job has runs remaining.
+    pub fn is_scheduled(&self) -> bool {
+        lock(&self.shared.state).entries.iter().any(|entry| entry.id == self.id)
+    }
+
+    /// Returns the number of times the job has run.
+    pub fn runs(&self) -> u64 {
+        lock(&self.status).runs
+    }
+
+    /// Returns the error from the most recent run that failed, if any.
+    pub fn last_error(&self) -> Option<Error> {
+        lock(&self.status).last_error.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cron_next_after() {
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+
+        // Saturday 2026-10-17 12:00 to Monday 2026-10-19 09:30
+        let cron: Cron = "30 9 * * 1-5".parse().unwrap();
+        assert_eq!(cron.next_after(at(1_792_238_400)), Some(at(1_792_402_200)));
+
+        // The next February 29th, from 2026
+        let cron: Cron = "0 0 29 2 *".parse().unwrap();
+        assert_eq!(cron.next_after(at(1_792_238_400)), Some(at(1_835_395_200)));
+
+        let cron: Cron = "*/15 * * * *".parse().unwrap();
+        assert_eq!(cron.next_after(at(1_792_238_400)), Some(at(1_792_238_400 + 900)));
+        assert_eq!("@daily".parse::<Cron>(), "0 0 * * *".parse::<Cron>());
+
+        assert!("0 0 30 2 *".parse::<Cron>().unwrap().next_after(at(0)).is_none());
+        assert!("60 * * * *".parse::<Cron>().is_err());
+        assert!("* * *".parse::<Cron>().is_err());
+        assert!("5-1 * * * *".parse::<Cron>().is_err());
+    }
+
+    #[test]
+    fn schedule_next() {
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        let start = Instant::now();
+        let after = |secs| Time::Monotonic(start + Duration::from_secs(secs));
+        let now = Now { instant: start + Duration::from_secs(100), system: at(1_792_238_400) };
+
+        let every = Schedule::every(Duration::from_secs(10));
+        assert_eq!(every.next(None, now), Some(after(110)));
+        assert_eq!(every.next(Some(after(95)), now), Some(after(105)));
+        // Missed runs are skipped
+        assert_eq!(every.next(Some(after(0)), now), Some(after(110)));
+
+        // Setting the system clock back doesn't delay intervals
+        let set_back = Now { system: at(0), ..now };
+        assert_eq!(every.next(Some(after(95)), set_back), Some(after(105)));
+        assert_eq!(after(105).until(set_back), Duration::from_secs(5));
+
+        // Cron runs are at wall clock times, wherever the previous run was
+        let cron = Schedule::cron("*/15 * * * *").unwrap();
+        let next = cron.next(Some(Time::System(at(0))), now);
+        assert_eq!(next, Some(Time::System(at(1_792_238_400 + 900))));
+        assert_eq!(next.unwrap().until(set_back), Duration::from_secs(1_792_238_400 + 900));
+    }
+}
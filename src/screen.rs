@@ -0,0 +1,1442 @@
+//! 🖥️ Observing the contents of the screen on any operating system.
+//!
+//! Locations are in global screen coordinates, with the origin at the
+//! upper-left corner of the main display and the y-axis pointing down. These
+//! are the same units as event locations: points on macOS, which may each
+//! span several pixels, and pixels elsewhere.
+//!
+//! The coordinates span every display, so displays above or left of the main
+//! display have negative locations. On Windows, they are physical pixels on
+//! every monitor whatever its DPI, even if the process isn't DPI aware.
+//! [`Display::at_point`](struct.Display.html#method.at_point) finds the
+//! display that a location lies on.
+//!
+//! Captures are in pixels, so on a Retina display they are twice as large as
+//! the area they cover.
+//! [`Capture::location_of`](struct.Capture.html#method.location_of) and
+//! [`Display::from_pixels`](struct.Display.html#method.from_pixels) turn
+//! pixels back into locations for clicking.
+//!
+//! [`find_text`](fn.find_text.html) and [`click_text`](fn.click_text.html)
+//! recognize the text on the screen to find labels that have no accessible
+//! element, such as in games and remote desktops. Text recognition is only
+//! supported on macOS 10.15 or later.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use auto::screen;
+//!
+//! for display in screen::displays().unwrap() {
+//!     println!("{:?} at {}x scale", display.size, display.scale);
+//! }
+//!
+//! // Colors are looked up by location, whatever the pixel density
+//! let capture = screen::capture().unwrap();
+//! assert_eq!(capture.color_at((10.0, 10.0)), screen::color_at((10.0, 10.0)));
+//! ```
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use color::Rgb;
+use error::{Error, Result};
+use events::Location;
+use image::{ColorGrid, Image};
+use mouse::{self, Button};
+use settings;
+
+/// How long to wait between reads of the screen by default.
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Returns whether each channel of `a` is within `tolerance` of `b`.
+#[inline]
+fn within(a: Rgb, b: Rgb, tolerance: u8) -> bool {
+    let close = |x: u8, y: u8| (x as i16 - y as i16).abs() <= tolerance as i16;
+    close(a.red, b.red) && close(a.green, b.green) && close(a.blue, b.blue)
+}
+
+/// A display attached to the computer.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Display {
+    /// Coordinates of the upper-left corner.
+    pub origin: Location,
+    /// Width and height of the display.
+    pub size: (f64, f64),
+    /// The number of pixels along each unit of `size`, such as 2 on a Retina
+    /// display.
+    ///
+    /// This is always 1 on Windows, where locations are in pixels; see
+    /// [`scale_factor`](#method.scale_factor) for the scale in the display
+    /// settings.
+    pub scale: f64,
+    /// Whether the display is the main display, whose upper-left corner is
+    /// at (0,0).
+    pub is_main: bool,
+}
+
+impl Display {
+    /// Returns the display that `point` lies on, or `None` if it lies off
+    /// every display, such as in a gap between displays of different sizes.
+    pub fn at_point(point: Location) -> Result<Option<Display>> {
+        Ok(display_at(displays()?, point))
+    }
+
+    /// Returns the factor by which the operating system scales content on the
+    /// display, such as 2 on a Retina display or 1.5 on a monitor set to 150%.
+    ///
+    /// This is the `backingScaleFactor` on macOS, which equals
+    /// [`scale`](#structfield.scale), and the DPI of the monitor over 96 on
+    /// Windows. Returns 1 if the display can't be found.
+    #[inline]
+    pub fn scale_factor(&self) -> f64 {
+        imp::scale_factor(self)
+    }
+
+    /// Converts `location` in screen coordinates into pixel coordinates from
+    /// the upper-left corner of the display, as in a capture of the whole
+    /// display.
+    #[inline]
+    pub fn to_pixels(&self, (x, y): Location) -> (f64, f64) {
+        ((x - self.origin.0) * self.scale, (y - self.origin.1) * self.scale)
+    }
+
+    /// Converts pixel coordinates from the upper-left corner of the display
+    /// into a location in screen coordinates, such as for clicking a pixel
+    /// found in a capture of the display.
+    #[inline]
+    pub fn from_pixels(&self, (x, y): (f64, f64)) -> Location {
+        (self.origin.0 + x / self.scale, self.origin.1 + y / self.scale)
+    }
+
+    /// Returns the location at the center of the display.
+    #[inline]
+    pub fn center(&self) -> Location {
+        let ((x, y), (w, h)) = (self.origin, self.size);
+        (x + w / 2.0, y + h / 2.0)
+    }
+
+    /// Returns whether `pos` lies on the display.
+    #[inline]
+    pub fn contains(&self, (x, y): Location) -> bool {
+        let (ox, oy) = self.origin;
+        let (w, h) = self.size;
+        x >= ox && y >= oy && x < ox + w && y < oy + h
+    }
+
+    /// Returns the width and height of the display in pixels.
+    #[inline]
+    pub fn pixels(&self) -> (usize, usize) {
+        let (w, h) = self.size;
+        ((w * self.scale).round() as usize, (h * self.scale).round() as usize)
+    }
+}
+
+/// Returns the display among `displays` that `point` lies on.
+fn display_at(displays: Vec<Display>, point: Location) -> Option<Display> {
+    displays.into_iter().find(|d| d.contains(point))
+}
+
+/// Returns all active displays.
+#[inline]
+pub fn displays() -> Result<Vec<Display>> {
+    imp::displays()
+}
+
+/// Returns the main display.
+pub fn main_display() -> Result<Display> {
+    displays()?.into_iter().find(|d| d.is_main).ok_or(Error::Failed)
+}
+
+/// Returns the width and height of the main display.
+#[inline]
+pub fn size() -> Result<(f64, f64)> {
+    main_display().map(|d| d.size)
+}
+
+/// A change to a single display, as reported by
+/// [`topology_events`](fn.topology_events.html).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TopologyEvent {
+    /// A display became active.
+    Added(Display),
+    /// A display is no longer active, as it was before it was removed.
+    Removed(Display),
+    /// The origin of a display moved.
+    Moved {
+        /// The display after it moved.
+        display: Display,
+        /// The previous origin.
+        from: Location,
+        /// The new origin.
+        to: Location,
+    },
+    /// The size of a display changed.
+    Resized {
+        /// The display after it was resized.
+        display: Display,
+        /// The previous size.
+        from: (f64, f64),
+        /// The new size.
+        to: (f64, f64),
+    },
+}
+
+/// A coalesced set of display changes along with the resulting displays.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TopologyChange {
+    /// The changes since the previous layout.
+    pub events: Vec<TopologyEvent>,
+    /// The active displays after the changes.
+    pub displays: Vec<Display>,
+}
+
+/// A blocking iterator over coalesced display topology changes.
+///
+/// Created by [`topology_events`](fn.topology_events.html).
+pub struct TopologyEvents(imp::TopologyEvents);
+
+impl fmt::Debug for TopologyEvents {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TopologyEvents").finish()
+    }
+}
+
+impl Iterator for TopologyEvents {
+    type Item = TopologyChange;
+
+    #[inline]
+    fn next(&mut self) -> Option<TopologyChange> {
+        self.0.next()
+    }
+}
+
+impl TopologyEvents {
+    /// Returns the next change if one is available without blocking.
+    #[inline]
+    pub fn try_next(&mut self) -> Option<TopologyChange> {
+        self.0.try_next()
+    }
+}
+
+/// Returns a stream of changes to the displays, such as one being plugged in
+/// or moved in the display settings.
+///
+/// Changes are coalesced until none have arrived for `debounce`, and bursts
+/// that leave the layout unchanged produce no item.
+///
+/// Fails with `Error::Unsupported` on platforms other than macOS.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use auto::screen::{self, TopologyEvent};
+///
+/// for change in screen::topology_events(Duration::from_millis(250)).unwrap() {
+///     for event in change.events {
+///         if let TopologyEvent::Added(display) = event {
+///             println!("display added at {:?}", display.origin);
+///         }
+///     }
+/// }
+/// ```
+#[inline]
+pub fn topology_events(debounce: Duration) -> Result<TopologyEvents> {
+    imp::TopologyEvents::new(debounce).map(TopologyEvents)
+}
+
+/// An area of the screen.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bounds {
+    /// Coordinates of the upper-left corner.
+    pub origin: Location,
+    /// Width and height of the area.
+    pub size: (f64, f64),
+}
+
+impl Bounds {
+    /// Returns the location at the center of the area.
+    #[inline]
+    pub fn center(&self) -> Location {
+        let ((x, y), (w, h)) = (self.origin, self.size);
+        (x + w / 2.0, y + h / 2.0)
+    }
+
+    /// Returns whether `pos` lies within the area.
+    #[inline]
+    pub fn contains(&self, (x, y): Location) -> bool {
+        let ((ox, oy), (w, h)) = (self.origin, self.size);
+        x >= ox && y >= oy && x < ox + w && y < oy + h
+    }
+}
+
+/// Captures every display and returns the bounds of the first recognized
+/// text that contains `text`, ignoring case, or `None` if there is none.
+///
+/// When `text` is part of a longer line, such as a word in a sentence, the
+/// bounds only cover that part on macOS.
+///
+/// # Examples
+///
+/// ```no_run
+/// use auto::screen;
+///
+/// if let Some(bounds) = screen::find_text("Submit").unwrap() {
+///     println!("found at {:?}", bounds.center());
+/// }
+/// ```
+pub fn find_text(text: &str) -> Result<Option<Bounds>> {
+    for display in displays()? {
+        let capture = capture_rect(display.origin, display.size)?;
+        if let Some(bounds) = capture.find_text(text)? {
+            return Ok(Some(bounds));
+        }
+    }
+    Ok(None)
+}
+
+/// Clicks the center of the text found by [`find_text`](fn.find_text.html)
+/// with the left button, returning its bounds, or returns `None` without
+/// clicking if the text isn't on the screen.
+///
+/// # Examples
+///
+/// ```no_run
+/// use auto::screen;
+///
+/// if screen::click_text("Submit").unwrap().is_none() {
+///     println!("no submit button");
+/// }
+/// ```
+pub fn click_text(text: &str) -> Result<Option<Bounds>> {
+    settings::paced(|| {
+        let bounds = match find_text(text)? {
+            Some(bounds) => bounds,
+            None => return Ok(None),
+        };
+        mouse::move_to(bounds.center())?;
+        mouse::click(Button::Left)?;
+        Ok(Some(bounds))
+    })
+}
+
+/// Returns the color of the pixel at `pos`, or `None` if it can't be read.
+#[inline]
+pub fn color_at(pos: Location) -> Option<Rgb> {
+    imp::color_at(pos)
+}
+
+/// Reads the color of a pixel on the screen repeatedly, such as many times a
+/// second in an automation loop, reusing the resources that
+/// [`color_at`](fn.color_at.html) acquires for every read.
+///
+/// # Examples
+///
+/// ```no_run
+/// use auto::screen::Sampler;
+///
+/// let mut sampler = Sampler::new((120.0, 300.0)).unwrap();
+/// let start = sampler.sample();
+/// while sampler.sample() == start {}
+/// ```
+pub struct Sampler {
+    pos: Location,
+    inner: imp::Sampler,
+}
+
+impl fmt::Debug for Sampler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Sampler").field("pos", &self.pos).finish()
+    }
+}
+
+impl Sampler {
+    /// Creates a sampler for the pixel at `pos`.
+    pub fn new(pos: Location) -> Result<Sampler> {
+        Ok(Sampler { pos, inner: imp::Sampler::new(pos)? })
+    }
+
+    /// Returns the current color of the pixel, or `None` if it can't be read.
+    #[inline]
+    pub fn sample(&mut self) -> Option<Rgb> {
+        self.inner.sample(self.pos)
+    }
+
+    /// Returns the location of the pixel.
+    #[inline]
+    pub fn position(&self) -> Location {
+        self.pos
+    }
+
+    /// Moves the sampler to the pixel at `pos`.
+    #[inline]
+    pub fn set_position(&mut self, pos: Location) {
+        self.pos = pos;
+    }
+}
+
+/// Captures the contents of the main display.
+pub fn capture() -> Result<Capture> {
+    let main = main_display()?;
+    capture_rect(main.origin, main.size)
+}
+
+/// Captures the area of the screen of `size` starting at `origin`.
+///
+/// On macOS, the area is cut off at the edges of the display containing
+/// `origin`.
+pub fn capture_rect(origin: Location, size: (f64, f64)) -> Result<Capture> {
+    let (image, scale) = imp::capture_rect(origin, size)?;
+    Ok(Capture { image, origin, scale })
+}
+
+/// Reads the colors of the area of the screen of `size` starting at `origin`
+/// from a single capture, rather than one pixel at a time.
+///
+/// Locations in the grid are relative to `origin`. On macOS, the area is cut
+/// off at the edges of the display containing `origin`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use auto::color::Rgb;
+/// use auto::screen;
+///
+/// let grid = screen::colors_in_rect((100.0, 100.0), (100.0, 100.0)).unwrap();
+/// let red = Rgb { red: 255, green: 0, blue: 0 };
+/// let count = grid.iter().filter(|&(_, color)| color.matches(&red, 3.0)).count();
+/// println!("{} red pixels", count);
+/// ```
+pub fn colors_in_rect(origin: Location, size: (f64, f64)) -> Result<ColorGrid> {
+    let (image, scale) = imp::capture_rect(origin, size)?;
+    Ok(ColorGrid::new(image, scale))
+}
+
+/// An image of an area of the screen, along with where it was taken from.
+///
+/// The image is in pixels, so it may be larger than the captured area on
+/// high-resolution displays. Use [`color_at`](#method.color_at) to read it
+/// with the same locations as the rest of the screen.
+#[derive(Clone, Debug)]
+pub struct Capture {
+    image: Image,
+    origin: Location,
+    scale: f64,
+}
+
+impl Capture {
+    /// Returns the captured image.
+    #[inline]
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    /// Returns the captured image, consuming the capture.
+    #[inline]
+    pub fn into_image(self) -> Image {
+        self.image
+    }
+
+    /// Returns the location of the upper-left corner of the image.
+    #[inline]
+    pub fn origin(&self) -> Location {
+        self.origin
+    }
+
+    /// Returns the number of pixels along each unit of the screen.
+    #[inline]
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// Returns the width and height of the captured area.
+    #[inline]
+    pub fn size(&self) -> (f64, f64) {
+        (self.image.width() as f64 / self.scale, self.image.height() as f64 / self.scale)
+    }
+
+    /// Returns the pixel of the image at `pos`, or `None` if it wasn't
+    /// captured.
+    pub fn pixel_at(&self, (x, y): Location) -> Option<(usize, usize)> {
+        let px = ((x - self.origin.0) * self.scale).floor();
+        let py = ((y - self.origin.1) * self.scale).floor();
+        if !(px >= 0.0 && py >= 0.0) {
+            return None;
+        }
+        let (px, py) = (px as usize, py as usize);
+        if px < self.image.width() && py < self.image.height() {
+            Some((px, py))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the location of the pixel of the image at `(x, y)`, such as
+    /// for clicking a match found in the image.
+    ///
+    /// This is the inverse of [`pixel_at`](#method.pixel_at).
+    #[inline]
+    pub fn location_of(&self, (x, y): (usize, usize)) -> Location {
+        (self.origin.0 + x as f64 / self.scale, self.origin.1 + y as f64 / self.scale)
+    }
+
+    /// Returns the color at `pos`, or `None` if it wasn't captured.
+    #[inline]
+    pub fn color_at(&self, pos: Location) -> Option<Rgb> {
+        let (x, y) = self.pixel_at(pos)?;
+        self.image.pixel(x, y)
+    }
+
+    /// Returns the bounds on the screen of the first text recognized in the
+    /// capture that contains `text`, ignoring case, as with
+    /// [`find_text`](fn.find_text.html).
+    pub fn find_text(&self, text: &str) -> Result<Option<Bounds>> {
+        Ok(imp::find_text(&self.image, text)?.map(|((x, y), (w, h))| Bounds {
+            origin: (self.origin.0 + x / self.scale, self.origin.1 + y / self.scale),
+            size: (w / self.scale, h / self.scale),
+        }))
+    }
+}
+
+/// Waits up to `timeout` for the pixel at `pos` to match `color`, with each
+/// channel allowed to differ by up to `tolerance`.
+///
+/// Returns whether the color matched. See [`ColorWait`](struct.ColorWait.html)
+/// for details and for setting the polling interval.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use auto::color::Rgb;
+/// use auto::screen;
+///
+/// let green = Rgb { red: 40, green: 200, blue: 80 };
+/// if screen::wait_for_color((120.0, 300.0), green, 8, Duration::from_secs(5)) {
+///     println!("build passed");
+/// }
+/// ```
+#[inline]
+pub fn wait_for_color(pos: Location, color: Rgb, tolerance: u8, timeout: Duration) -> bool {
+    ColorWait::new(pos, color).tolerance(tolerance).timeout(timeout).wait()
+}
+
+/// Returns a future that resolves once the pixel at `pos` matches `color`, as
+/// with [`wait_for_color`](fn.wait_for_color.html).
+#[inline]
+pub fn wait_for_color_async(
+    pos: Location,
+    color: Rgb,
+    tolerance: u8,
+    timeout: Duration,
+) -> ColorFuture {
+    ColorWait::new(pos, color).tolerance(tolerance).timeout(timeout).wait_async()
+}
+
+/// Options for waiting until a pixel on the screen has a given color.
+///
+/// The wait ends early, as if it timed out, if the display showing the pixel
+/// goes to sleep, since it can no longer change. Sleep is only detected on
+/// macOS.
+#[derive(Copy, Clone, Debug)]
+pub struct ColorWait {
+    pos: Location,
+    color: Rgb,
+    tolerance: Tolerance,
+    timeout: Duration,
+    interval: Duration,
+}
+
+/// How far a color may be from the expected color and still match.
+#[derive(Copy, Clone, Debug)]
+enum Tolerance {
+    /// The most each channel may differ by.
+    Channel(u8),
+    /// The largest perceptual distance, as in `Rgb::distance`.
+    Distance(f64),
+}
+
+impl ColorWait {
+    /// Creates options for waiting until the pixel at `pos` exactly matches
+    /// `color`, for at most five seconds.
+    #[inline]
+    pub fn new(pos: Location, color: Rgb) -> ColorWait {
+        ColorWait {
+            pos,
+            color,
+            tolerance: Tolerance::Channel(0),
+            timeout: Duration::from_secs(5),
+            interval: DEFAULT_INTERVAL,
+        }
+    }
+
+    /// Sets how much each channel may differ from the expected color.
+    ///
+    /// This replaces any [`max_distance`](#method.max_distance).
+    pub fn tolerance(&mut self, tolerance: u8) -> &mut ColorWait {
+        self.tolerance = Tolerance::Channel(tolerance);
+        self
+    }
+
+    /// Sets how far the color may be from the expected color, as measured by
+    /// [`Rgb::distance`](../color/struct.Rgb.html#method.distance), rather
+    /// than by channel.
+    ///
+    /// This replaces any [`tolerance`](#method.tolerance).
+    pub fn max_distance(&mut self, distance: f64) -> &mut ColorWait {
+        self.tolerance = Tolerance::Distance(distance);
+        self
+    }
+
+    /// Sets how long to wait for the color to match.
+    pub fn timeout(&mut self, timeout: Duration) -> &mut ColorWait {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how long to wait between reads of the pixel, which defaults to
+    /// 50 milliseconds.
+    pub fn interval(&mut self, interval: Duration) -> &mut ColorWait {
+        self.interval = interval;
+        self
+    }
+
+    /// Blocks until the color matches, returning `false` if it did not match
+    /// in time.
+    #[inline]
+    pub fn wait(&self) -> bool {
+        self.poll_until(|| false)
+    }
+
+    /// Returns a future that resolves to whether the color matched in time.
+    ///
+    /// The pixel is read on a background thread, so the future may be used
+    /// with any executor. Dropping the future stops the thread.
+    ///
+    /// With the `tokio` feature, when called within a Tokio runtime, the
+    /// pixel is instead read whenever the future is polled, between waits on
+    /// the runtime's timer, and no thread is spawned.
+    pub fn wait_async(&self) -> ColorFuture {
+        #[cfg(feature = "tokio")]
+        {
+            if tokio::runtime::Handle::try_current().is_ok() {
+                let inner = Inner::Timer {
+                    wait: *self,
+                    deadline: Instant::now().checked_add(self.timeout),
+                    sleep: None,
+                };
+                return ColorFuture { inner };
+            }
+        }
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let wait = *self;
+        let state = shared.clone();
+        thread::Builder::new()
+            .name("auto-wait-for-color".into())
+            .spawn(move || {
+                let matched = wait.poll_until(|| lock(&state).cancelled);
+                let mut state = lock(&state);
+                state.result = Some(matched);
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+            })
+            .expect("failed to spawn color wait thread");
+        ColorFuture { inner: Inner::Thread(shared) }
+    }
+
+    /// Returns whether the color matches, or `None` if it does not yet match
+    /// and may still.
+    ///
+    /// A `deadline` of `None` is too far away to represent, and never comes.
+    fn check(&self, deadline: Option<Instant>, sampler: Option<&mut Sampler>) -> Option<bool> {
+        let color = match sampler {
+            Some(sampler) => sampler.sample(),
+            None => imp::color_at(self.pos),
+        };
+        if let Some(color) = color {
+            let matched = match self.tolerance {
+                Tolerance::Channel(tolerance) => within(color, self.color, tolerance),
+                Tolerance::Distance(distance) => color.matches(&self.color, distance),
+            };
+            if matched {
+                return Some(true);
+            }
+        }
+        let expired = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        if expired || imp::is_asleep(self.pos) {
+            return Some(false);
+        }
+        None
+    }
+
+    fn poll_until<F: Fn() -> bool>(&self, cancelled: F) -> bool {
+        let deadline = Instant::now().checked_add(self.timeout);
+        let mut sampler = Sampler::new(self.pos).ok();
+        loop {
+            if let Some(matched) = self.check(deadline, sampler.as_mut()) {
+                return matched;
+            }
+            if cancelled() {
+                return false;
+            }
+            let now = Instant::now();
+            thread::sleep(deadline.map_or(self.interval, |deadline| {
+                self.interval.min(deadline.saturating_duration_since(now))
+            }));
+        }
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    result: Option<bool>,
+    waker: Option<Waker>,
+    cancelled: bool,
+}
+
+fn lock(shared: &Mutex<Shared>) -> MutexGuard<'_, Shared> {
+    shared.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A future returned by [`ColorWait::wait_async`](struct.ColorWait.html#method.wait_async)
+/// that resolves to whether the color matched in time.
+pub struct ColorFuture {
+    inner: Inner,
+}
+
+enum Inner {
+    Thread(Arc<Mutex<Shared>>),
+    #[cfg(feature = "tokio")]
+    Timer {
+        wait: ColorWait,
+        deadline: Option<Instant>,
+        sleep: Option<Pin<Box<tokio::time::Sleep>>>,
+    },
+}
+
+impl ::std::fmt::Debug for ColorFuture {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        let mut f = f.debug_struct("ColorFuture");
+        match self.inner {
+            Inner::Thread(ref shared) => f.field("result", &lock(shared).result),
+            #[cfg(feature = "tokio")]
+            Inner::Timer { ref wait, .. } => f.field("wait", wait),
+        };
+        f.finish()
+    }
+}
+
+impl Future for ColorFuture {
+    type Output = bool;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<bool> {
+        match self.get_mut().inner {
+            Inner::Thread(ref shared) => {
+                let mut shared = lock(shared);
+                match shared.result {
+                    Some(matched) => Poll::Ready(matched),
+                    None => {
+                        shared.waker = Some(cx.waker().clone());
+                        Poll::Pending
+                    },
+                }
+            },
+            #[cfg(feature = "tokio")]
+            Inner::Timer { ref wait, deadline, ref mut sleep } => loop {
+                if let Some(ref mut sleep) = *sleep {
+                    if sleep.as_mut().poll(cx).is_pending() {
+                        return Poll::Pending;
+                    }
+                }
+                if let Some(matched) = wait.check(deadline, None) {
+                    return Poll::Ready(matched);
+                }
+                let next = Instant::now() + wait.interval;
+                let next = deadline.map_or(next, |deadline| next.min(deadline));
+                *sleep = Some(Box::pin(tokio::time::sleep_until(next.into())));
+            },
+        }
+    }
+}
+
+impl Drop for ColorFuture {
+    // Timer waits only exist with the `tokio` feature
+    #[allow(irrefutable_let_patterns)]
+    fn drop(&mut self) {
+        if let Inner::Thread(ref shared) = self.inner {
+            lock(shared).cancelled = true;
+        }
+    }
+}
+
+/// Options for recording an area of the screen as a stream of frames, such as
+/// to keep evidence of an automated test run.
+///
+/// Frames are captured on a background thread at a steady rate and passed to
+/// a callback or sent over a channel. A frame is skipped when capturing or
+/// handling the previous one takes longer than the time between frames.
+///
+/// On macOS and Windows, frames are taken from a stream that the system
+/// updates as the screen changes, rather than by capturing the screen for
+/// every frame. Recording falls back to capturing the screen while the stream
+/// is unavailable, such as on Windows when the area spans several monitors.
+///
+/// # Examples
+///
+/// ```no_run
+/// use auto::screen::Recorder;
+///
+/// let (recording, frames) = Recorder::main_display().unwrap().fps(10.0).channel().unwrap();
+/// for frame in frames.iter().take(50) {
+///     let image = frame.capture.image();
+///     println!("frame {} at {:?}: {}x{}", frame.number, frame.time, image.width(), image.height());
+/// }
+/// recording.stop();
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Recorder {
+    origin: Location,
+    size: (f64, f64),
+    period: Duration,
+}
+
+/// A frame captured by a [`Recorder`](struct.Recorder.html).
+#[derive(Clone, Debug)]
+pub struct Frame {
+    /// The contents of the recorded area.
+    pub capture: Capture,
+    /// The time since recording started at which the frame was captured.
+    pub time: Duration,
+    /// The number of frame periods since recording started, which skips
+    /// numbers for frames that were not captured in time.
+    pub number: u64,
+}
+
+/// The number of frames that a channel holds before new frames are dropped.
+const CHANNEL_FRAMES: usize = 4;
+
+impl Recorder {
+    /// Creates options for recording the area of `size` starting at `origin`
+    /// at 30 frames per second.
+    #[inline]
+    pub fn rect(origin: Location, size: (f64, f64)) -> Recorder {
+        Recorder { origin, size, period: Duration::from_secs(1) / 30 }
+    }
+
+    /// Creates options for recording all of `display`.
+    #[inline]
+    pub fn display(display: &Display) -> Recorder {
+        Recorder::rect(display.origin, display.size)
+    }
+
+    /// Creates options for recording all of the main display.
+    #[inline]
+    pub fn main_display() -> Result<Recorder> {
+        main_display().map(|display| Recorder::display(&display))
+    }
+
+    /// Sets how many frames are captured per second.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fps` is not positive and finite, or so low that the time
+    /// between frames can't be represented.
+    pub fn fps(&mut self, fps: f64) -> &mut Recorder {
+        assert!(fps.is_finite() && fps > 0.0, "invalid frame rate");
+        self.period = Duration::try_from_secs_f64(1.0 / fps).expect("invalid frame rate");
+        self
+    }
+
+    /// Starts recording, calling `callback` on the recording thread with each
+    /// frame.
+    ///
+    /// The first frame is captured before this returns, so that an error such
+    /// as a lack of permission to record the screen is reported here.
+    pub fn start<F>(&self, callback: F) -> Result<RecorderHandle>
+        where F: FnMut(Frame) + Send + 'static
+    {
+        self.spawn(callback, Arc::default())
+    }
+
+    /// Starts recording, sending each frame over the returned channel.
+    ///
+    /// Frames are dropped, and counted as skipped, while the channel holds
+    /// a few frames that have not been received.
+    pub fn channel(&self) -> Result<(RecorderHandle, mpsc::Receiver<Frame>)> {
+        let (sender, receiver) = mpsc::sync_channel(CHANNEL_FRAMES);
+        let skipped = Arc::new(AtomicU64::new(0));
+        let dropped = skipped.clone();
+        let handle = self.spawn(move |frame| {
+            if sender.try_send(frame).is_err() {
+                dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }, skipped)?;
+        Ok((handle, receiver))
+    }
+
+    fn spawn<F>(&self, callback: F, skipped: Arc<AtomicU64>) -> Result<RecorderHandle>
+        where F: FnMut(Frame) + Send + 'static
+    {
+        let options = *self;
+        let first = capture_rect(options.origin, options.size)?;
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = {
+            let skipped = skipped.clone();
+            thread::Builder::new()
+                .name("auto-screen-recorder".into())
+                .spawn(move || record(options, first, callback, &stopped, &skipped))
+                .expect("failed to spawn screen recorder thread")
+        };
+        Ok(RecorderHandle { stop: Some(stop), thread: Some(thread), skipped })
+    }
+}
+
+/// Captures frames until `stopped` is disconnected.
+fn record<F: FnMut(Frame)>(
+    options: Recorder,
+    first: Capture,
+    mut callback: F,
+    stopped: &mpsc::Receiver<()>,
+    skipped: &AtomicU64,
+) {
+    let start = Instant::now();
+    let Recorder { origin, size, period } = options;
+    let mut stream = imp::Stream::new(origin, size).ok();
+    callback(Frame { capture: first, time: Duration::from_secs(0), number: 0 });
+    let mut number = 0u64;
+    loop {
+        let (due, wait) = next_frame(period, start.elapsed(), number);
+        skipped.fetch_add(due.saturating_sub(number + 1), Ordering::Relaxed);
+        number = due;
+        match stopped.recv_timeout(wait) {
+            Err(mpsc::RecvTimeoutError::Timeout) => {},
+            _ => return,
+        }
+        if let Ok(capture) = next_capture(&mut stream, origin, size) {
+            callback(Frame { capture, time: start.elapsed(), number });
+        } else {
+            skipped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Returns the number of the frame to capture next, after frame `last` and
+/// `elapsed` into recording, along with how long to wait for it.
+///
+/// Frames that could not be captured in time are skipped rather than captured
+/// late, so that frame numbers stay aligned with time.
+fn next_frame(period: Duration, elapsed: Duration, last: u64) -> (u64, Duration) {
+    let passed = (elapsed.as_secs_f64() / period.as_secs_f64()) as u64;
+    let due = passed.saturating_add(1).max(last.saturating_add(1));
+    let time = Duration::try_from_secs_f64(period.as_secs_f64() * due as f64)
+        .unwrap_or(Duration::MAX);
+    (due, time.saturating_sub(elapsed))
+}
+
+/// Takes the recorded area from `stream`, capturing the screen instead until
+/// the stream delivers a frame, and from then on if the stream fails.
+fn next_capture(
+    stream: &mut Option<imp::Stream>,
+    origin: Location,
+    size: (f64, f64),
+) -> Result<Capture> {
+    if let Some(ref mut frames) = *stream {
+        match frames.latest() {
+            Ok(Some((image, scale))) => return Ok(Capture { image, origin, scale }),
+            Ok(None) => {},
+            Err(_) => *stream = None,
+        }
+    }
+    capture_rect(origin, size)
+}
+
+/// A handle to a [`Recorder`](struct.Recorder.html) that is running.
+///
+/// Recording stops when the handle is dropped.
+#[derive(Debug)]
+pub struct RecorderHandle {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+    skipped: Arc<AtomicU64>,
+}
+
+impl RecorderHandle {
+    /// Returns the number of frames that were skipped so far, because they
+    /// could not be captured in time or were dropped from a full channel.
+    pub fn skipped_frames(&self) -> u64 {
+        self.skipped.load(Ordering::Relaxed)
+    }
+
+    /// Stops recording and waits for the frame being captured, if any.
+    #[inline]
+    pub fn stop(self) {}
+}
+
+impl Drop for RecorderHandle {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use std::time::Duration;
+
+    use color::Rgb;
+    use error::{Error, Result};
+    use events::Location;
+    use image::Image;
+    use os;
+    use os::screen::{self, Bounds, Display};
+
+    fn display(display: Display, bounds: Bounds) -> super::Display {
+        super::Display {
+            origin: bounds.origin,
+            size: bounds.size,
+            scale: display.scale_factor(),
+            is_main: display.is_main(),
+        }
+    }
+
+    pub fn displays() -> Result<Vec<super::Display>> {
+        Ok(Display::active()?.into_iter().map(|d| display(d, d.bounds())).collect())
+    }
+
+    /// Display changes from the window server, along with the displays they
+    /// were last reported as, so that removed displays can be described.
+    pub struct TopologyEvents {
+        events: screen::TopologyEvents,
+        layout: Vec<(Display, super::Display)>,
+    }
+
+    impl TopologyEvents {
+        pub fn new(debounce: Duration) -> Result<TopologyEvents> {
+            let events = screen::topology_events(debounce)?;
+            let layout = Display::active()?.into_iter().map(|d| (d, display(d, d.bounds()))).collect();
+            Ok(TopologyEvents { events, layout })
+        }
+
+        #[inline]
+        pub fn next(&mut self) -> Option<super::TopologyChange> {
+            let change = self.events.next()?;
+            Some(self.convert(change))
+        }
+
+        #[inline]
+        pub fn try_next(&mut self) -> Option<super::TopologyChange> {
+            let change = self.events.try_next()?;
+            Some(self.convert(change))
+        }
+
+        fn convert(&mut self, change: screen::TopologyChange) -> super::TopologyChange {
+            use super::TopologyEvent as Event;
+
+            let layout: Vec<_> = change.layout.iter().map(|&(d, bounds)| (d, display(d, bounds))).collect();
+            let find = |layout: &[(Display, super::Display)], d: Display| {
+                layout.iter().find(|&&(other, _)| other == d).map(|&(_, display)| display)
+            };
+            let events = change.events.iter().filter_map(|event| Some(match *event {
+                screen::TopologyEvent::Added(d, _) => Event::Added(find(&layout, d)?),
+                screen::TopologyEvent::Removed(d) => Event::Removed(find(&self.layout, d)?),
+                screen::TopologyEvent::Moved { display, from, to } => {
+                    Event::Moved { display: find(&layout, display)?, from, to }
+                },
+                screen::TopologyEvent::Resized { display, from, to } => {
+                    Event::Resized { display: find(&layout, display)?, from, to }
+                },
+            })).collect();
+            let displays = layout.iter().map(|&(_, display)| display).collect();
+            self.layout = layout;
+            super::TopologyChange { events, displays }
+        }
+    }
+
+    #[inline]
+    pub fn scale_factor(display: &super::Display) -> f64 {
+        display.scale
+    }
+
+    pub fn find_text(image: &Image, text: &str) -> Result<Option<(Location, (f64, f64))>> {
+        Ok(os::text::find(image, text)?.map(|bounds| (bounds.origin, bounds.size)))
+    }
+
+    pub fn capture_rect(pos: Location, size: (f64, f64)) -> Result<(Image, f64)> {
+        let (display, bounds) = display_at(pos).ok_or(Error::Failed)?;
+        let origin = (pos.0 - bounds.origin.0, pos.1 - bounds.origin.1);
+        let image = display.capture_rect(Bounds { origin, size })?;
+        Ok((image, display.scale_factor()))
+    }
+
+    /// The recorded area of a display, as streamed by the window server.
+    pub struct Stream {
+        stream: screen::DisplayStream,
+        scale: f64,
+    }
+
+    impl Stream {
+        pub fn new(pos: Location, size: (f64, f64)) -> Result<Stream> {
+            let (display, bounds) = display_at(pos).ok_or(Error::Failed)?;
+            let origin = (pos.0 - bounds.origin.0, pos.1 - bounds.origin.1);
+            let stream = display.stream(Bounds { origin, size })?;
+            Ok(Stream { stream, scale: display.scale_factor() })
+        }
+
+        #[inline]
+        pub fn latest(&mut self) -> Result<Option<(Image, f64)>> {
+            Ok(self.stream.latest().map(|image| (image, self.scale)))
+        }
+    }
+
+    fn display_at(pos: Location) -> Option<(Display, Bounds)> {
+        Display::active()
+            .ok()?
+            .into_iter()
+            .map(|d| (d, d.bounds()))
+            .find(|&(_, b)| b.contains(pos))
+    }
+
+    pub fn color_at(pos: Location) -> Option<Rgb> {
+        let (display, bounds) = display_at(pos)?;
+        display.color_at((pos.0 - bounds.origin.0, pos.1 - bounds.origin.1))
+    }
+
+    pub fn is_asleep(pos: Location) -> bool {
+        display_at(pos).map_or(Display::main().is_asleep(), |(d, _)| d.is_asleep())
+    }
+
+    /// Samples the display that the location was last on, looking it up
+    /// again when the location leaves it or reads fail.
+    pub struct Sampler {
+        current: Option<(Bounds, screen::Sampler)>,
+    }
+
+    impl Sampler {
+        pub fn new(_: Location) -> Result<Sampler> {
+            Ok(Sampler { current: None })
+        }
+
+        pub fn sample(&mut self, pos: Location) -> Option<Rgb> {
+            if !self.current.as_ref().is_some_and(|&(bounds, _)| bounds.contains(pos)) {
+                let (display, bounds) = display_at(pos)?;
+                self.current = Some((bounds, display.sampler(pos).ok()?));
+            }
+            let color = {
+                let (bounds, ref mut sampler) = *self.current.as_mut()?;
+                sampler.pos = (pos.0 - bounds.origin.0, pos.1 - bounds.origin.1);
+                sampler.sample()
+            };
+            if color.is_none() {
+                self.current = None;
+            }
+            color
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use std::time::Duration;
+
+    use color::Rgb;
+    use error::{Error, Result};
+    use events::Location;
+    use image::Image;
+    use os::screen;
+
+    pub fn displays() -> Result<Vec<super::Display>> {
+        Ok(screen::monitors()?.into_iter().map(|m| {
+            let screen::Bounds { origin: (x, y), size: (w, h) } = m.bounds;
+            super::Display {
+                origin: (x as f64, y as f64),
+                size: (w as f64, h as f64),
+                scale: 1.0,
+                is_main: m.is_primary,
+            }
+        }).collect())
+    }
+
+    pub fn scale_factor(display: &super::Display) -> f64 {
+        let origin = (display.origin.0 as i32, display.origin.1 as i32);
+        screen::monitors()
+            .ok()
+            .and_then(|monitors| monitors.into_iter().find(|m| m.bounds.origin == origin))
+            .map_or(1.0, |m| m.dpi as f64 / screen::DEFAULT_DPI as f64)
+    }
+
+    pub fn find_text(_: &Image, _: &str) -> Result<Option<(Location, (f64, f64))>> {
+        Err(Error::Unsupported)
+    }
+
+    fn bounds((x, y): Location, (w, h): (f64, f64)) -> screen::Bounds {
+        screen::Bounds {
+            origin: (x.floor() as i32, y.floor() as i32),
+            size: (w.ceil().max(0.0) as usize, h.ceil().max(0.0) as usize),
+        }
+    }
+
+    pub fn capture_rect(pos: Location, size: (f64, f64)) -> Result<(Image, f64)> {
+        Ok((screen::capture_rect(bounds(pos, size))?, 1.0))
+    }
+
+    /// The recorded area of a monitor, as duplicated by DXGI.
+    pub struct Stream(screen::Duplication);
+
+    impl Stream {
+        #[inline]
+        pub fn new(pos: Location, size: (f64, f64)) -> Result<Stream> {
+            screen::Duplication::new(bounds(pos, size)).map(Stream)
+        }
+
+        #[inline]
+        pub fn latest(&mut self) -> Result<Option<(Image, f64)>> {
+            Ok(self.0.latest()?.map(|image| (image, 1.0)))
+        }
+    }
+
+    pub fn color_at((x, y): Location) -> Option<Rgb> {
+        screen::color_at((x.floor() as i32, y.floor() as i32))
+    }
+
+    pub struct Sampler(screen::Sampler);
+
+    impl Sampler {
+        pub fn new(_: Location) -> Result<Sampler> {
+            screen::Sampler::new().map(Sampler)
+        }
+
+        pub fn sample(&mut self, (x, y): Location) -> Option<Rgb> {
+            self.0.sample((x.floor() as i32, y.floor() as i32))
+        }
+    }
+
+    pub fn is_asleep(_: Location) -> bool {
+        false
+    }
+
+    pub struct TopologyEvents;
+
+    impl TopologyEvents {
+        pub fn new(_: Duration) -> Result<TopologyEvents> {
+            Err(Error::Unsupported)
+        }
+
+        pub fn next(&mut self) -> Option<super::TopologyChange> {
+            None
+        }
+
+        pub fn try_next(&mut self) -> Option<super::TopologyChange> {
+            None
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod imp {
+    use std::time::Duration;
+
+    use color::Rgb;
+    use error::{Error, Result};
+    use events::Location;
+    use image::Image;
+
+    #[cfg(target_os = "linux")]
+    pub fn displays() -> Result<Vec<super::Display>> {
+        use os::screen::Display;
+
+        Ok(Display::active()?.into_iter().map(|d| {
+            let bounds = d.bounds();
+            super::Display {
+                origin: bounds.origin,
+                size: bounds.size,
+                scale: 1.0,
+                is_main: d.is_main(),
+            }
+        }).collect())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn displays() -> Result<Vec<super::Display>> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn scale_factor(display: &super::Display) -> f64 {
+        display.scale
+    }
+
+    pub fn find_text(_: &Image, _: &str) -> Result<Option<(Location, (f64, f64))>> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn capture_rect(_: Location, _: (f64, f64)) -> Result<(Image, f64)> {
+        Err(Error::Unsupported)
+    }
+
+    pub struct Stream;
+
+    impl Stream {
+        pub fn new(_: Location, _: (f64, f64)) -> Result<Stream> {
+            Err(Error::Unsupported)
+        }
+
+        pub fn latest(&mut self) -> Result<Option<(Image, f64)>> {
+            Ok(None)
+        }
+    }
+
+    pub fn color_at(_: Location) -> Option<Rgb> {
+        None
+    }
+
+    pub fn is_asleep(_: Location) -> bool {
+        false
+    }
+
+    pub struct Sampler;
+
+    impl Sampler {
+        pub fn new(_: Location) -> Result<Sampler> {
+            Err(Error::Unsupported)
+        }
+
+        pub fn sample(&mut self, _: Location) -> Option<Rgb> {
+            None
+        }
+    }
+
+    pub struct TopologyEvents;
+
+    impl TopologyEvents {
+        pub fn new(_: Duration) -> Result<TopologyEvents> {
+            Err(Error::Unsupported)
+        }
+
+        pub fn next(&mut self) -> Option<super::TopologyChange> {
+            None
+        }
+
+        pub fn try_next(&mut self) -> Option<super::TopologyChange> {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tolerance() {
+        let a = Rgb { red: 10, green: 200, blue: 250 };
+        let b = Rgb { red: 14, green: 196, blue: 255 };
+        assert!(within(a, a, 0));
+        assert!(!within(a, b, 4));
+        assert!(within(a, b, 5));
+    }
+
+    #[test]
+    fn capture_locations() {
+        // A 2x scale capture of the 2x1 point area at (10,20)
+        let mut data = vec![0u8; 4 * 2 * 4];
+        data[4 * 3] = 255;
+        let image = Image::from_rgba(4, 2, data).unwrap();
+        let capture = Capture { image, origin: (10.0, 20.0), scale: 2.0 };
+
+        assert_eq!(capture.size(), (2.0, 1.0));
+        assert_eq!(capture.pixel_at((10.0, 20.0)), Some((0, 0)));
+        assert_eq!(capture.pixel_at((11.5, 20.5)), Some((3, 1)));
+        assert_eq!(capture.pixel_at((12.0, 20.0)), None);
+        assert_eq!(capture.pixel_at((9.9, 20.0)), None);
+        assert_eq!(capture.color_at((11.5, 20.0)).map(|c| c.red), Some(255));
+        assert_eq!(capture.location_of((3, 1)), (11.5, 20.5));
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn topology_unsupported() {
+        let events = topology_events(Duration::from_millis(10));
+        assert_eq!(events.map(|_| ()), Err(Error::Unsupported));
+    }
+
+    #[test]
+    fn display_pixels() {
+        let display = Display { origin: (-1440.0, 0.0), size: (1440.0, 900.0), scale: 2.0, is_main: false };
+        assert_eq!(display.to_pixels((-720.0, 450.0)), (1440.0, 900.0));
+        assert_eq!(display.from_pixels((1440.0, 900.0)), (-720.0, 450.0));
+        assert_eq!(display.pixels(), (2880, 1800));
+    }
+
+    #[test]
+    fn display_at_point() {
+        let main = Display { origin: (0.0, 0.0), size: (1920.0, 1080.0), scale: 1.0, is_main: true };
+        let left = Display { origin: (-1440.0, 180.0), size: (1440.0, 900.0), scale: 2.0, is_main: false };
+        assert_eq!(main.center(), (960.0, 540.0));
+        assert_eq!(left.center(), (-720.0, 630.0));
+
+        let displays = || vec![main, left];
+        assert_eq!(display_at(displays(), (-1.0, 500.0)), Some(left));
+        assert_eq!(display_at(displays(), (0.0, 0.0)), Some(main));
+        assert_eq!(display_at(displays(), left.center()), Some(left));
+        // Edges past the last pixel and gaps between displays are off screen
+        assert_eq!(display_at(displays(), (1920.0, 500.0)), None);
+        assert_eq!(display_at(displays(), (-1.0, 100.0)), None);
+    }
+
+    #[test]
+    fn recorder_frames() {
+        let period = Duration::from_millis(100);
+        assert_eq!(next_frame(period, Duration::from_millis(30), 0), (1, Duration::from_millis(70)));
+        // Frames whose time passed are skipped
+        assert_eq!(next_frame(period, Duration::from_millis(350), 1), (4, Duration::from_millis(50)));
+        // A frame is never due again when the clock hasn't reached its time
+        assert_eq!(next_frame(period, Duration::from_millis(290), 3), (4, Duration::from_millis(110)));
+        assert_eq!(next_frame(period, Duration::MAX, 0).1, Duration::from_secs(0));
+
+        let mut recorder = Recorder::rect((0.0, 0.0), (10.0, 10.0));
+        assert_eq!(recorder.period, Duration::from_secs(1) / 30);
+        assert_eq!(recorder.fps(4.0).period, Duration::from_millis(250));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid frame rate")]
+    fn recorder_zero_fps() {
+        Recorder::rect((0.0, 0.0), (10.0, 10.0)).fps(0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid frame rate")]
+    fn recorder_tiny_fps() {
+        Recorder::rect((0.0, 0.0), (10.0, 10.0)).fps(1e-300);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn color_wait_on_timer() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let red = Rgb { red: 255, green: 0, blue: 0 };
+        let mut wait = ColorWait::new((-1e6, -1e6), red);
+        wait.timeout(Duration::from_millis(60)).interval(Duration::from_millis(10));
+
+        let start = Instant::now();
+        let future = {
+            let _context = runtime.enter();
+            wait.wait_async()
+        };
+        assert!(matches!(future.inner, Inner::Timer { .. }));
+        assert!(!runtime.block_on(future));
+        assert!(start.elapsed() >= Duration::from_millis(60));
+    }
+}
@@ -0,0 +1,497 @@
+//! 📜 Declarative automation scripts.
+//!
+//! A [`Script`](struct.Script.html) is a list of high-level
+//! [`Step`](enum.Step.html)s, such as moving the mouse, clicking, and typing
+//! text, built up in code and run as often as needed. Scripts can be saved
+//! to a plain text file and edited by hand, like
+//! [recordings](../record/index.html).
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use auto::mouse::Button;
+//! use auto::script::Script;
+//!
+//! let mut script = Script::new();
+//! script
+//!     .move_to((10.0, 10.0))
+//!     .click(Button::Left)
+//!     .wait(Duration::from_millis(500))
+//!     .type_text("hello");
+//!
+//! script.run().unwrap();
+//! script.save("hello.script").unwrap();
+//! ```
+//!
+//! # File Format
+//!
+//! Each line holds one step. Blank lines and lines starting with `#` are
+//! ignored. Text and labels are quoted and escaped as in Rust string
+//! literals, and waits are in seconds.
+//!
+//! ```text
+//! # auto script v1
+//! move 10 10
+//! click left
+//! wait 0.5
+//! type "hello"
+//! key return
+//! shortcut meta+shift+s
+//! scroll 0 -3
+//! wait 0.2 0.8
+//! sync "saved"
+//! ```
+//!
+//! # Queues
+//!
+//! [`Script::submit`](struct.Script.html#method.submit) runs the steps on a
+//! [dispatch queue](../dispatch/index.html), where waits become queue delays
+//! and sync steps become sync points, so scripts can be coordinated with
+//! other queues.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use abort::Token;
+use dispatch::{self, Queue};
+use error::{Error, Result};
+use events::Location;
+use keyboard::{self, Key};
+use mouse::{self, Button};
+
+/// The version of the text format written by this version of the crate.
+const VERSION: u32 = 1;
+
+const HEADER_PREFIX: &str = "# auto script v";
+
+/// A single step of a [`Script`](struct.Script.html).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Step {
+    /// Moves the mouse to a location, as with
+    /// [`mouse::move_to`](../mouse/fn.move_to.html).
+    MoveTo(Location),
+    /// Clicks a button at the current mouse location.
+    Click(Button),
+    /// Taps a key.
+    Key(Key),
+    /// Presses keys as a shortcut, as with
+    /// [`keyboard::send_shortcut`](../keyboard/fn.send_shortcut.html).
+    Shortcut(Vec<Key>),
+    /// Types text, as with
+    /// [`keyboard::type_text`](../keyboard/fn.type_text.html).
+    TypeText(String),
+    /// Scrolls by lines horizontally and vertically, where positive values
+    /// scroll right and up.
+    Scroll {
+        /// The horizontal offset.
+        dx: i32,
+        /// The vertical offset.
+        dy: i32,
+    },
+    /// Pauses for a duration.
+    Wait(Duration),
+    /// Pauses for a random duration within a range.
+    WaitBetween(Range<Duration>),
+    /// Marks a labeled point in the script.
+    ///
+    /// Sync steps do nothing when the script is run directly, and become
+    /// [sync points](../dispatch/struct.Queue.html#method.sync_point) when
+    /// it is submitted to a queue.
+    Sync(String),
+}
+
+impl Step {
+    /// Performs the step on the current thread.
+    ///
+    /// Waits end early if
+    /// [`dispatch::interrupt_delays`](../dispatch/fn.interrupt_delays.html)
+    /// is called.
+    pub fn run(&self) -> Result<()> {
+        match *self {
+            Step::MoveTo(location) => mouse::move_to(location),
+            Step::Click(button) => mouse::click(button),
+            Step::Key(key) => keyboard::send_shortcut(&[key]),
+            Step::Shortcut(ref keys) => keyboard::send_shortcut(keys),
+            Step::TypeText(ref text) => keyboard::type_text(text),
+            Step::Scroll { dx, dy } => mouse::scroll(dx, dy),
+            Step::Wait(duration) => {
+                dispatch::sleep(duration);
+                Ok(())
+            },
+            Step::WaitBetween(ref range) => {
+                dispatch::sleep(dispatch::random_between(range.start, range.end));
+                Ok(())
+            },
+            Step::Sync(_) => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Step::MoveTo((x, y)) => write!(f, "move {} {}", x, y),
+            Step::Click(button) => write!(f, "click {}", button),
+            Step::Key(key) => write!(f, "key {}", key),
+            Step::Shortcut(ref keys) => {
+                f.write_str("shortcut ")?;
+                for (i, key) in keys.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str("+")?;
+                    }
+                    write!(f, "{}", key)?;
+                }
+                Ok(())
+            },
+            Step::TypeText(ref text) => write!(f, "type {:?}", text),
+            Step::Scroll { dx, dy } => write!(f, "scroll {} {}", dx, dy),
+            Step::Wait(duration) => write!(f, "wait {}", duration.as_secs_f64()),
+            Step::WaitBetween(ref range) => {
+                write!(f, "wait {} {}", range.start.as_secs_f64(), range.end.as_secs_f64())
+            },
+            Step::Sync(ref label) => write!(f, "sync {:?}", label),
+        }
+    }
+}
+
+/// A sequence of automation steps that can be run, saved, and loaded.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Script {
+    /// The steps, in the order they run.
+    pub steps: Vec<Step>,
+}
+
+impl Script {
+    /// Creates an empty script.
+    #[inline]
+    pub fn new() -> Script {
+        Script::default()
+    }
+
+    /// Appends `step`.
+    pub fn push(&mut self, step: Step) -> &mut Script {
+        self.steps.push(step);
+        self
+    }
+
+    /// Appends a move of the mouse to `location`.
+    #[inline]
+    pub fn move_to(&mut self, location: Location) -> &mut Script {
+        self.push(Step::MoveTo(location))
+    }
+
+    /// Appends a click of `button`.
+    #[inline]
+    pub fn click(&mut self, button: Button) -> &mut Script {
+        self.push(Step::Click(button))
+    }
+
+    /// Appends a tap of `key`.
+    #[inline]
+    pub fn key(&mut self, key: Key) -> &mut Script {
+        self.push(Step::Key(key))
+    }
+
+    /// Appends a shortcut such as `[Key::Meta, Key::S]`.
+    #[inline]
+    pub fn shortcut(&mut self, keys: &[Key]) -> &mut Script {
+        self.push(Step::Shortcut(keys.to_vec()))
+    }
+
+    /// Appends typing of `text`.
+    #[inline]
+    pub fn type_text(&mut self, text: &str) -> &mut Script {
+        self.push(Step::TypeText(text.into()))
+    }
+
+    /// Appends a scroll by `dx` and `dy` lines.
+    #[inline]
+    pub fn scroll(&mut self, dx: i32, dy: i32) -> &mut Script {
+        self.push(Step::Scroll { dx, dy })
+    }
+
+    /// Appends a pause of `duration`.
+    #[inline]
+    pub fn wait(&mut self, duration: Duration) -> &mut Script {
+        self.push(Step::Wait(duration))
+    }
+
+    /// Appends a pause of a random duration within `range`.
+    #[inline]
+    pub fn wait_between(&mut self, range: Range<Duration>) -> &mut Script {
+        self.push(Step::WaitBetween(range))
+    }
+
+    /// Appends a sync step labeled `label`.
+    #[inline]
+    pub fn sync(&mut self, label: &str) -> &mut Script {
+        self.push(Step::Sync(label.into()))
+    }
+
+    /// Appends the steps of `other`.
+    pub fn extend(&mut self, other: &Script) -> &mut Script {
+        self.steps.extend(other.steps.iter().cloned());
+        self
+    }
+
+    /// Runs the steps in order on the current thread, stopping at the first
+    /// step that fails or when the [kill switch](../abort/index.html) is
+    /// pressed.
+    pub fn run(&self) -> Result<()> {
+        let token = Token::new();
+        self.steps.iter().try_for_each(|step| token.check().and_then(|_| step.run()))
+    }
+
+    /// Submits the steps to `queue` and returns immediately.
+    ///
+    /// Waits become [delays](../dispatch/struct.Queue.html#method.delay) and
+    /// sync steps become sync points. Once a step fails, the steps after it
+    /// are skipped, although their waits still elapse. Pressing the
+    /// [kill switch](../abort/index.html) skips the remaining steps and ends
+    /// their waits.
+    pub fn submit(&self, queue: &Queue) -> Submission {
+        let token = Token::new();
+        let error = Arc::new(Mutex::new(None));
+        for step in &self.steps {
+            match *step {
+                Step::Wait(duration) => { queue.delay(duration); },
+                Step::WaitBetween(ref range) => { queue.delay_range(range.clone()); },
+                Step::Sync(ref label) => { queue.sync_point(label); },
+                ref step => {
+                    let step = step.clone();
+                    let error = error.clone();
+                    queue.exec_async(move || {
+                        let mut error = error.lock().unwrap_or_else(|e| e.into_inner());
+                        if error.is_none() {
+                            *error = token.check().and_then(|_| step.run()).err();
+                        }
+                    });
+                },
+            }
+        }
+        Submission { queue: queue.clone(), error }
+    }
+
+    /// Reads a script from the file at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Script> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Writes the script to the file at `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}{}", HEADER_PREFIX, VERSION)?;
+        for step in &self.steps {
+            writeln!(f, "{}", step)?;
+        }
+        Ok(())
+    }
+}
+
+/// Steps of a [`Script`](struct.Script.html) submitted to a queue.
+#[derive(Debug)]
+pub struct Submission {
+    queue: Queue,
+    error: Arc<Mutex<Option<Error>>>,
+}
+
+impl Submission {
+    /// Blocks until the queue has finished every task submitted so far,
+    /// returning the error of the step that failed, if any.
+    pub fn wait(self) -> Result<()> {
+        self.queue.wait();
+        match self.error.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+/// An error returned when parsing a [`Script`](struct.Script.html) from text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    line: usize,
+    message: String,
+}
+
+impl ParseError {
+    /// Returns the one-based line number at which the error occurred.
+    #[inline]
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl ::std::error::Error for ParseError {}
+
+impl FromStr for Script {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> ::std::result::Result<Script, ParseError> {
+        let mut script = Script::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if let Some(version) = line.strip_prefix(HEADER_PREFIX) {
+                if version.parse().map_or(true, |v: u32| v > VERSION) {
+                    let message = format!("unsupported version {:?}", version);
+                    return Err(ParseError { line: i + 1, message });
+                }
+            }
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let step = parse_step(line).map_err(|message| ParseError { line: i + 1, message })?;
+            script.steps.push(step);
+        }
+        Ok(script)
+    }
+}
+
+fn parse_step(line: &str) -> ::std::result::Result<Step, String> {
+    fn arg<T: FromStr>(args: &mut dyn Iterator<Item = &str>, what: &str)
+        -> ::std::result::Result<T, String>
+    {
+        let s = args.next().ok_or_else(|| format!("missing {}", what))?;
+        s.parse().map_err(|_| format!("invalid {} {:?}", what, s))
+    }
+
+    fn seconds(s: &str) -> ::std::result::Result<Duration, String> {
+        s.parse::<f64>()
+            .ok()
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+            .ok_or_else(|| format!("invalid duration {:?}", s))
+    }
+
+    let (kind, rest) = match line.find(char::is_whitespace) {
+        Some(i) => (&line[..i], line[i..].trim_start()),
+        None => (line, ""),
+    };
+    if kind == "type" || kind == "sync" {
+        let (text, rest) = unquote(rest)?;
+        if !rest.trim().is_empty() {
+            return Err(format!("unexpected {:?}", rest.trim()));
+        }
+        return Ok(if kind == "type" { Step::TypeText(text) } else { Step::Sync(text) });
+    }
+
+    let mut args = rest.split_whitespace();
+    let step = match kind {
+        "move" => Step::MoveTo((arg(&mut args, "x")?, arg(&mut args, "y")?)),
+        "click" => Step::Click(arg(&mut args, "button")?),
+        "key" => Step::Key(arg(&mut args, "key")?),
+        "shortcut" => {
+            let keys = args.next().ok_or("missing keys")?;
+            let keys = keys
+                .split('+')
+                .map(|key| key.parse().map_err(|_| format!("invalid key {:?}", key)))
+                .collect::<::std::result::Result<_, _>>()?;
+            Step::Shortcut(keys)
+        },
+        "scroll" => Step::Scroll { dx: arg(&mut args, "dx")?, dy: arg(&mut args, "dy")? },
+        "wait" => {
+            let min = seconds(args.next().ok_or("missing duration")?)?;
+            match args.next() {
+                Some(max) => Step::WaitBetween(min..seconds(max)?),
+                None => Step::Wait(min),
+            }
+        },
+        _ => return Err(format!("unknown step {:?}", kind)),
+    };
+    if let Some(extra) = args.next() {
+        return Err(format!("unexpected {:?}", extra));
+    }
+    Ok(step)
+}
+
+/// Parses a quoted string at the start of `s`, returning its contents and
+/// the rest of `s`.
+fn unquote(s: &str) -> ::std::result::Result<(String, &str), String> {
+    let mut chars = s.char_indices();
+    if chars.next().map(|(_, c)| c) != Some('"') {
+        return Err(format!("expected quoted text, found {:?}", s));
+    }
+    let mut text = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((text, &s[i + 1..])),
+            '\\' => {
+                let escaped = match chars.next().map(|(_, c)| c) {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('0') => '\0',
+                    Some(c @ '\\') | Some(c @ '"') | Some(c @ '\'') => c,
+                    Some('u') => {
+                        let rest = &s[i + 2..];
+                        let end = rest.find('}').ok_or("unterminated unicode escape")?;
+                        let code = rest.get(1..end)
+                            .filter(|_| rest.starts_with('{'))
+                            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                            .and_then(::std::char::from_u32)
+                            .ok_or_else(|| format!("invalid unicode escape {:?}", &rest[..=end]))?;
+                        for _ in 0..=end {
+                            chars.next();
+                        }
+                        code
+                    },
+                    Some(c) => return Err(format!("invalid escape \\{}", c)),
+                    None => break,
+                };
+                text.push(escaped);
+            },
+            c => text.push(c),
+        }
+    }
+    Err("unterminated quoted text".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_round_trip() {
+        let mut script = Script::new();
+        script
+            .move_to((10.0, 20.5))
+            .click(Button::Left)
+            .wait(Duration::from_millis(500))
+            .type_text("say \"hi\"\n\u{7f}✓")
+            .key(Key::Return)
+            .shortcut(&[Key::Meta, Key::Shift, Key::S])
+            .scroll(0, -3)
+            .wait_between(Duration::from_millis(200)..Duration::from_millis(800))
+            .sync("saved file");
+
+        let text = script.to_string();
+        assert!(text.starts_with("# auto script v1\n"));
+        assert_eq!(text.parse::<Script>(), Ok(script));
+
+        let err = "move 1 2\n\nclick sideways".parse::<Script>().unwrap_err();
+        assert_eq!(err.line(), 3);
+        assert!("type \"open".parse::<Script>().is_err());
+        assert!("# auto script v99".parse::<Script>().is_err());
+        assert_eq!("wait 1e300".parse::<Script>().unwrap_err().line(), 1);
+        assert!("wait 0.5 -1".parse::<Script>().is_err());
+    }
+}
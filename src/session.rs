@@ -0,0 +1,346 @@
+//! 🧳 Independent automation contexts.
+//!
+//! [Settings](../settings/index.html), [rate limits](../safety/struct.RateLimit.html),
+//! and the [kill switch](../abort/index.html) are process-wide, so libraries
+//! that embed this crate would otherwise fight over them. A
+//! [`Session`](struct.Session.html) owns its own, which apply in place of the
+//! process-wide ones to every action taken on a thread that entered it.
+//!
+//! A session can also tag the events posted in it, so listeners can tell
+//! which context posted an event. On macOS, the tag is stored in the
+//! `SourceUserData` field of each event, and on Windows, it is the extra
+//! information of each input.
+//!
+//! Sessions don't separate input state, which the system keeps for the
+//! whole process: keys and buttons held down in one session are down in
+//! every other, and on macOS, the modifiers held in one session are applied
+//! to the events posted in every other.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use auto::{keyboard, Settings};
+//! use auto::keyboard::{Key, Modifiers};
+//! use auto::safety::RateLimit;
+//! use auto::session::Session;
+//!
+//! let session = Session::new();
+//! session.set_settings(Settings { pause: Duration::from_millis(50), ..Settings::new() });
+//! session.limit_rate(RateLimit::new(100, Duration::from_secs(1)));
+//! session.install_kill_switch(Modifiers::CONTROL | Modifiers::ALT, Key::K).unwrap();
+//!
+//! session.run(|| keyboard::type_text("typed in the session")).unwrap();
+//! ```
+
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use abort;
+use dispatch;
+use error::Result;
+use events::REPLACEMENT_MARKER;
+use hotkey::Hotkey;
+use keyboard::{Key, Modifiers};
+use safety::{Bucket, RateLimit};
+use settings::Settings;
+
+/// The identifier of the next session to be created.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+thread_local! {
+    /// The session that the current thread entered, if any.
+    static CURRENT: RefCell<Option<Session>> = const { RefCell::new(None) };
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+struct Inner {
+    id: u64,
+    settings: Mutex<Settings>,
+    bucket: Mutex<Option<Bucket>>,
+    source_tag: AtomicUsize,
+    kill_switch: Mutex<Option<Hotkey>>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        abort::forget_session(self.id);
+        dispatch::forget_session(self.id);
+    }
+}
+
+/// A context of settings, rate limit, event tag, and kill switch for
+/// automation, shared by its clones.
+///
+/// Threads take on a session with [`enter`](#method.enter) or
+/// [`run`](#method.run), and tasks submitted to a
+/// [queue](../dispatch/struct.Queue.html) run in the session of the thread
+/// that submitted them. Threads that did neither use the process-wide state.
+#[derive(Clone)]
+pub struct Session {
+    inner: Arc<Inner>,
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("id", &self.inner.id)
+            .field("settings", &self.settings())
+            .field("rate_limit", &self.rate_limit())
+            .field("source_tag", &self.source_tag())
+            .field("kill_switch", &*lock(&self.inner.kill_switch))
+            .finish()
+    }
+}
+
+impl PartialEq for Session {
+    #[inline]
+    fn eq(&self, other: &Session) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl Default for Session {
+    #[inline]
+    fn default() -> Session {
+        Session::new()
+    }
+}
+
+impl Session {
+    /// Creates a session with the default settings, no rate limit, no tag,
+    /// and no kill switch.
+    pub fn new() -> Session {
+        Session {
+            inner: Arc::new(Inner {
+                id: NEXT_ID.fetch_add(1, Ordering::Relaxed) as u64,
+                settings: Mutex::default(),
+                bucket: Mutex::default(),
+                source_tag: AtomicUsize::new(0),
+                kill_switch: Mutex::default(),
+            }),
+        }
+    }
+
+    /// Returns the settings of the session.
+    pub fn settings(&self) -> Settings {
+        *lock(&self.inner.settings)
+    }
+
+    /// Replaces the settings of the session.
+    pub fn set_settings(&self, settings: Settings) {
+        *lock(&self.inner.settings) = settings;
+    }
+
+    /// Limits the rate at which events are posted in the session, in place
+    /// of the process-wide [rate limit](../safety/fn.limit_rate.html).
+    pub fn limit_rate(&self, limit: RateLimit) {
+        *lock(&self.inner.bucket) = Some(Bucket::new(limit, Instant::now()));
+    }
+
+    /// Removes the rate limit of the session, so that the process-wide one
+    /// applies again.
+    pub fn clear_rate_limit(&self) {
+        *lock(&self.inner.bucket) = None;
+    }
+
+    /// Returns the rate limit of the session, if it has one.
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        lock(&self.inner.bucket).as_ref().map(Bucket::limit)
+    }
+
+    /// Returns the tag of events posted in the session, or 0 if they are not
+    /// tagged.
+    #[inline]
+    pub fn source_tag(&self) -> usize {
+        self.inner.source_tag.load(Ordering::Relaxed)
+    }
+
+    /// Tags events posted in the session with `tag`, or stops tagging them if
+    /// `tag` is 0.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tag` is `0x6175746f`, which marks the events that
+    /// [interceptors](../events/fn.intercept.html) post in place of others.
+    #[inline]
+    pub fn set_source_tag(&self, tag: usize) {
+        assert_ne!(tag, REPLACEMENT_MARKER, "source tag is reserved for replacement events");
+        self.inner.source_tag.store(tag, Ordering::Relaxed);
+    }
+
+    /// Registers a hotkey that [aborts](#method.abort) the session whenever
+    /// `key` is pressed while holding exactly `modifiers`, replacing any
+    /// previous one.
+    ///
+    /// The hotkey stays armed until it is removed or the last clone of the
+    /// session is dropped. See
+    /// [`Hotkey::register`](../hotkey/struct.Hotkey.html#method.register)
+    /// for when registration fails.
+    pub fn install_kill_switch(&self, modifiers: Modifiers, key: Key) -> Result<()> {
+        let weak: Weak<Inner> = Arc::downgrade(&self.inner);
+        let hotkey = Hotkey::register(modifiers, key, move || {
+            if let Some(inner) = weak.upgrade() {
+                abort::trigger_session(inner.id);
+            }
+        })?;
+        *lock(&self.inner.kill_switch) = Some(hotkey);
+        Ok(())
+    }
+
+    /// Unregisters the kill switch of the session.
+    pub fn remove_kill_switch(&self) {
+        lock(&self.inner.kill_switch).take();
+    }
+
+    /// Stops every operation in progress in the session, including its
+    /// delays on queues, leaving those of other sessions running.
+    ///
+    /// Stopped operations return
+    /// [`Error::Aborted`](../error/enum.Error.html#variant.Aborted), as they
+    /// do for the process-wide [kill switch](../abort/index.html), which
+    /// stops the operations of every session.
+    #[inline]
+    pub fn abort(&self) {
+        abort::trigger_session(self.inner.id);
+    }
+
+    /// Makes the session current on this thread until the returned guard is
+    /// dropped, when the previously current session is restored.
+    pub fn enter(&self) -> Entered {
+        let previous = CURRENT.with(|current| current.replace(Some(self.clone())));
+        Entered { previous }
+    }
+
+    /// Calls `f` with the session current on this thread.
+    pub fn run<T, F: FnOnce() -> T>(&self, f: F) -> T {
+        let _entered = self.enter();
+        f()
+    }
+}
+
+/// A guard that keeps a session current on a thread, returned by
+/// [`Session::enter`](struct.Session.html#method.enter).
+#[derive(Debug)]
+pub struct Entered {
+    previous: Option<Session>,
+}
+
+impl Drop for Entered {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+/// Returns the session that the current thread entered, if any.
+pub fn current() -> Option<Session> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+/// Returns the identifier of the current session, if any.
+pub(crate) fn current_id() -> Option<u64> {
+    CURRENT.with(|current| current.borrow().as_ref().map(|session| session.inner.id))
+}
+
+/// Returns the settings of the current session, if any.
+pub(crate) fn current_settings() -> Option<Settings> {
+    CURRENT.with(|current| current.borrow().as_ref().map(Session::settings))
+}
+
+/// Returns the tag of events posted in the current session, if it has one.
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+pub(crate) fn current_source_tag() -> Option<usize> {
+    CURRENT.with(|current| {
+        current.borrow().as_ref().map(Session::source_tag).filter(|&tag| tag != 0)
+    })
+}
+
+/// Takes a token from the rate limit of the current session, returning how
+/// long to wait before posting, or `None` if the session has no rate limit.
+pub(crate) fn take_rate_token() -> Option<Duration> {
+    CURRENT.with(|current| {
+        let current = current.borrow();
+        let session = current.as_ref()?;
+        let mut bucket = lock(&session.inner.bucket);
+        bucket.as_mut().map(|bucket| bucket.take(Instant::now()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use abort::Token;
+    use dispatch::Queue;
+    use error::Error;
+    use settings;
+
+    #[test]
+    fn run_scopes_settings() {
+        let session = Session::new();
+        let pause = Duration::from_millis(250);
+        session.set_settings(Settings { pause, ..Settings::new() });
+
+        let inner = session.run(|| {
+            assert_eq!(current(), Some(session.clone()));
+            Session::new().run(settings::current)
+        });
+        assert_eq!(inner, Settings::new());
+        assert_eq!(session.run(settings::current).pause, pause);
+        assert_eq!(current(), None);
+    }
+
+    #[test]
+    fn rate_limit_scoped_to_session() {
+        let session = Session::new();
+        session.limit_rate(RateLimit::new(1, Duration::from_secs(60)));
+        assert_eq!(take_rate_token(), None);
+        session.run(|| {
+            assert_eq!(take_rate_token(), Some(Duration::from_secs(0)));
+            assert!(take_rate_token() > Some(Duration::from_secs(59)));
+            assert_eq!(Session::new().run(take_rate_token), None);
+        });
+        session.clear_rate_limit();
+        assert_eq!(session.run(take_rate_token), None);
+    }
+
+    #[test]
+    fn abort_scoped_to_session() {
+        let (session, other) = (Session::new(), Session::new());
+        let token = session.run(Token::new);
+        let other_token = other.run(Token::new);
+        session.abort();
+        assert_eq!(token.check(), Err(Error::Aborted));
+        assert!(other_token.check().is_ok());
+        assert!(session.run(Token::new).check().is_ok());
+    }
+
+    #[test]
+    fn queue_tasks_run_in_session() {
+        let queue = Queue::new("test-session");
+        let session = Session::new();
+        assert_eq!(session.run(|| queue.exec_sync(current)), Some(session.clone()));
+        assert_eq!(queue.exec_sync(current), None);
+
+        // Aborting the session cuts its delays short
+        let start = Instant::now();
+        session.run(|| queue.delay(Duration::from_secs(60)));
+        thread::sleep(Duration::from_millis(20));
+        session.abort();
+        queue.wait();
+        assert!(start.elapsed() < Duration::from_secs(30));
+    }
+
+    #[test]
+    #[should_panic(expected = "reserved")]
+    fn replacement_marker_tag() {
+        Session::new().set_source_tag(REPLACEMENT_MARKER);
+    }
+}
@@ -0,0 +1,370 @@
+//! 📼 A flight recorder for long unattended runs.
+//!
+//! A [`SessionRecorder`](struct.SessionRecorder.html) periodically saves a
+//! downscaled screenshot along with the events synthesized since the last
+//! one, so that a failure hours into a run can be looked into afterwards.
+//!
+//! The output directory holds numbered segments, each a bundle in the same
+//! format as [`trace::export`](../trace/fn.export.html). Once the segments
+//! hold more than the allowed number of bytes, the oldest ones are deleted,
+//! including those left by earlier sessions in the same directory.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use auto::session_recorder;
+//!
+//! let recorder = session_recorder::start(Duration::from_secs(2), "flight").unwrap();
+//!
+//! // ... run the automation ...
+//!
+//! recorder.finish().unwrap();
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+use trace::{self, BundleWriter, Entry, EntryKind};
+
+/// The number of segments that the allowed disk usage is split across.
+const SEGMENTS: u64 = 8;
+
+/// The number of events kept for the recorder if tracing was disabled.
+const TRACE_CAPACITY: usize = 10_000;
+
+/// Starts recording a screenshot every `interval` into `dir` with the default
+/// [`Options`](struct.Options.html).
+#[inline]
+pub fn start<P: AsRef<Path>>(interval: Duration, dir: P) -> io::Result<SessionRecorder> {
+    Options::new(interval).start(dir)
+}
+
+/// Options for recording a session.
+#[derive(Copy, Clone, Debug)]
+pub struct Options {
+    interval: Duration,
+    max_width: usize,
+    max_bytes: u64,
+}
+
+impl Options {
+    /// Creates options for taking a screenshot every `interval`, at most 1280
+    /// pixels wide, using at most 256 MiB of disk space.
+    #[inline]
+    pub fn new(interval: Duration) -> Options {
+        Options {
+            interval,
+            max_width: 1280,
+            max_bytes: 256 << 20,
+        }
+    }
+
+    /// Sets the width in pixels that screenshots are downscaled to fit.
+    pub fn max_width(&mut self, width: usize) -> &mut Options {
+        self.max_width = width.max(1);
+        self
+    }
+
+    /// Sets roughly how many bytes the recorded segments may use in total.
+    pub fn max_bytes(&mut self, bytes: u64) -> &mut Options {
+        self.max_bytes = bytes;
+        self
+    }
+
+    /// Starts recording into `dir`, creating it if needed.
+    ///
+    /// If tracing is disabled, it is enabled until the recorder finishes so
+    /// that synthesized events are kept.
+    pub fn start<P: AsRef<Path>>(&self, dir: P) -> io::Result<SessionRecorder> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let segments = Segments::open(dir, (self.max_bytes / SEGMENTS).max(1))?;
+
+        let enabled_trace = !trace::is_enabled();
+        if enabled_trace {
+            trace::enable(TRACE_CAPACITY);
+        }
+
+        let (stop, stopped) = mpsc::channel();
+        let options = *self;
+        let thread = thread::Builder::new()
+            .name("auto-session-recorder".into())
+            .spawn(move || {
+                let result = options.run(segments, &stopped);
+                if enabled_trace {
+                    trace::disable();
+                }
+                result
+            })?;
+        Ok(SessionRecorder { stop, thread: Some(thread) })
+    }
+
+    fn run(&self, mut segments: Segments, stopped: &Receiver<()>) -> io::Result<()> {
+        let mut since = SystemTime::now();
+        let mut last_image = None;
+        loop {
+            let time = SystemTime::now();
+            let image = imp::capture().map(|image| {
+                let factor = image.width().div_ceil(self.max_width);
+                Arc::new(image.downsample(factor.max(1)))
+            });
+
+            for entry in trace::entries(since) {
+                if let EntryKind::Event(_) = entry.kind {
+                    since = entry.time + Duration::from_nanos(1);
+                    segments.write(&entry)?;
+                }
+            }
+
+            // Nothing is lost by skipping a screen that has not changed
+            if let Some(image) = image {
+                if last_image.as_ref() != Some(&image) {
+                    let kind = EntryKind::Screenshot(image.clone());
+                    segments.write(&Entry { time, kind })?;
+                    last_image = Some(image);
+                }
+            }
+            segments.flush()?;
+
+            // Waiting after the work is done keeps a slow capture from
+            // falling behind
+            match stopped.recv_timeout(self.interval) {
+                Err(RecvTimeoutError::Timeout) => continue,
+                _ => break,
+            }
+        }
+        for entry in trace::entries(since) {
+            if let EntryKind::Event(_) = entry.kind {
+                segments.write(&entry)?;
+            }
+        }
+        segments.flush()
+    }
+}
+
+/// Numbered bundles in a directory, with the oldest deleted to stay within a
+/// total size.
+struct Segments {
+    dir: PathBuf,
+    segment_bytes: u64,
+    finished: VecDeque<(PathBuf, u64)>,
+    current: BundleWriter,
+    next: u64,
+}
+
+impl Segments {
+    fn open(dir: &Path, segment_bytes: u64) -> io::Result<Segments> {
+        // Only directories written as segments are adopted, and later pruned
+        let mut existing = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let number = entry.file_name().to_str().and_then(|name| {
+                name.parse::<u64>().ok().filter(|&number| segment_path(dir, number) == entry.path())
+            });
+            if let Some(number) = number {
+                if entry.file_type()?.is_dir() && entry.path().join("trace.jsonl").is_file() {
+                    existing.push((number, entry.path()));
+                }
+            }
+        }
+        existing.sort();
+
+        let next = existing.last().map_or(0, |&(number, _)| number) + 1;
+        let mut finished = VecDeque::new();
+        for (_, path) in existing {
+            let size = dir_size(&path)?;
+            finished.push_back((path, size));
+        }
+        let current = BundleWriter::create(&segment_path(dir, next))?;
+        let mut segments = Segments {
+            dir: dir.to_owned(),
+            segment_bytes,
+            finished,
+            current,
+            next: next + 1,
+        };
+        segments.prune()?;
+        Ok(segments)
+    }
+
+    fn write(&mut self, entry: &Entry) -> io::Result<()> {
+        self.current.write(entry)?;
+        if self.current.bytes() >= self.segment_bytes {
+            self.rotate()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.current.flush()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.current.flush()?;
+        let path = segment_path(&self.dir, self.next);
+        let done = ::std::mem::replace(&mut self.current, BundleWriter::create(&path)?);
+        self.finished.push_back((segment_path(&self.dir, self.next - 1), done.bytes()));
+        self.next += 1;
+        self.prune()
+    }
+
+    /// Deletes the oldest finished segments until all of them, plus one full
+    /// segment in progress, fit within the total size.
+    fn prune(&mut self) -> io::Result<()> {
+        let limit = self.segment_bytes * (SEGMENTS - 1);
+        let mut total: u64 = self.finished.iter().map(|&(_, size)| size).sum();
+        while total > limit {
+            match self.finished.pop_front() {
+                Some((path, size)) => {
+                    fs::remove_dir_all(path)?;
+                    total -= size;
+                },
+                None => break,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn segment_path(dir: &Path, number: u64) -> PathBuf {
+    dir.join(format!("{:06}", number))
+}
+
+fn dir_size(path: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        size += if meta.is_dir() { dir_size(&entry.path())? } else { meta.len() };
+    }
+    Ok(size)
+}
+
+/// A background thread that records a session until finished or dropped.
+pub struct SessionRecorder {
+    stop: Sender<()>,
+    thread: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl fmt::Debug for SessionRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SessionRecorder").finish()
+    }
+}
+
+impl SessionRecorder {
+    /// Stops recording once the current screenshot is saved, returning the
+    /// first error encountered while writing to disk.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.stop_thread()
+    }
+
+    fn stop_thread(&mut self) -> io::Result<()> {
+        let _ = self.stop.send(());
+        match self.thread.take().map(|thread| thread.join()) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => Err(io::Error::other("session recorder thread panicked")),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for SessionRecorder {
+    fn drop(&mut self) {
+        let _ = self.stop_thread();
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use image::Image;
+    use os::screen::Display;
+
+    pub fn capture() -> Option<Image> {
+        Display::main().capture().ok()
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use image::Image;
+    use os::screen;
+
+    pub fn capture() -> Option<Image> {
+        screen::capture().ok()
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod imp {
+    use image::Image;
+
+    pub fn capture() -> Option<Image> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use events::InputEvent;
+    use keyboard::Key;
+
+    #[test]
+    fn rotation() {
+        let dir = ::std::env::temp_dir().join(format!("auto-session-{}", ::std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let entry = Entry {
+            time: SystemTime::now(),
+            kind: EntryKind::Event(InputEvent::KeyDown(Key::A)),
+        };
+        let mut segments = Segments::open(&dir, 200).unwrap();
+        for _ in 0..100 {
+            segments.write(&entry).unwrap();
+        }
+        segments.flush().unwrap();
+
+        let count = || fs::read_dir(&dir).unwrap().count() as u64;
+        let before = count();
+        assert!(before > 1 && before <= SEGMENTS, "{} segments", before);
+        assert!(dir_size(&dir).unwrap() <= 200 * SEGMENTS);
+
+        // Segments from an earlier session count toward the limit
+        drop(segments);
+        let mut segments = Segments::open(&dir, 200).unwrap();
+        for _ in 0..20 {
+            segments.write(&entry).unwrap();
+        }
+        assert!(count() <= SEGMENTS);
+        assert!(dir_size(&dir).unwrap() <= 200 * SEGMENTS);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn foreign_directories() {
+        let dir = ::std::env::temp_dir().join(format!("auto-session-foreign-{}", ::std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for name in &["000099", "42", "+00007"] {
+            fs::create_dir_all(dir.join(name)).unwrap();
+        }
+
+        let segments = Segments::open(&dir, 200).unwrap();
+        assert!(segments.finished.is_empty());
+        assert!(dir.join("000001").join("trace.jsonl").is_file());
+        for name in &["000099", "42", "+00007"] {
+            assert!(dir.join(name).is_dir());
+        }
+        drop(segments);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
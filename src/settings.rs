@@ -0,0 +1,115 @@
+//! ⏱️ Default pacing of the high-level helpers.
+//!
+//! Applications take time to react to input, and events fired back to back
+//! can arrive before a window has opened or a field has taken focus. The
+//! [`Settings`](struct.Settings.html) slow every high-level helper down by the
+//! same amount, like the `PAUSE` setting of PyAutoGUI, so scripts need not
+//! sprinkle sleeps between their steps.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use auto::{keyboard, settings, Settings};
+//!
+//! settings::set_global(Settings {
+//!     pause: Duration::from_millis(100),
+//!     move_duration: Duration::from_millis(250),
+//!     typing_delay: Duration::from_millis(20),
+//! });
+//! keyboard::type_text("slow and steady").unwrap();
+//! ```
+
+use std::cell::Cell;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use abort::Token;
+use error::Result;
+use session;
+
+/// Delays applied by the high-level helpers of the crate.
+///
+/// The default settings have no delays, so every helper returns as soon as
+/// its events are posted.
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct Settings {
+    /// The pause after each high-level action, such as a click, shortcut, or
+    /// scroll.
+    ///
+    /// Actions built from other actions, such as a
+    /// [`Sequence`](../keyboard/struct.Sequence.html), pause once at the end.
+    pub pause: Duration,
+    /// How long [`mouse::move_to`](../mouse/fn.move_to.html) takes to move the
+    /// mouse, which jumps there if this is zero.
+    pub move_duration: Duration,
+    /// The delay between characters typed by
+    /// [`keyboard::type_text`](../keyboard/fn.type_text.html).
+    pub typing_delay: Duration,
+}
+
+impl Settings {
+    /// Creates settings without any delays.
+    #[inline]
+    pub fn new() -> Settings {
+        Settings::default()
+    }
+}
+
+lazy_static! {
+    static ref GLOBAL: Mutex<Settings> = Mutex::default();
+}
+
+thread_local! {
+    /// Whether an action is being paced on this thread, so that actions built
+    /// from other actions only pause once.
+    static PACING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Returns the settings used by every thread.
+pub fn global() -> Settings {
+    *GLOBAL.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Replaces the settings used by every thread.
+pub fn set_global(settings: Settings) {
+    *GLOBAL.lock().unwrap_or_else(|e| e.into_inner()) = settings;
+}
+
+/// Returns the settings that apply to actions on the current thread, which
+/// are those of its [session](../session/index.html) if it entered one.
+#[inline]
+pub fn current() -> Settings {
+    session::current_settings().unwrap_or_else(global)
+}
+
+/// Performs the action `f`, then pauses for the current
+/// [`pause`](struct.Settings.html#structfield.pause) if it succeeded and is
+/// not part of another action.
+///
+/// The pause ends early with
+/// [`Error::Aborted`](../error/enum.Error.html#variant.Aborted) if the
+/// [kill switch](../abort/index.html) is pressed.
+pub(crate) fn paced<T, F>(f: F) -> Result<T>
+    where F: FnOnce() -> Result<T>
+{
+    if PACING.with(Cell::get) {
+        return f();
+    }
+    let value = {
+        struct Reset;
+        impl Drop for Reset {
+            fn drop(&mut self) {
+                PACING.with(|p| p.set(false));
+            }
+        }
+        PACING.with(|p| p.set(true));
+        let _reset = Reset;
+        f()?
+    };
+    let pause = current().pause;
+    if pause > Duration::from_secs(0) {
+        Token::new().sleep(pause)?;
+    }
+    Ok(value)
+}
@@ -0,0 +1,353 @@
+//! 🕰️ Tracing what automation saw and did over time.
+//!
+//! While enabled, the trace keeps every event synthesized through
+//! [`events::post`](../events/fn.post.html) along with screenshots, either
+//! added directly or taken periodically with
+//! [`capture_every`](fn.capture_every.html). The trace can be exported with
+//! [`export`](fn.export.html) as a bundle for external viewers.
+//!
+//! # Bundle Format
+//!
+//! An exported bundle is a directory containing `trace.jsonl` and an `images`
+//! directory. Each line of `trace.jsonl` is a JSON object with a `time_ms`
+//! field holding milliseconds since the Unix epoch and a `kind` field:
+//!
+//! - `"event"` entries hold the synthesized event in an `event` object whose
+//!   `type` is one of `key-down`, `key-up`, `button-down`, `button-up`,
+//!   `move`, `scroll`, `char`, `media`, `pan`, or `pinch`
+//!
+//! - `"screenshot"` entries hold the `width`, `height`, and `file` of a BMP
+//!   image, relative to the bundle directory
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::time::SystemTime;
+//! use auto::trace;
+//!
+//! trace::enable(10_000);
+//! let start = SystemTime::now();
+//!
+//! // ... run the automation ...
+//!
+//! trace::export("trace-bundle", start).unwrap();
+//! ```
+
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use events::{Gesture, InputEvent};
+use image::Image;
+use json;
+
+lazy_static! {
+    static ref TRACE: Mutex<Option<Buffer>> = Mutex::default();
+}
+
+struct Buffer {
+    capacity: usize,
+    entries: VecDeque<Entry>,
+}
+
+fn lock() -> MutexGuard<'static, Option<Buffer>> {
+    TRACE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Something that happened at a point in time during tracing.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    /// The time at which the entry was recorded.
+    pub time: SystemTime,
+    /// What was recorded.
+    pub kind: EntryKind,
+}
+
+/// The contents of an [`Entry`](struct.Entry.html).
+#[derive(Clone, Debug)]
+pub enum EntryKind {
+    /// An event was synthesized.
+    Event(InputEvent),
+    /// A screenshot was taken.
+    Screenshot(Arc<Image>),
+}
+
+/// Starts tracing, keeping at most the `capacity` most recent entries.
+///
+/// If tracing is already enabled, its capacity is changed and existing
+/// entries are kept.
+pub fn enable(capacity: usize) {
+    let mut trace = lock();
+    let buffer = trace.get_or_insert_with(|| Buffer {
+        capacity,
+        entries: VecDeque::new(),
+    });
+    buffer.capacity = capacity;
+    while buffer.entries.len() > capacity {
+        buffer.entries.pop_front();
+    }
+}
+
+/// Stops tracing and discards all entries.
+pub fn disable() {
+    *lock() = None;
+}
+
+/// Returns whether tracing is enabled.
+pub fn is_enabled() -> bool {
+    lock().is_some()
+}
+
+fn record(kind: EntryKind) {
+    if let Some(ref mut buffer) = *lock() {
+        if buffer.capacity == 0 {
+            return;
+        }
+        if buffer.entries.len() == buffer.capacity {
+            buffer.entries.pop_front();
+        }
+        buffer.entries.push_back(Entry { time: SystemTime::now(), kind });
+    }
+}
+
+/// Records that `event` was synthesized.
+pub(crate) fn record_event(event: &InputEvent) {
+    record(EntryKind::Event(*event));
+}
+
+/// Adds `image` to the trace as a screenshot taken now.
+///
+/// Does nothing if tracing is disabled.
+pub fn record_screenshot(image: Image) {
+    record(EntryKind::Screenshot(Arc::new(image)));
+}
+
+/// Returns the entries recorded at or after `since`, oldest first.
+pub fn entries(since: SystemTime) -> Vec<Entry> {
+    match *lock() {
+        Some(ref buffer) => {
+            buffer.entries.iter().filter(|e| e.time >= since).cloned().collect()
+        },
+        None => Vec::new(),
+    }
+}
+
+/// A background thread that adds screenshots to the trace until dropped.
+pub struct Capturer {
+    stop: mpsc::Sender<()>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ::std::fmt::Debug for Capturer {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        f.debug_struct("Capturer").finish()
+    }
+}
+
+impl Drop for Capturer {
+    fn drop(&mut self) {
+        let _ = self.stop.send(());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Adds a screenshot produced by `source` to the trace every `interval`
+/// until the returned capturer is dropped.
+///
+/// Screenshots for which `source` returns `None` are skipped.
+pub fn capture_every<F>(interval: Duration, mut source: F) -> Capturer
+    where F: FnMut() -> Option<Image> + Send + 'static
+{
+    let (stop, stopped) = mpsc::channel();
+    let thread = thread::Builder::new()
+        .name("auto-trace-capture".into())
+        .spawn(move || loop {
+            if is_enabled() {
+                if let Some(image) = source() {
+                    record_screenshot(image);
+                }
+            }
+            match stopped.recv_timeout(interval) {
+                Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                _ => break,
+            }
+        })
+        .expect("failed to spawn trace capture thread");
+    Capturer { stop, thread: Some(thread) }
+}
+
+/// Writes the entries recorded at or after `since` as a bundle in the
+/// directory at `path`, creating it if needed.
+pub fn export<P: AsRef<Path>>(path: P, since: SystemTime) -> io::Result<()> {
+    let mut bundle = BundleWriter::create(path.as_ref())?;
+    for entry in entries(since) {
+        bundle.write(&entry)?;
+    }
+    bundle.flush()
+}
+
+/// Writes entries to a bundle directory one at a time.
+pub(crate) struct BundleWriter {
+    dir: PathBuf,
+    out: BufWriter<File>,
+    line: String,
+    image_count: usize,
+    bytes: u64,
+}
+
+impl BundleWriter {
+    /// Creates a bundle in `dir`, replacing any trace already in it.
+    pub fn create(dir: &Path) -> io::Result<BundleWriter> {
+        fs::create_dir_all(dir.join("images"))?;
+        Ok(BundleWriter {
+            dir: dir.to_owned(),
+            out: BufWriter::new(File::create(dir.join("trace.jsonl"))?),
+            line: String::new(),
+            image_count: 0,
+            bytes: 0,
+        })
+    }
+
+    /// Returns the number of bytes written to the bundle so far.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Appends `entry`, writing its image if it is a screenshot.
+    pub fn write(&mut self, entry: &Entry) -> io::Result<()> {
+        let line = &mut self.line;
+        line.clear();
+        line.push_str("{\"time_ms\":");
+        json::write_f64(line, time_ms(entry.time));
+        match entry.kind {
+            EntryKind::Event(ref event) => {
+                line.push_str(",\"kind\":\"event\",\"event\":");
+                write_event(line, event);
+            },
+            EntryKind::Screenshot(ref image) => {
+                self.image_count += 1;
+                let file = format!("images/{:06}.bmp", self.image_count);
+                let mut out = BufWriter::new(File::create(self.dir.join(&file))?);
+                image.write_bmp(&mut out)?;
+                out.flush()?;
+                self.bytes += 54 + (image.width() * image.height() * 4) as u64;
+
+                line.push_str(",\"kind\":\"screenshot\",\"width\":");
+                line.push_str(&image.width().to_string());
+                line.push_str(",\"height\":");
+                line.push_str(&image.height().to_string());
+                line.push_str(",\"file\":");
+                json::write_str(line, &file);
+            },
+        }
+        line.push_str("}\n");
+        self.out.write_all(line.as_bytes())?;
+        self.bytes += line.len() as u64;
+        Ok(())
+    }
+
+    /// Flushes buffered lines to disk.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+fn time_ms(time: SystemTime) -> f64 {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    since_epoch.as_secs_f64() * 1000.0
+}
+
+fn write_event(out: &mut String, event: &InputEvent) {
+    fn write_location(out: &mut String, (x, y): (f64, f64)) {
+        out.push_str(",\"x\":");
+        json::write_f64(out, x);
+        out.push_str(",\"y\":");
+        json::write_f64(out, y);
+    }
+
+    out.push_str("{\"type\":");
+    match *event {
+        InputEvent::KeyDown(key) | InputEvent::KeyUp(key) => {
+            let down = matches!(*event, InputEvent::KeyDown(_));
+            json::write_str(out, if down { "key-down" } else { "key-up" });
+            out.push_str(",\"key\":");
+            json::write_str(out, &key.to_string());
+        },
+        InputEvent::ButtonDown(button, location) | InputEvent::ButtonUp(button, location) => {
+            let down = matches!(*event, InputEvent::ButtonDown(..));
+            json::write_str(out, if down { "button-down" } else { "button-up" });
+            out.push_str(",\"button\":");
+            json::write_str(out, &button.to_string());
+            write_location(out, location);
+        },
+        InputEvent::MouseMoved(location) => {
+            json::write_str(out, "move");
+            write_location(out, location);
+        },
+        InputEvent::Scroll { dx, dy } => {
+            json::write_str(out, "scroll");
+            out.push_str(&format!(",\"dx\":{},\"dy\":{}", dx, dy));
+        },
+        InputEvent::Char(c) => {
+            json::write_str(out, "char");
+            out.push_str(",\"char\":");
+            json::write_str(out, c.encode_utf8(&mut [0; 4]));
+        },
+        InputEvent::Media(key) => {
+            json::write_str(out, "media");
+            out.push_str(",\"key\":");
+            json::write_str(out, key.name());
+        },
+        InputEvent::Gesture(gesture, phase, location) => {
+            match gesture {
+                Gesture::Pan { dx, dy } => {
+                    json::write_str(out, "pan");
+                    out.push_str(",\"dx\":");
+                    json::write_f64(out, dx);
+                    out.push_str(",\"dy\":");
+                    json::write_f64(out, dy);
+                },
+                Gesture::Pinch { scale } => {
+                    json::write_str(out, "pinch");
+                    out.push_str(",\"scale\":");
+                    json::write_f64(out, scale);
+                },
+            }
+            out.push_str(",\"phase\":");
+            json::write_str(out, phase.name());
+            write_location(out, location);
+        },
+    }
+    out.push('}');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keyboard::{Key, MediaKey};
+
+    #[test]
+    fn event_json() {
+        let mut out = String::new();
+        write_event(&mut out, &InputEvent::KeyUp(Key::PageUp));
+        assert_eq!(out, r#"{"type":"key-up","key":"page-up"}"#);
+
+        out.clear();
+        write_event(&mut out, &InputEvent::MouseMoved((1.5, 2.0)));
+        assert_eq!(out, r#"{"type":"move","x":1.5,"y":2}"#);
+
+        out.clear();
+        write_event(&mut out, &InputEvent::Char('é'));
+        assert_eq!(out, r#"{"type":"char","char":"é"}"#);
+
+        out.clear();
+        write_event(&mut out, &InputEvent::Media(MediaKey::PlayPause));
+        assert_eq!(out, r#"{"type":"media","key":"play-pause"}"#);
+    }
+}
@@ -0,0 +1,164 @@
+//! 📐 Transforming coordinates between automation code and the screen.
+//!
+//! A transform lets automation written for one coordinate space run against
+//! another, such as a virtual machine shown letterboxed in a window, a scaled
+//! remote desktop stream, or a mirrored display, without changing every call
+//! site.
+//!
+//! The outgoing transform is applied to the locations of every event
+//! synthesized through [`events::post`](../events/fn.post.html). The incoming
+//! transform is applied to every location observed through
+//! [`events::listen`](../events/fn.listen.html) and
+//! [`mouse::location`](../mouse/fn.location.html), and should undo the
+//! outgoing one.
+//!
+//! # Examples
+//!
+//! ```
+//! use auto::transform;
+//!
+//! // The automation targets a VM whose display is shown at half size
+//! // starting at (100, 50)
+//! transform::set(|(x, y)| (100.0 + x / 2.0, 50.0 + y / 2.0));
+//! transform::set_incoming(|(x, y)| ((x - 100.0) * 2.0, (y - 50.0) * 2.0));
+//!
+//! assert_eq!(transform::outgoing((200.0, 100.0)), (200.0, 100.0));
+//! # transform::clear();
+//! ```
+
+use std::sync::{Arc, RwLock};
+
+use events::{Gesture, InputEvent, Location};
+use mirror::Mapping;
+
+type Function = Arc<dyn Fn(Location) -> Location + Send + Sync>;
+
+#[derive(Default)]
+struct Transforms {
+    outgoing: Option<Function>,
+    incoming: Option<Function>,
+}
+
+lazy_static! {
+    static ref TRANSFORMS: RwLock<Transforms> = RwLock::default();
+}
+
+fn update<F: FnOnce(&mut Transforms)>(f: F) {
+    f(&mut TRANSFORMS.write().unwrap_or_else(|e| e.into_inner()));
+}
+
+fn function(incoming: bool) -> Option<Function> {
+    let transforms = TRANSFORMS.read().unwrap_or_else(|e| e.into_inner());
+    if incoming {
+        transforms.incoming.clone()
+    } else {
+        transforms.outgoing.clone()
+    }
+}
+
+/// Sets the transform applied to the locations of synthesized events,
+/// replacing any previous one.
+pub fn set<F>(f: F)
+    where F: Fn(Location) -> Location + Send + Sync + 'static
+{
+    update(|t| t.outgoing = Some(Arc::new(f)));
+}
+
+/// Sets the transform applied to observed locations, replacing any previous
+/// one.
+pub fn set_incoming<F>(f: F)
+    where F: Fn(Location) -> Location + Send + Sync + 'static
+{
+    update(|t| t.incoming = Some(Arc::new(f)));
+}
+
+/// Sets both transforms from `mapping`, which maps from the space that the
+/// automation targets into the space of the screen.
+pub fn set_mapping(mapping: Mapping) {
+    let inverse = mapping.inverse();
+    update(|t| {
+        t.outgoing = Some(Arc::new(move |p| mapping.map_location(p)));
+        t.incoming = Some(Arc::new(move |p| inverse.map_location(p)));
+    });
+}
+
+/// Removes both transforms.
+pub fn clear() {
+    update(|t| *t = Transforms::default());
+}
+
+/// Applies the outgoing transform to `location`.
+#[inline]
+pub fn outgoing(location: Location) -> Location {
+    function(false).map_or(location, |f| f(location))
+}
+
+/// Applies the incoming transform to `location`.
+#[inline]
+pub fn incoming(location: Location) -> Location {
+    function(true).map_or(location, |f| f(location))
+}
+
+/// Applies the outgoing transform to the locations in `event`.
+#[inline]
+pub(crate) fn outgoing_event(event: &InputEvent) -> InputEvent {
+    map_event(event, function(false))
+}
+
+/// Applies the incoming transform to the locations in `event`.
+#[inline]
+pub(crate) fn incoming_event(event: &InputEvent) -> InputEvent {
+    map_event(event, function(true))
+}
+
+fn map_event(event: &InputEvent, f: Option<Function>) -> InputEvent {
+    match f {
+        Some(f) => map_locations(event, &*f),
+        None => *event,
+    }
+}
+
+/// Applies `f` to the locations in `event`, including the end of a pan.
+pub(crate) fn map_locations(event: &InputEvent, f: &dyn Fn(Location) -> Location) -> InputEvent {
+    match *event {
+        InputEvent::ButtonDown(button, location) => InputEvent::ButtonDown(button, f(location)),
+        InputEvent::ButtonUp(button, location) => InputEvent::ButtonUp(button, f(location)),
+        InputEvent::MouseMoved(location) => InputEvent::MouseMoved(f(location)),
+        InputEvent::Gesture(gesture, phase, location) => {
+            let gesture = match gesture {
+                Gesture::Pan { dx, dy } => {
+                    let (x0, y0) = f(location);
+                    let (x1, y1) = f((location.0 + dx, location.1 + dy));
+                    Gesture::Pan { dx: x1 - x0, dy: y1 - y0 }
+                },
+                pinch => pinch,
+            };
+            InputEvent::Gesture(gesture, phase, f(location))
+        },
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use events::Phase;
+    use mouse::Button;
+
+    #[test]
+    fn event_locations() {
+        let double = Some(Arc::new(|(x, y): Location| (x * 2.0, y * 2.0 + 1.0)) as Function);
+
+        let event = InputEvent::ButtonUp(Button::Left, (3.0, 4.0));
+        assert_eq!(map_event(&event, double.clone()), InputEvent::ButtonUp(Button::Left, (6.0, 9.0)));
+
+        let pan = InputEvent::Gesture(Gesture::Pan { dx: 1.0, dy: -2.0 }, Phase::Began, (0.0, 0.0));
+        assert_eq!(
+            map_event(&pan, double.clone()),
+            InputEvent::Gesture(Gesture::Pan { dx: 2.0, dy: -4.0 }, Phase::Began, (0.0, 1.0)),
+        );
+
+        let scroll = InputEvent::Scroll { dx: 1, dy: 2 };
+        assert_eq!(map_event(&scroll, double), scroll);
+    }
+}
@@ -0,0 +1,1262 @@
+//! 🪟 Inspecting the user interfaces of other applications.
+//!
+//! On macOS, the process must be trusted for accessibility.
+//!
+//! # Examples
+//!
+//! Elements are found with queries, which wait for a match to appear:
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use auto::ui::{self, Role, Scope};
+//!
+//! ui::find(Scope::Frontmost)
+//!     .role(Role::Button)
+//!     .title("Save")
+//!     .timeout(Duration::from_secs(5))
+//!     .wait()
+//!     .unwrap()
+//!     .click()
+//!     .unwrap();
+//! ```
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use abort::Token;
+use error::{Error, Result};
+use events::{self, InputEvent, Location};
+use mouse;
+
+/// How long to wait between checks for a tooltip.
+const TOOLTIP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long to wait between searches for an element matching a query.
+const QUERY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long [`Query::wait`](struct.Query.html#method.wait) waits by default.
+const DEFAULT_QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Moves the mouse to `point` and returns the text of the tooltip that
+/// appears within `timeout`.
+///
+/// On macOS, if no tooltip appears in time, the help text of the element at
+/// `point` is returned instead, since that is what its tooltip would show.
+///
+/// On Windows, only standard tooltip controls are recognized.
+pub fn tooltip_at(point: Location, timeout: Duration) -> Option<String> {
+    events::post(&InputEvent::MouseMoved(point)).ok()?;
+
+    let deadline = Instant::now().checked_add(timeout);
+    loop {
+        if let Some(text) = imp::visible_tooltip(point) {
+            return Some(text);
+        }
+        let now = Instant::now();
+        if deadline.is_some_and(|deadline| now >= deadline) {
+            return imp::help_at(point);
+        }
+        thread::sleep(deadline.map_or(TOOLTIP_POLL_INTERVAL, |deadline| {
+            TOOLTIP_POLL_INTERVAL.min(deadline - now)
+        }));
+    }
+}
+
+/// The applications whose user interface events are delivered by
+/// [`events`](fn.events.html), or whose elements are searched by
+/// [`find`](fn.find.html).
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum Scope {
+    /// The application with a process identifier.
+    Application(u32),
+    /// The application that is frontmost when subscribing.
+    Frontmost,
+}
+
+/// The kind of a [`UiEvent`](struct.UiEvent.html).
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum UiEventKind {
+    /// A window was created.
+    WindowCreated,
+    /// The value of an element, such as the text of a field, changed.
+    ValueChanged,
+    /// Keyboard focus moved to another element.
+    FocusChanged,
+    /// An element was destroyed.
+    ElementDestroyed,
+}
+
+/// A change to the user interface of an application.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UiEvent {
+    /// What happened.
+    pub kind: UiEventKind,
+    /// The role of the element, such as `AXWindow`, if known.
+    pub role: Option<String>,
+    /// The title of the element, if it has one.
+    pub title: Option<String>,
+}
+
+/// A blocking iterator over user interface events, which unsubscribes when
+/// dropped.
+///
+/// Created by [`events`](fn.events.html).
+#[derive(Debug)]
+pub struct UiEvents {
+    events: mpsc::Receiver<UiEvent>,
+    _subscription: imp::Subscription,
+}
+
+impl Iterator for UiEvents {
+    type Item = UiEvent;
+
+    #[inline]
+    fn next(&mut self) -> Option<UiEvent> {
+        self.events.recv().ok()
+    }
+}
+
+impl UiEvents {
+    /// Returns the next event if one is available without blocking.
+    #[inline]
+    pub fn try_next(&mut self) -> Option<UiEvent> {
+        self.events.try_recv().ok()
+    }
+
+    /// Waits up to `timeout` for the next event.
+    #[inline]
+    pub fn next_timeout(&mut self, timeout: Duration) -> Option<UiEvent> {
+        self.events.recv_timeout(timeout).ok()
+    }
+}
+
+/// Subscribes to window creation, value change, focus change, and element
+/// destruction events from the applications in `scope`.
+///
+/// This allows waiting for the user interface to change without repeatedly
+/// walking the element tree, which is slow for deep hierarchies.
+///
+/// On macOS, this uses accessibility observers. On Windows, this uses
+/// WinEvents, whose events only carry the title of the window involved.
+///
+/// # Examples
+///
+/// ```no_run
+/// use auto::ui::{self, Scope, UiEventKind};
+///
+/// let events = ui::events(Scope::Frontmost).unwrap();
+/// for event in events {
+///     if event.kind == UiEventKind::WindowCreated {
+///         println!("window {:?} appeared", event.title);
+///         break;
+///     }
+/// }
+/// ```
+pub fn events(scope: Scope) -> Result<UiEvents> {
+    let (tx, events) = mpsc::channel();
+    let subscription = imp::subscribe(scope, tx)?;
+    Ok(UiEvents { events, _subscription: subscription })
+}
+
+/// The kind of a user interface [`Element`](struct.Element.html), common to
+/// all platforms.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum Role {
+    /// A push button.
+    Button,
+    /// A check box.
+    CheckBox,
+    /// A radio button.
+    RadioButton,
+    /// A combo box or pop-up button.
+    ComboBox,
+    /// A field for entering text.
+    TextField,
+    /// Text that cannot be edited.
+    Text,
+    /// A hyperlink.
+    Link,
+    /// An image.
+    Image,
+    /// A list.
+    List,
+    /// A table or grid.
+    Table,
+    /// A menu bar.
+    MenuBar,
+    /// A menu.
+    Menu,
+    /// An item of a menu or menu bar.
+    MenuItem,
+    /// A set of tabs.
+    Tab,
+    /// A slider.
+    Slider,
+    /// A progress bar.
+    ProgressBar,
+    /// A scroll bar.
+    ScrollBar,
+    /// A toolbar.
+    ToolBar,
+    /// A group of other elements.
+    Group,
+    /// A window.
+    Window,
+    /// An element of a kind not listed here.
+    Other,
+}
+
+/// An element of an application's user interface, found with a
+/// [`Query`](struct.Query.html).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Element {
+    inner: imp::Element,
+}
+
+impl Element {
+    /// Returns the kind of the element, if it has one.
+    #[inline]
+    pub fn role(&self) -> Option<Role> {
+        imp::role(&self.inner)
+    }
+
+    /// Returns the label shown for the element, such as the text of a button.
+    ///
+    /// On macOS, elements without a title, such as buttons showing an icon,
+    /// are labeled by their description instead.
+    #[inline]
+    pub fn title(&self) -> Option<String> {
+        imp::title(&self.inner).filter(|title| !title.is_empty())
+    }
+
+    /// Returns the value of the element if it is text, such as the contents
+    /// of a text field.
+    #[inline]
+    pub fn value(&self) -> Option<String> {
+        imp::value(&self.inner)
+    }
+
+    /// Returns the location of the upper-left corner of the element and its
+    /// size.
+    #[inline]
+    pub fn bounds(&self) -> Option<(Location, (f64, f64))> {
+        imp::bounds(&self.inner)
+    }
+
+    /// Returns the location of the center of the element.
+    pub fn center(&self) -> Option<Location> {
+        self.bounds().map(|((x, y), (width, height))| (x + width / 2.0, y + height / 2.0))
+    }
+
+    /// Performs the default action of the element, such as pressing a
+    /// button, through accessibility rather than the mouse.
+    ///
+    /// This works even if the element is covered by another window.
+    ///
+    /// Returns [`Error::Unsupported`](../error/enum.Error.html#variant.Unsupported)
+    /// if the element has no default action.
+    #[inline]
+    pub fn press(&self) -> Result<()> {
+        imp::press(&self.inner)
+    }
+
+    /// Moves the mouse to the center of the element and clicks the left
+    /// button, as a user would.
+    ///
+    /// Returns [`Error::Unsupported`](../error/enum.Error.html#variant.Unsupported)
+    /// if the element has no location on screen.
+    pub fn click(&self) -> Result<()> {
+        let center = self.center().ok_or(Error::Unsupported)?;
+        mouse::move_to(center)?;
+        mouse::click(mouse::Button::Left)
+    }
+
+    /// Starts a query for the elements contained in the element.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use auto::ui::{self, Role, Scope};
+    ///
+    /// let toolbar = ui::find(Scope::Frontmost).role(Role::ToolBar).wait().unwrap();
+    /// let buttons = toolbar.find().role(Role::Button).all().unwrap();
+    /// println!("{} buttons in the toolbar", buttons.len());
+    /// ```
+    #[inline]
+    pub fn find(&self) -> Query {
+        Query::new(Root::Element(self.clone()))
+    }
+}
+
+/// Where a [`Query`](struct.Query.html) searches.
+#[derive(Clone, Debug)]
+enum Root {
+    Scope(Scope),
+    Element(Element),
+}
+
+impl Root {
+    /// Returns up to `limit` elements that match `filter`, in depth-first
+    /// order.
+    fn find(&self, filter: &Filter, limit: usize) -> Result<Vec<Element>> {
+        let elements = match *self {
+            Root::Scope(scope) => imp::find(scope, filter, limit)?,
+            Root::Element(ref element) => imp::find_in(&element.inner, filter, limit)?,
+        };
+        Ok(elements.into_iter().map(|inner| Element { inner }).collect())
+    }
+}
+
+/// The conditions of a [`Query`](struct.Query.html), which each platform
+/// checks as early in its search as it can.
+#[derive(Clone, Debug, Default)]
+struct Filter {
+    role: Option<Role>,
+    title: Option<String>,
+    title_contains: Option<String>,
+}
+
+impl Filter {
+    /// Returns whether an element of `role` matches, calling `title` for its
+    /// title only if a condition needs it.
+    #[cfg(any(target_os = "macos", target_os = "windows", test))]
+    fn matches_properties<F>(&self, role: Option<Role>, title: F) -> bool
+        where F: FnOnce() -> Option<String>
+    {
+        if self.role.is_some() && role != self.role {
+            return false;
+        }
+        if self.title.is_none() && self.title_contains.is_none() {
+            return true;
+        }
+        let title = title().unwrap_or_default();
+        self.title.as_ref().is_none_or(|expected| title == *expected)
+            && self.title_contains.as_ref().is_none_or(|text| title.contains(text.as_str()))
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    fn matches(&self, element: &imp::Element) -> bool {
+        self.matches_properties(imp::role(element), || imp::title(element))
+    }
+}
+
+/// A search for elements of the applications in a [`Scope`](enum.Scope.html),
+/// or within an element, that match every condition given.
+///
+/// Created by [`find`](fn.find.html) and
+/// [`Element::find`](struct.Element.html#method.find).
+#[derive(Clone, Debug)]
+pub struct Query {
+    root: Root,
+    filter: Filter,
+    timeout: Duration,
+}
+
+/// Starts a query for elements of the applications in `scope`.
+///
+/// With [`Scope::Frontmost`](enum.Scope.html#variant.Frontmost), the
+/// frontmost application is looked up on every search, so queries follow
+/// whichever application is frontmost while waiting.
+///
+/// # Examples
+///
+/// ```no_run
+/// use auto::ui::{self, Role, Scope};
+///
+/// let fields = ui::find(Scope::Frontmost).role(Role::TextField).all().unwrap();
+/// println!("{} text fields", fields.len());
+/// ```
+#[inline]
+pub fn find(scope: Scope) -> Query {
+    Query::new(Root::Scope(scope))
+}
+
+impl Query {
+    fn new(root: Root) -> Query {
+        Query {
+            root,
+            filter: Filter::default(),
+            timeout: DEFAULT_QUERY_TIMEOUT,
+        }
+    }
+
+    /// Matches only elements of `role`.
+    #[inline]
+    pub fn role(&mut self, role: Role) -> &mut Query {
+        self.filter.role = Some(role);
+        self
+    }
+
+    /// Matches only elements whose [title](struct.Element.html#method.title)
+    /// is exactly `title`.
+    #[inline]
+    pub fn title(&mut self, title: &str) -> &mut Query {
+        self.filter.title = Some(title.into());
+        self
+    }
+
+    /// Matches only elements whose [title](struct.Element.html#method.title)
+    /// contains `text`.
+    #[inline]
+    pub fn title_contains(&mut self, text: &str) -> &mut Query {
+        self.filter.title_contains = Some(text.into());
+        self
+    }
+
+    /// Sets how long [`wait`](#method.wait) waits for a match. The default
+    /// is 5 seconds.
+    #[inline]
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Query {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns every element that matches right now, in depth-first order.
+    #[inline]
+    pub fn all(&self) -> Result<Vec<Element>> {
+        self.root.find(&self.filter, usize::MAX)
+    }
+
+    /// Returns the first element that matches right now, if any, without
+    /// searching the rest of the elements.
+    pub fn first(&self) -> Result<Option<Element>> {
+        Ok(self.root.find(&self.filter, 1)?.into_iter().next())
+    }
+
+    /// Searches until an element matches, returning the first one.
+    ///
+    /// Fails with [`Error::TimedOut`](../error/enum.Error.html#variant.TimedOut)
+    /// if nothing matches within the [timeout](#method.timeout), or with
+    /// [`Error::Aborted`](../error/enum.Error.html#variant.Aborted) if the
+    /// [kill switch](../abort/index.html) is triggered while waiting.
+    pub fn wait(&self) -> Result<Element> {
+        let token = Token::new();
+        let deadline = Instant::now().checked_add(self.timeout);
+        loop {
+            if let Some(element) = self.first()? {
+                return Ok(element);
+            }
+            let now = Instant::now();
+            if deadline.is_some_and(|deadline| now >= deadline) {
+                return Err(Error::TimedOut);
+            }
+            token.sleep(deadline.map_or(QUERY_POLL_INTERVAL, |deadline| {
+                QUERY_POLL_INTERVAL.min(deadline - now)
+            }))?;
+        }
+    }
+}
+
+/// A dialog, alert, or sheet shown by an application.
+///
+/// Found with [`dialogs`](fn.dialogs.html) and
+/// [`wait_for_dialog`](fn.wait_for_dialog.html).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dialog {
+    element: Element,
+}
+
+impl Dialog {
+    /// Returns the element of the dialog itself.
+    #[inline]
+    pub fn element(&self) -> &Element {
+        &self.element
+    }
+
+    /// Returns the title of the dialog, if it has one.
+    ///
+    /// Sheets on macOS have no title.
+    #[inline]
+    pub fn title(&self) -> Option<String> {
+        self.element.title()
+    }
+
+    /// Returns the buttons of the dialog.
+    #[inline]
+    pub fn buttons(&self) -> Result<Vec<Element>> {
+        self.element.find().role(Role::Button).all()
+    }
+
+    /// Starts a query for the button labeled `label`, which may be
+    /// [waited for](struct.Query.html#method.wait) and then clicked.
+    pub fn button(&self, label: &str) -> Query {
+        let mut query = self.element.find();
+        query.role(Role::Button).title(label);
+        query
+    }
+
+    /// Presses the default button of the dialog, such as "OK" or "Save", or
+    /// its only button.
+    ///
+    /// Returns [`Error::Unsupported`](../error/enum.Error.html#variant.Unsupported)
+    /// if the dialog has several buttons and none is marked as the default.
+    pub fn accept(&self) -> Result<()> {
+        self.press(imp::default_button(&self.element.inner))
+    }
+
+    /// Presses the cancel button of the dialog, or its only button.
+    ///
+    /// Returns [`Error::Unsupported`](../error/enum.Error.html#variant.Unsupported)
+    /// if the dialog has several buttons and none is marked as the cancel
+    /// button.
+    pub fn dismiss(&self) -> Result<()> {
+        self.press(imp::cancel_button(&self.element.inner))
+    }
+
+    /// Presses `button`, falling back to the only button of the dialog.
+    fn press(&self, button: Option<imp::Element>) -> Result<()> {
+        if let Some(inner) = button {
+            return Element { inner }.press();
+        }
+        let mut buttons = self.buttons()?;
+        if buttons.len() == 1 {
+            buttons.remove(0).press()
+        } else {
+            Err(Error::Unsupported)
+        }
+    }
+}
+
+/// Returns the dialogs, alerts, and sheets currently shown by the
+/// applications in `scope`.
+///
+/// On macOS, these are windows with a dialog subrole and sheets attached to
+/// windows. On Windows, these are top-level windows of the standard dialog
+/// class, or owned windows with a caption and a dialog frame, along with
+/// system prompts such as those for credentials, which are shown by other
+/// processes.
+pub fn dialogs(scope: Scope) -> Result<Vec<Dialog>> {
+    Ok(imp::dialogs(scope)?
+        .into_iter()
+        .map(|inner| Dialog { element: Element { inner } })
+        .collect())
+}
+
+/// Waits up to `timeout` for the applications in `scope` to show a dialog
+/// whose title contains `title`, returning the first one.
+///
+/// An empty `title` matches any dialog, including untitled sheets.
+///
+/// Fails with [`Error::TimedOut`](../error/enum.Error.html#variant.TimedOut)
+/// if no dialog appears in time, or with
+/// [`Error::Aborted`](../error/enum.Error.html#variant.Aborted) if the
+/// [kill switch](../abort/index.html) is triggered while waiting.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use auto::ui::{self, Scope};
+///
+/// // Decline to save if the editor asks while closing
+/// if let Ok(dialog) = ui::wait_for_dialog(Scope::Frontmost, "", Duration::from_secs(2)) {
+///     dialog.button("Don't Save").wait().unwrap().press().unwrap();
+/// }
+/// ```
+pub fn wait_for_dialog(scope: Scope, title: &str, timeout: Duration) -> Result<Dialog> {
+    let token = Token::new();
+    let deadline = Instant::now().checked_add(timeout);
+    loop {
+        let dialog = dialogs(scope)?
+            .into_iter()
+            .find(|dialog| title.is_empty() || dialog.title().is_some_and(|t| t.contains(title)));
+        if let Some(dialog) = dialog {
+            return Ok(dialog);
+        }
+        let now = Instant::now();
+        if deadline.is_some_and(|deadline| now >= deadline) {
+            return Err(Error::TimedOut);
+        }
+        token.sleep(deadline.map_or(QUERY_POLL_INTERVAL, |deadline| {
+            QUERY_POLL_INTERVAL.min(deadline - now)
+        }))?;
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use std::sync::mpsc::Sender;
+
+    use error::{Error, Result};
+    use events::Location;
+    use os::app::{App, Pid};
+    use os::ax::{Observer, ObserverHandle};
+    use os::permissions;
+    use super::{Filter, Role, Scope, UiEvent, UiEventKind};
+
+    pub use os::ax::Element;
+
+    const HELP_TAG_ROLE: &str = "AXHelpTag";
+
+    const SHEET_ROLE: &str = "AXSheet";
+
+    const DIALOG_SUBROLES: &[&str] = &["AXDialog", "AXSystemDialog"];
+
+    const ROLES: &[(&str, Role)] = &[
+        ("AXButton", Role::Button),
+        ("AXCheckBox", Role::CheckBox),
+        ("AXRadioButton", Role::RadioButton),
+        ("AXComboBox", Role::ComboBox),
+        ("AXPopUpButton", Role::ComboBox),
+        ("AXTextField", Role::TextField),
+        ("AXTextArea", Role::TextField),
+        ("AXStaticText", Role::Text),
+        ("AXLink", Role::Link),
+        ("AXImage", Role::Image),
+        ("AXList", Role::List),
+        ("AXTable", Role::Table),
+        ("AXOutline", Role::Table),
+        ("AXMenuBar", Role::MenuBar),
+        ("AXMenu", Role::Menu),
+        ("AXMenuItem", Role::MenuItem),
+        ("AXMenuBarItem", Role::MenuItem),
+        ("AXTabGroup", Role::Tab),
+        ("AXSlider", Role::Slider),
+        ("AXProgressIndicator", Role::ProgressBar),
+        ("AXScrollBar", Role::ScrollBar),
+        ("AXToolbar", Role::ToolBar),
+        ("AXGroup", Role::Group),
+        ("AXWindow", Role::Window),
+    ];
+
+    const NOTIFICATIONS: &[(&str, UiEventKind)] = &[
+        ("AXWindowCreated", UiEventKind::WindowCreated),
+        ("AXValueChanged", UiEventKind::ValueChanged),
+        ("AXFocusedUIElementChanged", UiEventKind::FocusChanged),
+        ("AXUIElementDestroyed", UiEventKind::ElementDestroyed),
+    ];
+
+    pub type Subscription = ObserverHandle;
+
+    fn pid(scope: Scope) -> Result<Pid> {
+        match scope {
+            Scope::Application(pid) => Ok(pid as Pid),
+            Scope::Frontmost => {
+                App::frontmost().and_then(|app| app.pid()).ok_or(Error::Unsupported)
+            },
+        }
+    }
+
+    pub fn subscribe(scope: Scope, tx: Sender<UiEvent>) -> Result<Subscription> {
+        let pid = pid(scope)?;
+        let names: Vec<&str> = NOTIFICATIONS.iter().map(|&(name, _)| name).collect();
+        Observer::spawn(pid, &names, move |element, notification| {
+            let kind = NOTIFICATIONS.iter().find(|&&(name, _)| name == notification);
+            if let Some(&(_, kind)) = kind {
+                // Destroyed elements can no longer be asked for attributes
+                let (role, title) = match kind {
+                    UiEventKind::ElementDestroyed => (None, None),
+                    _ => (element.role(), element.title()),
+                };
+                let _ = tx.send(UiEvent { kind, role, title });
+            }
+        })
+    }
+
+    /// Returns the text of a help tag element, which holds its text either
+    /// directly or in a static text child.
+    fn help_tag_text(tag: &Element) -> Option<String> {
+        tag.description()
+            .or_else(|| tag.value())
+            .or_else(|| tag.children().iter().filter_map(Element::value).next())
+            .filter(|text| !text.is_empty())
+    }
+
+    pub fn visible_tooltip(point: Location) -> Option<String> {
+        let pid = Element::system_wide().element_at(point).ok()?.pid()?;
+        let app = Element::application(pid);
+        app.children()
+            .into_iter()
+            .chain(app.windows())
+            .filter(|e| e.role().as_deref() == Some(HELP_TAG_ROLE))
+            .find_map(|tag| help_tag_text(&tag))
+    }
+
+    pub fn help_at(point: Location) -> Option<String> {
+        Element::system_wide()
+            .element_at(point)
+            .ok()?
+            .help()
+            .filter(|text| !text.is_empty())
+    }
+
+    pub fn find(scope: Scope, filter: &Filter, limit: usize) -> Result<Vec<Element>> {
+        // Untrusted processes see applications without any children
+        if !permissions::accessibility_granted() {
+            return Err(Error::PermissionDenied);
+        }
+        find_in(&Element::application(pid(scope)?), filter, limit)
+    }
+
+    pub fn find_in(root: &Element, filter: &Filter, limit: usize) -> Result<Vec<Element>> {
+        let mut found = Vec::new();
+        let mut pending = root.children();
+        pending.reverse();
+        while let Some(element) = pending.pop() {
+            if filter.matches(&element) {
+                found.push(element.clone());
+                if found.len() == limit {
+                    break;
+                }
+            }
+            pending.extend(element.children().into_iter().rev());
+        }
+        Ok(found)
+    }
+
+    pub fn dialogs(scope: Scope) -> Result<Vec<Element>> {
+        if !permissions::accessibility_granted() {
+            return Err(Error::PermissionDenied);
+        }
+        let mut dialogs = Vec::new();
+        for window in Element::application(pid(scope)?).windows() {
+            let subrole = window.string_attribute("AXSubrole");
+            let sheets = window
+                .children()
+                .into_iter()
+                .filter(|child| child.role().as_deref() == Some(SHEET_ROLE));
+            if subrole.as_deref().is_some_and(|subrole| DIALOG_SUBROLES.contains(&subrole)) {
+                dialogs.push(window.clone());
+            }
+            dialogs.extend(sheets);
+        }
+        Ok(dialogs)
+    }
+
+    #[inline]
+    pub fn default_button(dialog: &Element) -> Option<Element> {
+        dialog.element_attribute("AXDefaultButton")
+    }
+
+    #[inline]
+    pub fn cancel_button(dialog: &Element) -> Option<Element> {
+        dialog.element_attribute("AXCancelButton")
+    }
+
+    pub fn role(element: &Element) -> Option<Role> {
+        let role = element.role()?;
+        Some(ROLES.iter().find(|&&(name, _)| name == role).map_or(Role::Other, |&(_, role)| role))
+    }
+
+    pub fn title(element: &Element) -> Option<String> {
+        element.title().filter(|title| !title.is_empty()).or_else(|| element.description())
+    }
+
+    #[inline]
+    pub fn value(element: &Element) -> Option<String> {
+        element.value()
+    }
+
+    pub fn bounds(element: &Element) -> Option<(Location, (f64, f64))> {
+        Some((element.position()?, element.size()?))
+    }
+
+    #[inline]
+    pub fn press(element: &Element) -> Result<()> {
+        element.perform_action("AXPress")
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use std::ptr;
+
+    use std::mem;
+    use std::sync::mpsc::{self, Sender};
+    use std::thread::{self, JoinHandle};
+    use std::cell::RefCell;
+
+    use winapi::shared::minwindef::DWORD;
+    use winapi::shared::ntdef::LONG;
+    use winapi::shared::windef::{HWINEVENTHOOK, HWND};
+    use winapi::um::processthreadsapi::GetCurrentThreadId;
+    use winapi::um::winuser::{
+        FindWindowExW,
+        GetForegroundWindow,
+        GetMessageW,
+        GetWindow,
+        GetWindowLongW,
+        GetWindowTextW,
+        GetWindowThreadProcessId,
+        IsWindowVisible,
+        PeekMessageW,
+        PostThreadMessageW,
+        SetWinEventHook,
+        UnhookWinEvent,
+        DS_MODALFRAME,
+        GWL_EXSTYLE,
+        GWL_STYLE,
+        GW_OWNER,
+        PM_NOREMOVE,
+        WINEVENT_OUTOFCONTEXT,
+        WINEVENT_SKIPOWNPROCESS,
+        WM_QUIT,
+        WM_USER,
+        WS_CAPTION,
+        WS_EX_DLGMODALFRAME,
+        WS_EX_TOOLWINDOW,
+    };
+
+    use error::{OsError, Result};
+    use events::Location;
+    use os::uia::{Condition, ControlType};
+    use super::{Filter, Role, Scope, UiEvent, UiEventKind};
+
+    pub use os::uia::Element;
+
+    /// The window class of standard dialogs and message boxes.
+    const DIALOG_CLASS: &str = "#32770";
+
+    /// The window classes of prompts that the system shows on behalf of
+    /// applications, such as `CredUIPromptForWindowsCredentials`.
+    const SYSTEM_PROMPT_CLASSES: &[&str] = &["Credential Dialog Xaml Host"];
+
+    /// `IDOK` and `IDYES`
+    const DEFAULT_BUTTON_IDS: &[&str] = &["1", "6"];
+
+    /// `IDCANCEL`
+    const CANCEL_BUTTON_IDS: &[&str] = &["2"];
+
+    const ROLES: &[(ControlType, Role)] = &[
+        (ControlType::Button, Role::Button),
+        (ControlType::SplitButton, Role::Button),
+        (ControlType::CheckBox, Role::CheckBox),
+        (ControlType::RadioButton, Role::RadioButton),
+        (ControlType::ComboBox, Role::ComboBox),
+        (ControlType::Edit, Role::TextField),
+        (ControlType::Text, Role::Text),
+        (ControlType::Hyperlink, Role::Link),
+        (ControlType::Image, Role::Image),
+        (ControlType::List, Role::List),
+        (ControlType::Table, Role::Table),
+        (ControlType::DataGrid, Role::Table),
+        (ControlType::MenuBar, Role::MenuBar),
+        (ControlType::Menu, Role::Menu),
+        (ControlType::MenuItem, Role::MenuItem),
+        (ControlType::Tab, Role::Tab),
+        (ControlType::Slider, Role::Slider),
+        (ControlType::ProgressBar, Role::ProgressBar),
+        (ControlType::ScrollBar, Role::ScrollBar),
+        (ControlType::ToolBar, Role::ToolBar),
+        (ControlType::Group, Role::Group),
+        (ControlType::Window, Role::Window),
+    ];
+
+    const EVENT_OBJECT_CREATE: DWORD = 0x8000;
+    const EVENT_OBJECT_DESTROY: DWORD = 0x8001;
+    const EVENT_OBJECT_FOCUS: DWORD = 0x8005;
+    const EVENT_OBJECT_VALUECHANGE: DWORD = 0x800E;
+    const OBJID_WINDOW: LONG = 0;
+
+    thread_local! {
+        /// Where the hook on this thread sends its events, since WinEvent
+        /// callbacks take no context.
+        static SENDER: RefCell<Option<Sender<UiEvent>>> = const { RefCell::new(None) };
+    }
+
+    fn window_title(window: HWND) -> Option<String> {
+        let mut buf = [0u16; 512];
+        let len = unsafe { GetWindowTextW(window, buf.as_mut_ptr(), buf.len() as i32) };
+        if len > 0 {
+            Some(String::from_utf16_lossy(&buf[..len as usize]))
+        } else {
+            None
+        }
+    }
+
+    unsafe extern "system" fn hook_callback(
+        _hook: HWINEVENTHOOK,
+        event: DWORD,
+        window: HWND,
+        object: LONG,
+        _child: LONG,
+        _thread: DWORD,
+        _time: DWORD,
+    ) {
+        let kind = match event {
+            EVENT_OBJECT_CREATE if object == OBJID_WINDOW => UiEventKind::WindowCreated,
+            EVENT_OBJECT_DESTROY => UiEventKind::ElementDestroyed,
+            EVENT_OBJECT_FOCUS => UiEventKind::FocusChanged,
+            EVENT_OBJECT_VALUECHANGE => UiEventKind::ValueChanged,
+            _ => return,
+        };
+        let title = match kind {
+            UiEventKind::ElementDestroyed => None,
+            _ if window.is_null() => None,
+            _ => window_title(window),
+        };
+        SENDER.with(|sender| {
+            if let Some(ref sender) = *sender.borrow() {
+                let _ = sender.send(UiEvent { kind, role: None, title });
+            }
+        });
+    }
+
+    pub struct Subscription {
+        thread_id: DWORD,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl ::std::fmt::Debug for Subscription {
+        fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+            f.debug_struct("Subscription").field("thread_id", &self.thread_id).finish()
+        }
+    }
+
+    impl Drop for Subscription {
+        fn drop(&mut self) {
+            unsafe { PostThreadMessageW(self.thread_id, WM_QUIT, 0, 0) };
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    fn pid(scope: Scope) -> DWORD {
+        match scope {
+            Scope::Application(pid) => pid,
+            Scope::Frontmost => unsafe {
+                let mut pid = 0;
+                GetWindowThreadProcessId(GetForegroundWindow(), &mut pid);
+                pid
+            },
+        }
+    }
+
+    pub fn subscribe(scope: Scope, tx: Sender<UiEvent>) -> Result<Subscription> {
+        let pid = pid(scope);
+        let (ready_tx, ready_rx) = mpsc::channel();
+
+        // Out-of-context hooks call back on the thread that set them, which
+        // must pump messages
+        let thread = thread::Builder::new()
+            .name("auto-ui-events".into())
+            .spawn(move || unsafe {
+                let mut msg = mem::zeroed();
+                PeekMessageW(&mut msg, ptr::null_mut(), WM_USER, WM_USER, PM_NOREMOVE);
+
+                let hook = SetWinEventHook(
+                    EVENT_OBJECT_CREATE,
+                    EVENT_OBJECT_VALUECHANGE,
+                    ptr::null_mut(),
+                    Some(hook_callback),
+                    pid,
+                    0,
+                    WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS,
+                );
+                if hook.is_null() {
+                    let _ = ready_tx.send(Err(OsError::last()));
+                    return;
+                }
+                SENDER.with(|sender| *sender.borrow_mut() = Some(tx));
+                let _ = ready_tx.send(Ok(GetCurrentThreadId()));
+
+                while GetMessageW(&mut msg, ptr::null_mut(), 0, 0) > 0 {}
+                UnhookWinEvent(hook);
+            })
+            .expect("failed to spawn UI event thread");
+
+        match ready_rx.recv() {
+            Ok(Ok(thread_id)) => Ok(Subscription { thread_id, thread: Some(thread) }),
+            Ok(Err(err)) => {
+                let _ = thread.join();
+                Err(err.into())
+            },
+            Err(_) => panic!("UI event thread exited during subscription"),
+        }
+    }
+
+    pub fn visible_tooltip(_: Location) -> Option<String> {
+        let class: Vec<u16> = "tooltips_class32\0".encode_utf16().collect();
+        let mut window = ptr::null_mut();
+        loop {
+            window = unsafe {
+                FindWindowExW(ptr::null_mut(), window, class.as_ptr(), ptr::null())
+            };
+            if window.is_null() {
+                return None;
+            }
+            if unsafe { IsWindowVisible(window) } == 0 {
+                continue;
+            }
+            let mut buf = [0u16; 1024];
+            let len = unsafe {
+                GetWindowTextW(window, buf.as_mut_ptr(), buf.len() as i32)
+            };
+            if len > 0 {
+                return Some(String::from_utf16_lossy(&buf[..len as usize]));
+            }
+        }
+    }
+
+    pub fn help_at(_: Location) -> Option<String> {
+        None
+    }
+
+    /// Returns a condition that every element matching `filter` satisfies,
+    /// and whether every element satisfying it also matches.
+    fn condition(filter: &Filter) -> Result<(Condition, bool)> {
+        let mut condition = Condition::always()?;
+        let mut exact = filter.title_contains.is_none();
+        match filter.role {
+            Some(Role::Other) => exact = false,
+            Some(role) => {
+                let mut types: Option<Condition> = None;
+                for &(control_type, _) in ROLES.iter().filter(|&&(_, other)| other == role) {
+                    let this = Condition::control_type(control_type)?;
+                    types = Some(match types {
+                        Some(types) => types.or(&this)?,
+                        None => this,
+                    });
+                }
+                if let Some(types) = types {
+                    condition = condition.and(&types)?;
+                }
+            },
+            None => {},
+        }
+        match filter.title {
+            // Untitled elements may have no name at all
+            Some(ref title) if title.is_empty() => exact = false,
+            Some(ref title) => condition = condition.and(&Condition::name(title)?)?,
+            None => {},
+        }
+        Ok((condition, exact))
+    }
+
+    /// Returns up to `limit` elements below `root` that match `filter`,
+    /// letting UI Automation check what `condition` covers.
+    fn search(
+        root: &Element,
+        &(ref condition, exact): &(Condition, bool),
+        filter: &Filter,
+        limit: usize,
+    ) -> Result<Vec<Element>> {
+        if exact && limit == 1 {
+            return Ok(root.first_descendant_where(condition)?.into_iter().collect());
+        }
+        Ok(root.descendants_where(condition)?
+            .into_iter()
+            .filter(|element| filter.matches(element))
+            .take(limit)
+            .collect())
+    }
+
+    pub fn find(scope: Scope, filter: &Filter, limit: usize) -> Result<Vec<Element>> {
+        let condition = condition(filter)?;
+        let mut found = Vec::new();
+        for window in Element::windows_of(pid(scope))? {
+            if filter.matches(&window) {
+                found.push(window.clone());
+            }
+            if found.len() < limit {
+                found.extend(search(&window, &condition, filter, limit - found.len())?);
+            }
+            if found.len() == limit {
+                break;
+            }
+        }
+        Ok(found)
+    }
+
+    #[inline]
+    pub fn find_in(root: &Element, filter: &Filter, limit: usize) -> Result<Vec<Element>> {
+        search(root, &condition(filter)?, filter, limit)
+    }
+
+    fn is_dialog(window: &Element) -> bool {
+        if window.class_name().as_deref() == Some(DIALOG_CLASS) {
+            return true;
+        }
+        let hwnd = match window.window() {
+            Some(hwnd) => hwnd,
+            None => return false,
+        };
+        // Owned windows also include tool windows and popups without a frame
+        let (owned, style, ex_style) = unsafe {
+            (
+                !GetWindow(hwnd, GW_OWNER).is_null(),
+                GetWindowLongW(hwnd, GWL_STYLE) as DWORD,
+                GetWindowLongW(hwnd, GWL_EXSTYLE) as DWORD,
+            )
+        };
+        owned
+            && style & WS_CAPTION == WS_CAPTION
+            && ex_style & WS_EX_TOOLWINDOW == 0
+            && (ex_style & WS_EX_DLGMODALFRAME != 0 || style & DS_MODALFRAME as DWORD != 0)
+    }
+
+    /// Returns the process of the window that owns `window`.
+    fn owner_pid(window: &Element) -> Option<DWORD> {
+        let owner = unsafe { GetWindow(window.window()?, GW_OWNER) };
+        if owner.is_null() {
+            return None;
+        }
+        let mut pid = 0;
+        unsafe { GetWindowThreadProcessId(owner, &mut pid) };
+        Some(pid)
+    }
+
+    pub fn dialogs(scope: Scope) -> Result<Vec<Element>> {
+        let pid = pid(scope);
+        // Dialogs shown for the application can belong to other processes,
+        // owned by one of its windows or by nothing at all
+        let dialogs = Element::root()?.children()?.into_iter().filter(|window| {
+            let class = window.class_name();
+            if class.as_deref().is_some_and(|class| SYSTEM_PROMPT_CLASSES.contains(&class)) {
+                return true;
+            }
+            (window.process_id() == Some(pid) || owner_pid(window) == Some(pid)) && is_dialog(window)
+        });
+        Ok(dialogs.collect())
+    }
+
+    /// Returns the button of `dialog` with one of the control identifiers
+    /// in `ids`, which standard dialogs expose as automation identifiers.
+    fn button_with_id(dialog: &Element, ids: &[&str]) -> Option<Element> {
+        dialog.descendants().ok()?.into_iter().find(|element| {
+            element.control_type() == Some(ControlType::Button)
+                && element.automation_id().is_some_and(|id| ids.contains(&id.as_str()))
+        })
+    }
+
+    #[inline]
+    pub fn default_button(dialog: &Element) -> Option<Element> {
+        button_with_id(dialog, DEFAULT_BUTTON_IDS)
+    }
+
+    #[inline]
+    pub fn cancel_button(dialog: &Element) -> Option<Element> {
+        button_with_id(dialog, CANCEL_BUTTON_IDS)
+    }
+
+    pub fn role(element: &Element) -> Option<Role> {
+        let kind = element.control_type()?;
+        Some(ROLES.iter().find(|&&(other, _)| other == kind).map_or(Role::Other, |&(_, role)| role))
+    }
+
+    #[inline]
+    pub fn title(element: &Element) -> Option<String> {
+        element.name()
+    }
+
+    #[inline]
+    pub fn value(element: &Element) -> Option<String> {
+        element.value()
+    }
+
+    pub fn bounds(element: &Element) -> Option<(Location, (f64, f64))> {
+        element.bounds().map(|((x, y), (width, height))| {
+            ((x as f64, y as f64), (width as f64, height as f64))
+        })
+    }
+
+    #[inline]
+    pub fn press(element: &Element) -> Result<()> {
+        element.invoke()
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod imp {
+    use std::sync::mpsc::Sender;
+
+    use error::{Error, Result};
+    use events::Location;
+    use super::{Filter, Role, Scope, UiEvent};
+
+    #[derive(Debug)]
+    pub struct Subscription;
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Element;
+
+    pub fn subscribe(_: Scope, _: Sender<UiEvent>) -> Result<Subscription> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn visible_tooltip(_: Location) -> Option<String> {
+        None
+    }
+
+    pub fn help_at(_: Location) -> Option<String> {
+        None
+    }
+
+    pub fn find(_: Scope, _: &Filter, _: usize) -> Result<Vec<Element>> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn find_in(_: &Element, _: &Filter, _: usize) -> Result<Vec<Element>> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn role(_: &Element) -> Option<Role> {
+        None
+    }
+
+    pub fn title(_: &Element) -> Option<String> {
+        None
+    }
+
+    pub fn value(_: &Element) -> Option<String> {
+        None
+    }
+
+    pub fn bounds(_: &Element) -> Option<(Location, (f64, f64))> {
+        None
+    }
+
+    pub fn press(_: &Element) -> Result<()> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn dialogs(_: Scope) -> Result<Vec<Element>> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn default_button(_: &Element) -> Option<Element> {
+        None
+    }
+
+    pub fn cancel_button(_: &Element) -> Option<Element> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Filter, Role};
+
+    #[test]
+    fn filter_matches() {
+        let untitled = || None;
+        let save = || Some("Save As…".to_owned());
+        let unreachable = || -> Option<String> { panic!("title looked up") };
+
+        let any = Filter::default();
+        assert!(any.matches_properties(None, unreachable));
+
+        let buttons = Filter { role: Some(Role::Button), ..Filter::default() };
+        assert!(buttons.matches_properties(Some(Role::Button), unreachable));
+        assert!(!buttons.matches_properties(Some(Role::Link), unreachable));
+        assert!(!buttons.matches_properties(None, unreachable));
+
+        let titled = Filter { title: Some("Save As…".into()), ..buttons.clone() };
+        assert!(titled.matches_properties(Some(Role::Button), save));
+        assert!(!titled.matches_properties(Some(Role::Button), untitled));
+        assert!(!titled.matches_properties(Some(Role::Link), unreachable));
+
+        let empty = Filter { title: Some(String::new()), ..Filter::default() };
+        assert!(empty.matches_properties(None, untitled));
+        assert!(!empty.matches_properties(None, save));
+
+        let contains = Filter { title_contains: Some("As".into()), ..Filter::default() };
+        assert!(contains.matches_properties(Some(Role::Other), save));
+        assert!(!contains.matches_properties(None, untitled));
+
+        let both = Filter { title_contains: Some("Open".into()), ..titled };
+        assert!(!both.matches_properties(Some(Role::Button), save));
+    }
+}
@@ -0,0 +1,386 @@
+//! ✅ Verifying that high-level actions had an effect.
+//!
+//! By default, high-level actions such as clicks and typing succeed as soon
+//! as their events are posted, whether or not the target application reacted.
+//! Once a [`Policy`](struct.Policy.html) is installed with
+//! [`set_policy`](fn.set_policy.html), each action also waits for the policy's
+//! postconditions and fails with
+//! [`Error::Unverified`](../error/enum.Error.html#variant.Unverified) if they
+//! do not hold in time.
+//!
+//! On macOS, the process must be trusted for accessibility to check focus and
+//! windows, and allowed to record the screen to check pixels.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use auto::verify::{self, Condition, Policy};
+//!
+//! let mut policy = Policy::new(Duration::from_secs(2));
+//! policy.require(Condition::FocusChanged);
+//! verify::set_policy(Some(policy));
+//! ```
+
+use std::cell::Cell;
+use std::error;
+use std::fmt;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use error::{Error, Result};
+use image::Image;
+
+/// How long to wait between checks of postconditions.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+lazy_static! {
+    static ref POLICY: Mutex<Option<Policy>> = Mutex::default();
+}
+
+thread_local! {
+    /// Whether an action is being verified on this thread, so that actions
+    /// built from other actions are only verified once.
+    static VERIFYING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// A postcondition that must hold after an action.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Condition {
+    /// The element with keyboard focus changed.
+    FocusChanged,
+    /// Some pixel changed within a region of the screen, in global screen
+    /// coordinates.
+    PixelsChanged {
+        /// Coordinates of the upper-left corner of the region.
+        origin: (i32, i32),
+        /// Width and height of the region.
+        size: (u32, u32),
+    },
+    /// A new window appeared in the focused application.
+    WindowAppeared,
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Condition::FocusChanged => f.write_str("focus change"),
+            Condition::PixelsChanged { origin: (x, y), size: (w, h) } => {
+                write!(f, "pixel change within {}x{} at ({}, {})", w, h, x, y)
+            },
+            Condition::WindowAppeared => f.write_str("new window"),
+        }
+    }
+}
+
+/// The postconditions checked after each high-level action.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Policy {
+    conditions: Vec<Condition>,
+    timeout: Duration,
+}
+
+impl Policy {
+    /// Creates a policy without postconditions that waits up to `timeout` for
+    /// them to hold.
+    #[inline]
+    pub fn new(timeout: Duration) -> Policy {
+        Policy { conditions: Vec::new(), timeout }
+    }
+
+    /// Requires `condition` to hold after each action.
+    pub fn require(&mut self, condition: Condition) -> &mut Policy {
+        self.conditions.push(condition);
+        self
+    }
+
+    /// Returns the postconditions, all of which must hold.
+    #[inline]
+    pub fn conditions(&self) -> &[Condition] {
+        &self.conditions
+    }
+
+    /// Returns how long to wait for the postconditions to hold.
+    #[inline]
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+/// Installs `policy` for all threads, or turns verification off if `None`.
+pub fn set_policy(policy: Option<Policy>) {
+    *POLICY.lock().unwrap_or_else(|e| e.into_inner()) = policy;
+}
+
+/// Returns the installed policy.
+pub fn policy() -> Option<Policy> {
+    POLICY.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Details of an action that did not pass verification.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Failure {
+    action: String,
+    condition: Condition,
+    timeout: Duration,
+}
+
+impl Failure {
+    /// Returns the name of the action, such as `"click"`.
+    #[inline]
+    pub fn action(&self) -> &str {
+        &self.action
+    }
+
+    /// Returns the postcondition that did not hold.
+    #[inline]
+    pub fn condition(&self) -> &Condition {
+        &self.condition
+    }
+
+    /// Returns how long the postcondition was waited for.
+    #[inline]
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} was not followed by a {} within {:?}",
+               self.action, self.condition, self.timeout)
+    }
+}
+
+impl error::Error for Failure {}
+
+/// The state observed before an action for one postcondition.
+enum Snapshot {
+    Focus(Option<imp::Focus>),
+    Pixels(Image),
+    Windows(Vec<imp::Window>),
+}
+
+impl Snapshot {
+    fn take(condition: &Condition) -> Result<Snapshot> {
+        match *condition {
+            Condition::FocusChanged => imp::focus().map(Snapshot::Focus),
+            Condition::PixelsChanged { origin, size } => {
+                imp::capture(origin, size).map(Snapshot::Pixels)
+            },
+            Condition::WindowAppeared => imp::windows().map(Snapshot::Windows),
+        }
+    }
+
+    /// Returns whether the state has changed as required since the snapshot.
+    fn holds(&self, condition: &Condition) -> bool {
+        match *self {
+            Snapshot::Focus(ref before) => {
+                matches!(imp::focus(), Ok(ref after) if after != before)
+            },
+            Snapshot::Pixels(ref before) => match *condition {
+                Condition::PixelsChanged { origin, size } => {
+                    matches!(imp::capture(origin, size), Ok(ref after) if after != before)
+                },
+                _ => false,
+            },
+            Snapshot::Windows(ref before) => match imp::windows() {
+                Ok(after) => after.iter().any(|w| !before.contains(w)),
+                Err(_) => false,
+            },
+        }
+    }
+}
+
+/// The frontmost application and the element with keyboard focus.
+#[derive(PartialEq)]
+pub(crate) struct FocusState {
+    app: Option<i64>,
+    element: Option<imp::Focus>,
+}
+
+/// Returns where keyboard input currently goes, to tell whether it has moved.
+pub(crate) fn focus_state() -> Result<FocusState> {
+    Ok(FocusState { app: imp::frontmost_app(), element: imp::focus()? })
+}
+
+/// Performs the high-level `action` with `f`, then waits for the installed
+/// policy's postconditions to hold.
+///
+/// If no policy is installed, or if this is called from within another
+/// verified action, `f` is performed without verification.
+///
+/// The crate's own high-level helpers are verified this way; this can be
+/// used to verify actions built from lower-level calls such as
+/// [`events::post`](../events/fn.post.html).
+pub fn verified<T, F>(action: &str, f: F) -> Result<T>
+    where F: FnOnce() -> Result<T>
+{
+    if VERIFYING.with(Cell::get) {
+        return f();
+    }
+    let policy = match policy() {
+        Some(ref policy) if !policy.conditions.is_empty() => policy.clone(),
+        _ => return f(),
+    };
+
+    let snapshots = policy.conditions.iter()
+        .map(Snapshot::take)
+        .collect::<Result<Vec<_>>>()?;
+
+    let value = {
+        struct Reset;
+        impl Drop for Reset {
+            fn drop(&mut self) {
+                VERIFYING.with(|v| v.set(false));
+            }
+        }
+        VERIFYING.with(|v| v.set(true));
+        let _reset = Reset;
+        f()?
+    };
+
+    let deadline = Instant::now().checked_add(policy.timeout);
+    let mut pending: Vec<_> = policy.conditions.iter().zip(&snapshots).collect();
+    loop {
+        pending.retain(|&(condition, snapshot)| !snapshot.holds(condition));
+        let condition = match pending.first() {
+            Some(&(condition, _)) => condition,
+            None => return Ok(value),
+        };
+        let now = Instant::now();
+        if deadline.is_some_and(|deadline| now >= deadline) {
+            let failure = Failure {
+                action: action.into(),
+                condition: condition.clone(),
+                timeout: policy.timeout,
+            };
+            return Err(Error::Unverified(Box::new(failure)));
+        }
+        thread::sleep(deadline.map_or(POLL_INTERVAL, |deadline| POLL_INTERVAL.min(deadline - now)));
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use error::{Error, Result};
+    use image::Image;
+    use os::app::App;
+    use os::ax::Element;
+    use os::screen::{Bounds, Display};
+
+    pub type Focus = Element;
+    pub type Window = Element;
+
+    pub fn focus() -> Result<Option<Focus>> {
+        Ok(Element::system_wide().focused_element())
+    }
+
+    // Unlike the focused element, this is known without accessibility access
+    pub fn frontmost_app() -> Option<i64> {
+        App::frontmost().and_then(|app| app.pid()).map(i64::from)
+    }
+
+    pub fn capture((x, y): (i32, i32), (w, h): (u32, u32)) -> Result<Image> {
+        let point = (x as f64, y as f64);
+        let (display, bounds) = Display::active()?
+            .into_iter()
+            .map(|d| (d, d.bounds()))
+            .find(|&(_, b)| b.contains(point))
+            .ok_or(Error::Unsupported)?;
+        let rect = Bounds {
+            origin: (point.0 - bounds.origin.0, point.1 - bounds.origin.1),
+            size: (w as f64, h as f64),
+        };
+        display.capture_rect(rect)
+    }
+
+    pub fn windows() -> Result<Vec<Window>> {
+        let app = Element::system_wide().focused_application();
+        Ok(app.map(|app| app.windows()).unwrap_or_default())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use std::mem;
+
+    use winapi::shared::minwindef::{BOOL, LPARAM, TRUE};
+    use winapi::shared::windef::HWND;
+    use winapi::um::winuser::{
+        EnumWindows,
+        GetForegroundWindow,
+        GetGUIThreadInfo,
+        IsWindowVisible,
+        GUITHREADINFO,
+    };
+
+    use error::{OsError, Result};
+    use image::Image;
+    use os::screen::{self, Bounds};
+
+    pub type Focus = usize;
+    pub type Window = usize;
+
+    pub fn focus() -> Result<Option<Focus>> {
+        let mut info: GUITHREADINFO = unsafe { mem::zeroed() };
+        info.cbSize = mem::size_of::<GUITHREADINFO>() as u32;
+        let window = match unsafe { GetGUIThreadInfo(0, &mut info) } {
+            0 => unsafe { GetForegroundWindow() },
+            _ if info.hwndFocus.is_null() => info.hwndActive,
+            _ => info.hwndFocus,
+        };
+        Ok(if window.is_null() { None } else { Some(window as usize) })
+    }
+
+    // The focused window already identifies the application that owns it
+    pub fn frontmost_app() -> Option<i64> {
+        None
+    }
+
+    pub fn capture(origin: (i32, i32), (w, h): (u32, u32)) -> Result<Image> {
+        screen::capture_rect(Bounds { origin, size: (w as usize, h as usize) })
+    }
+
+    pub fn windows() -> Result<Vec<Window>> {
+        unsafe extern "system" fn push(window: HWND, windows: LPARAM) -> BOOL {
+            if IsWindowVisible(window) != 0 {
+                (*(windows as *mut Vec<Window>)).push(window as usize);
+            }
+            TRUE
+        }
+
+        let mut windows = Vec::new();
+        let ptr = &mut windows as *mut Vec<Window> as LPARAM;
+        match unsafe { EnumWindows(Some(push), ptr) } {
+            0 => Err(OsError::last().into()),
+            _ => Ok(windows),
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod imp {
+    use error::{Error, Result};
+    use image::Image;
+
+    pub type Focus = ();
+    pub type Window = ();
+
+    pub fn focus() -> Result<Option<Focus>> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn frontmost_app() -> Option<i64> {
+        None
+    }
+
+    pub fn capture(_: (i32, i32), _: (u32, u32)) -> Result<Image> {
+        Err(Error::Unsupported)
+    }
+
+    pub fn windows() -> Result<Vec<Window>> {
+        Err(Error::Unsupported)
+    }
+}
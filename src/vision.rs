@@ -0,0 +1,177 @@
+//! 👁️ Finding images on the screen.
+//!
+//! A template is an [`Image`](../image/struct.Image.html) cropped from an
+//! earlier capture, such as a button or an icon, and is matched pixel for
+//! pixel against the screen. Templates are in pixels, so one taken on a
+//! Retina display only matches on displays with the same scale. Transparent
+//! pixels of the template match anything, which lets templates of rounded or
+//! irregular shapes ignore their background.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[cfg(feature = "image-io")] {
+//! use std::time::Duration;
+//! use auto::image::Image;
+//! use auto::{mouse, vision};
+//!
+//! let button = Image::load_png("ok-button.png").unwrap();
+//! if let Some(pos) = vision::wait_for_image(&button, None, Duration::from_secs(10), 2.5).unwrap() {
+//!     mouse::move_to(pos).unwrap();
+//!     mouse::click(mouse::Button::Left).unwrap();
+//! }
+//! # }
+//! ```
+
+use std::time::{Duration, Instant};
+
+use abort::Token;
+use color::Rgb;
+use error::Result;
+use events::Location;
+use image::Image;
+use screen::{self, Bounds, Capture};
+
+/// The shortest time to wait between captures of the screen.
+const MIN_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The longest time to wait between captures while the screen is unchanged.
+const MAX_INTERVAL: Duration = Duration::from_millis(800);
+
+/// Returns the center of the first area of the screen that matches
+/// `template`, or `None` if there is none.
+///
+/// Each pixel may differ from the template by up to `tolerance`, as measured
+/// by [`Rgb::distance`](../color/struct.Rgb.html#method.distance). A
+/// tolerance of 2.0 to 3.0 absorbs the variations in color from
+/// anti-aliasing, color profiles, and gamma.
+///
+/// Only `region` is searched if given, or else every display. Areas are
+/// searched row by row from the top.
+pub fn find_image(template: &Image, region: Option<Bounds>, tolerance: f64) -> Result<Option<Location>> {
+    for capture in capture(region)? {
+        if let Some(pos) = locate(&capture, template, tolerance) {
+            return Ok(Some(pos));
+        }
+    }
+    Ok(None)
+}
+
+/// Captures the screen until `template` appears, as with
+/// [`find_image`](fn.find_image.html), for at most `timeout`, returning the
+/// center of the match or `None` if it did not appear in time.
+///
+/// The screen is captured often while its contents change and less often
+/// while they stay the same, so long waits on an idle screen don't keep the
+/// processor busy. A `timeout` too long to represent waits indefinitely. It
+/// fails with
+/// [`Error::Aborted`](../error/enum.Error.html#variant.Aborted) if the
+/// [kill switch](../abort/index.html) is triggered while waiting.
+pub fn wait_for_image(
+    template: &Image,
+    region: Option<Bounds>,
+    timeout: Duration,
+    tolerance: f64,
+) -> Result<Option<Location>> {
+    let token = Token::new();
+    let deadline = Instant::now().checked_add(timeout);
+    let mut interval = MIN_INTERVAL;
+    let mut previous: Vec<Image> = Vec::new();
+    loop {
+        token.check()?;
+        let captures = capture(region)?;
+        for capture in &captures {
+            if let Some(pos) = locate(capture, template, tolerance) {
+                return Ok(Some(pos));
+            }
+        }
+        let now = Instant::now();
+        if deadline.is_some_and(|deadline| now >= deadline) {
+            return Ok(None);
+        }
+        let changed = captures.len() != previous.len()
+            || captures.iter().zip(&previous).any(|(capture, image)| capture.image() != image);
+        interval = if changed { MIN_INTERVAL } else { (interval * 2).min(MAX_INTERVAL) };
+        previous = captures.into_iter().map(Capture::into_image).collect();
+        token.sleep(deadline.map_or(interval, |deadline| interval.min(deadline - now)))?;
+    }
+}
+
+/// Returns the center of the first area of `capture` that matches
+/// `template`.
+pub fn locate(capture: &Capture, template: &Image, tolerance: f64) -> Option<Location> {
+    let (x, y) = position(capture.image(), template, tolerance)?;
+    let (w, h) = (template.width() as f64, template.height() as f64);
+    let ((ox, oy), scale) = (capture.origin(), capture.scale());
+    Some((ox + (x as f64 + w / 2.0) / scale, oy + (y as f64 + h / 2.0) / scale))
+}
+
+/// Captures `region`, or every display if it is `None`.
+fn capture(region: Option<Bounds>) -> Result<Vec<Capture>> {
+    match region {
+        Some(region) => Ok(vec![screen::capture_rect(region.origin, region.size)?]),
+        None => screen::displays()?
+            .iter()
+            .map(|display| screen::capture_rect(display.origin, display.size))
+            .collect(),
+    }
+}
+
+/// Returns the pixel of `image` at the upper-left corner of the first area
+/// that matches `template`.
+fn position(image: &Image, template: &Image, tolerance: f64) -> Option<(usize, usize)> {
+    let (width, height) = (template.width(), template.height());
+    if width == 0 || height == 0 || width > image.width() || height > image.height() {
+        return None;
+    }
+    let opaque: Vec<(usize, usize)> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter(|&(x, y)| template.row(y).is_some_and(|row| row[x * 4 + 3] != 0))
+        .collect();
+    // A transparent template matches anywhere
+    let (&first, rest) = match opaque.split_first() {
+        Some(split) => split,
+        None => return Some((0, 0)),
+    };
+    let rgb = |px: &[u8]| Rgb { red: px[0], green: px[1], blue: px[2] };
+    let matches = |ix: usize, iy: usize, (x, y): (usize, usize)| {
+        let a = &image.row(iy + y).unwrap()[(ix + x) * 4..];
+        let b = &template.row(y).unwrap()[x * 4..];
+        // Identical pixels are common, and cheaper to compare
+        a[..3] == b[..3] || rgb(a).matches(&rgb(b), tolerance)
+    };
+    for iy in 0..=image.height() - height {
+        for ix in 0..=image.width() - width {
+            if matches(ix, iy, first) && rest.iter().all(|&px| matches(ix, iy, px)) {
+                return Some((ix, iy));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use color::Rgb;
+    use image::Image;
+
+    #[test]
+    fn position() {
+        let mut data = vec![0; 6 * 4 * 4];
+        for (x, y) in [(3, 1), (4, 1), (3, 2)] {
+            data[(y * 6 + x) * 4..][..4].copy_from_slice(&[200, 100, 50, 255]);
+        }
+        let image = Image::from_rgba(6, 4, data).unwrap();
+
+        // The lower-right pixel is transparent, so anything matches there
+        let template = Image::from_rgba(2, 2, vec![
+            205, 95, 50, 255, 200, 100, 50, 255,
+            200, 100, 50, 255, 9, 9, 9, 0,
+        ]).unwrap();
+        let (templated, actual) = (Rgb::from_hex(0xcd5f32), Rgb::from_hex(0xc86432));
+        let distance = templated.distance(&actual);
+        assert_eq!(super::position(&image, &template, distance), Some((3, 1)));
+        assert_eq!(super::position(&image, &template, distance - 0.01), None);
+        assert_eq!(super::position(&template, &image, f64::INFINITY), None);
+    }
+}